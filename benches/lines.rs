@@ -0,0 +1,59 @@
+//! How long it takes to find the lines of many diagnostics in a large file,
+//! with the line index that `SourceMap` builds once, and by counting the
+//! newlines before each, as a baseline.
+
+extern crate rust_cc;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rust_cc::diagnostics::{self, Diagnostic, SourceMap};
+use rust_cc::semantic::Severity;
+
+const LINES: usize = 20_000;
+const DIAGNOSTICS: usize = 5_000;
+
+/// The line and column of `offset` in `source`, found by scanning it.
+fn rescanned(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() + 1, offset - line_start + 1)
+}
+
+fn time<F: FnMut()>(name: &str, mut run: F) -> Duration {
+    let start = Instant::now();
+    run();
+    let elapsed = start.elapsed();
+    println!("{:<24} {:>10.3}ms", name, elapsed.as_secs_f64() * 1000.0);
+    elapsed
+}
+
+fn main() {
+    let source = "int f(int a) { int b = a * 2; return b + a; }\n".repeat(LINES);
+    // Spread over the whole file, as the diagnostics of a broken one are.
+    let offsets: Vec<usize> = (0..DIAGNOSTICS).map(|n| n * (source.len() / DIAGNOSTICS)).collect();
+
+    let map = SourceMap::new("bench.c", &source);
+    let indexed = time("indexed lookups", || {
+        for &offset in &offsets {
+            black_box(map.position(offset));
+        }
+    });
+    let scanned = time("rescanned lookups", || {
+        for &offset in &offsets {
+            black_box(rescanned(&source, offset));
+        }
+    });
+    for &offset in &offsets {
+        assert_eq!(map.position(offset), rescanned(&source, offset));
+    }
+    println!("{:<24} {:>10.1}x", "speedup", scanned.as_secs_f64() / indexed.as_secs_f64());
+
+    time("rendering diagnostics", || {
+        for &offset in &offsets {
+            let (line, column) = map.position(offset);
+            let diagnostic = Diagnostic::new(Severity::Error, "unused".to_owned(), Some(map.span_at(line, column)));
+            black_box(diagnostics::render(&map, &diagnostic, false));
+        }
+    });
+}
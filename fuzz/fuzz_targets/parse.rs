@@ -0,0 +1,17 @@
+//! Lexes and parses arbitrary input. Any panic, including a stack overflow, is
+//! a bug: bad input must come back as a `LexError` or `ParseError`. Inputs that
+//! found crashes belong in `tests/parser_regressions.rs`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_cc::lexer::lex_str;
+use rust_cc::parser::parse_program_partial;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        if let Ok(lexemes) = lex_str(source) {
+            parse_program_partial(&lexemes);
+        }
+    }
+});
@@ -0,0 +1,99 @@
+/* Declares what src/capi.rs defines, in the form cbindgen writes. Regenerate
+ * it with `cbindgen --config cbindgen.toml --output include/rcc.h` rather than
+ * editing it. */
+
+#ifndef RCC_H
+#define RCC_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The program compiled.
+ */
+#define RCC_OK 0
+
+/**
+ * The program has errors, which are the result's diagnostics.
+ */
+#define RCC_COMPILE_ERROR 1
+
+/**
+ * An argument is null where it can't be or isn't UTF-8, or an option names
+ * no target, kind of output, optimization level or standard.
+ */
+#define RCC_INVALID_ARGUMENT 2
+
+/**
+ * The compiler panicked, which is a bug in it.
+ */
+#define RCC_PANIC 3
+
+/**
+ * How to compile a program. Any of the strings can be null, for the default.
+ */
+typedef struct RccOptions {
+  /**
+   * The target, as `--target` names it, by default the host.
+   */
+  const char *target;
+  /**
+   * What to compile it to, as `--emit` names it, by default `asm`.
+   */
+  const char *emit;
+  /**
+   * The standard, as `--std=` names it, by default `c11`.
+   */
+  const char *standard;
+  /**
+   * What to call the source in diagnostics, by default `<source>`.
+   */
+  const char *file_name;
+  /**
+   * 0, 1 or 2, as with `-O`.
+   */
+  int opt_level;
+} RccOptions;
+
+/**
+ * What compiling a program made.
+ */
+typedef struct RccResult {
+  /**
+   * What it was compiled to, or null if it wasn't.
+   */
+  char *output;
+  /**
+   * The errors and warnings, each as `--error-format=short` writes it.
+   */
+  char **diagnostics;
+  uintptr_t diagnostic_count;
+} RccResult;
+
+/**
+ * Compiles `source` as `options` say, or with the defaults if it is null,
+ * into `out`.
+ *
+ * # Safety
+ *
+ * `source` and each string in `options` must be null or point to a
+ * NUL-terminated string, `options` must be null or point to an
+ * `RccOptions`, and `out` must point to memory an `RccResult` can be
+ * written to. If it is null, `RCC_INVALID_ARGUMENT` is returned.
+ */
+int rcc_compile(const char *source, const RccOptions *options, RccResult *out);
+
+/**
+ * Frees what `rcc_compile` wrote to `result`, and leaves it empty, so that
+ * freeing it again does nothing.
+ *
+ * # Safety
+ *
+ * `result` must be null or point to an `RccResult` that `rcc_compile`
+ * wrote, or that this emptied.
+ */
+void rcc_result_free(RccResult *result);
+
+#endif /* RCC_H */
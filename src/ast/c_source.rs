@@ -0,0 +1,501 @@
+//! Prints the AST back out as C. Parsing the output gives back the same tree,
+//! except that a dangling `else` the tree attaches to an outer `if` has to be
+//! written with the inner `if` in braces.
+
+use lexer::{escape, spell_int};
+use super::*;
+
+// Precedence levels of the expression grammar, lowest first. Binary operators
+// sit between `CONDITIONAL` and `UNARY`, offset by their own precedence.
+const COMMA: u8 = 0;
+const ASSIGNMENT: u8 = 1;
+const CONDITIONAL: u8 = 2;
+const UNARY: u8 = 13;
+const POSTFIX: u8 = 14;
+const PRIMARY: u8 = 15;
+
+fn binary_level(op: BinaryOp) -> u8 {
+    CONDITIONAL + op.precedence()
+}
+
+fn level(expression: &Expression) -> u8 {
+    match expression.kind {
+        ExpressionKind::Comma(_) => COMMA,
+        ExpressionKind::Assign(..) => ASSIGNMENT,
+        ExpressionKind::Conditional(..) => CONDITIONAL,
+        ExpressionKind::Binary(op, ..) => binary_level(op),
+        ExpressionKind::Unary(op, _) if op.is_postfix() => POSTFIX,
+        ExpressionKind::Unary(..) | ExpressionKind::Cast { .. } | ExpressionKind::SizeOf(_) => UNARY,
+        // A negative literal can only be written with a unary minus.
+        ExpressionKind::IntLiteral(value, ref ty) if value < 0 && *ty != Type::UnsignedLong => UNARY,
+        ExpressionKind::Call(..)
+        | ExpressionKind::Subscript(..)
+        | ExpressionKind::Member(..)
+        | ExpressionKind::PointerMember(..) => POSTFIX,
+        ExpressionKind::IntLiteral(..)
+        | ExpressionKind::CharConstant(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::Variable(_) => PRIMARY,
+    }
+}
+
+/// Prints `expression` where the grammar expects at least `minimum`, adding
+/// parentheses only if it binds more loosely than that.
+fn operand(ast: &Ast, expression: ExprId, minimum: u8) -> String {
+    let text = expr(ast, expression);
+    if level(&ast[expression]) < minimum {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+fn expr(ast: &Ast, expression: ExprId) -> String {
+    match ast[expression].kind {
+        ExpressionKind::IntLiteral(value, ref ty) => spell_int(value, ty),
+        ExpressionKind::CharConstant(value) => format!("'{}'", escape(&[value], b'\'')),
+        ExpressionKind::StringLiteral(ref bytes) => format!("\"{}\"", escape(bytes, b'"')),
+        ExpressionKind::Variable(ref name) => name.clone(),
+        ExpressionKind::Unary(op, inner) if op.is_postfix() => format!("{}{}", operand(ast, inner, POSTFIX), op.symbol()),
+        ExpressionKind::Unary(op, inner) => {
+            let symbol = op.symbol();
+            let inner = operand(ast, inner, UNARY);
+            // Keep `- -x` and `& &x` from lexing as `--x` and `&&x`.
+            let separator = match (symbol.chars().last(), inner.chars().next()) {
+                (Some(last), Some(first)) if last == first && "+-&".contains(last) => " ",
+                _ => "",
+            };
+            format!("{}{}{}", symbol, separator, inner)
+        },
+        ExpressionKind::Binary(op, lhs, rhs) => {
+            let level = binary_level(op);
+            format!("{} {} {}", operand(ast, lhs, level), op.symbol(), operand(ast, rhs, level + 1))
+        },
+        ExpressionKind::Assign(op, target, value) => format!(
+            "{} {}= {}",
+            operand(ast, target, UNARY),
+            op.map_or("", BinaryOp::symbol),
+            operand(ast, value, ASSIGNMENT),
+        ),
+        ExpressionKind::Conditional(condition, then, otherwise) => format!(
+            "{} ? {} : {}",
+            operand(ast, condition, CONDITIONAL + 1),
+            expr(ast, then),
+            operand(ast, otherwise, CONDITIONAL),
+        ),
+        ExpressionKind::Call(ref name, ref arguments) => format!("{}({})", name, arguments_list(ast, arguments)),
+        ExpressionKind::Cast { ref to, expr } => format!("({}){}", type_name(to), operand(ast, expr, UNARY)),
+        ExpressionKind::SizeOf(ref ty) => format!("sizeof({})", type_name(ty)),
+        ExpressionKind::Subscript(array, index) => format!("{}[{}]", operand(ast, array, POSTFIX), expr(ast, index)),
+        ExpressionKind::Member(object, ref member) => format!("{}.{}", operand(ast, object, POSTFIX), member),
+        ExpressionKind::PointerMember(pointer, ref member) => format!("{}->{}", operand(ast, pointer, POSTFIX), member),
+        ExpressionKind::Comma(ref expressions) => arguments_list(ast, expressions),
+    }
+}
+
+/// Prints expressions separated by commas, each at assignment level.
+fn arguments_list(ast: &Ast, expressions: &[ExprId]) -> String {
+    expressions.iter().map(|&expression| operand(ast, expression, ASSIGNMENT)).collect::<Vec<_>>().join(", ")
+}
+
+/// Splits `ty` into the base type and the declarator that wraps `name` in it,
+/// so `char *argv[4]` gives `char` and `*argv[4]`.
+fn declarator(ty: &Type, name: String) -> (Type, String) {
+    match *ty {
+        Type::Pointer(ref pointee) => declarator(pointee, format!("*{}", name)),
+        Type::Const(ref inner) => match **inner {
+            Type::Pointer(ref pointee) => declarator(pointee, format!("*const {}", name).trim_end().to_owned()),
+            // A const array is an array of const elements.
+            Type::Array(ref element, size) => declarator(&Type::Array(Box::new((**element).clone().with_const()), size), name),
+            _ => (ty.clone(), name),
+        },
+        Type::Array(ref element, size) => {
+            let name = if name.starts_with('*') { format!("({})", name) } else { name };
+            declarator(element, format!("{}[{}]", name, size))
+        },
+        _ => (ty.clone(), name),
+    }
+}
+
+fn declaration_of(ty: &Type, name: &str) -> String {
+    let (base, declarator) = declarator(ty, name.to_owned());
+    format!("{} {}", base, declarator)
+}
+
+/// A type as written in a cast, e.g. `const char *`.
+fn type_name(ty: &Type) -> String {
+    declaration_of(ty, "").trim_end().to_owned()
+}
+
+fn initializer(ast: &Ast, initializer: &Initializer) -> String {
+    match *initializer {
+        Initializer::Expr(expression) => operand(ast, expression, ASSIGNMENT),
+        Initializer::List(ref items) => {
+            format!("{{{}}}", items.iter().map(|item| self::initializer(ast, item)).collect::<Vec<_>>().join(", "))
+        },
+    }
+}
+
+/// Prints declarations that share a declaration statement, without the semicolon.
+/// They're assumed to share the first declaration's storage class and base type.
+fn declarations(ast: &Ast, declarations: &[DeclId]) -> String {
+    let mut text = String::new();
+    if let Some(storage) = declarations.first().and_then(|&declaration| ast[declaration].storage) {
+        text.push_str(&format!("{} ", storage));
+    }
+
+    for (index, &declaration) in declarations.iter().enumerate() {
+        let declaration = &ast[declaration];
+        let (base, declarator) = declarator(&declaration.ty, declaration.name.clone());
+        if index == 0 {
+            text.push_str(&format!("{} {}", base, declarator));
+        } else {
+            text.push_str(&format!(", {}", declarator));
+        }
+        if let Some(ref init) = declaration.initializer {
+            text.push_str(&format!(" = {}", initializer(ast, init)));
+        }
+    }
+    text
+}
+
+/// Whether `statement` ends in an `if` without an `else`, which an `else`
+/// written after it would attach to.
+fn ends_in_open_if(ast: &Ast, statement: StmtId) -> bool {
+    match ast[statement].kind {
+        StatementKind::If(_, _, None) => true,
+        StatementKind::If(_, _, Some(otherwise)) => ends_in_open_if(ast, otherwise),
+        StatementKind::While(_, body) | StatementKind::For { body, .. } => ends_in_open_if(ast, body),
+        _ => false,
+    }
+}
+
+struct Writer<'a> {
+    ast: &'a Ast,
+    output: String,
+    depth: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.output.push_str("    ");
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn items(&mut self, items: &[BlockItem]) {
+        self.depth += 1;
+        for item in items {
+            match *item {
+                BlockItem::Statement(statement) => self.statement(statement),
+                BlockItem::Declaration(ref group) => self.line(&format!("{};", declarations(self.ast, group))),
+            }
+        }
+        self.depth -= 1;
+    }
+
+    /// Writes `header` followed by `body`. A compound body opens a brace on the
+    /// header's line and is left unclosed, so that the caller can put `else` or
+    /// `while` after the closing brace; returns whether that happened.
+    fn clause(&mut self, header: &str, body: StmtId) -> bool {
+        match self.ast[body].kind {
+            StatementKind::Compound(ref items) => {
+                self.line(&format!("{} {{", header));
+                self.items(items);
+                true
+            },
+            _ => {
+                self.line(header);
+                self.depth += 1;
+                self.statement(body);
+                self.depth -= 1;
+                false
+            },
+        }
+    }
+
+    fn if_statement(&mut self, prefix: &str, condition: ExprId, then: StmtId, otherwise: Option<StmtId>) {
+        let header = format!("{}if ({})", prefix, expr(self.ast, condition));
+        let braced = match otherwise {
+            Some(_) if ends_in_open_if(self.ast, then) => {
+                self.line(&format!("{} {{", header));
+                self.depth += 1;
+                self.statement(then);
+                self.depth -= 1;
+                true
+            },
+            _ => self.clause(&header, then),
+        };
+
+        let otherwise = match otherwise {
+            Some(otherwise) => otherwise,
+            None => {
+                if braced {
+                    self.line("}");
+                }
+                return;
+            },
+        };
+
+        let prefix = if braced { "} else" } else { "else" };
+        match self.ast[otherwise].kind {
+            StatementKind::If(condition, then, next) => self.if_statement(&format!("{} ", prefix), condition, then, next),
+            _ => {
+                if self.clause(prefix, otherwise) {
+                    self.line("}");
+                }
+            },
+        }
+    }
+
+    fn statement(&mut self, statement: StmtId) {
+        let ast = self.ast;
+        match ast[statement].kind {
+            StatementKind::Return(value) => self.line(&format!("return {};", expr(ast, value))),
+            StatementKind::Expression(None) => self.line(";"),
+            StatementKind::Expression(Some(expression)) => self.line(&format!("{};", expr(ast, expression))),
+            StatementKind::If(condition, then, otherwise) => self.if_statement("", condition, then, otherwise),
+            StatementKind::Compound(ref items) => {
+                self.line("{");
+                self.items(items);
+                self.line("}");
+            },
+            StatementKind::While(condition, body) => {
+                if self.clause(&format!("while ({})", expr(ast, condition)), body) {
+                    self.line("}");
+                }
+            },
+            StatementKind::DoWhile(body, condition) => {
+                let footer = format!("while ({});", expr(ast, condition));
+                if self.clause("do", body) {
+                    self.line(&format!("}} {}", footer));
+                } else {
+                    self.line(&footer);
+                }
+            },
+            StatementKind::For { ref init, condition, post, body } => {
+                let mut header = String::from("for (");
+                match *init {
+                    ForInit::Declaration(ref group) => header.push_str(&declarations(ast, group)),
+                    ForInit::Expression(Some(expression)) => header.push_str(&expr(ast, expression)),
+                    ForInit::Expression(None) => {},
+                }
+                header.push(';');
+                if let Some(condition) = condition {
+                    header.push_str(&format!(" {}", expr(ast, condition)));
+                }
+                header.push(';');
+                if let Some(post) = post {
+                    header.push_str(&format!(" {}", expr(ast, post)));
+                }
+                header.push(')');
+
+                if self.clause(&header, body) {
+                    self.line("}");
+                }
+            },
+            StatementKind::Break => self.line("break;"),
+            StatementKind::Continue => self.line("continue;"),
+            StatementKind::Goto(ref label) => self.line(&format!("goto {};", label)),
+            StatementKind::Labeled(ref label, statement) => {
+                self.line(&format!("{}:", label));
+                self.statement(statement);
+            },
+            StatementKind::Switch(value, body) => {
+                if self.clause(&format!("switch ({})", expr(ast, value)), body) {
+                    self.line("}");
+                }
+            },
+            StatementKind::Case(value, statement) => {
+                self.line(&format!("case {}:", expr(ast, value)));
+                self.statement(statement);
+            },
+            StatementKind::Default(statement) => {
+                self.line("default:");
+                self.statement(statement);
+            },
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        let mut parameters: Vec<String> = function.parameters.iter()
+            .map(|parameter| declaration_of(&parameter.ty, &parameter.name))
+            .collect();
+        if function.variadic {
+            parameters.push("...".to_owned());
+        }
+
+        let storage = function.storage.map_or(String::new(), |storage| format!("{} ", storage));
+        let header = format!("{}{}({})", storage, declaration_of(&function.return_type, &function.name), parameters.join(", "));
+        match function.body {
+            Some(ref body) => {
+                self.line(&format!("{} {{", header));
+                self.items(body);
+                self.line("}");
+            },
+            None => self.line(&format!("{};", header)),
+        }
+    }
+
+    fn top_level(&mut self, item: &TopLevel) {
+        match *item {
+            TopLevel::Function(ref function) => self.function(function),
+            TopLevel::Struct(ref definition) => {
+                self.line(&format!("struct {} {{", definition.name));
+                self.depth += 1;
+                for member in &definition.members {
+                    self.line(&format!("{};", declaration_of(&member.ty, &member.name)));
+                }
+                self.depth -= 1;
+                self.line("};");
+            },
+            TopLevel::Declaration(ref group) => self.line(&format!("{};", declarations(self.ast, group))),
+        }
+    }
+}
+
+/// Prints `expression` as C source, as it would appear on its own.
+pub fn expression_to_c_source(ast: &Ast, expression: ExprId) -> String {
+    expr(ast, expression)
+}
+
+/// Prints `program` as C source, indenting blocks by four spaces and putting
+/// the least parentheses that keep every expression's structure.
+pub fn to_c_source(program: &Program) -> String {
+    let mut writer = Writer { ast: &program.ast, output: String::new(), depth: 0 };
+    for (index, item) in program.items.iter().enumerate() {
+        // Functions and structs are set apart from their neighbours by a blank line.
+        let spaced = |item: &TopLevel| !matches!(*item, TopLevel::Declaration(_));
+        if index > 0 && (spaced(item) || spaced(&program.items[index - 1])) {
+            writer.output.push('\n');
+        }
+        writer.top_level(item);
+    }
+    writer.output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn parse(source: &str) -> Program {
+        parse_program(&lex_str(source).unwrap()).unwrap()
+    }
+
+    fn format(source: &str) -> String {
+        to_c_source(&parse(source))
+    }
+
+    #[test]
+    fn minimal_parentheses() {
+        assert_eq!(format("int x = (1 + 2) * 3 - (4 - 5) - 6;"), "int x = (1 + 2) * 3 - (4 - 5) - 6;\n");
+        assert_eq!(format("int x = ((1 * 2)) + (3 * 4);"), "int x = 1 * 2 + 3 * 4;\n");
+        assert_eq!(format("int f() { a = b = (c, d); x = (a ? b : c) ? d : (e ? f : g); }"), "\
+int f() {
+    a = b = (c, d);
+    x = (a ? b : c) ? d : e ? f : g;
+}
+");
+        assert_eq!(format("int f() { return -(-x) + -(x--) + (*p)++ + *p++ + (int)(char)-y + (a || b) && c; }"), "\
+int f() {
+    return - -x + -x-- + (*p)++ + *p++ + (int)(char)-y + (a || b) && c;
+}
+");
+        assert_eq!(format("int f() { return (p->next)[2].value + f((a, b), c); }"), "\
+int f() {
+    return p->next[2].value + f((a, b), c);
+}
+");
+        assert_eq!(format("int f() { return -sizeof(char *) * 2; }"), "\
+int f() {
+    return -sizeof(char *) * 2;
+}
+");
+    }
+
+    #[test]
+    fn declarations_and_types() {
+        assert_eq!(format("static const char *const names[2] = {\"a\", \"b\\n\"}, *p; int m[2][3]; extern int n;"), "\
+static const char *const names[2] = {\"a\", \"b\\n\"}, *p;
+int m[2][3];
+extern int n;
+");
+        assert_eq!(format("struct P { int x; char *name; }; int printf(char *format, ...); struct P origin = {0};"), "\
+struct P {
+    int x;
+    char *name;
+};
+
+int printf(char *format, ...);
+
+struct P origin = {0};
+");
+    }
+
+    #[test]
+    fn control_flow_layout() {
+        let source = "int main(int argc) { int i; for (i = 0; i < argc; i++) { if (i == 1) continue; else if (i == 2) { break; } else ; } \
+                      while (argc) argc--; do { argc++; } while (argc < 3); for (;;) return 'x'; { int nested; } }";
+        assert_eq!(format(source), "\
+int main(int argc) {
+    int i;
+    for (i = 0; i < argc; i++) {
+        if (i == 1)
+            continue;
+        else if (i == 2) {
+            break;
+        } else
+            ;
+    }
+    while (argc)
+        argc--;
+    do {
+        argc++;
+    } while (argc < 3);
+    for (;;)
+        return 'x';
+    {
+        int nested;
+    }
+}
+");
+    }
+
+    #[test]
+    fn goto_and_labels() {
+        assert_eq!(format("int main() { goto end; end: again: return 0; }"), "\
+int main() {
+    goto end;
+    end:
+    again:
+    return 0;
+}
+");
+    }
+
+    #[test]
+    fn dangling_else_is_braced() {
+        // An `else` belonging to the outer `if` can't be written after an unbraced inner one.
+        let mut ast = Ast::new();
+        let a = ast.alloc_expr(ExpressionKind::Variable("a".to_owned()).into());
+        let b = ast.alloc_expr(ExpressionKind::Variable("b".to_owned()).into());
+        let inner_then = ast.alloc_stmt(StatementKind::Break.into());
+        let inner = ast.alloc_stmt(StatementKind::If(b, inner_then, None).into());
+        let otherwise = ast.alloc_stmt(StatementKind::Continue.into());
+        let outer = ast.alloc_stmt(StatementKind::If(a, inner, Some(otherwise)).into());
+
+        let mut writer = Writer { ast: &ast, output: String::new(), depth: 0 };
+        writer.statement(outer);
+        assert_eq!(writer.output, "\
+if (a) {
+    if (b)
+        break;
+} else
+    continue;
+");
+    }
+}
@@ -0,0 +1,247 @@
+//! Renders the AST as a Graphviz graph, for `--emit ast-dot`.
+
+use super::*;
+use super::pretty::{declaration_label, expression_label, function_label, statement_label};
+use super::visit::{self, Visitor};
+
+struct DotWriter {
+    output: String,
+    next_id: usize,
+    /// The node that new nodes hang from, and the role to label the edge with.
+    parent: Option<(usize, &'static str)>,
+}
+
+impl DotWriter {
+    /// Adds a node and the edge from its parent, returning the node's id. Ids
+    /// are handed out in traversal order, so the output is deterministic.
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.output.push_str(&format!("  n{} [label=\"{}\"];\n", id, escape_label(label)));
+        match self.parent {
+            Some((parent, "")) => self.output.push_str(&format!("  n{} -> n{};\n", parent, id)),
+            Some((parent, role)) => self.output.push_str(&format!("  n{} -> n{} [label=\"{}\"];\n", parent, id, role)),
+            None => {},
+        }
+        id
+    }
+
+    /// Runs `visit` with nodes it adds becoming children of `parent` in `role`.
+    fn child<F: FnOnce(&mut DotWriter)>(&mut self, parent: usize, role: &'static str, visit: F) {
+        let previous = self.parent;
+        self.parent = Some((parent, role));
+        visit(self);
+        self.parent = previous;
+    }
+
+    fn optional_expr(&mut self, ast: &Ast, parent: usize, role: &'static str, expression: Option<ExprId>) {
+        if let Some(expression) = expression {
+            self.child(parent, role, |w| w.visit_expr(ast, expression));
+        }
+    }
+}
+
+fn escape_label(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Visitor for DotWriter {
+    fn visit_program(&mut self, program: &Program) {
+        let id = self.node("program");
+        self.child(id, "", |w| visit::walk_program(w, program));
+    }
+
+    fn visit_struct(&mut self, definition: &StructDefinition) {
+        let id = self.node(&format!("struct {}", definition.name));
+        for member in &definition.members {
+            self.child(id, "member", |w| {
+                w.node(&format!("{} {}", member.ty, member.name));
+            });
+        }
+    }
+
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        let id = self.node(&function_label(function));
+        self.child(id, "param", |w| {
+            for parameter in &function.parameters {
+                w.visit_parameter(parameter);
+            }
+            if function.variadic {
+                w.node("...");
+            }
+        });
+        if let Some(ref body) = function.body {
+            self.child(id, "body", |w| w.visit_block(ast, body));
+        }
+    }
+
+    fn visit_parameter(&mut self, parameter: &Parameter) {
+        self.node(&format!("{} {}", parameter.ty, parameter.name));
+    }
+
+    fn visit_block(&mut self, ast: &Ast, items: &[BlockItem]) {
+        let id = self.node("block");
+        self.child(id, "", |w| visit::walk_block(w, ast, items));
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        let declaration = &ast[declaration];
+        let id = self.node(&declaration_label(declaration));
+        if let Some(ref initializer) = declaration.initializer {
+            self.child(id, "init", |w| w.visit_initializer(ast, initializer));
+        }
+    }
+
+    fn visit_initializer(&mut self, ast: &Ast, initializer: &Initializer) {
+        match *initializer {
+            Initializer::Expr(expression) => self.visit_expr(ast, expression),
+            Initializer::List(_) => {
+                let id = self.node("list");
+                self.child(id, "", |w| visit::walk_initializer(w, ast, initializer));
+            },
+        }
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        let statement = &ast[statement];
+
+        // An expression statement is drawn as just its expression.
+        let id = match statement.kind {
+            StatementKind::Expression(Some(expression)) => return self.visit_expr(ast, expression),
+            StatementKind::Compound(ref items) => return self.visit_block(ast, items),
+            ref kind => self.node(&statement_label(kind)),
+        };
+
+        match statement.kind {
+            StatementKind::Return(value) => self.child(id, "value", |w| w.visit_expr(ast, value)),
+            StatementKind::If(condition, then, otherwise) => {
+                self.child(id, "condition", |w| w.visit_expr(ast, condition));
+                self.child(id, "then", |w| w.visit_stmt(ast, then));
+                if let Some(otherwise) = otherwise {
+                    self.child(id, "else", |w| w.visit_stmt(ast, otherwise));
+                }
+            },
+            StatementKind::While(condition, body) | StatementKind::DoWhile(body, condition) => {
+                self.child(id, "condition", |w| w.visit_expr(ast, condition));
+                self.child(id, "body", |w| w.visit_stmt(ast, body));
+            },
+            StatementKind::For { ref init, condition, post, body } => {
+                match *init {
+                    ForInit::Declaration(ref declarations) => self.child(id, "init", |w| {
+                        for &declaration in declarations {
+                            w.visit_declaration(ast, declaration);
+                        }
+                    }),
+                    ForInit::Expression(expression) => self.optional_expr(ast, id, "init", expression),
+                }
+                self.optional_expr(ast, id, "condition", condition);
+                self.optional_expr(ast, id, "post", post);
+                self.child(id, "body", |w| w.visit_stmt(ast, body));
+            },
+            StatementKind::Labeled(_, statement) | StatementKind::Default(statement) =>
+                self.child(id, "", |w| w.visit_stmt(ast, statement)),
+            StatementKind::Switch(value, body) => {
+                self.child(id, "value", |w| w.visit_expr(ast, value));
+                self.child(id, "body", |w| w.visit_stmt(ast, body));
+            },
+            StatementKind::Case(value, statement) => {
+                self.child(id, "value", |w| w.visit_expr(ast, value));
+                self.child(id, "", |w| w.visit_stmt(ast, statement));
+            },
+            _ => {},
+        }
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        let id = self.node(&expression_label(&ast[expression].kind));
+        match ast[expression].kind {
+            ExpressionKind::IntLiteral(..)
+            | ExpressionKind::CharConstant(_)
+            | ExpressionKind::StringLiteral(_)
+            | ExpressionKind::Variable(_)
+            | ExpressionKind::SizeOf(_) => {},
+            ExpressionKind::Unary(_, operand) | ExpressionKind::Cast { expr: operand, .. } => {
+                self.child(id, "operand", |w| w.visit_expr(ast, operand));
+            },
+            ExpressionKind::Binary(_, lhs, rhs) => {
+                self.child(id, "lhs", |w| w.visit_expr(ast, lhs));
+                self.child(id, "rhs", |w| w.visit_expr(ast, rhs));
+            },
+            ExpressionKind::Assign(_, target, value) => {
+                self.child(id, "target", |w| w.visit_expr(ast, target));
+                self.child(id, "value", |w| w.visit_expr(ast, value));
+            },
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                self.child(id, "condition", |w| w.visit_expr(ast, condition));
+                self.child(id, "then", |w| w.visit_expr(ast, then));
+                self.child(id, "else", |w| w.visit_expr(ast, otherwise));
+            },
+            ExpressionKind::Call(..) => self.child(id, "argument", |w| visit::walk_expr(w, ast, expression)),
+            ExpressionKind::Subscript(array, index) => {
+                self.child(id, "array", |w| w.visit_expr(ast, array));
+                self.child(id, "index", |w| w.visit_expr(ast, index));
+            },
+            ExpressionKind::Member(object, _) | ExpressionKind::PointerMember(object, _) => {
+                self.child(id, "object", |w| w.visit_expr(ast, object));
+            },
+            ExpressionKind::Comma(_) => self.child(id, "", |w| visit::walk_expr(w, ast, expression)),
+        }
+    }
+}
+
+/// Renders `program` as a Graphviz `digraph` for `dot`. Each node is labeled
+/// with its kind and data, and each edge with the role the child plays.
+pub fn to_dot(program: &Program) -> String {
+    let mut writer = DotWriter {
+        output: "digraph ast {\n  node [shape=box];\n".to_owned(),
+        next_id: 0,
+        parent: None,
+    };
+    writer.visit_program(program);
+    writer.output.push_str("}\n");
+    writer.output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    #[test]
+    fn if_else_with_binary_condition() {
+        let program = parse_program(&lex_str("int main(int x) { if (x > 1) return x - 1; else return \"no\"; }").unwrap()).unwrap();
+        assert_eq!(to_dot(&program), r#"digraph ast {
+  node [shape=box];
+  n0 [label="program"];
+  n1 [label="function int main"];
+  n0 -> n1;
+  n2 [label="int x"];
+  n1 -> n2 [label="param"];
+  n3 [label="block"];
+  n1 -> n3 [label="body"];
+  n4 [label="if"];
+  n3 -> n4;
+  n5 [label="binary >"];
+  n4 -> n5 [label="condition"];
+  n6 [label="var x"];
+  n5 -> n6 [label="lhs"];
+  n7 [label="int 1"];
+  n5 -> n7 [label="rhs"];
+  n8 [label="return"];
+  n4 -> n8 [label="then"];
+  n9 [label="binary -"];
+  n8 -> n9 [label="value"];
+  n10 [label="var x"];
+  n9 -> n10 [label="lhs"];
+  n11 [label="int 1"];
+  n9 -> n11 [label="rhs"];
+  n12 [label="return"];
+  n4 -> n12 [label="else"];
+  n13 [label="string \"no\""];
+  n12 -> n13 [label="value"];
+}
+"#);
+    }
+}
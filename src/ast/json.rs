@@ -0,0 +1,123 @@
+//! JSON serialization of the AST, for external tools. Enums are written as
+//! objects with a `kind` tag and, if the variant carries data, a `value`. Nodes
+//! refer to each other by their index in the `ast` object's arrays.
+
+use serde_json;
+use super::Program;
+
+pub fn to_json(program: &Program) -> String {
+    serde_json::to_string_pretty(program).expect("the AST has no maps with non-string keys, so it always serializes")
+}
+
+pub fn from_json(json: &str) -> Result<Program, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    #[test]
+    fn round_trip() {
+        let program = parse_program(&lex_str("
+            struct P { int x; };
+            static char *names[2] = {\"a\", \"b\"};
+            int f(const int *p, ...);
+            int main() {
+                struct P q = {1};
+                for (int i = 0; i < 2; i++) if (!i) continue; else q.x += f(&i, names[i][0]);
+                do ; while (0);
+                return (char)q.x ? -1 : 2, 3;
+            }
+        ").unwrap()).unwrap();
+
+        let json = to_json(&program);
+        let restored = from_json(&json).unwrap();
+        assert_eq!(restored, program);
+        // Spans compare equal regardless of position, so compare them through the JSON too.
+        assert_eq!(to_json(&restored), json);
+    }
+
+    #[test]
+    fn snapshot() {
+        let program = parse_program(&lex_str("int main() { return -x; }").unwrap()).unwrap();
+        assert_eq!(to_json(&program), r#"{
+  "items": [
+    {
+      "kind": "Function",
+      "value": {
+        "storage": null,
+        "return_type": {
+          "kind": "Int"
+        },
+        "name": "main",
+        "parameters": [],
+        "variadic": false,
+        "body": [
+          {
+            "kind": "Statement",
+            "value": 0
+          }
+        ],
+        "span": {
+          "start": 0,
+          "end": 25,
+          "line": 1,
+          "column": 1
+        },
+        "closing_brace": {
+          "start": 24,
+          "end": 25,
+          "line": 1,
+          "column": 25
+        }
+      }
+    }
+  ],
+  "ast": {
+    "expressions": [
+      {
+        "kind": "Variable",
+        "value": "x",
+        "span": {
+          "start": 21,
+          "end": 22,
+          "line": 1,
+          "column": 22
+        }
+      },
+      {
+        "kind": "Unary",
+        "value": [
+          {
+            "kind": "Negate"
+          },
+          0
+        ],
+        "span": {
+          "start": 20,
+          "end": 22,
+          "line": 1,
+          "column": 21
+        }
+      }
+    ],
+    "statements": [
+      {
+        "kind": "Return",
+        "value": 1,
+        "span": {
+          "start": 13,
+          "end": 23,
+          "line": 1,
+          "column": 14
+        }
+      }
+    ],
+    "declarations": []
+  }
+}"#);
+    }
+}
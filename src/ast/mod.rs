@@ -0,0 +1,563 @@
+//! The abstract syntax tree produced by the parser. Expressions, statements, and
+//! declarations live in the arenas of an `Ast` and refer to their children by id.
+
+use std::fmt;
+use std::ops::{Index, IndexMut};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use pragma::DiagnosticPragma;
+
+mod c_source;
+mod dot;
+#[cfg(feature = "serde")]
+mod json;
+mod pretty;
+pub mod visit;
+
+pub use self::c_source::{expression_to_c_source, to_c_source};
+pub use self::dot::to_dot;
+#[cfg(feature = "serde")]
+pub use self::json::{from_json, to_json};
+pub use self::pretty::{pretty_print, pretty_print_with_spans};
+
+/// A region of the source: the byte offsets `start..end`, and the line and
+/// column where it begins.
+///
+/// Spans compare equal to each other whatever their positions, so equality of
+/// AST nodes is structural: the same tree parsed from differently laid out
+/// source compares equal, and expected trees can be built without positions.
+/// Compare the fields directly to check a position.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Span) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum Type {
+    Int,
+    Char,
+    /// A 64-bit `long`.
+    Long,
+    UnsignedInt,
+    UnsignedLong,
+    Pointer(Box<Type>),
+    Array(Box<Type>, usize),
+    /// A struct type, referred to by its tag.
+    Struct(String),
+    /// A const-qualified type. `const char *` is `Pointer(Const(Char))`, while
+    /// `char *const` is `Const(Pointer(Char))`.
+    Const(Box<Type>),
+}
+
+impl Type {
+    /// Returns this type with a const qualifier, without doubling an existing one.
+    pub fn with_const(self) -> Type {
+        match self {
+            Type::Const(_) => self,
+            _ => Type::Const(Box::new(self)),
+        }
+    }
+
+    pub fn is_const(&self) -> bool {
+        matches!(*self, Type::Const(_))
+    }
+
+    /// Returns this type without any top-level qualifier.
+    pub fn unqualified(&self) -> &Type {
+        match *self {
+            Type::Const(ref inner) => inner,
+            _ => self,
+        }
+    }
+
+    /// Whether this is one of the integer types, ignoring qualifiers.
+    pub fn is_integer(&self) -> bool {
+        matches!(*self.unqualified(), Type::Int | Type::Char | Type::Long | Type::UnsignedInt | Type::UnsignedLong)
+    }
+
+    /// Whether this is a signed integer type, ignoring qualifiers. `char` is
+    /// signed, as it is on x86-64.
+    pub fn is_signed(&self) -> bool {
+        matches!(*self.unqualified(), Type::Int | Type::Char | Type::Long)
+    }
+
+    /// Whether this is an integer or pointer type, ignoring qualifiers. Only
+    /// scalars can be tested for truth.
+    pub fn is_scalar(&self) -> bool {
+        self.is_integer() || self.is_pointer()
+    }
+
+    /// Whether this is a pointer type, ignoring qualifiers.
+    pub fn is_pointer(&self) -> bool {
+        matches!(*self.unqualified(), Type::Pointer(_))
+    }
+
+    /// The type of a value of this type used as an operand: arrays become
+    /// pointers to their first element, and qualifiers are dropped.
+    pub fn decay(&self) -> Type {
+        match *self.unqualified() {
+            Type::Array(ref element, _) => Type::Pointer(element.clone()),
+            ref ty => ty.clone(),
+        }
+    }
+}
+
+/// Writes the type the way it would be spelled in a cast, except that array
+/// dimensions follow the element type directly (`int[2][3]`, `char*[4]`).
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Int => f.write_str("int"),
+            Type::Char => f.write_str("char"),
+            Type::Long => f.write_str("long"),
+            Type::UnsignedInt => f.write_str("unsigned int"),
+            Type::UnsignedLong => f.write_str("unsigned long"),
+            Type::Struct(ref tag) => write!(f, "struct {}", tag),
+            Type::Pointer(ref pointee) => write!(f, "{}*", pointee),
+            Type::Const(ref inner) => match **inner {
+                Type::Pointer(_) => write!(f, "{} const", inner),
+                _ => write!(f, "const {}", inner),
+            },
+            Type::Array(..) => {
+                let mut dimensions = Vec::new();
+                let mut element = self;
+                while let Type::Array(ref inner, length) = *element {
+                    dimensions.push(length);
+                    element = inner;
+                }
+                write!(f, "{}", element)?;
+                for length in dimensions {
+                    write!(f, "[{}]", length)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum UnaryOp {
+    Negate,
+    BitwiseNot,
+    LogicalNot,
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement,
+    Dereference,
+    AddressOf,
+}
+
+impl UnaryOp {
+    /// The operator as written in source. Increments and decrements are spelled
+    /// the same whether prefix or postfix.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Negate => "-",
+            UnaryOp::BitwiseNot => "~",
+            UnaryOp::LogicalNot => "!",
+            UnaryOp::PreIncrement | UnaryOp::PostIncrement => "++",
+            UnaryOp::PreDecrement | UnaryOp::PostDecrement => "--",
+            UnaryOp::Dereference => "*",
+            UnaryOp::AddressOf => "&",
+        }
+    }
+
+    pub fn is_postfix(self) -> bool {
+        matches!(self, UnaryOp::PostIncrement | UnaryOp::PostDecrement)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    ShiftLeft,
+    ShiftRight,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    BitwiseAnd,
+    BitwiseXor,
+    BitwiseOr,
+    LogicalAnd,
+    LogicalOr,
+}
+
+impl BinaryOp {
+    /// How tightly the operator binds, from 1 for `||` to 10 for the
+    /// multiplicative operators. All binary operators are left-associative.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::LogicalOr => 1,
+            BinaryOp::LogicalAnd => 2,
+            BinaryOp::BitwiseOr => 3,
+            BinaryOp::BitwiseXor => 4,
+            BinaryOp::BitwiseAnd => 5,
+            BinaryOp::Equal | BinaryOp::NotEqual => 6,
+            BinaryOp::LessThan | BinaryOp::LessEqual | BinaryOp::GreaterThan | BinaryOp::GreaterEqual => 7,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 8,
+            BinaryOp::Add | BinaryOp::Subtract => 9,
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => 10,
+        }
+    }
+
+    /// The operator as written in source.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+            BinaryOp::LessThan => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::GreaterThan => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::BitwiseAnd => "&",
+            BinaryOp::BitwiseXor => "^",
+            BinaryOp::BitwiseOr => "|",
+            BinaryOp::LogicalAnd => "&&",
+            BinaryOp::LogicalOr => "||",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Expression {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+impl From<ExpressionKind> for Expression {
+    /// Wraps `kind` with an empty span, for expressions that don't come from source.
+    fn from(kind: ExpressionKind) -> Expression {
+        Expression { kind, span: Span::default() }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum ExpressionKind {
+    /// An integer literal's value and type, as the lexer gives them.
+    IntLiteral(i64, Type),
+    CharConstant(u8),
+    /// The bytes of a string literal, without the implicit terminating null.
+    StringLiteral(Vec<u8>),
+    Variable(String),
+    Unary(UnaryOp, ExprId),
+    Binary(BinaryOp, ExprId, ExprId),
+    /// An assignment, optionally compound (`a += b` carries `Some(BinaryOp::Add)`).
+    Assign(Option<BinaryOp>, ExprId, ExprId),
+    /// `condition ? then : else`
+    Conditional(ExprId, ExprId, ExprId),
+    Call(String, Vec<ExprId>),
+    Cast { to: Type, expr: ExprId },
+    /// `sizeof(type)`.
+    SizeOf(Type),
+    /// `array[index]`
+    Subscript(ExprId, ExprId),
+    /// `object.member`
+    Member(ExprId, String),
+    /// `pointer->member`
+    PointerMember(ExprId, String),
+    /// A sequence of expressions separated by the comma operator; evaluates
+    /// each in order and yields the value of the last one.
+    Comma(Vec<ExprId>),
+}
+
+/// The initial value of a declared object. Aggregates given fewer initializers
+/// than they have elements have the remaining elements zero-initialized.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum Initializer {
+    Expr(ExprId),
+    /// A brace-enclosed initializer list.
+    List(Vec<Initializer>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum StorageClass {
+    /// At file scope, gives the declaration internal linkage. At block scope,
+    /// gives the variable static storage duration.
+    Static,
+    /// Declares an object or function defined elsewhere; no storage is allocated.
+    Extern,
+}
+
+impl fmt::Display for StorageClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            StorageClass::Static => "static",
+            StorageClass::Extern => "extern",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Declaration {
+    pub storage: Option<StorageClass>,
+    pub ty: Type,
+    pub name: String,
+    pub initializer: Option<Initializer>,
+    /// Covers the declarator and its initializer, e.g. `*p = 0` in `int *p = 0, q;`.
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum ForInit {
+    Declaration(Vec<DeclId>),
+    Expression(Option<ExprId>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Statement {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl From<StatementKind> for Statement {
+    /// Wraps `kind` with an empty span, for statements that don't come from source.
+    fn from(kind: StatementKind) -> Statement {
+        Statement { kind, span: Span::default() }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum StatementKind {
+    Return(ExprId),
+    /// An expression statement; `None` is the null statement `;`.
+    Expression(Option<ExprId>),
+    If(ExprId, StmtId, Option<StmtId>),
+    Compound(Vec<BlockItem>),
+    While(ExprId, StmtId),
+    DoWhile(StmtId, ExprId),
+    For {
+        init: ForInit,
+        condition: Option<ExprId>,
+        post: Option<ExprId>,
+        body: StmtId,
+    },
+    Break,
+    Continue,
+    Goto(String),
+    /// `label: statement`
+    Labeled(String, StmtId),
+    /// `switch (value) body`. The body's `case` and `default` labels, which may
+    /// be nested anywhere in it, say where control goes.
+    Switch(ExprId, StmtId),
+    /// `case value: statement`, where `value` is an integer constant expression.
+    Case(ExprId, StmtId),
+    /// `default: statement`
+    Default(StmtId),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum BlockItem {
+    Statement(StmtId),
+    /// A single declaration statement, which may declare several variables (`int a, b = 1;`).
+    Declaration(Vec<DeclId>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Parameter {
+    pub ty: Type,
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Function {
+    pub storage: Option<StorageClass>,
+    pub return_type: Type,
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    /// Whether the parameter list ends in `...`.
+    pub variadic: bool,
+    /// `None` for a prototype without a body.
+    pub body: Option<Vec<BlockItem>>,
+    pub span: Span,
+    /// The `}` that ends the body, which is where control falls off the end of
+    /// the function. `None` for a prototype.
+    pub closing_brace: Option<Span>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Member {
+    pub ty: Type,
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StructDefinition {
+    pub name: String,
+    pub members: Vec<Member>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum TopLevel {
+    Function(Function),
+    Struct(StructDefinition),
+    Declaration(Vec<DeclId>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Program {
+    pub items: Vec<TopLevel>,
+    /// The nodes that `items` refer to.
+    pub ast: Ast,
+    /// The `#pragma GCC diagnostic`s, in the order they are in the source.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub pragmas: Vec<DiagnosticPragma>,
+    /// The names declared by `typedef`s, in alphabetical order. The parser
+    /// replaces them with the types they name wherever they are used.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub typedefs: Vec<String>,
+}
+
+macro_rules! node_id {
+    ($(#[$attr:meta])* $id:ident, $node:ident, $arena:ident, $alloc:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        pub struct $id(u32);
+
+        impl $id {
+            /// The position of the node in its arena, for side tables kept in a dense `Vec`.
+            pub fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+
+        impl Ast {
+            pub fn $alloc(&mut self, node: $node) -> $id {
+                let id = $id(self.$arena.len() as u32);
+                self.$arena.push(node);
+                id
+            }
+        }
+
+        impl Index<$id> for Ast {
+            type Output = $node;
+
+            fn index(&self, id: $id) -> &$node {
+                &self.$arena[id.index()]
+            }
+        }
+
+        impl IndexMut<$id> for Ast {
+            fn index_mut(&mut self, id: $id) -> &mut $node {
+                &mut self.$arena[id.index()]
+            }
+        }
+    };
+}
+
+node_id!(
+    /// Refers to an `Expression` in an `Ast`.
+    ExprId, Expression, expressions, alloc_expr
+);
+node_id!(
+    /// Refers to a `Statement` in an `Ast`.
+    StmtId, Statement, statements, alloc_stmt
+);
+node_id!(
+    /// Refers to a `Declaration` in an `Ast`.
+    DeclId, Declaration, declarations, alloc_decl
+);
+
+/// The arenas holding a program's expressions, statements, and declarations.
+/// Nodes refer to each other by id, and ids are handed out in allocation order, and the parser allocates each node after its children,
+/// so a given program always gets the same ids.
+///
+/// Information computed about nodes is kept beside the tree rather than in
+/// it, in side tables keyed by id: a `HashMap<ExprId, Type>`, or a `Vec`
+/// indexed by `ExprId::index` and sized by `expression_count`.
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Ast {
+    expressions: Vec<Expression>,
+    statements: Vec<Statement>,
+    declarations: Vec<Declaration>,
+}
+
+impl Ast {
+    pub fn new() -> Ast {
+        Ast::default()
+    }
+
+    pub fn expression_count(&self) -> usize {
+        self.expressions.len()
+    }
+
+    pub fn statement_count(&self) -> usize {
+        self.statements.len()
+    }
+
+    pub fn declaration_count(&self) -> usize {
+        self.declarations.len()
+    }
+
+    /// Removes `id`, which must be the last expression allocated, for when the
+    /// parser turns out not to need a node it has already built.
+    pub(crate) fn discard_expr(&mut self, id: ExprId) -> Expression {
+        debug_assert_eq!(id.index() + 1, self.expressions.len());
+        self.expressions.pop().unwrap()
+    }
+
+    /// Removes every expression allocated after the first `count`, for
+    /// expressions that are only needed until they have been evaluated.
+    pub(crate) fn truncate_expressions(&mut self, count: usize) {
+        self.expressions.truncate(count);
+    }
+}
@@ -0,0 +1,429 @@
+//! A compact, indented rendering of the AST with one node per line, used by
+//! `--emit ast` and by tests that want readable diffs.
+
+use lexer::escape;
+use super::*;
+
+struct Printer<'a> {
+    ast: &'a Ast,
+    output: String,
+    depth: usize,
+    /// Whether to follow each node that has a span with its starting line and column.
+    spans: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn line(&mut self, text: &str, span: Option<Span>) {
+        for _ in 0..self.depth {
+            self.output.push_str("  ");
+        }
+        self.output.push_str(text);
+        if let (true, Some(span)) = (self.spans, span) {
+            self.output.push_str(&format!(" @{}:{}", span.line, span.column));
+        }
+        self.output.push('\n');
+    }
+
+    /// Writes `text` as a node and prints whatever `children` writes beneath it.
+    fn node<F: FnOnce(&mut Printer<'a>)>(&mut self, text: &str, span: Option<Span>, children: F) {
+        self.line(text, span);
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+    }
+
+    fn top_level(&mut self, item: &TopLevel) {
+        match *item {
+            TopLevel::Function(ref function) => self.function(function),
+            TopLevel::Struct(ref definition) => {
+                self.node(&format!("struct {}", definition.name), Some(definition.span), |p| {
+                    for member in &definition.members {
+                        p.line(&format!("member {} {}", member.ty, member.name), Some(member.span));
+                    }
+                });
+            },
+            TopLevel::Declaration(ref declarations) => self.declarations(declarations),
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.node(&function_label(function), Some(function.span), |p| {
+            for parameter in &function.parameters {
+                p.line(&format!("param {} {}", parameter.ty, parameter.name), Some(parameter.span));
+            }
+            if function.variadic {
+                p.line("...", None);
+            }
+            if let Some(ref body) = function.body {
+                p.block(body, None);
+            }
+        });
+    }
+
+    fn declarations(&mut self, declarations: &[DeclId]) {
+        for &declaration in declarations {
+            let declaration = &self.ast[declaration];
+            self.node(&declaration_label(declaration), Some(declaration.span), |p| {
+                if let Some(ref initializer) = declaration.initializer {
+                    p.initializer(initializer);
+                }
+            });
+        }
+    }
+
+    fn initializer(&mut self, initializer: &Initializer) {
+        match *initializer {
+            Initializer::Expr(expression) => self.expression(expression),
+            Initializer::List(ref elements) => {
+                self.node("list", None, |p| {
+                    for element in elements {
+                        p.initializer(element);
+                    }
+                });
+            },
+        }
+    }
+
+    fn block(&mut self, items: &[BlockItem], span: Option<Span>) {
+        self.node("block", span, |p| {
+            for item in items {
+                match *item {
+                    BlockItem::Statement(statement) => p.statement(statement),
+                    BlockItem::Declaration(ref declarations) => p.declarations(declarations),
+                }
+            }
+        });
+    }
+
+    /// Prints an optional expression, using `none` as a placeholder so that
+    /// the positions of the remaining children stay unambiguous.
+    fn optional_expression(&mut self, expression: Option<ExprId>) {
+        match expression {
+            Some(expression) => self.expression(expression),
+            None => self.line("none", None),
+        }
+    }
+
+    fn statement(&mut self, statement: StmtId) {
+        let statement = &self.ast[statement];
+        let span = Some(statement.span);
+        let label = &statement_label(&statement.kind);
+        match statement.kind {
+            StatementKind::Return(value) => self.node(label, span, |p| p.expression(value)),
+            StatementKind::Expression(None) => self.line(label, span),
+            StatementKind::Expression(Some(expression)) => self.expression(expression),
+            StatementKind::If(condition, then, otherwise) => {
+                self.node(label, span, |p| {
+                    p.expression(condition);
+                    p.statement(then);
+                    if let Some(otherwise) = otherwise {
+                        p.node("else", None, |p| p.statement(otherwise));
+                    }
+                });
+            },
+            StatementKind::Compound(ref items) => self.block(items, span),
+            StatementKind::While(condition, body) => {
+                self.node(label, span, |p| {
+                    p.expression(condition);
+                    p.statement(body);
+                });
+            },
+            StatementKind::DoWhile(body, condition) => {
+                self.node(label, span, |p| {
+                    p.statement(body);
+                    p.expression(condition);
+                });
+            },
+            StatementKind::For { ref init, condition, post, body } => {
+                self.node(label, span, |p| {
+                    match *init {
+                        ForInit::Declaration(ref declarations) => p.declarations(declarations),
+                        ForInit::Expression(expression) => p.optional_expression(expression),
+                    }
+                    p.optional_expression(condition);
+                    p.optional_expression(post);
+                    p.statement(body);
+                });
+            },
+            StatementKind::Labeled(_, statement) | StatementKind::Default(statement) =>
+                self.node(label, span, |p| p.statement(statement)),
+            StatementKind::Switch(expression, statement) | StatementKind::Case(expression, statement) => {
+                self.node(label, span, |p| {
+                    p.expression(expression);
+                    p.statement(statement);
+                });
+            },
+            StatementKind::Break | StatementKind::Continue | StatementKind::Goto(_) => self.line(label, span),
+        }
+    }
+
+    fn expression(&mut self, expression: ExprId) {
+        let expression = &self.ast[expression];
+        let span = Some(expression.span);
+        let label = expression_label(&expression.kind);
+        match expression.kind {
+            ExpressionKind::IntLiteral(..)
+            | ExpressionKind::CharConstant(_)
+            | ExpressionKind::StringLiteral(_)
+            | ExpressionKind::Variable(_)
+            | ExpressionKind::SizeOf(_) => self.line(&label, span),
+            ExpressionKind::Unary(_, operand)
+            | ExpressionKind::Cast { expr: operand, .. }
+            | ExpressionKind::Member(operand, _)
+            | ExpressionKind::PointerMember(operand, _) => self.node(&label, span, |p| p.expression(operand)),
+            ExpressionKind::Binary(_, lhs, rhs)
+            | ExpressionKind::Assign(_, lhs, rhs)
+            | ExpressionKind::Subscript(lhs, rhs) => {
+                self.node(&label, span, |p| {
+                    p.expression(lhs);
+                    p.expression(rhs);
+                });
+            },
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                self.node(&label, span, |p| {
+                    p.expression(condition);
+                    p.expression(then);
+                    p.expression(otherwise);
+                });
+            },
+            ExpressionKind::Call(_, ref expressions) | ExpressionKind::Comma(ref expressions) => {
+                self.node(&label, span, |p| {
+                    for &expression in expressions {
+                        p.expression(expression);
+                    }
+                });
+            },
+        }
+    }
+}
+
+fn storage_prefix(storage: Option<StorageClass>) -> String {
+    match storage {
+        Some(storage) => format!("{} ", storage),
+        None => String::new(),
+    }
+}
+
+// The labels below are shared with the other renderings of the tree, so that
+// they all describe nodes the same way.
+
+pub(super) fn function_label(function: &Function) -> String {
+    format!("function {}{} {}", storage_prefix(function.storage), function.return_type, function.name)
+}
+
+pub(super) fn declaration_label(declaration: &Declaration) -> String {
+    format!("declare {}{} {}", storage_prefix(declaration.storage), declaration.ty, declaration.name)
+}
+
+pub(super) fn statement_label(kind: &StatementKind) -> String {
+    let label = match *kind {
+        StatementKind::Return(_) => "return",
+        StatementKind::Expression(None) => "empty",
+        StatementKind::Expression(Some(_)) => "expression",
+        StatementKind::If(..) => "if",
+        StatementKind::Compound(_) => "block",
+        StatementKind::While(..) => "while",
+        StatementKind::DoWhile(..) => "do",
+        StatementKind::For { .. } => "for",
+        StatementKind::Break => "break",
+        StatementKind::Continue => "continue",
+        StatementKind::Goto(ref label) => return format!("goto {}", label),
+        StatementKind::Labeled(ref label, _) => return format!("label {}", label),
+        StatementKind::Switch(..) => "switch",
+        StatementKind::Case(..) => "case",
+        StatementKind::Default(_) => "default",
+    };
+    label.to_owned()
+}
+
+pub(super) fn expression_label(kind: &ExpressionKind) -> String {
+    match *kind {
+        ExpressionKind::IntLiteral(value, Type::UnsignedLong) => format!("unsigned long {}", value as u64),
+        ExpressionKind::IntLiteral(value, ref ty) => format!("{} {}", ty, value),
+        ExpressionKind::CharConstant(value) => format!("char '{}'", escape(&[value], b'\'')),
+        ExpressionKind::StringLiteral(ref bytes) => format!("string \"{}\"", escape(bytes, b'"')),
+        ExpressionKind::Variable(ref name) => format!("var {}", name),
+        ExpressionKind::Unary(op, _) => {
+            let fixity = if op.is_postfix() { "postfix" } else { "prefix" };
+            format!("{} {}", fixity, op.symbol())
+        },
+        ExpressionKind::Binary(op, ..) => format!("binary {}", op.symbol()),
+        ExpressionKind::Assign(op, ..) => format!("assign {}=", op.map_or("", BinaryOp::symbol)),
+        ExpressionKind::Conditional(..) => "conditional".to_owned(),
+        ExpressionKind::Call(ref name, _) => format!("call {}", name),
+        ExpressionKind::Cast { ref to, .. } => format!("cast {}", to),
+        ExpressionKind::SizeOf(ref ty) => format!("sizeof {}", ty),
+        ExpressionKind::Subscript(..) => "subscript".to_owned(),
+        ExpressionKind::Member(_, ref member) => format!("member .{}", member),
+        ExpressionKind::PointerMember(_, ref member) => format!("member ->{}", member),
+        ExpressionKind::Comma(_) => "comma".to_owned(),
+    }
+}
+
+fn print(program: &Program, spans: bool) -> String {
+    let mut printer = Printer { ast: &program.ast, output: String::new(), depth: 0, spans };
+    printer.node("program", None, |p| {
+        for item in &program.items {
+            p.top_level(item);
+        }
+    });
+    printer.output
+}
+
+/// Renders `program` as an indented tree, one node per line. Children are
+/// indented two spaces beneath their parent and appear in source order.
+pub fn pretty_print(program: &Program) -> String {
+    print(program, false)
+}
+
+/// Like `pretty_print`, but follows each node that has a span with the line
+/// and column it starts at, as in `return @3:5`.
+pub fn pretty_print_with_spans(program: &Program) -> String {
+    print(program, true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn parse(input: &str) -> Program {
+        parse_program(&lex_str(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn declarations_control_flow_and_expressions() {
+        let source = "
+            struct Point { int x; int y; };
+            static int counter = 0;
+            extern const char *name;
+            int sum(int *values, int count, ...);
+
+            int main(int argc) {
+                int grid[2][3] = {{1, 2}, {3}};
+                struct Point p;
+                p.x = -argc * (2 + 3);
+                for (int i = 0; i < 10; i++) {
+                    if (i % 2 == 0) continue;
+                    else counter += i;
+                }
+                for (;;) break;
+                while (counter > 0) counter--;
+                do ; while (!counter);
+                return counter ? (char)sum(grid[1], 'a', \"hi\\n\") : (p.y, &p)->x;
+            }
+        ";
+
+        assert_eq!(pretty_print(&parse(source)), "\
+program
+  struct Point
+    member int x
+    member int y
+  declare static int counter
+    int 0
+  declare extern const char* name
+  function int sum
+    param int* values
+    param int count
+    ...
+  function int main
+    param int argc
+    block
+      declare int[2][3] grid
+        list
+          list
+            int 1
+            int 2
+          list
+            int 3
+      declare struct Point p
+      assign =
+        member .x
+          var p
+        binary *
+          prefix -
+            var argc
+          binary +
+            int 2
+            int 3
+      for
+        declare int i
+          int 0
+        binary <
+          var i
+          int 10
+        postfix ++
+          var i
+        block
+          if
+            binary ==
+              binary %
+                var i
+                int 2
+              int 0
+            continue
+            else
+              assign +=
+                var counter
+                var i
+      for
+        none
+        none
+        none
+        break
+      while
+        binary >
+          var counter
+          int 0
+        postfix --
+          var counter
+      do
+        empty
+        prefix !
+          var counter
+      return
+        conditional
+          var counter
+          cast char
+            call sum
+              subscript
+                var grid
+                int 1
+              char 'a'
+              string \"hi\\n\"
+          member ->x
+            comma
+              member .y
+                var p
+              prefix &
+                var p
+");
+    }
+
+    #[test]
+    fn qualified_and_derived_types() {
+        assert_eq!(pretty_print(&parse("char *const p; const int *q[2]; int **r;")), "\
+program
+  declare char* const p
+  declare const int*[2] q
+  declare int** r
+");
+    }
+
+    #[test]
+    fn positions() {
+        let program = parse("int main() {\n    int x = 1;\n    return x + 2;\n}");
+        assert_eq!(pretty_print_with_spans(&program), "\
+program
+  function int main @1:1
+    block
+      declare int x @2:9
+        int 1 @2:13
+      return @3:5
+        binary + @3:12
+          var x @3:12
+          int 2 @3:16
+");
+    }
+}
@@ -0,0 +1,537 @@
+//! Traversal of the AST. `Visitor` walks a tree by reference and `VisitorMut`
+//! walks it mutably. Each method defaults to the matching `walk_*` function,
+//! which visits the node's children, so an implementation overrides only the
+//! nodes it cares about and calls `walk_*` itself to keep recursing.
+//!
+//! Nodes that live in the `Ast` are visited by id, alongside the `Ast` itself,
+//! so that a visitor can record what it finds in side tables keyed by id.
+
+use std::mem;
+use super::*;
+
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_top_level(&mut self, ast: &Ast, item: &TopLevel) {
+        walk_top_level(self, ast, item)
+    }
+
+    fn visit_struct(&mut self, _definition: &StructDefinition) {}
+
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        walk_function(self, ast, function)
+    }
+
+    fn visit_parameter(&mut self, _parameter: &Parameter) {}
+
+    /// Called for function bodies and compound statements.
+    fn visit_block(&mut self, ast: &Ast, items: &[BlockItem]) {
+        walk_block(self, ast, items)
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        walk_declaration(self, ast, declaration)
+    }
+
+    fn visit_initializer(&mut self, ast: &Ast, initializer: &Initializer) {
+        walk_initializer(self, ast, initializer)
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        walk_stmt(self, ast, statement)
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        walk_expr(self, ast, expression)
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for item in &program.items {
+        visitor.visit_top_level(&program.ast, item);
+    }
+}
+
+pub fn walk_top_level<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, item: &TopLevel) {
+    match *item {
+        TopLevel::Function(ref function) => visitor.visit_function(ast, function),
+        TopLevel::Struct(ref definition) => visitor.visit_struct(definition),
+        TopLevel::Declaration(ref declarations) => {
+            for &declaration in declarations {
+                visitor.visit_declaration(ast, declaration);
+            }
+        },
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, function: &Function) {
+    for parameter in &function.parameters {
+        visitor.visit_parameter(parameter);
+    }
+    if let Some(ref body) = function.body {
+        visitor.visit_block(ast, body);
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, items: &[BlockItem]) {
+    for item in items {
+        match *item {
+            BlockItem::Statement(statement) => visitor.visit_stmt(ast, statement),
+            BlockItem::Declaration(ref declarations) => {
+                for &declaration in declarations {
+                    visitor.visit_declaration(ast, declaration);
+                }
+            },
+        }
+    }
+}
+
+pub fn walk_declaration<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, declaration: DeclId) {
+    if let Some(ref initializer) = ast[declaration].initializer {
+        visitor.visit_initializer(ast, initializer);
+    }
+}
+
+pub fn walk_initializer<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, initializer: &Initializer) {
+    match *initializer {
+        Initializer::Expr(expression) => visitor.visit_expr(ast, expression),
+        Initializer::List(ref items) => {
+            for item in items {
+                visitor.visit_initializer(ast, item);
+            }
+        },
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, statement: StmtId) {
+    match ast[statement].kind {
+        StatementKind::Return(value) => visitor.visit_expr(ast, value),
+        StatementKind::Expression(expression) => {
+            if let Some(expression) = expression {
+                visitor.visit_expr(ast, expression);
+            }
+        },
+        StatementKind::If(condition, then, otherwise) => {
+            visitor.visit_expr(ast, condition);
+            visitor.visit_stmt(ast, then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_stmt(ast, otherwise);
+            }
+        },
+        StatementKind::Compound(ref items) => visitor.visit_block(ast, items),
+        StatementKind::While(condition, body) => {
+            visitor.visit_expr(ast, condition);
+            visitor.visit_stmt(ast, body);
+        },
+        StatementKind::DoWhile(body, condition) => {
+            visitor.visit_stmt(ast, body);
+            visitor.visit_expr(ast, condition);
+        },
+        StatementKind::For { ref init, condition, post, body } => {
+            match *init {
+                ForInit::Declaration(ref declarations) => {
+                    for &declaration in declarations {
+                        visitor.visit_declaration(ast, declaration);
+                    }
+                },
+                ForInit::Expression(Some(expression)) => visitor.visit_expr(ast, expression),
+                ForInit::Expression(None) => {},
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expr(ast, condition);
+            }
+            if let Some(post) = post {
+                visitor.visit_expr(ast, post);
+            }
+            visitor.visit_stmt(ast, body);
+        },
+        StatementKind::Labeled(_, statement) | StatementKind::Default(statement) => visitor.visit_stmt(ast, statement),
+        StatementKind::Switch(expression, statement) | StatementKind::Case(expression, statement) => {
+            visitor.visit_expr(ast, expression);
+            visitor.visit_stmt(ast, statement);
+        },
+        StatementKind::Break | StatementKind::Continue | StatementKind::Goto(_) => {},
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, expression: ExprId) {
+    match ast[expression].kind {
+        ExpressionKind::IntLiteral(..)
+        | ExpressionKind::CharConstant(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::Variable(_)
+        | ExpressionKind::SizeOf(_) => {},
+        ExpressionKind::Unary(_, operand) => visitor.visit_expr(ast, operand),
+        ExpressionKind::Binary(_, lhs, rhs)
+        | ExpressionKind::Assign(_, lhs, rhs)
+        | ExpressionKind::Subscript(lhs, rhs) => {
+            visitor.visit_expr(ast, lhs);
+            visitor.visit_expr(ast, rhs);
+        },
+        ExpressionKind::Conditional(condition, then, otherwise) => {
+            visitor.visit_expr(ast, condition);
+            visitor.visit_expr(ast, then);
+            visitor.visit_expr(ast, otherwise);
+        },
+        ExpressionKind::Call(_, ref expressions) | ExpressionKind::Comma(ref expressions) => {
+            for &expression in expressions {
+                visitor.visit_expr(ast, expression);
+            }
+        },
+        ExpressionKind::Cast { expr, .. } => visitor.visit_expr(ast, expr),
+        ExpressionKind::Member(object, _) | ExpressionKind::PointerMember(object, _) => visitor.visit_expr(ast, object),
+    }
+}
+
+/// The counterpart of `Visitor` for passes that rewrite the tree in place.
+/// Nodes in the `Ast` are rewritten through it, as in `ast[expression].kind = ...`.
+pub trait VisitorMut {
+    fn visit_program(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_top_level(&mut self, ast: &mut Ast, item: &mut TopLevel) {
+        walk_top_level_mut(self, ast, item)
+    }
+
+    fn visit_struct(&mut self, _definition: &mut StructDefinition) {}
+
+    fn visit_function(&mut self, ast: &mut Ast, function: &mut Function) {
+        walk_function_mut(self, ast, function)
+    }
+
+    fn visit_parameter(&mut self, _parameter: &mut Parameter) {}
+
+    /// Called for function bodies and compound statements.
+    fn visit_block(&mut self, ast: &mut Ast, items: &mut Vec<BlockItem>) {
+        walk_block_mut(self, ast, items)
+    }
+
+    fn visit_declaration(&mut self, ast: &mut Ast, declaration: DeclId) {
+        walk_declaration_mut(self, ast, declaration)
+    }
+
+    fn visit_initializer(&mut self, ast: &mut Ast, initializer: &mut Initializer) {
+        walk_initializer_mut(self, ast, initializer)
+    }
+
+    fn visit_stmt(&mut self, ast: &mut Ast, statement: StmtId) {
+        walk_stmt_mut(self, ast, statement)
+    }
+
+    fn visit_expr(&mut self, ast: &mut Ast, expression: ExprId) {
+        walk_expr_mut(self, ast, expression)
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    let Program { ref mut items, ref mut ast, .. } = *program;
+    for item in items {
+        visitor.visit_top_level(ast, item);
+    }
+}
+
+pub fn walk_top_level_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, item: &mut TopLevel) {
+    match *item {
+        TopLevel::Function(ref mut function) => visitor.visit_function(ast, function),
+        TopLevel::Struct(ref mut definition) => visitor.visit_struct(definition),
+        TopLevel::Declaration(ref declarations) => {
+            for &declaration in declarations {
+                visitor.visit_declaration(ast, declaration);
+            }
+        },
+    }
+}
+
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, function: &mut Function) {
+    for parameter in &mut function.parameters {
+        visitor.visit_parameter(parameter);
+    }
+    if let Some(ref mut body) = function.body {
+        visitor.visit_block(ast, body);
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, items: &mut Vec<BlockItem>) {
+    for item in items {
+        match *item {
+            BlockItem::Statement(statement) => visitor.visit_stmt(ast, statement),
+            BlockItem::Declaration(ref declarations) => {
+                for &declaration in declarations {
+                    visitor.visit_declaration(ast, declaration);
+                }
+            },
+        }
+    }
+}
+
+// The parts of a node that aren't nodes themselves, like an initializer or a
+// block's items, are taken out of the arena while they are visited, so that the
+// visitor can have the rest of it mutably at the same time.
+
+pub fn walk_declaration_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, declaration: DeclId) {
+    if let Some(mut initializer) = ast[declaration].initializer.take() {
+        visitor.visit_initializer(ast, &mut initializer);
+        ast[declaration].initializer = Some(initializer);
+    }
+}
+
+pub fn walk_initializer_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, initializer: &mut Initializer) {
+    match *initializer {
+        Initializer::Expr(expression) => visitor.visit_expr(ast, expression),
+        Initializer::List(ref mut items) => {
+            for item in items {
+                visitor.visit_initializer(ast, item);
+            }
+        },
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, statement: StmtId) {
+    match ast[statement].kind {
+        StatementKind::Return(value) => visitor.visit_expr(ast, value),
+        StatementKind::Expression(expression) => {
+            if let Some(expression) = expression {
+                visitor.visit_expr(ast, expression);
+            }
+        },
+        StatementKind::If(condition, then, otherwise) => {
+            visitor.visit_expr(ast, condition);
+            visitor.visit_stmt(ast, then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_stmt(ast, otherwise);
+            }
+        },
+        StatementKind::Compound(ref mut items) => {
+            let mut items = mem::take(items);
+            visitor.visit_block(ast, &mut items);
+            ast[statement].kind = StatementKind::Compound(items);
+        },
+        StatementKind::While(condition, body) => {
+            visitor.visit_expr(ast, condition);
+            visitor.visit_stmt(ast, body);
+        },
+        StatementKind::DoWhile(body, condition) => {
+            visitor.visit_stmt(ast, body);
+            visitor.visit_expr(ast, condition);
+        },
+        StatementKind::For { ref init, condition, post, body } => {
+            match init.clone() {
+                ForInit::Declaration(declarations) => {
+                    for declaration in declarations {
+                        visitor.visit_declaration(ast, declaration);
+                    }
+                },
+                ForInit::Expression(Some(expression)) => visitor.visit_expr(ast, expression),
+                ForInit::Expression(None) => {},
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expr(ast, condition);
+            }
+            if let Some(post) = post {
+                visitor.visit_expr(ast, post);
+            }
+            visitor.visit_stmt(ast, body);
+        },
+        StatementKind::Labeled(_, statement) | StatementKind::Default(statement) => visitor.visit_stmt(ast, statement),
+        StatementKind::Switch(expression, statement) | StatementKind::Case(expression, statement) => {
+            visitor.visit_expr(ast, expression);
+            visitor.visit_stmt(ast, statement);
+        },
+        StatementKind::Break | StatementKind::Continue | StatementKind::Goto(_) => {},
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, expression: ExprId) {
+    match ast[expression].kind {
+        ExpressionKind::IntLiteral(..)
+        | ExpressionKind::CharConstant(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::Variable(_)
+        | ExpressionKind::SizeOf(_) => {},
+        ExpressionKind::Unary(_, operand) => visitor.visit_expr(ast, operand),
+        ExpressionKind::Binary(_, lhs, rhs)
+        | ExpressionKind::Assign(_, lhs, rhs)
+        | ExpressionKind::Subscript(lhs, rhs) => {
+            visitor.visit_expr(ast, lhs);
+            visitor.visit_expr(ast, rhs);
+        },
+        ExpressionKind::Conditional(condition, then, otherwise) => {
+            visitor.visit_expr(ast, condition);
+            visitor.visit_expr(ast, then);
+            visitor.visit_expr(ast, otherwise);
+        },
+        ExpressionKind::Call(_, ref expressions) | ExpressionKind::Comma(ref expressions) => {
+            for expression in expressions.clone() {
+                visitor.visit_expr(ast, expression);
+            }
+        },
+        ExpressionKind::Cast { expr, .. } => visitor.visit_expr(ast, expr),
+        ExpressionKind::Member(object, _) | ExpressionKind::PointerMember(object, _) => visitor.visit_expr(ast, object),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn parse(source: &str) -> Program {
+        parse_program(&lex_str(source).unwrap()).unwrap()
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        functions: usize,
+        declarations: usize,
+        statements: usize,
+        expressions: usize,
+        literals: usize,
+    }
+
+    impl Visitor for Counter {
+        fn visit_function(&mut self, ast: &Ast, function: &Function) {
+            self.functions += 1;
+            walk_function(self, ast, function);
+        }
+
+        fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+            self.declarations += 1;
+            walk_declaration(self, ast, declaration);
+        }
+
+        fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+            self.statements += 1;
+            walk_stmt(self, ast, statement);
+        }
+
+        fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+            self.expressions += 1;
+            if let ExpressionKind::IntLiteral(..) = ast[expression].kind {
+                self.literals += 1;
+            }
+            walk_expr(self, ast, expression);
+        }
+    }
+
+    #[test]
+    fn counting_visitor() {
+        let program = parse("
+            int limit = 10;
+            int f(int x) { return x * 2; }
+            int main() {
+                int total = 0;
+                for (int i = 0; i < limit; i++) {
+                    if (i % 3) continue;
+                    total += f(i);
+                }
+                return total;
+            }
+        ");
+
+        let mut counter = Counter::default();
+        counter.visit_program(&program);
+        assert_eq!(counter.functions, 2);
+        assert_eq!(counter.declarations, 3);
+        // return, for, the loop's block, if, continue, +=, return
+        assert_eq!(counter.statements, 7);
+        assert_eq!(counter.literals, 5);
+        assert_eq!(counter.expressions, 19);
+
+        // Every node in the arenas is reachable from the items.
+        assert_eq!(program.ast.declaration_count(), counter.declarations);
+        assert_eq!(program.ast.statement_count(), counter.statements);
+        assert_eq!(program.ast.expression_count(), counter.expressions);
+    }
+
+    /// Doubles every integer literal.
+    struct Doubler;
+
+    impl VisitorMut for Doubler {
+        fn visit_expr(&mut self, ast: &mut Ast, expression: ExprId) {
+            if let ExpressionKind::IntLiteral(ref mut value, _) = ast[expression].kind {
+                *value *= 2;
+            }
+            walk_expr_mut(self, ast, expression);
+        }
+    }
+
+    #[test]
+    fn mutating_visitor() {
+        let mut program = parse("int a[2] = {1, 2}; int main() { int b = 3; while (b < 4) { b = b + 5; } return (b, 6); }");
+        Doubler.visit_program(&mut program);
+        assert_eq!(program, parse("int a[2] = {2, 4}; int main() { int b = 6; while (b < 8) { b = b + 10; } return (b, 12); }"));
+    }
+
+    /// Records how deeply each expression is nested within the outermost
+    /// expression of its statement.
+    #[derive(Default)]
+    struct Depths {
+        depth: usize,
+        depths: HashMap<ExprId, usize>,
+    }
+
+    impl Visitor for Depths {
+        fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+            let depth = self.depth;
+            self.depth = 0;
+            walk_stmt(self, ast, statement);
+            self.depth = depth;
+        }
+
+        fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+            self.depths.insert(expression, self.depth);
+            self.depth += 1;
+            walk_expr(self, ast, expression);
+            self.depth -= 1;
+        }
+    }
+
+    #[test]
+    fn side_table() {
+        let program = parse("int main() { int x = 1; return x * (2 + f(x)); }");
+        let mut depths = Depths::default();
+        depths.visit_program(&program);
+
+        let ast = &program.ast;
+        let body = match program.items[0] {
+            TopLevel::Function(Function { body: Some(ref body), .. }) => body,
+            _ => panic!("expected a function definition"),
+        };
+        let product = match body[1] {
+            BlockItem::Statement(statement) => match ast[statement].kind {
+                StatementKind::Return(value) => value,
+                _ => panic!("expected a return"),
+            },
+            _ => panic!("expected a statement"),
+        };
+        let (variable, sum) = match ast[product].kind {
+            ExpressionKind::Binary(BinaryOp::Multiply, lhs, rhs) => (lhs, rhs),
+            _ => panic!("expected a multiplication"),
+        };
+        let argument = match ast[sum].kind {
+            ExpressionKind::Binary(BinaryOp::Add, _, call) => match ast[call].kind {
+                ExpressionKind::Call(_, ref arguments) => arguments[0],
+                _ => panic!("expected a call"),
+            },
+            _ => panic!("expected an addition"),
+        };
+
+        assert_eq!(depths.depths[&product], 0);
+        assert_eq!(depths.depths[&variable], 1);
+        assert_eq!(depths.depths[&sum], 1);
+        assert_eq!(depths.depths[&argument], 3);
+
+        // Ids are dense, so the same table fits in a `Vec` indexed by id.
+        let mut dense = vec![None; ast.expression_count()];
+        for (&expression, &depth) in &depths.depths {
+            dense[expression.index()] = Some(depth);
+        }
+        assert!(dense.iter().all(Option::is_some));
+        assert_eq!(dense[argument.index()], Some(3));
+    }
+}
@@ -0,0 +1,250 @@
+//! The compiler, for C, and for anything that can call C, such as Python's
+//! `ctypes`. `include/rcc.h` declares what is here.
+//!
+//! `rcc_compile` fills in an `RccResult` whatever it returns, which is then
+//! the caller's to free with `rcc_result_free`. No panic unwinds out of
+//! either: each is caught, and reported as `RCC_PANIC`, with what it said as
+//! the one diagnostic.
+
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use codegen::{OptLevel, TargetSpec};
+use compile::{compile_str, CompileOptions, Emit};
+use diagnostics::{self, Diagnostic, SourceMap};
+use standard::Standard;
+
+/// The program compiled.
+pub const RCC_OK: c_int = 0;
+/// The program has errors, which are the result's diagnostics.
+pub const RCC_COMPILE_ERROR: c_int = 1;
+/// An argument is null where it can't be or isn't UTF-8, or an option names
+/// no target, kind of output, optimization level or standard.
+pub const RCC_INVALID_ARGUMENT: c_int = 2;
+/// The compiler panicked, which is a bug in it.
+pub const RCC_PANIC: c_int = 3;
+
+/// How to compile a program. Any of the strings can be null, for the default.
+#[repr(C)]
+pub struct RccOptions {
+    /// The target, as `--target` names it, by default the host.
+    pub target: *const c_char,
+    /// What to compile it to, as `--emit` names it, by default `asm`.
+    pub emit: *const c_char,
+    /// The standard, as `--std=` names it, by default `c11`.
+    pub standard: *const c_char,
+    /// What to call the source in diagnostics, by default `<source>`.
+    pub file_name: *const c_char,
+    /// 0, 1 or 2, as with `-O`.
+    pub opt_level: c_int,
+}
+
+/// What compiling a program made.
+#[repr(C)]
+pub struct RccResult {
+    /// What it was compiled to, or null if it wasn't.
+    pub output: *mut c_char,
+    /// The errors and warnings, each as `--error-format=short` writes it.
+    pub diagnostics: *mut *mut c_char,
+    pub diagnostic_count: usize,
+}
+
+/// Compiles `source` as `options` say, or with the defaults if it is null,
+/// into `out`.
+///
+/// # Safety
+///
+/// `source` and each string in `options` must be null or point to a
+/// NUL-terminated string, `options` must be null or point to an
+/// `RccOptions`, and `out` must point to memory an `RccResult` can be
+/// written to. If it is null, `RCC_INVALID_ARGUMENT` is returned.
+#[no_mangle]
+pub unsafe extern "C" fn rcc_compile(source: *const c_char, options: *const RccOptions, out: *mut RccResult) -> c_int {
+    if out.is_null() {
+        return RCC_INVALID_ARGUMENT;
+    }
+    let (status, output, found) = match panic::catch_unwind(AssertUnwindSafe(|| compile(source, options))) {
+        Ok(compiled) => compiled,
+        Err(payload) => (RCC_PANIC, None, vec![format!("error: the compiler panicked: {}", panic_message(&*payload))]),
+    };
+    let mut found: Vec<*mut c_char> = found.into_iter().map(c_string).collect();
+    found.shrink_to_fit();
+    let result = RccResult {
+        output: output.map_or(ptr::null_mut(), c_string),
+        diagnostic_count: found.len(),
+        diagnostics: Box::into_raw(found.into_boxed_slice()) as *mut *mut c_char,
+    };
+    ptr::write(out, result);
+    status
+}
+
+/// Frees what `rcc_compile` wrote to `result`, and leaves it empty, so that
+/// freeing it again does nothing.
+///
+/// # Safety
+///
+/// `result` must be null or point to an `RccResult` that `rcc_compile`
+/// wrote, or that this emptied.
+#[no_mangle]
+pub unsafe extern "C" fn rcc_result_free(result: *mut RccResult) {
+    if result.is_null() {
+        return;
+    }
+    // Only freeing memory, which doesn't panic, happens here, but nothing is
+    // to unwind into C if it does.
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let result = &mut *result;
+        if !result.output.is_null() {
+            drop(CString::from_raw(result.output));
+        }
+        if !result.diagnostics.is_null() {
+            let found = Box::from_raw(ptr::slice_from_raw_parts_mut(result.diagnostics, result.diagnostic_count));
+            for &diagnostic in found.iter() {
+                drop(CString::from_raw(diagnostic));
+            }
+        }
+        *result = RccResult { output: ptr::null_mut(), diagnostics: ptr::null_mut(), diagnostic_count: 0 };
+    }));
+}
+
+/// The status `rcc_compile` returns, what it compiled `source` to, if
+/// anything, and the diagnostics.
+unsafe fn compile(source: *const c_char, options: *const RccOptions) -> (c_int, Option<String>, Vec<String>) {
+    let invalid = |message: String| (RCC_INVALID_ARGUMENT, None, vec![format!("error: {}", message)]);
+    let source = match string(source, "the source") {
+        Ok(Some(source)) => source,
+        Ok(None) => return invalid("the source is null".to_owned()),
+        Err(message) => return invalid(message),
+    };
+    let options = match options.as_ref().map_or(Ok(CompileOptions::default()), |options| compile_options(options)) {
+        Ok(options) => options,
+        Err(message) => return invalid(message),
+    };
+    let map = SourceMap::new(options.file_name.as_ref().map_or("<source>", String::as_str), source);
+    let render = |found: &[Diagnostic]| found.iter().map(|diagnostic| diagnostics::render_short(&map, diagnostic, false)).collect();
+    match compile_str(source, &options) {
+        Ok(output) => (RCC_OK, Some(output.output), render(&output.warnings)),
+        Err(err) => (RCC_COMPILE_ERROR, None, render(&err.diagnostics)),
+    }
+}
+
+/// What `options` says to compile with, or what is wrong with it.
+unsafe fn compile_options(options: &RccOptions) -> Result<CompileOptions, String> {
+    let mut compile_options = CompileOptions::default();
+    if let Some(name) = string(options.target, "the target")? {
+        compile_options.target = TargetSpec::from_name(name).ok_or_else(|| format!("unknown target `{}`", name))?;
+    }
+    if let Some(name) = string(options.emit, "the kind of output")? {
+        compile_options.emit = Emit::from_name(name).ok_or_else(|| format!("unknown kind of output `{}`", name))?;
+    }
+    if let Some(name) = string(options.standard, "the standard")? {
+        compile_options.standard = Standard::from_name(name).ok_or_else(|| format!("unknown standard `{}`", name))?;
+    }
+    compile_options.file_name = string(options.file_name, "the file name")?.map(str::to_owned);
+    compile_options.opt_level = match options.opt_level {
+        0 => OptLevel::O0,
+        1 => OptLevel::O1,
+        2 => OptLevel::O2,
+        level => return Err(format!("unknown optimization level {}", level)),
+    };
+    Ok(compile_options)
+}
+
+/// The string at `pointer`, unless it is null, or that `what` isn't UTF-8.
+unsafe fn string<'a>(pointer: *const c_char, what: &str) -> Result<Option<&'a str>, String> {
+    if pointer.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(pointer).to_str().map(Some).map_err(|_| format!("{} isn't UTF-8", what))
+}
+
+/// `text`, for C to read and `rcc_result_free` to free, with any NUL in it
+/// escaped, since C would take it for the end of the string.
+fn c_string(text: String) -> *mut c_char {
+    let text = if text.contains('\0') { text.replace('\0', "\\0") } else { text };
+    CString::new(text).unwrap().into_raw()
+}
+
+/// What a panic said, if it said it with a string, as `panic!` does.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload.downcast_ref::<String>().map_or("no message", String::as_str),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::slice;
+
+    /// Compiles `source` with `options`, returning the status, the output and
+    /// the diagnostics, and frees the result.
+    fn compile_with(source: &[u8], options: Option<&RccOptions>) -> (c_int, Option<String>, Vec<String>) {
+        let source = CString::new(source).unwrap();
+        let mut result = RccResult { output: ptr::null_mut(), diagnostics: ptr::null_mut(), diagnostic_count: 0 };
+        unsafe {
+            let status = rcc_compile(source.as_ptr(), options.map_or(ptr::null(), |options| options as *const _), &mut result);
+            let output = result.output.as_ref().map(|output| CStr::from_ptr(output).to_str().unwrap().to_owned());
+            let found = slice::from_raw_parts(result.diagnostics, result.diagnostic_count).iter()
+                .map(|&diagnostic| CStr::from_ptr(diagnostic).to_str().unwrap().to_owned())
+                .collect();
+            rcc_result_free(&mut result);
+            assert!(result.output.is_null() && result.diagnostics.is_null());
+            rcc_result_free(&mut result);
+            (status, output, found)
+        }
+    }
+
+    #[test]
+    fn programs_are_compiled_with_their_diagnostics() {
+        let (status, output, found) = compile_with(b"int main() { int unused; return 0; }", None);
+        assert_eq!(status, RCC_OK);
+        assert!(output.unwrap().contains("main"));
+        assert_eq!(found, vec!["<source>:1:18: warning[W0002]: unused variable `unused` [-Wunused-variable]"]);
+
+        let name = CString::new("main.c").unwrap();
+        let options = RccOptions { target: ptr::null(), emit: ptr::null(), standard: ptr::null(), file_name: name.as_ptr(), opt_level: 2 };
+        let (status, output, found) = compile_with(b"int main() { return y; }", Some(&options));
+        assert_eq!(status, RCC_COMPILE_ERROR);
+        assert_eq!(output, None);
+        assert_eq!(found, vec!["main.c:1:21: error[E0102]: use of undeclared identifier `y`"]);
+    }
+
+    #[test]
+    fn bad_arguments_are_reported() {
+        let emit = CString::new("binary").unwrap();
+        let options = RccOptions { target: ptr::null(), emit: emit.as_ptr(), standard: ptr::null(), file_name: ptr::null(), opt_level: 0 };
+        assert_eq!(compile_with(b"int main() { return 0; }", Some(&options)).2, vec!["error: unknown kind of output `binary`"]);
+        assert_eq!(compile_with(b"int main() { return 0; } \xff", None).2, vec!["error: the source isn't UTF-8"]);
+        let options = RccOptions { emit: ptr::null(), opt_level: 3, ..options };
+        assert_eq!(compile_with(b"int main() { return 0; }", Some(&options)).0, RCC_INVALID_ARGUMENT);
+        unsafe {
+            let mut result = RccResult { output: ptr::null_mut(), diagnostics: ptr::null_mut(), diagnostic_count: 0 };
+            assert_eq!(rcc_compile(ptr::null(), ptr::null(), &mut result), RCC_INVALID_ARGUMENT);
+            assert_eq!(result.diagnostic_count, 1);
+            rcc_result_free(&mut result);
+            assert_eq!(rcc_compile(ptr::null(), ptr::null(), ptr::null_mut()), RCC_INVALID_ARGUMENT);
+            rcc_result_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn the_header_declares_everything() {
+        let header = include_str!("../include/rcc.h");
+        for declaration in &[
+            "#define RCC_OK 0",
+            "#define RCC_COMPILE_ERROR 1",
+            "#define RCC_INVALID_ARGUMENT 2",
+            "#define RCC_PANIC 3",
+            "typedef struct RccOptions {",
+            "typedef struct RccResult {",
+            "int rcc_compile(const char *source, const RccOptions *options, RccResult *out);",
+            "void rcc_result_free(RccResult *result);",
+        ] {
+            assert!(header.contains(declaration), "`include/rcc.h` doesn't declare `{}`", declaration);
+        }
+    }
+}
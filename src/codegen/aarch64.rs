@@ -0,0 +1,771 @@
+//! The AArch64 backend, for the AAPCS64 procedure call standard and Apple's
+//! variant of it.
+//!
+//! The accumulator is `w0`, or all of `x0` for a 64-bit value, and the scratch
+//! register is `w1` or `x1`. Temporaries are kept in `x11` to `x15`, which a
+//! call may change, or in `x19` to `x28`, which it preserves. The frame pointer
+//! is `x29`, and an lvalue whose address was saved is restored to `x2` to be
+//! stored through. `x9` and `x10` hold values and addresses that an instruction
+//! can't take as an immediate or an offset.
+//!
+//! The first eight arguments are passed in `x0` to `x7`, and the rest on the
+//! stack, which the caller reserves just before the call and removes after it.
+//! The stack pointer must stay a multiple of 16 whenever it is used, so that
+//! space is rounded up to one. Linux gives each argument 8 bytes, while macOS
+//! packs them to their natural alignment and passes every variadic argument on
+//! the stack, in 8 bytes, however many registers are left.
+//!
+//! `sdiv` and `udiv` never trap: dividing by zero gives zero, and dividing the
+//! most negative value by -1 gives it back.
+
+use ast::{BinaryOp, UnaryOp};
+use super::{position_directive, round_up, Assembly, Condition, Home, Location, Machine, Parameters, Registers, Source, TargetOs};
+
+/// How many integer arguments are passed in registers, in `x0` to `x7`.
+const ARGUMENT_REGISTERS: usize = 8;
+
+/// The registers temporaries are kept in: first the ones a call may change,
+/// then the ones it preserves. `x18` is left alone, since macOS reserves it.
+const TEMPORARIES: [u8; 15] = [11, 12, 13, 14, 15, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28];
+
+/// How many of them a call may change.
+const CALLER_SAVED: usize = 5;
+
+/// The name of general-purpose register `number` at `size`: a `w` register
+/// for up to 32 bits, and an `x` one for 64.
+fn register(number: u8, size: i64) -> String {
+    match size {
+        8 => format!("x{}", number),
+        _ => format!("w{}", number),
+    }
+}
+
+/// The condition code of the conditional instructions that test `condition`.
+fn condition_code(condition: Condition) -> &'static str {
+    match condition {
+        Condition::Equal => "eq",
+        Condition::NotEqual => "ne",
+        Condition::Less => "lt",
+        Condition::LessEqual => "le",
+        Condition::Greater => "gt",
+        Condition::GreaterEqual => "ge",
+        Condition::Below => "lo",
+        Condition::BelowEqual => "ls",
+        Condition::Above => "hi",
+        Condition::AboveEqual => "hs",
+    }
+}
+
+/// `value` as the operand of an instruction of `size`: a 32-bit instruction
+/// only sees the low 32 bits, so an `unsigned` value is the `int` with the same
+/// bits.
+fn truncate(value: i64, size: i64) -> i64 {
+    match size {
+        8 => value,
+        _ => i64::from(value as i32),
+    }
+}
+
+/// The suffix of a load or store of `size` bytes.
+fn suffix(size: i64, signed: bool) -> &'static str {
+    match size {
+        1 if signed => "sb",
+        1 => "b",
+        _ => "",
+    }
+}
+
+pub struct Aarch64 {
+    assembly: Assembly,
+    /// The preserved registers the function being generated saved, and where.
+    saved: Vec<(usize, i64)>,
+}
+
+impl Aarch64 {
+    pub fn new(assembly: Assembly) -> Aarch64 {
+        Aarch64 { assembly, saved: Vec::new() }
+    }
+
+    /// Writes an instruction on a line of its own.
+    fn emit(&mut self, instruction: &str) {
+        self.assembly.directive(instruction);
+    }
+
+    /// Loads `value` into `destination`, a `w` or `x` register of `size`,
+    /// with `mov` if it or its complement fits in 16 bits. Otherwise the
+    /// register starts as zeros with `movz`, or as ones with `movn` if more of
+    /// its 16-bit chunks are all ones, and `movk` fills in the other chunks.
+    fn move_constant(&mut self, destination: &str, value: i64, size: i64) {
+        let bits = if size == 8 { value as u64 } else { u64::from(value as u32) };
+        let width = if size == 8 { 64 } else { 32 };
+        let inverted = !bits & (u64::MAX >> (64 - width));
+        if bits <= 0xffff || inverted <= 0xffff {
+            return self.emit(&format!("mov {}, #{}", destination, truncate(value, size)));
+        }
+        let chunks: Vec<_> = (0..width).step_by(16).map(|shift| (shift, (bits >> shift) & 0xffff)).collect();
+        let ones = chunks.iter().filter(|&&(_, chunk)| chunk == 0xffff).count();
+        let zeros = chunks.iter().filter(|&&(_, chunk)| chunk == 0).count();
+        let fill = if ones > zeros { 0xffff } else { 0 };
+        let mut first = true;
+        for (shift, chunk) in chunks {
+            if chunk == fill {
+                continue;
+            }
+            if !first {
+                self.emit(&format!("movk {}, #{}, lsl #{}", destination, chunk, shift));
+            } else if fill == 0 {
+                self.emit(&format!("movz {}, #{}, lsl #{}", destination, chunk, shift));
+            } else {
+                self.emit(&format!("movn {}, #{}, lsl #{}", destination, !chunk & 0xffff, shift));
+            }
+            first = false;
+        }
+    }
+
+    /// Sets `destination` to `source` plus `value`, with an immediate if it
+    /// fits in 12 bits either way. Otherwise `value` is loaded into
+    /// `destination` first, or into `x9` if that is `source` or `sp`.
+    fn add_constant(&mut self, destination: &str, source: &str, value: i64, size: i64) {
+        let value = truncate(value, size);
+        if (0..4096).contains(&value) {
+            self.emit(&format!("add {}, {}, #{}", destination, source, value));
+        } else if (-4095..0).contains(&value) {
+            self.emit(&format!("sub {}, {}, #{}", destination, source, -value));
+        } else {
+            let scratch = if destination == source || destination == "sp" { register(9, size) } else { destination.to_owned() };
+            self.move_constant(&scratch, value, size);
+            self.emit(&format!("add {}, {}, {}", destination, source, scratch));
+        }
+    }
+
+    /// Loads the address of `label` into `destination`, from its 4KB page and
+    /// its offset within it.
+    fn page_address(&mut self, destination: &str, label: &str) {
+        let (page, offset) = match self.assembly.target_os {
+            TargetOs::Linux | TargetOs::Windows => (label.to_owned(), format!(":lo12:{}", label)),
+            TargetOs::MacOs => (format!("{}@PAGE", label), format!("{}@PAGEOFF", label)),
+        };
+        self.emit(&format!("adrp {}, {}", destination, page));
+        self.emit(&format!("add {}, {}, {}", destination, destination, offset));
+    }
+
+    /// The memory operand that accesses `size` bytes at `location`, and whether
+    /// it needs the unscaled forms of the loads and stores, `ldur` and `stur`.
+    /// An address that can't be written as one is computed into `x10` first.
+    fn memory(&mut self, location: &Location, size: i64) -> (String, bool) {
+        match *location {
+            Location::Frame(offset) if (-256..256).contains(&offset) => (format!("[x29, #{}]", offset), true),
+            Location::Frame(offset) if offset >= 0 && offset % size == 0 && offset / size < 4096 => {
+                (format!("[x29, #{}]", offset), false)
+            },
+            Location::Frame(offset) => {
+                self.add_constant("x10", "x29", offset, 8);
+                ("[x10]".to_owned(), false)
+            },
+            Location::Label(ref label) => {
+                self.page_address("x10", label);
+                ("[x10]".to_owned(), false)
+            },
+            Location::Accumulator => ("[x0]".to_owned(), false),
+            Location::Restored => ("[x2]".to_owned(), false),
+        }
+    }
+
+    /// Loads `size` bytes from `location` into `destination`, extending a
+    /// `char` by its sign if it is `signed`.
+    fn load_register(&mut self, destination: &str, location: &Location, size: i64, signed: bool) {
+        let (memory, unscaled) = self.memory(location, size);
+        let instruction = if unscaled { "ldur" } else { "ldr" };
+        self.emit(&format!("{}{} {}, {}", instruction, suffix(size, signed), destination, memory));
+    }
+
+    /// Copies the temporary in `home` to the `x` register `destination`.
+    fn restore_to(&mut self, destination: &str, home: Home) {
+        match home {
+            Home::Register(index) => self.emit(&format!("mov {}, x{}", destination, TEMPORARIES[index])),
+            Home::Slot(offset) => self.load_register(destination, &Location::Frame(offset), 8, false),
+        }
+    }
+
+    /// Stores the low `size` bytes of `source` to `location`.
+    fn store_register(&mut self, source: &str, location: &Location, size: i64) {
+        let (memory, unscaled) = self.memory(location, size);
+        let instruction = if unscaled { "stur" } else { "str" };
+        self.emit(&format!("{}{} {}, {}", instruction, suffix(size, false), source, memory));
+    }
+
+    /// Where the arguments of `sizes` that are passed on the stack go, as
+    /// offsets from the first one, and how many bytes each takes there. The
+    /// ones from `variadic` on are variadic arguments.
+    fn stack_slots(&self, sizes: &[i64], variadic: usize) -> Vec<(i64, i64)> {
+        let mut offset = 0;
+        let mut slots = Vec::new();
+        for (index, &size) in sizes.iter().enumerate() {
+            let width = match self.assembly.target_os {
+                TargetOs::MacOs if index < variadic => size,
+                _ => 8,
+            };
+            offset = round_up(offset, width);
+            slots.push((offset, width));
+            offset += width;
+        }
+        slots
+    }
+}
+
+impl Machine for Aarch64 {
+    fn assembly(&mut self) -> &mut Assembly {
+        &mut self.assembly
+    }
+
+    fn argument_registers(&self) -> usize {
+        ARGUMENT_REGISTERS
+    }
+
+    fn stack_arguments(&self, sizes: &[i64]) -> Vec<i64> {
+        self.stack_slots(sizes, sizes.len()).into_iter().map(|(offset, _)| offset).collect()
+    }
+
+    fn registers(&self) -> Registers {
+        Registers { caller_saved: CALLER_SAVED, callee_saved: TEMPORARIES.len() - CALLER_SAVED }
+    }
+
+    fn prologue(&mut self, frame_size: i64, saved: &[(usize, i64)]) {
+        self.emit("stp x29, x30, [sp, #-16]!");
+        self.emit("mov x29, sp");
+        if frame_size > 0 {
+            self.add_constant("sp", "sp", -frame_size, 8);
+        }
+        self.saved = saved.to_vec();
+        for &(index, offset) in saved {
+            self.store_register(&format!("x{}", TEMPORARIES[index]), &Location::Frame(offset), 8);
+        }
+    }
+
+    fn epilogue(&mut self) {
+        for (index, offset) in self.saved.clone() {
+            self.load_register(&format!("x{}", TEMPORARIES[index]), &Location::Frame(offset), 8, false);
+        }
+        self.emit("mov sp, x29");
+        self.emit("ldp x29, x30, [sp], #16");
+        self.emit("ret");
+    }
+
+    fn spill(&mut self, index: usize, size: i64, offset: i64) {
+        self.store_register(&register(index as u8, size), &Location::Frame(offset), size);
+    }
+
+    fn constant(&mut self, value: i64, size: i64) {
+        self.move_constant(&register(0, size), value, size);
+    }
+
+    fn scratch_constant(&mut self, value: i64, size: i64) {
+        self.move_constant(&register(1, size), value, size);
+    }
+
+    fn load(&mut self, location: &Location, size: i64, signed: bool) {
+        self.load_register(&register(0, size), location, size, signed);
+    }
+
+    fn store(&mut self, location: &Location, size: i64) {
+        self.store_register(&register(0, size), location, size);
+    }
+
+    fn store_constant(&mut self, offset: i64, size: i64, value: i64) {
+        let source = if value == 0 {
+            if size == 8 { "xzr".to_owned() } else { "wzr".to_owned() }
+        } else {
+            let scratch = register(9, size);
+            self.move_constant(&scratch, value, size);
+            scratch
+        };
+        self.store_register(&source, &Location::Frame(offset), size);
+    }
+
+    fn address(&mut self, location: &Location) {
+        match *location {
+            Location::Frame(offset) => self.add_constant("x0", "x29", offset, 8),
+            Location::Label(ref label) => self.page_address("x0", label),
+            Location::Accumulator => {},
+            Location::Restored => self.emit("mov x0, x2"),
+        }
+    }
+
+    fn save(&mut self, home: Home) {
+        match home {
+            Home::Register(index) => self.emit(&format!("mov x{}, x0", TEMPORARIES[index])),
+            Home::Slot(offset) => self.store_register("x0", &Location::Frame(offset), 8),
+        }
+    }
+
+    fn restore(&mut self, home: Home) {
+        self.restore_to("x0", home);
+    }
+
+    fn restore_address(&mut self, home: Home) {
+        self.restore_to("x2", home);
+    }
+
+    fn move_to_scratch(&mut self, size: i64) {
+        self.emit(&format!("mov {}, {}", register(1, size), register(0, size)));
+    }
+
+    fn restore_from_scratch(&mut self, size: i64) {
+        self.emit(&format!("mov {}, {}", register(0, size), register(1, size)));
+    }
+
+    fn binary(&mut self, operator: BinaryOp, size: i64, signed: bool, source: Source) {
+        let accumulator = register(0, size);
+        let instruction = match operator {
+            BinaryOp::Add => "add",
+            BinaryOp::Subtract => "sub",
+            BinaryOp::Multiply => "mul",
+            BinaryOp::ShiftLeft => "lsl",
+            BinaryOp::ShiftRight if signed => "asr",
+            BinaryOp::ShiftRight => "lsr",
+            BinaryOp::BitwiseAnd => "and",
+            BinaryOp::BitwiseXor => "eor",
+            BinaryOp::BitwiseOr => "orr",
+            operator => unreachable!("binary `{}` as one instruction", operator.symbol()),
+        };
+        let source = match (operator, source) {
+            (_, Source::Scratch) => register(1, size),
+            (BinaryOp::Add, Source::Immediate(value)) => return self.add_constant(&accumulator, &accumulator, value, size),
+            (BinaryOp::Subtract, Source::Immediate(value)) => {
+                return self.add_constant(&accumulator, &accumulator, -truncate(value, size), size);
+            },
+            // `mul` takes no immediate, and a bitwise instruction only takes
+            // some patterns of bits, so the constant goes in a register.
+            (BinaryOp::Multiply, Source::Immediate(value))
+            | (BinaryOp::BitwiseAnd, Source::Immediate(value))
+            | (BinaryOp::BitwiseXor, Source::Immediate(value))
+            | (BinaryOp::BitwiseOr, Source::Immediate(value)) => {
+                let scratch = register(9, size);
+                self.move_constant(&scratch, value, size);
+                scratch
+            },
+            (_, Source::Immediate(amount)) => format!("#{}", amount),
+        };
+        self.emit(&format!("{} {}, {}, {}", instruction, accumulator, accumulator, source));
+    }
+
+    fn divide(&mut self, size: i64, signed: bool, remainder: bool, zero: Option<&str>) {
+        let (accumulator, scratch) = (register(0, size), register(1, size));
+        if let Some(zero) = zero {
+            self.emit(&format!("cbz {}, {}", scratch, zero));
+        }
+        let instruction = if signed { "sdiv" } else { "udiv" };
+        if remainder {
+            // There is no remainder instruction: it is the dividend less the
+            // quotient times the divisor.
+            let quotient = register(9, size);
+            self.emit(&format!("{} {}, {}, {}", instruction, quotient, accumulator, scratch));
+            self.emit(&format!("msub {}, {}, {}, {}", accumulator, quotient, scratch, accumulator));
+        } else {
+            self.emit(&format!("{} {}, {}, {}", instruction, accumulator, accumulator, scratch));
+        }
+    }
+
+    fn divide_by_power(&mut self, size: i64, signed: bool, remainder: bool, shift: i64) {
+        let (accumulator, scratch) = (register(0, size), register(9, size));
+        let bits = size * 8;
+        if !signed {
+            if remainder {
+                self.emit(&format!("ubfx {}, {}, #0, #{}", accumulator, accumulator, shift));
+            } else {
+                self.emit(&format!("lsr {}, {}, #{}", accumulator, accumulator, shift));
+            }
+            return;
+        }
+        // Shifting rounds toward negative infinity and division toward zero, so
+        // a negative dividend is biased by one less than the divisor first.
+        self.emit(&format!("asr {}, {}, #{}", scratch, accumulator, bits - 1));
+        if remainder {
+            // The remainder is what rounding the biased dividend down to a
+            // multiple of the divisor takes away from the dividend.
+            self.emit(&format!("add {}, {}, {}, lsr #{}", scratch, accumulator, scratch, bits - shift));
+            self.emit(&format!("asr {}, {}, #{}", scratch, scratch, shift));
+            self.emit(&format!("sub {}, {}, {}, lsl #{}", accumulator, accumulator, scratch, shift));
+        } else {
+            self.emit(&format!("add {}, {}, {}, lsr #{}", accumulator, accumulator, scratch, bits - shift));
+            self.emit(&format!("asr {}, {}, #{}", accumulator, accumulator, shift));
+        }
+    }
+
+    fn compare(&mut self, size: i64, condition: Condition) {
+        self.emit(&format!("cmp {}, {}", register(0, size), register(1, size)));
+        self.set(condition);
+    }
+
+    fn test(&mut self, size: i64) {
+        self.emit(&format!("cmp {}, #0", register(0, size)));
+    }
+
+    fn compare_constant(&mut self, size: i64, value: i64) {
+        let accumulator = register(0, size);
+        let value = truncate(value, size);
+        if (0..4096).contains(&value) {
+            self.emit(&format!("cmp {}, #{}", accumulator, value));
+        } else if (-4095..0).contains(&value) {
+            self.emit(&format!("cmn {}, #{}", accumulator, -value));
+        } else {
+            let scratch = register(9, size);
+            self.move_constant(&scratch, value, size);
+            self.emit(&format!("cmp {}, {}", accumulator, scratch));
+        }
+    }
+
+    fn set(&mut self, condition: Condition) {
+        self.emit(&format!("cset w0, {}", condition_code(condition)));
+    }
+
+    fn unary(&mut self, operator: UnaryOp, size: i64) {
+        let instruction = match operator {
+            UnaryOp::Negate => "neg",
+            UnaryOp::BitwiseNot => "mvn",
+            operator => unreachable!("unary `{}` as one instruction", operator.symbol()),
+        };
+        let accumulator = register(0, size);
+        self.emit(&format!("{} {}, {}", instruction, accumulator, accumulator));
+    }
+
+    fn extend(&mut self, from: i64, to: i64, signed: bool) {
+        let instruction = match (from, signed) {
+            (1, true) => "sxtb",
+            (1, false) => "uxtb",
+            (_, true) => "sxtw",
+            // Writing `w0` clears the upper half of `x0`.
+            (_, false) => return self.emit("mov w0, w0"),
+        };
+        self.emit(&format!("{} {}, w0", instruction, register(0, to)));
+    }
+
+    fn label(&mut self, label: &str) {
+        self.assembly.label(label);
+    }
+
+    fn position(&mut self, line: usize, column: usize) {
+        self.assembly.directive(&position_directive(line, column));
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.assembly.directive(&format!("// {}", text));
+    }
+
+    fn jump(&mut self, condition: Option<Condition>, label: &str) {
+        match condition {
+            Some(condition) => self.emit(&format!("b.{} {}", condition_code(condition), label)),
+            None => self.emit(&format!("b {}", label)),
+        }
+    }
+
+    fn jump_table(&mut self, size: i64, min: i64, max: i64, otherwise: &str, table: &str) {
+        let accumulator = register(0, size);
+        // Anything below `min` wraps around to above `max - min`, so one
+        // unsigned compare catches values on either side of the table.
+        self.add_constant(&accumulator, &accumulator, -truncate(min, size), size);
+        self.compare_constant(size, max - min);
+        self.jump(Some(Condition::Above), otherwise);
+        // A 32-bit `sub` has zeroed the upper half of `x0` already.
+        self.page_address("x9", table);
+        self.emit("ldr x9, [x9, x0, lsl #3]");
+        self.emit("br x9");
+    }
+
+    fn call(&mut self, label: &str, arguments: &[(Home, i64)], parameters: Parameters) {
+        let variadic = match (self.assembly.target_os, parameters) {
+            (TargetOs::MacOs, Parameters::Variadic(fixed)) => fixed,
+            _ => arguments.len(),
+        };
+        let in_registers = arguments.len().min(ARGUMENT_REGISTERS).min(variadic);
+        let on_stack = &arguments[in_registers..];
+        let sizes: Vec<_> = on_stack.iter().map(|&(_, size)| size).collect();
+        let slots = self.stack_slots(&sizes, variadic.saturating_sub(in_registers));
+        let reserved = round_up(slots.last().map_or(0, |&(offset, width)| offset + width), 16);
+        if reserved > 0 {
+            self.add_constant("sp", "sp", -reserved, 8);
+        }
+        for (&(home, _), (offset, width)) in on_stack.iter().zip(slots) {
+            let source = match home {
+                Home::Register(index) => TEMPORARIES[index],
+                Home::Slot(offset) => {
+                    self.load_register("x9", &Location::Frame(offset), 8, false);
+                    9
+                },
+            };
+            self.emit(&format!("str{} {}, [sp, #{}]", suffix(width, false), register(source, width), offset));
+        }
+        for (index, &(home, _)) in arguments[..in_registers].iter().enumerate() {
+            self.restore_to(&format!("x{}", index), home);
+        }
+        self.emit(&format!("bl {}", label));
+        if reserved > 0 {
+            self.add_constant("sp", "sp", reserved, 8);
+        }
+    }
+
+    fn exit(&mut self, message: &str, length: usize, status: i32) {
+        let write = self.assembly.target_os.symbol("write");
+        let exit = self.assembly.target_os.symbol("exit");
+        self.emit("mov w0, #2");
+        self.page_address("x1", message);
+        self.move_constant("w2", length as i64, 4);
+        self.emit(&format!("bl {}", write));
+        self.move_constant("w0", i64::from(status), 4);
+        self.emit(&format!("bl {}", exit));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use codegen::{generate_with, Architecture, DebugInfo, Options, TargetSpec};
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn machine(target_os: TargetOs) -> Aarch64 {
+        Aarch64::new(Assembly { output: String::new(), labels: 0, function: String::new(), target_os })
+    }
+
+    fn lines(machine: &Aarch64) -> Vec<&str> {
+        machine.assembly.output.lines().map(str::trim).collect()
+    }
+
+    #[test]
+    fn constants() {
+        let mut machine = machine(TargetOs::Linux);
+        machine.constant(42, 4);
+        machine.constant(-1, 4);
+        machine.constant(4294967295, 4);
+        machine.constant(70000, 4);
+        machine.constant(-1, 8);
+        machine.scratch_constant(-65536, 8);
+        machine.scratch_constant(1 << 40, 8);
+        assert_eq!(lines(&machine), vec![
+            "mov w0, #42",
+            "mov w0, #-1",
+            "mov w0, #-1",
+            "movz w0, #4464, lsl #0",
+            "movk w0, #1, lsl #16",
+            "mov x0, #-1",
+            "mov x1, #-65536",
+            "movz x1, #256, lsl #32",
+        ]);
+    }
+
+    #[test]
+    fn frame_offsets() {
+        let mut machine = machine(TargetOs::Linux);
+        machine.load(&Location::Frame(-8), 8, true);
+        machine.load(&Location::Frame(-300), 1, true);
+        machine.load(&Location::Frame(400), 4, false);
+        machine.store_constant(-5000, 4, 7);
+        machine.address(&Location::Frame(-70000));
+        assert_eq!(lines(&machine), vec![
+            "ldur x0, [x29, #-8]",
+            "sub x10, x29, #300",
+            "ldrsb w0, [x10]",
+            "ldr w0, [x29, #400]",
+            "mov w9, #7",
+            "mov x10, #-5000",
+            "add x10, x29, x10",
+            "str w9, [x10]",
+            "movn x0, #4463, lsl #0",
+            "movk x0, #65534, lsl #16",
+            "add x0, x29, x0",
+        ]);
+    }
+
+    #[test]
+    fn labels_by_target() {
+        let mut linux = machine(TargetOs::Linux);
+        linux.load(&Location::Label("count".to_owned()), 4, true);
+        assert_eq!(lines(&linux), vec!["adrp x10, count", "add x10, x10, :lo12:count", "ldr w0, [x10]"]);
+        let mut mac = machine(TargetOs::MacOs);
+        mac.address(&Location::Label("_count".to_owned()));
+        assert_eq!(lines(&mac), vec!["adrp x0, _count@PAGE", "add x0, x0, _count@PAGEOFF"]);
+    }
+
+    #[test]
+    fn stack_arguments() {
+        let sizes = [1, 8, 1, 4];
+        assert_eq!(machine(TargetOs::Linux).stack_arguments(&sizes), vec![0, 8, 16, 24]);
+        assert_eq!(machine(TargetOs::MacOs).stack_arguments(&sizes), vec![0, 8, 16, 20]);
+    }
+
+    /// Arguments of `sizes` in the temporary registers in order, except for
+    /// any in `spilled`, which are in slots.
+    fn arguments(sizes: &[i64], spilled: &[usize]) -> Vec<(Home, i64)> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(index, &size)| match spilled.contains(&index) {
+                true => (Home::Slot(-8 * (index as i64 + 1)), size),
+                false => (Home::Register(index), size),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn calls_with_stack_arguments() {
+        let sizes = [8, 4, 4, 4, 4, 4, 4, 4, 1, 4];
+        let mut linux = machine(TargetOs::Linux);
+        linux.call("f", &arguments(&sizes, &[9]), Parameters::Fixed);
+        assert_eq!(lines(&linux)[..4], ["sub sp, sp, #16", "str x22, [sp, #0]", "ldur x9, [x29, #-80]", "str x9, [sp, #8]"]);
+        assert_eq!(lines(&linux)[4..], [
+            "mov x0, x11",
+            "mov x1, x12",
+            "mov x2, x13",
+            "mov x3, x14",
+            "mov x4, x15",
+            "mov x5, x19",
+            "mov x6, x20",
+            "mov x7, x21",
+            "bl f",
+            "add sp, sp, #16",
+        ]);
+        let mut mac = machine(TargetOs::MacOs);
+        mac.call("_f", &arguments(&sizes, &[9]), Parameters::Fixed);
+        assert_eq!(lines(&mac)[..4], ["sub sp, sp, #16", "strb w22, [sp, #0]", "ldur x9, [x29, #-80]", "str w9, [sp, #4]"]);
+    }
+
+    #[test]
+    fn variadic_arguments_on_macos_are_on_the_stack() {
+        let mut mac = machine(TargetOs::MacOs);
+        mac.call("_printf", &arguments(&[8, 1, 4], &[]), Parameters::Variadic(1));
+        assert_eq!(lines(&mac), vec![
+            "sub sp, sp, #16",
+            "str x12, [sp, #0]",
+            "str x13, [sp, #8]",
+            "mov x0, x11",
+            "bl _printf",
+            "add sp, sp, #16",
+        ]);
+        let mut linux = machine(TargetOs::Linux);
+        linux.call("printf", &arguments(&[8, 1, 4], &[]), Parameters::Variadic(1));
+        assert_eq!(lines(&linux), vec!["mov x0, x11", "mov x1, x12", "mov x2, x13", "bl printf"]);
+    }
+
+    #[test]
+    fn preserved_registers_are_saved_in_the_frame() {
+        let mut machine = machine(TargetOs::Linux);
+        machine.prologue(32, &[(5, -24), (6, -32)]);
+        machine.save(Home::Register(6));
+        machine.restore(Home::Slot(-16));
+        machine.epilogue();
+        assert_eq!(lines(&machine), vec![
+            "stp x29, x30, [sp, #-16]!",
+            "mov x29, sp",
+            "sub sp, sp, #32",
+            "stur x19, [x29, #-24]",
+            "stur x20, [x29, #-32]",
+            "mov x20, x0",
+            "ldur x0, [x29, #-16]",
+            "ldur x19, [x29, #-24]",
+            "ldur x20, [x29, #-32]",
+            "mov sp, x29",
+            "ldp x29, x30, [sp], #16",
+            "ret",
+        ]);
+    }
+
+    #[test]
+    fn division() {
+        let mut machine = machine(TargetOs::Linux);
+        machine.divide(4, true, false, Some(".Ldivision_by_zero"));
+        machine.divide(8, false, true, None);
+        assert_eq!(lines(&machine), vec![
+            "cbz w1, .Ldivision_by_zero",
+            "sdiv w0, w0, w1",
+            "udiv x9, x0, x1",
+            "msub x0, x9, x1, x0",
+        ]);
+    }
+
+    #[test]
+    fn division_by_powers_of_two() {
+        let mut machine = machine(TargetOs::Linux);
+        machine.divide_by_power(4, true, false, 2);
+        machine.divide_by_power(8, true, true, 35);
+        machine.divide_by_power(4, false, false, 3);
+        machine.divide_by_power(8, false, true, 63);
+        assert_eq!(lines(&machine), vec![
+            "asr w9, w0, #31",
+            "add w0, w0, w9, lsr #30",
+            "asr w0, w0, #2",
+            "asr x9, x0, #63",
+            "add x9, x0, x9, lsr #29",
+            "asr x9, x9, #35",
+            "sub x0, x0, x9, lsl #35",
+            "lsr w0, w0, #3",
+            "ubfx x0, x0, #0, #63",
+        ]);
+    }
+
+    #[test]
+    fn a_whole_program() {
+        let source = "long total;\nint main(int n) { char c = n; total = c / n; return total < 2; }";
+        let options = Options {
+            target: TargetSpec::new(Architecture::Aarch64, TargetOs::Linux),
+            checked_arithmetic: true,
+            debug_info: DebugInfo::Off,
+            ..Options::default()
+        };
+        assert_eq!(generate_with(&parse_program(&lex_str(source).unwrap()).unwrap(), &options), "    .text
+    .globl main
+main:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    sxtb w0, w0
+    sturb w0, [x29, #-5]
+    ldursb w0, [x29, #-5]
+    mov x11, x0
+    ldur w0, [x29, #-4]
+    mov w1, w0
+    mov x0, x11
+    cbz w1, .Ldivision_by_zero
+    sdiv w0, w0, w1
+    sxtw x0, w0
+    adrp x10, total
+    add x10, x10, :lo12:total
+    str x0, [x10]
+    adrp x10, total
+    add x10, x10, :lo12:total
+    ldr x0, [x10]
+    mov x11, x0
+    mov w0, #2
+    sxtw x0, w0
+    mov x1, x0
+    mov x0, x11
+    cmp x0, x1
+    cset w0, lt
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    mov w0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+.Ldivision_by_zero:
+    mov w0, #2
+    adrp x1, .Lstr0
+    add x1, x1, :lo12:.Lstr0
+    mov w2, #17
+    bl write
+    mov w0, #136
+    bl exit
+    .bss
+    .balign 8
+    .globl total
+total:
+    .zero 8
+    .section .rodata
+.Lstr0:
+    .asciz \"division by zero\\012\"
+    .section .note.GNU-stack,\"\",@progbits
+");
+    }
+}
@@ -0,0 +1,316 @@
+//! Removes the stores to a function's locals whose values are never read: the
+//! ones overwritten before any read, and the ones made when no read is left.
+//!
+//! Only the slots of locals and parameters whose address is never taken are
+//! considered. Nothing but a load from its slot can read one of those, so a
+//! call, which only sees memory through addresses, can't read it either. Each
+//! slot is one object, read and written whole.
+//!
+//! A constant left in the accumulator for a removed store, and replaced before
+//! anything reads it, is removed too.
+//!
+//! Which slots may still be read is worked out backwards over the function's
+//! basic blocks, and around its loops until nothing changes, so a store that a
+//! later iteration reads is kept. Control leaving through a `return` reads no
+//! slot, and leaving through a jump table, or to a label outside the function,
+//! might read any.
+
+use std::collections::{HashMap, HashSet};
+use super::Location;
+use super::ir::Instruction;
+
+/// The slots read at the start of a block, given those read after it.
+type Live = HashSet<i64>;
+
+/// A run of instructions that control only enters at the top and leaves at
+/// the bottom, or through the jump of a checked division.
+struct Block {
+    start: usize,
+    end: usize,
+    successors: Vec<usize>,
+    /// Whether control may go on to code this doesn't know, which may read
+    /// any slot.
+    unknown: bool,
+}
+
+/// The slot that `instruction` stores to, if it is the slot of a local.
+fn stored(instruction: &Instruction) -> Option<i64> {
+    match *instruction {
+        Instruction::Store(Location::Frame(offset), _) | Instruction::StoreConstant(offset, _, _) => Some(offset),
+        _ => None,
+    }
+}
+
+/// Splits `code` into basic blocks.
+fn blocks(code: &[Instruction]) -> Vec<Block> {
+    let mut starts = vec![0];
+    for (index, instruction) in code.iter().enumerate() {
+        match *instruction {
+            Instruction::Label(_) if starts.last() != Some(&index) => starts.push(index),
+            Instruction::Jump(..) | Instruction::JumpTable(..) | Instruction::Return => starts.push(index + 1),
+            _ => {},
+        }
+    }
+    starts.dedup();
+    starts.retain(|&start| start < code.len());
+    let ends: Vec<_> = starts.iter().skip(1).cloned().chain(Some(code.len())).collect();
+    let labels: HashMap<&str, usize> = starts.iter().enumerate()
+        .filter_map(|(block, &start)| match code[start] {
+            Instruction::Label(ref label) => Some((&label[..], block)),
+            _ => None,
+        })
+        .collect();
+    starts.iter().zip(&ends).enumerate().map(|(block, (&start, &end))| {
+        let mut successors = Vec::new();
+        let mut unknown = false;
+        let mut falls_through = true;
+        for instruction in &code[start..end] {
+            match *instruction {
+                // A division by zero jumps out of the function to exit.
+                Instruction::Divide(_, _, _, Some(ref label)) => successors.extend(labels.get(&label[..]).cloned()),
+                Instruction::Jump(condition, ref label) => {
+                    match labels.get(&label[..]) {
+                        Some(&target) => successors.push(target),
+                        None => unknown = true,
+                    }
+                    falls_through = condition.is_some();
+                },
+                Instruction::JumpTable(..) => {
+                    unknown = true;
+                    falls_through = false;
+                },
+                Instruction::Return => falls_through = false,
+                _ => {},
+            }
+        }
+        if falls_through && end < code.len() {
+            successors.push(block + 1);
+        }
+        Block { start, end, successors, unknown }
+    }).collect()
+}
+
+/// Removes the stores in `code` to the slots at `slots` that are never read.
+pub fn eliminate<I: IntoIterator<Item = i64>>(code: &mut Vec<Instruction>, slots: I) {
+    let addressed: HashSet<_> = code.iter()
+        .filter_map(|instruction| match *instruction {
+            Instruction::Address(Location::Frame(offset)) => Some(offset),
+            _ => None,
+        })
+        .collect();
+    let tracked: HashSet<_> = slots.into_iter().filter(|slot| !addressed.contains(slot)).collect();
+    if tracked.is_empty() {
+        return;
+    }
+
+    // Walks `block` backwards from the slots read after it, calling `dead` on
+    // each store to a slot that isn't read, and returns those read before it.
+    let walk = |code: &[Instruction], block: &Block, mut live: Live, dead: &mut dyn FnMut(usize)| -> Live {
+        for index in (block.start..block.end).rev() {
+            match code[index] {
+                Instruction::Load(Location::Frame(offset), _, _) if tracked.contains(&offset) => {
+                    live.insert(offset);
+                },
+                ref instruction => if let Some(offset) = stored(instruction).filter(|offset| tracked.contains(offset)) {
+                    if !live.remove(&offset) {
+                        dead(index);
+                    }
+                },
+            }
+        }
+        live
+    };
+
+    let blocks = blocks(code);
+    let live_out = |live_in: &[Live], block: &Block| -> Live {
+        if block.unknown {
+            return tracked.clone();
+        }
+        block.successors.iter().flat_map(|&successor| live_in[successor].iter().cloned()).collect()
+    };
+    let mut live_in = vec![Live::new(); blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, block) in blocks.iter().enumerate().rev() {
+            let live = walk(code, block, live_out(&live_in, block), &mut |_| {});
+            if live != live_in[index] {
+                live_in[index] = live;
+                changed = true;
+            }
+        }
+    }
+
+    let mut dead = HashSet::new();
+    for block in &blocks {
+        walk(code, block, live_out(&live_in, block), &mut |index| {
+            dead.insert(index);
+        });
+    }
+    remove(code, &dead);
+
+    // The value of a removed store is often a constant made just for it, which
+    // is now replaced before anything reads it.
+    let unread: HashSet<usize> = code.iter().enumerate()
+        .filter(|&(index, instruction)| match *instruction {
+            Instruction::Constant(..) => code[index + 1..].iter()
+                .find(|next| !matches!(**next, Instruction::Position(..) | Instruction::Comment(_)))
+                .is_some_and(replaces_accumulator),
+            _ => false,
+        })
+        .map(|(index, _)| index)
+        .collect();
+    remove(code, &unread);
+}
+
+/// Whether `instruction` replaces the value in the accumulator without reading it.
+fn replaces_accumulator(instruction: &Instruction) -> bool {
+    match *instruction {
+        Instruction::Constant(..) | Instruction::Restore(_) => true,
+        Instruction::Load(ref location, _, _) | Instruction::Address(ref location) => *location != Location::Accumulator,
+        _ => false,
+    }
+}
+
+fn remove(code: &mut Vec<Instruction>, indices: &HashSet<usize>) {
+    let mut index = 0;
+    code.retain(|_| {
+        index += 1;
+        !indices.contains(&(index - 1))
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use codegen::{Condition, Parameters};
+    use codegen::ir::Temp;
+
+    fn store(offset: i64) -> Instruction {
+        Instruction::Store(Location::Frame(offset), 4)
+    }
+
+    fn load(offset: i64) -> Instruction {
+        Instruction::Load(Location::Frame(offset), 4, true)
+    }
+
+    fn label(name: &str) -> Instruction {
+        Instruction::Label(name.to_owned())
+    }
+
+    fn jump(condition: Option<Condition>, name: &str) -> Instruction {
+        Instruction::Jump(condition, name.to_owned())
+    }
+
+    fn check(mut code: Vec<Instruction>, removed: &[usize]) {
+        let expected: Vec<_> = code.iter().enumerate()
+            .filter(|&(index, _)| !removed.contains(&index))
+            .map(|(_, instruction)| instruction.clone())
+            .collect();
+        eliminate(&mut code, vec![-4, -8]);
+        assert_eq!(code, expected);
+    }
+
+    #[test]
+    fn overwritten_stores_are_removed() {
+        check(vec![
+            Instruction::Constant(1, 4),
+            store(-4),
+            Instruction::Constant(2, 4),
+            store(-4),
+            load(-4),
+            Instruction::Return,
+        ], &[0, 1]);
+        // Nothing reads a slot after a return.
+        check(vec![Instruction::Constant(1, 4), store(-4), store(-8), Instruction::Return], &[1, 2]);
+        check(vec![Instruction::StoreConstant(-4, 4, 0), Instruction::StoreConstant(-4, 4, 1), load(-4), Instruction::Return], &[0]);
+    }
+
+    #[test]
+    fn constants_for_removed_stores_are_removed() {
+        check(vec![
+            Instruction::Constant(5, 4),
+            Instruction::Position(1, 25),
+            store(-4),
+            Instruction::Position(1, 25),
+            Instruction::Constant(7, 4),
+            Instruction::Return,
+        ], &[0, 2]);
+        // Unless something still reads it.
+        check(vec![Instruction::Constant(5, 4), store(-4), Instruction::Return], &[1]);
+    }
+
+    #[test]
+    fn calls_only_read_through_addresses() {
+        check(vec![
+            Instruction::Constant(1, 4),
+            store(-4),
+            Instruction::Save(Temp(0)),
+            Instruction::Call("f".to_owned(), vec![(Temp(0), 4)], Parameters::Fixed),
+            store(-4),
+            load(-4),
+            Instruction::Return,
+        ], &[1]);
+        let code = vec![
+            Instruction::Constant(1, 4),
+            store(-4),
+            Instruction::Address(Location::Frame(-4)),
+            Instruction::Save(Temp(0)),
+            Instruction::Call("f".to_owned(), vec![(Temp(0), 8)], Parameters::Fixed),
+            Instruction::Constant(2, 4),
+            store(-4),
+            Instruction::Return,
+        ];
+        check(code, &[]);
+    }
+
+    #[test]
+    fn stores_read_on_any_path_are_kept() {
+        // `x = 1; if (c) x = 2; return x;` and `x = 1; while (c) { y = x; x = 2; } return y;`
+        check(vec![
+            store(-4),
+            Instruction::Test(4),
+            jump(Some(Condition::Equal), "end"),
+            store(-4),
+            label("end"),
+            load(-4),
+            Instruction::Return,
+        ], &[]);
+        check(vec![
+            store(-4),
+            label("loop"),
+            Instruction::Test(4),
+            jump(Some(Condition::Equal), "end"),
+            load(-4),
+            store(-8),
+            store(-4),
+            jump(None, "loop"),
+            label("end"),
+            load(-8),
+            Instruction::Return,
+        ], &[]);
+        // Unless every path overwrites them first.
+        check(vec![
+            store(-4),
+            Instruction::Test(4),
+            jump(Some(Condition::Equal), "else"),
+            store(-4),
+            jump(None, "end"),
+            label("else"),
+            store(-4),
+            label("end"),
+            load(-4),
+            Instruction::Return,
+        ], &[0]);
+    }
+
+    #[test]
+    fn jump_tables_may_go_anywhere() {
+        check(vec![
+            store(-4),
+            Instruction::JumpTable(4, 0, 2, "default".to_owned(), "table".to_owned()),
+            label("default"),
+            Instruction::Return,
+        ], &[]);
+    }
+}
@@ -0,0 +1,351 @@
+//! Constant folding, done to the tree before any code is generated for it at
+//! `OptLevel::O1`. Each expression whose operands are all constants is
+//! evaluated with `const_eval` and replaced with a literal of its value, from
+//! the leaves up, so that `2 * 3 + 4` becomes `10`.
+//!
+//! `const_eval` does arithmetic in a signed type in `i64`, which is only what
+//! the program would compute if no step overflows the type it is done in. So
+//! an operand is only constant here if its value fits the type it is used as,
+//! and an expression is only folded if its value fits its own type, and a
+//! shift if the amount is less than the width of what is shifted. Anything else is left for the program to compute, as it
+//! would without folding. A folded expression of a type other than `int` is a
+//! literal cast to that type, and one whose value doesn't fit in an `int`
+//! isn't folded.
+//!
+//! `&&` and `||` with a constant left operand become the constant they are
+//! decided by, or the truth of their right operand, which must still be
+//! evaluated for its side effects. A `?:` with a constant condition becomes the
+//! operand it picks.
+//!
+//! An `if` with a constant condition becomes the branch it takes, and a
+//! `while` or `for` whose condition is zero disappears, unless what would be
+//! removed has a label in it, through which control could still get there. A
+//! loop whose condition is a nonzero constant becomes a `for` without one.
+
+use ast::*;
+use ast::visit::{walk_expr_mut, walk_stmt_mut, VisitorMut};
+use const_eval::{const_eval, convert, ConstEnv};
+use flow;
+use semantic;
+use typecheck::{Conversions, Types};
+
+/// Folds the constant expressions and branches of `program`, which must have
+/// passed semantic analysis without errors.
+pub fn fold(program: &mut Program) {
+    let (analysis, _) = semantic::analyze(program);
+    let evaluator = Evaluator { types: &analysis.types, conversions: &analysis.conversions };
+    let mut folder = Folder { evaluator };
+    folder.visit_program(program);
+}
+
+/// Whether `value` is one that `ty`, an integer type, can hold as it is.
+fn fits(value: i64, ty: &Type) -> bool {
+    convert(value, ty) == Some(value) && (ty.is_signed() || value >= 0)
+}
+
+/// The value of `expression` if it is a literal: an integer or character
+/// constant, a `sizeof`, or a cast of one.
+pub fn literal(ast: &Ast, expression: ExprId) -> Option<i64> {
+    match ast[expression].kind {
+        ExpressionKind::IntLiteral(..) | ExpressionKind::CharConstant(_) | ExpressionKind::SizeOf(_) => {},
+        ExpressionKind::Cast { expr, .. } => {
+            literal(ast, expr)?;
+        },
+        _ => return None,
+    }
+    const_eval(&ast[expression], &ConstEnv::new(ast)).ok()
+}
+
+/// Replaces `expression`, of type `ty`, with a literal of `value`, if it can be
+/// written as one, and says whether it could.
+pub fn replace(ast: &mut Ast, expression: ExprId, ty: &Type, value: i64) -> bool {
+    let ty = ty.unqualified().clone();
+    if !fits(value, &ty) {
+        return false;
+    }
+    let span = ast[expression].span;
+    // There are no `char` literals, so a `char` is an `int` converted.
+    ast[expression].kind = if ty == Type::Char {
+        let expr = ast.alloc_expr(Expression { kind: ExpressionKind::IntLiteral(value, Type::Int), span });
+        ExpressionKind::Cast { to: ty, expr }
+    } else {
+        ExpressionKind::IntLiteral(value, ty)
+    };
+    true
+}
+
+/// Evaluates the expressions whose values `const_eval` gets right, as this
+/// module describes.
+pub struct Evaluator<'a> {
+    pub types: &'a Types,
+    pub conversions: &'a Conversions,
+}
+
+impl<'a> Evaluator<'a> {
+    /// The value of `expression`, if it is made of constants, as its own type.
+    pub fn value(&self, ast: &Ast, expression: ExprId) -> Option<i64> {
+        let ty = self.types.get(&expression)?;
+        match ast[expression].kind {
+            ExpressionKind::IntLiteral(..) | ExpressionKind::CharConstant(_) | ExpressionKind::SizeOf(_) => {},
+            ExpressionKind::Unary(UnaryOp::Negate, operand)
+            | ExpressionKind::Unary(UnaryOp::BitwiseNot, operand)
+            | ExpressionKind::Unary(UnaryOp::LogicalNot, operand)
+            | ExpressionKind::Cast { expr: operand, .. } => {
+                self.operand(ast, operand)?;
+            },
+            ExpressionKind::Binary(BinaryOp::LogicalAnd, ..) | ExpressionKind::Binary(BinaryOp::LogicalOr, ..) => return None,
+            ExpressionKind::Binary(op, lhs, rhs) => {
+                self.operand(ast, lhs)?;
+                let rhs = self.operand(ast, rhs)?;
+                if (op == BinaryOp::ShiftLeft || op == BinaryOp::ShiftRight) && rhs >= ConstEnv::new(ast).size_of(ty)? * 8 {
+                    return None;
+                }
+            },
+            _ => return None,
+        }
+        let value = const_eval(&ast[expression], &ConstEnv::new(ast)).ok()?;
+        if fits(value, ty) { Some(value) } else { None }
+    }
+
+    /// The value of `expression`, if it is made of constants, as the type it
+    /// is used as.
+    pub fn operand(&self, ast: &Ast, expression: ExprId) -> Option<i64> {
+        let value = self.value(ast, expression)?;
+        let ty = self.conversions.get(&expression).or_else(|| self.types.get(&expression))?;
+        if fits(value, ty) { Some(value) } else { None }
+    }
+}
+
+struct Folder<'a> {
+    evaluator: Evaluator<'a>,
+}
+
+impl<'a> Folder<'a> {
+    /// Folds `expression`, whose operands have been folded already.
+    fn fold(&self, ast: &mut Ast, expression: ExprId) {
+        if literal(ast, expression).is_some() {
+            return;
+        }
+        let ty = match self.evaluator.types.get(&expression) {
+            Some(ty) => ty,
+            None => return,
+        };
+        let span = ast[expression].span;
+        match ast[expression].kind {
+            ExpressionKind::Binary(op @ BinaryOp::LogicalAnd, lhs, rhs) | ExpressionKind::Binary(op @ BinaryOp::LogicalOr, lhs, rhs) => {
+                let lhs = match self.evaluator.operand(ast, lhs) {
+                    Some(lhs) => lhs != 0,
+                    None => return,
+                };
+                // The left operand decides `0 && x` and `1 || x` on its own.
+                if lhs == (op == BinaryOp::LogicalOr) {
+                    ast[expression].kind = ExpressionKind::IntLiteral(lhs as i64, Type::Int);
+                } else if let Some(rhs) = self.evaluator.operand(ast, rhs) {
+                    ast[expression].kind = ExpressionKind::IntLiteral((rhs != 0) as i64, Type::Int);
+                } else {
+                    let zero = ast.alloc_expr(Expression { kind: ExpressionKind::IntLiteral(0, Type::Int), span });
+                    ast[expression].kind = ExpressionKind::Binary(BinaryOp::NotEqual, rhs, zero);
+                }
+                return;
+            },
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                let chosen = match self.evaluator.operand(ast, condition) {
+                    Some(0) => otherwise,
+                    Some(_) => then,
+                    None => return,
+                };
+                let chosen_ty = match self.evaluator.types.get(&chosen) {
+                    Some(chosen_ty) => chosen_ty,
+                    None => return,
+                };
+                if chosen_ty.decay() == ty.decay() {
+                    ast[expression].kind = ast[chosen].kind.clone();
+                } else if ty.is_integer() && chosen_ty.is_integer() {
+                    ast[expression].kind = ExpressionKind::Cast { to: ty.unqualified().clone(), expr: chosen };
+                }
+                return;
+            },
+            _ => {},
+        }
+        if let Some(value) = self.evaluator.value(ast, expression) {
+            replace(ast, expression, ty, value);
+        }
+    }
+}
+
+impl<'a> VisitorMut for Folder<'a> {
+    fn visit_stmt(&mut self, ast: &mut Ast, statement: StmtId) {
+        walk_stmt_mut(self, ast, statement);
+        let kind = match ast[statement].kind {
+            StatementKind::If(condition, then, otherwise) => {
+                let (taken, dead) = match self.evaluator.operand(ast, condition) {
+                    Some(0) => (otherwise, Some(then)),
+                    Some(_) => (Some(then), otherwise),
+                    None => return,
+                };
+                if dead.is_some_and(|dead| flow::labeled(ast, dead, true)) {
+                    return;
+                }
+                taken.map_or(StatementKind::Expression(None), |taken| ast[taken].kind.clone())
+            },
+            StatementKind::While(condition, body) => match self.evaluator.operand(ast, condition) {
+                Some(0) if !flow::labeled(ast, body, true) => StatementKind::Expression(None),
+                Some(0) | None => return,
+                Some(_) => StatementKind::For { init: ForInit::Expression(None), condition: None, post: None, body },
+            },
+            StatementKind::For { ref init, condition: Some(condition), post, body } => match self.evaluator.operand(ast, condition) {
+                // The initialization still happens.
+                Some(0) if !flow::labeled(ast, body, true) => match *init {
+                    ForInit::Expression(expression) => StatementKind::Expression(expression),
+                    ForInit::Declaration(ref declarations) =>
+                        StatementKind::Compound(vec![BlockItem::Declaration(declarations.clone())]),
+                },
+                Some(0) | None => return,
+                Some(_) => StatementKind::For { init: init.clone(), condition: None, post, body },
+            },
+            _ => return,
+        };
+        ast[statement].kind = kind;
+    }
+
+    fn visit_expr(&mut self, ast: &mut Ast, expression: ExprId) {
+        walk_expr_mut(self, ast, expression);
+        self.fold(ast, expression);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::{lex_str, spell_int};
+    use parser::parse_program;
+
+    fn parse(source: &str) -> Program {
+        parse_program(&lex_str(source).unwrap()).unwrap()
+    }
+
+    /// Folds `source`, and checks that it comes out as `expected`.
+    fn check(source: &str, expected: &str) {
+        let mut program = parse(source);
+        fold(&mut program);
+        let expected = parse(expected);
+        let body = |program: &Program| match program.items.last() {
+            Some(TopLevel::Function(function)) => function.body.clone().unwrap(),
+            _ => panic!("no function"),
+        };
+        let render = |program: &Program| format!("{:?}", body(program).iter().map(|item| match *item {
+            BlockItem::Statement(statement) => describe(&program.ast, statement),
+            BlockItem::Declaration(_) => "declaration".to_owned(),
+        }).collect::<Vec<_>>());
+        assert_eq!(render(&program), render(&expected), "{}", source);
+    }
+
+    /// `statement` written out with its expressions, by value rather than by id.
+    fn describe(ast: &Ast, statement: StmtId) -> String {
+        let expression = |id: ExprId| describe_expr(ast, id);
+        match ast[statement].kind {
+            StatementKind::Return(value) => format!("return {}", expression(value)),
+            StatementKind::Expression(value) => format!("{};", value.map_or(String::new(), expression)),
+            StatementKind::If(condition, then, otherwise) => format!(
+                "if {} {} else {}",
+                expression(condition),
+                describe(ast, then),
+                otherwise.map_or(String::new(), |otherwise| describe(ast, otherwise)),
+            ),
+            StatementKind::Compound(ref items) => format!("{{{}}}", items.iter().map(|item| match *item {
+                BlockItem::Statement(statement) => describe(ast, statement),
+                BlockItem::Declaration(_) => "declaration".to_owned(),
+            }).collect::<Vec<_>>().join(" ")),
+            StatementKind::While(condition, body) => format!("while {} {}", expression(condition), describe(ast, body)),
+            StatementKind::For { condition, body, .. } =>
+                format!("for {} {}", condition.map_or(String::new(), expression), describe(ast, body)),
+            StatementKind::Labeled(ref label, body) => format!("{}: {}", label, describe(ast, body)),
+            StatementKind::Case(value, body) => format!("case {}: {}", expression(value), describe(ast, body)),
+            StatementKind::Switch(value, body) => format!("switch {} {}", expression(value), describe(ast, body)),
+            ref kind => format!("{:?}", kind),
+        }
+    }
+
+    fn describe_expr(ast: &Ast, expression: ExprId) -> String {
+        let operand = |id: ExprId| describe_expr(ast, id);
+        match ast[expression].kind {
+            ExpressionKind::IntLiteral(value, ref ty) => spell_int(value, ty),
+            // As negative literals come out of folding.
+            ExpressionKind::Unary(UnaryOp::Negate, value) if matches!(ast[value].kind, ExpressionKind::IntLiteral(..)) =>
+                format!("-{}", operand(value)),
+            ExpressionKind::Unary(op, value) => format!("({:?} {})", op, operand(value)),
+            ExpressionKind::Binary(op, lhs, rhs) => format!("({} {:?} {})", operand(lhs), op, operand(rhs)),
+            ExpressionKind::Cast { ref to, expr } => format!("({:?}) {}", to, operand(expr)),
+            ExpressionKind::Call(ref name, ref arguments) =>
+                format!("{}({})", name, arguments.iter().map(|&argument| operand(argument)).collect::<Vec<_>>().join(", ")),
+            ref kind => format!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn arithmetic_is_folded() {
+        check("int main() { return 2 * 3 + 4; }", "int main() { return 10; }");
+        check("int main() { return -(1 << 4) / ~2 % 5; }", "int main() { return 0; }");
+        check("int main() { int x = 1; return x + 2 * 3; }", "int main() { int x = 1; return x + 6; }");
+        check("int main() { return (char)300 + !0; }", "int main() { return 45; }");
+        check("long f() { return (long)2 * 3; }", "long f() { return 6L; }");
+        check("long f() { return (long)65536 * 65536; }", "long f() { return 4294967296L; }");
+    }
+
+    #[test]
+    fn what_the_program_computes_differently_is_left() {
+        // Compared as unsigned, `-1` is the greater.
+        check("int main() { return -1 < (unsigned)1; }", "int main() { return -1 < (unsigned)1; }");
+        // Overflowing `int`, and shifting by its width or more.
+        check("int main() { return 2147483647 + 1; }", "int main() { return 2147483647 + 1; }");
+        check("int main() { return 1 << 31; }", "int main() { return 1 << 31; }");
+        check("int main() { return 256 >> 40; }", "int main() { return 256 >> 40; }");
+        check("int main() { return 1 / 0; }", "int main() { return 1 / 0; }");
+    }
+
+    #[test]
+    fn unsigned_arithmetic_wraps() {
+        check("int main() { return (unsigned)0 - 1 > 0; }", "int main() { return 1; }");
+        check("int main() { return (0u - 1) / 2 == 2147483647; }", "int main() { return 1; }");
+    }
+
+    #[test]
+    fn logical_operators_keep_side_effects() {
+        check("int f(); int main() { return 0 && f(); }", "int f(); int main() { return 0; }");
+        check("int f(); int main() { return 2 || f(); }", "int f(); int main() { return 1; }");
+        check("int f(); int main() { return 1 && f(); }", "int f(); int main() { return f() != 0; }");
+        check("int f(); int main() { return 0 || f(); }", "int f(); int main() { return f() != 0; }");
+        check("int f(); int main() { return f() && 0; }", "int f(); int main() { return f() && 0; }");
+        check("int main() { return 3 && 4; }", "int main() { return 1; }");
+    }
+
+    #[test]
+    fn conditionals_pick_an_operand() {
+        check("int f(); int main() { return 1 ? f() : 2; }", "int f(); int main() { return f(); }");
+        check("long f(); int main() { return 0 ? f() : 2; }", "long f(); int main() { return (long)2; }");
+    }
+
+    #[test]
+    fn branches_are_folded() {
+        check("int f(); int main() { if (2 - 2) f(); else return 3; return 4; }",
+              "int f(); int main() { return 3; return 4; }");
+        check("int f(); int main() { if (1) { f(); } return 4; }", "int f(); int main() { { f(); } return 4; }");
+        check("int f(); int main() { if (0) f(); return 4; }", "int f(); int main() { ; return 4; }");
+        check("int f(); int main() { while (0) f(); return 4; }", "int f(); int main() { ; return 4; }");
+        check("int f(); int main() { while (1) f(); }", "int f(); int main() { for (;;) f(); }");
+        check("int f(); int main() { int i; for (i = 5; 1 - 1; i++) f(); return i; }",
+              "int f(); int main() { int i; i = 5; return i; }");
+        check("int f(); int main() { for (int i = 5; 1;) f(); }", "int f(); int main() { for (int i = 5;;) f(); }");
+    }
+
+    #[test]
+    fn dead_branches_with_labels_are_kept() {
+        let source = "int f(); int main() { goto inside; if (0) { inside: return f(); } return 4; }";
+        check(source, source);
+        let source = "int main() { switch (2) { case 1: if (0) { case 2: return 5; } return 1; } return 0; }";
+        check(source, source);
+        // A label of a `switch` inside the branch only matters within it.
+        check("int f(); int main() { if (0) switch (f()) { case 1: return 5; } return 4; }",
+              "int f(); int main() { ; return 4; }");
+    }
+}
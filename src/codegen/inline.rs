@@ -0,0 +1,139 @@
+//! Picks the functions whose calls are replaced with their bodies in the
+//! assembly at `OptLevel::O2`. A function is inlined if its body is a single
+//! `return` of an expression of at most `MAX_SIZE` nodes, it takes a fixed
+//! number of scalar parameters, and it can't end up inlined into itself
+//! through the calls in that expression. Nothing can take the address of a
+//! function, since a function isn't a value, so every use of one is a call.
+//!
+//! At an inlined call, the arguments are evaluated as they are for any other,
+//! and then stored to slots in the caller's frame that stand for the
+//! parameters, where the returned expression finds them. It refers to them by
+//! what they resolve to rather than by name, so none of the caller's variables
+//! can get in the way.
+
+use std::collections::{HashMap, HashSet};
+use ast::*;
+use ast::visit::{self, Visitor};
+
+/// The most nodes the returned expression of a function to inline may have.
+pub const MAX_SIZE: usize = 16;
+
+/// A function to inline.
+#[derive(Debug, PartialEq)]
+pub struct Inlinable {
+    /// Its definition's index in the program's items, which its parameters
+    /// resolve to.
+    pub index: usize,
+    pub parameters: Vec<Type>,
+    /// The expression it returns.
+    pub body: ExprId,
+}
+
+/// Counts the nodes of an expression, and collects the functions it calls.
+struct Expression {
+    size: usize,
+    calls: Vec<String>,
+}
+
+impl Visitor for Expression {
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        self.size += 1;
+        if let ExpressionKind::Call(ref name, _) = ast[expression].kind {
+            self.calls.push(name.clone());
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// The functions of `program` to inline, by name.
+pub fn inlinable(program: &Program) -> HashMap<String, Inlinable> {
+    let mut candidates = HashMap::new();
+    let mut calls = HashMap::new();
+    for (index, item) in program.items.iter().enumerate() {
+        let function = match *item {
+            TopLevel::Function(ref function) if !function.variadic => function,
+            _ => continue,
+        };
+        let body = match function.body.as_deref() {
+            Some(&[BlockItem::Statement(statement)]) => match program.ast[statement].kind {
+                StatementKind::Return(body) => body,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if !function.return_type.is_scalar() || !function.parameters.iter().all(|parameter| parameter.ty.is_scalar()) {
+            continue;
+        }
+        let mut expression = Expression { size: 0, calls: Vec::new() };
+        expression.visit_expr(&program.ast, body);
+        if expression.size > MAX_SIZE {
+            continue;
+        }
+        let parameters = function.parameters.iter().map(|parameter| parameter.ty.clone()).collect();
+        candidates.insert(function.name.clone(), Inlinable { index, parameters, body });
+        calls.insert(function.name.clone(), expression.calls);
+    }
+
+    // A function that reaches itself through the calls of the others would be
+    // inlined into itself forever.
+    let recursive: Vec<String> = candidates.keys()
+        .filter(|&name| {
+            let mut seen = HashSet::new();
+            let mut pending = calls[name].clone();
+            while let Some(callee) = pending.pop() {
+                if callee == *name {
+                    return true;
+                }
+                if seen.insert(callee.clone()) {
+                    pending.extend(calls.get(&callee).into_iter().flatten().cloned());
+                }
+            }
+            false
+        })
+        .cloned()
+        .collect();
+    for name in recursive {
+        candidates.remove(&name);
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn inlined(source: &str) -> Vec<String> {
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let mut names: Vec<_> = inlinable(&program).into_keys().collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn small_functions_are_inlined() {
+        let source = "static int sq(int x) { return x * x; }\n\
+                      long twice(long x, int *p) { return sq(x) * 2 + *p; }\n\
+                      int main() { int x = 1; return twice(3, &x); }";
+        assert_eq!(inlined(source), ["sq", "twice"]);
+    }
+
+    #[test]
+    fn only_small_single_returns_are_inlined() {
+        assert_eq!(inlined("int f(int x) { int y = x; return y; }"), Vec::<String>::new());
+        assert_eq!(inlined("int f(int x) { return x + x + x + x + x + x + x + x + x; }"), Vec::<String>::new());
+        assert_eq!(inlined("int f(int x, ...) { return x; }"), Vec::<String>::new());
+        assert_eq!(inlined("int f(int x);"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn recursive_functions_are_not_inlined() {
+        assert_eq!(inlined("int f(int x) { return x ? x * f(x - 1) : 1; }"), Vec::<String>::new());
+        let source = "int odd(int x);\n\
+                      int even(int x) { return x == 0 || odd(x - 1); }\n\
+                      int odd(int x) { return x != 0 && even(x - 1); }\n\
+                      int g(int x) { return even(x); }";
+        assert_eq!(inlined(source), ["g"]);
+    }
+}
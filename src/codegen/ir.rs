@@ -0,0 +1,127 @@
+//! The instructions each function is lowered to before any of its assembly is
+//! written. They are the operations of `Machine`, except that a value waiting
+//! for the rest of an expression is saved to a temporary rather than pushed.
+//! Once the whole function is lowered, `registers::allocate` gives every
+//! temporary a home, and `write` has the machine write each instruction with
+//! the homes filled in.
+
+use std::collections::HashMap;
+use ast::{BinaryOp, UnaryOp};
+use super::{Condition, Location, Machine, Parameters, Source};
+use super::registers::Home;
+
+/// A value saved from the accumulator, numbered in the order it is saved.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Temp(pub usize);
+
+/// An operation of `Machine`, with the same operands as its method of the same
+/// name, or one of the operations on temporaries.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction {
+    Label(String),
+    Position(usize, usize),
+    Comment(String),
+    Constant(i64, i64),
+    ScratchConstant(i64, i64),
+    Load(Location, i64, bool),
+    Store(Location, i64),
+    StoreConstant(i64, i64, i64),
+    Address(Location),
+    /// Copies all of the accumulator to a temporary, which is saved exactly
+    /// once.
+    Save(Temp),
+    /// Copies a temporary back to all of the accumulator. This is its last use.
+    Restore(Temp),
+    /// Copies an address in a temporary to where `Location::Restored` is. This
+    /// is its last use.
+    RestoreAddress(Temp),
+    MoveToScratch(i64),
+    RestoreFromScratch(i64),
+    Binary(BinaryOp, i64, bool, Source),
+    Divide(i64, bool, bool, Option<String>),
+    DivideByPower(i64, bool, bool, i64),
+    Compare(i64, Condition),
+    Test(i64),
+    CompareConstant(i64, i64),
+    Set(Condition),
+    Unary(UnaryOp, i64),
+    Extend(i64, i64, bool),
+    Jump(Option<Condition>, String),
+    JumpTable(i64, i64, i64, String, String),
+    /// Calls a label with the arguments in temporaries, with their sizes. This
+    /// is the last use of each of them.
+    Call(String, Vec<(Temp, i64)>, Parameters),
+    /// Returns with the value in the accumulator.
+    Return,
+}
+
+impl Instruction {
+    /// The temporaries this instruction uses, for the last time.
+    pub fn uses(&self) -> Vec<Temp> {
+        match *self {
+            Instruction::Restore(temp) | Instruction::RestoreAddress(temp) => vec![temp],
+            Instruction::Call(_, ref arguments, _) => arguments.iter().map(|&(temp, _)| temp).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Numbers the temporaries left in `code` in the order they are saved again,
+/// after a pass has removed the code that saved some of them.
+pub fn renumber(code: &mut [Instruction]) {
+    let mut numbers = HashMap::new();
+    for instruction in code {
+        match *instruction {
+            Instruction::Save(ref mut temp) => {
+                let number = Temp(numbers.len());
+                numbers.insert(temp.0, number);
+                *temp = number;
+            },
+            Instruction::Restore(ref mut temp) | Instruction::RestoreAddress(ref mut temp) => *temp = numbers[&temp.0],
+            Instruction::Call(_, ref mut arguments, _) => {
+                for &mut (ref mut temp, _) in arguments {
+                    *temp = numbers[&temp.0];
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Has `machine` write `code`, with each temporary at its home in `homes`.
+pub fn write(code: &[Instruction], homes: &[Home], machine: &mut dyn Machine) {
+    for instruction in code {
+        match *instruction {
+            Instruction::Label(ref label) => machine.label(label),
+            Instruction::Position(line, column) => machine.position(line, column),
+            Instruction::Comment(ref text) => machine.comment(text),
+            Instruction::Constant(value, size) => machine.constant(value, size),
+            Instruction::ScratchConstant(value, size) => machine.scratch_constant(value, size),
+            Instruction::Load(ref location, size, signed) => machine.load(location, size, signed),
+            Instruction::Store(ref location, size) => machine.store(location, size),
+            Instruction::StoreConstant(offset, size, value) => machine.store_constant(offset, size, value),
+            Instruction::Address(ref location) => machine.address(location),
+            Instruction::Save(Temp(temp)) => machine.save(homes[temp]),
+            Instruction::Restore(Temp(temp)) => machine.restore(homes[temp]),
+            Instruction::RestoreAddress(Temp(temp)) => machine.restore_address(homes[temp]),
+            Instruction::MoveToScratch(size) => machine.move_to_scratch(size),
+            Instruction::RestoreFromScratch(size) => machine.restore_from_scratch(size),
+            Instruction::Binary(operator, size, signed, source) => machine.binary(operator, size, signed, source),
+            Instruction::Divide(size, signed, remainder, ref zero) => machine.divide(size, signed, remainder, zero.as_deref()),
+            Instruction::DivideByPower(size, signed, remainder, shift) => machine.divide_by_power(size, signed, remainder, shift),
+            Instruction::Compare(size, condition) => machine.compare(size, condition),
+            Instruction::Test(size) => machine.test(size),
+            Instruction::CompareConstant(size, value) => machine.compare_constant(size, value),
+            Instruction::Set(condition) => machine.set(condition),
+            Instruction::Unary(operator, size) => machine.unary(operator, size),
+            Instruction::Extend(from, to, signed) => machine.extend(from, to, signed),
+            Instruction::Jump(condition, ref label) => machine.jump(condition, label),
+            Instruction::JumpTable(size, min, max, ref otherwise, ref table) => machine.jump_table(size, min, max, otherwise, table),
+            Instruction::Call(ref label, ref arguments, parameters) => {
+                let arguments: Vec<_> = arguments.iter().map(|&(Temp(temp), size)| (homes[temp], size)).collect();
+                machine.call(label, &arguments, parameters);
+            },
+            Instruction::Return => machine.epilogue(),
+        }
+    }
+}
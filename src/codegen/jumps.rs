@@ -0,0 +1,222 @@
+//! Removes the jumps and labels that a function's lowered code doesn't need,
+//! and the code control can never reach. Lowering each construct on its own
+//! leaves jumps to the very next instruction, jumps to another jump, labels
+//! nothing jumps to, and code after a `return` or `break` that falls off the
+//! end of nothing. Each rule is applied over and over until none of them
+//! changes anything, since removing one jump can leave a label unused, and the
+//! code after that unreachable.
+//!
+//! A jump table's targets are referenced from where the table is placed, not
+//! from the code, so the labels they name are always kept. A jump to a label
+//! outside the function, like that of a checked division by zero, is left as
+//! it is.
+
+use std::collections::{HashMap, HashSet};
+use super::ir::{self, Instruction};
+
+/// Cleans up `code`, in which the labels named in jump tables are `targets`.
+pub fn clean_up<'a, I: IntoIterator<Item = &'a str>>(code: &mut Vec<Instruction>, targets: I) {
+    let targets: HashSet<String> = targets.into_iter().map(str::to_owned).collect();
+    while thread(code) | jump_to_next(code) | unreachable(code, &targets) {}
+    ir::renumber(code);
+}
+
+/// Whether `instruction` only marks a place in the code, and has none itself.
+fn marker(instruction: &Instruction) -> bool {
+    matches!(*instruction, Instruction::Label(_) | Instruction::Position(..) | Instruction::Comment(_))
+}
+
+/// The label that jumping to `label` ends up at, following the unconditional
+/// jumps just after it. A chain of them that loops back on itself is left as
+/// it is.
+fn destination<'a>(code: &'a [Instruction], labels: &HashMap<&str, usize>, mut label: &'a str) -> &'a str {
+    let mut seen = HashSet::new();
+    while let Some(&start) = labels.get(label) {
+        match code[start..].iter().find(|instruction| !marker(instruction)) {
+            Some(&Instruction::Jump(None, ref next)) if seen.insert(label) => label = next,
+            _ => break,
+        }
+    }
+    label
+}
+
+/// A jump to an unconditional jump goes straight to where that one goes.
+fn thread(code: &mut [Instruction]) -> bool {
+    let retargeted: Vec<(usize, String)> = {
+        let labels: HashMap<&str, usize> = code.iter().enumerate()
+            .filter_map(|(index, instruction)| match *instruction {
+                Instruction::Label(ref label) => Some((&label[..], index)),
+                _ => None,
+            })
+            .collect();
+        code.iter().enumerate()
+            .filter_map(|(index, instruction)| match *instruction {
+                Instruction::Jump(_, ref label) => {
+                    let destination = destination(code, &labels, label);
+                    if destination != label { Some((index, destination.to_owned())) } else { None }
+                },
+                _ => None,
+            })
+            .collect()
+    };
+    let changed = !retargeted.is_empty();
+    for (index, destination) in retargeted {
+        if let Instruction::Jump(_, ref mut label) = code[index] {
+            *label = destination;
+        }
+    }
+    changed
+}
+
+/// A jump to the label just after it, with nothing but other labels and
+/// markers between, is removed.
+fn jump_to_next(code: &mut Vec<Instruction>) -> bool {
+    let redundant: HashSet<usize> = code.iter().enumerate()
+        .filter(|&(index, instruction)| match *instruction {
+            Instruction::Jump(_, ref label) => code[index + 1..].iter()
+                .take_while(|next| marker(next))
+                .any(|next| *next == Instruction::Label(label.clone())),
+            _ => false,
+        })
+        .map(|(index, _)| index)
+        .collect();
+    remove(code, &redundant)
+}
+
+/// The labels that nothing jumps to are removed, and so is the code after an
+/// unconditional jump or a return, up to the next label something jumps to.
+fn unreachable(code: &mut Vec<Instruction>, targets: &HashSet<String>) -> bool {
+    let unused: HashSet<usize> = {
+        let mut referenced: HashSet<&str> = targets.iter().map(|label| &label[..]).collect();
+        for instruction in code.iter() {
+            match *instruction {
+                Instruction::Jump(_, ref label) | Instruction::JumpTable(_, _, _, ref label, _) => {
+                    referenced.insert(label);
+                },
+                _ => {},
+            }
+        }
+        let mut reachable = true;
+        code.iter().enumerate()
+            .filter(|&(_, instruction)| match *instruction {
+                Instruction::Label(ref label) if referenced.contains(&label[..]) => {
+                    reachable = true;
+                    false
+                },
+                Instruction::Label(_) => true,
+                Instruction::Jump(None, _) | Instruction::JumpTable(..) | Instruction::Return => {
+                    let removed = !reachable;
+                    reachable = false;
+                    removed
+                },
+                _ => !reachable,
+            })
+            .map(|(index, _)| index)
+            .collect()
+    };
+    remove(code, &unused)
+}
+
+/// Removes the instructions at `indices` from `code`, and says whether there
+/// were any.
+fn remove(code: &mut Vec<Instruction>, indices: &HashSet<usize>) -> bool {
+    let mut index = 0;
+    code.retain(|_| {
+        index += 1;
+        !indices.contains(&(index - 1))
+    });
+    !indices.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use codegen::{Condition, Parameters};
+    use codegen::ir::Temp;
+
+    fn label(name: &str) -> Instruction {
+        Instruction::Label(name.to_owned())
+    }
+
+    fn jump(condition: Option<Condition>, name: &str) -> Instruction {
+        Instruction::Jump(condition, name.to_owned())
+    }
+
+    fn check(mut code: Vec<Instruction>, expected: Vec<Instruction>) {
+        clean_up(&mut code, vec!["case"]);
+        assert_eq!(code, expected);
+    }
+
+    #[test]
+    fn jumps_to_the_next_instruction_are_removed() {
+        check(
+            vec![Instruction::Test(4), jump(Some(Condition::Equal), "a"), Instruction::Position(2, 1), label("a"), Instruction::Return],
+            vec![Instruction::Test(4), Instruction::Position(2, 1), Instruction::Return],
+        );
+    }
+
+    #[test]
+    fn jumps_to_jumps_are_threaded() {
+        check(
+            vec![
+                Instruction::Test(4),
+                jump(Some(Condition::Equal), "a"),
+                Instruction::Constant(1, 4),
+                Instruction::Return,
+                label("a"),
+                jump(None, "b"),
+                label("c"),
+                Instruction::Constant(2, 4),
+                label("b"),
+                jump(None, "c"),
+            ],
+            vec![
+                Instruction::Test(4),
+                jump(Some(Condition::Equal), "c"),
+                Instruction::Constant(1, 4),
+                Instruction::Return,
+                label("c"),
+                Instruction::Constant(2, 4),
+                jump(None, "c"),
+            ],
+        );
+        // A jump to itself is a loop that never ends.
+        check(vec![label("a"), jump(None, "a")], vec![label("a"), jump(None, "a")]);
+    }
+
+    #[test]
+    fn unused_labels_are_removed() {
+        check(
+            vec![label("a"), Instruction::Constant(1, 4), label("case"), Instruction::Return],
+            vec![Instruction::Constant(1, 4), label("case"), Instruction::Return],
+        );
+    }
+
+    #[test]
+    fn unreachable_code_is_removed() {
+        check(
+            vec![
+                Instruction::Constant(1, 4),
+                Instruction::Return,
+                Instruction::Constant(2, 4),
+                Instruction::Save(Temp(0)),
+                jump(None, "a"),
+                label("a"),
+                Instruction::Restore(Temp(0)),
+                Instruction::Return,
+                label("case"),
+                Instruction::Save(Temp(1)),
+                Instruction::Call("f".to_owned(), vec![(Temp(1), 4)], Parameters::Fixed),
+                Instruction::Return,
+            ],
+            vec![
+                Instruction::Constant(1, 4),
+                Instruction::Return,
+                label("case"),
+                Instruction::Save(Temp(0)),
+                Instruction::Call("f".to_owned(), vec![(Temp(0), 4)], Parameters::Fixed),
+                Instruction::Return,
+            ],
+        );
+    }
+}
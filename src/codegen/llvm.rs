@@ -0,0 +1,1372 @@
+//! Generates LLVM IR rather than assembly, as the text of a `.ll` file that
+//! `clang`, `llc`, or `lli` accept like any other. It is a second backend to
+//! the assembly one, chosen with `Options::format`, and works from the same
+//! analysis, struct layouts, and static objects.
+//!
+//! The IR is the straightforward kind a C compiler gives its optimizer. Every
+//! local variable and parameter has an `alloca` in the function's entry block,
+//! which it is loaded from and stored to wherever it is used. Every expression
+//! is a value of the LLVM type of its C type: `i8` for a `char`, `i32` for an
+//! `int`, `i64` for a `long`, whether signed or not, and `ptr` for any pointer.
+//! A `struct tag` is the LLVM struct type `%struct.tag`, whose members LLVM
+//! lays out the same way `Layouts` does.
+//!
+//! Control flow is made of basic blocks named for what they are in, as in
+//! `while.body3`, numbered through the function, with `br` between them. A
+//! variable's `alloca` is named for it, with a number after it if another
+//! variable in the function has the same name; a block's name has a dot in
+//! it followed by a word, which no variable's has, so the two never collide.
+//! Temporaries are numbered as LLVM requires, after the parameters and the
+//! entry block, which are unnamed. Code after a jump or `return` is given a
+//! block of its own, which nothing branches to.
+//!
+//! `&&` and `||` meet in a `phi` of the operands' truth, and the operands of
+//! `?:` in a `phi` of their values. A `switch` is LLVM's `switch`, which LLVM
+//! lowers as it sees fit. A C label is the start of a `goto.label` block.
+//!
+//! Globals are named as they are in C, `static` locals with the number of their
+//! declaration after them, as in the assembly, and string literals are
+//! `@.str.<n>`. Functions the program declares without defining, or calls
+//! without declaring, are declared at the end. The options that only concern
+//! assembly, like the syntax and the optimization level, are ignored.
+//!
+//! Pointers are opaque, which is all LLVM 17 and later support; LLVM 14 needs
+//! `-opaque-pointers` to read them.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::mem;
+use ast::*;
+use resolve::Symbol;
+use semantic::Analysis;
+use statics::{self, StaticValue};
+use super::fold;
+use super::{Architecture, Layouts, Options, StaticObject, StaticsBuilder, TargetOs, DIVISION_BY_ZERO_STATUS};
+
+/// The function checked divisions call when the divisor is zero, which writes
+/// a message and exits. It starts with a dot so that it can't be one of the
+/// program's.
+const DIVISION_BY_ZERO: &str = "@.division_by_zero";
+
+struct Generator<'a> {
+    ast: &'a Ast,
+    items: &'a [TopLevel],
+    analysis: &'a Analysis,
+    layouts: Layouts<'a>,
+    options: &'a Options,
+    /// The name of every declaration of an object with static storage duration.
+    statics: HashMap<DeclId, String>,
+    /// The bytes of each distinct string literal, numbered by position.
+    strings: Vec<Vec<u8>>,
+    /// The functions called without a prototype, in the order they are first
+    /// called.
+    undeclared: Vec<String>,
+    /// The `alloca`s of the function being generated, and the rest of its code.
+    allocas: String,
+    code: String,
+    /// The `alloca` of each local variable and parameter of the function.
+    locals: HashMap<Symbol, String>,
+    /// How many of the function's locals have been named for each name.
+    names: HashMap<String, usize>,
+    /// The number of the function's next temporary, and of its last block.
+    temps: usize,
+    labels: usize,
+    /// The block being generated, as a `phi` refers to it, and whether it has
+    /// been ended with a terminator.
+    block: String,
+    terminated: bool,
+    /// Where `break` and `continue` branch to in the statement being
+    /// generated, innermost last.
+    breaks: Vec<String>,
+    continues: Vec<String>,
+    /// The block of each `case` and `default` label in the `switch`es
+    /// generated so far.
+    cases: HashMap<StmtId, String>,
+    /// The block of each label of the function that a `goto` or a labeled
+    /// statement has named so far, by name.
+    goto_labels: HashMap<String, String>,
+    /// The return type of the function being generated.
+    returns: Type,
+    /// Whether any division calls `DIVISION_BY_ZERO`, which is then defined.
+    division_checked: bool,
+}
+
+impl<'a> Generator<'a> {
+    /// Writes `instruction` at the end of the block being generated. If that
+    /// block has ended, nothing can reach the instruction, but it still needs a
+    /// block, so a new one is started.
+    fn emit(&mut self, instruction: &str) {
+        if self.terminated {
+            let label = self.new_label("after.jump");
+            self.start(&label);
+        }
+        writeln!(self.code, "  {}", instruction).unwrap();
+    }
+
+    /// Writes `instruction`, whose result goes in a new temporary, and returns
+    /// the temporary.
+    fn value(&mut self, instruction: &str) -> String {
+        let temp = format!("%{}", self.temps);
+        self.temps += 1;
+        self.emit(&format!("{} = {}", temp, instruction));
+        temp
+    }
+
+    /// Ends the block being generated with `terminator`.
+    fn terminate(&mut self, terminator: &str) {
+        self.emit(terminator);
+        self.terminated = true;
+    }
+
+    /// Branches to `label`, unless the block being generated has already ended.
+    fn branch(&mut self, label: &str) {
+        if !self.terminated {
+            self.terminate(&format!("br label %{}", label));
+        }
+    }
+
+    /// Starts the block `label`, which the one before it falls through to if
+    /// it hasn't ended.
+    fn start(&mut self, label: &str) {
+        self.branch(label);
+        writeln!(self.code, "{}:", label).unwrap();
+        self.block = label.to_owned();
+        self.terminated = false;
+    }
+
+    /// Makes a name for a new block of the function, for what `kind` says it
+    /// is, as in `if.end3`.
+    fn new_label(&mut self, kind: &str) -> String {
+        self.labels += 1;
+        format!("{}{}", kind, self.labels)
+    }
+
+    /// Gives `symbol`, named `name`, an `alloca` for a `ty` in the function's
+    /// entry block, and returns it.
+    fn allocate(&mut self, symbol: Symbol, name: &str, ty: &Type) -> String {
+        let count = self.names.entry(name.to_owned()).or_insert(0);
+        let slot = match *count {
+            0 => format!("%{}", name),
+            count => format!("%{}.{}", name, count),
+        };
+        *count += 1;
+        let ty = self.ir_type(ty);
+        writeln!(self.allocas, "  {} = alloca {}", slot, ty).unwrap();
+        self.locals.insert(symbol, slot.clone());
+        slot
+    }
+
+    /// The LLVM type of values of type `ty`.
+    fn ir_type(&self, ty: &Type) -> String {
+        match *ty.unqualified() {
+            Type::Char => "i8".to_owned(),
+            Type::Int | Type::UnsignedInt => "i32".to_owned(),
+            Type::Long | Type::UnsignedLong => "i64".to_owned(),
+            Type::Pointer(_) => "ptr".to_owned(),
+            Type::Array(ref element, length) => format!("[{} x {}]", length, self.ir_type(element)),
+            Type::Struct(ref tag) => format!("%struct.{}", tag),
+            Type::Const(_) => unreachable!("a doubly qualified type"),
+        }
+    }
+
+    /// The LLVM type of a parameter or return value of type `ty`. A `char` is
+    /// sign-extended to 32 bits by whoever passes it, as the ABIs say.
+    fn abi_type(&self, ty: &Type) -> String {
+        match *ty.unqualified() {
+            Type::Char => "i8 signext".to_owned(),
+            Type::Struct(_) => unreachable!("a struct passed by value, which analysis rejects"),
+            ref ty => self.ir_type(ty),
+        }
+    }
+
+    /// The return type of a function returning `ty`, with its attributes.
+    fn return_type(&self, ty: &Type) -> String {
+        match *ty.unqualified() {
+            Type::Char => "signext i8".to_owned(),
+            ref ty => self.abi_type(ty),
+        }
+    }
+
+    /// `value`, an integer of type `ty`, as LLVM reads it: as a signed number
+    /// that fits in its type. A null pointer constant may be a pointer.
+    fn integer(&self, ty: &Type, value: i64) -> String {
+        if ty.is_pointer() {
+            return match value {
+                0 => "null".to_owned(),
+                _ => format!("inttoptr (i64 {} to ptr)", value),
+            };
+        }
+        match self.layouts.size_of(ty) {
+            1 => (value as i8).to_string(),
+            4 => (value as i32).to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// The zero of the scalar type `ty`.
+    fn zero(&self, ty: &Type) -> &'static str {
+        if ty.is_pointer() { "null" } else { "0" }
+    }
+
+    /// Generates the definition of the function at `index` in the program's
+    /// items, which has `body`.
+    fn function(&mut self, index: usize, function: &Function, body: &[BlockItem]) -> String {
+        let parameters: Vec<_> = function.parameters
+            .iter()
+            .enumerate()
+            .map(|(position, parameter)| format!("{} %{}", self.abi_type(&parameter.ty), position))
+            .chain(if function.variadic { Some("...".to_owned()) } else { None })
+            .collect();
+        let linkage = if function.storage == Some(StorageClass::Static) { "internal " } else { "" };
+        let mut definition = format!(
+            "define {}{} @{}({}) {{\n",
+            linkage,
+            self.return_type(&function.return_type),
+            function.name,
+            parameters.join(", "),
+        );
+
+        self.returns = function.return_type.clone();
+        self.locals.clear();
+        self.names.clear();
+        self.labels = 0;
+        self.goto_labels.clear();
+        // The parameters are numbered first, then the entry block.
+        self.temps = function.parameters.len() + 1;
+        self.block = function.parameters.len().to_string();
+        self.terminated = false;
+        for (position, parameter) in function.parameters.iter().enumerate() {
+            let symbol = Symbol::Parameter { function: index, index: position };
+            let slot = self.allocate(symbol, &parameter.name, &parameter.ty);
+            let ty = self.ir_type(&parameter.ty);
+            self.emit(&format!("store {} %{}, ptr {}", ty, position, slot));
+        }
+        self.block(body);
+        // Falling off the end of `main` returns 0. For any other function the
+        // value is meaningless, and returning 0 is as good as anything.
+        if !self.terminated {
+            let ty = self.ir_type(&function.return_type);
+            let zero = self.zero(&function.return_type);
+            self.terminate(&format!("ret {} {}", ty, zero));
+        }
+        definition.push_str(&mem::take(&mut self.allocas));
+        definition.push_str(&mem::take(&mut self.code));
+        definition.push_str("}\n");
+        definition
+    }
+
+    fn block(&mut self, items: &[BlockItem]) {
+        for item in items {
+            match *item {
+                BlockItem::Statement(statement) => self.statement(statement),
+                BlockItem::Declaration(ref declarations) => {
+                    for &declaration in declarations {
+                        self.declaration(declaration);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Gives a local variable its `alloca`, and stores its initial value there.
+    /// The value of a `static` one is in its global already.
+    fn declaration(&mut self, id: DeclId) {
+        let declaration = &self.ast[id];
+        if declaration.storage.is_some() {
+            return;
+        }
+        let slot = self.allocate(Symbol::Variable(id), &declaration.name, &declaration.ty);
+        if let Some(ref initializer) = declaration.initializer {
+            self.initialize(&declaration.ty, initializer, &slot);
+        }
+    }
+
+    /// Stores `initializer`'s value for an object of type `ty` at `address`.
+    /// Whatever an initializer list leaves out is zeroed.
+    fn initialize(&mut self, ty: &Type, initializer: &Initializer, address: &str) {
+        match (ty.unqualified(), initializer) {
+            (&Type::Array(_, length), &Initializer::Expr(value)) => match self.ast[value].kind {
+                ExpressionKind::StringLiteral(ref bytes) => {
+                    // The terminating null is dropped if the array has no room for it.
+                    let mut contents: Vec<u8> = bytes.iter().cloned().chain(Some(0)).take(length).collect();
+                    contents.resize(length, 0);
+                    self.emit(&format!("store [{} x i8] c\"{}\", ptr {}", length, escape(&contents), address));
+                },
+                ref kind => unreachable!("an array initialized from {:?}", kind),
+            },
+            (Type::Array(element, length), Initializer::List(items)) => {
+                let ir_type = self.ir_type(ty);
+                if items.len() < *length {
+                    self.emit(&format!("store {} zeroinitializer, ptr {}", ir_type, address));
+                }
+                for (index, item) in items.iter().enumerate() {
+                    let element_address = self.value(&format!("getelementptr {}, ptr {}, i64 0, i64 {}", ir_type, address, index));
+                    self.initialize(element, item, &element_address);
+                }
+            },
+            (Type::Struct(tag), Initializer::List(items)) => {
+                let members = self.members(tag);
+                if items.len() < members.len() {
+                    self.emit(&format!("store %struct.{} zeroinitializer, ptr {}", tag, address));
+                }
+                for (index, (member, item)) in members.iter().zip(items).enumerate() {
+                    let member_address = self.value(&format!("getelementptr %struct.{}, ptr {}, i32 0, i32 {}", tag, address, index));
+                    self.initialize(&member.ty, item, &member_address);
+                }
+            },
+            (_, Initializer::List(items)) => match items.first() {
+                Some(item) => self.initialize(ty, item, address),
+                None => {
+                    let zero = self.zero(ty);
+                    self.store(ty, zero, address);
+                },
+            },
+            (_, &Initializer::Expr(value)) => {
+                let value = self.operand(value, ty);
+                self.store(ty, &value, address);
+            },
+        }
+    }
+
+    /// The members of `struct tag`.
+    fn members(&self, tag: &str) -> &'a [Member] {
+        self.items.iter()
+            .filter_map(|item| match *item {
+                TopLevel::Struct(ref definition) if definition.name == tag => Some(&definition.members[..]),
+                _ => None,
+            })
+            .next()
+            .unwrap()
+    }
+
+    fn statement(&mut self, statement: StmtId) {
+        match self.ast[statement].kind {
+            StatementKind::Return(value) => {
+                let returns = self.returns.clone();
+                let result = self.operand(value, &returns);
+                let ty = self.ir_type(&returns);
+                self.terminate(&format!("ret {} {}", ty, result));
+            },
+            StatementKind::Expression(Some(expression)) => {
+                self.expression(expression);
+            },
+            StatementKind::Expression(None) => {},
+            StatementKind::Compound(ref items) => self.block(items),
+            StatementKind::If(condition, then, otherwise) => {
+                let then_label = self.new_label("if.then");
+                let else_label = otherwise.map(|_| self.new_label("if.else"));
+                let end = self.new_label("if.end");
+                let truth = self.truth(condition);
+                let other = else_label.clone().unwrap_or_else(|| end.clone());
+                self.terminate(&format!("br i1 {}, label %{}, label %{}", truth, then_label, other));
+                self.start(&then_label);
+                self.statement(then);
+                if let (Some(otherwise), Some(else_label)) = (otherwise, else_label) {
+                    self.branch(&end);
+                    self.start(&else_label);
+                    self.statement(otherwise);
+                }
+                self.start(&end);
+            },
+            StatementKind::While(condition, body) => {
+                let (start, body_label, end) = (self.new_label("while.cond"), self.new_label("while.body"), self.new_label("while.end"));
+                self.start(&start);
+                self.condition(condition, &body_label, &end);
+                self.start(&body_label);
+                self.body(body, &end, &start);
+                self.branch(&start);
+                self.start(&end);
+            },
+            StatementKind::DoWhile(body, condition) => {
+                let (start, next, end) = (self.new_label("do.body"), self.new_label("do.cond"), self.new_label("do.end"));
+                self.start(&start);
+                self.body(body, &end, &next);
+                self.start(&next);
+                self.condition(condition, &start, &end);
+                self.start(&end);
+            },
+            StatementKind::For { ref init, condition, post, body } => {
+                match *init {
+                    ForInit::Declaration(ref declarations) => {
+                        for &declaration in declarations {
+                            self.declaration(declaration);
+                        }
+                    },
+                    ForInit::Expression(Some(expression)) => {
+                        self.expression(expression);
+                    },
+                    ForInit::Expression(None) => {},
+                }
+                let (start, body_label) = (self.new_label("for.cond"), self.new_label("for.body"));
+                let (next, end) = (self.new_label("for.inc"), self.new_label("for.end"));
+                self.start(&start);
+                if let Some(condition) = condition {
+                    self.condition(condition, &body_label, &end);
+                }
+                self.start(&body_label);
+                self.body(body, &end, &next);
+                self.start(&next);
+                if let Some(post) = post {
+                    self.expression(post);
+                }
+                self.branch(&start);
+                self.start(&end);
+            },
+            StatementKind::Switch(value, body) => self.switch(statement, value, body),
+            StatementKind::Case(_, body) | StatementKind::Default(body) => {
+                let label = self.cases[&statement].clone();
+                self.start(&label);
+                self.statement(body);
+            },
+            StatementKind::Break => {
+                let target = self.breaks.last().unwrap().clone();
+                self.branch(&target);
+            },
+            StatementKind::Continue => {
+                let target = self.continues.last().unwrap().clone();
+                self.branch(&target);
+            },
+            StatementKind::Goto(ref name) => {
+                let target = self.goto_label(name);
+                self.branch(&target);
+            },
+            StatementKind::Labeled(ref name, body) => {
+                let label = self.goto_label(name);
+                self.start(&label);
+                self.statement(body);
+            },
+        }
+    }
+
+    /// The block of the label `name` in the function, which is named the
+    /// first time a `goto` or the labeled statement names it.
+    fn goto_label(&mut self, name: &str) -> String {
+        if let Some(label) = self.goto_labels.get(name) {
+            return label.clone();
+        }
+        let label = self.new_label("goto.label");
+        self.goto_labels.insert(name.to_owned(), label.clone());
+        label
+    }
+
+    /// Evaluates `condition` and branches to `then` if it is true and to
+    /// `otherwise` if it isn't.
+    fn condition(&mut self, condition: ExprId, then: &str, otherwise: &str) {
+        let truth = self.truth(condition);
+        self.terminate(&format!("br i1 {}, label %{}, label %{}", truth, then, otherwise));
+    }
+
+    /// Evaluates the scalar `expression` and compares it with zero, giving an
+    /// `i1` that is true if it isn't.
+    fn truth(&mut self, expression: ExprId) -> String {
+        let value = self.expression(expression);
+        let ty = self.value_type(expression);
+        let (ir_type, zero) = (self.ir_type(&ty), self.zero(&ty));
+        self.value(&format!("icmp ne {} {}, {}", ir_type, value, zero))
+    }
+
+    /// Generates the `switch` statement `switch` on `value`, whose labels are
+    /// then only the starts of blocks that the ones before fall through to.
+    fn switch(&mut self, switch: StmtId, value: ExprId, body: StmtId) {
+        let labels = &self.analysis.switches[&switch];
+        let mut cases = Vec::new();
+        for &(value, case) in &labels.cases {
+            let label = self.new_label("sw.case");
+            self.cases.insert(case, label.clone());
+            cases.push((value, label));
+        }
+        let default = labels.default.map(|default| {
+            let label = self.new_label("sw.default");
+            self.cases.insert(default, label.clone());
+            label
+        });
+        let end = self.new_label("sw.end");
+
+        let result = self.expression(value);
+        let ty = self.value_type(value);
+        let ir_type = self.ir_type(&ty);
+        let mut instruction = format!("switch {} {}, label %{} [\n", ir_type, result, default.unwrap_or_else(|| end.clone()));
+        for (value, label) in cases {
+            writeln!(instruction, "    {} {}, label %{}", ir_type, self.integer(&ty, value), label).unwrap();
+        }
+        instruction.push_str("  ]");
+        self.terminate(&instruction);
+
+        self.breaks.push(end.clone());
+        self.statement(body);
+        self.breaks.pop();
+        self.start(&end);
+    }
+
+    /// Generates the body of a loop, in which `break` branches to `end` and
+    /// `continue` to `next`.
+    fn body(&mut self, body: StmtId, end: &str, next: &str) {
+        self.breaks.push(end.to_owned());
+        self.continues.push(next.to_owned());
+        self.statement(body);
+        self.breaks.pop();
+        self.continues.pop();
+    }
+
+    /// The address of the variable that `variable` refers to: its `alloca`, or
+    /// its global.
+    fn variable(&self, variable: ExprId) -> String {
+        let symbol = self.analysis.resolutions[&variable];
+        if let Some(slot) = self.locals.get(&symbol) {
+            return slot.clone();
+        }
+        match symbol {
+            Symbol::Variable(declaration) => format!("@{}", self.statics[&declaration]),
+            _ => unreachable!("a parameter outside its function"),
+        }
+    }
+
+    /// Loads a `ty` from `address`.
+    fn load(&mut self, ty: &Type, address: &str) -> String {
+        if !ty.is_scalar() {
+            unimplemented!("codegen for values of type `{}`", ty);
+        }
+        let ir_type = self.ir_type(ty);
+        self.value(&format!("load {}, ptr {}", ir_type, address))
+    }
+
+    /// Stores `value`, a `ty`, at `address`.
+    fn store(&mut self, ty: &Type, value: &str, address: &str) {
+        if !ty.is_scalar() {
+            unimplemented!("codegen for values of type `{}`", ty);
+        }
+        let ir_type = self.ir_type(ty);
+        self.emit(&format!("store {} {}, ptr {}", ir_type, value, address));
+    }
+
+    /// The global of the string literal with these bytes, which is given one if
+    /// it doesn't have one yet.
+    fn string(&mut self, bytes: &[u8]) -> String {
+        let index = match self.strings.iter().position(|string| string == bytes) {
+            Some(index) => index,
+            None => {
+                self.strings.push(bytes.to_owned());
+                self.strings.len() - 1
+            },
+        };
+        format!("@.str.{}", index)
+    }
+
+    /// The definition of `object`, which is `external` if other files can
+    /// refer to it.
+    fn static_object(&mut self, object: &StaticObject, external: bool) -> String {
+        let declaration = &self.ast[object.declaration];
+        let value = self.static_value(&declaration.ty, &self.analysis.statics[&object.declaration]);
+        let linkage = if external { "" } else { "internal " };
+        format!("@{} = {}global {}\n", object.label, linkage, value)
+    }
+
+    /// The constant of type `ty` with `value`, with its LLVM type before it.
+    fn static_value(&mut self, ty: &Type, value: &StaticValue) -> String {
+        let ir_type = self.ir_type(ty);
+        match *value {
+            StaticValue::Zero if ty.is_scalar() => format!("{} {}", ir_type, self.zero(ty)),
+            StaticValue::Zero => format!("{} zeroinitializer", ir_type),
+            StaticValue::Int(value) => format!("{} {}", ir_type, self.integer(ty, value)),
+            StaticValue::Address { ref object, offset } => {
+                let global = match *object {
+                    statics::StaticObject::Declaration(declaration) => format!("@{}", self.statics[&declaration]),
+                    statics::StaticObject::String(literal) => match self.ast[literal].kind {
+                        ExpressionKind::StringLiteral(ref bytes) => self.string(bytes),
+                        ref kind => unreachable!("a string literal that is {:?}", kind),
+                    },
+                };
+                match offset {
+                    0 => format!("ptr {}", global),
+                    _ => format!("ptr getelementptr (i8, ptr {}, i64 {})", global, offset),
+                }
+            },
+            StaticValue::List(ref items) => match *ty.unqualified() {
+                Type::Array(ref element, length) => {
+                    let mut values: Vec<_> = items.iter().map(|item| self.static_value(element, item)).collect();
+                    while values.len() < length {
+                        values.push(self.static_value(element, &StaticValue::Zero));
+                    }
+                    format!("{} [{}]", ir_type, values.join(", "))
+                },
+                Type::Struct(ref tag) => {
+                    let members = self.members(tag);
+                    let values: Vec<_> = members
+                        .iter()
+                        .enumerate()
+                        .map(|(index, member)| self.static_value(&member.ty, items.get(index).unwrap_or(&StaticValue::Zero)))
+                        .collect();
+                    format!("{} {{ {} }}", ir_type, values.join(", "))
+                },
+                ref ty => unreachable!("a static initializer list for `{}`", ty),
+            },
+        }
+    }
+
+    /// Evaluates `expression` as a value of type `ty`. It is that type already,
+    /// unless it is a null pointer constant used as a pointer, which the
+    /// analysis leaves an integer.
+    fn operand(&mut self, expression: ExprId, ty: &Type) -> String {
+        let value = self.expression(expression);
+        let from = self.value_type(expression);
+        self.convert(&value, &from, ty)
+    }
+
+    /// Evaluates `expression`, converted to the type its value is used as.
+    fn expression(&mut self, expression: ExprId) -> String {
+        let value = self.evaluate(expression);
+        match self.analysis.conversions.get(&expression) {
+            Some(to) => {
+                let from = self.analysis.types[&expression].decay();
+                self.convert(&value, &from, to)
+            },
+            None => value,
+        }
+    }
+
+    /// Evaluates `expression` as its own type. An array evaluates to the
+    /// address of its first element, and a struct, whose value analysis only
+    /// lets be discarded, to its address.
+    fn evaluate(&mut self, expression: ExprId) -> String {
+        let ty = self.analysis.types[&expression].clone();
+        match *ty.unqualified() {
+            Type::Array(..) | Type::Struct(_) => return self.address(expression),
+            _ => {},
+        }
+        match self.ast[expression].kind {
+            ExpressionKind::IntLiteral(value, _) => self.integer(&ty, value),
+            ExpressionKind::CharConstant(value) => self.integer(&ty, i64::from(value)),
+            ExpressionKind::SizeOf(ref operand) => {
+                let size = self.layouts.size_of(operand);
+                self.integer(&ty, size)
+            },
+            ExpressionKind::Variable(_) => {
+                let address = self.variable(expression);
+                self.load(&ty, &address)
+            },
+            ExpressionKind::Assign(operator, target, value) => self.assign(operator, target, value),
+            ExpressionKind::Unary(UnaryOp::AddressOf, operand) => self.address(operand),
+            ExpressionKind::Unary(UnaryOp::Dereference, _)
+            | ExpressionKind::Subscript(..)
+            | ExpressionKind::Member(..)
+            | ExpressionKind::PointerMember(..) => {
+                let address = self.address(expression);
+                self.load(&ty, &address)
+            },
+            ExpressionKind::Unary(operator @ UnaryOp::PreIncrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PreDecrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PostIncrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PostDecrement, operand) => self.increment(operator, operand),
+            ExpressionKind::Unary(operator, operand) => {
+                let value = self.expression(operand);
+                let ty = self.value_type(operand);
+                self.unary(operator, &ty, &value)
+            },
+            ExpressionKind::Cast { ref to, expr } => {
+                let value = self.expression(expr);
+                let from = self.value_type(expr);
+                self.convert(&value, &from, to.unqualified())
+            },
+            ExpressionKind::Binary(operator @ BinaryOp::LogicalAnd, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::LogicalOr, lhs, rhs) => self.logical(operator, lhs, rhs),
+            ExpressionKind::Binary(operator @ BinaryOp::Add, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::Subtract, lhs, rhs)
+                if self.is_pointer(lhs) || self.is_pointer(rhs) => self.pointer_arithmetic(operator, lhs, rhs),
+            ExpressionKind::Binary(operator @ BinaryOp::ShiftLeft, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::ShiftRight, lhs, rhs) => {
+                // Only the low bits of the amount are used, as the machines'
+                // shift instructions use them; LLVM would make any larger
+                // amount poison.
+                let operands = self.value_type(lhs);
+                let mask = self.layouts.size_of(&operands) * 8 - 1;
+                let value = self.expression(lhs);
+                let amount = match self.constant(rhs) {
+                    Some(amount) => (amount & mask).to_string(),
+                    None => {
+                        let amount = self.expression(rhs);
+                        let amount = self.convert(&amount, &self.value_type(rhs), &operands);
+                        let ir_type = self.ir_type(&operands);
+                        self.value(&format!("and {} {}, {}", ir_type, amount, mask))
+                    },
+                };
+                self.binary(operator, &operands, &value, &amount)
+            },
+            ExpressionKind::Binary(operator, lhs, rhs) => {
+                // A pointer may be compared with a null pointer constant.
+                let operands = match self.value_type(rhs) {
+                    ty @ Type::Pointer(_) => ty,
+                    _ => self.value_type(lhs),
+                };
+                let lhs_value = self.operand(lhs, &operands);
+                let rhs_value = self.operand(rhs, &operands);
+                self.binary(operator, &operands, &lhs_value, &rhs_value)
+            },
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                let (then_label, else_label, end) = (self.new_label("cond.true"), self.new_label("cond.false"), self.new_label("cond.end"));
+                self.condition(condition, &then_label, &else_label);
+                self.start(&then_label);
+                let then_value = self.operand(then, &ty);
+                let then_block = self.block.clone();
+                self.branch(&end);
+                self.start(&else_label);
+                let else_value = self.operand(otherwise, &ty);
+                let else_block = self.block.clone();
+                self.start(&end);
+                let ir_type = self.ir_type(&ty);
+                self.value(&format!("phi {} [ {}, %{} ], [ {}, %{} ]", ir_type, then_value, then_block, else_value, else_block))
+            },
+            ExpressionKind::Comma(ref expressions) => {
+                let mut value = String::new();
+                for &expression in expressions {
+                    value = self.expression(expression);
+                }
+                value
+            },
+            ExpressionKind::Call(ref name, ref arguments) => self.call(expression, name, arguments),
+            ExpressionKind::StringLiteral(_) => unreachable!("a string literal that isn't an array"),
+        }
+    }
+
+    /// The address of the lvalue `expression`.
+    fn address(&mut self, expression: ExprId) -> String {
+        match self.ast[expression].kind {
+            ExpressionKind::Variable(_) => self.variable(expression),
+            ExpressionKind::StringLiteral(ref bytes) => self.string(bytes),
+            ExpressionKind::Unary(UnaryOp::Dereference, pointer) => self.expression(pointer),
+            // `a[i]` is `*(a + i)`, whichever of the two is the pointer.
+            ExpressionKind::Subscript(array, index) => self.pointer_arithmetic(BinaryOp::Add, array, index),
+            ExpressionKind::Member(object, ref member) => {
+                let address = self.address(object);
+                let ty = self.analysis.types[&object].clone();
+                self.member(&ty, &address, member)
+            },
+            ExpressionKind::PointerMember(pointer, ref member) => {
+                let address = self.expression(pointer);
+                let ty = match *self.value_type(pointer).unqualified() {
+                    Type::Pointer(ref pointee) => (**pointee).clone(),
+                    ref ty => unreachable!("`->` applied to `{}`", ty),
+                };
+                self.member(&ty, &address, member)
+            },
+            ref kind => unimplemented!("codegen for the address of {:?}", kind),
+        }
+    }
+
+    /// The address of `member` of the struct of type `ty` at `address`.
+    fn member(&mut self, ty: &Type, address: &str, member: &str) -> String {
+        let tag = match *ty.unqualified() {
+            Type::Struct(ref tag) => tag,
+            ref ty => unreachable!("a member of `{}`", ty),
+        };
+        let index = self.members(tag).iter().position(|candidate| candidate.name == member).unwrap();
+        self.value(&format!("getelementptr %struct.{}, ptr {}, i32 0, i32 {}", tag, address, index))
+    }
+
+    /// Assigns `value` to `target`, returning the value assigned. With an
+    /// `operator`, assigns the result of applying it to both.
+    fn assign(&mut self, operator: Option<BinaryOp>, target: ExprId, value: ExprId) -> String {
+        let ty = self.analysis.types[&target].clone();
+        let address = self.address(target);
+        let value_type = self.value_type(value);
+        let mut result = match operator {
+            Some(_) => self.expression(value),
+            None => self.operand(value, &ty.decay()),
+        };
+        match operator {
+            Some(operator @ BinaryOp::Add) | Some(operator @ BinaryOp::Subtract) if ty.is_pointer() => {
+                let pointer = self.load(&ty, &address);
+                result = self.offset(&ty, &pointer, &value_type, &result, operator == BinaryOp::Subtract);
+            },
+            Some(operator) => {
+                // The target is converted like the left operand of `operator`,
+                // to the type the right one was, and the result back.
+                let current = self.load(&ty, &address);
+                let current = self.convert(&current, &ty.decay(), &value_type);
+                result = self.binary(operator, &value_type, &current, &result);
+                result = self.convert(&result, &value_type, &ty.decay());
+            },
+            None => {},
+        }
+        self.store(&ty, &result, &address);
+        result
+    }
+
+    /// Increments or decrements `target` by one, or by its pointee's size if it
+    /// is a pointer, returning its new or old value as `operator` says.
+    fn increment(&mut self, operator: UnaryOp, target: ExprId) -> String {
+        let ty = self.analysis.types[&target].clone();
+        let address = self.address(target);
+        let old = self.load(&ty, &address);
+        let decrement = matches!(operator, UnaryOp::PreDecrement | UnaryOp::PostDecrement);
+        let new = if ty.is_pointer() {
+            self.offset(&ty, &old, &Type::Long, if decrement { "-1" } else { "1" }, false)
+        } else {
+            let ir_type = self.ir_type(&ty);
+            self.value(&format!("{} {} {}, 1", if decrement { "sub" } else { "add" }, ir_type, old))
+        };
+        self.store(&ty, &new, &address);
+        if operator.is_postfix() { old } else { new }
+    }
+
+    /// Whether `expression`'s value is a pointer.
+    fn is_pointer(&self, expression: ExprId) -> bool {
+        self.value_type(expression).is_pointer()
+    }
+
+    /// Offsets `pointer`, of type `pointer_type`, by `index` of what it points
+    /// to, or `back` by that many. The index is an integer of type `index_type`.
+    fn offset(&mut self, pointer_type: &Type, pointer: &str, index_type: &Type, index: &str, back: bool) -> String {
+        let pointee = match *pointer_type.unqualified() {
+            Type::Pointer(ref pointee) => self.ir_type(pointee),
+            ref ty => unreachable!("the pointee of `{}`", ty),
+        };
+        let mut index = self.convert(index, index_type, &Type::Long);
+        if back {
+            index = self.value(&format!("sub i64 0, {}", index));
+        }
+        self.value(&format!("getelementptr {}, ptr {}, i64 {}", pointee, pointer, index))
+    }
+
+    /// Adds an integer to a pointer, subtracts one from a pointer, or subtracts
+    /// two pointers, giving the number of objects between them.
+    fn pointer_arithmetic(&mut self, operator: BinaryOp, lhs: ExprId, rhs: ExprId) -> String {
+        let (lhs_type, rhs_type) = (self.value_type(lhs), self.value_type(rhs));
+        let lhs_value = self.expression(lhs);
+        let rhs_value = self.expression(rhs);
+        match (lhs_type.is_pointer(), rhs_type.is_pointer()) {
+            (true, true) => {
+                let start = self.value(&format!("ptrtoint ptr {} to i64", lhs_value));
+                let end = self.value(&format!("ptrtoint ptr {} to i64", rhs_value));
+                let bytes = self.value(&format!("sub i64 {}, {}", start, end));
+                match self.layouts.size_of(&pointee(&lhs_type)) {
+                    1 => bytes,
+                    size => self.value(&format!("sdiv exact i64 {}, {}", bytes, size)),
+                }
+            },
+            (true, false) => self.offset(&lhs_type, &lhs_value, &rhs_type, &rhs_value, operator == BinaryOp::Subtract),
+            _ => self.offset(&rhs_type, &rhs_value, &lhs_type, &lhs_value, false),
+        }
+    }
+
+    /// Calls the function `name` with `arguments`, returning its return value.
+    /// `call` is the expression making the call.
+    fn call(&mut self, call: ExprId, name: &str, arguments: &[ExprId]) -> String {
+        // The arguments are evaluated from right to left, as the assembly
+        // backend evaluates them.
+        let function = match self.analysis.resolutions.get(&call) {
+            Some(&Symbol::Function(index)) => match self.items[index] {
+                TopLevel::Function(ref function) => Some(function),
+                _ => unreachable!("a call resolved to something other than a function"),
+            },
+            _ => None,
+        };
+        let types: Vec<_> = arguments
+            .iter()
+            .enumerate()
+            .map(|(position, &argument)| match function.and_then(|function| function.parameters.get(position)) {
+                Some(parameter) => parameter.ty.clone(),
+                None => self.value_type(argument),
+            })
+            .collect();
+        let mut values: Vec<_> = arguments.iter().zip(&types).rev().map(|(&argument, ty)| self.operand(argument, ty)).collect();
+        values.reverse();
+        let values: Vec<_> = values
+            .into_iter()
+            .zip(&types)
+            .map(|(value, ty)| format!("{} {}", self.abi_type(ty), value))
+            .collect();
+        // A variadic function's type has to be given with the call.
+        let callee = match function {
+            Some(function) => match *function {
+                Function { variadic: true, ref parameters, ref return_type, .. } => {
+                    let parameters: Vec<_> = parameters.iter().map(|parameter| self.ir_type(&parameter.ty)).collect();
+                    let parameters = parameters.into_iter().chain(Some("...".to_owned())).collect::<Vec<_>>().join(", ");
+                    format!("{} ({})", self.return_type(return_type), parameters)
+                },
+                Function { ref return_type, .. } => self.return_type(return_type),
+            },
+            None => {
+                if !self.undeclared.iter().any(|undeclared| undeclared == name) {
+                    self.undeclared.push(name.to_owned());
+                }
+                "i32 (...)".to_owned()
+            },
+        };
+        self.value(&format!("call {} @{}({})", callee, name, values.join(", ")))
+    }
+
+    /// Evaluates `lhs && rhs` or `lhs || rhs`, skipping `rhs` if `lhs` decides
+    /// the result, which is then the truth of whichever was evaluated last.
+    fn logical(&mut self, operator: BinaryOp, lhs: ExprId, rhs: ExprId) -> String {
+        let and = operator == BinaryOp::LogicalAnd;
+        let (rhs_label, end) = if and {
+            (self.new_label("land.rhs"), self.new_label("land.end"))
+        } else {
+            (self.new_label("lor.rhs"), self.new_label("lor.end"))
+        };
+        let lhs_truth = self.truth(lhs);
+        let lhs_block = self.block.clone();
+        if and {
+            self.terminate(&format!("br i1 {}, label %{}, label %{}", lhs_truth, rhs_label, end));
+        } else {
+            self.terminate(&format!("br i1 {}, label %{}, label %{}", lhs_truth, end, rhs_label));
+        }
+        self.start(&rhs_label);
+        let rhs_truth = self.truth(rhs);
+        let rhs_block = self.block.clone();
+        self.start(&end);
+        let truth = self.value(&format!("phi i1 [ {}, %{} ], [ {}, %{} ]", !and, lhs_block, rhs_truth, rhs_block));
+        self.value(&format!("zext i1 {} to i32", truth))
+    }
+
+    /// Converts `value` from type `from` to `to`.
+    fn convert(&mut self, value: &str, from: &Type, to: &Type) -> String {
+        let bits = |ty: &Type| match *ty.unqualified() {
+            Type::Char => Some(8),
+            Type::Int | Type::UnsignedInt => Some(32),
+            Type::Long | Type::UnsignedLong | Type::Pointer(_) => Some(64),
+            _ => None,
+        };
+        let (from_bits, to_bits) = match (bits(from), bits(to)) {
+            (Some(from_bits), Some(to_bits)) => (from_bits, to_bits),
+            _ => unimplemented!("codegen for conversions from `{}` to `{}`", from, to),
+        };
+        let (from_type, to_type) = (self.ir_type(from), self.ir_type(to));
+        match (from.is_pointer(), to.is_pointer()) {
+            (true, true) => value.to_owned(),
+            (true, false) => self.value(&format!("ptrtoint ptr {} to {}", value, to_type)),
+            (false, true) => {
+                // The integer is extended the way it would be to a `long` first.
+                let value = self.convert(value, from, &Type::Long);
+                self.value(&format!("inttoptr i64 {} to ptr", value))
+            },
+            _ if from_bits == to_bits => value.to_owned(),
+            _ if from_bits > to_bits => self.value(&format!("trunc {} {} to {}", from_type, value, to_type)),
+            _ => {
+                let extend = if from.is_signed() { "sext" } else { "zext" };
+                self.value(&format!("{} {} {} to {}", extend, from_type, value, to_type))
+            },
+        }
+    }
+
+    /// Applies `operator` to `value`, which is of type `operand`.
+    fn unary(&mut self, operator: UnaryOp, operand: &Type, value: &str) -> String {
+        let ir_type = self.ir_type(operand);
+        match operator {
+            UnaryOp::Negate => self.value(&format!("sub {} 0, {}", ir_type, value)),
+            UnaryOp::BitwiseNot => self.value(&format!("xor {} {}, -1", ir_type, value)),
+            UnaryOp::LogicalNot => {
+                let zero = self.zero(operand);
+                let truth = self.value(&format!("icmp eq {} {}, {}", ir_type, value, zero));
+                self.value(&format!("zext i1 {} to i32", truth))
+            },
+            operator => unreachable!("unary `{}` as an arithmetic operator", operator.symbol()),
+        }
+    }
+
+    /// The type `expression`'s value is used as: its own, decayed, or the one
+    /// it is implicitly converted to.
+    fn value_type(&self, expression: ExprId) -> Type {
+        match self.analysis.conversions.get(&expression) {
+            Some(ty) => ty.clone(),
+            None => self.analysis.types[&expression].decay(),
+        }
+    }
+
+    /// Applies `operator` to `lhs` and `rhs`, which are both of type `operands`
+    /// after conversion.
+    fn binary(&mut self, operator: BinaryOp, operands: &Type, lhs: &str, rhs: &str) -> String {
+        if !operands.is_scalar() {
+            unimplemented!("codegen for binary `{}` on `{}`", operator.symbol(), operands);
+        }
+        // Pointers compare as unsigned addresses.
+        let signed = operands.is_signed();
+        let instruction = match operator {
+            BinaryOp::Add => "add",
+            BinaryOp::Subtract => "sub",
+            BinaryOp::Multiply => "mul",
+            BinaryOp::ShiftLeft => "shl",
+            BinaryOp::ShiftRight if signed => "ashr",
+            BinaryOp::ShiftRight => "lshr",
+            BinaryOp::BitwiseAnd => "and",
+            BinaryOp::BitwiseXor => "xor",
+            BinaryOp::BitwiseOr => "or",
+            BinaryOp::Divide => return self.divide(operands, lhs, rhs, false),
+            BinaryOp::Modulo => return self.divide(operands, lhs, rhs, true),
+            BinaryOp::Equal => "icmp eq",
+            BinaryOp::NotEqual => "icmp ne",
+            BinaryOp::LessThan => if signed { "icmp slt" } else { "icmp ult" },
+            BinaryOp::LessEqual => if signed { "icmp sle" } else { "icmp ule" },
+            BinaryOp::GreaterThan => if signed { "icmp sgt" } else { "icmp ugt" },
+            BinaryOp::GreaterEqual => if signed { "icmp sge" } else { "icmp uge" },
+            operator => unreachable!("binary `{}` as an arithmetic operator", operator.symbol()),
+        };
+        let ir_type = self.ir_type(operands);
+        let result = self.value(&format!("{} {} {}, {}", instruction, ir_type, lhs, rhs));
+        if instruction.starts_with("icmp") {
+            self.value(&format!("zext i1 {} to i32", result))
+        } else {
+            result
+        }
+    }
+
+    /// The value of `expression` if it is made of constants, as the type it
+    /// is used as.
+    fn constant(&self, expression: ExprId) -> Option<i64> {
+        let evaluator = fold::Evaluator { types: &self.analysis.types, conversions: &self.analysis.conversions };
+        evaluator.operand(self.ast, expression)
+    }
+
+    /// Divides `lhs` by `rhs`, which are of type `operands`, giving the
+    /// quotient or the `remainder`. With checked arithmetic, a zero divisor
+    /// calls `DIVISION_BY_ZERO`, and dividing the most negative value by -1,
+    /// which LLVM leaves undefined, wraps around.
+    fn divide(&mut self, operands: &Type, lhs: &str, rhs: &str, remainder: bool) -> String {
+        let signed = operands.is_signed();
+        let instruction = match (signed, remainder) {
+            (true, false) => "sdiv",
+            (true, true) => "srem",
+            (false, false) => "udiv",
+            (false, true) => "urem",
+        };
+        let ir_type = self.ir_type(operands);
+        if !self.options.checked_arithmetic {
+            return self.value(&format!("{} {} {}, {}", instruction, ir_type, lhs, rhs));
+        }
+        self.division_checked = true;
+        let zero = self.value(&format!("icmp eq {} {}, 0", ir_type, rhs));
+        let (fail, divide) = (self.new_label("div.zero"), self.new_label("div.ok"));
+        self.terminate(&format!("br i1 {}, label %{}, label %{}", zero, fail, divide));
+        self.start(&fail);
+        self.emit(&format!("call void {}()", DIVISION_BY_ZERO));
+        self.terminate("unreachable");
+        self.start(&divide);
+        if !signed {
+            return self.value(&format!("{} {} {}, {}", instruction, ir_type, lhs, rhs));
+        }
+        let minus_one = self.value(&format!("icmp eq {} {}, -1", ir_type, rhs));
+        let divisor = self.value(&format!("select i1 {}, {} 1, {} {}", minus_one, ir_type, ir_type, rhs));
+        let result = self.value(&format!("{} {} {}, {}", instruction, ir_type, lhs, divisor));
+        let wrapped = if remainder { "0".to_owned() } else { self.value(&format!("sub {} 0, {}", ir_type, lhs)) };
+        self.value(&format!("select i1 {}, {} {}, {} {}", minus_one, ir_type, wrapped, ir_type, result))
+    }
+
+    /// The definition of `DIVISION_BY_ZERO`, which writes a message to standard
+    /// error and exits.
+    fn division_by_zero(&mut self) -> String {
+        let message = b"division by zero\n";
+        let string = self.string(message);
+        format!(
+            "define private void {}() noreturn {{\n  %1 = call i64 @write(i32 2, ptr {}, i64 {})\n  call void @exit(i32 {})\n  unreachable\n}}\n",
+            DIVISION_BY_ZERO,
+            string,
+            message.len(),
+            DIVISION_BY_ZERO_STATUS,
+        )
+    }
+}
+
+/// What the pointer type `pointer` points to.
+fn pointee(pointer: &Type) -> Type {
+    match *pointer.unqualified() {
+        Type::Pointer(ref pointee) => (**pointee).clone(),
+        ref ty => unreachable!("the pointee of `{}`", ty),
+    }
+}
+
+/// Writes `bytes` as the contents of an LLVM string constant: printable
+/// characters other than `"` and `\\` as they are, and any other byte as a
+/// hexadecimal escape.
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for &byte in bytes {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => escaped.push(byte as char),
+            _ => write!(escaped, "\\{:02X}", byte).unwrap(),
+        }
+    }
+    escaped
+}
+
+/// The target triple for the architecture and operating system `options` say.
+fn target_triple(options: &Options) -> &'static str {
+    match (options.target.arch, options.target.os) {
+        (Architecture::X86_64, TargetOs::Linux) => "x86_64-pc-linux-gnu",
+        (Architecture::X86_64, TargetOs::MacOs) => "x86_64-apple-macosx",
+        (Architecture::X86_64, TargetOs::Windows) => "x86_64-w64-windows-gnu",
+        (Architecture::Aarch64, TargetOs::Linux) => "aarch64-unknown-linux-gnu",
+        (Architecture::Aarch64, TargetOs::MacOs) => "arm64-apple-macosx",
+        (Architecture::Aarch64, TargetOs::Windows) => unimplemented!("codegen for Windows on AArch64"),
+    }
+}
+
+/// Generates the LLVM IR module for `program`, whose `analysis` has found no
+/// errors and whose static objects `statics` has labelled.
+pub fn generate(program: &Program, analysis: &Analysis, statics: &StaticsBuilder, options: &Options) -> String {
+    let mut generator = Generator {
+        ast: &program.ast,
+        items: &program.items,
+        analysis,
+        layouts: Layouts::new(program),
+        options,
+        statics: statics.labels.clone(),
+        strings: Vec::new(),
+        undeclared: Vec::new(),
+        allocas: String::new(),
+        code: String::new(),
+        locals: HashMap::new(),
+        names: HashMap::new(),
+        temps: 0,
+        labels: 0,
+        block: String::new(),
+        terminated: false,
+        breaks: Vec::new(),
+        continues: Vec::new(),
+        cases: HashMap::new(),
+        goto_labels: HashMap::new(),
+        returns: Type::Int,
+        division_checked: false,
+    };
+
+    let mut functions = Vec::new();
+    for (index, item) in program.items.iter().enumerate() {
+        if let TopLevel::Function(ref function) = *item {
+            if let Some(ref body) = function.body {
+                functions.push(generator.function(index, function, body));
+            }
+        }
+    }
+    if generator.division_checked {
+        functions.push(generator.division_by_zero());
+    }
+    let globals: Vec<_> = statics.objects
+        .iter()
+        .map(|object| {
+            let external = !object.local && !statics.internal.contains(&object.label);
+            generator.static_object(object, external)
+        })
+        .collect();
+
+    let path = options.source_path.as_deref().unwrap_or("<source>");
+    let mut module = format!("source_filename = \"{}\"\ntarget triple = \"{}\"\n", escape(path.as_bytes()), target_triple(options));
+    let types: Vec<_> = program.items
+        .iter()
+        .filter_map(|item| match *item {
+            TopLevel::Struct(ref definition) => {
+                let members: Vec<_> = definition.members.iter().map(|member| generator.ir_type(&member.ty)).collect();
+                Some(format!("%struct.{} = type {{ {} }}\n", definition.name, members.join(", ")))
+            },
+            _ => None,
+        })
+        .collect();
+    if !types.is_empty() {
+        module.push('\n');
+        module.extend(types);
+    }
+    if !globals.is_empty() || !generator.strings.is_empty() {
+        module.push('\n');
+        module.extend(globals);
+        for (index, bytes) in generator.strings.iter().enumerate() {
+            writeln!(
+                module,
+                "@.str.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"",
+                index,
+                bytes.len() + 1,
+                escape(bytes),
+            ).unwrap();
+        }
+    }
+    for function in functions {
+        module.push('\n');
+        module.push_str(&function);
+    }
+
+    // Every function called or declared that isn't defined here is declared,
+    // once.
+    let mut declared: HashSet<&str> = program.items
+        .iter()
+        .filter_map(|item| match *item {
+            TopLevel::Function(ref function) if function.body.is_some() => Some(function.name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut declarations = Vec::new();
+    for item in &program.items {
+        if let TopLevel::Function(ref function) = *item {
+            if declared.insert(&function.name) {
+                let parameters: Vec<_> = function.parameters
+                    .iter()
+                    .map(|parameter| generator.abi_type(&parameter.ty))
+                    .chain(if function.variadic { Some("...".to_owned()) } else { None })
+                    .collect();
+                let return_type = generator.return_type(&function.return_type);
+                declarations.push(format!("declare {} @{}({})\n", return_type, function.name, parameters.join(", ")));
+            }
+        }
+    }
+    for name in &generator.undeclared {
+        if declared.insert(name) {
+            declarations.push(format!("declare i32 @{}(...)\n", name));
+        }
+    }
+    if generator.division_checked {
+        for &(name, declaration) in &[("write", "declare i64 @write(i32, ptr, i64)\n"), ("exit", "declare void @exit(i32)\n")] {
+            if declared.insert(name) {
+                declarations.push(declaration.to_owned());
+            }
+        }
+    }
+    if !declarations.is_empty() {
+        module.push('\n');
+        module.extend(declarations);
+    }
+    module
+}
+
+#[cfg(test)]
+mod test {
+    use codegen::{generate_with, Architecture, Format, Options, TargetOs, TargetSpec};
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn generate_ir(source: &str) -> String {
+        let options = Options {
+            target: TargetSpec::new(Architecture::X86_64, TargetOs::Linux),
+            format: Format::LlvmIr,
+            ..Options::default()
+        };
+        generate_with(&parse_program(&lex_str(source).unwrap()).unwrap(), &options)
+    }
+
+    #[test]
+    fn locals_live_in_allocas() {
+        let source = "int add(int a, int b) { int c = a + b; return c; }\nint main() { return add(1, 2); }";
+        assert_eq!(generate_ir(source), r#"source_filename = "<source>"
+target triple = "x86_64-pc-linux-gnu"
+
+define i32 @add(i32 %0, i32 %1) {
+  %a = alloca i32
+  %b = alloca i32
+  %c = alloca i32
+  store i32 %0, ptr %a
+  store i32 %1, ptr %b
+  %3 = load i32, ptr %a
+  %4 = load i32, ptr %b
+  %5 = add i32 %3, %4
+  store i32 %5, ptr %c
+  %6 = load i32, ptr %c
+  ret i32 %6
+}
+
+define i32 @main() {
+  %1 = call i32 @add(i32 1, i32 2)
+  ret i32 %1
+}
+"#);
+    }
+
+    #[test]
+    fn control_flow_branches_between_blocks() {
+        let source = "int main(int n) { int i = 0; while (i < n && i != 3) { if (!i) continue; i++; } return i; }";
+        assert_eq!(generate_ir(source), r#"source_filename = "<source>"
+target triple = "x86_64-pc-linux-gnu"
+
+define i32 @main(i32 %0) {
+  %n = alloca i32
+  %i = alloca i32
+  store i32 %0, ptr %n
+  store i32 0, ptr %i
+  br label %while.cond1
+while.cond1:
+  %2 = load i32, ptr %i
+  %3 = load i32, ptr %n
+  %4 = icmp slt i32 %2, %3
+  %5 = zext i1 %4 to i32
+  %6 = icmp ne i32 %5, 0
+  br i1 %6, label %land.rhs4, label %land.end5
+land.rhs4:
+  %7 = load i32, ptr %i
+  %8 = icmp ne i32 %7, 3
+  %9 = zext i1 %8 to i32
+  %10 = icmp ne i32 %9, 0
+  br label %land.end5
+land.end5:
+  %11 = phi i1 [ false, %while.cond1 ], [ %10, %land.rhs4 ]
+  %12 = zext i1 %11 to i32
+  %13 = icmp ne i32 %12, 0
+  br i1 %13, label %while.body2, label %while.end3
+while.body2:
+  %14 = load i32, ptr %i
+  %15 = icmp eq i32 %14, 0
+  %16 = zext i1 %15 to i32
+  %17 = icmp ne i32 %16, 0
+  br i1 %17, label %if.then6, label %if.end7
+if.then6:
+  br label %while.cond1
+if.end7:
+  %18 = load i32, ptr %i
+  %19 = add i32 %18, 1
+  store i32 %19, ptr %i
+  br label %while.cond1
+while.end3:
+  %20 = load i32, ptr %i
+  ret i32 %20
+}
+"#);
+    }
+
+    #[test]
+    fn globals_strings_and_declarations() {
+        let source = "struct pair { char a; long b; };\nstruct pair p = { 1, 2 };\nstatic char *s = \"a\\\"b\";\n\
+                      int puts(const char *s);\nint main() { struct pair *q = &p; puts(s); return q->b + strlen(s); }";
+        assert_eq!(generate_ir(source), r#"source_filename = "<source>"
+target triple = "x86_64-pc-linux-gnu"
+
+%struct.pair = type { i8, i64 }
+
+@p = global %struct.pair { i8 1, i64 2 }
+@s = internal global ptr @.str.0
+@.str.0 = private unnamed_addr constant [4 x i8] c"a\22b\00"
+
+define i32 @main() {
+  %q = alloca ptr
+  store ptr @p, ptr %q
+  %1 = load ptr, ptr @s
+  %2 = call i32 @puts(ptr %1)
+  %3 = load ptr, ptr %q
+  %4 = getelementptr %struct.pair, ptr %3, i32 0, i32 1
+  %5 = load i64, ptr %4
+  %6 = load ptr, ptr @s
+  %7 = call i32 (...) @strlen(ptr %6)
+  %8 = sext i32 %7 to i64
+  %9 = add i64 %5, %8
+  %10 = trunc i64 %9 to i32
+  ret i32 %10
+}
+
+declare i32 @puts(ptr)
+declare i32 @strlen(...)
+"#);
+    }
+
+    #[test]
+    fn shadowed_locals_and_unreachable_code() {
+        let ir = generate_ir("int main() { int x = 1; { int x = 2; return x; x = 3; } }");
+        assert!(ir.contains("  %x = alloca i32\n  %x.1 = alloca i32\n"), "{}", ir);
+        assert!(ir.contains("  ret i32 %1\nafter.jump1:\n  store i32 3, ptr %x.1\n  ret i32 0\n"), "{}", ir);
+    }
+
+    #[test]
+    fn checked_division_calls_a_function_that_exits() {
+        let options = Options {
+            target: TargetSpec::new(Architecture::X86_64, TargetOs::Linux),
+            format: Format::LlvmIr,
+            checked_arithmetic: true,
+            ..Options::default()
+        };
+        let ir = generate_with(&parse_program(&lex_str("int main(int x) { return 7 / x; }").unwrap()).unwrap(), &options);
+        assert!(ir.contains("  br i1 %3, label %div.zero1, label %div.ok2\ndiv.zero1:\n  call void @.division_by_zero()\n  unreachable\n"), "{}", ir);
+        assert!(ir.contains("define private void @.division_by_zero() noreturn {\n"), "{}", ir);
+        assert!(ir.ends_with("declare i64 @write(i32, ptr, i64)\ndeclare void @exit(i32)\n"), "{}", ir);
+    }
+}
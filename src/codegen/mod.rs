@@ -0,0 +1,2773 @@
+//! The third stage: generates assembly from a program that semantic analysis
+//! has accepted, for the GNU assembler. The output can be written to a `.s`
+//! file and assembled and linked with `gcc`. It is for x86-64 or AArch64, as
+//! `Options::target` says; the generator here lowers each function to
+//! the operations of a simple accumulator machine, and a `Machine` for each
+//! architecture writes those as instructions. x86-64 assembly is in AT&T
+//! syntax, or in Intel syntax with `Options::syntax`.
+//!
+//! Every expression leaves its value in the machine's accumulator, all 64 bits
+//! of it if it is a `long` or a pointer. A `char` is kept sign-extended to 32
+//! bits, so that it is already an `int` when it is promoted to one; it is only
+//! truncated to a byte when it is stored. An operator evaluates its operand
+//! into the accumulator and then works on it there. A binary operator evaluates
+//! its left operand first and saves it to a temporary, then evaluates its right
+//! operand, moves it to the scratch register, and restores the left one to the
+//! accumulator, so that it works on the two with the result left in the
+//! accumulator. However deeply expressions nest, each temporary is restored by
+//! the operator that saved it. Once a whole function is lowered, each temporary
+//! is given a register where one is free and a slot in the frame otherwise, as
+//! `registers` describes.
+//!
+//! Each function keeps a frame pointer pointing at the base of its frame, and
+//! every local variable has a slot in the frame at a fixed negative offset from
+//! it. Without a frame pointer, as `Options::omit_frame_pointer` decides, an
+//! x86-64 function addresses the slots from the stack pointer instead, as if
+//! it had one. Every local in the function, in whatever block, gets a slot of
+//! its own, so slots never need to be tracked in and out of scope. The temporaries that
+//! didn't get a register have slots below the locals.
+//!
+//! A variable is read and written in its slot or at its label directly. Any
+//! other lvalue has its address computed into the accumulator first; one being
+//! assigned to has its address saved while the value is evaluated, and
+//! restored again to store through.
+//!
+//! An array is never a value of its own: wherever one is used in an expression
+//! its address is computed instead, which is the pointer it decays to. `a[i]`
+//! is addressed as `*(a + i)`, so indexing an array of arrays gives the address
+//! of a row, which is indexed in turn.
+//!
+//! Struct members are laid out in order, each at the next offset its alignment
+//! allows, and are addressed as the struct's address plus their offset, whether
+//! through `.` or `->`. Structs are only ever accessed a member at a time:
+//! assigning, passing, and returning whole structs isn't supported yet, and
+//! analysis rejects them.
+//!
+//! The arguments of a call are evaluated and saved from right to left, then
+//! the machine moves them to where its calling convention wants them. The
+//! callee copies its register parameters into slots of its own on entry, while
+//! the ones passed on the stack are used where they are.
+//!
+//! Globals and `static` locals are placed in `.data`, or in `.bss` if they
+//! start as all zeros, and are addressed relative to the program counter. A
+//! global's label is its name; a `static` local's is its name followed by a
+//! number, since several functions may have one of the same name.
+//!
+//! A `switch` jumps through a table of labels when its `case` values are dense
+//! enough, and compares its value with each of them in turn otherwise.
+//!
+//! The local labels control flow jumps to are named for the function they are
+//! in and what they mark, and numbered through the whole file, as in
+//! `.Lmain_end_3`.
+//!
+//! How a division by zero behaves depends on the machine. With
+//! `Options::checked_arithmetic` the divisor is checked first instead, and the
+//! program exits with a message.
+//!
+//! String literals are placed in `.rodata`, each distinct one once, under local
+//! labels of their own.
+//!
+//! Unless `Options::debug_info` turns it off, each statement's instructions are
+//! marked with the line and column it starts at, and on Linux each function
+//! with its type and size, so that debuggers and profilers can map the code
+//! back to the source.
+//!
+//! With `Options::source_comments`, the source line each statement starts on
+//! is quoted in a comment before its instructions.
+//!
+//! At `OptLevel::O1`, the program's constant expressions and branches are
+//! folded first, as `fold` describes, whatever it is generated as. The jumps,
+//! labels, and code that the lowered code of each function doesn't need are
+//! removed from it, as `jumps` describes, and so are the stores to locals that
+//! are never read, as `dead_stores` describes. Its x86-64 instructions go
+//! through a peephole pass before they are written, which removes the ones
+//! that do nothing.
+//!
+//! At `OptLevel::O2`, the constants that locals are known to hold are also
+//! propagated to where they are read, as `propagate` describes, until nothing
+//! more folds. The calls to small functions are replaced with their bodies in
+//! the assembly, as `inline` describes, multiplying, dividing, and taking the
+//! remainder by a constant power of two are done with shifts, as `strength`
+//! describes, and the frame pointer is omitted.
+//!
+//! `passes` says which pass runs when, and `Options::disabled_passes` turns
+//! any of them off.
+//!
+//! The assembly is for Linux, macOS, or Windows, whose assemblers differ in how
+//! symbols, local labels, and sections are written; `TargetOs` says which. It
+//! also decides the calling convention on x86-64, unless the `TargetSpec`
+//! says otherwise.
+//!
+//! With `Options::format`, the program is generated as LLVM IR instead, by
+//! the backend in `llvm`, or as WebAssembly, by the one in `wasm`.
+//!
+//! Code can't be generated for some of the language yet, in some formats;
+//! `check` finds what of a program can't, which `generate` panics on.
+
+mod aarch64;
+mod dead_stores;
+mod fold;
+mod inline;
+mod ir;
+mod jumps;
+mod llvm;
+mod passes;
+mod propagate;
+mod registers;
+mod strength;
+mod wasm;
+mod x86_64;
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::mem;
+use ast::*;
+use ast::visit::{self, Visitor};
+use const_eval::ConstEnv;
+use resolve::Symbol;
+use semantic::{self, Analysis};
+use statics::{self, StaticValue, StaticValues};
+use source::LineIndex;
+use typecheck::promote;
+use self::aarch64::Aarch64;
+use self::inline::Inlinable;
+use self::ir::{Instruction, Temp};
+use self::passes::{Lowered, Lowering, PassManager};
+use self::registers::{Home, Registers};
+use self::x86_64::X86_64;
+
+pub use self::passes::{names as pass_names, Timings};
+pub use self::x86_64::Syntax;
+
+/// Where each local variable and parameter of a function lives: an offset
+/// from the frame pointer.
+type Frame = HashMap<Symbol, i64>;
+
+/// Where the members of a struct are.
+struct StructLayout {
+    /// The offset of each member from the start of the struct, by name.
+    offsets: HashMap<String, i64>,
+    /// The strictest alignment of any member.
+    alignment: i64,
+}
+
+/// The sizes and alignments of types, with every struct in the program laid
+/// out. Each member is placed at the first offset after the one before it that
+/// is a multiple of its alignment, and the struct's size is rounded up to a
+/// multiple of its alignment, so that the members of every element of an array
+/// of them are aligned too.
+struct Layouts<'a> {
+    /// Knows the size of every struct.
+    env: ConstEnv<'a>,
+    structs: HashMap<String, StructLayout>,
+}
+
+impl<'a> Layouts<'a> {
+    fn new(program: &'a Program) -> Layouts<'a> {
+        let mut layouts = Layouts { env: ConstEnv::new(&program.ast), structs: HashMap::new() };
+        for item in &program.items {
+            if let TopLevel::Struct(ref definition) = *item {
+                // A struct can only contain structs defined before it.
+                let mut layout = StructLayout { offsets: HashMap::new(), alignment: 1 };
+                let mut size = 0;
+                for member in &definition.members {
+                    let alignment = layouts.alignment(&member.ty);
+                    let offset = round_up(size, alignment);
+                    layout.offsets.insert(member.name.clone(), offset);
+                    layout.alignment = layout.alignment.max(alignment);
+                    size = offset + layouts.size_of(&member.ty);
+                }
+                layouts.env.struct_sizes.insert(definition.name.clone(), round_up(size, layout.alignment));
+                layouts.structs.insert(definition.name.clone(), layout);
+            }
+        }
+        layouts
+    }
+
+    fn size_of(&self, ty: &Type) -> i64 {
+        self.env.size_of(ty).unwrap()
+    }
+
+    /// The alignment of `ty`, which for a scalar is its size.
+    fn alignment(&self, ty: &Type) -> i64 {
+        match *ty.unqualified() {
+            Type::Array(ref element, _) => self.alignment(element),
+            Type::Struct(ref tag) => self.structs[tag].alignment,
+            ref ty => self.size_of(ty),
+        }
+    }
+
+    /// The offset of `member` from the start of a `struct tag`.
+    fn offset_of(&self, tag: &str, member: &str) -> i64 {
+        self.structs[tag].offsets[member]
+    }
+}
+
+/// Lays out the frame of a function, giving a slot to every parameter passed
+/// in a register and every declaration in its body with automatic storage.
+struct FrameBuilder<'a, 'b: 'a> {
+    layouts: &'a Layouts<'b>,
+    inlined: &'a HashMap<String, Inlinable>,
+    frame: Frame,
+    /// The bytes of the frame used so far.
+    size: i64,
+}
+
+impl<'a, 'b> FrameBuilder<'a, 'b> {
+    /// Gives `symbol` a slot big enough for a `ty`.
+    fn allocate(&mut self, symbol: Symbol, ty: &Type) {
+        let alignment = self.layouts.alignment(ty);
+        self.size = round_up(self.size + self.layouts.size_of(ty), alignment);
+        self.frame.insert(symbol, -self.size);
+    }
+}
+
+impl<'a, 'b> Visitor for FrameBuilder<'a, 'b> {
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        let ty = &ast[declaration].ty;
+        if ast[declaration].storage.is_none() {
+            self.allocate(Symbol::Variable(declaration), ty);
+        }
+        visit::walk_declaration(self, ast, declaration);
+    }
+
+    /// Gives the parameters of each function inlined here a slot, once, and
+    /// looks for the calls it inlines in turn.
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        if let ExpressionKind::Call(ref name, _) = ast[expression].kind {
+            if let Some(inlined) = self.inlined.get(name) {
+                for (index, ty) in inlined.parameters.iter().enumerate() {
+                    let symbol = Symbol::Parameter { function: inlined.index, index };
+                    if !self.frame.contains_key(&symbol) {
+                        self.allocate(symbol, ty);
+                    }
+                }
+                self.visit_expr(ast, inlined.body);
+            }
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// An object with static storage duration to define.
+struct StaticObject {
+    label: String,
+    /// The declaration whose value the object starts with: one with an
+    /// initializer if there is one.
+    declaration: DeclId,
+    /// Whether this is a `static` local, which is never visible to other files.
+    local: bool,
+}
+
+/// Labels the objects with static storage duration, and finds the declaration
+/// that defines each one. A global may be declared any number of times, but is
+/// defined once.
+struct StaticsBuilder<'a> {
+    values: &'a StaticValues,
+    /// Whether the walk is inside a function body.
+    local: bool,
+    /// The label of every declaration of an object with static storage duration.
+    labels: HashMap<DeclId, String>,
+    /// The objects to define, in the order they are first declared.
+    objects: Vec<StaticObject>,
+    /// The globals with internal linkage, which some declaration says are `static`.
+    internal: HashSet<String>,
+}
+
+impl<'a> Visitor for StaticsBuilder<'a> {
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        self.local = true;
+        visit::walk_function(self, ast, function);
+        self.local = false;
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, id: DeclId) {
+        let declaration = &ast[id];
+        let label = match declaration.storage {
+            None if self.local => None,
+            Some(StorageClass::Static) if self.local => Some(format!("{}.{}", declaration.name, id.index())),
+            Some(StorageClass::Static) => {
+                self.internal.insert(declaration.name.clone());
+                Some(declaration.name.clone())
+            },
+            _ => Some(declaration.name.clone()),
+        };
+        if let Some(label) = label {
+            if self.values.contains_key(&id) {
+                match self.objects.iter_mut().find(|object| object.label == label) {
+                    Some(object) => if declaration.initializer.is_some() {
+                        object.declaration = id;
+                    },
+                    None => self.objects.push(StaticObject { label: label.clone(), declaration: id, local: self.local }),
+                }
+            }
+            self.labels.insert(id, label);
+        }
+        visit::walk_declaration(self, ast, id);
+    }
+}
+
+/// The operating system the assembly is for, which decides the dialect of the
+/// assembler and the object format it makes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TargetOs {
+    /// ELF, assembled by the GNU assembler.
+    Linux,
+    /// Mach-O, assembled by Apple's assembler. Every symbol's name has a `_`
+    /// in front of it, local labels start with `L` rather than `.L`, and the
+    /// sections have different names.
+    MacOs,
+    /// PE/COFF, assembled by the GNU assembler from MinGW. Symbols are named as
+    /// on Linux, since only 32-bit Windows puts a `_` in front of them, but
+    /// read-only data goes in `.rdata`.
+    Windows,
+}
+
+impl TargetOs {
+    /// The operating system the compiler itself runs on, or Linux if it is
+    /// none of them.
+    pub fn host() -> TargetOs {
+        if cfg!(target_os = "macos") {
+            TargetOs::MacOs
+        } else if cfg!(windows) {
+            TargetOs::Windows
+        } else {
+            TargetOs::Linux
+        }
+    }
+
+    /// The calling convention functions follow on this operating system.
+    pub fn calling_convention(self) -> CallingConvention {
+        match self {
+            TargetOs::Linux | TargetOs::MacOs => CallingConvention::SystemV,
+            TargetOs::Windows => CallingConvention::Windows,
+        }
+    }
+
+    /// The label of the symbol called `name` in C.
+    fn symbol(self, name: &str) -> String {
+        match self {
+            TargetOs::Linux | TargetOs::Windows => name.to_owned(),
+            TargetOs::MacOs => format!("_{}", name),
+        }
+    }
+
+    /// The label that `name` is made into for a label local to the file, which
+    /// doesn't go in the symbol table.
+    fn local_label(self, name: &str) -> String {
+        match self {
+            TargetOs::Linux | TargetOs::Windows => format!(".L{}", name),
+            TargetOs::MacOs => format!("L{}", name),
+        }
+    }
+}
+
+impl Default for TargetOs {
+    fn default() -> TargetOs {
+        TargetOs::host()
+    }
+}
+
+/// How arguments are passed to x86-64 functions, and which registers a
+/// function must preserve for its caller.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CallingConvention {
+    /// The System V AMD64 ABI, which Linux and macOS follow.
+    SystemV,
+    /// The Microsoft x64 calling convention.
+    Windows,
+}
+
+/// The architecture to generate code for.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Architecture {
+    X86_64,
+    Aarch64,
+}
+
+impl Architecture {
+    /// The architecture the compiler itself runs on, or x86-64 if it is
+    /// neither.
+    pub fn host() -> Architecture {
+        if cfg!(target_arch = "aarch64") { Architecture::Aarch64 } else { Architecture::X86_64 }
+    }
+}
+
+impl Default for Architecture {
+    fn default() -> Architecture {
+        Architecture::host()
+    }
+}
+
+/// The targets `TargetSpec::from_name` knows, by name.
+pub const TARGETS: &[&str] = &["x86_64-linux", "x86_64-macos", "x86_64-windows", "aarch64-linux", "aarch64-macos"];
+
+/// What to generate code for: the architecture, the operating system, and the
+/// calling convention functions follow.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TargetSpec {
+    pub arch: Architecture,
+    pub os: TargetOs,
+    /// The calling convention on x86-64, which is the operating system's
+    /// unless it is changed.
+    pub calling_convention: CallingConvention,
+}
+
+impl TargetSpec {
+    /// The target `os` on `arch`, with its usual calling convention.
+    pub fn new(arch: Architecture, os: TargetOs) -> TargetSpec {
+        TargetSpec { arch, os, calling_convention: os.calling_convention() }
+    }
+
+    /// The target the compiler itself runs on.
+    pub fn host() -> TargetSpec {
+        TargetSpec::new(Architecture::host(), TargetOs::host())
+    }
+
+    /// The target called `name`, one of `TARGETS`, as in `--target
+    /// aarch64-macos`.
+    pub fn from_name(name: &str) -> Option<TargetSpec> {
+        if !TARGETS.contains(&name) {
+            return None;
+        }
+        let (arch, os) = name.split_at(name.find('-')?);
+        let arch = match arch {
+            "x86_64" => Architecture::X86_64,
+            _ => Architecture::Aarch64,
+        };
+        let os = match os {
+            "-linux" => TargetOs::Linux,
+            "-macos" => TargetOs::MacOs,
+            _ => TargetOs::Windows,
+        };
+        Some(TargetSpec::new(arch, os))
+    }
+
+    /// The name of the target, as `from_name` knows it, whatever its calling
+    /// convention.
+    pub fn name(self) -> String {
+        let arch = match self.arch {
+            Architecture::X86_64 => "x86_64",
+            Architecture::Aarch64 => "aarch64",
+        };
+        let os = match self.os {
+            TargetOs::Linux => "linux",
+            TargetOs::MacOs => "macos",
+            TargetOs::Windows => "windows",
+        };
+        format!("{}-{}", arch, os)
+    }
+}
+
+impl Default for TargetSpec {
+    fn default() -> TargetSpec {
+        TargetSpec::host()
+    }
+}
+
+/// How much to optimize the generated code.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum OptLevel {
+    /// Generate each construct the same way wherever it is.
+    #[default]
+    O0,
+    /// Fold constants, remove jumps and code that aren't needed and stores
+    /// that are never read, and clean up the instructions the construct at a
+    /// time approach leaves redundant: x86-64 code is rewritten by its
+    /// peephole pass.
+    O1,
+    /// Do all of that, propagate constants through locals, use shifts for
+    /// powers of two, replace the calls to small functions with their bodies,
+    /// and omit the frame pointer on x86-64.
+    O2,
+}
+
+/// What debug information to generate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DebugInfo {
+    /// Which line and column of the source each statement's instructions come
+    /// from, with `.file` and `.loc`, which the assembler turns into a DWARF
+    /// line table for debuggers and profilers to read.
+    #[default]
+    Lines,
+    Off,
+}
+
+/// What to generate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Format {
+    /// Assembly for the GNU assembler.
+    #[default]
+    Assembly,
+    /// Textual LLVM IR, as `llvm` describes.
+    LlvmIr,
+    /// The WebAssembly text format, as `wasm` describes.
+    Wat,
+}
+
+/// How to generate code.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Whether to generate assembly, LLVM IR, or WebAssembly, by default
+    /// assembly.
+    pub format: Format,
+    /// The target to generate code for, by default the host.
+    pub target: TargetSpec,
+    /// The syntax to write x86-64 instructions in, by default AT&T.
+    pub syntax: Syntax,
+    /// Whether to keep every temporary in a slot in the frame rather than in a
+    /// register, as if the machine had none to spare.
+    pub spill_temporaries: bool,
+    /// How hard to optimize the code, by default not at all.
+    pub opt_level: OptLevel,
+    /// The debug information to generate, by default the source position of
+    /// each statement.
+    pub debug_info: DebugInfo,
+    /// The path of the source file, which the debug information refers to. It
+    /// is `<source>` without one.
+    pub source_path: Option<String>,
+    /// The text of the source file, which `source_comments` quotes.
+    pub source: Option<String>,
+    /// Whether to write the source line each statement starts on as a comment
+    /// before its instructions, whenever the line changes, so that the
+    /// assembly can be read alongside the source. It needs `source`.
+    pub source_comments: bool,
+    /// Whether to check the divisor of every `/` and `%` first, so that dividing
+    /// by zero exits with a message rather than raising `SIGFPE` or giving a
+    /// meaningless result, and dividing the most negative value by -1 wraps
+    /// around.
+    pub checked_arithmetic: bool,
+    /// The names of the passes not to run, from `pass_names`, whatever
+    /// `opt_level` says.
+    pub disabled_passes: Vec<String>,
+    /// Whether x86-64 functions address their frames from `%rsp` rather than
+    /// keep `%rbp` pointing at them, which frees two instructions of each
+    /// prologue. Without it, they do at `OptLevel::O2` and don't below it.
+    /// AArch64 functions always keep their frame pointer. Line information is
+    /// the same either way, but since no unwind information is generated, a
+    /// debugger or profiler can only walk the stack through functions that
+    /// keep it.
+    pub omit_frame_pointer: Option<bool>,
+}
+
+/// The local label checked divisions jump to when the divisor is zero.
+const DIVISION_BY_ZERO: &str = "division_by_zero";
+
+/// The exit status of a program that divides by zero with checks on, which is
+/// the one a shell reports for a process killed by `SIGFPE`.
+pub const DIVISION_BY_ZERO_STATUS: i32 = 136;
+
+/// The fewest `case` labels a `switch` needs to be given a jump table.
+const MIN_JUMP_TABLE_CASES: usize = 4;
+
+/// The directive that marks the instructions after it as coming from `line`
+/// and `column` of the source, which is file 1.
+fn position_directive(line: usize, column: usize) -> String {
+    format!(".loc 1 {} {}", line, column)
+}
+
+/// Rounds `value` up to a multiple of `multiple`.
+fn round_up(value: i64, multiple: i64) -> i64 {
+    (value + multiple - 1) / multiple * multiple
+}
+
+/// The text of the assembly being generated.
+pub struct Assembly {
+    output: String,
+    /// How many local labels have been made so far, which numbers the next one.
+    labels: usize,
+    /// The name of the function being generated, which its labels start with.
+    function: String,
+    target_os: TargetOs,
+}
+
+impl Assembly {
+    /// Writes an instruction or directive on a line of its own.
+    fn directive(&mut self, directive: &str) {
+        writeln!(self.output, "    {}", directive).unwrap();
+    }
+
+    fn label(&mut self, label: &str) {
+        writeln!(self.output, "{}:", label).unwrap();
+    }
+
+    /// Makes a name for a new local label in the function being generated,
+    /// for what `kind` says it marks: `.L<function>_<kind>_<n>`, where `n`
+    /// counts the labels made so far in the file. A kind is one word, so the
+    /// number makes the label unique in the output, and its position in the
+    /// file makes it the same every time.
+    fn new_label(&mut self, kind: &str) -> String {
+        self.labels += 1;
+        self.target_os.local_label(&format!("{}_{}_{}", self.function, kind, self.labels))
+    }
+}
+
+/// Where an object is, to load it, store it, or take its address.
+#[derive(Debug, PartialEq, Clone)]
+enum Location {
+    /// At an offset from the frame pointer.
+    Frame(i64),
+    /// At a label, addressed relative to the program counter.
+    Label(String),
+    /// At the address in the accumulator.
+    Accumulator,
+    /// At the address `Machine::restore_address` restored.
+    Restored,
+}
+
+/// A condition on how the first operand of a comparison compared with the
+/// second: as signed values for `Less` to `GreaterEqual`, and as unsigned ones
+/// for `Below` to `AboveEqual`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Condition {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Below,
+    BelowEqual,
+    Above,
+    AboveEqual,
+}
+
+/// The right operand of a binary operation on the accumulator.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Source {
+    /// The scratch register.
+    Scratch,
+    Immediate(i64),
+}
+
+/// What a call knows about the parameters of the function it calls.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Parameters {
+    /// There is one for each argument.
+    Fixed,
+    /// There are this many, followed by `...`.
+    Variadic(usize),
+    /// Nothing, since the function was never declared.
+    Unknown,
+}
+
+/// The instructions of one architecture, which the generator lowers a program
+/// to. Each expression leaves its value in the accumulator, 32 bits of it for
+/// any value up to that wide and all 64 for the rest, and a binary operator
+/// saves its left operand to a temporary while the right one is evaluated. The
+/// right one is then moved to the scratch register, and the left one restored
+/// to the accumulator. Sizes are in bytes.
+trait Machine {
+    fn assembly(&mut self) -> &mut Assembly;
+
+    /// How many of a function's arguments are passed in registers.
+    fn argument_registers(&self) -> usize;
+    /// Where the arguments of `sizes` that are passed on the stack go, as
+    /// offsets from the first one.
+    fn stack_arguments(&self, sizes: &[i64]) -> Vec<i64>;
+    /// The registers free to hold temporaries.
+    fn registers(&self) -> Registers;
+
+    /// Saves the caller's frame pointer, points it at this frame, and reserves
+    /// `frame_size` bytes below it, a multiple of 16. Each of the preserved
+    /// registers in `saved` is saved at its offset from the frame pointer.
+    fn prologue(&mut self, frame_size: i64, saved: &[(usize, i64)]);
+    /// Restores the registers the prologue saved and the caller's frame, and
+    /// returns to it with the value in the accumulator.
+    fn epilogue(&mut self);
+    /// Copies the parameter passed in argument register `index`, of `size`
+    /// bytes, to its slot at `offset` from the frame pointer.
+    fn spill(&mut self, index: usize, size: i64, offset: i64);
+
+    /// Loads a constant of `size` into the accumulator.
+    fn constant(&mut self, value: i64, size: i64);
+    /// Loads a constant of `size` into the scratch register.
+    fn scratch_constant(&mut self, value: i64, size: i64);
+    /// Loads an object of `size` from `location` into the accumulator,
+    /// extending a `char` to 32 bits by its sign if it is `signed`.
+    fn load(&mut self, location: &Location, size: i64, signed: bool);
+    /// Stores the low `size` bytes of the accumulator to `location`.
+    fn store(&mut self, location: &Location, size: i64);
+    /// Stores a constant of `size` at `offset` from the frame pointer.
+    fn store_constant(&mut self, offset: i64, size: i64, value: i64);
+    /// Loads the address of `location` into the accumulator.
+    fn address(&mut self, location: &Location);
+
+    /// Copies all of the accumulator to `home`.
+    fn save(&mut self, home: Home);
+    /// Copies `home` to all of the accumulator.
+    fn restore(&mut self, home: Home);
+    /// Copies the address in `home` to where `Location::Restored` is.
+    fn restore_address(&mut self, home: Home);
+    /// Copies the accumulator to the scratch register.
+    fn move_to_scratch(&mut self, size: i64);
+    /// Copies the scratch register back to the accumulator.
+    fn restore_from_scratch(&mut self, size: i64);
+
+    /// Applies `operator`, which is `+`, `-`, `*`, `<<`, `>>`, `&`, `^`, or
+    /// `|`, to the accumulator and `source`, leaving the result in the accumulator.
+    fn binary(&mut self, operator: BinaryOp, size: i64, signed: bool, source: Source);
+    /// Divides the accumulator by the scratch register, leaving the quotient or
+    /// the `remainder` in the accumulator. With `zero`, jumps there if the
+    /// divisor is zero, and wraps around dividing the most negative value by -1.
+    fn divide(&mut self, size: i64, signed: bool, remainder: bool, zero: Option<&str>);
+    /// Divides the accumulator by 2 to the power `shift`, which is at least 1
+    /// and less than the width of the operands, leaving the quotient or the
+    /// `remainder` in the accumulator as `divide` would, with shifts instead.
+    fn divide_by_power(&mut self, size: i64, signed: bool, remainder: bool, shift: i64);
+    /// Compares the accumulator with the scratch register, setting the
+    /// accumulator to 1 if `condition` holds and to 0 otherwise.
+    fn compare(&mut self, size: i64, condition: Condition);
+    /// Compares the accumulator with zero, for a conditional jump.
+    fn test(&mut self, size: i64);
+    /// Compares the accumulator with a constant, for a conditional jump.
+    fn compare_constant(&mut self, size: i64, value: i64);
+    /// Sets the accumulator to 1 if `condition` holds of the last comparison,
+    /// and to 0 otherwise.
+    fn set(&mut self, condition: Condition);
+    /// Applies `-` or `~` to the accumulator.
+    fn unary(&mut self, operator: UnaryOp, size: i64);
+    /// Converts the value of `from` bytes in the accumulator to one of `to`
+    /// bytes: from a `char` to an `int`, or from 32 bits to 64, by its sign if
+    /// it is `signed` and with zeros otherwise.
+    fn extend(&mut self, from: i64, to: i64, signed: bool);
+
+    /// Places `label` at this point in the function's code.
+    fn label(&mut self, label: &str);
+    /// Marks the instructions after this as coming from `line` and `column`
+    /// of the source.
+    fn position(&mut self, line: usize, column: usize);
+    /// Writes `text` as a comment on a line of its own.
+    fn comment(&mut self, text: &str);
+    /// Jumps to `label`, if the last comparison meets `condition`.
+    fn jump(&mut self, condition: Option<Condition>, label: &str);
+    /// Jumps through `table`, whose entries are the labels to jump to for the
+    /// values `min` to `max` of the accumulator, or to `otherwise` if it is
+    /// outside them.
+    fn jump_table(&mut self, size: i64, min: i64, max: i64, otherwise: &str, table: &str);
+
+    /// Copies the `arguments` in their homes, with their sizes, to where they
+    /// are passed, and calls `label`. Its return value is left in the
+    /// accumulator, and the registers a call may change are changed.
+    fn call(&mut self, label: &str, arguments: &[(Home, i64)], parameters: Parameters);
+    /// Writes the `length` bytes at `message` to standard error and exits with
+    /// `status`.
+    fn exit(&mut self, message: &str, length: usize, status: i32);
+
+    /// Runs the passes over the instructions of the function just written
+    /// that `options` enables, if the machine has any, and adds the time each
+    /// takes to `timings`.
+    fn optimize(&mut self, _options: &Options, _timings: &mut Timings) {}
+}
+
+struct Generator<'a> {
+    ast: &'a Ast,
+    items: &'a [TopLevel],
+    analysis: &'a Analysis,
+    layouts: Layouts<'a>,
+    machine: Box<dyn Machine>,
+    /// The label of every declaration of an object with static storage duration.
+    statics: HashMap<DeclId, String>,
+    /// The bytes of each distinct string literal, numbered by position.
+    strings: Vec<Vec<u8>>,
+    /// The frame of the function being generated.
+    frame: Frame,
+    /// The code of the function being generated, and the number of
+    /// temporaries it has saved so far.
+    code: Vec<Instruction>,
+    temps: usize,
+    /// Where `break` and `continue` jump to in the statement being generated,
+    /// innermost last.
+    breaks: Vec<String>,
+    continues: Vec<String>,
+    /// The label of each `case` and `default` label in the `switch`es
+    /// generated so far.
+    cases: HashMap<StmtId, String>,
+    /// The label of each label of the function being generated that a `goto`
+    /// or a labeled statement has named so far, by name.
+    goto_labels: HashMap<String, String>,
+    /// The jump tables to place in `.data.rel.ro`: each one's label, and the
+    /// labels it jumps to.
+    tables: Vec<(String, Vec<String>)>,
+    /// The functions whose calls are replaced with their bodies, by name.
+    inlined: HashMap<String, Inlinable>,
+    /// The passes over the code of each function, and how long all the
+    /// passes have taken so far.
+    passes: PassManager<Lowered>,
+    timings: Timings,
+    options: &'a Options,
+    /// Whether any division jumps to `DIVISION_BY_ZERO`, which is then defined.
+    division_checked: bool,
+    /// Where the lines of `options.source` are, to quote them in comments,
+    /// and the number of the one quoted last in the function being generated.
+    source_lines: Option<LineIndex>,
+    commented_line: usize,
+}
+
+impl<'a> Generator<'a> {
+    /// Writes a directive on a line of its own.
+    fn directive(&mut self, directive: &str) {
+        self.machine.assembly().directive(directive);
+    }
+
+    /// Places `label` at this point in the function's code.
+    fn label(&mut self, label: &str) {
+        self.emit(Instruction::Label(label.to_owned()));
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.code.push(instruction);
+    }
+
+    /// Marks the code after this as coming from where `span` starts, if `span`
+    /// is from the source: with a comment quoting the line, if comments are
+    /// wanted and it isn't the line quoted last, and with its position, if
+    /// debug information is wanted.
+    fn position(&mut self, span: Span) {
+        if let Some(comment) = self.comment(span) {
+            self.emit(Instruction::Comment(comment));
+        }
+        if self.options.debug_info == DebugInfo::Lines && span.line > 0 {
+            self.emit(Instruction::Position(span.line, span.column));
+        }
+    }
+
+    /// The comment that quotes the line `span` starts on, if there is to be one.
+    fn comment(&mut self, span: Span) -> Option<String> {
+        if !self.options.source_comments || span.line == 0 || span.line == self.commented_line {
+            return None;
+        }
+        self.commented_line = span.line;
+        let source = self.options.source.as_deref()?;
+        let text = &source[self.source_lines.as_ref()?.line_span(span.line)?];
+        Some(format!("{}: {}", span.line, text.trim()))
+    }
+
+    /// Saves all of the accumulator to a new temporary.
+    fn save(&mut self) -> Temp {
+        let temp = Temp(self.temps);
+        self.temps += 1;
+        self.emit(Instruction::Save(temp));
+        temp
+    }
+
+    fn new_label(&mut self, kind: &str) -> String {
+        self.machine.assembly().new_label(kind)
+    }
+
+    /// Generates the function at `index` in the program's items.
+    fn function(&mut self, index: usize, function: &Function) {
+        let body = match function.body {
+            Some(ref body) => body,
+            None => return,
+        };
+        let registers = self.machine.argument_registers();
+        let sizes: Vec<_> = function.parameters.iter().map(|parameter| self.size_of(&parameter.ty)).collect();
+        let mut stack = self.machine.stack_arguments(&sizes[registers.min(sizes.len())..]).into_iter();
+        let mut builder = FrameBuilder { layouts: &self.layouts, inlined: &self.inlined, frame: HashMap::new(), size: 0 };
+        for (parameter, ty) in function.parameters.iter().map(|parameter| &parameter.ty).enumerate() {
+            let symbol = Symbol::Parameter { function: index, index: parameter };
+            if parameter < registers {
+                builder.allocate(symbol, ty);
+            } else {
+                // Above the saved frame pointer and the return address.
+                builder.frame.insert(symbol, 16 + stack.next().unwrap());
+            }
+        }
+        builder.visit_block(self.ast, body);
+        let locals = builder.size;
+        self.frame = builder.frame;
+
+        self.temps = 0;
+        self.goto_labels.clear();
+        self.machine.assembly().function = function.name.clone();
+        self.commented_line = 0;
+        let header = self.comment(function.span);
+        self.block(body);
+        // Falling off the end of `main` returns 0. For any other function the
+        // value is meaningless, and returning 0 is as good as anything.
+        if let Some(closing_brace) = function.closing_brace {
+            self.position(closing_brace);
+        }
+        self.emit(Instruction::Constant(0, 4));
+        self.emit(Instruction::Return);
+        let mut lowered = Lowered {
+            code: mem::take(&mut self.code),
+            targets: self.tables.iter().flat_map(|table| table.1.iter().cloned()).collect(),
+            slots: self.frame.values().cloned().collect(),
+        };
+        self.passes.run(&mut lowered, self.options, &mut self.timings);
+        let code = lowered.code;
+        let allocation = registers::allocate(&code, self.machine.registers(), locals, self.options.spill_temporaries);
+
+        let label = self.options.target.os.symbol(&function.name);
+        if function.storage != Some(StorageClass::Static) {
+            self.directive(&format!(".globl {}", label));
+        }
+        // Only ELF has symbol types and sizes, which tools like `perf` and
+        // `objdump` use to tell which function an address is in.
+        let elf = self.options.target.os == TargetOs::Linux && self.options.debug_info == DebugInfo::Lines;
+        if elf {
+            // `@` starts a comment in AArch64 assembly.
+            let ty = if self.options.target.arch == Architecture::Aarch64 { "%function" } else { "@function" };
+            self.directive(&format!(".type {}, {}", label, ty));
+        }
+        self.machine.assembly().label(&label);
+        if let Some(header) = header {
+            self.machine.comment(&header);
+        }
+        if self.options.debug_info == DebugInfo::Lines {
+            self.machine.position(function.span.line, function.span.column);
+        }
+        self.machine.prologue(allocation.frame_size, &allocation.saved);
+        for (position, &size) in sizes.iter().enumerate().take(registers) {
+            let slot = self.frame[&Symbol::Parameter { function: index, index: position }];
+            self.machine.spill(position, size, slot);
+        }
+        ir::write(&code, &allocation.homes, &mut *self.machine);
+        self.machine.optimize(self.options, &mut self.timings);
+        if elf {
+            self.directive(&format!(".size {}, .-{}", label, label));
+        }
+    }
+
+    fn block(&mut self, items: &[BlockItem]) {
+        for item in items {
+            match *item {
+                BlockItem::Statement(statement) => self.statement(statement),
+                BlockItem::Declaration(ref declarations) => {
+                    for &declaration in declarations {
+                        self.declaration(declaration);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Stores the initial value of a local variable in its slot. The value of a
+    /// `static` one is in its data already.
+    fn declaration(&mut self, id: DeclId) {
+        let declaration = &self.ast[id];
+        if declaration.storage.is_some() {
+            return;
+        }
+        if let Some(ref initializer) = declaration.initializer {
+            self.position(declaration.span);
+            let offset = self.frame[&Symbol::Variable(id)];
+            self.initialize(&declaration.ty, initializer, offset);
+        }
+    }
+
+    /// Stores `initializer`'s value for an object of type `ty` at `offset` from
+    /// the frame pointer. Whatever an initializer list leaves out is zeroed.
+    fn initialize(&mut self, ty: &Type, initializer: &Initializer, offset: i64) {
+        match (ty.unqualified(), initializer) {
+            (&Type::Array(_, length), &Initializer::Expr(value)) => match self.ast[value].kind {
+                ExpressionKind::StringLiteral(ref bytes) => {
+                    // The terminating null is dropped if the array has no room for it.
+                    for (index, &byte) in bytes.iter().chain(Some(&0)).take(length).enumerate() {
+                        self.emit(Instruction::StoreConstant(offset + index as i64, 1, i64::from(byte as i8)));
+                    }
+                    let filled = (bytes.len() + 1).min(length) as i64;
+                    self.zero(offset + filled, length as i64 - filled);
+                },
+                ref kind => unreachable!("an array initialized from {:?}", kind),
+            },
+            (Type::Array(element, length), Initializer::List(items)) => {
+                let size = self.size_of(element);
+                for (index, item) in items.iter().enumerate() {
+                    self.initialize(element, item, offset + index as i64 * size);
+                }
+                self.zero(offset + items.len() as i64 * size, (*length - items.len()) as i64 * size);
+            },
+            (Type::Struct(tag), Initializer::List(items)) => {
+                // The padding is zeroed along with the members left out.
+                let mut filled = 0;
+                for (member, item) in self.members(tag).iter().zip(items) {
+                    let start = self.layouts.offset_of(tag, &member.name);
+                    self.zero(offset + filled, start - filled);
+                    self.initialize(&member.ty, item, offset + start);
+                    filled = start + self.size_of(&member.ty);
+                }
+                let size = self.size_of(ty);
+                self.zero(offset + filled, size - filled);
+            },
+            (_, Initializer::List(items)) => match items.first() {
+                Some(item) => self.initialize(ty, item, offset),
+                None => {
+                    let size = self.size_of(ty);
+                    self.zero(offset, size);
+                },
+            },
+            (_, &Initializer::Expr(value)) => {
+                self.expression(value);
+                self.store(ty, &Location::Frame(offset));
+            },
+        }
+    }
+
+    /// The members of `struct tag`.
+    fn members(&self, tag: &str) -> &'a [Member] {
+        self.items.iter()
+            .filter_map(|item| match *item {
+                TopLevel::Struct(ref definition) if definition.name == tag => Some(&definition.members[..]),
+                _ => None,
+            })
+            .next()
+            .unwrap()
+    }
+
+    /// Zeroes `size` bytes at `offset` from the frame pointer.
+    fn zero(&mut self, mut offset: i64, size: i64) {
+        let end = offset + size;
+        for &width in &[8, 4, 1] {
+            while end - offset >= width {
+                self.emit(Instruction::StoreConstant(offset, width, 0));
+                offset += width;
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: StmtId) {
+        // The statements in a block mark their own positions.
+        if !matches!(self.ast[statement].kind, StatementKind::Compound(_)) {
+            self.position(self.ast[statement].span);
+        }
+        match self.ast[statement].kind {
+            StatementKind::Return(value) => {
+                self.expression(value);
+                self.emit(Instruction::Return);
+            },
+            StatementKind::Expression(Some(expression)) => self.expression(expression),
+            StatementKind::Expression(None) => {},
+            StatementKind::Compound(ref items) => self.block(items),
+            StatementKind::If(condition, then, otherwise) => {
+                let end = self.new_label("end");
+                self.test(condition);
+                match otherwise {
+                    Some(otherwise) => {
+                        let other = self.new_label("else");
+                        self.emit(Instruction::Jump(Some(Condition::Equal), other.clone()));
+                        self.statement(then);
+                        self.emit(Instruction::Jump(None, end.clone()));
+                        self.label(&other);
+                        self.statement(otherwise);
+                    },
+                    None => {
+                        self.emit(Instruction::Jump(Some(Condition::Equal), end.clone()));
+                        self.statement(then);
+                    },
+                }
+                self.label(&end);
+            },
+            StatementKind::While(condition, body) => {
+                let (start, end) = (self.new_label("loop"), self.new_label("end"));
+                self.label(&start);
+                self.condition(condition, &end);
+                self.body(body, &end, &start);
+                self.emit(Instruction::Jump(None, start.clone()));
+                self.label(&end);
+            },
+            StatementKind::DoWhile(body, condition) => {
+                let (start, next, end) = (self.new_label("loop"), self.new_label("next"), self.new_label("end"));
+                self.label(&start);
+                self.body(body, &end, &next);
+                self.label(&next);
+                self.test(condition);
+                self.emit(Instruction::Jump(Some(Condition::NotEqual), start.clone()));
+                self.label(&end);
+            },
+            StatementKind::For { ref init, condition, post, body } => {
+                match *init {
+                    ForInit::Declaration(ref declarations) => {
+                        for &declaration in declarations {
+                            self.declaration(declaration);
+                        }
+                    },
+                    ForInit::Expression(Some(expression)) => self.expression(expression),
+                    ForInit::Expression(None) => {},
+                }
+                let (start, next, end) = (self.new_label("loop"), self.new_label("next"), self.new_label("end"));
+                self.label(&start);
+                if let Some(condition) = condition {
+                    self.condition(condition, &end);
+                }
+                self.body(body, &end, &next);
+                self.label(&next);
+                if let Some(post) = post {
+                    self.expression(post);
+                }
+                self.emit(Instruction::Jump(None, start.clone()));
+                self.label(&end);
+            },
+            StatementKind::Switch(value, body) => self.switch(statement, value, body),
+            StatementKind::Case(_, body) | StatementKind::Default(body) => {
+                let label = self.cases[&statement].clone();
+                self.label(&label);
+                self.statement(body);
+            },
+            StatementKind::Break => {
+                let target = self.breaks.last().unwrap().clone();
+                self.emit(Instruction::Jump(None, target.clone()));
+            },
+            StatementKind::Continue => {
+                let target = self.continues.last().unwrap().clone();
+                self.emit(Instruction::Jump(None, target.clone()));
+            },
+            StatementKind::Goto(ref name) => {
+                let target = self.goto_label(name);
+                self.emit(Instruction::Jump(None, target));
+            },
+            StatementKind::Labeled(ref name, body) => {
+                let label = self.goto_label(name);
+                self.label(&label);
+                self.statement(body);
+            },
+        }
+    }
+
+    /// The label of the label `name` in the function being generated, which
+    /// is made the first time a `goto` or the labeled statement names it.
+    fn goto_label(&mut self, name: &str) -> String {
+        if let Some(label) = self.goto_labels.get(name) {
+            return label.clone();
+        }
+        let label = self.new_label("label");
+        self.goto_labels.insert(name.to_owned(), label.clone());
+        label
+    }
+
+    /// Evaluates `condition` and jumps to `target` if it is false.
+    fn condition(&mut self, condition: ExprId, target: &str) {
+        self.test(condition);
+        self.emit(Instruction::Jump(Some(Condition::Equal), target.to_owned()));
+    }
+
+    /// Evaluates the scalar `expression` and compares it with zero, setting the
+    /// flags.
+    fn test(&mut self, expression: ExprId) {
+        self.expression(expression);
+        let size = self.width(&self.value_type(expression));
+        self.emit(Instruction::Test(size));
+    }
+
+    /// Defines `DIVISION_BY_ZERO`, which writes a message to standard error and
+    /// exits.
+    fn division_by_zero(&mut self) {
+        let message = b"division by zero\n";
+        let label = self.string(message);
+        let target_os = self.options.target.os;
+        self.machine.assembly().label(&target_os.local_label(DIVISION_BY_ZERO));
+        self.machine.exit(&label, message.len(), DIVISION_BY_ZERO_STATUS);
+    }
+
+    /// Generates the `switch` statement `switch` on `value`. Its value jumps to
+    /// the label it matches through a jump table if the labels are dense enough
+    /// for one, and through a compare for each label otherwise. The labels are
+    /// then only labels: control falls through them like any other.
+    fn switch(&mut self, switch: StmtId, value: ExprId, body: StmtId) {
+        let labels = &self.analysis.switches[&switch];
+        let end = self.new_label("end");
+        let mut cases = Vec::new();
+        for &(value, case) in &labels.cases {
+            let label = self.new_label("case");
+            self.cases.insert(case, label.clone());
+            cases.push((value, label));
+        }
+        let otherwise = match labels.default {
+            Some(default) => {
+                let label = self.new_label("default");
+                self.cases.insert(default, label.clone());
+                label
+            },
+            None => end.clone(),
+        };
+
+        self.expression(value);
+        let size = self.width(&self.value_type(value));
+        let (min, max) = match (cases.iter().map(|case| case.0).min(), cases.iter().map(|case| case.0).max()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => (0, 0),
+        };
+        // Up to two thirds of a table may jump to `otherwise`.
+        let dense = cases.len() >= MIN_JUMP_TABLE_CASES && max - min < 3 * cases.len() as i64;
+        if dense {
+            let table = self.new_label("table");
+            let targets = (min..=max)
+                .map(|value| match cases.iter().find(|case| case.0 == value) {
+                    Some(case) => case.1.clone(),
+                    None => otherwise.clone(),
+                })
+                .collect();
+            self.tables.push((table.clone(), targets));
+            self.emit(Instruction::JumpTable(size, min, max, otherwise.clone(), table));
+        } else {
+            for &(value, ref label) in &cases {
+                self.emit(Instruction::CompareConstant(size, value));
+                self.emit(Instruction::Jump(Some(Condition::Equal), label.clone()));
+            }
+            self.emit(Instruction::Jump(None, otherwise.clone()));
+        }
+
+        self.breaks.push(end.clone());
+        self.statement(body);
+        self.breaks.pop();
+        self.label(&end);
+    }
+
+    /// Generates the body of a loop, in which `break` jumps to `end` and
+    /// `continue` to `next`.
+    fn body(&mut self, body: StmtId, end: &str, next: &str) {
+        self.breaks.push(end.to_owned());
+        self.continues.push(next.to_owned());
+        self.statement(body);
+        self.breaks.pop();
+        self.continues.pop();
+    }
+
+    /// Where the variable that `variable` refers to is: in its slot in the
+    /// frame, or at its label.
+    fn location(&self, variable: ExprId) -> Location {
+        let symbol = self.analysis.resolutions[&variable];
+        if let Some(&offset) = self.frame.get(&symbol) {
+            return Location::Frame(offset);
+        }
+        match symbol {
+            Symbol::Variable(declaration) => Location::Label(self.options.target.os.symbol(&self.statics[&declaration])),
+            _ => unreachable!("a parameter outside its function's frame"),
+        }
+    }
+
+    fn size_of(&self, ty: &Type) -> i64 {
+        self.layouts.size_of(ty)
+    }
+
+    /// Loads a `ty` from `location` into the accumulator.
+    fn load(&mut self, ty: &Type, location: &Location) {
+        match self.size_of(ty) {
+            size @ 1 | size @ 4 | size @ 8 => self.emit(Instruction::Load(location.clone(), size, ty.is_signed())),
+            _ => unimplemented!("codegen for values of type `{}`", ty),
+        }
+    }
+
+    /// Stores the `ty` in the accumulator to `location`.
+    fn store(&mut self, ty: &Type, location: &Location) {
+        match self.size_of(ty) {
+            size @ 1 | size @ 4 | size @ 8 => self.emit(Instruction::Store(location.clone(), size)),
+            _ => unimplemented!("codegen for values of type `{}`", ty),
+        }
+    }
+
+    /// The label of the string literal with these bytes, which is given one
+    /// if it doesn't have one yet.
+    fn string(&mut self, bytes: &[u8]) -> String {
+        let index = match self.strings.iter().position(|string| string == bytes) {
+            Some(index) => index,
+            None => {
+                self.strings.push(bytes.to_owned());
+                self.strings.len() - 1
+            },
+        };
+        self.options.target.os.local_label(&format!("str{}", index))
+    }
+
+    /// Defines `object` in `.data`, or in `.bss` if it starts as all zeros.
+    fn static_object(&mut self, object: &StaticObject, external: bool) {
+        let declaration = &self.ast[object.declaration];
+        let value = &self.analysis.statics[&object.declaration];
+        self.directive(if *value == StaticValue::Zero { ".bss" } else { ".data" });
+        let alignment = self.layouts.alignment(&declaration.ty);
+        self.directive(&format!(".balign {}", alignment));
+        let label = self.options.target.os.symbol(&object.label);
+        if external {
+            self.directive(&format!(".globl {}", label));
+        }
+        self.machine.assembly().label(&label);
+        let ty = declaration.ty.clone();
+        self.static_value(&ty, value);
+    }
+
+    /// Writes the data directives for an object of type `ty` starting as `value`.
+    fn static_value(&mut self, ty: &Type, value: &StaticValue) {
+        let size = self.size_of(ty);
+        match *value {
+            StaticValue::Zero => self.directive(&format!(".zero {}", size)),
+            StaticValue::Int(value) => {
+                let directive = match size {
+                    1 => ".byte",
+                    4 => ".long",
+                    8 => ".quad",
+                    _ => unreachable!("a scalar of {} bytes", size),
+                };
+                self.directive(&format!("{} {}", directive, value));
+            },
+            StaticValue::Address { ref object, offset } => {
+                let label = match *object {
+                    statics::StaticObject::Declaration(declaration) => self.options.target.os.symbol(&self.statics[&declaration]),
+                    statics::StaticObject::String(literal) => match self.ast[literal].kind {
+                        ExpressionKind::StringLiteral(ref bytes) => self.string(bytes),
+                        ref kind => unreachable!("a string literal that is {:?}", kind),
+                    },
+                };
+                match offset {
+                    0 => self.directive(&format!(".quad {}", label)),
+                    _ => self.directive(&format!(".quad {}{:+}", label, offset)),
+                }
+            },
+            StaticValue::List(ref items) => match *ty.unqualified() {
+                Type::Array(ref element, length) => {
+                    for item in items {
+                        self.static_value(element, item);
+                    }
+                    if items.len() < length {
+                        self.directive(&format!(".zero {}", (length - items.len()) as i64 * self.size_of(element)));
+                    }
+                },
+                Type::Struct(ref tag) => {
+                    let mut filled = 0;
+                    for (member, item) in self.members(tag).iter().zip(items) {
+                        let offset = self.layouts.offset_of(tag, &member.name);
+                        if offset > filled {
+                            self.directive(&format!(".zero {}", offset - filled));
+                        }
+                        self.static_value(&member.ty, item);
+                        filled = offset + self.size_of(&member.ty);
+                    }
+                    if size > filled {
+                        self.directive(&format!(".zero {}", size - filled));
+                    }
+                },
+                ref ty => unreachable!("a static initializer list for `{}`", ty),
+            },
+        }
+    }
+
+    /// Evaluates `expression` into the accumulator, converted to the type its
+    /// value is used as.
+    fn expression(&mut self, expression: ExprId) {
+        self.evaluate(expression);
+        if let Some(to) = self.analysis.conversions.get(&expression) {
+            let from = self.analysis.types[&expression].decay();
+            self.convert(&from, to);
+        }
+    }
+
+    /// Evaluates `expression` into the accumulator, as its own type. An array
+    /// evaluates to the address of its first element, and a struct, whose value
+    /// analysis only lets be discarded, to its address.
+    fn evaluate(&mut self, expression: ExprId) {
+        match *self.analysis.types[&expression].unqualified() {
+            Type::Array(..) | Type::Struct(_) => return self.address(expression),
+            _ => {},
+        }
+        match self.ast[expression].kind {
+            ExpressionKind::IntLiteral(value, ref ty) => {
+                let size = self.width(ty);
+                self.emit(Instruction::Constant(value, size));
+            },
+            ExpressionKind::CharConstant(value) => self.emit(Instruction::Constant(i64::from(value), 4)),
+            ExpressionKind::SizeOf(ref ty) => {
+                let size = self.size_of(ty);
+                self.emit(Instruction::Constant(size, 4));
+            },
+            ExpressionKind::Variable(_) => {
+                let location = self.location(expression);
+                let ty = self.analysis.types[&expression].clone();
+                self.load(&ty, &location);
+            },
+            ExpressionKind::Assign(operator, target, value) => self.assign(operator, target, value),
+            ExpressionKind::Unary(UnaryOp::AddressOf, operand) => self.address(operand),
+            ExpressionKind::Unary(UnaryOp::Dereference, _)
+            | ExpressionKind::Subscript(..)
+            | ExpressionKind::Member(..)
+            | ExpressionKind::PointerMember(..) => {
+                self.address(expression);
+                let ty = self.analysis.types[&expression].clone();
+                self.load(&ty, &Location::Accumulator);
+            },
+            ExpressionKind::Unary(operator @ UnaryOp::PreIncrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PreDecrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PostIncrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PostDecrement, operand) => self.increment(operator, operand),
+            ExpressionKind::Unary(operator, operand) => {
+                self.expression(operand);
+                let ty = self.value_type(operand);
+                self.unary(operator, &ty);
+            },
+            ExpressionKind::Cast { ref to, expr } => {
+                self.expression(expr);
+                let from = self.value_type(expr);
+                self.convert(&from, to.unqualified());
+            },
+            ExpressionKind::Binary(operator @ BinaryOp::LogicalAnd, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::LogicalOr, lhs, rhs) => self.logical(operator, lhs, rhs),
+            ExpressionKind::Binary(operator @ BinaryOp::Add, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::Subtract, lhs, rhs)
+                if self.is_pointer(lhs) || self.is_pointer(rhs) => self.pointer_arithmetic(operator, lhs, rhs),
+            ExpressionKind::Binary(operator @ BinaryOp::ShiftLeft, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::ShiftRight, lhs, rhs) if self.constant(rhs).is_some() => {
+                // Only the low bits of the amount are used, as they would be of
+                // an amount in a register.
+                let operands = self.value_type(lhs);
+                let amount = self.constant(rhs).unwrap() & (self.size_of(&operands) * 8 - 1);
+                self.expression(lhs);
+                let size = self.width(&operands);
+                self.emit(Instruction::Binary(operator, size, operands.is_signed(), Source::Immediate(amount)));
+            },
+            ExpressionKind::Binary(operator, lhs, rhs) => {
+                self.expression(lhs);
+                let temp = self.save();
+                self.expression(rhs);
+                let operands = self.value_type(lhs);
+                let size = self.width(&operands);
+                self.emit(Instruction::MoveToScratch(size));
+                self.emit(Instruction::Restore(temp));
+                self.binary(operator, &operands);
+            },
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                // The operands are converted to the result's type already.
+                let (other, end) = (self.new_label("else"), self.new_label("end"));
+                self.condition(condition, &other);
+                self.expression(then);
+                self.emit(Instruction::Jump(None, end.clone()));
+                self.label(&other);
+                self.expression(otherwise);
+                self.label(&end);
+            },
+            ExpressionKind::Comma(ref expressions) => {
+                for &expression in expressions {
+                    self.expression(expression);
+                }
+            },
+            ExpressionKind::Call(ref name, ref arguments) => self.call(expression, name, arguments),
+            ExpressionKind::StringLiteral(_) => unreachable!("a string literal that isn't an array"),
+        }
+    }
+
+    /// Leaves the address of the lvalue `expression` in the accumulator.
+    fn address(&mut self, expression: ExprId) {
+        match self.ast[expression].kind {
+            ExpressionKind::Variable(_) => {
+                let location = self.location(expression);
+                self.emit(Instruction::Address(location));
+            },
+            ExpressionKind::StringLiteral(ref bytes) => {
+                let label = self.string(bytes);
+                self.emit(Instruction::Address(Location::Label(label)));
+            },
+            ExpressionKind::Unary(UnaryOp::Dereference, pointer) => self.expression(pointer),
+            // `a[i]` is `*(a + i)`, whichever of the two is the pointer.
+            ExpressionKind::Subscript(array, index) => self.pointer_arithmetic(BinaryOp::Add, array, index),
+            ExpressionKind::Member(object, ref member) => {
+                self.address(object);
+                let ty = self.analysis.types[&object].clone();
+                self.member_offset(&ty, member);
+            },
+            ExpressionKind::PointerMember(pointer, ref member) => {
+                self.expression(pointer);
+                let ty = match *self.value_type(pointer).unqualified() {
+                    Type::Pointer(ref pointee) => (**pointee).clone(),
+                    ref ty => unreachable!("`->` applied to `{}`", ty),
+                };
+                self.member_offset(&ty, member);
+            },
+            ref kind => unimplemented!("codegen for the address of {:?}", kind),
+        }
+    }
+
+    /// Where the lvalue `target` is. A variable is addressed directly; anything
+    /// else has its address computed and saved, to be restored with `unplace`
+    /// once it is needed.
+    fn place(&mut self, target: ExprId) -> Result<Location, Temp> {
+        match self.ast[target].kind {
+            ExpressionKind::Variable(_) => Ok(self.location(target)),
+            _ => {
+                self.address(target);
+                Err(self.save())
+            },
+        }
+    }
+
+    /// Finishes addressing the lvalue that `place` gave `location` for,
+    /// returning where it is.
+    fn unplace(&mut self, location: Result<Location, Temp>) -> Location {
+        location.unwrap_or_else(|temp| {
+            self.emit(Instruction::RestoreAddress(temp));
+            Location::Restored
+        })
+    }
+
+    /// Assigns `value` to `target`, leaving the value assigned in the
+    /// accumulator. With an `operator`, assigns the result of applying it to both.
+    fn assign(&mut self, operator: Option<BinaryOp>, target: ExprId, value: ExprId) {
+        let ty = self.analysis.types[&target].clone();
+        let location = self.place(target);
+        self.expression(value);
+        let location = self.unplace(location);
+        match operator {
+            Some(operator @ BinaryOp::Add) | Some(operator @ BinaryOp::Subtract) if ty.is_pointer() => {
+                let operand = self.value_type(value);
+                self.scale(&operand, &ty);
+                self.emit(Instruction::MoveToScratch(8));
+                self.load(&ty, &location);
+                self.emit(Instruction::Binary(operator, 8, false, Source::Scratch));
+            },
+            Some(operator) => {
+                // The target is converted like the left operand of `operator`,
+                // to the type the right one was, and the result back.
+                let operands = self.value_type(value);
+                let size = self.width(&operands);
+                self.emit(Instruction::MoveToScratch(size));
+                self.load(&ty, &location);
+                self.convert(&ty.decay(), &operands);
+                self.binary(operator, &operands);
+                self.convert(&operands, &ty.decay());
+            },
+            None => {},
+        }
+        self.store(&ty, &location);
+    }
+
+    /// Increments or decrements `target` by one, or by its pointee's size if it
+    /// is a pointer, leaving its new or old value in the accumulator as `operator`
+    /// says.
+    fn increment(&mut self, operator: UnaryOp, target: ExprId) {
+        let ty = self.analysis.types[&target].clone();
+        let location = self.place(target);
+        let location = self.unplace(location);
+        let amount = if ty.is_pointer() { self.pointee_size(&ty) } else { 1 };
+        let operation = match operator {
+            UnaryOp::PreIncrement | UnaryOp::PostIncrement => BinaryOp::Add,
+            _ => BinaryOp::Subtract,
+        };
+        let size = self.width(&ty);
+        self.load(&ty, &location);
+        // The old value is kept in the scratch register.
+        self.emit(Instruction::MoveToScratch(size));
+        self.emit(Instruction::Binary(operation, size, ty.is_signed(), Source::Immediate(amount)));
+        self.convert(&promote(&ty), &ty);
+        self.store(&ty, &location);
+        if operator.is_postfix() {
+            self.emit(Instruction::RestoreFromScratch(size));
+        }
+    }
+
+    /// Offsets the address in the accumulator of a struct of type `ty` to that of its
+    /// `member`.
+    fn member_offset(&mut self, ty: &Type, member: &str) {
+        let offset = match *ty.unqualified() {
+            Type::Struct(ref tag) => self.layouts.offset_of(tag, member),
+            ref ty => unreachable!("a member of `{}`", ty),
+        };
+        if offset != 0 {
+            self.emit(Instruction::Binary(BinaryOp::Add, 8, false, Source::Immediate(offset)));
+        }
+    }
+
+    /// The size of what the pointer type `pointer` points to.
+    fn pointee_size(&self, pointer: &Type) -> i64 {
+        match *pointer.unqualified() {
+            Type::Pointer(ref pointee) => self.size_of(pointee),
+            ref ty => unreachable!("the pointee of `{}`", ty),
+        }
+    }
+
+    /// Whether `expression`'s value is a pointer.
+    fn is_pointer(&self, expression: ExprId) -> bool {
+        self.value_type(expression).is_pointer()
+    }
+
+    /// Converts the integer of type `ty` in the accumulator to the offset in bytes of
+    /// that many objects that `pointer` points to.
+    fn scale(&mut self, ty: &Type, pointer: &Type) {
+        self.convert(ty, &Type::Long);
+        let size = self.pointee_size(pointer);
+        if size != 1 {
+            self.emit(Instruction::Binary(BinaryOp::Multiply, 8, true, Source::Immediate(size)));
+        }
+    }
+
+    /// Adds an integer to a pointer, subtracts one from a pointer, or subtracts
+    /// two pointers, giving the number of objects between them.
+    fn pointer_arithmetic(&mut self, operator: BinaryOp, lhs: ExprId, rhs: ExprId) {
+        let (lhs_type, rhs_type) = (self.value_type(lhs), self.value_type(rhs));
+        let pointer = if lhs_type.is_pointer() { lhs_type.clone() } else { rhs_type.clone() };
+        self.expression(lhs);
+        if !lhs_type.is_pointer() {
+            self.scale(&lhs_type, &pointer);
+        }
+        let temp = self.save();
+        self.expression(rhs);
+        if !rhs_type.is_pointer() {
+            self.scale(&rhs_type, &pointer);
+        }
+        self.emit(Instruction::MoveToScratch(8));
+        self.emit(Instruction::Restore(temp));
+        self.emit(Instruction::Binary(operator, 8, false, Source::Scratch));
+        if operator == BinaryOp::Subtract && rhs_type.is_pointer() {
+            let size = self.pointee_size(&pointer);
+            if size != 1 {
+                self.emit(Instruction::ScratchConstant(size, 8));
+                self.emit(Instruction::Divide(8, true, false, None));
+            }
+        }
+    }
+
+    /// Calls the function `name` with `arguments`, leaving its return value in
+    /// the accumulator. `call` is the expression making the call.
+    fn call(&mut self, call: ExprId, name: &str, arguments: &[ExprId]) {
+        if self.inlined.contains_key(name) {
+            return self.inline(name, arguments);
+        }
+        let mut temps: Vec<_> = arguments
+            .iter()
+            .rev()
+            .map(|&argument| {
+                self.expression(argument);
+                self.save()
+            })
+            .collect();
+        temps.reverse();
+        let parameters = match self.analysis.resolutions.get(&call) {
+            Some(&Symbol::Function(index)) => match self.items[index] {
+                TopLevel::Function(ref function) if function.variadic => Parameters::Variadic(function.parameters.len()),
+                TopLevel::Function(_) => Parameters::Fixed,
+                _ => unreachable!("a call resolved to something other than a function"),
+            },
+            _ => Parameters::Unknown,
+        };
+        let arguments = temps
+            .into_iter()
+            .zip(arguments)
+            .map(|(temp, &argument)| (temp, self.size_of(&self.value_type(argument))))
+            .collect();
+        let label = self.options.target.os.symbol(name);
+        self.emit(Instruction::Call(label, arguments, parameters));
+    }
+
+    /// Evaluates a call to the function `name` in place, by evaluating the
+    /// arguments as for a call, storing them to the slots of its parameters,
+    /// and then evaluating what it returns.
+    fn inline(&mut self, name: &str, arguments: &[ExprId]) {
+        let (index, parameters, body) = {
+            let inlined = &self.inlined[name];
+            (inlined.index, inlined.parameters.clone(), inlined.body)
+        };
+        let mut temps: Vec<_> = arguments
+            .iter()
+            .rev()
+            .map(|&argument| {
+                self.expression(argument);
+                self.save()
+            })
+            .collect();
+        temps.reverse();
+        for (position, (temp, ty)) in temps.into_iter().zip(&parameters).enumerate() {
+            let slot = self.frame[&Symbol::Parameter { function: index, index: position }];
+            self.emit(Instruction::Restore(temp));
+            self.store(ty, &Location::Frame(slot));
+        }
+        self.expression(body);
+    }
+
+    /// Evaluates `lhs && rhs` or `lhs || rhs`, skipping `rhs` if `lhs` decides
+    /// the result. Either way the flags end up set by comparing the last operand
+    /// evaluated with zero, which gives the result.
+    fn logical(&mut self, operator: BinaryOp, lhs: ExprId, rhs: ExprId) {
+        let end = self.new_label("end");
+        self.test(lhs);
+        let skip = if operator == BinaryOp::LogicalAnd { Condition::Equal } else { Condition::NotEqual };
+        self.emit(Instruction::Jump(Some(skip), end.clone()));
+        self.test(rhs);
+        self.label(&end);
+        self.emit(Instruction::Set(Condition::NotEqual));
+    }
+
+    /// Converts the value in the accumulator from type `from` to `to`.
+    fn convert(&mut self, from: &Type, to: &Type) {
+        let size = |ty: &Type| match *ty.unqualified() {
+            Type::Char => Some(1),
+            Type::Int | Type::UnsignedInt => Some(4),
+            Type::Long | Type::UnsignedLong | Type::Pointer(_) => Some(8),
+            _ => None,
+        };
+        match (size(from), size(to)) {
+            (Some(1), Some(1)) => {},
+            // Keeps the low byte, extended again the way a `char` is kept.
+            (Some(_), Some(1)) => self.emit(Instruction::Extend(1, 4, true)),
+            // A `char` is extended to 32 bits already, and a conversion to a
+            // type no wider keeps the low bits as they are.
+            (Some(_), Some(4)) | (Some(8), Some(8)) => {},
+            (Some(_), Some(8)) => self.emit(Instruction::Extend(4, 8, from.is_signed())),
+            _ => unimplemented!("codegen for conversions from `{}` to `{}`", from, to),
+        }
+    }
+
+    /// Applies `operator` to the value in the accumulator, which is of type `operand`.
+    fn unary(&mut self, operator: UnaryOp, operand: &Type) {
+        let size = self.width(operand);
+        match operator {
+            UnaryOp::Negate | UnaryOp::BitwiseNot => self.emit(Instruction::Unary(operator, size)),
+            UnaryOp::LogicalNot => {
+                self.emit(Instruction::Test(size));
+                self.emit(Instruction::Set(Condition::Equal));
+            },
+            operator => unreachable!("unary `{}` as an arithmetic operator", operator.symbol()),
+        }
+    }
+
+    /// The size values of `ty` are worked on as: 4 bytes for any up to that
+    /// wide, and 8 for the rest.
+    fn width(&self, ty: &Type) -> i64 {
+        if self.size_of(ty) == 8 { 8 } else { 4 }
+    }
+
+    /// The type `expression`'s value is used as: its own, decayed, or the one
+    /// it is implicitly converted to.
+    fn value_type(&self, expression: ExprId) -> Type {
+        match self.analysis.conversions.get(&expression) {
+            Some(ty) => ty.clone(),
+            None => self.analysis.types[&expression].decay(),
+        }
+    }
+
+    /// Applies `operator` to the left operand in the accumulator and the right
+    /// one in the scratch register, which are both of type `operands` after
+    /// conversion.
+    fn binary(&mut self, operator: BinaryOp, operands: &Type) {
+        if !operands.is_scalar() {
+            unimplemented!("codegen for binary `{}` on `{}`", operator.symbol(), operands);
+        }
+        // Pointers compare as unsigned addresses.
+        let signed = operands.is_signed();
+        let size = self.width(operands);
+        match operator {
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::ShiftLeft | BinaryOp::ShiftRight
+            | BinaryOp::BitwiseAnd | BinaryOp::BitwiseXor | BinaryOp::BitwiseOr => {
+                self.emit(Instruction::Binary(operator, size, signed, Source::Scratch));
+            },
+            BinaryOp::Divide => self.divide(operands, false),
+            BinaryOp::Modulo => self.divide(operands, true),
+            BinaryOp::Equal => self.emit(Instruction::Compare(size, Condition::Equal)),
+            BinaryOp::NotEqual => self.emit(Instruction::Compare(size, Condition::NotEqual)),
+            BinaryOp::LessThan => self.emit(Instruction::Compare(size, if signed { Condition::Less } else { Condition::Below })),
+            BinaryOp::LessEqual => self.emit(Instruction::Compare(size, if signed { Condition::LessEqual } else { Condition::BelowEqual })),
+            BinaryOp::GreaterThan => self.emit(Instruction::Compare(size, if signed { Condition::Greater } else { Condition::Above })),
+            BinaryOp::GreaterEqual => self.emit(Instruction::Compare(size, if signed { Condition::GreaterEqual } else { Condition::AboveEqual })),
+            operator => unreachable!("binary `{}` as an arithmetic operator", operator.symbol()),
+        }
+    }
+
+    /// The value of `expression` if it is made of constants, as the type it
+    /// is used as.
+    fn constant(&self, expression: ExprId) -> Option<i64> {
+        let evaluator = fold::Evaluator { types: &self.analysis.types, conversions: &self.analysis.conversions };
+        evaluator.operand(self.ast, expression)
+    }
+
+    /// Divides the `operands` in the accumulator by the one in the scratch
+    /// register, leaving the quotient or the `remainder` in the accumulator.
+    fn divide(&mut self, operands: &Type, remainder: bool) {
+        let size = self.width(operands);
+        let zero = if self.options.checked_arithmetic {
+            self.division_checked = true;
+            Some(self.options.target.os.local_label(DIVISION_BY_ZERO))
+        } else {
+            None
+        };
+        self.emit(Instruction::Divide(size, operands.is_signed(), remainder, zero));
+    }
+}
+/// Writes `bytes` as the contents of a string for the assembler: printable
+/// characters as they are, except for `"` and `\\`, which are escaped, and any
+/// other byte as an octal escape.
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                escaped.push('\\');
+                escaped.push(byte as char);
+            },
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => write!(escaped, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    escaped
+}
+
+/// The constructs in `program` that code can't be generated for yet in the
+/// format `options` ask for, as errors. Only WebAssembly has any so far.
+pub fn check(program: &Program, options: &Options) -> Vec<semantic::SemanticError> {
+    match options.format {
+        Format::Wat => wasm::check(program),
+        Format::Assembly | Format::LlvmIr => Vec::new(),
+    }
+}
+
+/// Generates the assembly for `program`, which must have passed semantic
+/// analysis without errors, with the default options.
+pub fn generate(program: &Program) -> String {
+    generate_with(program, &Options::default())
+}
+
+/// Generates the assembly for `program`, which must have passed semantic
+/// analysis, and `check`, without errors. The analysis is redone for the
+/// types codegen needs.
+pub fn generate_with(program: &Program, options: &Options) -> String {
+    generate_timed(program, options).0
+}
+
+/// Like `generate_with`, but also says how long each optimization pass took.
+pub fn generate_timed(program: &Program, options: &Options) -> (String, Timings) {
+    let mut timings = Timings::default();
+    let mut optimized = Cow::Borrowed(program);
+    if options.opt_level >= OptLevel::O1 {
+        passes::tree().run(optimized.to_mut(), options, &mut timings);
+    }
+    let program = &*optimized;
+    let (analysis, _) = semantic::analyze(program);
+    let mut statics = StaticsBuilder {
+        values: &analysis.statics,
+        local: false,
+        labels: HashMap::new(),
+        objects: Vec::new(),
+        internal: HashSet::new(),
+    };
+    statics.visit_program(program);
+    if options.format == Format::LlvmIr {
+        return (llvm::generate(program, &analysis, &statics, options), timings);
+    }
+    if options.format == Format::Wat {
+        return (wasm::generate(program, &analysis, &statics, options), timings);
+    }
+    let mut lowering = Lowering { program, inlined: HashMap::new() };
+    passes::lowering().run(&mut lowering, options, &mut timings);
+    let assembly = Assembly { output: String::new(), labels: 0, function: String::new(), target_os: options.target.os };
+    let machine: Box<dyn Machine> = match options.target.arch {
+        Architecture::X86_64 => {
+            let frame_pointer = !options.omit_frame_pointer.unwrap_or(options.opt_level >= OptLevel::O2);
+            Box::new(X86_64::new(assembly, options.syntax, options.target.calling_convention, frame_pointer))
+        },
+        Architecture::Aarch64 if options.target.os == TargetOs::Windows => unimplemented!("codegen for Windows on AArch64"),
+        Architecture::Aarch64 => Box::new(Aarch64::new(assembly)),
+    };
+    let mut generator = Generator {
+        ast: &program.ast,
+        items: &program.items,
+        analysis: &analysis,
+        layouts: Layouts::new(program),
+        machine,
+        statics: statics.labels,
+        strings: Vec::new(),
+        frame: HashMap::new(),
+        code: Vec::new(),
+        temps: 0,
+        breaks: Vec::new(),
+        continues: Vec::new(),
+        cases: HashMap::new(),
+        goto_labels: HashMap::new(),
+        tables: Vec::new(),
+        inlined: lowering.inlined,
+        passes: passes::code(),
+        timings,
+        options,
+        division_checked: false,
+        source_lines: options.source.as_deref().map(LineIndex::new),
+        commented_line: 0,
+    };
+    if options.debug_info == DebugInfo::Lines {
+        let path = options.source_path.as_deref().unwrap_or("<source>");
+        generator.directive(&format!(".file 1 \"{}\"", escape(path.as_bytes())));
+    }
+    generator.directive(".text");
+    for (index, item) in program.items.iter().enumerate() {
+        match *item {
+            TopLevel::Function(ref function) => generator.function(index, function),
+            TopLevel::Struct(_) | TopLevel::Declaration(_) => {},
+        }
+    }
+    if generator.division_checked {
+        generator.division_by_zero();
+    }
+    for object in &statics.objects {
+        let external = !object.local && !statics.internal.contains(&object.label);
+        generator.static_object(object, external);
+    }
+    let target_os = options.target.os;
+    if !generator.strings.is_empty() {
+        generator.directive(match target_os {
+            TargetOs::Linux => ".section .rodata",
+            TargetOs::MacOs => ".cstring",
+            TargetOs::Windows => ".section .rdata,\"dr\"",
+        });
+        for (index, bytes) in generator.strings.clone().iter().enumerate() {
+            generator.machine.assembly().label(&target_os.local_label(&format!("str{}", index)));
+            generator.directive(&format!(".asciz \"{}\"", escape(bytes)));
+        }
+    }
+    if !generator.tables.is_empty() {
+        // Jump tables hold absolute addresses, which the dynamic linker fills in
+        // when `gcc` links a position-independent executable, as it does by
+        // default; `.rodata` can't have that done to it. The Windows loader
+        // relocates read-only sections too.
+        generator.directive(match target_os {
+            TargetOs::Linux => ".section .data.rel.ro",
+            TargetOs::MacOs => ".const_data",
+            TargetOs::Windows => ".section .rdata,\"dr\"",
+        });
+        generator.directive(".balign 8");
+        for (label, targets) in generator.tables.clone() {
+            generator.machine.assembly().label(&label);
+            for target in targets {
+                generator.directive(&format!(".quad {}", target));
+            }
+        }
+    }
+    // Marks the stack as non-executable, which the GNU linker otherwise warns
+    // about. Mach-O stacks are never executable.
+    if target_os == TargetOs::Linux {
+        generator.directive(".section .note.GNU-stack,\"\",@progbits");
+    }
+    (mem::take(&mut generator.machine.assembly().output), generator.timings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    /// Generates `source` for Linux, whatever the host is.
+    fn generate_source(source: &str) -> String {
+        generate_for(source, TargetOs::Linux)
+    }
+
+    fn generate_for(source: &str, target_os: TargetOs) -> String {
+        let options = Options { target: TargetSpec::new(Architecture::X86_64, target_os), debug_info: DebugInfo::Off, ..Options::default() };
+        generate_with(&parse_program(&lex_str(source).unwrap()).unwrap(), &options)
+    }
+
+    fn generate_in(source: &str, syntax: Syntax) -> String {
+        let options = Options {
+            target: TargetSpec::new(Architecture::X86_64, TargetOs::Linux),
+            syntax,
+            debug_info: DebugInfo::Off,
+            ..Options::default()
+        };
+        generate_with(&parse_program(&lex_str(source).unwrap()).unwrap(), &options)
+    }
+
+    #[test]
+    fn targets_are_named() {
+        for name in TARGETS {
+            assert_eq!(TargetSpec::from_name(name).unwrap().name(), *name);
+        }
+        let windows = TargetSpec::from_name("x86_64-windows").unwrap();
+        assert_eq!(windows, TargetSpec { arch: Architecture::X86_64, os: TargetOs::Windows, calling_convention: CallingConvention::Windows });
+        assert_eq!(TargetSpec::from_name("aarch64-windows"), None);
+        assert_eq!(TargetSpec::from_name("x86_64"), None);
+    }
+
+    #[test]
+    fn return_a_constant() {
+        assert_eq!(generate_source("int main() { return 2; }"), "    .text
+    .globl main
+main:
+    pushq %rbp
+    movq %rsp, %rbp
+    movl $2, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .section .note.GNU-stack,\"\",@progbits
+");
+    }
+
+    #[test]
+    fn statements_are_marked_with_their_source_positions() {
+        let source = "int add(int a, int b) {\n    int c = a + b;\n    if (c)\n        return c;\n    return 0;\n}";
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let options = Options {
+            target: TargetSpec::new(Architecture::X86_64, TargetOs::Linux),
+            source_path: Some("add.c".to_owned()),
+            ..Options::default()
+        };
+        let assembly = generate_with(&program, &options);
+        let lines: Vec<_> = assembly.lines().map(str::trim).collect();
+        assert_eq!(&lines[..6], &[".file 1 \"add.c\"", ".text", ".globl add", ".type add, @function", "add:", ".loc 1 1 1"]);
+        let positions: Vec<_> = lines.iter().cloned().filter(|line| line.starts_with(".loc")).collect();
+        assert_eq!(positions, [".loc 1 1 1", ".loc 1 2 9", ".loc 1 3 5", ".loc 1 4 9", ".loc 1 5 5", ".loc 1 6 1"]);
+        let end = lines.iter().position(|line| *line == ".loc 1 6 1").unwrap();
+        assert_eq!(&lines[end..end + 3], &[".loc 1 6 1", "movl $0, %eax", "movq %rbp, %rsp"]);
+        assert!(assembly.contains("    ret\n    .size add, .-add\n"), "{}", assembly);
+
+        // Mach-O has no symbol types or sizes.
+        let macos = generate_with(&program, &Options { target: TargetSpec { os: TargetOs::MacOs, ..options.target }, ..options });
+        assert!(macos.contains("_add:\n    .loc 1 1 1\n"), "{}", macos);
+        assert!(!macos.contains(".type") && !macos.contains(".size"), "{}", macos);
+        assert!(!generate_source(source).contains(".loc"));
+    }
+
+    #[test]
+    fn source_lines_are_interleaved_as_comments() {
+        let source = "int add(int a, int b) {\n    int c = a\n        + b;\n    if (c) return c; return 0;\n}\n";
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let options = Options {
+            target: TargetSpec::new(Architecture::X86_64, TargetOs::Linux),
+            debug_info: DebugInfo::Off,
+            source: Some(source.to_owned()),
+            source_comments: true,
+            ..Options::default()
+        };
+        assert_eq!(generate_with(&program, &options), "    .text
+    .globl add
+add:
+    # 1: int add(int a, int b) {
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $16, %rsp
+    movl %edi, -4(%rbp)
+    movl %esi, -8(%rbp)
+    # 2: int c = a
+    movl -4(%rbp), %eax
+    movq %rax, %r10
+    movl -8(%rbp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movl %eax, -12(%rbp)
+    # 4: if (c) return c; return 0;
+    movl -12(%rbp), %eax
+    cmpl $0, %eax
+    je .Ladd_end_1
+    movl -12(%rbp), %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+.Ladd_end_1:
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    # 5: }
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .section .note.GNU-stack,\"\",@progbits
+");
+        let aarch64 = generate_with(&program, &Options { target: TargetSpec { arch: Architecture::Aarch64, ..options.target }, ..options.clone() });
+        assert!(aarch64.contains("add:\n    // 1: int add(int a, int b) {\n"), "{}", aarch64);
+
+        let uncommented = generate_with(&program, &Options { source_comments: false, ..options });
+        assert!(!uncommented.contains('#'), "{}", uncommented);
+        assert_eq!(uncommented, generate_source(source));
+    }
+
+    /// The instructions and local labels generated for the body of `main`, one
+    /// per line, after the prologue and up to the first epilogue.
+    fn main_body(source: &str) -> Vec<String> {
+        generate_source(source).lines()
+            .skip_while(|line| *line != "main:")
+            .skip(3)
+            .take_while(|line| line.trim() != "movq %rbp, %rsp")
+            .map(|line| line.trim().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(main_body("int main() { return -3; }"), vec!["movl $3, %eax", "negl %eax"]);
+        assert_eq!(main_body("int main() { return ~3; }"), vec!["movl $3, %eax", "notl %eax"]);
+        assert_eq!(main_body("int main() { return !3; }"), vec![
+            "movl $3, %eax", "cmpl $0, %eax", "sete %al", "movzbl %al, %eax",
+        ]);
+    }
+
+    #[test]
+    fn nested_unary_operators_apply_innermost_first() {
+        assert_eq!(main_body("int main() { return !-~3; }"), vec![
+            "movl $3, %eax", "notl %eax", "negl %eax", "cmpl $0, %eax", "sete %al", "movzbl %al, %eax",
+        ]);
+    }
+
+    #[test]
+    fn binary_operators_evaluate_the_left_operand_first() {
+        let operands = ["movl $7, %eax", "movq %rax, %r10", "movl $2, %eax", "movl %eax, %ecx", "movq %r10, %rax"];
+        for &(operator, instructions) in &[
+            ("+", &["addl %ecx, %eax"][..]),
+            ("-", &["subl %ecx, %eax"]),
+            ("*", &["imull %ecx, %eax"]),
+            ("/", &["cltd", "idivl %ecx"]),
+            ("%", &["cltd", "idivl %ecx", "movl %edx, %eax"]),
+        ] {
+            let mut expected: Vec<&str> = operands.to_vec();
+            expected.extend(instructions);
+            assert_eq!(main_body(&format!("int main() {{ return 7 {} 2; }}", operator)), expected, "{}", operator);
+        }
+    }
+
+    #[test]
+    fn nested_binary_operators_take_a_register_each() {
+        let body = main_body("int main() { return (1 + 2) * (3 - (4 / 5)) % -6; }");
+        let saves: Vec<_> = body.iter().filter(|line| line.starts_with("movq %rax, %r")).collect();
+        assert_eq!(saves, ["movq %rax, %r10", "movq %rax, %r10", "movq %rax, %r11", "movq %rax, %rbx", "movq %rax, %r10"]);
+        // `%rbx` is preserved for the caller.
+        let assembly = generate_source("int main() { return (1 + 2) * (3 - (4 / 5)) % -6; }");
+        assert!(assembly.contains("    subq $16, %rsp\n    movq %rbx, -8(%rbp)\n"), "{}", assembly);
+        assert!(assembly.contains("    movq -8(%rbp), %rbx\n    movq %rbp, %rsp\n"), "{}", assembly);
+    }
+
+    #[test]
+    fn comparisons_set_zero_or_one() {
+        for &(operator, set) in &[("==", "sete"), ("!=", "setne"), ("<", "setl"), ("<=", "setle"), (">", "setg"), (">=", "setge")] {
+            let body = main_body(&format!("int main() {{ return 7 {} 2; }}", operator));
+            let set = format!("{} %al", set);
+            assert_eq!(&body[5..], &["cmpl %ecx, %eax", &set, "movzbl %al, %eax"][..], "{}", operator);
+        }
+    }
+
+    #[test]
+    fn comparisons_use_the_signedness_of_the_converted_operands() {
+        let compares = |source: &str| -> Vec<String> {
+            main_body(source).into_iter().filter(|line| line.starts_with("set")).collect()
+        };
+        let source = "int main() { return ((unsigned)1 < 1) + ((unsigned)1 <= 1) + ((unsigned)1 > 1) + ((unsigned)1 >= 1); }";
+        assert_eq!(compares(source), vec!["setb %al", "setbe %al", "seta %al", "setae %al"]);
+        // `-1` converts to `unsigned int` to compare with the right operand.
+        assert_eq!(compares("int main() { return -1 < (unsigned)1; }"), vec!["setb %al"]);
+        assert_eq!(compares("int main() { return -1 < (int)1; }"), vec!["setl %al"]);
+    }
+
+    #[test]
+    fn logical_operators_jump_over_the_right_operand() {
+        assert_eq!(main_body("int main() { return 1 && 2; }"), vec![
+            "movl $1, %eax", "cmpl $0, %eax", "je .Lmain_end_1", "movl $2, %eax", "cmpl $0, %eax",
+            ".Lmain_end_1:", "setne %al", "movzbl %al, %eax",
+        ]);
+        assert_eq!(main_body("int main() { return 1 || 2; }"), vec![
+            "movl $1, %eax", "cmpl $0, %eax", "jne .Lmain_end_1", "movl $2, %eax", "cmpl $0, %eax",
+            ".Lmain_end_1:", "setne %al", "movzbl %al, %eax",
+        ]);
+    }
+
+    #[test]
+    fn nested_logical_operators_get_their_own_labels() {
+        let body = main_body("int main() { return 1 && (0 || 3); }");
+        assert_eq!(body.iter().filter(|line| line.starts_with(".L")).collect::<Vec<_>>(), vec![".Lmain_end_2:", ".Lmain_end_1:"]);
+        assert_eq!(&body[..3], &["movl $1, %eax", "cmpl $0, %eax", "je .Lmain_end_1"]);
+        assert!(body.contains(&"jne .Lmain_end_2".to_owned()));
+    }
+
+    #[test]
+    fn locals_get_their_own_slots() {
+        let assembly = generate_source("int main() { int a = 1; { int b = 2; a = b; } int c; c = a; return c; }");
+        let lines: Vec<_> = assembly.lines().map(str::trim).collect();
+        assert_eq!(&lines[3..6], &["pushq %rbp", "movq %rsp, %rbp", "subq $16, %rsp"]);
+        assert_eq!(main_body("int main() { int a = 1; { int b = 2; a = b; } int c; c = a; return c; }"), vec![
+            "subq $16, %rsp",
+            "movl $1, %eax", "movl %eax, -4(%rbp)",
+            "movl $2, %eax", "movl %eax, -8(%rbp)",
+            "movl -8(%rbp), %eax", "movl %eax, -4(%rbp)",
+            "movl -4(%rbp), %eax", "movl %eax, -12(%rbp)",
+            "movl -12(%rbp), %eax",
+        ]);
+    }
+
+    #[test]
+    fn compound_assignments_operate_on_the_slot() {
+        assert_eq!(main_body("int main() { int a = 7; a -= 2; return a; }"), vec![
+            "subq $16, %rsp",
+            "movl $7, %eax", "movl %eax, -4(%rbp)",
+            "movl $2, %eax", "movl %eax, %ecx", "movl -4(%rbp), %eax", "subl %ecx, %eax", "movl %eax, -4(%rbp)",
+            "movl -4(%rbp), %eax",
+        ]);
+    }
+
+    #[test]
+    fn every_return_restores_the_stack_pointer() {
+        let assembly = generate_source("int main() { int a = 1; return a; }");
+        let lines: Vec<_> = assembly.lines().map(str::trim).collect();
+        for (index, _) in lines.iter().enumerate().filter(|&(_, line)| *line == "ret") {
+            assert_eq!(&lines[index - 2..index], &["movq %rbp, %rsp", "popq %rbp"]);
+        }
+    }
+
+    #[test]
+    fn if_else_jumps_around_the_branches() {
+        assert_eq!(main_body("int main() { if (1) return 2; else return 3; }"), vec![
+            "movl $1, %eax", "cmpl $0, %eax", "je .Lmain_else_2",
+            "movl $2, %eax",
+        ]);
+        let assembly = generate_source("int main() { int a = 0; if (a) a = 2; else a = 3; return a; }");
+        let lines: Vec<_> = assembly.lines().map(str::trim).collect();
+        let start = lines.iter().position(|line| *line == "je .Lmain_else_2").unwrap();
+        assert_eq!(&lines[start..start + 8], &[
+            "je .Lmain_else_2", "movl $2, %eax", "movl %eax, -4(%rbp)", "jmp .Lmain_end_1",
+            ".Lmain_else_2:", "movl $3, %eax", "movl %eax, -4(%rbp)", ".Lmain_end_1:",
+        ]);
+    }
+
+    #[test]
+    fn labels_are_unique_across_functions() {
+        let assembly = generate_source(
+            "int f() { int a = 1; if (a) a = 2; if (a && a) { if (a) a = 3; } else a = 4; return a; }\n\
+             int main() { int b = 0; if (b || 1) b = 1; else if (b) b = 2; return b; }"
+        );
+        let mut labels: Vec<_> = assembly.lines().filter(|line| line.starts_with(".L")).collect();
+        let count = labels.len();
+        assert_eq!(count, 9);
+        assert_eq!(labels.iter().filter(|label| label.starts_with(".Lf_")).count(), 5, "{}", assembly);
+        assert_eq!(labels.iter().filter(|label| label.starts_with(".Lmain_")).count(), 4, "{}", assembly);
+        labels.sort();
+        labels.dedup();
+        assert_eq!(labels.len(), count, "{}", assembly);
+    }
+
+    #[test]
+    fn loops_test_their_condition_at_the_right_end() {
+        assert_eq!(main_body("int main() { while (1) ; return 0; }"), vec![
+            ".Lmain_loop_1:", "movl $1, %eax", "cmpl $0, %eax", "je .Lmain_end_2", "jmp .Lmain_loop_1", ".Lmain_end_2:", "movl $0, %eax",
+        ]);
+        assert_eq!(main_body("int main() { do ; while (1); return 0; }"), vec![
+            ".Lmain_loop_1:", ".Lmain_next_2:", "movl $1, %eax", "cmpl $0, %eax", "jne .Lmain_loop_1", ".Lmain_end_3:", "movl $0, %eax",
+        ]);
+    }
+
+    #[test]
+    fn continue_in_a_for_loop_runs_the_post_expression() {
+        let body = main_body("int main() { int i; for (i = 0; i < 3; i += 1) { if (i) continue; break; } return i; }");
+        let next = body.iter().position(|line| line == ".Lmain_next_2:").unwrap();
+        assert_eq!(body[next + 1], "movl $1, %eax");
+        assert!(body.contains(&"jmp .Lmain_next_2".to_owned()));
+        assert!(body.contains(&"jmp .Lmain_end_3".to_owned()));
+        assert_eq!(body.iter().filter(|line| *line == "jmp .Lmain_loop_1").count(), 1);
+    }
+
+    #[test]
+    fn register_parameters_are_copied_to_slots() {
+        let assembly = generate_source("int f(int a, int b) { return a - b; }\nint main() { return 0; }");
+        let lines: Vec<_> = assembly.lines().map(str::trim).collect();
+        let start = lines.iter().position(|line| *line == "f:").unwrap();
+        assert_eq!(&lines[start + 1..start + 9], &[
+            "pushq %rbp", "movq %rsp, %rbp", "subq $16, %rsp",
+            "movl %edi, -4(%rbp)", "movl %esi, -8(%rbp)",
+            "movl -4(%rbp), %eax", "movq %rax, %r10", "movl -8(%rbp), %eax",
+        ]);
+    }
+
+    #[test]
+    fn arguments_are_evaluated_right_to_left() {
+        let body = main_body(
+            "int f(int a, int b, int c, int d, int e, int f, int g, int h);\n\
+             int main() { return f(1, 2, 3, 4, 5, 6, 7, 8); }"
+        );
+        let homes = ["%r10", "%r11", "%rbx", "%r12", "%r13", "%r14", "%r15", "-8(%rbp)"];
+        let mut expected = Vec::new();
+        for (argument, home) in (1..9).rev().zip(&homes) {
+            expected.push(format!("movl ${}, %eax", argument));
+            expected.push(format!("movq %rax, {}", home));
+        }
+        expected.push("pushq %r10".to_owned());
+        expected.push("pushq %r11".to_owned());
+        for (register, home) in ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"].iter().zip(homes.iter().rev()) {
+            expected.push(format!("movq {}, {}", home, register));
+        }
+        expected.push("call f".to_owned());
+        expected.push("addq $16, %rsp".to_owned());
+        let start = body.iter().position(|line| line == "movl $8, %eax").unwrap();
+        assert_eq!(body[start..start + expected.len()], expected[..]);
+    }
+
+    #[test]
+    fn temporaries_live_across_calls_in_preserved_registers() {
+        let body = main_body("int f(int x);\nint main() { return 1 + f(2); }");
+        assert_eq!(body, vec![
+            "subq $16, %rsp", "movq %rbx, -8(%rbp)",
+            "movl $1, %eax", "movq %rax, %rbx",
+            "movl $2, %eax", "movq %rax, %r10", "movq %r10, %rdi", "call f",
+            "movl %eax, %ecx", "movq %rbx, %rax", "addl %ecx, %eax",
+            "movq -8(%rbp), %rbx",
+        ]);
+    }
+
+    #[test]
+    fn calls_are_padded_to_a_16_byte_boundary() {
+        // One stack argument needs padding, and two don't.
+        let body = main_body("int f(int a, int b, int c, int d, int e, int f, int g);\nint main() { return f(1, 2, 3, 4, 5, 6, 7); }");
+        assert!(body.contains(&"subq $8, %rsp".to_owned()));
+        assert!(body.contains(&"addq $16, %rsp".to_owned()));
+        let body = main_body("int f(int a, int b, int c, int d, int e, int f, int g, int h);\nint main() { return f(1, 2, 3, 4, 5, 6, 7, 8); }");
+        assert!(!body.contains(&"subq $8, %rsp".to_owned()));
+        assert!(body.contains(&"addq $16, %rsp".to_owned()));
+    }
+
+    #[test]
+    fn stack_parameters_are_read_above_the_return_address() {
+        let assembly = generate_source("int f(int a, int b, int c, int d, int e, int f, int g, int h) { return g - h; }");
+        assert!(assembly.contains("movl 16(%rbp), %eax"));
+        assert!(assembly.contains("movl 24(%rbp), %eax"));
+    }
+
+    #[test]
+    fn variadic_calls_clear_al() {
+        let body = main_body("int f();\nint g(int x);\nint main() { return f() + g(1); }");
+        assert!(!body.contains(&"movl $0, %eax".to_owned()));
+        let body = main_body("int printf(const char *format, ...);\nint main() { return printf(0); }");
+        assert_eq!(&body[body.len() - 2..], &["movl $0, %eax", "call printf"]);
+    }
+
+    #[test]
+    fn globals_are_addressed_relative_to_rip() {
+        let assembly = generate_source("int x = 3;\nint y;\nint main() { y = x; return y; }");
+        assert!(assembly.contains("    movl x(%rip), %eax\n    movl %eax, y(%rip)\n"), "{}", assembly);
+        assert!(assembly.ends_with("    .data
+    .balign 4
+    .globl x
+x:
+    .long 3
+    .bss
+    .balign 4
+    .globl y
+y:
+    .zero 4
+    .section .note.GNU-stack,\"\",@progbits
+"), "{}", assembly);
+    }
+
+    #[test]
+    fn globals_are_defined_once() {
+        let assembly = generate_source("int x;\nextern int x;\nint x = 5;\nextern int z;\nint main() { return x + z; }");
+        assert_eq!(assembly.matches("\nx:\n").count(), 1);
+        assert!(assembly.contains("x:\n    .long 5\n"));
+        assert!(!assembly.contains("\nz:"));
+    }
+
+    #[test]
+    fn static_objects_are_not_global() {
+        let assembly = generate_source(
+            "static int hidden = 1;\nstatic int helper() { return hidden; }\n\
+             int f() { static int count; count += 1; return count; }\n\
+             int g() { static int count = 4; return count; }\nint main() { return helper() + f() + g(); }"
+        );
+        for name in &["hidden", "helper", "count"] {
+            assert!(!assembly.contains(&format!(".globl {}", name)), "{}", assembly);
+        }
+        let counts: Vec<_> = assembly.lines().filter(|line| line.starts_with("count.")).collect();
+        assert_eq!(counts.len(), 2);
+        assert_ne!(counts[0], counts[1]);
+        assert!(assembly.contains(".globl main"));
+    }
+
+    #[test]
+    fn string_literals_are_loaded_by_address() {
+        let assembly = generate_source("int puts(const char *s);\nint main() { puts(\"hi\"); return 0; }");
+        assert!(assembly.contains("    leaq .Lstr0(%rip), %rax\n    movq %rax, %r10\n    movq %r10, %rdi\n"), "{}", assembly);
+        assert!(assembly.ends_with("    .section .rodata
+.Lstr0:
+    .asciz \"hi\"
+    .section .note.GNU-stack,\"\",@progbits
+"), "{}", assembly);
+    }
+
+    #[test]
+    fn identical_string_literals_share_a_label() {
+        let assembly = generate_source(
+            "int puts(const char *s);\nchar *global = \"a\";\nint main() { char *s = \"a\"; puts(\"b\"); puts(\"a\"); return 0; }"
+        );
+        assert_eq!(assembly.matches(".asciz").count(), 2);
+        assert_eq!(assembly.matches("leaq .Lstr0(%rip)").count(), 2);
+        assert!(assembly.contains("global:\n    .quad .Lstr0\n"));
+    }
+
+    #[test]
+    fn strings_are_escaped_for_the_assembler() {
+        assert_eq!(escape(b"plain text"), "plain text");
+        assert_eq!(escape(b"say \"hi\"\\n"), "say \\\"hi\\\"\\\\n");
+        assert_eq!(escape(b"line\n\0\t\x7f"), "line\\012\\000\\011\\177");
+    }
+
+    #[test]
+    fn chars_are_loaded_and_stored_as_bytes() {
+        assert_eq!(main_body("int main() { char c = 'a'; int i = 1; c = c + i; return c; }"), vec![
+            "subq $16, %rsp",
+            "movl $97, %eax", "movsbl %al, %eax", "movb %al, -1(%rbp)",
+            "movl $1, %eax", "movl %eax, -8(%rbp)",
+            "movsbl -1(%rbp), %eax", "movq %rax, %r10", "movl -8(%rbp), %eax", "movl %eax, %ecx", "movq %r10, %rax",
+            "addl %ecx, %eax", "movsbl %al, %eax", "movb %al, -1(%rbp)",
+            "movsbl -1(%rbp), %eax",
+        ]);
+    }
+
+    #[test]
+    fn char_parameters_are_copied_as_bytes() {
+        let assembly = generate_source("int f(char a, int b, char c) { return a + b + c; }");
+        assert!(assembly.contains("movb %dil, -1(%rbp)\n    movl %esi, -8(%rbp)\n    movb %dl, -9(%rbp)\n"), "{}", assembly);
+    }
+
+    #[test]
+    fn longs_are_worked_on_in_64_bit_registers() {
+        assert_eq!(main_body("int main() { long a = 3; return a / 2 < a; }"), vec![
+            "subq $16, %rsp",
+            "movl $3, %eax", "movslq %eax, %rax", "movq %rax, -8(%rbp)",
+            "movq -8(%rbp), %rax", "movq %rax, %r10", "movl $2, %eax", "movslq %eax, %rax",
+            "movq %rax, %rcx", "movq %r10, %rax", "cqto", "idivq %rcx",
+            "movq %rax, %r10", "movq -8(%rbp), %rax", "movq %rax, %rcx", "movq %r10, %rax",
+            "cmpq %rcx, %rax", "setl %al", "movzbl %al, %eax",
+        ]);
+    }
+
+    #[test]
+    fn unsigned_ints_are_zero_extended_to_long() {
+        let body = main_body("int main() { unsigned u = 1; long l = u; return l; }");
+        assert!(body.contains(&"movl %eax, %eax".to_owned()), "{:?}", body);
+        assert!(!body.contains(&"movslq %eax, %rax".to_owned()), "{:?}", body);
+    }
+
+    #[test]
+    fn unsigned_operands_select_unsigned_instructions() {
+        let instructions = |source: &str| -> Vec<String> {
+            let body = main_body(source);
+            let start = body.iter().position(|line| line == "movq %r10, %rax").unwrap();
+            body[start + 1..].to_vec()
+        };
+        assert_eq!(instructions("int main() { return (unsigned)7 / 2; }"), vec!["xorl %edx, %edx", "divl %ecx"]);
+        assert_eq!(instructions("int main() { return (unsigned)7 % 2; }"), vec!["xorl %edx, %edx", "divl %ecx", "movl %edx, %eax"]);
+        assert_eq!(instructions("int main() { return (unsigned long)7 / 2; }"), vec!["xorq %rdx, %rdx", "divq %rcx"]);
+        assert_eq!(instructions("int main(int n) { return (unsigned)7 >> n; }"), vec!["shrl %cl, %eax"]);
+        assert_eq!(instructions("int main(int n) { return 7 >> n; }"), vec!["sarl %cl, %eax"]);
+        assert_eq!(instructions("int main(int n) { return (unsigned)7 << n; }"), vec!["shll %cl, %eax"]);
+    }
+
+    #[test]
+    fn pointers_are_dereferenced_through_rax() {
+        assert_eq!(main_body("int main() { int x = 1; int *p = &x; *p = 2; return *p; }"), vec![
+            "subq $16, %rsp",
+            "movl $1, %eax", "movl %eax, -4(%rbp)",
+            "leaq -4(%rbp), %rax", "movq %rax, -16(%rbp)",
+            "movq -16(%rbp), %rax", "movq %rax, %r10", "movl $2, %eax", "movq %r10, %rsi", "movl %eax, (%rsi)",
+            "movq -16(%rbp), %rax", "movl (%rax), %eax",
+        ]);
+    }
+
+    #[test]
+    fn pointer_arithmetic_is_scaled_by_the_pointee_size() {
+        let body = main_body("int main() { long x; long *p = &x; long *q = p + 3; return q - p; }");
+        assert!(body.contains(&"imulq $8, %rax".to_owned()), "{:?}", body);
+        assert!(body.ends_with(&["subq %rcx, %rax".to_owned(), "movq $8, %rcx".to_owned(), "cqto".to_owned(), "idivq %rcx".to_owned()]), "{:?}", body);
+        let body = main_body("int main() { char c; char *p = &c; p = p + 3; return 0; }");
+        assert!(!body.iter().any(|line| line.starts_with("imulq")), "{:?}", body);
+    }
+
+    #[test]
+    fn increments_keep_the_old_value_in_ecx() {
+        assert_eq!(main_body("int main() { int x = 1; return x++; }")[3..], [
+            "movl -4(%rbp), %eax", "movl %eax, %ecx", "addl $1, %eax", "movl %eax, -4(%rbp)", "movl %ecx, %eax",
+        ]);
+        assert_eq!(main_body("int main() { int x; int *p = &x; return *--p; }")[3..6], [
+            "movq -16(%rbp), %rax", "movq %rax, %rcx", "subq $4, %rax",
+        ]);
+    }
+
+    #[test]
+    fn prototypes_generate_nothing() {
+        let assembly = generate_source("int f();\nint main() { return 0; }");
+        assert!(!assembly.contains("f:"));
+        assert!(assembly.contains(".globl main\nmain:\n"));
+    }
+
+    #[test]
+    fn arrays_decay_to_their_address() {
+        let body = main_body("int main() { int a[3]; int *p = a; return a[2]; }");
+        assert_eq!(body[1], "leaq -12(%rbp), %rax");
+        assert!(body.ends_with(&["addq %rcx, %rax".to_owned(), "movl (%rax), %eax".to_owned()]), "{:?}", body);
+    }
+
+    #[test]
+    fn local_array_initializers_zero_what_they_leave_out() {
+        assert_eq!(main_body("int main() { int a[4] = { 1, 2 }; return 0; }")[1..7], [
+            "movl $1, %eax", "movl %eax, -16(%rbp)", "movl $2, %eax", "movl %eax, -12(%rbp)", "movq $0, -8(%rbp)",
+            "movl $0, %eax",
+        ]);
+        assert_eq!(main_body("int main() { char s[6] = \"hi\"; return 0; }")[1..6], [
+            "movb $104, -6(%rbp)", "movb $105, -5(%rbp)", "movb $0, -4(%rbp)", "movb $0, -3(%rbp)", "movb $0, -2(%rbp)",
+        ]);
+    }
+
+    #[test]
+    fn static_arrays_are_initialized_element_by_element() {
+        let assembly = generate_source("int a[4] = { 1, 2 };\nint x;\nint *p[2] = { &x, &a[1] };\nint main() { return 0; }");
+        assert!(assembly.contains("a:\n    .long 1\n    .long 2\n    .zero 8\n"), "{}", assembly);
+        assert!(assembly.contains("p:\n    .quad x\n    .quad a+4\n"), "{}", assembly);
+    }
+
+    #[test]
+    fn struct_members_are_aligned() {
+        let program = parse_program(&lex_str(
+            "struct padded { char c; int i; char d; };\nstruct outer { char c; struct padded inner; long l; };"
+        ).unwrap()).unwrap();
+        let layouts = Layouts::new(&program);
+        let padded = Type::Struct("padded".to_owned());
+        assert_eq!((layouts.size_of(&padded), layouts.alignment(&padded)), (12, 4));
+        assert_eq!(["c", "i", "d"].iter().map(|member| layouts.offset_of("padded", member)).collect::<Vec<_>>(), [0, 4, 8]);
+        let outer = Type::Struct("outer".to_owned());
+        assert_eq!((layouts.size_of(&outer), layouts.alignment(&outer)), (24, 8));
+        assert_eq!(["c", "inner", "l"].iter().map(|member| layouts.offset_of("outer", member)).collect::<Vec<_>>(), [0, 4, 16]);
+    }
+
+    #[test]
+    fn members_are_addressed_by_offset() {
+        let body = main_body("struct s { char c; int i; };\nint main() { struct s x; struct s *p = &x; x.i = 1; return p->i; }");
+        assert!(body.ends_with(&["movq -16(%rbp), %rax".to_owned(), "addq $4, %rax".to_owned(), "movl (%rax), %eax".to_owned()]), "{:?}", body);
+        let body = main_body("struct s { char c; int i; };\nint main() { struct s x; return x.c; }");
+        assert_eq!(body[1..], ["leaq -8(%rbp), %rax", "movsbl (%rax), %eax"]);
+    }
+
+    #[test]
+    fn dense_switches_jump_through_a_table() {
+        let assembly = generate_source(
+            "int main(int x) { switch (x) { case 1: return 1; case 2: case 3: return 2; case 5: break; default: return 0; } return 5; }"
+        );
+        assert!(assembly.contains("    subl $1, %eax\n    cmpl $4, %eax\n    ja .Lmain_default_6\n    leaq .Lmain_table_7(%rip), %rcx\n    jmp *(%rcx,%rax,8)\n"), "{}", assembly);
+        assert!(assembly.contains(".section .data.rel.ro\n    .balign 8\n.Lmain_table_7:\n    .quad .Lmain_case_2\n    .quad .Lmain_case_3\n    .quad .Lmain_case_4\n    .quad .Lmain_default_6\n    .quad .Lmain_case_5\n"), "{}", assembly);
+    }
+
+    #[test]
+    fn sparse_switches_compare_each_case() {
+        let body = main_body("int main() { long x = 0; switch (x) { case 1: case 1000: case -5: case 1 << 20: x = 1; } return 0; }");
+        assert_eq!(body[5..14], [
+            "cmpq $1, %rax", "je .Lmain_case_2", "cmpq $1000, %rax", "je .Lmain_case_3", "cmpq $-5, %rax", "je .Lmain_case_4",
+            "cmpq $1048576, %rax", "je .Lmain_case_5", "jmp .Lmain_end_1",
+        ]);
+        let assembly = generate_source("int main(unsigned x) { switch (x) { case -1: return 1; } return 0; }");
+        assert!(assembly.contains("    cmpl $-1, %eax\n    je .Lmain_case_2\n    jmp .Lmain_end_1\n"), "{}", assembly);
+        assert!(!assembly.contains(".data.rel.ro"));
+    }
+
+    #[test]
+    fn checked_division_tests_the_divisor_first() {
+        let program = parse_program(&lex_str("int main(int x) { return 7 % x; }").unwrap()).unwrap();
+        let assembly = generate_with(&program, &Options {
+            target: TargetSpec::new(Architecture::X86_64, TargetOs::Linux),
+            checked_arithmetic: true,
+            debug_info: DebugInfo::Off,
+            ..Options::default()
+        });
+        assert!(assembly.contains("\
+    cmpl $0, %ecx
+    je .Ldivision_by_zero
+    cmpl $-1, %ecx
+    jne .Lmain_divide_1
+    negl %eax
+    xorl %edx, %edx
+    jmp .Lmain_end_2
+.Lmain_divide_1:
+    cltd
+    idivl %ecx
+.Lmain_end_2:
+    movl %edx, %eax
+"), "{}", assembly);
+        assert!(assembly.contains(".Ldivision_by_zero:\n    andq $-16, %rsp\n"), "{}", assembly);
+        assert!(assembly.contains(".asciz \"division by zero\\012\""), "{}", assembly);
+    }
+
+    #[test]
+    fn constant_shifts_use_an_immediate() {
+        assert_eq!(main_body("int main(int x) { return x << 3; }")[2..], ["movl -4(%rbp), %eax", "shll $3, %eax"]);
+        assert_eq!(main_body("int main(unsigned x) { return x >> (1 + 1); }")[2..], ["movl -4(%rbp), %eax", "shrl $2, %eax"]);
+        // Only the low bits count, as they would in `%cl`.
+        assert_eq!(main_body("int main(long x) { return x >> 65; }")[2..], ["movq -8(%rbp), %rax", "sarq $1, %rax"]);
+    }
+
+    #[test]
+    fn macos_names_symbols_labels_and_sections_differently() {
+        let source = "static char *greeting = \"hi\";\nint count;\n\
+                      int main() { while (count < 2) count++; switch (count) { case 0: case 1: case 2: case 3: break; } return puts(greeting); }";
+        let (linux, macos) = (generate_for(source, TargetOs::Linux), generate_for(source, TargetOs::MacOs));
+        for &(in_linux, in_macos) in &[
+            (".globl main\nmain:\n", ".globl _main\n_main:\n"),
+            ("call puts\n", "call _puts\n"),
+            ("count(%rip)", "_count(%rip)"),
+            (".globl count\ncount:\n", ".globl _count\n_count:\n"),
+            ("greeting:\n    .quad .Lstr0\n", "_greeting:\n    .quad Lstr0\n"),
+            ("jmp .Lmain_loop_1\n", "jmp Lmain_loop_1\n"),
+            (".section .rodata\n.Lstr0:\n", ".cstring\nLstr0:\n"),
+            (".section .data.rel.ro\n", ".const_data\n"),
+        ] {
+            assert!(linux.contains(in_linux), "{}", linux);
+            assert!(macos.contains(in_macos), "{}", macos);
+        }
+        assert!(!macos.contains(".L") && !macos.contains("GNU-stack") && !macos.contains(".globl greeting"), "{}", macos);
+        assert!(linux.ends_with(".section .note.GNU-stack,\"\",@progbits\n"));
+    }
+
+    #[test]
+    fn the_same_program_for_both_targets() {
+        let source = "int total;\nint main() { if (total) return puts(\"none\"); return total; }";
+        assert_eq!(generate_for(source, TargetOs::Linux), "    .text
+    .globl main
+main:
+    pushq %rbp
+    movq %rsp, %rbp
+    movl total(%rip), %eax
+    cmpl $0, %eax
+    je .Lmain_end_1
+    leaq .Lstr0(%rip), %rax
+    movq %rax, %r10
+    movq %r10, %rdi
+    movl $0, %eax
+    call puts
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+.Lmain_end_1:
+    movl total(%rip), %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .bss
+    .balign 4
+    .globl total
+total:
+    .zero 4
+    .section .rodata
+.Lstr0:
+    .asciz \"none\"
+    .section .note.GNU-stack,\"\",@progbits
+");
+        assert_eq!(generate_for(source, TargetOs::MacOs), "    .text
+    .globl _main
+_main:
+    pushq %rbp
+    movq %rsp, %rbp
+    movl _total(%rip), %eax
+    cmpl $0, %eax
+    je Lmain_end_1
+    leaq Lstr0(%rip), %rax
+    movq %rax, %r10
+    movq %r10, %rdi
+    movl $0, %eax
+    call _puts
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+Lmain_end_1:
+    movl _total(%rip), %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .bss
+    .balign 4
+    .globl _total
+_total:
+    .zero 4
+    .cstring
+Lstr0:
+    .asciz \"none\"
+");
+    }
+
+    #[test]
+    fn the_same_program_in_both_syntaxes() {
+        let source = "long total;\nint main(int n) { char c = n; total = c / n; return total < 2; }";
+        assert_eq!(generate_in(source, Syntax::Att), "    .text
+    .globl main
+main:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $16, %rsp
+    movl %edi, -4(%rbp)
+    movl -4(%rbp), %eax
+    movsbl %al, %eax
+    movb %al, -5(%rbp)
+    movsbl -5(%rbp), %eax
+    movq %rax, %r10
+    movl -4(%rbp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cltd
+    idivl %ecx
+    movslq %eax, %rax
+    movq %rax, total(%rip)
+    movq total(%rip), %rax
+    movq %rax, %r10
+    movl $2, %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %r10, %rax
+    cmpq %rcx, %rax
+    setl %al
+    movzbl %al, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .bss
+    .balign 8
+    .globl total
+total:
+    .zero 8
+    .section .note.GNU-stack,\"\",@progbits
+");
+        assert_eq!(generate_in(source, Syntax::Intel), "    .intel_syntax noprefix
+    .text
+    .globl main
+main:
+    push rbp
+    mov rbp, rsp
+    sub rsp, 16
+    mov DWORD PTR [rbp-4], edi
+    mov eax, DWORD PTR [rbp-4]
+    movsx eax, al
+    mov BYTE PTR [rbp-5], al
+    movsx eax, BYTE PTR [rbp-5]
+    mov r10, rax
+    mov eax, DWORD PTR [rbp-4]
+    mov ecx, eax
+    mov rax, r10
+    cdq
+    idiv ecx
+    movsxd rax, eax
+    mov QWORD PTR total[rip], rax
+    mov rax, QWORD PTR total[rip]
+    mov r10, rax
+    mov eax, 2
+    movsxd rax, eax
+    mov rcx, rax
+    mov rax, r10
+    cmp rax, rcx
+    setl al
+    movzx eax, al
+    mov rsp, rbp
+    pop rbp
+    ret
+    mov eax, 0
+    mov rsp, rbp
+    pop rbp
+    ret
+    .bss
+    .balign 8
+    .globl total
+total:
+    .zero 8
+    .section .note.GNU-stack,\"\",@progbits
+");
+    }
+}
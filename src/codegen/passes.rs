@@ -0,0 +1,298 @@
+//! The optimization passes, the `OptLevel` each starts at, and the order they
+//! run in.
+//!
+//! The passes over the program's tree run first, in the order `tree`
+//! registers them, whatever it is generated as. Those that decide how it is
+//! lowered to assembly run next, in the order `lowering` registers them. The
+//! passes over the lowered code of each function then run in the order `code`
+//! registers them, and those over its x86-64 instructions in the order
+//! `assembly` does.
+//!
+//! Any of them can be turned off by name with `Options::disabled_passes`, to
+//! find out which one a miscompilation comes from. The time each takes is
+//! added up over the whole program, in `Timings`, with the `timings` feature.
+
+use std::collections::HashMap;
+use std::time::Duration;
+#[cfg(feature = "timings")]
+use std::time::Instant;
+use ast::Program;
+use super::{dead_stores, fold, inline, jumps, propagate, strength, x86_64, OptLevel, Options};
+use super::inline::Inlinable;
+use super::ir::Instruction;
+
+/// A pass over `T`, the program's tree, what is decided about it, or a
+/// function's code.
+pub trait Pass<T: ?Sized> {
+    /// The name that `Options::disabled_passes` turns it off by.
+    fn name(&self) -> &'static str;
+    fn run(&self, unit: &mut T);
+}
+
+/// Passes, in the order they run, with the level each starts at.
+pub struct PassManager<T: ?Sized> {
+    passes: Vec<(OptLevel, Box<dyn Pass<T>>)>,
+}
+
+impl<T: ?Sized> PassManager<T> {
+    pub fn new() -> PassManager<T> {
+        PassManager { passes: Vec::new() }
+    }
+
+    /// Adds `pass`, to run after those added before it at `level` and above.
+    pub fn register<P: Pass<T> + 'static>(&mut self, level: OptLevel, pass: P) {
+        self.passes.push((level, Box::new(pass)));
+    }
+
+    /// The names of the passes, in the order they run.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|(_, pass)| pass.name()).collect()
+    }
+
+    /// Runs the passes that `options` enables over `unit`, and adds the time
+    /// each takes to `timings`.
+    pub fn run(&self, unit: &mut T, options: &Options, timings: &mut Timings) {
+        for &(level, ref pass) in &self.passes {
+            if enabled(options, pass.name(), level) {
+                timings.add(pass.name(), time(|| pass.run(unit)));
+            }
+        }
+    }
+}
+
+/// How long `run` takes.
+#[cfg(feature = "timings")]
+fn time<F: FnOnce()>(run: F) -> Duration {
+    let start = Instant::now();
+    run();
+    start.elapsed()
+}
+
+/// No time at all, without a clock to measure `run` with, which not every
+/// target has.
+#[cfg(not(feature = "timings"))]
+fn time<F: FnOnce()>(run: F) -> Duration {
+    run();
+    Duration::default()
+}
+
+/// Whether `options` runs the pass `name`, which starts at `level`.
+fn enabled(options: &Options, name: &str, level: OptLevel) -> bool {
+    options.opt_level >= level && !options.disabled_passes.iter().any(|disabled| disabled == name)
+}
+
+/// The time each pass took, in the order they first ran.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Timings {
+    passes: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    fn add(&mut self, name: &'static str, time: Duration) {
+        match self.passes.iter_mut().find(|&&mut (pass, _)| pass == name) {
+            Some(&mut (_, ref mut total)) => *total += time,
+            None => self.passes.push((name, time)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(&'static str, Duration)> {
+        self.passes.iter()
+    }
+}
+
+/// What is decided about how a program is lowered.
+pub struct Lowering<'a> {
+    pub program: &'a Program,
+    /// The functions whose calls are replaced with their bodies, by name.
+    pub inlined: HashMap<String, Inlinable>,
+}
+
+/// The code of a function, as lowered.
+pub struct Lowered {
+    pub code: Vec<Instruction>,
+    /// The labels that its jump tables jump to.
+    pub targets: Vec<String>,
+    /// The offsets of the slots of its locals and parameters.
+    pub slots: Vec<i64>,
+}
+
+struct Fold;
+
+impl Pass<Program> for Fold {
+    fn name(&self) -> &'static str {
+        "fold"
+    }
+
+    fn run(&self, program: &mut Program) {
+        fold::fold(program);
+    }
+}
+
+/// Propagates constants, folding what that makes constant, until nothing
+/// changes.
+struct Propagate;
+
+impl Pass<Program> for Propagate {
+    fn name(&self) -> &'static str {
+        "propagate"
+    }
+
+    fn run(&self, program: &mut Program) {
+        while propagate::propagate(program) {
+            fold::fold(program);
+        }
+    }
+}
+
+struct Inline;
+
+impl<'a> Pass<Lowering<'a>> for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&self, lowering: &mut Lowering<'a>) {
+        lowering.inlined = inline::inlinable(lowering.program);
+    }
+}
+
+struct StrengthReduce;
+
+impl Pass<Lowered> for StrengthReduce {
+    fn name(&self) -> &'static str {
+        "strength-reduce"
+    }
+
+    fn run(&self, function: &mut Lowered) {
+        strength::reduce(&mut function.code);
+    }
+}
+
+struct Jumps;
+
+impl Pass<Lowered> for Jumps {
+    fn name(&self) -> &'static str {
+        "jumps"
+    }
+
+    fn run(&self, function: &mut Lowered) {
+        jumps::clean_up(&mut function.code, function.targets.iter().map(|target| &target[..]));
+    }
+}
+
+struct DeadStores;
+
+impl Pass<Lowered> for DeadStores {
+    fn name(&self) -> &'static str {
+        "dead-stores"
+    }
+
+    fn run(&self, function: &mut Lowered) {
+        dead_stores::eliminate(&mut function.code, function.slots.iter().cloned());
+    }
+}
+
+struct Peephole;
+
+impl Pass<Vec<x86_64::Instruction>> for Peephole {
+    fn name(&self) -> &'static str {
+        "peephole"
+    }
+
+    fn run(&self, code: &mut Vec<x86_64::Instruction>) {
+        x86_64::peephole::optimize(code);
+    }
+}
+
+/// The passes over the program's tree.
+pub fn tree() -> PassManager<Program> {
+    let mut passes = PassManager::new();
+    passes.register(OptLevel::O1, Fold);
+    passes.register(OptLevel::O2, Propagate);
+    passes
+}
+
+/// The passes that decide how the program is lowered.
+pub fn lowering<'a>() -> PassManager<Lowering<'a>> {
+    let mut passes = PassManager::new();
+    passes.register(OptLevel::O2, Inline);
+    passes
+}
+
+/// The passes over the lowered code of each function.
+pub fn code() -> PassManager<Lowered> {
+    let mut passes = PassManager::new();
+    passes.register(OptLevel::O2, StrengthReduce);
+    passes.register(OptLevel::O1, Jumps);
+    passes.register(OptLevel::O1, DeadStores);
+    passes
+}
+
+/// The passes over the x86-64 instructions of each function.
+pub fn assembly() -> PassManager<Vec<x86_64::Instruction>> {
+    let mut passes = PassManager::new();
+    passes.register(OptLevel::O1, Peephole);
+    passes
+}
+
+/// The names of every pass, in the order they run.
+pub fn names() -> Vec<&'static str> {
+    let mut names = tree().names();
+    names.extend(lowering().names());
+    names.extend(code().names());
+    names.extend(assembly().names());
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Append(&'static str);
+
+    impl Pass<Vec<&'static str>> for Append {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn run(&self, unit: &mut Vec<&'static str>) {
+            unit.push(self.0);
+        }
+    }
+
+    fn run(options: &Options) -> (Vec<&'static str>, Vec<&'static str>) {
+        let mut passes = PassManager::new();
+        passes.register(OptLevel::O1, Append("b"));
+        passes.register(OptLevel::O2, Append("c"));
+        passes.register(OptLevel::O1, Append("a"));
+        let mut ran = Vec::new();
+        let mut timings = Timings::default();
+        passes.run(&mut ran, options, &mut timings);
+        passes.run(&mut ran, options, &mut timings);
+        (ran, timings.iter().map(|&(name, _)| name).collect())
+    }
+
+    #[test]
+    fn passes_run_in_order_from_their_level() {
+        assert_eq!(run(&Options::default()), (vec![], vec![]));
+        let options = Options { opt_level: OptLevel::O1, ..Options::default() };
+        assert_eq!(run(&options), (vec!["b", "a", "b", "a"], vec!["b", "a"]));
+        let options = Options { opt_level: OptLevel::O2, ..Options::default() };
+        assert_eq!(run(&options).0, ["b", "c", "a", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn passes_can_be_disabled() {
+        let options = Options { opt_level: OptLevel::O2, disabled_passes: vec!["c".to_owned()], ..Options::default() };
+        assert_eq!(run(&options).0, ["b", "a", "b", "a"]);
+    }
+
+    #[test]
+    fn every_pass_has_a_name_of_its_own() {
+        let mut names = names();
+        assert_eq!(names, ["fold", "propagate", "inline", "strength-reduce", "jumps", "dead-stores", "peephole"]);
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 7);
+    }
+}
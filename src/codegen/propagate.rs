@@ -0,0 +1,396 @@
+//! Constant propagation, done to the tree after folding at `OptLevel::O2`. A
+//! read of a local that is known to hold a constant is replaced with a literal
+//! of it, so that `int x = 5; return x + 2;` becomes `return 5 + 2;`, which
+//! `fold` then finishes, and the store to `x` is left for `dead_stores` to
+//! remove.
+//!
+//! Only the integer locals and parameters of a function whose address is
+//! never taken are considered, since nothing else can change those. One is
+//! known from a declaration or an assignment that gives it a literal, until
+//! something assigns it again. Where control joins, after an `if`, only what
+//! both ways agree on is still known. Nothing assigned anywhere in a loop is
+//! known in it or after it, nor in a `switch`, whose `case` labels can be
+//! jumped to with any of it. A function with a label in it is left alone,
+//! since a `goto` can get to it from anywhere.
+
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use ast::*;
+use ast::visit::{self, walk_expr_mut, Visitor, VisitorMut};
+use const_eval::convert;
+use flow;
+use resolve::{Resolutions, Symbol};
+use semantic;
+use super::fold;
+
+/// The values of the locals known to hold constants, converted to their types.
+type Known = HashMap<Symbol, i64>;
+
+/// Replaces the reads of the locals of `program` known to hold constants, and
+/// says whether there were any. `program` must have passed semantic analysis
+/// without errors.
+pub fn propagate(program: &mut Program) -> bool {
+    let (analysis, _) = semantic::analyze(program);
+    let mut changed = false;
+    for (index, item) in program.items.iter().enumerate() {
+        let function = match *item {
+            TopLevel::Function(ref function) => function,
+            _ => continue,
+        };
+        let body = match function.body {
+            Some(ref body) => body,
+            None => continue,
+        };
+        let labeled = body.iter().any(|item| match *item {
+            BlockItem::Statement(statement) => flow::labeled(&program.ast, statement, false),
+            BlockItem::Declaration(_) => false,
+        });
+        if labeled {
+            continue;
+        }
+        let mut locals = Locals { resolutions: &analysis.resolutions, types: HashMap::new(), addressed: HashSet::new() };
+        for (parameter, declared) in function.parameters.iter().enumerate() {
+            locals.types.insert(Symbol::Parameter { function: index, index: parameter }, declared.ty.clone());
+        }
+        locals.visit_block(&program.ast, body);
+        let Locals { types, addressed, .. } = locals;
+        let mut propagator = Propagator {
+            ast: &mut program.ast,
+            resolutions: &analysis.resolutions,
+            types: types.into_iter()
+                .filter(|(symbol, ty)| ty.is_integer() && !addressed.contains(symbol))
+                .collect(),
+            known: Known::new(),
+            switches: Vec::new(),
+            changed: false,
+        };
+        propagator.block(body);
+        changed |= propagator.changed;
+    }
+    changed
+}
+
+/// Collects the types of a function's locals, and the symbols whose address
+/// it takes.
+struct Locals<'a> {
+    resolutions: &'a Resolutions,
+    types: HashMap<Symbol, Type>,
+    addressed: HashSet<Symbol>,
+}
+
+impl<'a> Visitor for Locals<'a> {
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        if ast[declaration].storage.is_none() {
+            self.types.insert(Symbol::Variable(declaration), ast[declaration].ty.clone());
+        }
+        visit::walk_declaration(self, ast, declaration);
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        if let ExpressionKind::Unary(UnaryOp::AddressOf, operand) = ast[expression].kind {
+            self.addressed.extend(self.resolutions.get(&operand).cloned());
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// Collects the symbols that a statement or expression assigns.
+struct Assigned<'a> {
+    resolutions: &'a Resolutions,
+    symbols: HashSet<Symbol>,
+}
+
+impl<'a> Visitor for Assigned<'a> {
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        self.symbols.insert(Symbol::Variable(declaration));
+        visit::walk_declaration(self, ast, declaration);
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        match ast[expression].kind {
+            ExpressionKind::Assign(_, target, _)
+            | ExpressionKind::Unary(UnaryOp::PreIncrement, target)
+            | ExpressionKind::Unary(UnaryOp::PreDecrement, target)
+            | ExpressionKind::Unary(UnaryOp::PostIncrement, target)
+            | ExpressionKind::Unary(UnaryOp::PostDecrement, target) => {
+                self.symbols.extend(self.resolutions.get(&target).cloned());
+            },
+            _ => {},
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// Replaces the reads of known locals in an expression, except of those it
+/// assigns itself.
+struct Substitution<'a> {
+    resolutions: &'a Resolutions,
+    types: &'a HashMap<Symbol, Type>,
+    known: &'a Known,
+    assigned: HashSet<Symbol>,
+    changed: bool,
+}
+
+impl<'a> VisitorMut for Substitution<'a> {
+    fn visit_expr(&mut self, ast: &mut Ast, expression: ExprId) {
+        if let ExpressionKind::Variable(_) = ast[expression].kind {
+            let symbol = match self.resolutions.get(&expression) {
+                Some(symbol) if !self.assigned.contains(symbol) => symbol,
+                _ => return,
+            };
+            if let Some(&value) = self.known.get(symbol) {
+                self.changed |= fold::replace(ast, expression, &self.types[symbol], value);
+            }
+            return;
+        }
+        walk_expr_mut(self, ast, expression);
+    }
+}
+
+struct Propagator<'a> {
+    ast: &'a mut Ast,
+    resolutions: &'a Resolutions,
+    /// The locals considered, with their types.
+    types: HashMap<Symbol, Type>,
+    known: Known,
+    /// What is known at the start of the body of each `switch` around the
+    /// statement, and so at each of its `case` labels.
+    switches: Vec<Known>,
+    changed: bool,
+}
+
+impl<'a> Propagator<'a> {
+    fn assigned(&self, visit: impl FnOnce(&mut Assigned)) -> HashSet<Symbol> {
+        let mut assigned = Assigned { resolutions: self.resolutions, symbols: HashSet::new() };
+        visit(&mut assigned);
+        assigned.symbols
+    }
+
+    /// Forgets what `statement` may assign.
+    fn forget(&mut self, statement: StmtId) {
+        let assigned = self.assigned(|assigned| assigned.visit_stmt(self.ast, statement));
+        self.known.retain(|symbol, _| !assigned.contains(symbol));
+    }
+
+    /// Replaces the known reads in `expression`, then forgets what it assigns,
+    /// and learns what it assigns a literal.
+    fn expression(&mut self, expression: ExprId) {
+        let assigned = self.assigned(|assigned| assigned.visit_expr(self.ast, expression));
+        let mut substitution = Substitution {
+            resolutions: self.resolutions,
+            types: &self.types,
+            known: &self.known,
+            assigned,
+            changed: false,
+        };
+        substitution.visit_expr(self.ast, expression);
+        self.changed |= substitution.changed;
+        for symbol in &substitution.assigned {
+            self.known.remove(symbol);
+        }
+        self.learn(expression);
+    }
+
+    /// Learns the value of the local that `expression` assigns a literal, if
+    /// it does.
+    fn learn(&mut self, expression: ExprId) {
+        match self.ast[expression].kind {
+            ExpressionKind::Assign(None, target, value) => {
+                if let Some(&symbol) = self.resolutions.get(&target) {
+                    self.set(symbol, value);
+                }
+            },
+            ExpressionKind::Comma(ref expressions) => {
+                for expression in expressions.clone() {
+                    self.learn(expression);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Records that `symbol` holds the value of `value`, if it is considered
+    /// and that is a literal.
+    fn set(&mut self, symbol: Symbol, value: ExprId) {
+        let value = match self.types.get(&symbol) {
+            Some(ty) => fold::literal(self.ast, value).and_then(|value| convert(value, ty.unqualified())),
+            None => return,
+        };
+        match value {
+            Some(value) => self.known.insert(symbol, value),
+            None => self.known.remove(&symbol),
+        };
+    }
+
+    fn declaration(&mut self, declaration: DeclId) {
+        if self.ast[declaration].storage.is_some() {
+            return;
+        }
+        let symbol = Symbol::Variable(declaration);
+        self.known.remove(&symbol);
+        match self.ast[declaration].initializer.clone() {
+            Some(Initializer::Expr(value)) => {
+                self.expression(value);
+                self.set(symbol, value);
+            },
+            Some(Initializer::List(items)) => {
+                let mut pending = items;
+                while let Some(item) = pending.pop() {
+                    match item {
+                        Initializer::Expr(value) => self.expression(value),
+                        Initializer::List(items) => pending.extend(items),
+                    }
+                }
+            },
+            None => {},
+        }
+    }
+
+    fn block(&mut self, items: &[BlockItem]) {
+        for item in items {
+            match *item {
+                BlockItem::Statement(statement) => self.statement(statement),
+                BlockItem::Declaration(ref declarations) => {
+                    for &declaration in declarations {
+                        self.declaration(declaration);
+                    }
+                },
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: StmtId) {
+        match self.ast[statement].kind.clone() {
+            StatementKind::Return(value) => self.expression(value),
+            StatementKind::Expression(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            },
+            StatementKind::If(condition, then, otherwise) => {
+                self.expression(condition);
+                let before = self.known.clone();
+                self.statement(then);
+                let after_then = mem::replace(&mut self.known, before);
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise);
+                }
+                self.known.retain(|symbol, value| after_then.get(symbol) == Some(value));
+            },
+            StatementKind::Compound(items) => self.block(&items),
+            StatementKind::While(condition, body) | StatementKind::DoWhile(body, condition) => {
+                self.forget(statement);
+                let before = self.known.clone();
+                self.expression(condition);
+                self.statement(body);
+                self.known = before;
+            },
+            StatementKind::For { init, condition, post, body } => {
+                match init {
+                    ForInit::Declaration(declarations) => {
+                        for declaration in declarations {
+                            self.declaration(declaration);
+                        }
+                    },
+                    ForInit::Expression(Some(init)) => self.expression(init),
+                    ForInit::Expression(None) => {},
+                }
+                // The initialization isn't repeated, so what it assigns is
+                // only forgotten if the rest of the loop assigns it again.
+                let assigned = self.assigned(|assigned| {
+                    for expression in condition.into_iter().chain(post) {
+                        assigned.visit_expr(self.ast, expression);
+                    }
+                    assigned.visit_stmt(self.ast, body);
+                });
+                self.known.retain(|symbol, _| !assigned.contains(symbol));
+                let before = self.known.clone();
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                self.statement(body);
+                if let Some(post) = post {
+                    self.expression(post);
+                }
+                self.known = before;
+            },
+            StatementKind::Switch(value, body) => {
+                self.expression(value);
+                self.forget(body);
+                self.switches.push(self.known.clone());
+                self.statement(body);
+                self.known = self.switches.pop().unwrap();
+            },
+            StatementKind::Case(_, body) | StatementKind::Default(body) => {
+                self.known = self.switches.last().cloned().unwrap_or_default();
+                self.statement(body);
+            },
+            StatementKind::Labeled(_, body) => self.statement(body),
+            StatementKind::Break | StatementKind::Continue | StatementKind::Goto(_) => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    /// Propagates and folds `source` until nothing changes, and returns what
+    /// its last function returns at the end.
+    fn returned(source: &str) -> ExpressionKind {
+        let mut program = parse_program(&lex_str(source).unwrap()).unwrap();
+        fold::fold(&mut program);
+        while propagate(&mut program) {
+            fold::fold(&mut program);
+        }
+        let statement = match program.items.last() {
+            Some(TopLevel::Function(Function { body: Some(ref body), .. })) => match body.last() {
+                Some(&BlockItem::Statement(statement)) => statement,
+                _ => panic!("no statement"),
+            },
+            _ => panic!("no function"),
+        };
+        match program.ast[statement].kind {
+            StatementKind::Return(value) => program.ast[value].kind.clone(),
+            ref kind => panic!("{:?} isn't a return", kind),
+        }
+    }
+
+    fn variable(name: &str) -> ExpressionKind {
+        ExpressionKind::Variable(name.to_owned())
+    }
+
+    #[test]
+    fn constants_are_propagated() {
+        assert_eq!(returned("int main() { int x = 5; return x + 2; }"), ExpressionKind::IntLiteral(7, Type::Int));
+        assert_eq!(returned("int main() { int x = 5; int y = x * 2; x = y - 1; return x; }"), ExpressionKind::IntLiteral(9, Type::Int));
+        assert_eq!(returned("int main() { char c = 300; return c + 0; }"), ExpressionKind::IntLiteral(44, Type::Int));
+        assert_eq!(returned("int f(int a) { a = 3; return a; }"), ExpressionKind::IntLiteral(3, Type::Int));
+    }
+
+    #[test]
+    fn assignments_forget_constants() {
+        assert_eq!(returned("int f(); int main() { int x = 5; x = f(); return x; }"), variable("x"));
+        assert_eq!(returned("int main() { int x = 5; x++; return x; }"), variable("x"));
+        assert_eq!(returned("int g(int *p); int main() { int x = 5; g(&x); return x; }"), variable("x"));
+    }
+
+    #[test]
+    fn joins_keep_what_agrees() {
+        assert_eq!(returned("int f(int c) { int x = 1; if (c) x = 2; return x; }"), variable("x"));
+        assert_eq!(returned("int f(int c) { int x = 1; if (c) x = 2; else x = 2; return x; }"), ExpressionKind::IntLiteral(2, Type::Int));
+        assert_eq!(returned("int f(int c) { int x = 1; while (c) { c = x; x = 2; } return x; }"), variable("x"));
+        assert_eq!(returned("int f(int c) { int x = 1; while (c) c--; return x; }"), ExpressionKind::IntLiteral(1, Type::Int));
+        assert_eq!(
+            returned("int f(int c) { int x = 1; switch (c) { case 1: x = 2; break; default: break; } return x; }"),
+            variable("x"),
+        );
+    }
+
+    #[test]
+    fn labels_stop_propagation() {
+        assert_eq!(returned("int f(int c) { int x = 1; if (c) goto end; x = 2; end: ; return x; }"), variable("x"));
+    }
+}
@@ -0,0 +1,248 @@
+//! Gives every temporary of a function a home: one of the machine's spare
+//! registers if one is free for as long as the temporary lives, and a slot in
+//! the frame otherwise. This is linear scan: a temporary lives from the
+//! instruction that saves it to the last one that uses it, and the temporaries
+//! are visited in the order they are saved, each taking a register that no
+//! temporary still alive holds.
+//!
+//! A call may change some of the registers, which its caller must save if it
+//! still needs them, and must preserve the rest. A temporary that lives across
+//! a call is only ever given one of the preserved ones, which the function then
+//! saves in its prologue and restores on return; any other temporary takes one
+//! the callee may change first, since those cost nothing to use. When none is
+//! free, whichever of the temporaries that could use the same registers lives
+//! longest is spilled to a slot, which leaves the most registers free for the
+//! temporaries after it.
+
+use super::round_up;
+use super::ir::{Instruction, Temp};
+
+/// The registers a machine has free to hold temporaries: first the ones a
+/// call may change, then the ones it preserves.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Registers {
+    pub caller_saved: usize,
+    pub callee_saved: usize,
+}
+
+/// Where a temporary is kept.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Home {
+    /// In a register, by its index among the machine's `Registers`.
+    Register(usize),
+    /// In 8 bytes at an offset from the frame pointer.
+    Slot(i64),
+}
+
+/// The homes of a function's temporaries, and what its frame needs for them.
+#[derive(Debug, PartialEq)]
+pub struct Allocation {
+    /// The home of each temporary, by number.
+    pub homes: Vec<Home>,
+    /// Each preserved register that was used, and the offset from the frame
+    /// pointer that it is saved at.
+    pub saved: Vec<(usize, i64)>,
+    /// The size of the whole frame, a multiple of 16.
+    pub frame_size: i64,
+}
+
+/// Where a temporary lives: the positions in the code of the instruction that
+/// saves it and of its last use.
+struct Range {
+    start: usize,
+    end: usize,
+    across_call: bool,
+}
+
+/// Finds homes for the temporaries in `code`, in a frame whose locals take
+/// `locals` bytes. With `spill`, every temporary gets a slot.
+pub fn allocate(code: &[Instruction], registers: Registers, locals: i64, spill: bool) -> Allocation {
+    let mut ranges: Vec<Range> = Vec::new();
+    let mut calls = Vec::new();
+    for (position, instruction) in code.iter().enumerate() {
+        match *instruction {
+            Instruction::Save(Temp(temp)) => {
+                debug_assert_eq!(temp, ranges.len(), "temporaries are saved in order");
+                ranges.push(Range { start: position, end: position, across_call: false });
+            },
+            Instruction::Call(..) => calls.push(position),
+            _ => {},
+        }
+        for Temp(temp) in instruction.uses() {
+            ranges[temp].end = position;
+        }
+    }
+    for range in &mut ranges {
+        range.across_call = calls.iter().any(|&call| range.start < call && call < range.end);
+    }
+
+    let mut frame = Frame { size: round_up(locals, 8), free: Vec::new(), active: Vec::new() };
+    let mut homes = vec![Home::Slot(0); ranges.len()];
+    let total = registers.caller_saved + registers.callee_saved;
+    let mut free = vec![true; total];
+    let mut used = vec![false; total];
+    // The temporaries in registers, with their last uses.
+    let mut active: Vec<(usize, usize)> = Vec::new();
+    for (temp, range) in ranges.iter().enumerate() {
+        active.retain(|&(other, end)| {
+            if end < range.start {
+                if let Home::Register(register) = homes[other] {
+                    free[register] = true;
+                }
+            }
+            end >= range.start
+        });
+        frame.expire(range.start);
+        if spill {
+            homes[temp] = frame.slot(range.end, true);
+            continue;
+        }
+        let first = if range.across_call { registers.caller_saved } else { 0 };
+        if let Some(register) = (first..total).find(|&register| free[register]) {
+            free[register] = false;
+            used[register] = true;
+            homes[temp] = Home::Register(register);
+            active.push((temp, range.end));
+            continue;
+        }
+        let victim = active
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(other, _))| match homes[other] {
+                Home::Register(register) => register >= first,
+                Home::Slot(_) => false,
+            })
+            .max_by_key(|&(_, &(_, end))| end)
+            .map(|(index, &(other, end))| (index, other, end));
+        match victim {
+            Some((index, other, end)) if end > range.end => {
+                // The other temporary was already live, so the slots freed
+                // since it was saved may have been in use then.
+                homes[temp] = homes[other];
+                homes[other] = frame.slot(end, false);
+                active[index] = (temp, range.end);
+            },
+            _ => homes[temp] = frame.slot(range.end, true),
+        }
+    }
+
+    let mut saved = Vec::new();
+    for (register, _) in used.iter().enumerate().skip(registers.caller_saved).filter(|&(_, &used)| used) {
+        frame.size += 8;
+        saved.push((register, -frame.size));
+    }
+    Allocation { homes, saved, frame_size: round_up(frame.size, 16) }
+}
+
+/// The slots of the frame given to temporaries.
+struct Frame {
+    /// The bytes of the frame used so far.
+    size: i64,
+    /// The slots free to be used again.
+    free: Vec<i64>,
+    /// The slots in use, with the last use of the temporary in each.
+    active: Vec<(i64, usize)>,
+}
+
+impl Frame {
+    /// Frees the slots of the temporaries that are dead by `position`.
+    fn expire(&mut self, position: usize) {
+        let free = &mut self.free;
+        self.active.retain(|&(slot, end)| {
+            if end < position {
+                free.push(slot);
+            }
+            end >= position
+        });
+    }
+
+    /// A slot for a temporary whose last use is at `end`: one freed before, if
+    /// it may `reuse` one, or a new one.
+    fn slot(&mut self, end: usize, reuse: bool) -> Home {
+        let slot = if reuse && !self.free.is_empty() {
+            self.free.pop().unwrap()
+        } else {
+            self.size += 8;
+            -self.size
+        };
+        self.active.push((slot, end));
+        Home::Slot(slot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use codegen::Parameters;
+
+    const REGISTERS: Registers = Registers { caller_saved: 2, callee_saved: 1 };
+
+    fn call(arguments: &[usize]) -> Instruction {
+        let arguments = arguments.iter().map(|&temp| (Temp(temp), 4)).collect();
+        Instruction::Call("f".to_owned(), arguments, Parameters::Fixed)
+    }
+
+    #[test]
+    fn registers_are_reused_once_free() {
+        let code = [
+            Instruction::Save(Temp(0)),
+            Instruction::Restore(Temp(0)),
+            Instruction::Save(Temp(1)),
+            Instruction::Save(Temp(2)),
+            Instruction::Restore(Temp(2)),
+            Instruction::Restore(Temp(1)),
+        ];
+        let allocation = allocate(&code, REGISTERS, 4, false);
+        assert_eq!(allocation.homes, vec![Home::Register(0), Home::Register(0), Home::Register(1)]);
+        assert_eq!(allocation.saved, vec![]);
+        assert_eq!(allocation.frame_size, 16);
+    }
+
+    #[test]
+    fn temporaries_across_calls_take_preserved_registers() {
+        let code = [
+            Instruction::Save(Temp(0)),
+            Instruction::Save(Temp(1)),
+            call(&[1]),
+            Instruction::Save(Temp(2)),
+            call(&[2, 0]),
+        ];
+        let allocation = allocate(&code, REGISTERS, 4, false);
+        assert_eq!(allocation.homes, vec![Home::Register(2), Home::Register(0), Home::Register(0)]);
+        assert_eq!(allocation.saved, vec![(2, -16)]);
+        assert_eq!(allocation.frame_size, 16);
+    }
+
+    #[test]
+    fn the_longest_lived_temporary_is_spilled() {
+        let code = [
+            Instruction::Save(Temp(0)),
+            Instruction::Save(Temp(1)),
+            Instruction::Save(Temp(2)),
+            Instruction::Save(Temp(3)),
+            Instruction::Restore(Temp(3)),
+            Instruction::Restore(Temp(2)),
+            Instruction::Restore(Temp(1)),
+            Instruction::Restore(Temp(0)),
+        ];
+        let allocation = allocate(&code, REGISTERS, 0, false);
+        assert_eq!(allocation.homes, vec![Home::Slot(-8), Home::Register(1), Home::Register(2), Home::Register(0)]);
+        assert_eq!(allocation.saved, vec![(2, -16)]);
+        assert_eq!(allocation.frame_size, 16);
+    }
+
+    #[test]
+    fn spilling_everything_reuses_slots() {
+        let code = [
+            Instruction::Save(Temp(0)),
+            Instruction::Save(Temp(1)),
+            Instruction::Restore(Temp(1)),
+            Instruction::Save(Temp(2)),
+            Instruction::Restore(Temp(2)),
+            Instruction::Restore(Temp(0)),
+        ];
+        let allocation = allocate(&code, REGISTERS, 5, true);
+        assert_eq!(allocation.homes, vec![Home::Slot(-16), Home::Slot(-24), Home::Slot(-24)]);
+        assert_eq!(allocation.frame_size, 32);
+    }
+}
@@ -0,0 +1,164 @@
+//! Strength reduction, done to the lowered code of each function at
+//! `OptLevel::O2`. Multiplying, dividing, and taking the remainder of integers
+//! by a constant power of two are done with shifts instead, as
+//! `DivideByPower` is, and by 1 with nothing at all. Only a multiplication may
+//! have the power on the left.
+//!
+//! The generator lowers `x * 8` to the code for `x`, a `Save` of it, the
+//! `Constant` 8, extended if it is converted to a wider type, a
+//! `MoveToScratch`, the `Restore` of `x`, and the multiplication. All but the
+//! code for `x` is replaced. `8 * x` is lowered the same way, with the
+//! constant saved first, and the code for `x` between.
+
+use ast::BinaryOp;
+use super::Source;
+use super::ir::{self, Instruction};
+
+/// Reduces each operation of `code` by a constant power of two.
+pub fn reduce(code: &mut Vec<Instruction>) {
+    let mut index = 0;
+    let mut reduced = false;
+    while index < code.len() {
+        index = match reduce_at(code, index) {
+            Some(next) => {
+                reduced = true;
+                next
+            },
+            None => index + 1,
+        };
+    }
+    if reduced {
+        ir::renumber(code);
+    }
+}
+
+/// Reduces the operation at `index`, if it can be, and says where to go on
+/// looking from.
+fn reduce_at(code: &mut Vec<Instruction>, index: usize) -> Option<usize> {
+    let (operator, size, signed) = match code[index] {
+        Instruction::Binary(BinaryOp::Multiply, size, signed, Source::Scratch) => (BinaryOp::Multiply, size, signed),
+        Instruction::Divide(size, signed, remainder, _) => (if remainder { BinaryOp::Modulo } else { BinaryOp::Divide }, size, signed),
+        _ => return None,
+    };
+    if index < 3 {
+        return None;
+    }
+    let temp = match (&code[index - 2], &code[index - 1]) {
+        (&Instruction::MoveToScratch(moved), &Instruction::Restore(temp)) if moved == size => temp,
+        _ => return None,
+    };
+
+    // The power on the right, just before the operand is moved.
+    if let Some((start, value)) = constant_ending(code, index - 3) {
+        if start > 0 && code[start - 1] == Instruction::Save(temp) {
+            let reduced = reduced(operator, size, signed, value)?;
+            let next = start - 1 + reduced.len();
+            code.splice(start - 1..=index, reduced);
+            return Some(next);
+        }
+    }
+
+    // The power on the left, just before the other operand is saved.
+    if operator != BinaryOp::Multiply {
+        return None;
+    }
+    let save = code[..index].iter().rposition(|instruction| *instruction == Instruction::Save(temp))?;
+    let (start, value) = constant_ending(code, save.checked_sub(1)?)?;
+    let reduced = reduced(operator, size, signed, value)?;
+    let next = index - 2 - (save + 1 - start) + reduced.len();
+    code.splice(index - 2..=index, reduced);
+    code.drain(start..=save);
+    Some(next)
+}
+
+/// The constant that the instructions ending at `end` load into the
+/// accumulator, if they do, and where they start.
+fn constant_ending(code: &[Instruction], end: usize) -> Option<(usize, i64)> {
+    match code[end] {
+        Instruction::Constant(value, _) => Some((end, value)),
+        Instruction::Extend(4, 8, signed) => match code[end.checked_sub(1)?] {
+            Instruction::Constant(value, 4) if signed => Some((end - 1, i64::from(value as i32))),
+            Instruction::Constant(value, 4) => Some((end - 1, i64::from(value as u32))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// What `operator` by `value` is done with, on operands of `size`, if `value`
+/// is a power of two. The top bit of a `long` is its most negative value, and
+/// not a power.
+fn reduced(operator: BinaryOp, size: i64, signed: bool, value: i64) -> Option<Vec<Instruction>> {
+    if value <= 0 || !(value as u64).is_power_of_two() {
+        return None;
+    }
+    let shift = i64::from(value.trailing_zeros());
+    if shift >= size * 8 {
+        return None;
+    }
+    Some(match operator {
+        // Anything divided by 1 leaves no remainder.
+        BinaryOp::Modulo if shift == 0 => vec![Instruction::Constant(0, size)],
+        _ if shift == 0 => vec![],
+        BinaryOp::Multiply => vec![Instruction::Binary(BinaryOp::ShiftLeft, size, signed, Source::Immediate(shift))],
+        _ => vec![Instruction::DivideByPower(size, signed, operator == BinaryOp::Modulo, shift)],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::ir::Temp;
+
+    /// `lhs operation rhs`, as the generator lowers it, with each operand one
+    /// instruction.
+    fn lowered(lhs: Instruction, rhs: Instruction, operation: Instruction) -> Vec<Instruction> {
+        vec![lhs, Instruction::Save(Temp(0)), rhs, Instruction::MoveToScratch(4), Instruction::Restore(Temp(0)), operation]
+    }
+
+    fn load() -> Instruction {
+        Instruction::Load(super::super::Location::Accumulator, 4, true)
+    }
+
+    #[test]
+    fn powers_of_two_become_shifts() {
+        let multiply = Instruction::Binary(BinaryOp::Multiply, 4, true, Source::Scratch);
+        let mut code = lowered(load(), Instruction::Constant(8, 4), multiply.clone());
+        reduce(&mut code);
+        assert_eq!(code, [load(), Instruction::Binary(BinaryOp::ShiftLeft, 4, true, Source::Immediate(3))]);
+
+        let mut code = lowered(Instruction::Constant(8, 4), load(), multiply);
+        reduce(&mut code);
+        assert_eq!(code, [load(), Instruction::Binary(BinaryOp::ShiftLeft, 4, true, Source::Immediate(3))]);
+
+        let mut code = lowered(load(), Instruction::Constant(16, 4), Instruction::Divide(4, false, true, None));
+        reduce(&mut code);
+        assert_eq!(code, [load(), Instruction::DivideByPower(4, false, true, 4)]);
+
+        let mut code = lowered(load(), Instruction::Constant(1, 4), Instruction::Divide(4, true, true, None));
+        reduce(&mut code);
+        assert_eq!(code, [load(), Instruction::Constant(0, 4)]);
+    }
+
+    #[test]
+    fn the_temporaries_left_are_renumbered() {
+        let mut code = lowered(load(), Instruction::Constant(2, 4), Instruction::Binary(BinaryOp::Multiply, 4, true, Source::Scratch));
+        code.extend(vec![Instruction::Save(Temp(1)), load(), Instruction::MoveToScratch(4), Instruction::Restore(Temp(1))]);
+        reduce(&mut code);
+        assert_eq!(code[2..], [Instruction::Save(Temp(0)), load(), Instruction::MoveToScratch(4), Instruction::Restore(Temp(0))]);
+    }
+
+    #[test]
+    fn only_positive_powers_on_the_right_of_a_division_are_reduced() {
+        for code in &[
+            lowered(load(), Instruction::Constant(6, 4), Instruction::Divide(4, true, false, None)),
+            lowered(load(), Instruction::Constant(-8, 4), Instruction::Divide(4, true, false, None)),
+            lowered(Instruction::Constant(8, 4), load(), Instruction::Divide(4, true, false, None)),
+            lowered(load(), Instruction::Constant(8, 4), Instruction::Binary(BinaryOp::Subtract, 4, true, Source::Scratch)),
+        ] {
+            let mut reduced = code.clone();
+            reduce(&mut reduced);
+            assert_eq!(&reduced, code);
+        }
+    }
+}
@@ -0,0 +1,1663 @@
+//! Generates WebAssembly rather than assembly, as the text of a `.wat` module
+//! that `wasmtime` runs, or `wat2wasm` assembles, like any other. It is a third
+//! backend beside the assembly and LLVM IR ones, chosen with `Options::format`,
+//! and works from the same analysis, struct layouts, and static objects.
+//!
+//! Every C function is a wasm function of the same name, which takes and
+//! returns an `i32` for each `char`, `int`, `unsigned int`, and pointer, and an
+//! `i64` for each `long`. A `char` is kept sign-extended in its `i32`. The
+//! functions other files could call are exported, so that `main` can be
+//! invoked, and so is the memory.
+//!
+//! Pointers are 32 bits, as linear memory is addressed, but take 8 bytes in
+//! memory as they do on the other targets, so that `sizeof` and the layouts of
+//! structs stay the same: a pointer is stored as an `i64` and loaded from the
+//! low half.
+//!
+//! A local variable or parameter that is a scalar, and whose address is never
+//! taken, is a wasm local named for it, with a number after it if another
+//! variable in the function has the same name. Any other lives in the
+//! function's frame, on a shadow stack in linear memory that grows down from
+//! its top. The stack pointer is the global `$.sp`, and each function with a
+//! frame keeps its frame pointer in the local `$.fp`. Temporaries are locals
+//! `$.t<n>`. Names the compiler makes up start with a dot, which no variable's
+//! can, so the two never collide.
+//!
+//! Objects with static storage duration and string literals have fixed
+//! addresses from `DATA_START` up, with data segments for whatever isn't zero
+//! in them, and the stack is above them.
+//!
+//! Wasm only has structured control flow. A loop is a `loop` in a `block` that
+//! `break` branches out of, and a `switch` is a `block` for each of its labels,
+//! nested so that branching out of one starts the code after the label, which
+//! means the labels must be directly in the body of the `switch` rather than
+//! anywhere in it, as C allows, and `check` rejects any that aren't. A `goto`
+//! can go anywhere, so `check` rejects it.
+//!
+//! Functions called but not defined are imported from the module `env`. Wasm
+//! traps on division by zero, with or without checked arithmetic, since it
+//! can't exit with a message; checked arithmetic still makes dividing the most
+//! negative value by -1 wrap around rather than trap. `check` rejects variadic
+//! functions and calls to them, and the options that only concern assembly are
+//! ignored.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::mem;
+use ast::*;
+use ast::visit::{self, Visitor};
+use resolve::{Resolutions, Symbol};
+use semantic::{Analysis, SemanticError};
+use statics::{self, StaticValue};
+use super::fold;
+use super::{round_up, Layouts, Options, StaticsBuilder, MIN_JUMP_TABLE_CASES};
+
+/// The address of the first static object. Nothing is placed below it, so that
+/// no object's address is a null pointer.
+const DATA_START: i64 = 16;
+
+/// The bytes of linear memory given to the stack.
+const STACK_SIZE: i64 = 64 * 1024;
+
+/// The size of a page of linear memory, which is allocated by the page.
+const PAGE_SIZE: i64 = 64 * 1024;
+
+/// Where a local variable or parameter lives.
+enum Home {
+    /// In the wasm local with this name.
+    Local(String),
+    /// At this offset from the frame pointer.
+    Frame(i64),
+}
+
+/// Finds the variables whose address is taken with `&`, which have to be in
+/// memory.
+struct AddressTaken<'a> {
+    resolutions: &'a Resolutions,
+    symbols: HashSet<Symbol>,
+}
+
+impl<'a> Visitor for AddressTaken<'a> {
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        if let ExpressionKind::Unary(UnaryOp::AddressOf, operand) = ast[expression].kind {
+            if let ExpressionKind::Variable(_) = ast[operand].kind {
+                self.symbols.insert(self.resolutions[&operand]);
+            }
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// Collects the declarations of a function's local variables with automatic
+/// storage, in order.
+struct Declarations(Vec<DeclId>);
+
+impl Visitor for Declarations {
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        if ast[declaration].storage.is_none() {
+            self.0.push(declaration);
+        }
+        visit::walk_declaration(self, ast, declaration);
+    }
+}
+
+/// Finds the constructs that can't be generated in wasm, as `check` describes.
+struct Unsupported {
+    /// The names of the variadic functions the program declares.
+    variadic: HashSet<String>,
+    /// The `case` and `default` labels directly in the body of their `switch`.
+    placed: HashSet<StmtId>,
+    errors: Vec<SemanticError>,
+}
+
+impl Unsupported {
+    fn unsupported(&mut self, construct: &'static str, span: Span) {
+        self.errors.push(SemanticError::Unsupported { construct, line: span.line, column: span.column });
+    }
+}
+
+impl Visitor for Unsupported {
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        if function.variadic && function.body.is_some() {
+            self.unsupported("a variadic function in WebAssembly", function.span);
+        }
+        visit::walk_function(self, ast, function);
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        match ast[statement].kind {
+            StatementKind::Goto(_) => self.unsupported("`goto` in WebAssembly", ast[statement].span),
+            StatementKind::Switch(_, body) => {
+                let items = match ast[body].kind {
+                    StatementKind::Compound(ref items) => items.clone(),
+                    _ => vec![BlockItem::Statement(body)],
+                };
+                for item in items {
+                    if let BlockItem::Statement(mut label) = item {
+                        while let StatementKind::Case(_, inner) | StatementKind::Default(inner) = ast[label].kind {
+                            self.placed.insert(label);
+                            label = inner;
+                        }
+                    }
+                }
+            },
+            StatementKind::Case(..) | StatementKind::Default(_) if !self.placed.contains(&statement) => {
+                self.unsupported("a label inside another statement in a `switch` in WebAssembly", ast[statement].span);
+            },
+            _ => {},
+        }
+        visit::walk_stmt(self, ast, statement);
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        if let ExpressionKind::Call(ref name, _) = ast[expression].kind {
+            if self.variadic.contains(name) {
+                self.unsupported("a call to a variadic function in WebAssembly", ast[expression].span);
+            }
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// Collects the bytes of each distinct string literal in the program, in the
+/// order they appear.
+struct Strings(Vec<Vec<u8>>);
+
+impl Visitor for Strings {
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        if let ExpressionKind::StringLiteral(ref bytes) = ast[expression].kind {
+            if !self.0.contains(bytes) {
+                self.0.push(bytes.clone());
+            }
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+struct Generator<'a> {
+    ast: &'a Ast,
+    items: &'a [TopLevel],
+    analysis: &'a Analysis,
+    layouts: Layouts<'a>,
+    options: &'a Options,
+    /// The name of every declaration of an object with static storage duration.
+    statics: HashMap<DeclId, String>,
+    /// The address of each static object, by name.
+    addresses: HashMap<String, i64>,
+    /// The bytes of each distinct string literal, and its address.
+    strings: Vec<(Vec<u8>, i64)>,
+    /// The local variables and parameters whose address is taken.
+    address_taken: HashSet<Symbol>,
+    /// The functions the program defines.
+    defined: HashSet<&'a str>,
+    /// The import of each function called but not defined, in the order they
+    /// are first called.
+    imports: Vec<(String, String)>,
+    /// The locals of the function being generated, other than its parameters,
+    /// with their types, and its code.
+    locals: Vec<(String, &'static str)>,
+    code: String,
+    /// How many blocks deep the code being generated is.
+    depth: usize,
+    /// Where each local variable and parameter of the function lives.
+    homes: HashMap<Symbol, Home>,
+    /// How many of the function's locals have been named for each name.
+    names: HashMap<String, usize>,
+    /// The bytes of the function's frame used so far.
+    frame_size: i64,
+    /// The number of the function's next temporary, and of its last label.
+    temps: usize,
+    labels: usize,
+    /// What `break` and `continue` branch out of in the statement being
+    /// generated, innermost last.
+    breaks: Vec<String>,
+    continues: Vec<String>,
+    /// The return type of the function being generated.
+    returns: Type,
+}
+
+impl<'a> Generator<'a> {
+    fn emit(&mut self, instruction: &str) {
+        writeln!(self.code, "{:2$}{}", "", instruction, 4 + 2 * self.depth).unwrap();
+    }
+
+    /// Writes `instruction`, which starts a block, and indents what follows.
+    fn open(&mut self, instruction: &str) {
+        self.emit(instruction);
+        self.depth += 1;
+    }
+
+    /// Ends the innermost block.
+    fn close(&mut self) {
+        self.depth -= 1;
+        self.emit("end");
+    }
+
+    /// Starts the `else` of the innermost `if`.
+    fn otherwise(&mut self) {
+        self.depth -= 1;
+        self.emit("else");
+        self.depth += 1;
+    }
+
+    /// Makes a name for a new label of the function, for what `kind` says it
+    /// is, as in `$while.end3`.
+    fn new_label(&mut self, kind: &str) -> String {
+        self.labels += 1;
+        format!("${}{}", kind, self.labels)
+    }
+
+    /// Makes a new temporary local of wasm type `ty`.
+    fn temp(&mut self, ty: &'static str) -> String {
+        let temp = format!("$.t{}", self.temps);
+        self.temps += 1;
+        self.locals.push((temp.clone(), ty));
+        temp
+    }
+
+    /// Makes the name of the local for a variable named `name`.
+    fn local_name(&mut self, name: &str) -> String {
+        let count = self.names.entry(name.to_owned()).or_insert(0);
+        let local = match *count {
+            0 => format!("${}", name),
+            count => format!("${}.{}", name, count),
+        };
+        *count += 1;
+        local
+    }
+
+    /// Gives an object of type `ty` a slot in the frame, and returns its offset.
+    fn allocate(&mut self, ty: &Type) -> i64 {
+        let offset = round_up(self.frame_size, self.layouts.alignment(ty));
+        self.frame_size = offset + self.layouts.size_of(ty);
+        offset
+    }
+
+    /// The wasm type of values of the scalar type `ty`.
+    fn wasm_type(&self, ty: &Type) -> &'static str {
+        match *ty.unqualified() {
+            Type::Char | Type::Int | Type::UnsignedInt | Type::Pointer(_) => "i32",
+            Type::Long | Type::UnsignedLong => "i64",
+            ref ty => unreachable!("a value of type `{}`", ty),
+        }
+    }
+
+    /// The instruction that pushes `value`, an integer of type `ty`.
+    fn integer(&self, ty: &Type, value: i64) -> String {
+        match (self.wasm_type(ty), ty.unqualified()) {
+            ("i64", _) => format!("i64.const {}", value),
+            (_, &Type::Char) => format!("i32.const {}", value as i8),
+            _ => format!("i32.const {}", value as i32),
+        }
+    }
+
+    /// Generates the definition of the function at `index` in the program's
+    /// items, which has `body`.
+    fn function(&mut self, index: usize, function: &Function, body: &[BlockItem]) -> String {
+        self.returns = function.return_type.clone();
+        self.locals.clear();
+        self.homes.clear();
+        self.names.clear();
+        self.frame_size = 0;
+        self.temps = 0;
+        self.labels = 0;
+
+        // Scalars whose address isn't taken are locals, and everything else
+        // has a slot in the frame. A parameter in the frame is copied there
+        // from the wasm parameter.
+        let mut parameters = Vec::new();
+        let mut copies = Vec::new();
+        for (position, parameter) in function.parameters.iter().enumerate() {
+            let symbol = Symbol::Parameter { function: index, index: position };
+            let name = self.local_name(&parameter.name);
+            parameters.push(format!(" (param {} {})", name, self.wasm_type(&parameter.ty)));
+            if self.address_taken.contains(&symbol) {
+                let offset = self.allocate(&parameter.ty);
+                self.homes.insert(symbol, Home::Frame(offset));
+                copies.push((name, offset, &parameter.ty));
+            } else {
+                self.homes.insert(symbol, Home::Local(name));
+            }
+        }
+        let mut declarations = Declarations(Vec::new());
+        declarations.visit_block(self.ast, body);
+        for declaration in declarations.0 {
+            let symbol = Symbol::Variable(declaration);
+            let (name, ty) = (&self.ast[declaration].name, &self.ast[declaration].ty);
+            if ty.is_scalar() && !self.address_taken.contains(&symbol) {
+                let name = self.local_name(name);
+                let wasm_type = self.wasm_type(ty);
+                self.locals.push((name.clone(), wasm_type));
+                self.homes.insert(symbol, Home::Local(name));
+            } else {
+                let offset = self.allocate(ty);
+                self.homes.insert(symbol, Home::Frame(offset));
+            }
+        }
+        self.frame_size = round_up(self.frame_size, 16);
+
+        if self.frame_size > 0 {
+            self.locals.insert(0, ("$.fp".to_owned(), "i32"));
+            self.emit("global.get $.sp");
+            self.emit(&format!("i32.const {}", self.frame_size));
+            self.emit("i32.sub");
+            self.emit("local.tee $.fp");
+            self.emit("global.set $.sp");
+        }
+        for (name, offset, ty) in copies {
+            self.frame_address(offset);
+            self.emit(&format!("local.get {}", name));
+            self.store(ty);
+        }
+        self.block(body);
+        // Falling off the end of `main` returns 0. For any other function the
+        // value is meaningless, and returning 0 is as good as anything.
+        let zero = self.integer(&function.return_type, 0);
+        self.emit(&zero);
+        self.leave();
+
+        let export = match function.storage {
+            Some(StorageClass::Static) => String::new(),
+            _ => format!(" (export \"{}\")", function.name),
+        };
+        let mut definition = format!(
+            "  (func ${}{}{} (result {})\n",
+            function.name,
+            export,
+            parameters.concat(),
+            self.wasm_type(&function.return_type),
+        );
+        for (name, ty) in &self.locals {
+            writeln!(definition, "    (local {} {})", name, ty).unwrap();
+        }
+        definition.push_str(&mem::take(&mut self.code));
+        definition.push_str("  )\n");
+        definition
+    }
+
+    /// Pops the function's frame off the stack, before it returns.
+    fn leave(&mut self) {
+        if self.frame_size > 0 {
+            self.emit("local.get $.fp");
+            self.emit(&format!("i32.const {}", self.frame_size));
+            self.emit("i32.add");
+            self.emit("global.set $.sp");
+        }
+    }
+
+    /// Pushes the address `offset` bytes into the frame.
+    fn frame_address(&mut self, offset: i64) {
+        self.emit("local.get $.fp");
+        if offset != 0 {
+            self.emit(&format!("i32.const {}", offset));
+            self.emit("i32.add");
+        }
+    }
+
+    fn block(&mut self, items: &[BlockItem]) {
+        for item in items {
+            match *item {
+                BlockItem::Statement(statement) => self.statement(statement),
+                BlockItem::Declaration(ref declarations) => {
+                    for &declaration in declarations {
+                        self.declaration(declaration);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Stores a local variable's initial value, if it has one. The value of a
+    /// `static` one is in its data segment already.
+    fn declaration(&mut self, id: DeclId) {
+        let declaration = &self.ast[id];
+        let initializer = match declaration.initializer {
+            Some(ref initializer) if declaration.storage.is_none() => initializer,
+            _ => return,
+        };
+        match self.homes[&Symbol::Variable(id)] {
+            Home::Local(ref name) => {
+                let name = name.clone();
+                let value = match *initializer {
+                    Initializer::Expr(value) => Some(value),
+                    Initializer::List(ref items) => match items.first() {
+                        Some(&Initializer::Expr(value)) => Some(value),
+                        Some(_) => unreachable!("a nested initializer list for a scalar"),
+                        None => None,
+                    },
+                };
+                match value {
+                    Some(value) => self.operand(value, &declaration.ty.decay()),
+                    None => {
+                        let zero = self.integer(&declaration.ty, 0);
+                        self.emit(&zero);
+                    },
+                }
+                self.emit(&format!("local.set {}", name));
+            },
+            Home::Frame(offset) => self.initialize(&declaration.ty, initializer, offset),
+        }
+    }
+
+    /// Stores `initializer`'s value for an object of type `ty` at `offset` in
+    /// the frame. Whatever an initializer list leaves out is zeroed.
+    fn initialize(&mut self, ty: &Type, initializer: &Initializer, offset: i64) {
+        match (ty.unqualified(), initializer) {
+            (&Type::Array(_, length), &Initializer::Expr(value)) => match self.ast[value].kind {
+                ExpressionKind::StringLiteral(ref bytes) => {
+                    // The literal is copied, without its terminating null if
+                    // the array has no room for it.
+                    let length = length as i64;
+                    let copied = length.min(bytes.len() as i64 + 1);
+                    let source = self.string(bytes);
+                    self.frame_address(offset);
+                    self.emit(&format!("i32.const {}", source));
+                    self.emit(&format!("i32.const {}", copied));
+                    self.emit("memory.copy");
+                    if copied < length {
+                        self.zero(offset + copied, length - copied);
+                    }
+                },
+                ref kind => unreachable!("an array initialized from {:?}", kind),
+            },
+            (Type::Array(element, length), Initializer::List(items)) => {
+                if items.len() < *length {
+                    let size = self.layouts.size_of(ty);
+                    self.zero(offset, size);
+                }
+                let element_size = self.layouts.size_of(element);
+                for (index, item) in items.iter().enumerate() {
+                    self.initialize(element, item, offset + index as i64 * element_size);
+                }
+            },
+            (Type::Struct(tag), Initializer::List(items)) => {
+                let members = self.members(tag);
+                if items.len() < members.len() {
+                    let size = self.layouts.size_of(ty);
+                    self.zero(offset, size);
+                }
+                for (member, item) in members.iter().zip(items) {
+                    let member_offset = self.layouts.offset_of(tag, &member.name);
+                    self.initialize(&member.ty, item, offset + member_offset);
+                }
+            },
+            (_, Initializer::List(items)) => match items.first() {
+                Some(item) => self.initialize(ty, item, offset),
+                None => {
+                    let size = self.layouts.size_of(ty);
+                    self.zero(offset, size);
+                },
+            },
+            (_, &Initializer::Expr(value)) => {
+                self.frame_address(offset);
+                self.operand(value, &ty.decay());
+                self.store(ty);
+            },
+        }
+    }
+
+    /// Zeroes `size` bytes at `offset` in the frame.
+    fn zero(&mut self, offset: i64, size: i64) {
+        self.frame_address(offset);
+        self.emit("i32.const 0");
+        self.emit(&format!("i32.const {}", size));
+        self.emit("memory.fill");
+    }
+
+    /// The members of `struct tag`.
+    fn members(&self, tag: &str) -> &'a [Member] {
+        self.items.iter()
+            .filter_map(|item| match *item {
+                TopLevel::Struct(ref definition) if definition.name == tag => Some(&definition.members[..]),
+                _ => None,
+            })
+            .next()
+            .unwrap()
+    }
+
+    fn statement(&mut self, statement: StmtId) {
+        match self.ast[statement].kind {
+            StatementKind::Return(value) => {
+                let returns = self.returns.clone();
+                self.operand(value, &returns);
+                self.leave();
+                self.emit("return");
+            },
+            StatementKind::Expression(Some(expression)) => {
+                self.expression(expression);
+                self.emit("drop");
+            },
+            StatementKind::Expression(None) => {},
+            StatementKind::Compound(ref items) => self.block(items),
+            StatementKind::If(condition, then, otherwise) => {
+                self.test(condition);
+                self.open("if");
+                self.statement(then);
+                if let Some(otherwise) = otherwise {
+                    self.otherwise();
+                    self.statement(otherwise);
+                }
+                self.close();
+            },
+            StatementKind::While(condition, body) => {
+                let (end, start) = (self.new_label("while.end"), self.new_label("while.cond"));
+                self.open(&format!("block {}", end));
+                self.open(&format!("loop {}", start));
+                self.break_unless(condition, &end);
+                self.body(body, &end, &start);
+                self.emit(&format!("br {}", start));
+                self.close();
+                self.close();
+            },
+            StatementKind::DoWhile(body, condition) => {
+                let (end, start, next) = (self.new_label("do.end"), self.new_label("do.body"), self.new_label("do.cond"));
+                self.open(&format!("block {}", end));
+                self.open(&format!("loop {}", start));
+                self.open(&format!("block {}", next));
+                self.body(body, &end, &next);
+                self.close();
+                self.test(condition);
+                self.emit(&format!("br_if {}", start));
+                self.close();
+                self.close();
+            },
+            StatementKind::For { ref init, condition, post, body } => {
+                match *init {
+                    ForInit::Declaration(ref declarations) => {
+                        for &declaration in declarations {
+                            self.declaration(declaration);
+                        }
+                    },
+                    ForInit::Expression(Some(expression)) => {
+                        self.expression(expression);
+                        self.emit("drop");
+                    },
+                    ForInit::Expression(None) => {},
+                }
+                let (end, start, next) = (self.new_label("for.end"), self.new_label("for.cond"), self.new_label("for.inc"));
+                self.open(&format!("block {}", end));
+                self.open(&format!("loop {}", start));
+                if let Some(condition) = condition {
+                    self.break_unless(condition, &end);
+                }
+                self.open(&format!("block {}", next));
+                self.body(body, &end, &next);
+                self.close();
+                if let Some(post) = post {
+                    self.expression(post);
+                    self.emit("drop");
+                }
+                self.emit(&format!("br {}", start));
+                self.close();
+                self.close();
+            },
+            StatementKind::Switch(value, body) => self.switch(statement, value, body),
+            // The `switch` has placed the label already.
+            StatementKind::Case(_, body) | StatementKind::Default(body) => self.statement(body),
+            StatementKind::Break => {
+                let target = self.breaks.last().unwrap().clone();
+                self.emit(&format!("br {}", target));
+            },
+            StatementKind::Continue => {
+                let target = self.continues.last().unwrap().clone();
+                self.emit(&format!("br {}", target));
+            },
+            // Nothing can jump to the label, since `check` rejects `goto`.
+            StatementKind::Labeled(_, body) => self.statement(body),
+            StatementKind::Goto(_) => unreachable!("a `goto`, which `check` rejects"),
+        }
+    }
+
+    /// Evaluates the scalar `expression`, leaving an `i32` that isn't zero if
+    /// it is true, as `if` and `br_if` take.
+    fn test(&mut self, expression: ExprId) {
+        self.expression(expression);
+        if self.wasm_type(&self.value_type(expression)) == "i64" {
+            self.emit("i64.const 0");
+            self.emit("i64.ne");
+        }
+    }
+
+    /// Evaluates the scalar `expression`, leaving 1 if it is true and 0 if it
+    /// isn't.
+    fn truth(&mut self, expression: ExprId) {
+        let wasm_type = self.wasm_type(&self.value_type(expression));
+        self.expression(expression);
+        self.emit(&format!("{}.const 0", wasm_type));
+        self.emit(&format!("{}.ne", wasm_type));
+    }
+
+    /// Evaluates `condition` and branches out of `label` if it is false.
+    fn break_unless(&mut self, condition: ExprId, label: &str) {
+        let wasm_type = self.wasm_type(&self.value_type(condition));
+        self.expression(condition);
+        self.emit(&format!("{}.eqz", wasm_type));
+        self.emit(&format!("br_if {}", label));
+    }
+
+    /// Generates the `switch` statement `switch` on `value`. Its body is split
+    /// at each label, and each part after a label follows the end of a block
+    /// that its label's value branches out of: through a `br_table` if the
+    /// labels are dense enough for one, and a compare for each label otherwise.
+    fn switch(&mut self, switch: StmtId, value: ExprId, body: StmtId) {
+        let items = match self.ast[body].kind {
+            StatementKind::Compound(ref items) => items.clone(),
+            _ => vec![BlockItem::Statement(body)],
+        };
+        // The labels that start each part, and the items in it. The first part
+        // is whatever is before the first label, which nothing reaches.
+        let mut parts = vec![(Vec::new(), Vec::new())];
+        for item in items {
+            if let BlockItem::Statement(mut statement) = item {
+                if let StatementKind::Case(..) | StatementKind::Default(_) = self.ast[statement].kind {
+                    parts.push((Vec::new(), Vec::new()));
+                }
+                while let StatementKind::Case(_, inner) | StatementKind::Default(inner) = self.ast[statement].kind {
+                    parts.last_mut().unwrap().0.push(statement);
+                    statement = inner;
+                }
+            }
+            parts.last_mut().unwrap().1.push(item);
+        }
+
+        let labels = &self.analysis.switches[&switch];
+        let end = self.new_label("sw.end");
+        let mut blocks = HashMap::new();
+        let mut names = Vec::new();
+        for (starts, _) in &parts[1..] {
+            let kind = if Some(starts[0]) == labels.default { "sw.default" } else { "sw.case" };
+            let name = self.new_label(kind);
+            for &label in starts {
+                blocks.insert(label, name.clone());
+            }
+            names.push(name);
+        }
+        let placed = |label: &StmtId| blocks.contains_key(label);
+        if !labels.cases.iter().all(|(_, case)| placed(case)) || !labels.default.iter().all(placed) {
+            unreachable!("a label inside another statement in a `switch`, which `check` rejects");
+        }
+        let cases: Vec<_> = labels.cases.iter().map(|&(value, case)| (value, blocks[&case].clone())).collect();
+        let otherwise = match labels.default {
+            Some(default) => blocks[&default].clone(),
+            None => end.clone(),
+        };
+
+        self.open(&format!("block {}", end));
+        for name in names.iter().rev() {
+            self.open(&format!("block {}", name));
+        }
+        let ty = self.value_type(value);
+        let wasm_type = self.wasm_type(&ty);
+        let (min, max) = match (cases.iter().map(|case| case.0).min(), cases.iter().map(|case| case.0).max()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => (0, 0),
+        };
+        // Up to two thirds of a table may branch to `otherwise`.
+        let dense = cases.len() >= MIN_JUMP_TABLE_CASES && max - min < 3 * cases.len() as i64;
+        self.expression(value);
+        if dense && wasm_type == "i32" {
+            if min != 0 {
+                let min = self.integer(&ty, min);
+                self.emit(&min);
+                self.emit("i32.sub");
+            }
+            let targets: Vec<_> = (min..=max)
+                .map(|value| match cases.iter().find(|case| case.0 == value) {
+                    Some(case) => case.1.clone(),
+                    None => otherwise.clone(),
+                })
+                .collect();
+            self.emit(&format!("br_table {} {}", targets.join(" "), otherwise));
+        } else {
+            let temp = self.temp(wasm_type);
+            self.emit(&format!("local.set {}", temp));
+            for (value, label) in &cases {
+                self.emit(&format!("local.get {}", temp));
+                let value = self.integer(&ty, *value);
+                self.emit(&value);
+                self.emit(&format!("{}.eq", wasm_type));
+                self.emit(&format!("br_if {}", label));
+            }
+            self.emit(&format!("br {}", otherwise));
+        }
+
+        self.breaks.push(end);
+        let mut parts = parts.into_iter();
+        self.block(&parts.next().unwrap().1);
+        for (_, items) in parts {
+            self.close();
+            self.block(&items);
+        }
+        self.breaks.pop();
+        self.close();
+    }
+
+    /// Generates the body of a loop, in which `break` branches out of `end`
+    /// and `continue` out of `next`, or back to the start of the loop `next`.
+    fn body(&mut self, body: StmtId, end: &str, next: &str) {
+        self.breaks.push(end.to_owned());
+        self.continues.push(next.to_owned());
+        self.statement(body);
+        self.breaks.pop();
+        self.continues.pop();
+    }
+
+    /// The local that the variable `variable` refers to is kept in, if it is
+    /// kept in one rather than in memory.
+    fn local(&self, variable: ExprId) -> Option<String> {
+        if let ExpressionKind::Variable(_) = self.ast[variable].kind {
+            if let Some(Home::Local(name)) = self.homes.get(&self.analysis.resolutions[&variable]) {
+                return Some(name.clone());
+            }
+        }
+        None
+    }
+
+    /// Loads a `ty` from the address on the stack.
+    fn load(&mut self, ty: &Type) {
+        let instruction = match *ty.unqualified() {
+            Type::Char => "i32.load8_s",
+            Type::Int | Type::UnsignedInt | Type::Pointer(_) => "i32.load",
+            Type::Long | Type::UnsignedLong => "i64.load",
+            ref ty => unimplemented!("codegen for values of type `{}`", ty),
+        };
+        self.emit(instruction);
+    }
+
+    /// Stores the `ty` on the stack at the address under it.
+    fn store(&mut self, ty: &Type) {
+        match *ty.unqualified() {
+            Type::Char => self.emit("i32.store8"),
+            Type::Int | Type::UnsignedInt => self.emit("i32.store"),
+            Type::Long | Type::UnsignedLong => self.emit("i64.store"),
+            // The upper half of a pointer's 8 bytes is zero.
+            Type::Pointer(_) => {
+                self.emit("i64.extend_i32_u");
+                self.emit("i64.store");
+            },
+            ref ty => unimplemented!("codegen for values of type `{}`", ty),
+        }
+    }
+
+    /// The address of the string literal with these bytes.
+    fn string(&self, bytes: &[u8]) -> i64 {
+        self.strings.iter().find(|string| string.0 == bytes).unwrap().1
+    }
+
+    /// The address of the static object declared by `declaration`.
+    fn static_address(&self, declaration: DeclId) -> i64 {
+        match self.addresses.get(&self.statics[&declaration]) {
+            Some(&address) => address,
+            None => unimplemented!("codegen for objects defined in other files in WebAssembly"),
+        }
+    }
+
+    /// Writes `value`, the initial value of a `ty`, into `bytes` at `offset`.
+    fn static_value(&self, ty: &Type, value: &StaticValue, bytes: &mut [u8], offset: usize) {
+        let size = self.layouts.size_of(ty) as usize;
+        match *value {
+            StaticValue::Zero => {},
+            StaticValue::Int(value) => bytes[offset..offset + size].copy_from_slice(&value.to_le_bytes()[..size]),
+            StaticValue::Address { ref object, offset: extra } => {
+                let address = match *object {
+                    statics::StaticObject::Declaration(declaration) => self.static_address(declaration),
+                    statics::StaticObject::String(literal) => match self.ast[literal].kind {
+                        ExpressionKind::StringLiteral(ref bytes) => self.string(bytes),
+                        ref kind => unreachable!("a string literal that is {:?}", kind),
+                    },
+                };
+                bytes[offset..offset + size].copy_from_slice(&(address + extra).to_le_bytes()[..size]);
+            },
+            StaticValue::List(ref items) => match *ty.unqualified() {
+                Type::Array(ref element, _) => {
+                    let element_size = self.layouts.size_of(element) as usize;
+                    for (index, item) in items.iter().enumerate() {
+                        self.static_value(element, item, bytes, offset + index * element_size);
+                    }
+                },
+                Type::Struct(ref tag) => {
+                    for (member, item) in self.members(tag).iter().zip(items) {
+                        let member_offset = self.layouts.offset_of(tag, &member.name) as usize;
+                        self.static_value(&member.ty, item, bytes, offset + member_offset);
+                    }
+                },
+                ref ty => unreachable!("a static initializer list for `{}`", ty),
+            },
+        }
+    }
+
+    /// Evaluates `expression` as a value of type `ty`. It is that type already,
+    /// unless it is a null pointer constant used as a pointer, which the
+    /// analysis leaves an integer.
+    fn operand(&mut self, expression: ExprId, ty: &Type) {
+        self.expression(expression);
+        let from = self.value_type(expression);
+        self.convert(&from, ty);
+    }
+
+    /// Evaluates `expression`, converted to the type its value is used as.
+    fn expression(&mut self, expression: ExprId) {
+        self.evaluate(expression);
+        if let Some(to) = self.analysis.conversions.get(&expression) {
+            let from = self.analysis.types[&expression].decay();
+            self.convert(&from, to);
+        }
+    }
+
+    /// Evaluates `expression` as its own type, leaving its value on the stack.
+    /// An array evaluates to the address of its first element, and a struct,
+    /// whose value analysis only lets be discarded, to its address.
+    fn evaluate(&mut self, expression: ExprId) {
+        let ty = self.analysis.types[&expression].clone();
+        match *ty.unqualified() {
+            Type::Array(..) | Type::Struct(_) => return self.address(expression),
+            _ => {},
+        }
+        match self.ast[expression].kind {
+            ExpressionKind::IntLiteral(value, _) => {
+                let value = self.integer(&ty, value);
+                self.emit(&value);
+            },
+            ExpressionKind::CharConstant(value) => {
+                let value = self.integer(&ty, i64::from(value));
+                self.emit(&value);
+            },
+            ExpressionKind::SizeOf(ref operand) => {
+                let value = self.integer(&ty, self.layouts.size_of(operand));
+                self.emit(&value);
+            },
+            ExpressionKind::Variable(_) => match self.local(expression) {
+                Some(local) => self.emit(&format!("local.get {}", local)),
+                None => {
+                    self.address(expression);
+                    self.load(&ty);
+                },
+            },
+            ExpressionKind::Assign(operator, target, value) => self.assign(operator, target, value),
+            ExpressionKind::Unary(UnaryOp::AddressOf, operand) => self.address(operand),
+            ExpressionKind::Unary(UnaryOp::Dereference, _)
+            | ExpressionKind::Subscript(..)
+            | ExpressionKind::Member(..)
+            | ExpressionKind::PointerMember(..) => {
+                self.address(expression);
+                self.load(&ty);
+            },
+            ExpressionKind::Unary(operator @ UnaryOp::PreIncrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PreDecrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PostIncrement, operand)
+            | ExpressionKind::Unary(operator @ UnaryOp::PostDecrement, operand) => self.increment(operator, operand),
+            ExpressionKind::Unary(operator, operand) => self.unary(operator, operand),
+            ExpressionKind::Cast { ref to, expr } => {
+                self.expression(expr);
+                let from = self.value_type(expr);
+                self.convert(&from, to.unqualified());
+            },
+            ExpressionKind::Binary(operator @ BinaryOp::LogicalAnd, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::LogicalOr, lhs, rhs) => self.logical(operator, lhs, rhs),
+            ExpressionKind::Binary(operator @ BinaryOp::Add, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::Subtract, lhs, rhs)
+                if self.is_pointer(lhs) || self.is_pointer(rhs) => self.pointer_arithmetic(operator, lhs, rhs),
+            ExpressionKind::Binary(operator @ BinaryOp::ShiftLeft, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::ShiftRight, lhs, rhs) => {
+                // Wasm only uses the low bits of the amount, as the machines'
+                // shift instructions do, but the amount has to be the same type
+                // as the value shifted.
+                let operands = self.value_type(lhs);
+                self.expression(lhs);
+                match self.constant(rhs) {
+                    Some(amount) => {
+                        let amount = self.integer(&operands, amount & (self.layouts.size_of(&operands) * 8 - 1));
+                        self.emit(&amount);
+                    },
+                    None => self.operand(rhs, &operands),
+                }
+                self.binary(operator, &operands);
+            },
+            ExpressionKind::Binary(operator, lhs, rhs) => {
+                // A pointer may be compared with a null pointer constant.
+                let operands = match self.value_type(rhs) {
+                    ty @ Type::Pointer(_) => ty,
+                    _ => self.value_type(lhs),
+                };
+                self.operand(lhs, &operands);
+                self.operand(rhs, &operands);
+                self.binary(operator, &operands);
+            },
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                self.test(condition);
+                self.open(&format!("if (result {})", self.wasm_type(&ty)));
+                self.operand(then, &ty);
+                self.otherwise();
+                self.operand(otherwise, &ty);
+                self.close();
+            },
+            ExpressionKind::Comma(ref expressions) => {
+                let (last, rest) = expressions.split_last().unwrap();
+                for &expression in rest {
+                    self.expression(expression);
+                    self.emit("drop");
+                }
+                self.expression(*last);
+            },
+            ExpressionKind::Call(ref name, ref arguments) => self.call(expression, name, arguments),
+            ExpressionKind::StringLiteral(_) => unreachable!("a string literal that isn't an array"),
+        }
+    }
+
+    /// Pushes the address of the lvalue `expression`.
+    fn address(&mut self, expression: ExprId) {
+        match self.ast[expression].kind {
+            ExpressionKind::Variable(_) => {
+                let symbol = self.analysis.resolutions[&expression];
+                match (self.homes.get(&symbol), symbol) {
+                    (Some(&Home::Frame(offset)), _) => self.frame_address(offset),
+                    (Some(&Home::Local(_)), _) => unreachable!("the address of a variable kept in a local"),
+                    (None, Symbol::Variable(declaration)) => {
+                        let address = self.static_address(declaration);
+                        self.emit(&format!("i32.const {}", address));
+                    },
+                    (None, _) => unreachable!("a parameter outside its function"),
+                }
+            },
+            ExpressionKind::StringLiteral(ref bytes) => {
+                let address = self.string(bytes);
+                self.emit(&format!("i32.const {}", address));
+            },
+            ExpressionKind::Unary(UnaryOp::Dereference, pointer) => self.expression(pointer),
+            // `a[i]` is `*(a + i)`, whichever of the two is the pointer.
+            ExpressionKind::Subscript(array, index) => self.pointer_arithmetic(BinaryOp::Add, array, index),
+            ExpressionKind::Member(object, ref member) => {
+                self.address(object);
+                let ty = self.analysis.types[&object].clone();
+                self.member(&ty, member);
+            },
+            ExpressionKind::PointerMember(pointer, ref member) => {
+                self.expression(pointer);
+                let ty = match *self.value_type(pointer).unqualified() {
+                    Type::Pointer(ref pointee) => (**pointee).clone(),
+                    ref ty => unreachable!("`->` applied to `{}`", ty),
+                };
+                self.member(&ty, member);
+            },
+            ref kind => unimplemented!("codegen for the address of {:?}", kind),
+        }
+    }
+
+    /// Offsets the address on the stack, of a struct of type `ty`, to that of
+    /// its `member`.
+    fn member(&mut self, ty: &Type, member: &str) {
+        let offset = match *ty.unqualified() {
+            Type::Struct(ref tag) => self.layouts.offset_of(tag, member),
+            ref ty => unreachable!("a member of `{}`", ty),
+        };
+        if offset != 0 {
+            self.emit(&format!("i32.const {}", offset));
+            self.emit("i32.add");
+        }
+    }
+
+    /// Assigns `value` to `target`, leaving the value assigned. With an
+    /// `operator`, assigns the result of applying it to both.
+    fn assign(&mut self, operator: Option<BinaryOp>, target: ExprId, value: ExprId) {
+        let ty = self.analysis.types[&target].clone();
+        let wasm_type = self.wasm_type(&ty);
+        let local = self.local(target);
+        // The address stays on the stack for the store.
+        if local.is_none() {
+            self.address(target);
+        }
+        match operator {
+            None => self.operand(value, &ty.decay()),
+            Some(operator) => {
+                let address = match local {
+                    Some(_) => None,
+                    None => {
+                        let address = self.temp("i32");
+                        self.emit(&format!("local.tee {}", address));
+                        Some(address)
+                    },
+                };
+                let value_type = self.value_type(value);
+                self.expression(value);
+                let rhs = self.temp(self.wasm_type(&value_type));
+                self.emit(&format!("local.set {}", rhs));
+                match (&local, &address) {
+                    (Some(local), _) => self.emit(&format!("local.get {}", local)),
+                    (_, Some(address)) => {
+                        self.emit(&format!("local.get {}", address));
+                        self.load(&ty);
+                    },
+                    _ => unreachable!(),
+                }
+                match operator {
+                    BinaryOp::Add | BinaryOp::Subtract if ty.is_pointer() => {
+                        self.emit(&format!("local.get {}", rhs));
+                        self.scale(&value_type, &ty);
+                        self.emit(if operator == BinaryOp::Add { "i32.add" } else { "i32.sub" });
+                    },
+                    // The target is converted like the left operand of
+                    // `operator`, to the type the right one was, and the result
+                    // back.
+                    _ => {
+                        self.convert(&ty.decay(), &value_type);
+                        self.emit(&format!("local.get {}", rhs));
+                        self.binary(operator, &value_type);
+                        self.convert(&value_type, &ty.decay());
+                    },
+                }
+            },
+        }
+        match local {
+            Some(local) => self.emit(&format!("local.tee {}", local)),
+            None => {
+                let result = self.temp(wasm_type);
+                self.emit(&format!("local.tee {}", result));
+                self.store(&ty);
+                self.emit(&format!("local.get {}", result));
+            },
+        }
+    }
+
+    /// Increments or decrements `target` by one, or by its pointee's size if it
+    /// is a pointer, leaving its new or old value as `operator` says.
+    fn increment(&mut self, operator: UnaryOp, target: ExprId) {
+        let ty = self.analysis.types[&target].clone();
+        let wasm_type = self.wasm_type(&ty);
+        let decrement = matches!(operator, UnaryOp::PreDecrement | UnaryOp::PostDecrement);
+        let step = if ty.is_pointer() { self.layouts.size_of(&pointee(&ty)) } else { 1 };
+        let step = format!("{}.const {}", wasm_type, step);
+        let instruction = format!("{}.{}", wasm_type, if decrement { "sub" } else { "add" });
+        match self.local(target) {
+            Some(local) => {
+                if operator.is_postfix() {
+                    self.emit(&format!("local.get {}", local));
+                }
+                self.emit(&format!("local.get {}", local));
+                self.emit(&step);
+                self.emit(&instruction);
+                self.narrow(&ty);
+                let set = if operator.is_postfix() { "local.set" } else { "local.tee" };
+                self.emit(&format!("{} {}", set, local));
+            },
+            None => {
+                let address = self.temp("i32");
+                self.address(target);
+                self.emit(&format!("local.tee {}", address));
+                self.emit(&format!("local.get {}", address));
+                self.load(&ty);
+                let old = if operator.is_postfix() {
+                    let old = self.temp(wasm_type);
+                    self.emit(&format!("local.tee {}", old));
+                    Some(old)
+                } else {
+                    None
+                };
+                self.emit(&step);
+                self.emit(&instruction);
+                self.narrow(&ty);
+                let result = match old {
+                    Some(old) => old,
+                    None => {
+                        let new = self.temp(wasm_type);
+                        self.emit(&format!("local.tee {}", new));
+                        new
+                    },
+                };
+                self.store(&ty);
+                self.emit(&format!("local.get {}", result));
+            },
+        }
+    }
+
+    /// Sign-extends the low byte of the `i32` on the stack if `ty` is `char`,
+    /// after arithmetic on it.
+    fn narrow(&mut self, ty: &Type) {
+        if *ty.unqualified() == Type::Char {
+            self.emit("i32.extend8_s");
+        }
+    }
+
+    /// Whether `expression`'s value is a pointer.
+    fn is_pointer(&self, expression: ExprId) -> bool {
+        self.value_type(expression).is_pointer()
+    }
+
+    /// Converts the index on the stack, of type `ty`, to the number of bytes
+    /// that many of what `pointer` points to take.
+    fn scale(&mut self, ty: &Type, pointer: &Type) {
+        self.convert(ty, &Type::Int);
+        let size = self.layouts.size_of(&pointee(pointer));
+        if size != 1 {
+            self.emit(&format!("i32.const {}", size));
+            self.emit("i32.mul");
+        }
+    }
+
+    /// Adds an integer to a pointer, subtracts one from a pointer, or subtracts
+    /// two pointers, giving the number of objects between them.
+    fn pointer_arithmetic(&mut self, operator: BinaryOp, lhs: ExprId, rhs: ExprId) {
+        let (lhs_type, rhs_type) = (self.value_type(lhs), self.value_type(rhs));
+        self.expression(lhs);
+        match (lhs_type.is_pointer(), rhs_type.is_pointer()) {
+            (true, true) => {
+                self.expression(rhs);
+                self.emit("i32.sub");
+                let size = self.layouts.size_of(&pointee(&lhs_type));
+                if size != 1 {
+                    self.emit(&format!("i32.const {}", size));
+                    self.emit("i32.div_s");
+                }
+                self.emit("i64.extend_i32_s");
+            },
+            (true, false) => {
+                self.expression(rhs);
+                self.scale(&rhs_type, &lhs_type);
+                self.emit(if operator == BinaryOp::Subtract { "i32.sub" } else { "i32.add" });
+            },
+            _ => {
+                self.scale(&lhs_type, &rhs_type);
+                self.expression(rhs);
+                self.emit("i32.add");
+            },
+        }
+    }
+
+    /// Calls the function `name` with `arguments`, leaving its return value.
+    /// `call` is the expression making the call. A function the program doesn't
+    /// define is imported, with the types of its prototype if it has one, and
+    /// otherwise of the arguments and `int`.
+    fn call(&mut self, call: ExprId, name: &str, arguments: &[ExprId]) {
+        let function = match self.analysis.resolutions.get(&call) {
+            Some(&Symbol::Function(index)) => match self.items[index] {
+                TopLevel::Function(ref function) => Some(function),
+                _ => unreachable!("a call resolved to something other than a function"),
+            },
+            _ => None,
+        };
+        let types: Vec<_> = arguments
+            .iter()
+            .enumerate()
+            .map(|(position, &argument)| match function.and_then(|function| function.parameters.get(position)) {
+                Some(parameter) => parameter.ty.clone(),
+                None => self.value_type(argument),
+            })
+            .collect();
+        for (&argument, ty) in arguments.iter().zip(&types) {
+            self.operand(argument, ty);
+        }
+        if !self.defined.contains(name) && !self.imports.iter().any(|import| import.0 == name) {
+            let parameters: Vec<_> = types.iter().map(|ty| format!(" (param {})", self.wasm_type(ty))).collect();
+            let returns = self.wasm_type(function.map_or(&Type::Int, |function| &function.return_type));
+            let import = format!("(import \"env\" \"{}\" (func ${}{} (result {})))", name, name, parameters.concat(), returns);
+            self.imports.push((name.to_owned(), import));
+        }
+        self.emit(&format!("call ${}", name));
+    }
+
+    /// Evaluates `lhs && rhs` or `lhs || rhs`, skipping `rhs` if `lhs` decides
+    /// the result, which is then the truth of whichever was evaluated last.
+    fn logical(&mut self, operator: BinaryOp, lhs: ExprId, rhs: ExprId) {
+        self.test(lhs);
+        self.open("if (result i32)");
+        if operator == BinaryOp::LogicalAnd {
+            self.truth(rhs);
+            self.otherwise();
+            self.emit("i32.const 0");
+        } else {
+            self.emit("i32.const 1");
+            self.otherwise();
+            self.truth(rhs);
+        }
+        self.close();
+    }
+
+    /// Converts the value on the stack from type `from` to `to`.
+    fn convert(&mut self, from: &Type, to: &Type) {
+        if !from.is_scalar() || !to.is_scalar() {
+            unimplemented!("codegen for conversions from `{}` to `{}`", from, to);
+        }
+        match (self.wasm_type(from), self.wasm_type(to)) {
+            ("i32", "i64") if from.is_signed() => self.emit("i64.extend_i32_s"),
+            ("i32", "i64") => self.emit("i64.extend_i32_u"),
+            ("i64", "i32") => self.emit("i32.wrap_i64"),
+            _ => {},
+        }
+        if *from.unqualified() != Type::Char {
+            self.narrow(to);
+        }
+    }
+
+    /// Applies `operator` to `operand`, leaving the result.
+    fn unary(&mut self, operator: UnaryOp, operand: ExprId) {
+        let wasm_type = self.wasm_type(&self.value_type(operand));
+        match operator {
+            UnaryOp::Negate => {
+                self.emit(&format!("{}.const 0", wasm_type));
+                self.expression(operand);
+                self.emit(&format!("{}.sub", wasm_type));
+            },
+            UnaryOp::BitwiseNot => {
+                self.expression(operand);
+                self.emit(&format!("{}.const -1", wasm_type));
+                self.emit(&format!("{}.xor", wasm_type));
+            },
+            UnaryOp::LogicalNot => {
+                self.expression(operand);
+                self.emit(&format!("{}.eqz", wasm_type));
+            },
+            operator => unreachable!("unary `{}` as an arithmetic operator", operator.symbol()),
+        }
+    }
+
+    /// The type `expression`'s value is used as: its own, decayed, or the one
+    /// it is implicitly converted to.
+    fn value_type(&self, expression: ExprId) -> Type {
+        match self.analysis.conversions.get(&expression) {
+            Some(ty) => ty.clone(),
+            None => self.analysis.types[&expression].decay(),
+        }
+    }
+
+    /// Applies `operator` to the two values on the stack, which are both of
+    /// type `operands`.
+    fn binary(&mut self, operator: BinaryOp, operands: &Type) {
+        if !operands.is_scalar() {
+            unimplemented!("codegen for binary `{}` on `{}`", operator.symbol(), operands);
+        }
+        // Pointers compare as unsigned addresses.
+        let signed = operands.is_signed();
+        let instruction = match operator {
+            BinaryOp::Add => "add",
+            BinaryOp::Subtract => "sub",
+            BinaryOp::Multiply => "mul",
+            BinaryOp::ShiftLeft => "shl",
+            BinaryOp::ShiftRight => if signed { "shr_s" } else { "shr_u" },
+            BinaryOp::BitwiseAnd => "and",
+            BinaryOp::BitwiseXor => "xor",
+            BinaryOp::BitwiseOr => "or",
+            BinaryOp::Divide => return self.divide(operands, false),
+            BinaryOp::Modulo => return self.divide(operands, true),
+            BinaryOp::Equal => "eq",
+            BinaryOp::NotEqual => "ne",
+            BinaryOp::LessThan => if signed { "lt_s" } else { "lt_u" },
+            BinaryOp::LessEqual => if signed { "le_s" } else { "le_u" },
+            BinaryOp::GreaterThan => if signed { "gt_s" } else { "gt_u" },
+            BinaryOp::GreaterEqual => if signed { "ge_s" } else { "ge_u" },
+            operator => unreachable!("binary `{}` as an arithmetic operator", operator.symbol()),
+        };
+        self.emit(&format!("{}.{}", self.wasm_type(operands), instruction));
+    }
+
+    /// The value of `expression` if it is made of constants, as the type it
+    /// is used as.
+    fn constant(&self, expression: ExprId) -> Option<i64> {
+        let evaluator = fold::Evaluator { types: &self.analysis.types, conversions: &self.analysis.conversions };
+        evaluator.operand(self.ast, expression)
+    }
+
+    /// Divides the two values on the stack, which are of type `operands`,
+    /// leaving the quotient or the `remainder`. Wasm traps on a zero divisor,
+    /// and on dividing the most negative value by -1, which with checked
+    /// arithmetic wraps around instead. The remainder of that is 0 anyway.
+    fn divide(&mut self, operands: &Type, remainder: bool) {
+        let wasm_type = self.wasm_type(operands);
+        let signed = operands.is_signed();
+        let instruction = format!(
+            "{}.{}_{}",
+            wasm_type,
+            if remainder { "rem" } else { "div" },
+            if signed { "s" } else { "u" },
+        );
+        if remainder || !signed || !self.options.checked_arithmetic {
+            return self.emit(&instruction);
+        }
+        let (lhs, rhs) = (self.temp(wasm_type), self.temp(wasm_type));
+        self.emit(&format!("local.set {}", rhs));
+        self.emit(&format!("local.set {}", lhs));
+        self.emit(&format!("local.get {}", rhs));
+        self.emit(&format!("{}.const -1", wasm_type));
+        self.emit(&format!("{}.eq", wasm_type));
+        self.open(&format!("if (result {})", wasm_type));
+        self.emit(&format!("{}.const 0", wasm_type));
+        self.emit(&format!("local.get {}", lhs));
+        self.emit(&format!("{}.sub", wasm_type));
+        self.otherwise();
+        self.emit(&format!("local.get {}", lhs));
+        self.emit(&format!("local.get {}", rhs));
+        self.emit(&instruction);
+        self.close();
+    }
+}
+
+/// What the pointer type `pointer` points to.
+fn pointee(pointer: &Type) -> Type {
+    match *pointer.unqualified() {
+        Type::Pointer(ref pointee) => (**pointee).clone(),
+        ref ty => unreachable!("the pointee of `{}`", ty),
+    }
+}
+
+/// Writes `bytes` as the contents of a wasm string: printable characters other
+/// than `"` and `\\` as they are, and any other byte as a hexadecimal escape.
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for &byte in bytes {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => escaped.push(byte as char),
+            _ => write!(escaped, "\\{:02x}", byte).unwrap(),
+        }
+    }
+    escaped
+}
+
+/// The constructs in `program` that can't be generated in wasm, as errors.
+pub fn check(program: &Program) -> Vec<SemanticError> {
+    let variadic = program.items.iter().filter_map(|item| match *item {
+        TopLevel::Function(ref function) if function.variadic => Some(function.name.clone()),
+        _ => None,
+    }).collect();
+    let mut unsupported = Unsupported { variadic, placed: HashSet::new(), errors: Vec::new() };
+    unsupported.visit_program(program);
+    unsupported.errors
+}
+
+/// Generates the WebAssembly module for `program`, whose `analysis` and
+/// `check` have found no errors and whose static objects `statics` has
+/// labelled.
+pub fn generate(program: &Program, analysis: &Analysis, statics: &StaticsBuilder, options: &Options) -> String {
+    let mut address_taken = AddressTaken { resolutions: &analysis.resolutions, symbols: HashSet::new() };
+    address_taken.visit_program(program);
+    let mut strings = Strings(Vec::new());
+    strings.visit_program(program);
+
+    let mut generator = Generator {
+        ast: &program.ast,
+        items: &program.items,
+        analysis,
+        layouts: Layouts::new(program),
+        options,
+        statics: statics.labels.clone(),
+        addresses: HashMap::new(),
+        strings: Vec::new(),
+        address_taken: address_taken.symbols,
+        defined: program.items
+            .iter()
+            .filter_map(|item| match *item {
+                TopLevel::Function(ref function) if function.body.is_some() => Some(function.name.as_str()),
+                _ => None,
+            })
+            .collect(),
+        imports: Vec::new(),
+        locals: Vec::new(),
+        code: String::new(),
+        depth: 0,
+        homes: HashMap::new(),
+        names: HashMap::new(),
+        frame_size: 0,
+        temps: 0,
+        labels: 0,
+        breaks: Vec::new(),
+        continues: Vec::new(),
+        returns: Type::Int,
+    };
+
+    // The static objects, then the strings, are placed from `DATA_START` up.
+    let mut end = DATA_START;
+    for object in &statics.objects {
+        let ty = &program.ast[object.declaration].ty;
+        let address = round_up(end, generator.layouts.alignment(ty));
+        generator.addresses.insert(object.label.clone(), address);
+        end = address + generator.layouts.size_of(ty);
+    }
+    for bytes in strings.0 {
+        let length = bytes.len() as i64 + 1;
+        generator.strings.push((bytes, end));
+        end += length;
+    }
+    let pages = round_up(end + STACK_SIZE, PAGE_SIZE) / PAGE_SIZE;
+
+    let mut data = Vec::new();
+    for object in &statics.objects {
+        let ty = &program.ast[object.declaration].ty;
+        let mut bytes = vec![0; generator.layouts.size_of(ty) as usize];
+        generator.static_value(ty, &analysis.statics[&object.declaration], &mut bytes, 0);
+        if bytes.iter().any(|&byte| byte != 0) {
+            data.push(format!("  (data (i32.const {}) \"{}\")\n", generator.addresses[&object.label], escape(&bytes)));
+        }
+    }
+    for (bytes, address) in &generator.strings {
+        data.push(format!("  (data (i32.const {}) \"{}\\00\")\n", address, escape(bytes)));
+    }
+
+    let mut functions = Vec::new();
+    for (index, item) in program.items.iter().enumerate() {
+        if let TopLevel::Function(ref function) = *item {
+            if let Some(ref body) = function.body {
+                functions.push(generator.function(index, function, body));
+            }
+        }
+    }
+
+    let mut module = "(module\n".to_owned();
+    for (_, import) in &generator.imports {
+        writeln!(module, "  {}", import).unwrap();
+    }
+    writeln!(module, "  (memory (export \"memory\") {})", pages).unwrap();
+    writeln!(module, "  (global $.sp (mut i32) (i32.const {}))", pages * PAGE_SIZE).unwrap();
+    module.extend(data);
+    module.extend(functions);
+    module.push_str(")\n");
+    module
+}
+
+#[cfg(test)]
+mod test {
+    use codegen::{generate_with, Format, Options};
+    use semantic::SemanticError;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn generate_wat(source: &str) -> String {
+        let options = Options { format: Format::Wat, ..Options::default() };
+        generate_with(&parse_program(&lex_str(source).unwrap()).unwrap(), &options)
+    }
+
+    #[test]
+    fn scalars_are_locals() {
+        let source = "int add(int a, int b) { int c = a + b; return c; }\nint main() { return add(1, 2); }";
+        assert_eq!(generate_wat(source), r#"(module
+  (memory (export "memory") 2)
+  (global $.sp (mut i32) (i32.const 131072))
+  (func $add (export "add") (param $a i32) (param $b i32) (result i32)
+    (local $c i32)
+    local.get $a
+    local.get $b
+    i32.add
+    local.set $c
+    local.get $c
+    return
+    i32.const 0
+  )
+  (func $main (export "main") (result i32)
+    i32.const 1
+    i32.const 2
+    call $add
+    return
+    i32.const 0
+  )
+)
+"#);
+    }
+
+    #[test]
+    fn control_flow_is_structured() {
+        let source = "int main(int n) { int i = 0; while (i < n && i != 3) { if (!i) continue; i++; } return i; }";
+        assert_eq!(generate_wat(source), r#"(module
+  (memory (export "memory") 2)
+  (global $.sp (mut i32) (i32.const 131072))
+  (func $main (export "main") (param $n i32) (result i32)
+    (local $i i32)
+    i32.const 0
+    local.set $i
+    block $while.end1
+      loop $while.cond2
+        local.get $i
+        local.get $n
+        i32.lt_s
+        if (result i32)
+          local.get $i
+          i32.const 3
+          i32.ne
+          i32.const 0
+          i32.ne
+        else
+          i32.const 0
+        end
+        i32.eqz
+        br_if $while.end1
+        local.get $i
+        i32.eqz
+        if
+          br $while.cond2
+        end
+        local.get $i
+        local.get $i
+        i32.const 1
+        i32.add
+        local.set $i
+        drop
+        br $while.cond2
+      end
+    end
+    local.get $i
+    return
+    i32.const 0
+  )
+)
+"#);
+    }
+
+    #[test]
+    fn memory_holds_frames_and_statics() {
+        let source = "int g = 5;\nint f(int *p) { return *p + g; }\n\
+                      int main() { int x = 1; char s[3] = \"a\"; long l = x; x = f(&x); return s[0] + l + x; }";
+        assert_eq!(generate_wat(source), r#"(module
+  (memory (export "memory") 2)
+  (global $.sp (mut i32) (i32.const 131072))
+  (data (i32.const 16) "\05\00\00\00")
+  (data (i32.const 20) "a\00")
+  (func $f (export "f") (param $p i32) (result i32)
+    local.get $p
+    i32.load
+    i32.const 16
+    i32.load
+    i32.add
+    return
+    i32.const 0
+  )
+  (func $main (export "main") (result i32)
+    (local $.fp i32)
+    (local $l i64)
+    (local $.t0 i32)
+    global.get $.sp
+    i32.const 16
+    i32.sub
+    local.tee $.fp
+    global.set $.sp
+    local.get $.fp
+    i32.const 1
+    i32.store
+    local.get $.fp
+    i32.const 4
+    i32.add
+    i32.const 20
+    i32.const 2
+    memory.copy
+    local.get $.fp
+    i32.const 6
+    i32.add
+    i32.const 0
+    i32.const 1
+    memory.fill
+    local.get $.fp
+    i32.load
+    i64.extend_i32_s
+    local.set $l
+    local.get $.fp
+    local.get $.fp
+    call $f
+    local.tee $.t0
+    i32.store
+    local.get $.t0
+    drop
+    local.get $.fp
+    i32.const 4
+    i32.add
+    i32.const 0
+    i32.add
+    i32.load8_s
+    i64.extend_i32_s
+    local.get $l
+    i64.add
+    local.get $.fp
+    i32.load
+    i64.extend_i32_s
+    i64.add
+    i32.wrap_i64
+    local.get $.fp
+    i32.const 16
+    i32.add
+    global.set $.sp
+    return
+    i32.const 0
+    local.get $.fp
+    i32.const 16
+    i32.add
+    global.set $.sp
+  )
+)
+"#);
+    }
+
+    #[test]
+    fn switches_branch_out_of_nested_blocks() {
+        let wat = generate_wat("int main(int x) { switch (x) { case 1: x = 2; break; case 7: return 3; } return x; }");
+        assert!(wat.contains("    block $sw.end1\n      block $sw.case3\n        block $sw.case2\n          local.get $x\n"), "{}", wat);
+        assert!(wat.contains("          br_if $sw.case3\n          br $sw.end1\n        end\n        i32.const 2\n"), "{}", wat);
+
+        let wat = generate_wat("int main(int x) { switch (x) { case 1: case 2: x = 4; case 3: case 5: return x; } return 0; }");
+        assert!(wat.contains("          i32.const 1\n          i32.sub\n          br_table $sw.case2 $sw.case2 $sw.case3 $sw.end1 $sw.case3 $sw.end1\n"), "{}", wat);
+    }
+
+    #[test]
+    fn undefined_functions_are_imported() {
+        let wat = generate_wat("int puts(const char *s);\nint main() { puts(\"hi\"); return f(1, (long)2); }");
+        assert!(wat.starts_with("(module\n  (import \"env\" \"puts\" (func $puts (param i32) (result i32)))\n  \
+                                 (import \"env\" \"f\" (func $f (param i32) (param i64) (result i32)))\n"), "{}", wat);
+    }
+
+    #[test]
+    fn checked_division_wraps_around() {
+        let options = Options { format: Format::Wat, checked_arithmetic: true, ..Options::default() };
+        let wat = generate_with(&parse_program(&lex_str("int main(int x) { return 7 / x; }").unwrap()).unwrap(), &options);
+        assert!(wat.contains("    i32.const -1\n    i32.eq\n    if (result i32)\n      i32.const 0\n      local.get $.t0\n      i32.sub\n    else\n"), "{}", wat);
+    }
+
+    #[test]
+    fn what_wasm_cant_have_is_checked() {
+        let check = |source: &str| -> Vec<(&str, usize)> {
+            super::check(&parse_program(&lex_str(source).unwrap()).unwrap()).into_iter().map(|error| match error {
+                SemanticError::Unsupported { construct, line, .. } => (construct, line),
+                error => panic!("{:?}", error),
+            }).collect()
+        };
+        assert_eq!(check("int printf(const char *format, ...);\nint main() { switch (1) { case 0: default: ; } return 0; }"), vec![]);
+        assert_eq!(check("int sum(int count, ...) { return count; }\nint main() {\n    return sum(1, 2);\n}"), vec![
+            ("a variadic function in WebAssembly", 1),
+            ("a call to a variadic function in WebAssembly", 3),
+        ]);
+        assert_eq!(check("int main(int x) {\n    switch (x) { case 0: if (x) { case 1: x++; } }\n    return x;\n}"), vec![
+            ("a label inside another statement in a `switch` in WebAssembly", 2),
+        ]);
+    }
+}
@@ -0,0 +1,322 @@
+//! The x86-64 instructions codegen emits, and the two syntaxes they can be
+//! written in: AT&T, which the GNU assembler expects by default, and Intel,
+//! which it takes after `.intel_syntax noprefix`.
+//!
+//! AT&T syntax writes the source operand before the destination, marks
+//! registers with `%` and immediates with `$`, and gives the size of the
+//! operands as a suffix of the mnemonic: `movl $1, -4(%rbp)`. Intel syntax
+//! writes the destination first, without the sigils, and gives the size on a
+//! memory operand instead: `mov DWORD PTR [rbp-4], 1`.
+
+/// How instructions are written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Syntax {
+    #[default]
+    Att,
+    /// Labels are written bare, so a symbol can't have the name of a register.
+    Intel,
+}
+
+/// The size of an operand.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Size {
+    Byte,
+    Long,
+    Quad,
+}
+
+impl Size {
+    /// The size of an operand of `bytes` bytes, which must be 1, 4, or 8.
+    pub fn of(bytes: i64) -> Size {
+        match bytes {
+            1 => Size::Byte,
+            4 => Size::Long,
+            8 => Size::Quad,
+            _ => unreachable!("an operand of {} bytes", bytes),
+        }
+    }
+
+    fn suffix(self) -> char {
+        match self {
+            Size::Byte => 'b',
+            Size::Long => 'l',
+            Size::Quad => 'q',
+        }
+    }
+
+    /// The word Intel syntax gives the size of a memory operand with.
+    fn pointer(self) -> &'static str {
+        match self {
+            Size::Byte => "BYTE",
+            Size::Long => "DWORD",
+            Size::Quad => "QWORD",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Register {
+    Ax,
+    Bx,
+    Cx,
+    Dx,
+    Si,
+    Di,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+    Bp,
+    Sp,
+}
+
+impl Register {
+    /// The name of the part of the register that is `size` big.
+    fn name(self, size: Size) -> &'static str {
+        let (quad, long, byte) = match self {
+            Register::Ax => ("rax", "eax", "al"),
+            Register::Bx => ("rbx", "ebx", "bl"),
+            Register::Cx => ("rcx", "ecx", "cl"),
+            Register::Dx => ("rdx", "edx", "dl"),
+            Register::Si => ("rsi", "esi", "sil"),
+            Register::Di => ("rdi", "edi", "dil"),
+            Register::R8 => ("r8", "r8d", "r8b"),
+            Register::R9 => ("r9", "r9d", "r9b"),
+            Register::R10 => ("r10", "r10d", "r10b"),
+            Register::R11 => ("r11", "r11d", "r11b"),
+            Register::R12 => ("r12", "r12d", "r12b"),
+            Register::R13 => ("r13", "r13d", "r13b"),
+            Register::R14 => ("r14", "r14d", "r14b"),
+            Register::R15 => ("r15", "r15d", "r15b"),
+            Register::Bp => ("rbp", "ebp", "bpl"),
+            Register::Sp => ("rsp", "esp", "spl"),
+        };
+        match size {
+            Size::Byte => byte,
+            Size::Long => long,
+            Size::Quad => quad,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operand {
+    Register(Register, Size),
+    Immediate(i64),
+    /// The memory `offset` bytes past the address in `base`, and past `index`
+    /// times the scale if there is an index.
+    Memory { base: Register, offset: i64, index: Option<(Register, i64)> },
+    /// The memory at `label`, addressed relative to the instruction pointer.
+    Label(String),
+}
+
+impl Operand {
+    /// The memory at the address in `base`.
+    pub fn at(base: Register) -> Operand {
+        Operand::Memory { base, offset: 0, index: None }
+    }
+
+    /// The memory `offset` bytes from where `%rbp` points.
+    pub fn frame(offset: i64) -> Operand {
+        Operand::Memory { base: Register::Bp, offset, index: None }
+    }
+
+    fn att(&self) -> String {
+        match *self {
+            Operand::Register(register, size) => format!("%{}", register.name(size)),
+            Operand::Immediate(value) => format!("${}", value),
+            Operand::Memory { base, offset, index } => {
+                let offset = if offset == 0 { String::new() } else { offset.to_string() };
+                match index {
+                    Some((index, scale)) => format!("{}(%{},%{},{})", offset, base.name(Size::Quad), index.name(Size::Quad), scale),
+                    None => format!("{}(%{})", offset, base.name(Size::Quad)),
+                }
+            },
+            Operand::Label(ref label) => format!("{}(%rip)", label),
+        }
+    }
+
+    /// Writes the operand in Intel syntax, as one of `size` if it is in memory,
+    /// or as a bare address without one.
+    fn intel(&self, size: Option<Size>) -> String {
+        let pointer = match (self, size) {
+            (&Operand::Memory { .. }, Some(size)) | (&Operand::Label(_), Some(size)) => format!("{} PTR ", size.pointer()),
+            _ => String::new(),
+        };
+        match *self {
+            Operand::Register(register, size) => register.name(size).to_owned(),
+            Operand::Immediate(value) => value.to_string(),
+            Operand::Memory { base, offset, index } => {
+                let mut address = base.name(Size::Quad).to_owned();
+                if let Some((index, scale)) = index {
+                    address += &format!("+{}*{}", index.name(Size::Quad), scale);
+                }
+                if offset != 0 {
+                    address += &format!("{:+}", offset);
+                }
+                format!("{}[{}]", pointer, address)
+            },
+            Operand::Label(ref label) => format!("{}{}[rip]", pointer, label),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction {
+    /// An instruction on a source and a destination of one size, such as `mov`,
+    /// `add`, or `cmp`. The source of a shift is `%cl` or an immediate.
+    Binary(&'static str, Size, Operand, Operand),
+    /// An instruction on one operand, such as `neg`, `idiv`, or `push`.
+    Unary(&'static str, Size, Operand),
+    /// Extends `source`, which is `from` big, into the register `destination`
+    /// is part of, which is `to` big: by its sign if `signed` is set, and with
+    /// zeros otherwise.
+    Extend { signed: bool, from: Size, to: Size, source: Operand, destination: Register },
+    /// Loads the address of `source`, which is in memory, into the 64-bit
+    /// `destination`.
+    Lea(Operand, Register),
+    /// Extends `%eax` into `%edx`, or `%rax` into `%rdx`, by its sign, for a
+    /// division.
+    SignExtendAx(Size),
+    /// Sets the low byte of a register to 1 if the condition (`e`, `l`, and so
+    /// on) holds, and to 0 otherwise.
+    Set(&'static str, Register),
+    /// A jump to a label, unconditional with `jmp` and conditional with any
+    /// other mnemonic.
+    Jump(&'static str, String),
+    /// A jump to the address stored in memory.
+    JumpIndirect(Operand),
+    Call(String),
+    Ret,
+    /// Loads a 64-bit immediate, which other instructions can't take, into a
+    /// 64-bit register.
+    MoveAbsolute(i64, Register),
+    /// Places a label, which is kept among the instructions so that jumps can
+    /// be matched with where they go.
+    Label(String),
+    /// A directive to the assembler among the instructions, such as `.loc`,
+    /// written as it is.
+    Directive(String),
+}
+
+impl Instruction {
+    /// Whether the instruction reads the flags the last comparison or
+    /// arithmetic set.
+    pub fn reads_flags(&self) -> bool {
+        match *self {
+            Instruction::Jump(mnemonic, _) => mnemonic != "jmp",
+            Instruction::Set(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Writes the instruction in `syntax`, without indentation.
+    pub fn render(&self, syntax: Syntax) -> String {
+        match syntax {
+            Syntax::Att => self.att(),
+            Syntax::Intel => self.intel(),
+        }
+    }
+
+    fn att(&self) -> String {
+        match *self {
+            Instruction::Binary(mnemonic, size, ref source, ref destination) =>
+                format!("{}{} {}, {}", mnemonic, size.suffix(), source.att(), destination.att()),
+            Instruction::Unary(mnemonic, size, ref operand) => format!("{}{} {}", mnemonic, size.suffix(), operand.att()),
+            Instruction::Extend { signed, from, to, ref source, destination } => format!(
+                "mov{}{}{} {}, {}",
+                if signed { 's' } else { 'z' }, from.suffix(), to.suffix(), source.att(), Operand::Register(destination, to).att(),
+            ),
+            Instruction::Lea(ref source, destination) => format!("leaq {}, %{}", source.att(), destination.name(Size::Quad)),
+            Instruction::SignExtendAx(Size::Quad) => "cqto".to_owned(),
+            Instruction::SignExtendAx(_) => "cltd".to_owned(),
+            Instruction::Set(condition, register) => format!("set{} %{}", condition, register.name(Size::Byte)),
+            Instruction::Jump(mnemonic, ref label) => format!("{} {}", mnemonic, label),
+            Instruction::JumpIndirect(ref operand) => format!("jmp *{}", operand.att()),
+            Instruction::Call(ref label) => format!("call {}", label),
+            Instruction::Ret => "ret".to_owned(),
+            Instruction::MoveAbsolute(value, register) => format!("movabsq ${}, %{}", value, register.name(Size::Quad)),
+            Instruction::Label(ref label) => format!("{}:", label),
+            Instruction::Directive(ref directive) => directive.clone(),
+        }
+    }
+
+    fn intel(&self) -> String {
+        match *self {
+            Instruction::Binary(mnemonic, size, ref source, ref destination) =>
+                format!("{} {}, {}", mnemonic, destination.intel(Some(size)), source.intel(Some(size))),
+            Instruction::Unary(mnemonic, size, ref operand) => format!("{} {}", mnemonic, operand.intel(Some(size))),
+            // Only `movsxd` extends a 32-bit value.
+            Instruction::Extend { signed: true, from: Size::Long, to, ref source, destination } =>
+                format!("movsxd {}, {}", destination.name(to), source.intel(Some(Size::Long))),
+            Instruction::Extend { signed, from, to, ref source, destination } => format!(
+                "mov{}x {}, {}",
+                if signed { 's' } else { 'z' }, destination.name(to), source.intel(Some(from)),
+            ),
+            Instruction::Lea(ref source, destination) => format!("lea {}, {}", destination.name(Size::Quad), source.intel(None)),
+            Instruction::SignExtendAx(Size::Quad) => "cqo".to_owned(),
+            Instruction::SignExtendAx(_) => "cdq".to_owned(),
+            Instruction::Set(condition, register) => format!("set{} {}", condition, register.name(Size::Byte)),
+            Instruction::Jump(mnemonic, ref label) => format!("{} {}", mnemonic, label),
+            Instruction::JumpIndirect(ref operand) => format!("jmp {}", operand.intel(Some(Size::Quad))),
+            Instruction::Call(ref label) => format!("call {}", label),
+            Instruction::Ret => "ret".to_owned(),
+            Instruction::MoveAbsolute(value, register) => format!("movabs {}, {}", register.name(Size::Quad), value),
+            Instruction::Label(ref label) => format!("{}:", label),
+            Instruction::Directive(ref directive) => directive.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn both(instruction: Instruction) -> (String, String) {
+        (instruction.render(Syntax::Att), instruction.render(Syntax::Intel))
+    }
+
+    #[test]
+    fn operands_are_reversed_and_sized() {
+        let store = Instruction::Binary("mov", Size::Long, Operand::Immediate(1), Operand::frame(-4));
+        assert_eq!(both(store), ("movl $1, -4(%rbp)".to_owned(), "mov DWORD PTR [rbp-4], 1".to_owned()));
+        let add = Instruction::Binary("add", Size::Quad, Operand::Register(Register::Cx, Size::Quad), Operand::Register(Register::Ax, Size::Quad));
+        assert_eq!(both(add), ("addq %rcx, %rax".to_owned(), "add rax, rcx".to_owned()));
+        let shift = Instruction::Binary("sar", Size::Long, Operand::Register(Register::Cx, Size::Byte), Operand::Register(Register::Ax, Size::Long));
+        assert_eq!(both(shift), ("sarl %cl, %eax".to_owned(), "sar eax, cl".to_owned()));
+        let push = Instruction::Unary("push", Size::Quad, Operand::Register(Register::Bp, Size::Quad));
+        assert_eq!(both(push), ("pushq %rbp".to_owned(), "push rbp".to_owned()));
+    }
+
+    #[test]
+    fn memory_operands() {
+        let load = Instruction::Binary("mov", Size::Quad, Operand::Label("count".to_owned()), Operand::Register(Register::Ax, Size::Quad));
+        assert_eq!(both(load), ("movq count(%rip), %rax".to_owned(), "mov rax, QWORD PTR count[rip]".to_owned()));
+        let store = Instruction::Binary("mov", Size::Byte, Operand::Register(Register::Ax, Size::Byte), Operand::at(Register::Si));
+        assert_eq!(both(store), ("movb %al, (%rsi)".to_owned(), "mov BYTE PTR [rsi], al".to_owned()));
+        let table = Operand::Memory { base: Register::Cx, offset: 0, index: Some((Register::Ax, 8)) };
+        assert_eq!(both(Instruction::JumpIndirect(table)), ("jmp *(%rcx,%rax,8)".to_owned(), "jmp QWORD PTR [rcx+rax*8]".to_owned()));
+        let parameter = Instruction::Lea(Operand::frame(16), Register::Ax);
+        assert_eq!(both(parameter), ("leaq 16(%rbp), %rax".to_owned(), "lea rax, [rbp+16]".to_owned()));
+        let string = Instruction::Lea(Operand::Label(".Lstr0".to_owned()), Register::Ax);
+        assert_eq!(both(string), ("leaq .Lstr0(%rip), %rax".to_owned(), "lea rax, .Lstr0[rip]".to_owned()));
+    }
+
+    #[test]
+    fn extensions_and_special_forms() {
+        let byte = Instruction::Extend { signed: true, from: Size::Byte, to: Size::Long, source: Operand::frame(-1), destination: Register::Ax };
+        assert_eq!(both(byte), ("movsbl -1(%rbp), %eax".to_owned(), "movsx eax, BYTE PTR [rbp-1]".to_owned()));
+        let flag = Instruction::Extend { signed: false, from: Size::Byte, to: Size::Long, source: Operand::Register(Register::Ax, Size::Byte), destination: Register::Ax };
+        assert_eq!(both(flag), ("movzbl %al, %eax".to_owned(), "movzx eax, al".to_owned()));
+        let long = Instruction::Extend { signed: true, from: Size::Long, to: Size::Quad, source: Operand::Register(Register::Ax, Size::Long), destination: Register::Ax };
+        assert_eq!(both(long), ("movslq %eax, %rax".to_owned(), "movsxd rax, eax".to_owned()));
+        assert_eq!(both(Instruction::SignExtendAx(Size::Long)), ("cltd".to_owned(), "cdq".to_owned()));
+        assert_eq!(both(Instruction::SignExtendAx(Size::Quad)), ("cqto".to_owned(), "cqo".to_owned()));
+        assert_eq!(both(Instruction::Set("ne", Register::Ax)), ("setne %al".to_owned(), "setne al".to_owned()));
+        assert_eq!(both(Instruction::MoveAbsolute(1 << 40, Register::Cx)), ("movabsq $1099511627776, %rcx".to_owned(), "movabs rcx, 1099511627776".to_owned()));
+    }
+}
@@ -0,0 +1,867 @@
+//! The x86-64 backend, for the System V AMD64 ABI.
+//!
+//! The accumulator is `%eax`, or all of `%rax` for a 64-bit value, and the
+//! scratch register is `%ecx` or `%rcx`. Temporaries are kept whole, 8 bytes at
+//! a time, in `%r10` and `%r11`, which a call may change, or in `%rbx` and
+//! `%r12` to `%r15`, which it preserves. The frame pointer is `%rbp`, and an
+//! lvalue whose address was saved is restored to `%rsi` to be stored through.
+//!
+//! The first six arguments are passed in `%rdi`, `%rsi`, `%rdx`, `%rcx`,
+//! `%r8`, and `%r9`, and the rest on the stack in 8-byte slots, above the
+//! return address. They are moved and pushed there from their temporaries just
+//! before the call, and the caller removes them after it.
+//!
+//! The ABI also requires `%rsp` to be a multiple of 16 at every `call`. It is
+//! one after the prologue, since the frame size is rounded up to keep it so, and
+//! nothing is pushed but stack arguments. Where an odd number of them would
+//! leave it misaligned, 8 bytes of padding are reserved before they are pushed,
+//! and released with them after the call.
+//!
+//! Without the frame pointer, the prologue reserves 8 bytes more in place of
+//! the pushed `%rbp`, so `%rsp` ends up where it would have with it, and
+//! everything in the frame is addressed from `%rsp` instead, at the offset it
+//! would have had from `%rbp` plus the size of the frame. While stack
+//! arguments are pushed for a call, that is moved by as much as they take.
+//!
+//! With the Windows calling convention, the first four arguments are passed in
+//! `%rcx`, `%rdx`, `%r8`, and `%r9` instead. The caller reserves 32 bytes of
+//! shadow space below the stack arguments for the callee to save them in, and
+//! removes it with them after the call. `%rsi` and `%rdi` are among the
+//! registers a function must preserve for its caller: `%rdi` can hold a
+//! temporary like `%rbx`, while `%rsi` is always saved below the frame and
+//! restored on return. A frame bigger than a page is reserved with
+//! `___chkstk_ms` first, which touches each page of it in order, as Windows
+//! only grows the stack one page at a time. `long` stays 64 bits, where it is
+//! 32 on Windows, so only functions without `long` parameters or results can
+//! be called from or by other compilers' code.
+//!
+//! Division is done with `div` and `idiv`, which raise `SIGFPE` on a zero
+//! divisor, and `idiv` on the most negative value divided by -1 too, so either
+//! kills the program without a message. A checked division tests for both.
+//!
+//! The instructions of a function are kept until the whole of it is generated,
+//! and only written when the assembly is next asked for. The passes over them
+//! run in between, when the generator asks for them to be; at `OptLevel::O1`
+//! and above, `peephole` rewrites them.
+
+mod asm;
+pub mod peephole;
+
+use std::mem;
+use ast::{BinaryOp, UnaryOp};
+use super::{position_directive, Assembly, CallingConvention, Condition, Home, Location, Machine, Options, Parameters, Registers, Source};
+use super::passes::{self, PassManager, Timings};
+use self::asm::{Operand, Register, Size};
+
+pub use self::asm::{Instruction, Syntax};
+
+/// The registers the first six integer arguments are passed in by the System V
+/// ABI.
+const SYSTEM_V_ARGUMENTS: [Register; 6] = [Register::Di, Register::Si, Register::Dx, Register::Cx, Register::R8, Register::R9];
+
+/// The registers the first four are passed in by the Windows convention.
+const WINDOWS_ARGUMENTS: [Register; 4] = [Register::Cx, Register::Dx, Register::R8, Register::R9];
+
+/// The registers temporaries are kept in by the System V ABI: first the ones a
+/// call may change, then the ones it preserves.
+const SYSTEM_V_TEMPORARIES: [Register; 7] =
+    [Register::R10, Register::R11, Register::Bx, Register::R12, Register::R13, Register::R14, Register::R15];
+
+/// The registers they are kept in by the Windows convention, which preserves
+/// `%rdi` too.
+const WINDOWS_TEMPORARIES: [Register; 8] =
+    [Register::R10, Register::R11, Register::Bx, Register::Di, Register::R12, Register::R13, Register::R14, Register::R15];
+
+/// How many of the registers temporaries are kept in a call may change, by
+/// either convention.
+const CALLER_SAVED: usize = 2;
+
+/// The bytes a Windows caller reserves just above the return address for the
+/// callee to save its register arguments in.
+const SHADOW_SPACE: i64 = 32;
+
+/// The most a Windows function can move `%rsp` down by without touching the
+/// stack in between.
+const PAGE_SIZE: i64 = 4096;
+
+/// `%eax` or `%rax`, as `size` says.
+fn ax(size: Size) -> Operand {
+    Operand::Register(Register::Ax, size)
+}
+
+/// `%ecx` or `%rcx`.
+fn cx(size: Size) -> Operand {
+    Operand::Register(Register::Cx, size)
+}
+
+/// `%edx` or `%rdx`.
+fn dx(size: Size) -> Operand {
+    Operand::Register(Register::Dx, size)
+}
+
+/// `%rsp`.
+fn sp() -> Operand {
+    Operand::Register(Register::Sp, Size::Quad)
+}
+
+/// `value` as an immediate operand of an instruction of `size`, if it can be
+/// one. 64-bit instructions sign-extend a 32-bit immediate, while 32-bit ones
+/// take any 32 bits, so an `unsigned` value is written as the `int` with the
+/// same bits.
+fn immediate(value: i64, size: Size) -> Option<Operand> {
+    match size {
+        Size::Quad if value == i64::from(value as i32) => Some(Operand::Immediate(value)),
+        Size::Quad => None,
+        _ => Some(Operand::Immediate(i64::from(value as i32))),
+    }
+}
+
+/// The suffix of the conditional instructions that test `condition`.
+fn condition_code(condition: Condition) -> &'static str {
+    match condition {
+        Condition::Equal => "e",
+        Condition::NotEqual => "ne",
+        Condition::Less => "l",
+        Condition::LessEqual => "le",
+        Condition::Greater => "g",
+        Condition::GreaterEqual => "ge",
+        Condition::Below => "b",
+        Condition::BelowEqual => "be",
+        Condition::Above => "a",
+        Condition::AboveEqual => "ae",
+    }
+}
+
+pub struct X86_64 {
+    assembly: Assembly,
+    syntax: Syntax,
+    convention: CallingConvention,
+    /// The size of the frame of the function being generated, below which a
+    /// Windows function saves `%rsi`.
+    frame_size: i64,
+    /// The preserved registers the function being generated saved, and where.
+    saved: Vec<(usize, i64)>,
+    /// The instructions not yet written, and the passes over them.
+    code: Vec<Instruction>,
+    passes: PassManager<Vec<Instruction>>,
+    /// Whether each function keeps `%rbp` pointing at its frame, and if not,
+    /// how far `%rsp` has been moved down since the prologue.
+    frame_pointer: bool,
+    pushed: i64,
+}
+
+impl X86_64 {
+    pub fn new(assembly: Assembly, syntax: Syntax, convention: CallingConvention, frame_pointer: bool) -> X86_64 {
+        let mut machine = X86_64 {
+            assembly,
+            syntax,
+            convention,
+            frame_size: 0,
+            saved: Vec::new(),
+            code: Vec::new(),
+            passes: passes::assembly(),
+            frame_pointer,
+            pushed: 0,
+        };
+        if syntax == Syntax::Intel {
+            machine.assembly.directive(".intel_syntax noprefix");
+        }
+        machine
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.code.push(instruction);
+    }
+
+    /// Writes the instructions not yet written, each on a line of its own, in
+    /// the syntax asked for.
+    fn flush(&mut self) {
+        for instruction in mem::take(&mut self.code) {
+            match instruction {
+                Instruction::Label(ref label) => self.assembly.label(label),
+                instruction => self.assembly.directive(&instruction.render(self.syntax)),
+            }
+        }
+    }
+
+    /// The registers integer arguments are passed in.
+    fn arguments(&self) -> &'static [Register] {
+        match self.convention {
+            CallingConvention::SystemV => &SYSTEM_V_ARGUMENTS,
+            CallingConvention::Windows => &WINDOWS_ARGUMENTS,
+        }
+    }
+
+    /// The registers temporaries are kept in.
+    fn temporaries(&self) -> &'static [Register] {
+        match self.convention {
+            CallingConvention::SystemV => &SYSTEM_V_TEMPORARIES,
+            CallingConvention::Windows => &WINDOWS_TEMPORARIES,
+        }
+    }
+
+    /// The operand that is all 64 bits of `home`.
+    fn home(&self, home: Home) -> Operand {
+        match home {
+            Home::Register(index) => Operand::Register(self.temporaries()[index], Size::Quad),
+            Home::Slot(offset) => self.frame(offset),
+        }
+    }
+
+    /// What the prologue moves `%rsp` down by after saving `%rbp`, or where
+    /// it would have saved it: 16 bytes more than the frame on Windows, to
+    /// save `%rsi` in and keep `%rsp` aligned.
+    fn reserved(&self) -> i64 {
+        match self.convention {
+            CallingConvention::SystemV => self.frame_size,
+            CallingConvention::Windows => self.frame_size + 16,
+        }
+    }
+
+    /// The memory `offset` bytes from where `%rbp` points, or would point.
+    fn frame(&self, offset: i64) -> Operand {
+        if self.frame_pointer {
+            Operand::frame(offset)
+        } else {
+            Operand::Memory { base: Register::Sp, offset: offset + self.reserved() + self.pushed, index: None }
+        }
+    }
+
+    /// Moves `%rsp` down by `bytes`, or up for a negative number.
+    fn push_space(&mut self, bytes: i64) {
+        let (operator, amount) = if bytes < 0 { ("add", -bytes) } else { ("sub", bytes) };
+        self.emit(Instruction::Binary(operator, Size::Quad, Operand::Immediate(amount), sp()));
+        self.pushed += bytes;
+    }
+
+    /// Where a Windows function saves `%rsi`.
+    fn saved_rsi(&self) -> Operand {
+        self.frame(-self.frame_size - 8)
+    }
+
+    fn operand(&self, location: &Location) -> Operand {
+        match *location {
+            Location::Frame(offset) => self.frame(offset),
+            Location::Label(ref label) => Operand::Label(label.clone()),
+            Location::Accumulator => Operand::at(Register::Ax),
+            Location::Restored => Operand::at(Register::Si),
+        }
+    }
+}
+
+impl Machine for X86_64 {
+    fn assembly(&mut self) -> &mut Assembly {
+        self.flush();
+        &mut self.assembly
+    }
+
+    fn argument_registers(&self) -> usize {
+        self.arguments().len()
+    }
+
+    fn stack_arguments(&self, sizes: &[i64]) -> Vec<i64> {
+        let shadow = if self.convention == CallingConvention::Windows { SHADOW_SPACE } else { 0 };
+        (0..sizes.len() as i64).map(|index| shadow + 8 * index).collect()
+    }
+
+    fn registers(&self) -> Registers {
+        Registers { caller_saved: CALLER_SAVED, callee_saved: self.temporaries().len() - CALLER_SAVED }
+    }
+
+    fn prologue(&mut self, frame_size: i64, saved: &[(usize, i64)]) {
+        let bp = Operand::Register(Register::Bp, Size::Quad);
+        self.frame_size = frame_size;
+        self.saved = saved.to_vec();
+        self.pushed = 0;
+        let mut reserved = self.reserved();
+        if self.frame_pointer {
+            self.emit(Instruction::Unary("push", Size::Quad, bp.clone()));
+            self.emit(Instruction::Binary("mov", Size::Quad, sp(), bp));
+        } else {
+            reserved += 8;
+        }
+        if self.convention == CallingConvention::Windows && reserved > PAGE_SIZE {
+            self.emit(Instruction::Binary("mov", Size::Quad, Operand::Immediate(reserved), ax(Size::Quad)));
+            self.emit(Instruction::Call("___chkstk_ms".to_owned()));
+        }
+        if reserved > 0 {
+            self.emit(Instruction::Binary("sub", Size::Quad, Operand::Immediate(reserved), sp()));
+        }
+        if self.convention == CallingConvention::Windows {
+            let saved = self.saved_rsi();
+            self.emit(Instruction::Binary("mov", Size::Quad, Operand::Register(Register::Si, Size::Quad), saved));
+        }
+        for &(register, offset) in saved {
+            let register = self.home(Home::Register(register));
+            let slot = self.frame(offset);
+            self.emit(Instruction::Binary("mov", Size::Quad, register, slot));
+        }
+    }
+
+    fn epilogue(&mut self) {
+        let bp = Operand::Register(Register::Bp, Size::Quad);
+        for (register, offset) in self.saved.clone() {
+            let register = self.home(Home::Register(register));
+            let slot = self.frame(offset);
+            self.emit(Instruction::Binary("mov", Size::Quad, slot, register));
+        }
+        if self.convention == CallingConvention::Windows {
+            let saved = self.saved_rsi();
+            self.emit(Instruction::Binary("mov", Size::Quad, saved, Operand::Register(Register::Si, Size::Quad)));
+        }
+        if self.frame_pointer {
+            self.emit(Instruction::Binary("mov", Size::Quad, bp.clone(), sp()));
+            self.emit(Instruction::Unary("pop", Size::Quad, bp));
+        } else {
+            self.emit(Instruction::Binary("add", Size::Quad, Operand::Immediate(self.reserved() + 8), sp()));
+        }
+        self.emit(Instruction::Ret);
+    }
+
+    fn spill(&mut self, index: usize, size: i64, offset: i64) {
+        let size = Size::of(size);
+        let slot = self.frame(offset);
+        self.emit(Instruction::Binary("mov", size, Operand::Register(self.arguments()[index], size), slot));
+    }
+
+    fn constant(&mut self, value: i64, size: i64) {
+        let size = Size::of(size);
+        match immediate(value, size) {
+            Some(value) => self.emit(Instruction::Binary("mov", size, value, ax(size))),
+            None => self.emit(Instruction::MoveAbsolute(value, Register::Ax)),
+        }
+    }
+
+    fn scratch_constant(&mut self, value: i64, size: i64) {
+        let size = Size::of(size);
+        match immediate(value, size) {
+            Some(value) => self.emit(Instruction::Binary("mov", size, value, cx(size))),
+            None => self.emit(Instruction::MoveAbsolute(value, Register::Cx)),
+        }
+    }
+
+    fn load(&mut self, location: &Location, size: i64, signed: bool) {
+        let source = self.operand(location);
+        match size {
+            1 => self.emit(Instruction::Extend { signed, from: Size::Byte, to: Size::Long, source, destination: Register::Ax }),
+            _ => self.emit(Instruction::Binary("mov", Size::of(size), source, ax(Size::of(size)))),
+        }
+    }
+
+    fn store(&mut self, location: &Location, size: i64) {
+        let size = Size::of(size);
+        let destination = self.operand(location);
+        self.emit(Instruction::Binary("mov", size, ax(size), destination));
+    }
+
+    fn store_constant(&mut self, offset: i64, size: i64, value: i64) {
+        let slot = self.frame(offset);
+        self.emit(Instruction::Binary("mov", Size::of(size), Operand::Immediate(value), slot));
+    }
+
+    fn address(&mut self, location: &Location) {
+        let source = self.operand(location);
+        self.emit(Instruction::Lea(source, Register::Ax));
+    }
+
+    fn save(&mut self, home: Home) {
+        let home = self.home(home);
+        self.emit(Instruction::Binary("mov", Size::Quad, ax(Size::Quad), home));
+    }
+
+    fn restore(&mut self, home: Home) {
+        let home = self.home(home);
+        self.emit(Instruction::Binary("mov", Size::Quad, home, ax(Size::Quad)));
+    }
+
+    fn restore_address(&mut self, home: Home) {
+        let home = self.home(home);
+        self.emit(Instruction::Binary("mov", Size::Quad, home, Operand::Register(Register::Si, Size::Quad)));
+    }
+
+    fn move_to_scratch(&mut self, size: i64) {
+        let size = Size::of(size);
+        self.emit(Instruction::Binary("mov", size, ax(size), cx(size)));
+    }
+
+    fn restore_from_scratch(&mut self, size: i64) {
+        let size = Size::of(size);
+        self.emit(Instruction::Binary("mov", size, cx(size), ax(size)));
+    }
+
+    fn binary(&mut self, operator: BinaryOp, size: i64, signed: bool, source: Source) {
+        let size = Size::of(size);
+        let source = match source {
+            // The amount of a shift must be in `%cl`. Only the low bits of it
+            // are used, so the width of the right operand doesn't matter.
+            Source::Scratch if operator == BinaryOp::ShiftLeft || operator == BinaryOp::ShiftRight => cx(Size::Byte),
+            Source::Scratch => cx(size),
+            Source::Immediate(value) => Operand::Immediate(value),
+        };
+        let instruction = match operator {
+            BinaryOp::Add => "add",
+            BinaryOp::Subtract => "sub",
+            BinaryOp::Multiply => "imul",
+            BinaryOp::ShiftLeft => "shl",
+            BinaryOp::ShiftRight if signed => "sar",
+            BinaryOp::ShiftRight => "shr",
+            BinaryOp::BitwiseAnd => "and",
+            BinaryOp::BitwiseXor => "xor",
+            BinaryOp::BitwiseOr => "or",
+            operator => unreachable!("binary `{}` as one instruction", operator.symbol()),
+        };
+        self.emit(Instruction::Binary(instruction, size, source, ax(size)));
+    }
+
+    fn divide(&mut self, size: i64, signed: bool, remainder: bool, zero: Option<&str>) {
+        let size = Size::of(size);
+        let mut end = None;
+        if let Some(zero) = zero {
+            self.emit(Instruction::Binary("cmp", size, Operand::Immediate(0), cx(size)));
+            self.emit(Instruction::Jump("je", zero.to_owned()));
+            // Dividing by -1 only negates, and does it without trapping on the
+            // most negative value, whose negation wraps around to itself.
+            if signed {
+                let (divide, after) = (self.assembly.new_label("divide"), self.assembly.new_label("end"));
+                self.emit(Instruction::Binary("cmp", size, Operand::Immediate(-1), cx(size)));
+                self.emit(Instruction::Jump("jne", divide.clone()));
+                self.emit(Instruction::Unary("neg", size, ax(size)));
+                self.emit(Instruction::Binary("xor", size, dx(size), dx(size)));
+                self.emit(Instruction::Jump("jmp", after.clone()));
+                self.label(&divide);
+                end = Some(after);
+            }
+        }
+        // Division divides `%edx:%eax`, or `%rdx:%rax`, so the dividend is
+        // extended into `%edx` first: by its sign for `idiv`, and with zeros for
+        // `div`.
+        if signed {
+            self.emit(Instruction::SignExtendAx(size));
+            self.emit(Instruction::Unary("idiv", size, cx(size)));
+        } else {
+            self.emit(Instruction::Binary("xor", size, dx(size), dx(size)));
+            self.emit(Instruction::Unary("div", size, cx(size)));
+        }
+        if let Some(end) = end {
+            self.label(&end);
+        }
+        if remainder {
+            self.emit(Instruction::Binary("mov", size, dx(size), ax(size)));
+        }
+    }
+
+    fn divide_by_power(&mut self, size: i64, signed: bool, remainder: bool, shift: i64) {
+        let bits = size * 8;
+        let size = Size::of(size);
+        // Masks wider than 31 bits don't fit in an immediate, so the bits are
+        // shifted out instead.
+        let clear = |machine: &mut X86_64, high: bool, operand: Operand| if shift < 32 {
+            let mask = if high { -(1 << shift) } else { (1 << shift) - 1 };
+            machine.emit(Instruction::Binary("and", size, Operand::Immediate(mask), operand));
+        } else if high {
+            machine.emit(Instruction::Binary("sar", size, Operand::Immediate(shift), operand.clone()));
+            machine.emit(Instruction::Binary("shl", size, Operand::Immediate(shift), operand));
+        } else {
+            machine.emit(Instruction::Binary("shl", size, Operand::Immediate(bits - shift), operand.clone()));
+            machine.emit(Instruction::Binary("shr", size, Operand::Immediate(bits - shift), operand));
+        };
+        if !signed {
+            if remainder {
+                clear(self, false, ax(size));
+            } else {
+                self.emit(Instruction::Binary("shr", size, Operand::Immediate(shift), ax(size)));
+            }
+            return;
+        }
+        // Shifting rounds toward negative infinity and division toward zero, so
+        // a negative dividend is biased by one less than the divisor first:
+        // its sign copied to every bit, of which the low `shift` are kept.
+        self.emit(Instruction::Binary("mov", size, ax(size), cx(size)));
+        self.emit(Instruction::Binary("sar", size, Operand::Immediate(bits - 1), cx(size)));
+        self.emit(Instruction::Binary("shr", size, Operand::Immediate(bits - shift), cx(size)));
+        if remainder {
+            // The remainder is what rounding the biased dividend down to a
+            // multiple of the divisor takes away from the dividend.
+            self.emit(Instruction::Binary("add", size, ax(size), cx(size)));
+            clear(self, true, cx(size));
+            self.emit(Instruction::Binary("sub", size, cx(size), ax(size)));
+        } else {
+            self.emit(Instruction::Binary("add", size, cx(size), ax(size)));
+            self.emit(Instruction::Binary("sar", size, Operand::Immediate(shift), ax(size)));
+        }
+    }
+
+    fn compare(&mut self, size: i64, condition: Condition) {
+        let size = Size::of(size);
+        self.emit(Instruction::Binary("cmp", size, cx(size), ax(size)));
+        self.set(condition);
+    }
+
+    fn test(&mut self, size: i64) {
+        let size = Size::of(size);
+        self.emit(Instruction::Binary("cmp", size, Operand::Immediate(0), ax(size)));
+    }
+
+    fn set(&mut self, condition: Condition) {
+        self.emit(Instruction::Set(condition_code(condition), Register::Ax));
+        self.emit(Instruction::Extend {
+            signed: false,
+            from: Size::Byte,
+            to: Size::Long,
+            source: ax(Size::Byte),
+            destination: Register::Ax,
+        });
+    }
+
+    fn unary(&mut self, operator: UnaryOp, size: i64) {
+        let size = Size::of(size);
+        let instruction = match operator {
+            UnaryOp::Negate => "neg",
+            UnaryOp::BitwiseNot => "not",
+            operator => unreachable!("unary `{}` as one instruction", operator.symbol()),
+        };
+        self.emit(Instruction::Unary(instruction, size, ax(size)));
+    }
+
+    fn extend(&mut self, from: i64, to: i64, signed: bool) {
+        let (from, to) = (Size::of(from), Size::of(to));
+        // Writing `%eax` clears the upper half of `%rax`.
+        if !signed && from == Size::Long {
+            return self.emit(Instruction::Binary("mov", Size::Long, ax(Size::Long), ax(Size::Long)));
+        }
+        self.emit(Instruction::Extend { signed, from, to, source: ax(from), destination: Register::Ax });
+    }
+
+    fn label(&mut self, label: &str) {
+        self.emit(Instruction::Label(label.to_owned()));
+    }
+
+    fn position(&mut self, line: usize, column: usize) {
+        self.emit(Instruction::Directive(position_directive(line, column)));
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.emit(Instruction::Directive(format!("# {}", text)));
+    }
+
+    fn jump(&mut self, condition: Option<Condition>, label: &str) {
+        let mnemonic = match condition {
+            None => "jmp",
+            Some(Condition::Equal) => "je",
+            Some(Condition::NotEqual) => "jne",
+            Some(Condition::Less) => "jl",
+            Some(Condition::LessEqual) => "jle",
+            Some(Condition::Greater) => "jg",
+            Some(Condition::GreaterEqual) => "jge",
+            Some(Condition::Below) => "jb",
+            Some(Condition::BelowEqual) => "jbe",
+            Some(Condition::Above) => "ja",
+            Some(Condition::AboveEqual) => "jae",
+        };
+        self.emit(Instruction::Jump(mnemonic, label.to_owned()));
+    }
+
+    fn compare_constant(&mut self, size: i64, value: i64) {
+        let size = Size::of(size);
+        match immediate(value, size) {
+            Some(value) => self.emit(Instruction::Binary("cmp", size, value, ax(size))),
+            None => {
+                self.emit(Instruction::MoveAbsolute(value, Register::Cx));
+                self.emit(Instruction::Binary("cmp", Size::Quad, cx(Size::Quad), ax(Size::Quad)));
+            },
+        }
+    }
+
+    fn jump_table(&mut self, size: i64, min: i64, max: i64, otherwise: &str, table: &str) {
+        let size = Size::of(size);
+        // Anything below `min` wraps around to above `max - min`, so one
+        // unsigned compare catches values on either side of the table.
+        match immediate(min, size) {
+            Some(min) => self.emit(Instruction::Binary("sub", size, min, ax(size))),
+            None => {
+                self.emit(Instruction::MoveAbsolute(min, Register::Cx));
+                self.emit(Instruction::Binary("sub", Size::Quad, cx(Size::Quad), ax(Size::Quad)));
+            },
+        }
+        self.emit(Instruction::Binary("cmp", size, Operand::Immediate(max - min), ax(size)));
+        self.jump(Some(Condition::Above), otherwise);
+        // A 32-bit `sub` has zeroed the upper half of `%rax` already.
+        self.emit(Instruction::Lea(Operand::Label(table.to_owned()), Register::Cx));
+        let index = Some((Register::Ax, 8));
+        self.emit(Instruction::JumpIndirect(Operand::Memory { base: Register::Cx, offset: 0, index }));
+    }
+
+    fn call(&mut self, label: &str, arguments: &[(Home, i64)], parameters: Parameters) {
+        let registers = self.arguments();
+        let on_stack = &arguments[arguments.len().min(registers.len())..];
+        // The shadow space is a multiple of 16, so it doesn't change the padding.
+        if on_stack.len() % 2 == 1 {
+            self.push_space(8);
+        }
+        for &(home, _) in on_stack.iter().rev() {
+            let home = self.home(home);
+            self.emit(Instruction::Unary("push", Size::Quad, home));
+            self.pushed += 8;
+        }
+        for (&register, &(home, _)) in registers.iter().zip(arguments) {
+            let home = self.home(home);
+            self.emit(Instruction::Binary("mov", Size::Quad, home, Operand::Register(register, Size::Quad)));
+        }
+        match self.convention {
+            // A variadic function is told in `%al` how many arguments are
+            // passed in vector registers, which is none. So is a function
+            // without a prototype, which may turn out to be variadic.
+            CallingConvention::SystemV if parameters != Parameters::Fixed => self.constant(0, 4),
+            CallingConvention::SystemV => {},
+            CallingConvention::Windows => self.push_space(SHADOW_SPACE),
+        }
+        self.emit(Instruction::Call(label.to_owned()));
+        if self.pushed > 0 {
+            let pushed = self.pushed;
+            self.push_space(-pushed);
+        }
+    }
+
+    fn exit(&mut self, message: &str, length: usize, status: i32) {
+        // Nothing returns here, so the stack may be left in any state, and is
+        // aligned for the calls as it is.
+        let write = self.assembly.target_os.symbol("write");
+        let exit = self.assembly.target_os.symbol("exit");
+        let registers = self.arguments();
+        self.emit(Instruction::Binary("and", Size::Quad, Operand::Immediate(-16), sp()));
+        if self.convention == CallingConvention::Windows {
+            self.emit(Instruction::Binary("sub", Size::Quad, Operand::Immediate(SHADOW_SPACE), sp()));
+        }
+        self.emit(Instruction::Binary("mov", Size::Long, Operand::Immediate(2), Operand::Register(registers[0], Size::Long)));
+        self.emit(Instruction::Lea(Operand::Label(message.to_owned()), registers[1]));
+        self.emit(Instruction::Binary("mov", Size::Long, Operand::Immediate(length as i64), Operand::Register(registers[2], Size::Long)));
+        self.emit(Instruction::Call(write));
+        self.emit(Instruction::Binary("mov", Size::Long, Operand::Immediate(i64::from(status)), Operand::Register(registers[0], Size::Long)));
+        self.emit(Instruction::Call(exit));
+    }
+
+    fn optimize(&mut self, options: &Options, timings: &mut Timings) {
+        self.passes.run(&mut self.code, options, timings);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use codegen::{generate_with, Architecture, CallingConvention, DebugInfo, Options, TargetOs, TargetSpec};
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn generate_for(source: &str, target_os: TargetOs) -> String {
+        let options = Options { target: TargetSpec::new(Architecture::X86_64, target_os), debug_info: DebugInfo::Off, ..Options::default() };
+        generate_with(&parse_program(&lex_str(source).unwrap()).unwrap(), &options)
+    }
+
+    #[test]
+    fn the_same_call_in_both_conventions() {
+        let source = "int f(int a, int b, int c, int d, int e, int g) { return a + g; }\nint main() { return f(1, 2, 3, 4, 5, 6); }";
+        assert_eq!(generate_for(source, TargetOs::Linux), "    .text
+    .globl f
+f:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $32, %rsp
+    movl %edi, -4(%rbp)
+    movl %esi, -8(%rbp)
+    movl %edx, -12(%rbp)
+    movl %ecx, -16(%rbp)
+    movl %r8d, -20(%rbp)
+    movl %r9d, -24(%rbp)
+    movl -4(%rbp), %eax
+    movq %rax, %r10
+    movl -24(%rbp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .globl main
+main:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $32, %rsp
+    movq %rbx, -8(%rbp)
+    movq %r12, -16(%rbp)
+    movq %r13, -24(%rbp)
+    movq %r14, -32(%rbp)
+    movl $6, %eax
+    movq %rax, %r10
+    movl $5, %eax
+    movq %rax, %r11
+    movl $4, %eax
+    movq %rax, %rbx
+    movl $3, %eax
+    movq %rax, %r12
+    movl $2, %eax
+    movq %rax, %r13
+    movl $1, %eax
+    movq %rax, %r14
+    movq %r14, %rdi
+    movq %r13, %rsi
+    movq %r12, %rdx
+    movq %rbx, %rcx
+    movq %r11, %r8
+    movq %r10, %r9
+    call f
+    movq -8(%rbp), %rbx
+    movq -16(%rbp), %r12
+    movq -24(%rbp), %r13
+    movq -32(%rbp), %r14
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    movl $0, %eax
+    movq -8(%rbp), %rbx
+    movq -16(%rbp), %r12
+    movq -24(%rbp), %r13
+    movq -32(%rbp), %r14
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .section .note.GNU-stack,\"\",@progbits
+");
+        assert_eq!(generate_for(source, TargetOs::Windows), "    .text
+    .globl f
+f:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $32, %rsp
+    movq %rsi, -24(%rbp)
+    movl %ecx, -4(%rbp)
+    movl %edx, -8(%rbp)
+    movl %r8d, -12(%rbp)
+    movl %r9d, -16(%rbp)
+    movl -4(%rbp), %eax
+    movq %rax, %r10
+    movl 56(%rbp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq -24(%rbp), %rsi
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    movl $0, %eax
+    movq -24(%rbp), %rsi
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .globl main
+main:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $48, %rsp
+    movq %rsi, -40(%rbp)
+    movq %rbx, -8(%rbp)
+    movq %rdi, -16(%rbp)
+    movq %r12, -24(%rbp)
+    movq %r13, -32(%rbp)
+    movl $6, %eax
+    movq %rax, %r10
+    movl $5, %eax
+    movq %rax, %r11
+    movl $4, %eax
+    movq %rax, %rbx
+    movl $3, %eax
+    movq %rax, %rdi
+    movl $2, %eax
+    movq %rax, %r12
+    movl $1, %eax
+    movq %rax, %r13
+    pushq %r10
+    pushq %r11
+    movq %r13, %rcx
+    movq %r12, %rdx
+    movq %rdi, %r8
+    movq %rbx, %r9
+    subq $32, %rsp
+    call f
+    addq $48, %rsp
+    movq -8(%rbp), %rbx
+    movq -16(%rbp), %rdi
+    movq -24(%rbp), %r12
+    movq -32(%rbp), %r13
+    movq -40(%rbp), %rsi
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    movl $0, %eax
+    movq -8(%rbp), %rbx
+    movq -16(%rbp), %rdi
+    movq -24(%rbp), %r12
+    movq -32(%rbp), %r13
+    movq -40(%rbp), %rsi
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+");
+    }
+
+    #[test]
+    fn windows_sections_and_large_frames() {
+        let assembly = generate_for("int puts(const char *s);\nint main() { char big[5000]; big[0] = 0; puts(\"hi\"); return 0; }", TargetOs::Windows);
+        assert!(assembly.contains("    movq $5024, %rax\n    call ___chkstk_ms\n    subq $5024, %rsp\n"), "{}", assembly);
+        assert!(assembly.contains("    .section .rdata,\"dr\"\n.Lstr0:\n"), "{}", assembly);
+        assert!(!assembly.contains("GNU-stack"), "{}", assembly);
+    }
+
+    #[test]
+    fn the_convention_can_be_chosen_apart_from_the_target() {
+        let program = parse_program(&lex_str("int f(int a);\nint main() { return f(1); }").unwrap()).unwrap();
+        let options = Options {
+            target: TargetSpec { calling_convention: CallingConvention::Windows, ..TargetSpec::new(Architecture::X86_64, TargetOs::Linux) },
+            ..Options::default()
+        };
+        let assembly = generate_with(&program, &options);
+        assert!(assembly.contains("    movq %r10, %rcx\n    subq $32, %rsp\n    call f\n"), "{}", assembly);
+        assert!(assembly.contains("GNU-stack"), "{}", assembly);
+    }
+
+    #[test]
+    fn frames_without_the_frame_pointer() {
+        let source = "int add(int a, int b) { return a + b; }\n\
+                      int f(int a, int b, int c, int d, int e, int g, int h) { return a + h; }\n\
+                      int main() { return f(1, 2, 3, 4, 5, 6, 7); }";
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let options = Options {
+            target: TargetSpec::new(Architecture::X86_64, TargetOs::Linux),
+            debug_info: DebugInfo::Off,
+            spill_temporaries: true,
+            omit_frame_pointer: Some(true),
+            ..Options::default()
+        };
+        let assembly = generate_with(&program, &options);
+        assert!(!assembly.contains("%rbp"), "{}", assembly);
+        // 8 bytes more than the frame stand in for the saved `%rbp`.
+        assert!(assembly.contains("add:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl %esi, 8(%rsp)
+    movl 12(%rsp), %eax
+    movq %rax, (%rsp)
+    movl 8(%rsp), %eax
+    movl %eax, %ecx
+    movq (%rsp), %rax
+    addl %ecx, %eax
+    addq $24, %rsp
+    ret
+"), "{}", assembly);
+        // The stack argument is above the return address, and the slots move
+        // up as the arguments are pushed.
+        assert!(assembly.contains("    movl 48(%rsp), %eax\n"), "{}", assembly);
+        assert!(assembly.contains("    movq %rax, 56(%rsp)\n"), "{}", assembly);
+        assert!(assembly.contains("    subq $8, %rsp
+    pushq 64(%rsp)
+    movq 24(%rsp), %rdi
+"), "{}", assembly);
+    }
+}
@@ -0,0 +1,213 @@
+//! Rewrites short runs of a function's instructions that do nothing, or more
+//! than they need to, before they are written. Each rule looks at an
+//! instruction and the ones around it, and the rules are applied over and over
+//! until none of them changes anything.
+//!
+//! The generator only ever reads the flags with the conditional jump or `set`
+//! just after the comparison that set them, so an instruction whose flags
+//! nothing reads next can be removed or replaced with one that sets others.
+
+use super::asm::{Instruction, Operand, Size};
+
+/// Applies every rule to `code` until none of them applies any more.
+pub fn optimize(code: &mut Vec<Instruction>) {
+    while push_pop(code) | self_move(code) | jump_to_next(code) | redundant_test(code) | identity(code) {}
+}
+
+/// Removes the instructions at `index` for which `remove` holds, given the
+/// code around them, and says whether any were.
+fn remove_where<F>(code: &mut Vec<Instruction>, remove: F) -> bool
+where
+    F: Fn(&[Instruction], usize) -> bool,
+{
+    let before = code.len();
+    let mut index = 0;
+    while index < code.len() {
+        if remove(code, index) {
+            code.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+    code.len() != before
+}
+
+/// Whether the instruction after `index` reads the flags.
+fn flags_read_after(code: &[Instruction], index: usize) -> bool {
+    code.get(index + 1).is_some_and(Instruction::reads_flags)
+}
+
+/// `push` followed by `pop` becomes a `mov` from what was pushed to where it
+/// was popped, or nothing if that's the same register.
+fn push_pop(code: &mut Vec<Instruction>) -> bool {
+    let mut changed = false;
+    let mut index = 0;
+    while index + 1 < code.len() {
+        let replacement = match (&code[index], &code[index + 1]) {
+            (&Instruction::Unary("push", Size::Quad, ref source), &Instruction::Unary("pop", Size::Quad, ref destination)) =>
+                Some(Instruction::Binary("mov", Size::Quad, source.clone(), destination.clone())),
+            _ => None,
+        };
+        match replacement {
+            Some(replacement) => {
+                code.splice(index..index + 2, Some(replacement));
+                changed = true;
+            },
+            None => index += 1,
+        }
+    }
+    changed
+}
+
+/// A `mov` of a register to itself does nothing, unless it is 32 bits, which
+/// clears the upper half of the register.
+fn self_move(code: &mut Vec<Instruction>) -> bool {
+    remove_where(code, |code, index| match code[index] {
+        Instruction::Binary("mov", size, Operand::Register(source, _), Operand::Register(destination, _)) =>
+            size != Size::Long && source == destination,
+        _ => false,
+    })
+}
+
+/// A jump to a label among the ones just after it does nothing, whether it is
+/// taken or not. Directives between them don't change that.
+fn jump_to_next(code: &mut Vec<Instruction>) -> bool {
+    remove_where(code, |code, index| match code[index] {
+        Instruction::Jump(_, ref target) => code[index + 1..]
+            .iter()
+            .take_while(|instruction| matches!(**instruction, Instruction::Label(_) | Instruction::Directive(_)))
+            .any(|instruction| *instruction == Instruction::Label(target.clone())),
+        _ => false,
+    })
+}
+
+/// Comparing a register with 0 for `je` or `jne` just after an instruction
+/// that set the register and the zero flag by its value repeats what that
+/// instruction did. Only the zero flag is the same, so any other condition
+/// still needs the comparison.
+fn redundant_test(code: &mut Vec<Instruction>) -> bool {
+    remove_where(code, |code, index| {
+        let tested = match code[index] {
+            Instruction::Binary("cmp", size, Operand::Immediate(0), ref operand @ Operand::Register(..)) => (size, operand),
+            _ => return false,
+        };
+        let set = match index.checked_sub(1).map(|previous| &code[previous]) {
+            Some(&Instruction::Binary(mnemonic, size, _, ref destination)) =>
+                ["add", "sub", "and", "or", "xor"].contains(&mnemonic) && (size, destination) == tested,
+            Some(&Instruction::Unary("neg", size, ref operand)) => (size, operand) == tested,
+            _ => false,
+        };
+        let zero_only = match code.get(index + 1) {
+            Some(&Instruction::Jump(mnemonic, _)) => mnemonic == "je" || mnemonic == "jne",
+            _ => false,
+        };
+        set && zero_only && !flags_read_after(code, index + 1)
+    })
+}
+
+/// Adding, subtracting, or shifting by 0 leaves the operand as it was.
+fn identity(code: &mut Vec<Instruction>) -> bool {
+    remove_where(code, |code, index| match code[index] {
+        Instruction::Binary(mnemonic, _, Operand::Immediate(0), _) =>
+            ["add", "sub", "shl", "sar", "shr"].contains(&mnemonic) && !flags_read_after(code, index),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::asm::Register;
+
+    fn register(register: Register, size: Size) -> Operand {
+        Operand::Register(register, size)
+    }
+
+    fn optimized(mut code: Vec<Instruction>) -> Vec<Instruction> {
+        optimize(&mut code);
+        code
+    }
+
+    #[test]
+    fn push_and_pop_become_a_move() {
+        let code = vec![
+            Instruction::Unary("push", Size::Quad, register(Register::Ax, Size::Quad)),
+            Instruction::Unary("pop", Size::Quad, register(Register::Cx, Size::Quad)),
+            Instruction::Unary("push", Size::Quad, register(Register::Dx, Size::Quad)),
+            Instruction::Unary("pop", Size::Quad, register(Register::Dx, Size::Quad)),
+        ];
+        let mov = Instruction::Binary("mov", Size::Quad, register(Register::Ax, Size::Quad), register(Register::Cx, Size::Quad));
+        assert_eq!(optimized(code), vec![mov]);
+    }
+
+    #[test]
+    fn moves_to_the_same_register_are_removed() {
+        let code = vec![
+            Instruction::Binary("mov", Size::Quad, register(Register::R10, Size::Quad), register(Register::R10, Size::Quad)),
+            Instruction::Binary("mov", Size::Byte, register(Register::Ax, Size::Byte), register(Register::Ax, Size::Byte)),
+            Instruction::Binary("mov", Size::Long, register(Register::Ax, Size::Long), register(Register::Ax, Size::Long)),
+            Instruction::Binary("mov", Size::Quad, register(Register::Ax, Size::Quad), register(Register::Cx, Size::Quad)),
+        ];
+        assert_eq!(optimized(code.clone()), code[2..].to_vec());
+    }
+
+    #[test]
+    fn jumps_to_the_next_label_are_removed() {
+        let code = vec![
+            Instruction::Jump("jmp", ".L2".to_owned()),
+            Instruction::Label(".L1".to_owned()),
+            Instruction::Directive(".loc 1 2 5".to_owned()),
+            Instruction::Label(".L2".to_owned()),
+            Instruction::Jump("je", ".L3".to_owned()),
+            Instruction::Label(".L3".to_owned()),
+            Instruction::Jump("jmp", ".L4".to_owned()),
+            Instruction::Ret,
+            Instruction::Label(".L4".to_owned()),
+        ];
+        assert_eq!(optimized(code.clone()), code[1..].iter().filter(|instruction| **instruction != code[4]).cloned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn comparisons_with_zero_after_arithmetic_are_removed() {
+        let eax = register(Register::Ax, Size::Long);
+        let subtract = Instruction::Binary("sub", Size::Long, register(Register::Cx, Size::Long), eax.clone());
+        let test = Instruction::Binary("cmp", Size::Long, Operand::Immediate(0), eax.clone());
+        let jump = Instruction::Jump("je", ".L1".to_owned());
+        let code = vec![subtract.clone(), test.clone(), jump.clone()];
+        assert_eq!(optimized(code), vec![subtract.clone(), jump.clone()]);
+
+        // The sign flag of a subtraction isn't the sign of its result.
+        let less = Instruction::Jump("jl", ".L1".to_owned());
+        let code = vec![subtract.clone(), test.clone(), less];
+        assert_eq!(optimized(code.clone()), code);
+        // Nor is the zero flag of a multiplication meaningful.
+        let multiply = Instruction::Binary("imul", Size::Long, register(Register::Cx, Size::Long), eax.clone());
+        let code = vec![multiply, test.clone(), jump.clone()];
+        assert_eq!(optimized(code.clone()), code);
+        // A subtraction of 64 bits sets the flags by all of `%rax`.
+        let wide = Instruction::Binary("sub", Size::Quad, register(Register::Cx, Size::Quad), register(Register::Ax, Size::Quad));
+        let code = vec![wide, test, jump];
+        assert_eq!(optimized(code.clone()), code);
+    }
+
+    #[test]
+    fn arithmetic_with_zero_is_removed() {
+        let eax = register(Register::Ax, Size::Long);
+        let code = vec![
+            Instruction::Binary("add", Size::Quad, Operand::Immediate(0), register(Register::Ax, Size::Quad)),
+            Instruction::Binary("shl", Size::Long, Operand::Immediate(0), eax.clone()),
+            Instruction::Binary("sub", Size::Long, Operand::Immediate(0), Operand::frame(-4)),
+            Instruction::Binary("sub", Size::Long, Operand::Immediate(1), eax.clone()),
+            Instruction::Ret,
+        ];
+        assert_eq!(optimized(code.clone()), code[3..].to_vec());
+
+        // Its flags are still the ones of the result.
+        let code = vec![
+            Instruction::Binary("add", Size::Long, Operand::Immediate(0), eax.clone()),
+            Instruction::Binary("cmp", Size::Long, Operand::Immediate(0), eax),
+            Instruction::Jump("jne", ".L1".to_owned()),
+        ];
+        assert_eq!(optimized(code.clone()), vec![code[0].clone(), code[2].clone()]);
+    }
+}
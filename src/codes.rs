@@ -0,0 +1,1001 @@
+//! The codes that name each kind of diagnostic, as in `error[E0102]`, and the
+//! longer explanation of each that `rust-cc --explain E0102` prints. Errors
+//! are numbered by the stage that finds them, `E00xx` for lexing and parsing,
+//! `E01xx` for names and control flow, `E02xx` for types, `E03xx` for
+//! constant expressions and `E04xx` for what code can't be generated for yet,
+//! and warnings are `W00xx`.
+//!
+//! Codes are never reused for another kind of diagnostic, so that what is
+//! written about one stays right.
+
+/// A code, with what it is for and an explanation with an example.
+#[derive(Debug)]
+pub struct Code {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+/// The code `code`, ignoring case, if there is one.
+pub fn lookup(code: &str) -> Option<&'static Code> {
+    CODES.iter().find(|entry| entry.code.eq_ignore_ascii_case(code))
+}
+
+/// Every code, in order.
+pub const CODES: &[Code] = &[
+    Code {
+        code: "E0001",
+        title: "unrecognized input",
+        explanation: "\
+A character that doesn't begin any token of C, outside a string, character
+constant or comment.
+
+```c
+int main() {
+    return 1 @ 2;
+}
+```
+
+`@`, `$` and `` ` `` aren't part of C. Characters outside ASCII can only be
+written in strings and comments.",
+    },
+    Code {
+        code: "E0002",
+        title: "integer literal too large",
+        explanation: "\
+An integer literal too large for any integer type, even `unsigned long`, whose
+largest value is 18446744073709551615.
+
+```c
+unsigned long a = 18446744073709551616;
+```",
+    },
+    Code {
+        code: "E0003",
+        title: "feature from a later standard",
+        explanation: "\
+Something that the standard the program is compiled as doesn't have, such as a
+`//` comment or a declaration after a statement before C99. The message names
+the standard with it, and `--std` compiles the program as that standard.
+
+```c
+/* With --std=c89: */
+int main() {
+    int a = 1;
+    a++;
+    int b = a; // declarations after statements are a C99 feature
+    return b;
+}
+```",
+    },
+    Code {
+        code: "E0004",
+        title: "unterminated block comment",
+        explanation: "\
+A `/*` comment with no `*/` after it, so that the rest of the file is in it.
+Comments don't nest, so the first `*/` ends one however many `/*` come before
+it.
+
+```c
+int main() {
+    /* return 1;
+    return 0;
+}
+```",
+    },
+    Code {
+        code: "E0005",
+        title: "unterminated string literal",
+        explanation: "\
+A string literal with no closing `\"` on its line. A string can't go on to the
+next line, but it can be continued by another string literal after it, which it
+is joined to.
+
+```c
+const char *greeting = \"hello,
+    world\";
+```",
+    },
+    Code {
+        code: "E0006",
+        title: "unterminated character literal",
+        explanation: "\
+A character literal with no closing `'` on its line.
+
+```c
+char newline = '\\n;
+```",
+    },
+    Code {
+        code: "E0010",
+        title: "unexpected token",
+        explanation: "\
+A token where it can't be, with what could have been there instead. Often what
+is missing is a `;` or a closing bracket on the line before.
+
+```c
+int main() {
+    return 0
+}
+```",
+    },
+    Code {
+        code: "E0011",
+        title: "unexpected end of input",
+        explanation: "\
+The file ends in the middle of a declaration, statement or expression.
+
+```c
+int main() {
+    return 0;
+}
+
+int limit = (1 +
+```",
+    },
+    Code {
+        code: "E0012",
+        title: "too many initializers",
+        explanation: "\
+An initializer list with more elements than the array or struct it initializes
+has.
+
+```c
+int a[2] = { 1, 2, 3 };
+```",
+    },
+    Code {
+        code: "E0013",
+        title: "initializer doesn't fit the declared type",
+        explanation: "\
+An initializer whose shape doesn't fit the object it initializes, such as a
+single value for an array, or a list for a scalar with more than one element.
+
+```c
+int a[2] = 5;
+```",
+    },
+    Code {
+        code: "E0014",
+        title: "designated initializer",
+        explanation: "\
+Designated initializers, as in `{ .x = 1 }` or `{ [2] = 1 }`, aren't
+supported yet. Give the elements in order instead.
+
+```c
+struct point { int x; int y; };
+struct point p = { .y = 2 };
+```",
+    },
+    Code {
+        code: "E0015",
+        title: "`...` without a named parameter",
+        explanation: "\
+A variadic function needs a named parameter before the `...`, for `va_start`
+to start from.
+
+```c
+int sum(...);
+```",
+    },
+    Code {
+        code: "E0016",
+        title: "`...` before another parameter",
+        explanation: "\
+`...` stands for every argument after the named ones, so it can only be the
+last parameter.
+
+```c
+int print_at(char *format, ..., int column);
+```",
+    },
+    Code {
+        code: "E0017",
+        title: "conflicting linkage",
+        explanation: "\
+A name at file scope declared both with and without `static`. A `static` name
+can't be seen from other files, and any other can, so it can only be one. An
+`extern` declaration, or a function declared without a storage class, takes on
+the linkage of the declaration before it.
+
+```c
+static int count;
+int count;
+```",
+    },
+    Code {
+        code: "E0018",
+        title: "negative array size",
+        explanation: "\
+An array declared with a size less than zero.
+
+```c
+int a[-1];
+```",
+    },
+    Code {
+        code: "E0019",
+        title: "unsupported type",
+        explanation: "\
+A combination of type keywords that doesn't name a type, such as `signed
+unsigned`, or that names one that isn't supported yet, such as `long long`.
+
+```c
+unsigned signed int a;
+```",
+    },
+    Code {
+        code: "E0020",
+        title: "nesting too deep",
+        explanation: "\
+Statements, expressions, initializer lists or declarators nested more than 128
+levels deep. The limit keeps the compiler from running out of stack. Split the
+construct up, as into functions or variables of its own.
+
+```c
+int a = ((((((((((((((((((((((((((((((((1))))))))))))))))))))))))))))))));
+/* ...nested 128 times. */
+```",
+    },
+    Code {
+        code: "E0021",
+        title: "unclosed brace",
+        explanation: "\
+A `{` with no `}` to close it before the file ends. Where there are several,
+the innermost is the one reported, and what comes after it isn't checked, since
+it is all taken to be inside it.
+
+```c
+int first() {
+    return 1;
+
+int second() {
+    return 2;
+}
+```",
+    },
+    Code {
+        code: "E0100",
+        title: "redeclaration",
+        explanation: "\
+A name declared twice in the same block, or two parameters of a function with
+the same name.
+
+```c
+int main() {
+    int a = 1;
+    int a = 2;
+    return a;
+}
+```",
+    },
+    Code {
+        code: "E0101",
+        title: "conflicting types",
+        explanation: "\
+Two declarations of the same name at file scope with different types, such as
+a function declared with one return type and defined with another.
+
+```c
+int f(int a);
+long f(int a) { return a; }
+```",
+    },
+    Code {
+        code: "E0102",
+        title: "use of an undeclared identifier",
+        explanation: "\
+A name used where no declaration of it is in scope. It may be misspelled,
+declared later in the file, or declared in a block that has ended, which the
+message notes.
+
+```c
+int main() {
+    {
+        int count = 1;
+    }
+    return count;
+}
+```",
+    },
+    Code {
+        code: "E0103",
+        title: "use of an undeclared label",
+        explanation: "\
+A `goto` to a label that isn't defined anywhere in the function. Labels belong
+to the function they are in, so one in another function can't be jumped to.
+
+```c
+int main() {
+    goto end;
+}
+```",
+    },
+    Code {
+        code: "E0104",
+        title: "redefinition of a label",
+        explanation: "\
+A label defined twice in the same function, so that a `goto` to it could mean
+either.
+
+```c
+int main() {
+again:
+    ;
+again:
+    return 0;
+}
+```",
+    },
+    Code {
+        code: "E0105",
+        title: "`break` outside a loop or `switch`",
+        explanation: "\
+`break` leaves the innermost loop or `switch`, so there has to be one around
+it.
+
+```c
+int main() {
+    break;
+}
+```",
+    },
+    Code {
+        code: "E0106",
+        title: "`continue` outside a loop",
+        explanation: "\
+`continue` goes on to the next iteration of the innermost loop, so there has to
+be one around it. A `switch` isn't enough.
+
+```c
+int main() {
+    switch (1) { case 1: continue; }
+}
+```",
+    },
+    Code {
+        code: "E0107",
+        title: "`case` outside a `switch`",
+        explanation: "\
+A `case` label is where a `switch` jumps to, so it has to be inside one.
+
+```c
+int main() {
+    case 1: return 0;
+}
+```",
+    },
+    Code {
+        code: "E0108",
+        title: "`default` outside a `switch`",
+        explanation: "\
+A `default` label is where a `switch` jumps to when no `case` matches, so it
+has to be inside one.
+
+```c
+int main() {
+    default: return 0;
+}
+```",
+    },
+    Code {
+        code: "E0109",
+        title: "duplicate `case` value",
+        explanation: "\
+Two `case` labels of the same `switch` with the same value, once both are
+converted to the type of the value switched on.
+
+```c
+int main() {
+    switch (1) {
+        case 1: return 1;
+        case 1: return 2;
+    }
+    return 0;
+}
+```",
+    },
+    Code {
+        code: "E0110",
+        title: "more than one `default`",
+        explanation: "\
+A `switch` with two `default` labels, so that it can't say which to jump to.
+
+```c
+int main() {
+    switch (1) {
+        default: return 1;
+        default: return 2;
+    }
+}
+```",
+    },
+    Code {
+        code: "E0200",
+        title: "invalid operands to a binary operator",
+        explanation: "\
+A binary operator, or a compound assignment, applied to operands of types it
+doesn't work on, such as `+` on two pointers or `*` on a struct.
+
+```c
+struct point { int x; };
+int main() {
+    struct point p;
+    return 1 + p;
+}
+```",
+    },
+    Code {
+        code: "E0201",
+        title: "invalid operand to a unary operator",
+        explanation: "\
+A unary operator applied to an operand of a type it doesn't work on, such as
+`*` on an `int` or `-` on a pointer.
+
+```c
+int main() {
+    int a = 1;
+    return *a;
+}
+```",
+    },
+    Code {
+        code: "E0202",
+        title: "incompatible assignment",
+        explanation: "\
+A value assigned to an lvalue of a type it can't be converted to implicitly,
+such as a struct to an `int`, or an integer other than `0` to a pointer.
+
+```c
+int main() {
+    int *p;
+    p = 5;
+    return 0;
+}
+```",
+    },
+    Code {
+        code: "E0203",
+        title: "incompatible initializer",
+        explanation: "\
+An object initialized with a value of a type that can't be converted to its
+own implicitly.
+
+```c
+struct point { int x; };
+struct point p;
+int a = p;
+```",
+    },
+    Code {
+        code: "E0204",
+        title: "incompatible return value",
+        explanation: "\
+A `return` with a value that can't be converted implicitly to the return type
+of the function, or with a value in a `void` function.
+
+```c
+int *f() {
+    return 5;
+}
+```",
+    },
+    Code {
+        code: "E0205",
+        title: "condition isn't a scalar",
+        explanation: "\
+The condition of an `if`, a loop, `?:`, `!`, `&&` or `||` has to be an
+arithmetic type or a pointer, to compare with zero. A struct can't be.
+
+```c
+struct point { int x; };
+int main() {
+    struct point p;
+    if (p) return 1;
+    return 0;
+}
+```",
+    },
+    Code {
+        code: "E0206",
+        title: "`switch` on a value that isn't an integer",
+        explanation: "\
+A `switch` compares its value with integer `case` labels, so the value has to
+be an integer too.
+
+```c
+int main() {
+    int *p = 0;
+    switch (p) { default: return 0; }
+}
+```",
+    },
+    Code {
+        code: "E0207",
+        title: "branches of a conditional with incompatible types",
+        explanation: "\
+The two branches of `?:` have to have types that can be brought to a common
+one, such as two arithmetic types or two pointers to the same type.
+
+```c
+struct point { int x; };
+int main() {
+    struct point p;
+    return 1 ? 2 : p;
+}
+```",
+    },
+    Code {
+        code: "E0208",
+        title: "invalid cast",
+        explanation: "\
+A cast between types that can't be converted to each other, such as a struct
+to an integer. Only scalar types can be cast, and anything can be cast to
+`void`.
+
+```c
+struct point { int x; };
+int main() {
+    struct point p;
+    return (int) p;
+}
+```",
+    },
+    Code {
+        code: "E0209",
+        title: "invalid subscript",
+        explanation: "\
+A subscript of something that isn't an array or a pointer, or with an index
+that isn't an integer.
+
+```c
+int main() {
+    int a = 1;
+    return a[0];
+}
+```",
+    },
+    Code {
+        code: "E0210",
+        title: "call to something that isn't a function",
+        explanation: "\
+A call to a name declared as a variable or parameter. There are no function
+pointers yet.
+
+```c
+int main() {
+    int f = 1;
+    return f();
+}
+```",
+    },
+    Code {
+        code: "E0211",
+        title: "wrong number of arguments",
+        explanation: "\
+A call with fewer arguments than the function has parameters, or with more if
+the function isn't variadic. The message notes where the function is
+declared.
+
+```c
+int add(int a, int b);
+int main() {
+    return add(1);
+}
+```",
+    },
+    Code {
+        code: "E0212",
+        title: "incompatible argument",
+        explanation: "\
+An argument of a type that can't be converted implicitly to the type of its
+parameter. The message notes where the parameter is declared.
+
+```c
+int length(char *s);
+int main() {
+    return length(5);
+}
+```",
+    },
+    Code {
+        code: "E0213",
+        title: "function used as a value",
+        explanation: "\
+A function named other than to call it. There are no function pointers yet, so
+a function can't be assigned, passed or compared.
+
+```c
+int f() { return 0; }
+int main() {
+    int a = f;
+    return a;
+}
+```",
+    },
+    Code {
+        code: "E0214",
+        title: "invalid member access",
+        explanation: "\
+`.` applied to something that isn't a struct, or `->` to something that isn't a
+pointer to one.
+
+```c
+struct point { int x; };
+int main() {
+    struct point p;
+    return p->x;
+}
+```",
+    },
+    Code {
+        code: "E0215",
+        title: "no such member",
+        explanation: "\
+A member access naming a member that the struct doesn't have.
+
+```c
+struct point { int x; int y; };
+int main() {
+    struct point p;
+    return p.z;
+}
+```",
+    },
+    Code {
+        code: "E0216",
+        title: "not an lvalue",
+        explanation: "\
+Something that needs an lvalue, an expression that names an object, given
+something else: assignment, `++`, `--` and `&` need one.
+
+```c
+int main() {
+    int a = 1;
+    a + 1 = 2;
+    return a;
+}
+```",
+    },
+    Code {
+        code: "E0217",
+        title: "modification of a `const` object",
+        explanation: "\
+An assignment, increment or decrement of an lvalue whose type is
+`const`-qualified. The message notes the declaration of the variable, if there
+is one.
+
+```c
+int main() {
+    const int a = 1;
+    a = 2;
+    return a;
+}
+```",
+    },
+    Code {
+        code: "E0300",
+        title: "division by zero in a constant expression",
+        explanation: "\
+A constant expression, such as an array size, a `case` label or the
+initializer of a static object, that divides by zero, which has no value.
+
+```c
+int a[10 / 0];
+```",
+    },
+    Code {
+        code: "E0301",
+        title: "overflow in a constant expression",
+        explanation: "\
+A constant expression whose value, or some part of it, is too large for the
+compiler to work out, or a shift by more than 63 bits.
+
+```c
+int a[2147483647 * 2147483647 * 4];
+```",
+    },
+    Code {
+        code: "E0302",
+        title: "not a constant",
+        explanation: "\
+An expression needed at compile time, such as an array size, a `case` label or
+the initializer of a global or `static` variable, that depends on something
+only known when the program runs, such as a variable or a call.
+
+```c
+int size = 4;
+int a[size];
+```",
+    },
+    Code {
+        code: "E0303",
+        title: "size of a type that isn't known",
+        explanation: "\
+`sizeof` in a constant expression applied to a struct that is declared but not
+defined, so that its size isn't known.
+
+```c
+struct node;
+int a[sizeof(struct node)];
+```",
+    },
+    Code {
+        code: "E0304",
+        title: "unsupported address constant",
+        explanation: "\
+The address of a struct member in the initializer of a static object, which is
+a constant in C, but which the compiler can't work out yet.
+
+```c
+struct point { int x; int y; };
+struct point p;
+int *y = &p.y;
+```",
+    },
+    Code {
+        code: "E0305",
+        title: "division by zero",
+        explanation: "\
+A division or remainder by a constant zero, which is undefined when it runs.
+
+```c
+int main() {
+    int a = 1;
+    return a / 0;
+}
+```",
+    },
+    Code {
+        code: "E0400",
+        title: "unsupported construct",
+        explanation: "\
+Something that is valid C, but that the compiler can't generate code for yet,
+at least for the output asked for. The message says what it is. A `goto` can't
+be compiled to WebAssembly, whose control flow is only ever structured.
+
+```c
+/* With --emit wat: */
+int main() {
+    int i = 0;
+again:
+    if (++i < 3) goto again;
+    return i;
+}
+```",
+    },
+    Code {
+        code: "W0001",
+        title: "control reaches the end of a non-`void` function",
+        explanation: "\
+A function with a return type whose end can be reached without a `return`, so
+that what it returns there is undefined. `main` is the exception: it returns 0
+from its end. `-Wno-return-type` turns this off.
+
+```c
+int sign(int a) {
+    if (a > 0) return 1;
+    if (a < 0) return -1;
+}
+```",
+    },
+    Code {
+        code: "W0002",
+        title: "unused variable",
+        explanation: "\
+A local variable that is never read, or only ever assigned to. It can be
+removed. `-Wno-unused-variable` turns this off.
+
+```c
+int main() {
+    int unused = 1;
+    return 0;
+}
+```",
+    },
+    Code {
+        code: "W0003",
+        title: "unused parameter",
+        explanation: "\
+A parameter that is never read. Leaving its name out of the definition, where
+C allows it, or casting it to `void` says that is meant.
+`-Wno-unused-parameter` turns this off.
+
+```c
+int first(int a, int b) {
+    return a;
+}
+```",
+    },
+    Code {
+        code: "W0004",
+        title: "variable may be used uninitialized",
+        explanation: "\
+A read of a local variable that may come before anything is assigned to it,
+so that its value is undefined. `-Wno-maybe-uninitialized` turns this off.
+
+```c
+int f(int a) {
+    int b;
+    if (a) b = 1;
+    return b;
+}
+```",
+    },
+    Code {
+        code: "W0005",
+        title: "unreachable statement",
+        explanation: "\
+A statement that control can't reach, such as one after a `return`, so that it
+never runs. `-Wno-unreachable-code` turns this off.
+
+```c
+int main() {
+    return 0;
+    return 1;
+}
+```",
+    },
+    Code {
+        code: "W0006",
+        title: "`case` value out of range",
+        explanation: "\
+A `case` label whose value doesn't fit the type of the value switched on, so
+that it can never match. `-Wno-switch-outside-range` turns this off.
+
+```c
+int f(char c) {
+    switch (c) { case 1000: return 1; }
+    return 0;
+}
+```",
+    },
+    Code {
+        code: "W0007",
+        title: "implicit declaration of a function",
+        explanation: "\
+A call to a function that isn't declared, which C89 declares as `int name()`,
+taking any arguments. Declare it before the call instead.
+`-Wno-implicit-function-declaration` turns this off.
+
+```c
+int main() {
+    return helper(1);
+}
+```",
+    },
+    Code {
+        code: "W0008",
+        title: "conversion changes a constant's value",
+        explanation: "\
+A constant converted implicitly to a type it doesn't fit, so that its value
+changes. `-Wno-overflow` turns this off.
+
+```c
+char c = 300;
+```",
+    },
+    Code {
+        code: "W0009",
+        title: "conversion discards `const`",
+        explanation: "\
+A pointer to `const` converted implicitly to a pointer to non-`const`, through
+which what it points to could be modified. `-Wno-discarded-qualifiers` turns
+this off.
+
+```c
+int main() {
+    const int a = 1;
+    int *p = &a;
+    return *p;
+}
+```",
+    },
+    Code {
+        code: "W0010",
+        title: "shift amount out of range",
+        explanation: "\
+A shift by a constant that is negative, or at least as many bits as the type
+shifted has, which is undefined. `-Wno-shift-count-overflow` turns this off.
+
+```c
+int main() {
+    return 1 << 32;
+}
+```",
+    },
+    Code {
+        code: "W0011",
+        title: "unknown warning in a pragma",
+        explanation: "\
+A `#pragma GCC diagnostic` whose option isn't the `-W` flag of a warning, and
+so does nothing. The warnings are named as they are in the flags that turn them
+off, as in `-Wunused-variable`. `-Wno-pragmas` turns this off.
+
+```c
+#pragma GCC diagnostic ignored \"-Wunused-varaible\"
+int main() {
+    int x;
+    return 0;
+}
+```",
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use codegen;
+    use lexer;
+    use parser;
+    use regex::Regex;
+    use semantic;
+    use standard::Standard;
+    use std::collections::HashSet;
+
+    #[test]
+    fn codes_are_unique_and_in_order() {
+        let format = Regex::new(r"^[EW]\d{4}$").unwrap();
+        for pair in CODES.windows(2) {
+            assert!(pair[0].code < pair[1].code, "{} is out of order", pair[1].code);
+        }
+        for code in CODES {
+            assert!(format.is_match(code.code), "{} isn't a code", code.code);
+            assert!(!code.title.is_empty() && code.explanation.contains("```c\n"), "{} has no example", code.code);
+        }
+        assert_eq!(lookup("e0102").unwrap().code, "E0102");
+        assert!(lookup("E9999").is_none());
+    }
+
+    /// The codes of what is wrong with `source`, as far as it gets.
+    fn codes_of(source: &str, standard: Standard) -> Vec<&'static str> {
+        let lexemes = match lexer::lex_str_with(source, standard) {
+            Ok(lexemes) => lexemes,
+            Err(error) => return vec![error.code()],
+        };
+        let program = match parser::parse_program_with(&lexemes, standard) {
+            Ok(program) => program,
+            Err(errors) => return errors.iter().map(|error| error.code()).collect(),
+        };
+        let mut codes: Vec<_> = semantic::check_program(&program).iter().map(|diagnostic| diagnostic.code()).collect();
+        if source.contains("--emit wat") {
+            let options = codegen::Options { format: codegen::Format::Wat, ..codegen::Options::default() };
+            codes.extend(codegen::check(&program, &options).iter().map(|diagnostic| diagnostic.code()));
+        }
+        codes
+    }
+
+    #[test]
+    fn examples_give_their_codes() {
+        // Nesting too deep takes too much source to show it all.
+        for code in CODES.iter().filter(|code| code.code != "E0020") {
+            let start = code.explanation.find("```c\n").unwrap() + "```c\n".len();
+            let example = &code.explanation[start..start + code.explanation[start..].find("```").unwrap()];
+            let standard = if example.contains("--std=c89") { Standard::C89 } else { Standard::C11 };
+            let codes = codes_of(example, standard);
+            assert!(codes.contains(&code.code), "the example for {} gives {:?}", code.code, codes);
+        }
+    }
+
+    /// Every code the diagnostics of each stage are given, which are the
+    /// codes written anywhere in those stages' source.
+    fn emitted() -> HashSet<&'static str> {
+        let sources = [
+            include_str!("lexer.rs"),
+            include_str!("parser.rs"),
+            include_str!("semantic.rs"),
+            include_str!("typecheck.rs"),
+            include_str!("const_eval.rs"),
+        ];
+        let code = Regex::new(r#""([EW]\d{4})""#).unwrap();
+        sources.iter().flat_map(|source| code.captures_iter(source).map(|captures| captures.get(1).unwrap().as_str())).collect()
+    }
+
+    #[test]
+    fn every_emitted_code_is_registered() {
+        let emitted = emitted();
+        let registered: HashSet<_> = CODES.iter().map(|code| code.code).collect();
+        assert_eq!(emitted.difference(&registered).collect::<Vec<_>>(), Vec::<&&str>::new(), "emitted but not registered");
+        assert_eq!(registered.difference(&emitted).collect::<Vec<_>>(), Vec::<&&str>::new(), "registered but never emitted");
+    }
+}
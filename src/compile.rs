@@ -0,0 +1,673 @@
+//! Compiling a program from its source in one call, for a build script, a
+//! service, or anything else that would rather not take it through each stage
+//! itself:
+//!
+//! ```
+//! use rust_cc::{Compiler, Emit};
+//! use rust_cc::codegen::TargetSpec;
+//!
+//! let output = Compiler::new()
+//!     .target(TargetSpec::from_name("x86_64-linux").unwrap())
+//!     .emit(Emit::Assembly)
+//!     .compile("int main() { return 0; }")
+//!     .unwrap();
+//! assert!(output.output.contains("main:"));
+//! assert!(output.warnings.is_empty());
+//! ```
+//!
+//! `compile_str`, `compile_file`, `Compiler`, `CompileOptions`,
+//! `CompileOutput`, `CompileError` and `Emit` are meant to be depended on:
+//! they only change in ways that keep code that uses them compiling. Options
+//! and kinds of output are added, so `CompileOptions` is best made from its
+//! `Default`, and a match on `Emit` needs a `_` arm. What is emitted, and the
+//! messages of diagnostics, can change from one version to the next, as can
+//! the modules for each stage, which the driver and the tests reach into, and
+//! `compile_str_to`, `CompileOutputs` and `Stages`, which are the driver's
+//! way in.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use ast;
+use codegen::{self, OptLevel, TargetSpec};
+use diagnostics::{self, Diagnostic, SourceMap};
+use lexer::{self, LexError};
+use parser::{self, ParseError};
+use semantic::{self, SemanticError, Severity, WarningSettings};
+#[cfg(feature = "fs")]
+use source::Source;
+use standard::Standard;
+
+/// What to compile a program to, in the order of the stages each comes from.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+#[non_exhaustive]
+pub enum Emit {
+    /// Its lexemes, one to a line, as `lexer::pretty_print` writes them.
+    Tokens,
+    /// Its syntax tree, as `ast::pretty_print` writes it.
+    Ast,
+    /// Its syntax tree as a Graphviz graph.
+    AstDot,
+    /// Its syntax tree as JSON.
+    #[cfg(feature = "serde")]
+    AstJson,
+    /// Textual LLVM IR.
+    LlvmIr,
+    /// The WebAssembly text format.
+    Wat,
+    /// Assembly for the GNU assembler.
+    #[default]
+    Assembly,
+}
+
+impl Emit {
+    /// The kind called `name`, as in `--emit llvm-ir`.
+    pub fn from_name(name: &str) -> Option<Emit> {
+        match name {
+            "tokens" => Some(Emit::Tokens),
+            "ast" => Some(Emit::Ast),
+            "ast-dot" => Some(Emit::AstDot),
+            #[cfg(feature = "serde")]
+            "ast-json" => Some(Emit::AstJson),
+            "llvm-ir" => Some(Emit::LlvmIr),
+            "wat" => Some(Emit::Wat),
+            "asm" => Some(Emit::Assembly),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::AstDot => "ast-dot",
+            #[cfg(feature = "serde")]
+            Emit::AstJson => "ast-json",
+            Emit::LlvmIr => "llvm-ir",
+            Emit::Wat => "wat",
+            Emit::Assembly => "asm",
+        }
+    }
+}
+
+/// How to compile a program, as the driver's flags say.
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// What to compile it to, by default assembly.
+    pub emit: Emit,
+    /// The target to generate code for, by default the host.
+    pub target: TargetSpec,
+    pub opt_level: OptLevel,
+    /// The standard it is written in, by default C11.
+    pub standard: Standard,
+    /// How each warning is reported, before any `#pragma GCC diagnostic`
+    /// changes it.
+    pub warnings: WarningSettings,
+    /// Macros to define, with their values, as with `-D`. There is no
+    /// preprocessor yet, so they are accepted as the driver accepts `-D`, and
+    /// change nothing.
+    pub defines: Vec<(String, Option<String>)>,
+    /// Directories to search for headers, as with `-I`. Like `defines`, they
+    /// change nothing yet.
+    pub include_dirs: Vec<PathBuf>,
+    /// What to call the source in diagnostics and debug information, by
+    /// default `<source>`.
+    pub file_name: Option<String>,
+    /// Whether x86-64 functions do without a frame pointer, as
+    /// `codegen::Options::omit_frame_pointer` says, by default only at
+    /// `OptLevel::O2`.
+    pub omit_frame_pointer: Option<bool>,
+    /// The optimization passes not to run, by the names `codegen::pass_names`
+    /// gives them.
+    pub disabled_passes: Vec<String>,
+    /// Whether the tree emitted as `Emit::Ast` says where each node is, as
+    /// `ast::pretty_print_with_spans` writes it.
+    pub spans: bool,
+}
+
+/// What compiling a program made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileOutput {
+    /// The tokens, tree, or code asked for.
+    pub output: String,
+    /// The warnings found, in the order they were.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Why a program didn't compile: its errors, and the warnings found before it
+/// was given up on, or why it couldn't be read.
+///
+/// Each stage's error converts into one, so that `?` works on any of them in
+/// a function that returns a `CompileError`, or a `Box<dyn Error>`. Its
+/// `source` is the first error, as the stage that found it made it.
+#[derive(Debug)]
+pub struct CompileError {
+    /// The errors, and the warnings, in the order they were found.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The first error, boxed, since it can be large, and a `Result` is as
+    /// large as its error.
+    cause: Box<Cause>,
+    /// The diagnostics, each on one line as `gcc` writes them.
+    rendered: String,
+}
+
+#[derive(Debug)]
+enum Cause {
+    Lex(LexError),
+    Parse(ParseError),
+    Semantic(SemanticError),
+    Io(io::Error),
+}
+
+impl CompileError {
+    /// The error `cause`, reported as `diagnostics`, which are about the file
+    /// `map` maps.
+    fn new(map: &SourceMap, diagnostics: Vec<Diagnostic>, cause: Cause) -> CompileError {
+        let rendered = diagnostics.iter().map(|diagnostic| diagnostics::render_short(map, diagnostic, false)).collect::<Vec<_>>().join("\n");
+        CompileError { diagnostics, cause: Box::new(cause), rendered }
+    }
+
+    /// The error `cause`, reported as `diagnostics`, which are about no file
+    /// in particular.
+    fn bare(diagnostics: Vec<Diagnostic>, cause: Cause) -> CompileError {
+        let rendered = diagnostics.iter().map(|diagnostic| diagnostics::render_bare(diagnostic, false)).collect::<Vec<_>>().join("\n");
+        CompileError { diagnostics, cause: Box::new(cause), rendered }
+    }
+
+    /// The diagnostics that are errors.
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.rendered)
+    }
+}
+
+impl Error for CompileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(match *self.cause {
+            Cause::Lex(ref err) => err,
+            Cause::Parse(ref err) => err,
+            Cause::Semantic(ref err) => err,
+            Cause::Io(ref err) => err,
+        })
+    }
+}
+
+// Without the file they are in, errors are written as their kind and message
+// alone, which says where they are.
+impl From<LexError> for CompileError {
+    fn from(err: LexError) -> CompileError {
+        let diagnostic = Diagnostic::new(Severity::Error, err.to_string(), None).with_code(err.code());
+        CompileError::bare(vec![diagnostic], Cause::Lex(err))
+    }
+}
+
+impl From<ParseError> for CompileError {
+    fn from(err: ParseError) -> CompileError {
+        CompileError::from(vec![err])
+    }
+}
+
+/// The errors `parser::parse_program` fails with, of which there is always
+/// at least one.
+impl From<Vec<ParseError>> for CompileError {
+    fn from(mut errors: Vec<ParseError>) -> CompileError {
+        let diagnostics = errors.iter().map(|err| Diagnostic::new(Severity::Error, err.to_string(), None).with_code(err.code())).collect();
+        CompileError::bare(diagnostics, Cause::Parse(errors.swap_remove(0)))
+    }
+}
+
+/// A semantic error, or a warning that is to be taken as one.
+impl From<SemanticError> for CompileError {
+    fn from(err: SemanticError) -> CompileError {
+        let diagnostic = Diagnostic::new(Severity::Error, err.to_string(), None).with_code(err.code());
+        CompileError::bare(vec![diagnostic], Cause::Semantic(err))
+    }
+}
+
+impl From<io::Error> for CompileError {
+    fn from(err: io::Error) -> CompileError {
+        CompileError::bare(vec![Diagnostic::new(Severity::Error, err.to_string(), None)], Cause::Io(err))
+    }
+}
+
+/// Compiles `source` as `options` say. Tokens are emitted whether or not it
+/// parses, and its tree whether or not it is free of semantic errors, as with
+/// the driver's `--emit`.
+pub fn compile_str(source: &str, options: &CompileOptions) -> Result<CompileOutput, CompileError> {
+    let CompileOutputs { mut outputs, warnings } = compile_str_to(source, options, &[options.emit], &mut None)?;
+    Ok(CompileOutput { output: outputs.remove(0), warnings })
+}
+
+/// What compiling a program to several kinds of output at once made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileOutputs {
+    /// What it was compiled to, for each kind asked for, in the order they
+    /// were asked for.
+    pub outputs: Vec<String>,
+    /// The warnings found, in the order they were.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// How long each stage of compiling took, over every program it is added up
+/// for, and how much there was for them to work on, as the driver's
+/// `--timings` reports them.
+#[derive(Debug, Clone, Default)]
+pub struct Stages {
+    /// The stages, with each pass of code generation as one, in the order
+    /// they first ran.
+    pub times: Vec<(&'static str, Duration)>,
+    pub lexemes: usize,
+    pub nodes: usize,
+}
+
+impl Stages {
+    pub fn add(&mut self, stage: &'static str, time: Duration) {
+        match self.times.iter_mut().find(|&&mut (name, _)| name == stage) {
+            Some(&mut (_, ref mut total)) => *total += time,
+            None => self.times.push((stage, time)),
+        }
+    }
+
+    /// Adds what `other` timed and counted to these.
+    pub fn merge(&mut self, other: Stages) {
+        for (stage, time) in other.times {
+            self.add(stage, time);
+        }
+        self.lexemes += other.lexemes;
+        self.nodes += other.nodes;
+    }
+}
+
+/// Writes the time each stage took, and the total, one to a line.
+impl fmt::Display for Stages {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let milliseconds = |time: Duration| time.as_secs_f64() * 1000.0;
+        writeln!(f, "{:<16} {:>12}", "stage", "time")?;
+        for &(stage, time) in &self.times {
+            let size = match stage {
+                "lex" => format!("  {} lexemes", self.lexemes),
+                "parse" => format!("  {} nodes", self.nodes),
+                _ => String::new(),
+            };
+            writeln!(f, "{:<16} {:>10.3}ms{}", stage, milliseconds(time), size)?;
+        }
+        let total = self.times.iter().map(|&(_, time)| time).sum();
+        writeln!(f, "{:<16} {:>10.3}ms", "total", milliseconds(total))
+    }
+}
+
+/// Runs `stage`, adding the time it takes to `stages` as `name`, unless there
+/// are no stages to time.
+pub fn timed<T, F: FnOnce() -> T>(stages: &mut Option<Stages>, name: &'static str, stage: F) -> T {
+    match *stages {
+        Some(ref mut stages) => {
+            let start = Instant::now();
+            let result = stage();
+            stages.add(name, start.elapsed());
+            result
+        },
+        None => stage(),
+    }
+}
+
+/// Generates `program` with `options`, adding the time each pass took, and the
+/// rest of the time, as `codegen`, to `stages` if there are any.
+fn generate(program: &ast::Program, options: &codegen::Options, stages: &mut Option<Stages>) -> String {
+    let start = Instant::now();
+    let (output, timings) = codegen::generate_timed(program, options);
+    if let Some(ref mut stages) = *stages {
+        let mut rest = start.elapsed();
+        for &(pass, time) in timings.iter() {
+            stages.add(pass, time);
+            rest = rest.saturating_sub(time);
+        }
+        stages.add("codegen", rest);
+    }
+    output
+}
+
+/// The format code is generated in for `emit`, if it is code.
+fn format_of(emit: Emit) -> Option<codegen::Format> {
+    match emit {
+        Emit::LlvmIr => Some(codegen::Format::LlvmIr),
+        Emit::Wat => Some(codegen::Format::Wat),
+        Emit::Assembly => Some(codegen::Format::Assembly),
+        _ => None,
+    }
+}
+
+/// Compiles `source` as `compile_str` does, but to each of `emits` rather than
+/// to `options.emit`, adding how long each stage took to `stages` if there are
+/// any. It is taken no further than the last of them needs, but if any of them
+/// can't be made, none are.
+pub fn compile_str_to(source: &str, options: &CompileOptions, emits: &[Emit], stages: &mut Option<Stages>) -> Result<CompileOutputs, CompileError> {
+    let name = options.file_name.as_ref().map_or("<source>", String::as_str);
+    let map = SourceMap::new(name, source);
+    let last = emits.iter().copied().max().unwrap_or(Emit::Tokens);
+    let mut outputs: Vec<Option<String>> = vec![None; emits.len()];
+    let done = |outputs: Vec<Option<String>>, warnings| CompileOutputs { outputs: outputs.into_iter().map(Option::unwrap).collect(), warnings };
+
+    // Every error in the stage that fails is found before it is given up on.
+    let (lexemes, mut errors) = timed(stages, "lex", || lexer::lex_all(source, options.standard));
+    if !errors.is_empty() {
+        let diagnostics = errors.iter().map(|err| lex_diagnostic(&map, err)).collect();
+        return Err(CompileError::new(&map, diagnostics, Cause::Lex(errors.swap_remove(0))));
+    }
+    if let Some(ref mut stages) = *stages {
+        stages.lexemes += lexemes.len();
+    }
+    for (output, &emit) in outputs.iter_mut().zip(emits) {
+        if emit == Emit::Tokens {
+            *output = Some(lexer::pretty_print(source, &lexemes));
+        }
+    }
+    if last < Emit::Ast {
+        return Ok(done(outputs, Vec::new()));
+    }
+
+    let program = match timed(stages, "parse", || parser::parse_program_with(&lexemes, options.standard)) {
+        Ok(program) => program,
+        Err(mut errors) => {
+            let diagnostics = errors.iter().map(|err| parse_diagnostic(&map, err)).collect();
+            return Err(CompileError::new(&map, diagnostics, Cause::Parse(errors.swap_remove(0))));
+        },
+    };
+    if let Some(ref mut stages) = *stages {
+        let ast = &program.ast;
+        stages.nodes += ast.expression_count() + ast.statement_count() + ast.declaration_count();
+    }
+    for (output, &emit) in outputs.iter_mut().zip(emits) {
+        match emit {
+            Emit::Ast if options.spans => *output = Some(ast::pretty_print_with_spans(&program)),
+            Emit::Ast => *output = Some(ast::pretty_print(&program)),
+            Emit::AstDot => *output = Some(ast::to_dot(&program)),
+            #[cfg(feature = "serde")]
+            Emit::AstJson => *output = Some(format!("{}\n", ast::to_json(&program))),
+            _ => {},
+        }
+    }
+    if format_of(last).is_none() {
+        return Ok(done(outputs, Vec::new()));
+    }
+
+    // Warnings are reported with the flag that turns them off, or that made
+    // them errors, as `gcc` reports them, unless a `#pragma GCC diagnostic`
+    // says otherwise where they are.
+    let settings = options.warnings.with_pragmas(&program.pragmas);
+    let mut checked = timed(stages, "semantic", || semantic::check_program(&program));
+    for format in emits.iter().copied().filter_map(format_of) {
+        checked.extend(codegen::check(&program, &codegen::Options { format, ..codegen::Options::default() }));
+    }
+    let mut diagnostics = Vec::new();
+    let mut first_error = None;
+    for err in checked {
+        let severity = match settings.severity(&err) {
+            Some(severity) => severity,
+            None => continue,
+        };
+        diagnostics.push(semantic_diagnostic(&map, &err, severity));
+        if severity == Severity::Error && first_error.is_none() {
+            first_error = Some(err);
+        }
+    }
+    if let Some(err) = first_error {
+        return Err(CompileError::new(&map, diagnostics, Cause::Semantic(err)));
+    }
+
+    let codegen_options = codegen::Options {
+        target: options.target,
+        opt_level: options.opt_level,
+        source_path: options.file_name.clone(),
+        disabled_passes: options.disabled_passes.clone(),
+        omit_frame_pointer: options.omit_frame_pointer,
+        ..codegen::Options::default()
+    };
+    for (output, &emit) in outputs.iter_mut().zip(emits) {
+        if let Some(format) = format_of(emit) {
+            // Assembly quotes the line each statement starts on, so that it
+            // can be read alongside the source.
+            let source = if emit == Emit::Assembly { Some(source.to_owned()) } else { None };
+            let options = codegen::Options { format, source_comments: source.is_some(), source, ..codegen_options.clone() };
+            *output = Some(generate(&program, &options, stages));
+        }
+    }
+    Ok(done(outputs, diagnostics))
+}
+
+/// Compiles the file at `path` as `compile_str` does, calling it by its path
+/// unless `options` name it otherwise.
+#[cfg(feature = "fs")]
+pub fn compile_file<P: AsRef<Path>>(path: P, options: &CompileOptions) -> Result<CompileOutput, CompileError> {
+    let path = path.as_ref().to_string_lossy().into_owned();
+    let source = match Source::open(&path) {
+        Ok(source) => source,
+        Err(err) => {
+            let diagnostic = Diagnostic::new(Severity::Error, err.to_string(), None);
+            return Err(CompileError::new(&SourceMap::new(&path, ""), vec![diagnostic], Cause::Io(err)));
+        },
+    };
+    match options.file_name {
+        Some(_) => compile_str(&source, options),
+        None => compile_str(&source, &CompileOptions { file_name: Some(path.clone()), ..options.clone() }),
+    }
+}
+
+/// Options for compiling, set one at a time, and then compiling with them.
+#[derive(Debug, Clone, Default)]
+pub struct Compiler {
+    options: CompileOptions,
+}
+
+impl Compiler {
+    /// A compiler with the default options.
+    pub fn new() -> Compiler {
+        Compiler::default()
+    }
+
+    pub fn emit(mut self, emit: Emit) -> Compiler {
+        self.options.emit = emit;
+        self
+    }
+
+    pub fn target(mut self, target: TargetSpec) -> Compiler {
+        self.options.target = target;
+        self
+    }
+
+    pub fn opt_level(mut self, opt_level: OptLevel) -> Compiler {
+        self.options.opt_level = opt_level;
+        self
+    }
+
+    pub fn omit_frame_pointer(mut self, omit: bool) -> Compiler {
+        self.options.omit_frame_pointer = Some(omit);
+        self
+    }
+
+    /// Leaves out the optimization pass `name`, one of `codegen::pass_names`.
+    pub fn disable_pass(mut self, name: &str) -> Compiler {
+        self.options.disabled_passes.push(name.to_owned());
+        self
+    }
+
+    pub fn spans(mut self, spans: bool) -> Compiler {
+        self.options.spans = spans;
+        self
+    }
+
+    pub fn standard(mut self, standard: Standard) -> Compiler {
+        self.options.standard = standard;
+        self
+    }
+
+    pub fn warnings(mut self, warnings: WarningSettings) -> Compiler {
+        self.options.warnings = warnings;
+        self
+    }
+
+    /// Defines the macro `name`, as `1` if there is no `value`.
+    pub fn define(mut self, name: &str, value: Option<&str>) -> Compiler {
+        self.options.defines.push((name.to_owned(), value.map(str::to_owned)));
+        self
+    }
+
+    pub fn include_dir<P: Into<PathBuf>>(mut self, dir: P) -> Compiler {
+        self.options.include_dirs.push(dir.into());
+        self
+    }
+
+    pub fn file_name(mut self, name: &str) -> Compiler {
+        self.options.file_name = Some(name.to_owned());
+        self
+    }
+
+    pub fn options(&self) -> &CompileOptions {
+        &self.options
+    }
+
+    /// Compiles `source` as `compile_str` does.
+    pub fn compile(&self, source: &str) -> Result<CompileOutput, CompileError> {
+        compile_str(source, &self.options)
+    }
+
+    /// Compiles the file at `path` as `compile_file` does.
+    #[cfg(feature = "fs")]
+    pub fn compile_file<P: AsRef<Path>>(&self, path: P) -> Result<CompileOutput, CompileError> {
+        compile_file(path, &self.options)
+    }
+}
+
+/// `diagnostic`, with `labels`, each at what is at its position in the file
+/// `map` maps.
+fn labelled(map: &SourceMap, diagnostic: Diagnostic, labels: Vec<((usize, usize), &str)>) -> Diagnostic {
+    labels.into_iter().fold(diagnostic, |diagnostic, ((line, column), label)| diagnostic.with_label(map.span_at(line, column), label))
+}
+
+/// `err`, in the file `map` maps, as a diagnostic.
+pub fn lex_diagnostic(map: &SourceMap, err: &LexError) -> Diagnostic {
+    let (line, column) = err.position();
+    let diagnostic = Diagnostic::new(Severity::Error, err.message(), Some(map.span_at(line, column))).with_code(err.code());
+    labelled(map, diagnostic, err.labels())
+}
+
+/// `err`, in the file `map` maps, as a diagnostic.
+pub fn parse_diagnostic(map: &SourceMap, err: &ParseError) -> Diagnostic {
+    let span = err.span().or_else(|| err.position().map(|(line, column)| map.span_at(line, column)));
+    let mut diagnostic = labelled(map, Diagnostic::new(Severity::Error, err.message(), span), err.labels()).with_code(err.code());
+    diagnostic.notes.extend(err.notes().into_iter().map(str::to_owned));
+    diagnostic.help.extend(err.help());
+    diagnostic
+}
+
+/// `err`, in the file `map` maps, as a diagnostic with `severity`. Warnings
+/// are reported with the flag that turns them off, or that made them errors,
+/// as `gcc` reports them.
+pub fn semantic_diagnostic(map: &SourceMap, err: &SemanticError, severity: Severity) -> Diagnostic {
+    let message = match err.warning() {
+        Some(warning) if severity == Severity::Error => format!("{} [-Werror={}]", err.message(), warning),
+        Some(warning) => format!("{} [-W{}]", err.message(), warning),
+        None => err.message(),
+    };
+    let (line, column) = err.position();
+    let span = err.span().unwrap_or_else(|| map.span_at(line, column));
+    let mut diagnostic = labelled(map, Diagnostic::new(severity, message, Some(span)), err.labels()).with_code(err.code());
+    diagnostic.help.extend(err.help());
+    diagnostic
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn emits_are_named_as_the_driver_names_them() {
+        for &name in &["tokens", "ast", "ast-dot", "llvm-ir", "wat", "asm"] {
+            assert_eq!(Emit::from_name(name).map(Emit::name), Some(name));
+        }
+        assert_eq!(Emit::from_name("s"), None);
+    }
+
+    #[test]
+    fn each_stage_fails_with_its_errors() {
+        let lexed = compile_str("int main() { return 1 @ 2; }", &CompileOptions::default()).unwrap_err();
+        assert_eq!(lexed.diagnostics[0].code, Some("E0001"));
+        assert!(lexed.source().unwrap().is::<LexError>());
+        let parsed = compile_str("int main() { return 1 2; }", &CompileOptions::default()).unwrap_err();
+        assert_eq!(parsed.errors().count(), 1);
+        assert!(parsed.source().unwrap().is::<ParseError>());
+        let checked = compile_str("int main() { return y; }", &CompileOptions { file_name: Some("main.c".to_owned()), ..CompileOptions::default() }).unwrap_err();
+        assert_eq!(checked.to_string(), "main.c:1:21: error[E0102]: use of undeclared identifier `y`");
+        assert!(checked.source().unwrap().is::<SemanticError>());
+    }
+
+    #[test]
+    fn stage_errors_are_written_without_a_file() {
+        let error = CompileError::from(LexError::IntLiteralOutOfRange { line: 2, column: 5 });
+        assert_eq!(error.to_string(), "error[E0002]: integer literal is too large at 2:5");
+        let error = CompileError::from(vec![
+            ParseError::TooManyInitializers { line: 1, column: 3 },
+            ParseError::NegativeArraySize { line: 2, column: 7 },
+        ]);
+        assert_eq!(error.diagnostics.len(), 2);
+        assert_eq!(error.source().unwrap().to_string(), ParseError::TooManyInitializers { line: 1, column: 3 }.to_string());
+        let error = CompileError::from(io::Error::other("disk on fire"));
+        assert_eq!(error.to_string(), "error: disk on fire");
+    }
+
+    #[test]
+    fn what_the_output_cant_have_is_an_error() {
+        let source = "int main() { int i = 0; again: if (++i < 3) goto again; return i; }";
+        assert!(compile_str(source, &CompileOptions { emit: Emit::LlvmIr, ..CompileOptions::default() }).is_ok());
+        let wat = compile_str(source, &CompileOptions { emit: Emit::Wat, ..CompileOptions::default() }).unwrap_err();
+        assert_eq!(wat.diagnostics[0].code, Some("E0400"));
+        assert!(wat.source().unwrap().is::<SemanticError>());
+    }
+
+    #[test]
+    fn the_frame_pointer_can_be_kept_or_omitted_at_any_level() {
+        let source = "int main() { int x = 1; return x; }";
+        let x86 = |opt_level, omit_frame_pointer| {
+            let target = TargetSpec::from_name("x86_64-linux").unwrap();
+            compile_str(source, &CompileOptions { target, opt_level, omit_frame_pointer, ..CompileOptions::default() }).unwrap().output
+        };
+        assert!(x86(OptLevel::O0, None).contains("pushq %rbp"));
+        assert!(!x86(OptLevel::O2, None).contains("pushq %rbp"));
+        assert!(x86(OptLevel::O2, Some(false)).contains("pushq %rbp"));
+        assert!(!x86(OptLevel::O0, Some(true)).contains("pushq %rbp"));
+    }
+
+    #[test]
+    fn earlier_stages_are_emitted_without_the_later_ones() {
+        let tokens = CompileOptions { emit: Emit::Tokens, ..CompileOptions::default() };
+        assert!(compile_str("int main( {", &tokens).is_ok());
+        let ast = CompileOptions { emit: Emit::Ast, ..CompileOptions::default() };
+        assert!(compile_str("int main() { return y; }", &ast).is_ok());
+    }
+
+    #[test]
+    fn several_kinds_are_emitted_from_one_compile() {
+        let emits = [Emit::Assembly, Emit::Tokens, Emit::Ast];
+        let mut stages = Some(Stages::default());
+        let compiled = compile_str_to("int main() { int x; return 0; }", &CompileOptions::default(), &emits, &mut stages).unwrap();
+        assert!(compiled.outputs[0].contains("main:"), "{}", compiled.outputs[0]);
+        assert!(compiled.outputs[1].starts_with("Keyword \"int\""), "{}", compiled.outputs[1]);
+        assert!(compiled.outputs[2].starts_with("program"), "{}", compiled.outputs[2]);
+        assert_eq!(compiled.warnings.len(), 1);
+        let stages = stages.unwrap();
+        assert_eq!(&stages.times.iter().map(|&(stage, _)| stage).take(3).collect::<Vec<_>>(), &["lex", "parse", "semantic"]);
+        assert_eq!(stages.lexemes, 12);
+
+        // The tokens aren't had without the code, if it can't be generated.
+        let err = compile_str_to("int main() { return y; }", &CompileOptions::default(), &[Emit::Tokens, Emit::Assembly], &mut None).unwrap_err();
+        assert_eq!(err.errors().count(), 1);
+    }
+}
@@ -0,0 +1,373 @@
+//! Evaluation of integer constant expressions, shared by everything that needs
+//! a value at compile time: array sizes, case labels, and static initializers.
+//!
+//! Values have the types the program would give them. Arithmetic in a signed
+//! type is done in `i64`, and overflowing that is an error rather than
+//! wrapping, so a constant is never silently different from what was written.
+//! Arithmetic in an unsigned type wraps, as it does in the program.
+
+use std::collections::HashMap;
+use std::fmt;
+use ast::*;
+use typecheck::{common_type, promote};
+
+/// What a constant expression may refer to besides literals.
+pub struct ConstEnv<'a> {
+    /// The arenas the expression's children live in.
+    pub ast: &'a Ast,
+    /// Named constants, such as enumerators, by name.
+    pub constants: HashMap<String, i64>,
+    /// The sizes of struct types, by tag, for `sizeof`.
+    pub struct_sizes: HashMap<String, i64>,
+}
+
+impl<'a> ConstEnv<'a> {
+    /// An environment with no named constants or struct sizes.
+    pub fn new(ast: &'a Ast) -> ConstEnv<'a> {
+        ConstEnv { ast, constants: HashMap::new(), struct_sizes: HashMap::new() }
+    }
+
+    /// The size of `ty` in bytes, or `None` for a struct that isn't in the table.
+    pub fn size_of(&self, ty: &Type) -> Option<i64> {
+        match *ty {
+            Type::Int | Type::UnsignedInt => Some(4),
+            Type::Char => Some(1),
+            Type::Long | Type::UnsignedLong | Type::Pointer(_) => Some(8),
+            Type::Array(ref element, length) => self.size_of(element)?.checked_mul(length as i64),
+            Type::Struct(ref tag) => self.struct_sizes.get(tag).cloned(),
+            Type::Const(ref inner) => self.size_of(inner),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConstEvalError {
+    DivisionByZero { span: Span },
+    /// A result, or a shift amount, outside the range of `i64`.
+    Overflow { span: Span },
+    /// A subexpression whose value isn't known at compile time, such as a
+    /// variable or a call.
+    NotConstant { span: Span },
+    /// `sizeof` applied to a struct whose size isn't known.
+    UnknownSize { span: Span },
+}
+
+impl ConstEvalError {
+    /// The code of the error, from `codes::CODES`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ConstEvalError::DivisionByZero { .. } => "E0300",
+            ConstEvalError::Overflow { .. } => "E0301",
+            ConstEvalError::NotConstant { .. } => "E0302",
+            ConstEvalError::UnknownSize { .. } => "E0303",
+        }
+    }
+
+    /// What the error is, without where: a diagnostic shows that itself.
+    pub fn message(&self) -> &'static str {
+        match *self {
+            ConstEvalError::DivisionByZero { .. } => "division by zero in constant expression",
+            ConstEvalError::Overflow { .. } => "overflow in constant expression",
+            ConstEvalError::NotConstant { .. } => "expression is not a compile-time constant",
+            ConstEvalError::UnknownSize { .. } => "size of type is not known",
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match *self {
+            ConstEvalError::DivisionByZero { span }
+            | ConstEvalError::Overflow { span }
+            | ConstEvalError::NotConstant { span }
+            | ConstEvalError::UnknownSize { span } => span,
+        }
+    }
+}
+
+impl fmt::Display for ConstEvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span();
+        write!(f, "{} at {}:{}", self.message(), span.line, span.column)
+    }
+}
+
+/// Converts `value` to the integer type `ty` the way a cast does, truncating it
+/// to the type's width and then extending it by the type's signedness. Values
+/// of `unsigned long` past `i64::MAX` wrap around to negative numbers. `None` if
+/// `ty` isn't an integer type.
+pub fn convert(value: i64, ty: &Type) -> Option<i64> {
+    match *ty.unqualified() {
+        Type::Int => Some(i64::from(value as i32)),
+        Type::Char => Some(i64::from(value as i8)),
+        Type::UnsignedInt => Some(i64::from(value as u32)),
+        Type::Long | Type::UnsignedLong => Some(value),
+        _ => None,
+    }
+}
+
+/// Evaluates `expression` as an integer constant expression. `&&`, `||`, and
+/// `?:` only evaluate the operands that decide the result, so `0 && 1 / 0` is
+/// `0`. Operands are brought to a common type by the usual arithmetic
+/// conversions, as the program would bring them, so `-1 < 1u` is `0`.
+/// Arithmetic in an unsigned type wraps around to the type's width, and
+/// arithmetic in a signed type is done in `i64`, as the module describes.
+pub fn const_eval(expression: &Expression, env: &ConstEnv) -> Result<i64, ConstEvalError> {
+    typed_eval(expression, env).map(|(value, _)| value)
+}
+
+/// Evaluates `expression` as `const_eval` does, with the type of its value.
+fn typed_eval(expression: &Expression, env: &ConstEnv) -> Result<(i64, Type), ConstEvalError> {
+    let span = expression.span;
+    let eval = |id: ExprId| typed_eval(&env.ast[id], env);
+    let overflow = ConstEvalError::Overflow { span };
+    // A value of an unsigned type is kept in its range. One of a signed type
+    // is left as it is, overflowing only `i64`.
+    let as_type = |value: i64, ty: &Type| match convert(value, ty) {
+        Some(_) if ty.is_signed() => Ok(value),
+        Some(converted) => Ok(converted),
+        None => Err(ConstEvalError::NotConstant { span }),
+    };
+    let truth = |value: bool| Ok((value as i64, Type::Int));
+
+    match expression.kind {
+        ExpressionKind::IntLiteral(value, ref ty) => Ok((value, ty.clone())),
+        ExpressionKind::CharConstant(value) => Ok((i64::from(value), Type::Int)),
+        ExpressionKind::Variable(ref name) => match env.constants.get(name) {
+            Some(&value) => Ok((value, Type::Int)),
+            None => Err(ConstEvalError::NotConstant { span }),
+        },
+        ExpressionKind::SizeOf(ref ty) => Ok((env.size_of(ty).ok_or(ConstEvalError::UnknownSize { span })?, Type::Int)),
+        ExpressionKind::Unary(op @ UnaryOp::Negate, operand)
+        | ExpressionKind::Unary(op @ UnaryOp::BitwiseNot, operand)
+        | ExpressionKind::Unary(op @ UnaryOp::LogicalNot, operand) => {
+            let (value, ty) = eval(operand)?;
+            let ty = promote(&ty);
+            match op {
+                UnaryOp::Negate if ty.is_signed() => Ok((value.checked_neg().ok_or(overflow)?, ty)),
+                UnaryOp::Negate => Ok((as_type(value.wrapping_neg(), &ty)?, ty)),
+                UnaryOp::BitwiseNot => Ok((as_type(!value, &ty)?, ty)),
+                _ => truth(value == 0),
+            }
+        },
+        ExpressionKind::Unary(..) => Err(ConstEvalError::NotConstant { span }),
+        ExpressionKind::Binary(BinaryOp::LogicalAnd, lhs, rhs) => {
+            truth(eval(lhs)?.0 != 0 && eval(rhs)?.0 != 0)
+        },
+        ExpressionKind::Binary(BinaryOp::LogicalOr, lhs, rhs) => {
+            truth(eval(lhs)?.0 != 0 || eval(rhs)?.0 != 0)
+        },
+        // A shift is done in the type of what is shifted, whatever the type
+        // of the amount.
+        ExpressionKind::Binary(op @ BinaryOp::ShiftLeft, lhs, rhs) | ExpressionKind::Binary(op @ BinaryOp::ShiftRight, lhs, rhs) => {
+            let ((lhs, ty), (rhs, _)) = (eval(lhs)?, eval(rhs)?);
+            let ty = promote(&ty);
+            let shift = if (0..64).contains(&rhs) { rhs as u32 } else { return Err(overflow) };
+            let value = match op {
+                BinaryOp::ShiftLeft => lhs << shift,
+                _ if ty.is_signed() => lhs >> shift,
+                _ => ((lhs as u64) >> shift) as i64,
+            };
+            Ok((as_type(value, &ty)?, ty))
+        },
+        ExpressionKind::Binary(op, lhs, rhs) => {
+            let ((lhs, lhs_ty), (rhs, rhs_ty)) = (eval(lhs)?, eval(rhs)?);
+            let ty = common_type(&lhs_ty, &rhs_ty);
+            let (lhs, rhs) = (as_type(lhs, &ty)?, as_type(rhs, &ty)?);
+            let value = if ty.is_signed() { signed(op, lhs, rhs, span)? } else { unsigned(op, lhs as u64, rhs as u64, span)? as i64 };
+            match op {
+                BinaryOp::LessThan | BinaryOp::LessEqual | BinaryOp::GreaterThan | BinaryOp::GreaterEqual
+                | BinaryOp::Equal | BinaryOp::NotEqual => Ok((value, Type::Int)),
+                _ => Ok((as_type(value, &ty)?, ty)),
+            }
+        },
+        ExpressionKind::Conditional(condition, then, otherwise) => {
+            let (chosen, other) = if eval(condition)?.0 != 0 { (then, otherwise) } else { (otherwise, then) };
+            let (value, ty) = eval(chosen)?;
+            // The operand not chosen isn't evaluated, as far as any error in
+            // it goes, but its type still counts towards the result's.
+            match eval(other) {
+                Ok((_, other_ty)) => {
+                    let ty = common_type(&ty, &other_ty);
+                    Ok((as_type(value, &ty)?, ty))
+                },
+                Err(_) => Ok((value, ty)),
+            }
+        },
+        ExpressionKind::Cast { ref to, expr } => {
+            let value = convert(eval(expr)?.0, to).ok_or(ConstEvalError::NotConstant { span })?;
+            Ok((value, to.unqualified().clone()))
+        },
+        ExpressionKind::StringLiteral(_)
+        | ExpressionKind::Assign(..)
+        | ExpressionKind::Call(..)
+        | ExpressionKind::Subscript(..)
+        | ExpressionKind::Member(..)
+        | ExpressionKind::PointerMember(..)
+        | ExpressionKind::Comma(_) => Err(ConstEvalError::NotConstant { span }),
+    }
+}
+
+/// `lhs op rhs` for operands of a signed type, where overflowing `i64` is an
+/// error.
+fn signed(op: BinaryOp, lhs: i64, rhs: i64, span: Span) -> Result<i64, ConstEvalError> {
+    let overflow = ConstEvalError::Overflow { span };
+    match op {
+        BinaryOp::Add => lhs.checked_add(rhs).ok_or(overflow),
+        BinaryOp::Subtract => lhs.checked_sub(rhs).ok_or(overflow),
+        BinaryOp::Multiply => lhs.checked_mul(rhs).ok_or(overflow),
+        BinaryOp::Divide | BinaryOp::Modulo if rhs == 0 => Err(ConstEvalError::DivisionByZero { span }),
+        // `i64::MIN / -1` is the one quotient that doesn't fit.
+        BinaryOp::Divide => lhs.checked_div(rhs).ok_or(overflow),
+        BinaryOp::Modulo => lhs.checked_rem(rhs).ok_or(overflow),
+        BinaryOp::LessThan => Ok((lhs < rhs) as i64),
+        BinaryOp::LessEqual => Ok((lhs <= rhs) as i64),
+        BinaryOp::GreaterThan => Ok((lhs > rhs) as i64),
+        BinaryOp::GreaterEqual => Ok((lhs >= rhs) as i64),
+        BinaryOp::Equal => Ok((lhs == rhs) as i64),
+        BinaryOp::NotEqual => Ok((lhs != rhs) as i64),
+        BinaryOp::BitwiseAnd => Ok(lhs & rhs),
+        BinaryOp::BitwiseXor => Ok(lhs ^ rhs),
+        BinaryOp::BitwiseOr => Ok(lhs | rhs),
+        _ => unreachable!("`{}` is evaluated on its own", op.symbol()),
+    }
+}
+
+/// `lhs op rhs` for operands of an unsigned type, which wraps around rather
+/// than overflowing. The caller brings the result back into the type's range.
+fn unsigned(op: BinaryOp, lhs: u64, rhs: u64, span: Span) -> Result<u64, ConstEvalError> {
+    match op {
+        BinaryOp::Add => Ok(lhs.wrapping_add(rhs)),
+        BinaryOp::Subtract => Ok(lhs.wrapping_sub(rhs)),
+        BinaryOp::Multiply => Ok(lhs.wrapping_mul(rhs)),
+        BinaryOp::Divide | BinaryOp::Modulo if rhs == 0 => Err(ConstEvalError::DivisionByZero { span }),
+        BinaryOp::Divide => Ok(lhs / rhs),
+        BinaryOp::Modulo => Ok(lhs % rhs),
+        BinaryOp::LessThan => Ok((lhs < rhs) as u64),
+        BinaryOp::LessEqual => Ok((lhs <= rhs) as u64),
+        BinaryOp::GreaterThan => Ok((lhs > rhs) as u64),
+        BinaryOp::GreaterEqual => Ok((lhs >= rhs) as u64),
+        BinaryOp::Equal => Ok((lhs == rhs) as u64),
+        BinaryOp::NotEqual => Ok((lhs != rhs) as u64),
+        BinaryOp::BitwiseAnd => Ok(lhs & rhs),
+        BinaryOp::BitwiseXor => Ok(lhs ^ rhs),
+        BinaryOp::BitwiseOr => Ok(lhs | rhs),
+        _ => unreachable!("`{}` is evaluated on its own", op.symbol()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    /// Evaluates `source` as the initializer of a global, with `A` defined as 10
+    /// and `struct pair` as 8 bytes.
+    fn eval(source: &str) -> Result<i64, ConstEvalError> {
+        let program = parse_program(&lex_str(&format!("int x = {};", source)).unwrap()).unwrap();
+        let declaration = match program.items[0] {
+            TopLevel::Declaration(ref declarations) => &program.ast[declarations[0]],
+            _ => unreachable!(),
+        };
+        let expression = match declaration.initializer {
+            Some(Initializer::Expr(expression)) => expression,
+            _ => unreachable!(),
+        };
+
+        let mut env = ConstEnv::new(&program.ast);
+        env.constants.insert("A".to_owned(), 10);
+        env.struct_sizes.insert("pair".to_owned(), 8);
+        const_eval(&program.ast[expression], &env)
+    }
+
+    #[test]
+    fn values() {
+        let cases = [
+            ("42", 42),
+            ("'a'", 97),
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("7 / 2", 3),
+            ("-7 / 2", -3),
+            ("-7 % 3", -1),
+            ("1 << 4", 16),
+            ("-16 >> 2", -4),
+            ("6 & 3", 2),
+            ("6 ^ 3", 5),
+            ("6 | 3", 7),
+            ("~0", -1),
+            ("!5", 0),
+            ("!0", 1),
+            ("-A", -10),
+            ("3 < 4", 1),
+            ("3 <= 2", 0),
+            ("3 > 2", 1),
+            ("3 >= 4", 0),
+            ("2 == 2", 1),
+            ("2 != 2", 0),
+            ("2 && 3", 1),
+            ("0 || 0", 0),
+            ("A > 5 ? A * 2 : 0", 20),
+            ("0 ? 1 : 2", 2),
+            ("sizeof(int)", 4),
+            ("sizeof(char *)", 8),
+            ("sizeof(struct pair)", 8),
+            ("(char)300", 44),
+            ("(int)(65536 * 65536 + 1)", 1),
+            ("(unsigned)-1", 4294967295),
+            ("(long)65536 * 65536", 4294967296),
+            ("sizeof(long) + sizeof(unsigned)", 12),
+            ("2147483647 + 1", 2147483648),
+            ("-1 < 1u", 0),
+            ("-1 < 1ul", 0),
+            ("(0u - 1) / 2", 2147483647),
+            ("0ul - 1 == -1", 1),
+            ("~0u", 4294967295),
+            ("-1u", 4294967295),
+            ("(unsigned)-1 >> 28", 15),
+            ("1 ? -1 : 0u", 4294967295),
+        ];
+        for &(source, expected) in &cases {
+            assert_eq!(eval(source), Ok(expected), "{}", source);
+        }
+    }
+
+    #[test]
+    fn short_circuiting_skips_unevaluated_operands() {
+        assert_eq!(eval("0 && 1 / 0"), Ok(0));
+        assert_eq!(eval("1 || 1 / 0"), Ok(1));
+        assert_eq!(eval("1 ? 2 : 1 / 0"), Ok(2));
+        assert!(eval("1 && 1 / 0").is_err());
+    }
+
+    #[test]
+    fn division_by_zero() {
+        match eval("1 + 5 / (2 - 2)") {
+            Err(ConstEvalError::DivisionByZero { span }) => assert_eq!(span.column, 13),
+            result => panic!("{:?}", result),
+        }
+        assert!(matches!(eval("5 % 0"), Err(ConstEvalError::DivisionByZero { .. })));
+    }
+
+    #[test]
+    fn overflow() {
+        for source in &["2147483647 * 2147483647 * 2147483647", "-(1 << 63)", "1 << 64", "1 << -1"] {
+            assert!(matches!(eval(source), Err(ConstEvalError::Overflow { .. })), "{}", source);
+        }
+    }
+
+    #[test]
+    fn non_constant_subexpressions_are_reported() {
+        match eval("1 + y * 2") {
+            Err(ConstEvalError::NotConstant { span }) => assert_eq!((span.line, span.column), (1, 13)),
+            result => panic!("{:?}", result),
+        }
+        for source in &["f()", "\"s\"", "(y = 1)", "(1, 2)", "*p", "a[0]", "s.x"] {
+            assert!(matches!(eval(source), Err(ConstEvalError::NotConstant { .. })), "{}", source);
+        }
+    }
+
+    #[test]
+    fn unknown_struct_size() {
+        assert!(matches!(eval("sizeof(struct other)"), Err(ConstEvalError::UnknownSize { .. })));
+    }
+}
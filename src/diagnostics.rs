@@ -0,0 +1,488 @@
+//! Diagnostics, and the ways they are written: as rustc writes them, with a
+//! header with the severity and the message, the file, line and column they
+//! are at, and the line of source they are about, with the part they are about
+//! underlined; on one line, as gcc writes them; or, with the `serde` feature,
+//! as JSON. Each is written from the same `Diagnostic`.
+//!
+//! ```text
+//! error[E0102]: use of undeclared identifier `y`
+//!  --> main.c:1:21
+//!   |
+//! 1 | int main() { return y; }
+//!   |                     ^
+//! ```
+//!
+//! A span over several lines is underlined to the end of its first line, and
+//! the line it ends on is noted after the underline. Tabs are shown as four
+//! spaces, and the underline is moved along to match. Labels are underlined
+//! with `-` on their own lines, in order with the line the diagnostic is
+//! about, and notes and then help follow, each after a `= note:` or a
+//! `= help:`.
+//!
+//! With `color`, the severity and the underline are colored as the severity
+//! is, red for errors and yellow for warnings, labels are blue, the message
+//! and the location are bold, and the gutter is blue, with ANSI escape
+//! sequences. Without it, there are none.
+
+use ast::Span;
+use semantic::Severity;
+use source::LineIndex;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// How many columns a tab is shown as.
+const TAB_WIDTH: usize = 4;
+
+/// The ANSI parameters for each style of text.
+const BOLD: &str = "1";
+const GUTTER: &str = "1;34";
+const LABEL: &str = "1;34";
+
+fn severity_style(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "1;31",
+        Severity::Warning => "1;33",
+    }
+}
+
+/// `text` in `style`, if there is to be color.
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color && !text.is_empty() {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Something wrong with a file, or that might be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The code of the kind of diagnostic it is, if it has one.
+    pub code: Option<&'static str>,
+    /// What it is about, or nothing if it is about the end of the file.
+    pub span: Option<Span>,
+    /// Other parts of the file it has to do with.
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    /// What might be done about it.
+    pub help: Vec<String>,
+}
+
+/// A part of a file a diagnostic has to do with, and what it has to do with
+/// it: ``previously declared here``.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: String, span: Option<Span>) -> Diagnostic {
+        Diagnostic { severity, message, code: None, span, labels: Vec::new(), notes: Vec::new(), help: Vec::new() }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Diagnostic {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> Diagnostic {
+        self.labels.push(Label { span, message: message.to_owned() });
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_owned());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Diagnostic {
+        self.help.push(help.to_owned());
+        self
+    }
+
+    /// The notes, and then the help, each with what it is.
+    fn footnotes(&self) -> impl Iterator<Item = (&'static str, &String)> {
+        self.notes.iter().map(|note| ("note", note)).chain(self.help.iter().map(|help| ("help", help)))
+    }
+
+    /// The line and column it is at, if it is at one.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.span.map(|span| (span.line, span.column))
+    }
+
+    /// The severity, with the code if there is one: ``error[E0102]``.
+    fn kind(&self) -> String {
+        match self.code {
+            Some(code) => format!("{}[{}]", self.severity, code),
+            None => self.severity.to_string(),
+        }
+    }
+}
+
+/// A source file, and where each of its lines starts, to find the line a span
+/// is on and the text of that line.
+pub struct SourceMap<'a> {
+    name: &'a str,
+    source: &'a str,
+    lines: LineIndex,
+}
+
+impl<'a> SourceMap<'a> {
+    /// The map of `source`, which diagnostics call `name`.
+    pub fn new(name: &'a str, source: &'a str) -> SourceMap<'a> {
+        SourceMap { name, source, lines: LineIndex::new(source) }
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// The text of `line`, counted from 1, without its line ending.
+    pub fn line(&self, line: usize) -> Option<&'a str> {
+        Some(self.source[self.lines.line_span(line)?].trim_end_matches('\r'))
+    }
+
+    /// The byte offset of `line` and `column`, both counted from 1 as the
+    /// lexer counts them, the column in bytes.
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        self.lines.offset(line, column)
+    }
+
+    /// The line and column of the byte at `offset`.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        self.lines.lookup(offset)
+    }
+
+    /// The empty span at the end of the source, for a diagnostic about
+    /// something missing from it.
+    pub fn end(&self) -> Span {
+        let (line, column) = self.position(self.source.len());
+        Span { start: self.source.len(), end: self.source.len(), line, column }
+    }
+
+    /// The span of what is at `line` and `column`, for a diagnostic that only
+    /// has a position: the word there, or the one character there if it isn't
+    /// in a word, or nothing at the end of the input.
+    pub fn span_at(&self, line: usize, column: usize) -> Span {
+        let start = self.offset(line, column).unwrap_or(self.source.len());
+        let rest = &self.source[start..];
+        let word = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        let len = match rest.chars().next() {
+            _ if word > 0 => word,
+            Some('\n') | None => 0,
+            Some(c) => c.len_utf8(),
+        };
+        Span { start, end: start + len, line, column }
+    }
+}
+
+/// How many columns `text` takes up when it is shown.
+fn width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+/// Renders `diagnostic`, about the file `map` maps, in color if `color` says.
+pub fn render(map: &SourceMap, diagnostic: &Diagnostic, color: bool) -> String {
+    let style = severity_style(diagnostic.severity);
+    let span = diagnostic.span.unwrap_or_else(|| map.end());
+    let (line, column) = map.position(span.start);
+
+    // What is underlined, with the line it is on: the span the diagnostic is
+    // about, and then its labels', each line in order.
+    let mut marks = vec![(line, span, '^', style, "")];
+    marks.extend(diagnostic.labels.iter().map(|label| (map.position(label.span.start).0, label.span, '-', LABEL, &label.message[..])));
+    marks.sort_by_key(|&(line, _, _, _, _)| line);
+    let gutter = marks.iter().map(|&(line, _, _, _, _)| line).max().unwrap().to_string().len();
+
+    let bar = paint("|", GUTTER, color);
+    let mut rendered = format!("{}\n", render_bare(diagnostic, color));
+    rendered += &format!("{:gutter$}{} {}\n", "", paint("-->", GUTTER, color), paint(&format!("{}:{}:{}", map.name, line, column), BOLD, color), gutter = gutter);
+    rendered += &format!("{:gutter$} {}\n", "", bar, gutter = gutter);
+    let mut last = None;
+    for (line, span, mark, style, message) in marks {
+        let text = map.line(line).unwrap_or("");
+        if last != Some(line) {
+            if last.is_some_and(|last| line > last + 1) {
+                rendered += &format!("{}\n", paint("...", GUTTER, color));
+            }
+            let number = format!("{:>gutter$}", line, gutter = gutter);
+            let line = format!("{} {} {}", paint(&number, GUTTER, color), bar, text.replace('\t', &" ".repeat(TAB_WIDTH)));
+            rendered += &format!("{}\n", line.trim_end());
+        }
+        last = Some(line);
+
+        // The underline runs from the start of the span to its end, or to the
+        // end of the line if it goes on past it.
+        let start = (map.position(span.start).1 - 1).min(text.len());
+        let (end_line, _) = map.position(span.end.max(span.start));
+        let end = if end_line > line { text.len() } else { (start + span.end.saturating_sub(span.start)).min(text.len()) };
+        let mut underline = mark.to_string().repeat(width(&text[start..end]).max(1));
+        if end_line > line {
+            underline += &format!(" ...to line {}", end_line);
+        }
+        if !message.is_empty() {
+            underline += &format!(" {}", message);
+        }
+        rendered += &format!("{:gutter$} {} {:indent$}{}\n", "", bar, "", paint(&underline, style, color), gutter = gutter, indent = width(&text[..start]));
+    }
+    if diagnostic.footnotes().next().is_some() {
+        rendered += &format!("{:gutter$} {}\n", "", bar, gutter = gutter);
+    }
+    for (kind, text) in diagnostic.footnotes() {
+        rendered += &format!("{:gutter$} {} {}: {}\n", "", paint("=", GUTTER, color), paint(kind, BOLD, color), text, gutter = gutter);
+    }
+    rendered
+}
+
+/// Renders `diagnostic` on one line, as `path:line:column: severity: message`,
+/// leaving out the position if it doesn't have one, in color if `color` says.
+/// Its labels and notes follow it, each on a line of its own.
+pub fn render_short(map: &SourceMap, diagnostic: &Diagnostic, color: bool) -> String {
+    let location = |position: Option<(usize, usize)>| {
+        let location = match position {
+            Some((line, column)) => format!("{}:{}:{}:", map.name, line, column),
+            None => format!("{}:", map.name),
+        };
+        paint(&location, BOLD, color)
+    };
+    let severity = paint(&format!("{}:", diagnostic.kind()), severity_style(diagnostic.severity), color);
+    let mut rendered = format!("{} {} {}", location(diagnostic.position()), severity, diagnostic.message);
+    for label in &diagnostic.labels {
+        rendered += &format!("\n{} {} {}", location(Some((label.span.line, label.span.column))), paint("note:", BOLD, color), label.message);
+    }
+    for (kind, text) in diagnostic.footnotes() {
+        rendered += &format!("\n{} {} {}", location(diagnostic.position()), paint(&format!("{}:", kind), BOLD, color), text);
+    }
+    rendered
+}
+
+/// Renders `diagnostic`, which is about no file in particular, as its kind
+/// and message alone, in color if `color` says.
+pub fn render_bare(diagnostic: &Diagnostic, color: bool) -> String {
+    format!("{}{}", paint(&diagnostic.kind(), severity_style(diagnostic.severity), color), paint(&format!(": {}", diagnostic.message), BOLD, color))
+}
+
+/// Writes `diagnostic`, about the file `map` maps, as a JSON object on one
+/// line, with each span as the file, the byte offsets and the lines and
+/// columns it starts and ends at.
+#[cfg(feature = "serde")]
+pub fn to_json(map: &SourceMap, diagnostic: &Diagnostic) -> String {
+    json(Some(map), diagnostic)
+}
+
+/// Writes `diagnostic`, which is about no file in particular, as `to_json`
+/// does, with a null file.
+#[cfg(feature = "serde")]
+pub fn bare_to_json(diagnostic: &Diagnostic) -> String {
+    json(None, diagnostic)
+}
+
+#[cfg(feature = "serde")]
+fn json(map: Option<&SourceMap>, diagnostic: &Diagnostic) -> String {
+    use serde_json;
+
+    #[derive(Serialize)]
+    struct JsonDiagnostic<'a> {
+        severity: String,
+        message: &'a str,
+        code: Option<&'static str>,
+        file: Option<&'a str>,
+        span: Option<JsonSpan<'a>>,
+        labels: Vec<JsonLabel<'a>>,
+        notes: &'a [String],
+        help: &'a [String],
+    }
+
+    #[derive(Serialize)]
+    struct JsonSpan<'a> {
+        file: &'a str,
+        byte_start: usize,
+        byte_end: usize,
+        line_start: usize,
+        column_start: usize,
+        line_end: usize,
+        column_end: usize,
+    }
+
+    #[derive(Serialize)]
+    struct JsonLabel<'a> {
+        span: JsonSpan<'a>,
+        message: &'a str,
+    }
+
+    let span = |span: Span| {
+        let map = map.expect("a diagnostic about no file has no spans");
+        let ((line_start, column_start), (line_end, column_end)) = (map.position(span.start), map.position(span.end));
+        JsonSpan { file: map.name, byte_start: span.start, byte_end: span.end, line_start, column_start, line_end, column_end }
+    };
+    let json = JsonDiagnostic {
+        severity: diagnostic.severity.to_string(),
+        message: &diagnostic.message,
+        code: diagnostic.code,
+        file: map.map(|map| map.name),
+        span: diagnostic.span.map(span),
+        labels: diagnostic.labels.iter().map(|label| JsonLabel { span: span(label.span), message: &label.message }).collect(),
+        notes: &diagnostic.notes,
+        help: &diagnostic.help,
+    };
+    serde_json::to_string(&json).expect("diagnostics have no maps, so they always serialize")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+    use semantic::check_program;
+
+    /// Renders the first error in `source` as the driver does.
+    fn render_first(source: &str) -> String {
+        let map = SourceMap::new("test.c", source);
+        let (code, message, span) = match lex_str(source) {
+            Err(error) => (error.code(), error.message(), map.span_at(error.position().0, error.position().1)),
+            Ok(lexemes) => match parse_program(&lexemes) {
+                Err(errors) => {
+                    let (line, column) = errors[0].position().unwrap();
+                    (errors[0].code(), errors[0].message(), errors[0].span().unwrap_or_else(|| map.span_at(line, column)))
+                },
+                Ok(program) => {
+                    let error = check_program(&program).into_iter().find(|error| error.severity() == Severity::Error).unwrap();
+                    let (line, column) = error.position();
+                    (error.code(), error.message(), error.span().unwrap_or_else(|| map.span_at(line, column)))
+                },
+            },
+        };
+        render(&map, &Diagnostic::new(Severity::Error, message, Some(span)).with_code(code), false)
+    }
+
+    #[test]
+    fn lines_are_mapped() {
+        let map = SourceMap::new("test.c", "int a;\r\n\nint b;");
+        assert_eq!(map.line(1), Some("int a;"));
+        assert_eq!(map.line(2), Some(""));
+        assert_eq!(map.line(3), Some("int b;"));
+        assert_eq!(map.line(4), None);
+        assert_eq!(map.offset(3, 5), Some(13));
+        assert_eq!(map.position(13), (3, 5));
+        assert_eq!(map.position(17), (3, 9));
+        let span = map.span_at(3, 5);
+        assert_eq!((span.start, span.end), (13, 14));
+        let span = map.span_at(1, 1);
+        assert_eq!((span.start, span.end), (0, 3));
+    }
+
+    #[test]
+    fn lex_errors_are_rendered() {
+        assert_eq!(render_first("int main() {\n    return 1 @ 2;\n}\n"), "\
+error[E0001]: unrecognized input
+ --> test.c:2:14
+  |
+2 |     return 1 @ 2;
+  |              ^
+");
+    }
+
+    #[test]
+    fn parse_errors_are_rendered() {
+        assert_eq!(render_first("int main() {\n\treturn 0\n}\n"), "\
+error[E0010]: expected operator or `;`, found `}`
+ --> test.c:3:1
+  |
+3 | }
+  | ^
+");
+        let source = "\n".repeat(9) + "int main() {\n\tint\tcount = 0;\n\tcount = count + 2 unknown;\n}\n";
+        assert_eq!(render_first(&source), "\
+error[E0010]: expected operator or `;`, found identifier `unknown`
+  --> test.c:12:20
+   |
+12 |     count = count + 2 unknown;
+   |                       ^^^^^^^
+");
+    }
+
+    #[test]
+    fn spans_over_several_lines_are_underlined_on_the_first() {
+        let source = "struct point { int x; };\nint main() {\n    struct point p;\n    int a = 1 +\n        p;\n    return a;\n}\n";
+        assert_eq!(render_first(source), "\
+error[E0200]: invalid operands to binary `+` (`int` and `struct point`)
+ --> test.c:4:13
+  |
+4 |     int a = 1 +
+  |             ^^^ ...to line 5
+");
+    }
+
+    /// The conflicting linkage error, with a label, a note and help.
+    fn conflicting_linkage(map: &SourceMap) -> Diagnostic {
+        Diagnostic::new(Severity::Error, "`count` declared with conflicting linkage".to_owned(), Some(map.span_at(12, 5)))
+            .with_label(map.span_at(1, 12), "previously declared here")
+            .with_note("`extern` takes on an earlier linkage")
+            .with_help("declare it `extern` here")
+    }
+
+    #[test]
+    fn labels_notes_and_help_are_rendered() {
+        let source = "static int count;\n".to_owned() + &"\n".repeat(10) + "int count;\n";
+        let map = SourceMap::new("test.c", &source);
+        assert_eq!(render(&map, &conflicting_linkage(&map), false), "\
+error: `count` declared with conflicting linkage
+  --> test.c:12:5
+   |
+ 1 | static int count;
+   |            ----- previously declared here
+...
+12 | int count;
+   |     ^^^^^
+   |
+   = note: `extern` takes on an earlier linkage
+   = help: declare it `extern` here
+");
+        assert_eq!(render_short(&map, &conflicting_linkage(&map), false), "\
+test.c:12:5: error: `count` declared with conflicting linkage
+test.c:1:12: note: previously declared here
+test.c:12:5: note: `extern` takes on an earlier linkage
+test.c:12:5: help: declare it `extern` here");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_has_every_span_in_full() {
+        let map = SourceMap::new("test.c", "static int count;\nint count;\n");
+        let diagnostic = Diagnostic::new(Severity::Error, "conflicting".to_owned(), Some(map.span_at(2, 5)))
+            .with_label(map.span_at(1, 12), "previously declared here");
+        assert_eq!(
+            to_json(&map, &diagnostic),
+            r#"{"severity":"error","message":"conflicting","code":null,"file":"test.c","#.to_owned()
+                + r#""span":{"file":"test.c","byte_start":22,"byte_end":27,"line_start":2,"column_start":5,"line_end":2,"column_end":10},"#
+                + r#""labels":[{"span":{"file":"test.c","byte_start":11,"byte_end":16,"line_start":1,"column_start":12,"line_end":1,"column_end":17},"#
+                + r#""message":"previously declared here"}],"notes":[],"help":[]}"#,
+        );
+        let whole = Diagnostic::new(Severity::Warning, "empty".to_owned(), None);
+        assert_eq!(to_json(&map, &whole), r#"{"severity":"warning","message":"empty","code":null,"file":"test.c","span":null,"labels":[],"notes":[],"help":[]}"#);
+        assert_eq!(bare_to_json(&whole), r#"{"severity":"warning","message":"empty","code":null,"file":null,"span":null,"labels":[],"notes":[],"help":[]}"#);
+    }
+
+    #[test]
+    fn color_is_only_used_when_asked_for() {
+        let map = SourceMap::new("test.c", "int main() { int x; return 0; }\n");
+        let warning = Diagnostic::new(Severity::Warning, "unused variable `x`".to_owned(), Some(map.span_at(1, 18)));
+        let plain = render(&map, &warning, false);
+        assert!(!plain.contains('\x1b'));
+        let colored = render(&map, &warning, true);
+        assert!(colored.starts_with("\x1b[1;33mwarning\x1b[0m\x1b[1m: unused variable `x`\x1b[0m\n"), "{:?}", colored);
+        assert!(colored.contains("\x1b[1;33m^\x1b[0m\n"), "{:?}", colored);
+        let error = Diagnostic::new(Severity::Error, "bad".to_owned(), Some(map.span_at(1, 18)));
+        assert!(render(&map, &error, true).starts_with("\x1b[1;31merror\x1b[0m"));
+        assert_eq!(render_bare(&error, false), "error: bad");
+
+        assert_eq!(render_short(&map, &error, false), "test.c:1:18: error: bad");
+        assert_eq!(
+            render_short(&map, &error, true),
+            "\x1b[1mtest.c:1:18:\x1b[0m \x1b[1;31merror:\x1b[0m bad",
+        );
+    }
+}
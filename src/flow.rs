@@ -0,0 +1,344 @@
+//! Control-flow checks over function bodies: whether control can reach the end
+//! of a function without returning a value, and statements it can't reach at all.
+//!
+//! A loop whose condition is a nonzero constant, or a `for` without one, only
+//! ends through a `break`, so control can't pass it otherwise. A label can be
+//! jumped to from anywhere in the function, so it is always reachable. The
+//! `case` and `default` labels of a `switch` are reachable if the `switch` is,
+//! and control only passes a `switch` with a `default` by finishing its body or
+//! through a `break`.
+
+use std::mem;
+use ast::*;
+use const_eval::{const_eval, ConstEnv};
+use semantic::SemanticError;
+
+/// Whether `condition` is a constant that is always true.
+pub fn always_true(ast: &Ast, condition: ExprId) -> bool {
+    const_eval(&ast[condition], &ConstEnv::new(ast)).is_ok_and(|value| value != 0)
+}
+
+/// Whether `statement`, a loop or `switch` body, contains `jump`, a `break` or
+/// `continue`, that belongs to it, rather than to a loop nested in it.
+fn contains_jump(ast: &Ast, statement: StmtId, jump: &StatementKind) -> bool {
+    let kind = &ast[statement].kind;
+    kind == jump || match *kind {
+        StatementKind::If(_, then, otherwise) =>
+            contains_jump(ast, then, jump) || otherwise.is_some_and(|otherwise| contains_jump(ast, otherwise, jump)),
+        StatementKind::Compound(ref items) => items.iter().any(|item| match *item {
+            BlockItem::Statement(statement) => contains_jump(ast, statement, jump),
+            BlockItem::Declaration(_) => false,
+        }),
+        StatementKind::Labeled(_, statement) | StatementKind::Case(_, statement) | StatementKind::Default(statement) =>
+            contains_jump(ast, statement, jump),
+        // A `break` in a `switch` leaves the `switch`, but a `continue` goes on to the loop.
+        StatementKind::Switch(_, body) => *jump == StatementKind::Continue && contains_jump(ast, body, jump),
+        _ => false,
+    }
+}
+
+fn breaks(ast: &Ast, body: StmtId) -> bool {
+    contains_jump(ast, body, &StatementKind::Break)
+}
+
+fn continues(ast: &Ast, body: StmtId) -> bool {
+    contains_jump(ast, body, &StatementKind::Continue)
+}
+
+/// Whether `statement`, a `switch` body, contains a `default` label that
+/// belongs to the `switch`, rather than to a `switch` nested in it.
+fn has_default(ast: &Ast, statement: StmtId) -> bool {
+    match ast[statement].kind {
+        StatementKind::Default(_) => true,
+        StatementKind::If(_, then, otherwise) =>
+            has_default(ast, then) || otherwise.is_some_and(|otherwise| has_default(ast, otherwise)),
+        StatementKind::Compound(ref items) => items.iter().any(|item| match *item {
+            BlockItem::Statement(statement) => has_default(ast, statement),
+            BlockItem::Declaration(_) => false,
+        }),
+        StatementKind::While(_, body) | StatementKind::DoWhile(body, _) | StatementKind::For { body, .. }
+        | StatementKind::Labeled(_, body) | StatementKind::Case(_, body) => has_default(ast, body),
+        _ => false,
+    }
+}
+
+/// Whether `statement` contains a label, through which control can enter it
+/// even if it can't otherwise. `cases` says whether the `case` and `default`
+/// labels of the innermost `switch` around it count, as they do if the
+/// `switch` can be reached.
+pub fn labeled(ast: &Ast, statement: StmtId, cases: bool) -> bool {
+    match ast[statement].kind {
+        StatementKind::Labeled(..) => true,
+        StatementKind::Case(..) | StatementKind::Default(_) if cases => true,
+        StatementKind::Case(_, statement) | StatementKind::Default(statement) => labeled(ast, statement, cases),
+        StatementKind::If(_, then, otherwise) =>
+            labeled(ast, then, cases) || otherwise.is_some_and(|otherwise| labeled(ast, otherwise, cases)),
+        StatementKind::Compound(ref items) => items.iter().any(|item| match *item {
+            BlockItem::Statement(statement) => labeled(ast, statement, cases),
+            BlockItem::Declaration(_) => false,
+        }),
+        StatementKind::While(_, body) | StatementKind::DoWhile(body, _) | StatementKind::For { body, .. } =>
+            labeled(ast, body, cases),
+        StatementKind::Switch(_, body) => labeled(ast, body, false),
+        _ => false,
+    }
+}
+
+struct Flow<'a> {
+    ast: &'a Ast,
+    /// Whether the innermost `switch` being walked can be reached, and so its
+    /// `case` and `default` labels.
+    cases: bool,
+    errors: Vec<SemanticError>,
+}
+
+impl<'a> Flow<'a> {
+    /// Walks `items`, which control reaches if `reachable` is set, warning at
+    /// the first statement of each unreachable stretch that starts among them.
+    /// `fresh` says whether an unreachable first statement would start a
+    /// stretch, rather than continue one reported outside the block. Returns
+    /// whether control can reach the end of them.
+    fn block(&mut self, items: &[BlockItem], mut reachable: bool, mut fresh: bool) -> bool {
+        for item in items {
+            let statement = match *item {
+                BlockItem::Statement(statement) => statement,
+                BlockItem::Declaration(_) => continue,
+            };
+            let entered = match self.ast[statement].kind {
+                StatementKind::Labeled(..) => true,
+                StatementKind::Case(..) | StatementKind::Default(_) => self.cases,
+                _ => false,
+            };
+            if entered {
+                reachable = true;
+                fresh = true;
+            }
+            // A statement with a label in it can be reached through the label.
+            if !reachable && fresh && !labeled(self.ast, statement, self.cases) {
+                let span = self.ast[statement].span;
+                self.errors.push(SemanticError::UnreachableStatement { line: span.line, column: span.column });
+                fresh = false;
+            }
+            reachable = self.statement(statement, reachable);
+            fresh |= reachable;
+        }
+        reachable
+    }
+
+    /// Walks `statement`, which control reaches if `reachable` is set, and
+    /// returns whether control can pass to whatever follows it. `break`,
+    /// `continue`, and `goto` don't, since they jump elsewhere.
+    fn statement(&mut self, statement: StmtId, reachable: bool) -> bool {
+        let ast = self.ast;
+        match ast[statement].kind {
+            StatementKind::Return(_) | StatementKind::Break | StatementKind::Continue | StatementKind::Goto(_) => false,
+            StatementKind::Expression(_) => reachable,
+            StatementKind::Compound(ref items) => self.block(items, reachable, reachable),
+            StatementKind::Labeled(_, statement) => self.statement(statement, true),
+            StatementKind::Case(_, statement) | StatementKind::Default(statement) =>
+                self.statement(statement, reachable || self.cases),
+            StatementKind::Switch(_, body) => {
+                let entered = reachable || labeled(ast, body, false);
+                let cases = mem::replace(&mut self.cases, reachable);
+                // Control only enters the body at a label, and whatever comes
+                // before the first one can't be reached.
+                let finished = match ast[body].kind {
+                    StatementKind::Compound(ref items) => self.block(items, false, reachable),
+                    _ => self.statement(body, false),
+                };
+                self.cases = cases;
+                // Without a `default`, a value no `case` matches skips the body.
+                finished || (reachable && !has_default(ast, body)) || (entered && breaks(ast, body))
+            },
+            StatementKind::If(_, then, otherwise) => {
+                let then = self.statement(then, reachable);
+                let otherwise = match otherwise {
+                    Some(otherwise) => self.statement(otherwise, reachable),
+                    None => reachable,
+                };
+                then || otherwise
+            },
+            StatementKind::While(condition, body) => {
+                let entered = reachable || labeled(ast, body, self.cases);
+                self.statement(body, reachable);
+                entered && (!always_true(ast, condition) || breaks(ast, body))
+            },
+            StatementKind::For { condition, body, .. } => {
+                let entered = reachable || labeled(ast, body, self.cases);
+                self.statement(body, reachable);
+                entered && (condition.is_some_and(|condition| !always_true(ast, condition)) || breaks(ast, body))
+            },
+            // The condition is only tested if the body finishes or continues.
+            StatementKind::DoWhile(body, condition) => {
+                let entered = reachable || labeled(ast, body, self.cases);
+                let tested = self.statement(body, reachable) || (entered && continues(ast, body));
+                (tested && !always_true(ast, condition)) || (entered && breaks(ast, body))
+            },
+        }
+    }
+}
+
+/// Finds unreachable statements, and functions whose end control can reach,
+/// which return an unspecified value when it does. `main` is exempt from the
+/// latter, since reaching its end returns 0.
+pub fn check(program: &Program) -> Vec<SemanticError> {
+    let mut flow = Flow { ast: &program.ast, cases: false, errors: Vec::new() };
+    for item in &program.items {
+        if let TopLevel::Function(Function { ref name, body: Some(ref body), closing_brace: Some(closing_brace), .. }) = *item {
+            if flow.block(body, true, true) && name != "main" {
+                flow.errors.push(SemanticError::MissingReturn {
+                    name: name.clone(),
+                    line: closing_brace.line,
+                    column: closing_brace.column,
+                });
+            }
+        }
+    }
+    flow.errors
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    /// The names of the functions in `source` that can fall off their end.
+    fn missing_returns(source: &str) -> Vec<String> {
+        check(&parse_program(&lex_str(source).unwrap()).unwrap()).into_iter()
+            .filter_map(|error| match error {
+                SemanticError::MissingReturn { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn missing_return_points_at_closing_brace() {
+        let errors = check(&parse_program(&lex_str("int f(int x) {\n    if (x) return 1;\n}").unwrap()).unwrap());
+        assert_eq!(errors, vec![SemanticError::MissingReturn { name: "f".to_owned(), line: 3, column: 1 }]);
+        assert_eq!(errors[0].to_string(), "control reaches end of non-void function `f` at 3:1");
+    }
+
+    #[test]
+    fn branches_and_blocks() {
+        assert_eq!(missing_returns("int f(int x) { if (x) return 1; }"), vec!["f"]);
+        assert_eq!(missing_returns("int f(int x) { if (x) return 1; else ; }"), vec!["f"]);
+        assert_eq!(missing_returns("int f(int x) { if (x) return 1; else return 2; }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f(int x) { { x = 1; { return x; } } x = 2; }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f(int x) { if (x) { return 1; } else if (x > 1) { return 2; } else { return 3; } }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn loops() {
+        // Infinite loops only end through a `break`.
+        assert_eq!(missing_returns("int f() { while (1) ; }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f() { for (;;) { if (0) continue; } }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f() { do ; while (2 - 1); }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f() { while (1) { while (1) break; } }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f() { while (1) { if (1) break; } }"), vec!["f"]);
+        assert_eq!(missing_returns("int f(int x) { while (x) return 1; }"), vec!["f"]);
+        assert_eq!(missing_returns("int f(int x) { for (; x; ) return 1; }"), vec!["f"]);
+
+        // A `do` body that always returns never tests the condition.
+        assert_eq!(missing_returns("int f(int x) { do return 1; while (x); }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f(int x) { do { if (x) continue; return 1; } while (x); }"), vec!["f"]);
+    }
+
+    #[test]
+    fn main_and_prototypes_are_exempt() {
+        assert_eq!(missing_returns("int f(int x); int main() { }"), Vec::<String>::new());
+    }
+
+    /// Where the unreachable statements in `source` start.
+    fn unreachable(source: &str) -> Vec<(usize, usize)> {
+        check(&parse_program(&lex_str(source).unwrap()).unwrap()).into_iter()
+            .filter_map(|error| match error {
+                SemanticError::UnreachableStatement { line, column } => Some((line, column)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn statements_after_jumps_are_unreachable() {
+        let source = "\
+int main(int x) {
+    while (x) {
+        if (x) {
+            break;
+            x = 1;
+        }
+        continue;
+        x = 2;
+    }
+    goto end;
+    x = 3;
+end:
+    return x;
+    x = 4;
+}";
+        assert_eq!(unreachable(source), vec![(5, 13), (8, 9), (11, 5), (14, 5)]);
+
+        let errors = check(&parse_program(&lex_str("int main() { return 0; ; }").unwrap()).unwrap());
+        assert_eq!(errors[0].to_string(), "unreachable statement at 1:24");
+    }
+
+    #[test]
+    fn only_the_first_unreachable_statement_is_reported() {
+        // Including the statements nested in it.
+        assert_eq!(unreachable("int main(int x) { return 0; x = 1; { x = 2; if (x) x = 3; } x = 4; }"), vec![(1, 29)]);
+    }
+
+    #[test]
+    fn statements_after_returning_branches_and_infinite_loops() {
+        assert_eq!(unreachable("int main(int x) { if (x) return 1; else return 2; x = 3; }"), vec![(1, 51)]);
+        assert_eq!(unreachable("int main(int x) { while (1) x++; return x; }"), vec![(1, 34)]);
+        assert_eq!(unreachable("int main(int x) { for (;;) { if (x) continue; } return x; }"), vec![(1, 49)]);
+        assert_eq!(unreachable("int main(int x) { do ; while (1); return x; }"), vec![(1, 35)]);
+
+        // Unless a branch can finish, or a `break` leaves the loop.
+        assert_eq!(unreachable("int main(int x) { if (x) return 1; x = 3; return x; }"), vec![]);
+        assert_eq!(unreachable("int main(int x) { while (1) if (x) break; return x; }"), vec![]);
+        assert_eq!(unreachable("int main(int x) { while (x) return 1; return x; }"), vec![]);
+        // `-1` converts to the largest `unsigned`, so the loop never runs.
+        assert_eq!(unreachable("int main(int x) { while (-1 < 1u) x++; return x; }"), vec![]);
+    }
+
+    #[test]
+    fn switches() {
+        // Control passes a `switch` without a `default` when no `case` matches.
+        assert_eq!(missing_returns("int f(int x) { switch (x) { case 1: return 1; } }"), vec!["f"]);
+        assert_eq!(missing_returns("int f(int x) { switch (x) { case 1: return 1; default: return 2; } }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f(int x) { switch (x) { case 1: break; default: return 2; } }"), vec!["f"]);
+        assert_eq!(missing_returns("int f(int x) { switch (x) { default: x++; } }"), vec!["f"]);
+
+        // A `break` in a nested loop or `switch` doesn't leave this one, but
+        // a `default` nested in a loop still belongs to it.
+        assert_eq!(missing_returns("int f(int x) { switch (x) { default: while (x) break; return 1; } }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f(int x) { switch (x) { default: switch (x) { case 0: break; } return 1; } }"), Vec::<String>::new());
+        assert_eq!(missing_returns("int f(int x) { switch (x) { case 0: while (x) { default: return 1; } return 2; } }"), Vec::<String>::new());
+
+        // A `continue` in a `switch` belongs to the loop around it.
+        assert_eq!(missing_returns("int f(int x) { do switch (x) { default: continue; } while (x); }"), vec!["f"]);
+    }
+
+    #[test]
+    fn unreachable_statements_in_switches() {
+        assert_eq!(unreachable("int main(int x) { switch (x) { x = 1; case 1: x = 2; break; x = 3; default: ; } return x; }"), vec![(1, 32), (1, 61)]);
+        assert_eq!(unreachable("int main(int x) { switch (x) { case 1: return 1; default: return 2; } return x; }"), vec![(1, 71)]);
+        // The labels of an unreachable `switch` are too.
+        assert_eq!(unreachable("int main(int x) { return 0; switch (x) { case 1: x = 2; } return x; }"), vec![(1, 29)]);
+    }
+
+    #[test]
+    fn labels_are_reachable() {
+        // Control can jump to a label, so the statements after one are reachable.
+        assert_eq!(unreachable("int main(int x) { return 0; again: x++; if (x) goto again; return x; }"), vec![]);
+        assert_eq!(unreachable("int main(int x) { return 0; x = 1; again: x++; goto again; x = 2; }"), vec![(1, 29), (1, 60)]);
+
+        // Including into a loop body whose start can't be reached.
+        assert_eq!(unreachable("int main(int x) { goto inside; while (x) { inside: x--; } return x; }"), vec![]);
+        assert_eq!(missing_returns("int f(int x) { goto done; return 1; done: ; }"), vec!["f"]);
+    }
+}
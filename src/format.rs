@@ -0,0 +1,444 @@
+//! Formats source from its lexemes alone, so that code the parser can't
+//! handle yet is formatted too, and comments are kept where they are. Unlike
+//! `ast::to_c_source`, which prints a program as the parser sees it, this
+//! only changes the whitespace between lexemes.
+//!
+//! Lines are broken where they were, with blank lines between them kept, but
+//! no more than one in a row. Each line is indented by how many braces are
+//! open at its start, and once more if it carries on from the line before,
+//! and the lexemes on it are spaced out: one space around binary operators
+//! and after `,` and `;`, and none inside brackets, before `,` and `;`, or
+//! between a unary operator and its operand, unless the two would then be
+//! lexed as one, as `- -x` would be. Formatting what has already been
+//! formatted changes nothing.
+
+use std::collections::HashSet;
+use lexer::{self, LexError, Lexeme, LexemeKind};
+use parser;
+use standard::Standard;
+
+/// How to format source.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FormatOptions {
+    /// The number of spaces each open brace indents by.
+    pub indent_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions { indent_width: 4 }
+    }
+}
+
+/// Formats `source` as `options` say. Fails with the first error if it can't
+/// all be lexed, since what can't be would be lost.
+pub fn format_source(source: &str, options: &FormatOptions) -> Result<String, LexError> {
+    let (lexemes, errors) = lexer::lex_with_comments(source, Standard::default());
+    if let Some(error) = errors.into_iter().next() {
+        return Err(error);
+    }
+
+    // The parser knows which names are typedefs even in a program it can't
+    // parse all of, which tells `size_t *p` from `x * y`.
+    let code: Vec<Lexeme> = lexemes.iter().filter(|lexeme| !is_trivia(&lexeme.kind)).cloned().collect();
+    let typedefs: HashSet<String> = parser::parse_program_partial(&code).0.typedefs.into_iter().collect();
+
+    let mut formatter = Formatter {
+        source,
+        options,
+        typedefs,
+        output: String::new(),
+        line: String::new(),
+        glued: String::new(),
+        open: Vec::new(),
+        previous: None,
+        operand: false,
+        prefix: false,
+        after_header: false,
+        bodies: 0,
+        header_bodies: 0,
+        conditionals: 0,
+        comment: false,
+    };
+    for (index, lexeme) in lexemes.iter().enumerate() {
+        let newlines = index.checked_sub(1).map_or(0, |previous| source[lexemes[previous].end..lexeme.start].matches('\n').count());
+        let next = lexemes[index + 1..].iter().map(|lexeme| &lexeme.kind).find(|kind| !is_trivia(kind));
+        formatter.push(lexeme, next, index == 0 || newlines > 0, newlines > 1);
+    }
+    formatter.end_line();
+    Ok(formatter.output)
+}
+
+/// Comments and `#pragma`s, which don't take part in the code around them.
+fn is_trivia(kind: &LexemeKind) -> bool {
+    matches!(*kind, LexemeKind::Comment(_) | LexemeKind::Pragma(_) | LexemeKind::Whitespace(_))
+}
+
+/// A bracket that is open, and what it is for.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Bracket {
+    /// A `(` around a cast's type, or after `if`, `for`, `while` or
+    /// `switch`, or else any other.
+    Cast,
+    Header,
+    Paren,
+    Square,
+    /// A `{` around statements or struct members, whose line is indented by
+    /// `level`, with `bodies` as it was there.
+    Block { level: usize, bodies: usize },
+    /// A `{` around an initializer's elements.
+    Initializer { level: usize },
+}
+
+struct Formatter<'a, 'b> {
+    source: &'a str,
+    options: &'b FormatOptions,
+    typedefs: HashSet<String>,
+    output: String,
+    /// The line being written, which is added to `output` once it ends.
+    line: String,
+    /// The lexemes at the end of `line` with no space between them, which a
+    /// lexeme added without a space mustn't run into.
+    glued: String,
+    open: Vec<Bracket>,
+    /// The last lexeme that isn't trivia.
+    previous: Option<LexemeKind<'a>>,
+    /// Whether `previous` ends an operand, so that an operator after it is
+    /// binary, or postfix.
+    operand: bool,
+    /// Whether `previous` is a prefix operator or a cast, which its operand
+    /// follows without a space.
+    prefix: bool,
+    /// Whether `previous` ends the header of a statement whose body can be a
+    /// statement without braces: `if (...)`, `else`, and so on.
+    after_header: bool,
+    /// How many headers the statement being written is the body of, without
+    /// braces, each of which indents it once more.
+    bodies: usize,
+    /// What `bodies` was at the last header, which an `else` after its body
+    /// goes back to.
+    header_bodies: usize,
+    /// How many `?`s in the current statement no `:` has yet matched.
+    conditionals: usize,
+    /// Whether the last lexeme is a comment.
+    comment: bool,
+}
+
+impl<'a, 'b> Formatter<'a, 'b> {
+    /// Adds `lexeme`, which `next` is the code after, at the start of a line
+    /// of its own if `starts_line`, after a blank line if `after_blank`.
+    fn push(&mut self, lexeme: &Lexeme<'a>, next: Option<&LexemeKind>, starts_line: bool, after_blank: bool) {
+        let text = &self.source[lexeme.start..lexeme.end];
+        let text = match lexeme.kind {
+            LexemeKind::Comment(_) if text.starts_with("//") => text.trim_end(),
+            LexemeKind::Pragma(_) => text.trim_end(),
+            _ => text,
+        };
+        let kind = &lexeme.kind;
+
+        if starts_line {
+            if !self.output.is_empty() || !self.line.is_empty() {
+                self.end_line();
+                if after_blank {
+                    self.output.push('\n');
+                }
+            }
+            // A body without braces is indented once more than its header,
+            // rather than as what carries on from it.
+            let body = self.after_header && !is_trivia(kind) && *kind != LexemeKind::OpenBrace;
+            if body {
+                self.bodies += 1;
+            }
+            let indent = self.indent(kind, next);
+            if body {
+                self.after_header = false;
+            }
+            self.line.extend((0..indent * self.options.indent_width).map(|_| ' '));
+            self.glued.clear();
+        } else if self.space_before(kind) || self.runs_into(text) {
+            self.line.push(' ');
+            self.glued.clear();
+        }
+        self.line.push_str(text);
+        self.glued.push_str(text);
+
+        self.comment = is_trivia(kind);
+        if self.comment {
+            return;
+        }
+        let mut operand = self.is_operand(kind);
+        let mut prefix = !operand && self.is_prefix(kind);
+        self.after_header = matches!(*kind, LexemeKind::Keyword("else") | LexemeKind::Keyword("do"));
+        match *kind {
+            LexemeKind::OpenParen => {
+                let bracket = match self.previous {
+                    Some(LexemeKind::Keyword("if")) | Some(LexemeKind::Keyword("for")) | Some(LexemeKind::Keyword("while")) | Some(LexemeKind::Keyword("switch")) => {
+                        self.header_bodies = self.bodies;
+                        Bracket::Header
+                    },
+                    Some(LexemeKind::Keyword("sizeof")) => Bracket::Paren,
+                    _ if !self.operand && next.is_some_and(|next| self.is_type(next)) => Bracket::Cast,
+                    _ => Bracket::Paren,
+                };
+                self.open.push(bracket);
+            },
+            LexemeKind::OpenBracket => self.open.push(Bracket::Square),
+            LexemeKind::OpenBrace => {
+                let level = self.base() + self.bodies;
+                let initializer = match self.previous {
+                    Some(LexemeKind::Assign) => true,
+                    Some(LexemeKind::OpenBrace) | Some(LexemeKind::Comma) => matches!(self.open.last(), Some(&Bracket::Initializer { .. })),
+                    _ => false,
+                };
+                self.open.push(if initializer { Bracket::Initializer { level } } else { Bracket::Block { level, bodies: self.bodies } });
+                self.bodies = 0;
+                self.conditionals = 0;
+            },
+            LexemeKind::CloseParen | LexemeKind::CloseBracket | LexemeKind::CloseBrace => match self.open.pop() {
+                Some(Bracket::Cast) => {
+                    operand = false;
+                    prefix = true;
+                },
+                Some(Bracket::Header) => self.after_header = true,
+                // An `else` goes with the header of what it follows.
+                Some(Bracket::Block { bodies, .. }) => {
+                    self.bodies = if next == Some(&LexemeKind::Keyword("else")) { bodies } else { 0 };
+                    self.conditionals = 0;
+                },
+                _ => {},
+            },
+            LexemeKind::Semicolon if !matches!(self.open.last(), Some(&Bracket::Header) | Some(&Bracket::Paren)) => {
+                self.bodies = if next == Some(&LexemeKind::Keyword("else")) { self.header_bodies } else { 0 };
+                self.conditionals = 0;
+            },
+            LexemeKind::QuestionMark => self.conditionals += 1,
+            LexemeKind::Colon => self.conditionals = self.conditionals.saturating_sub(1),
+            _ => {},
+        }
+        self.operand = operand;
+        self.prefix = prefix;
+        self.previous = Some(kind.clone());
+    }
+
+    /// Whether `text`, added without a space, would be lexed together with
+    /// what is before it, as `- -x` would be as `--x`, or `. . .` as `...`.
+    fn runs_into(&self, text: &str) -> bool {
+        let joined = format!("{}{}", self.glued, text);
+        let (lexemes, errors) = lexer::lex_with_comments(&joined, Standard::default());
+        !errors.is_empty() || lexemes.last().is_none_or(|last| last.start != self.glued.len())
+            || lexer::lex_with_comments(&self.glued, Standard::default()).0.len() + 1 != lexemes.len()
+    }
+
+    fn end_line(&mut self) {
+        if !self.line.is_empty() {
+            self.output.push_str(self.line.trim_end());
+            self.output.push('\n');
+            self.line.clear();
+        }
+        self.glued.clear();
+    }
+
+    /// The level the innermost braces indent what is in them to.
+    fn base(&self) -> usize {
+        self.open.iter().rev().find_map(|bracket| match *bracket {
+            Bracket::Block { level, .. } | Bracket::Initializer { level } => Some(level + 1),
+            _ => None,
+        }).unwrap_or(0)
+    }
+
+    /// How many levels a line starting with `kind`, with `next` after it, is
+    /// indented by. Labels are outdented: `case` and `default` to the
+    /// `switch`, and others to the start of the line.
+    fn indent(&self, kind: &LexemeKind, next: Option<&LexemeKind>) -> usize {
+        let level = self.base() + self.bodies;
+        match *kind {
+            LexemeKind::Pragma(_) => 0,
+            LexemeKind::CloseBrace => match self.open.last() {
+                Some(&Bracket::Block { level, .. }) | Some(&Bracket::Initializer { level }) => level,
+                _ => level.saturating_sub(1),
+            },
+            LexemeKind::OpenBrace => level,
+            LexemeKind::Keyword("case") | LexemeKind::Keyword("default") => level.saturating_sub(1),
+            LexemeKind::Identifier(_) if next == Some(&LexemeKind::Colon) && self.conditionals == 0 && !self.continues() => 0,
+            _ if is_trivia(kind) => level,
+            _ => level + (!self.after_header && self.continues()) as usize,
+        }
+    }
+
+    /// Whether a line starting here carries on from the line before: it is
+    /// inside brackets, or what is before it doesn't end a statement, a
+    /// block, a label or an element of an initializer.
+    fn continues(&self) -> bool {
+        match (self.open.last(), self.previous.as_ref()) {
+            (Some(&Bracket::Block { .. }), _) | (Some(&Bracket::Initializer { .. }), _) | (None, _) => {},
+            _ => return true,
+        }
+        match self.previous {
+            None => false,
+            Some(LexemeKind::Comma) => !matches!(self.open.last(), Some(&Bracket::Initializer { .. })),
+            Some(ref previous) => !matches!(*previous, LexemeKind::Semicolon | LexemeKind::OpenBrace | LexemeKind::CloseBrace | LexemeKind::Colon),
+        }
+    }
+
+    /// Whether `kind`, on the same line as the lexeme before it, is spaced
+    /// from it.
+    fn space_before(&self, kind: &LexemeKind) -> bool {
+        let previous = match self.previous {
+            Some(ref previous) => previous,
+            None => return true,
+        };
+        // Comments are spaced from what is on either side of them.
+        if self.comment || is_trivia(kind) {
+            return true;
+        }
+        let initializer = matches!(self.open.last(), Some(&Bracket::Initializer { .. }));
+        match (previous, kind) {
+            (_, &LexemeKind::Semicolon) | (_, &LexemeKind::Comma) => false,
+            (&LexemeKind::OpenParen, _) | (&LexemeKind::OpenBracket, _) => false,
+            (_, &LexemeKind::CloseParen) | (_, &LexemeKind::CloseBracket) => false,
+            (&LexemeKind::OpenBrace, _) | (_, &LexemeKind::CloseBrace) if initializer => false,
+            (&LexemeKind::Dot, _) | (&LexemeKind::Arrow, _) | (_, &LexemeKind::Dot) | (_, &LexemeKind::Arrow) => false,
+            _ if self.prefix => false,
+            (_, &LexemeKind::Increment) | (_, &LexemeKind::Decrement) if self.operand => false,
+            (&LexemeKind::Keyword("sizeof"), &LexemeKind::OpenParen) => false,
+            (_, &LexemeKind::OpenParen) | (_, &LexemeKind::OpenBracket) => !self.operand,
+            (_, &LexemeKind::Colon) => self.conditionals > 0,
+            _ => true,
+        }
+    }
+
+    /// Whether `kind`, coming after `previous`, ends an operand.
+    fn is_operand(&self, kind: &LexemeKind) -> bool {
+        match *kind {
+            LexemeKind::Identifier(name) => {
+                !self.typedefs.contains(name) && self.previous != Some(LexemeKind::Keyword("struct"))
+            },
+            LexemeKind::IntLiteral(..) | LexemeKind::CharLiteral(_) | LexemeKind::StringLiteral(_) => true,
+            LexemeKind::CloseParen | LexemeKind::CloseBracket => true,
+            LexemeKind::Increment | LexemeKind::Decrement => self.operand,
+            _ => false,
+        }
+    }
+
+    /// Whether `kind`, which isn't an operand, is a prefix operator.
+    fn is_prefix(&self, kind: &LexemeKind) -> bool {
+        match *kind {
+            LexemeKind::Bang | LexemeKind::Tilde | LexemeKind::Increment | LexemeKind::Decrement => true,
+            LexemeKind::Plus | LexemeKind::Minus | LexemeKind::Star | LexemeKind::Ampersand => !self.operand,
+            _ => false,
+        }
+    }
+
+    /// Whether `kind` starts a type name, as a cast's type starts.
+    fn is_type(&self, kind: &LexemeKind) -> bool {
+        match *kind {
+            LexemeKind::Keyword(keyword) => matches!(keyword, "int" | "char" | "long" | "signed" | "unsigned" | "const" | "struct" | "_Bool"),
+            LexemeKind::Identifier(name) => self.typedefs.contains(name),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        let formatted = format_source(source, &FormatOptions::default()).unwrap();
+        assert_eq!(format_source(&formatted, &FormatOptions::default()).unwrap(), formatted, "formatting again changed:\n{}", formatted);
+        formatted
+    }
+
+    #[test]
+    fn messy_code_is_spaced_and_indented() {
+        assert_eq!(format("int  main( ){\nint x=1 ,*p=&x;\n  if(x>0&&!*p)\nx=-x ;\n      for(x=0;x<3;x++){p [0]+=sizeof(int);}\nreturn x?1:f( x , 2 );\n}\n"), "\
+int main() {
+    int x = 1, *p = &x;
+    if (x > 0 && !*p)
+        x = -x;
+    for (x = 0; x < 3; x++) { p[0] += sizeof(int); }
+    return x ? 1 : f(x, 2);
+}
+");
+        assert_eq!(format("struct s{int a;};\nvoid g(struct s*q){\nswitch(q->a){\ncase 1:\nq -> a --;\n}\nlabel:;\n}"), "\
+struct s { int a; };
+void g(struct s *q) {
+    switch (q->a) {
+    case 1:
+        q->a--;
+    }
+label:;
+}
+");
+    }
+
+    #[test]
+    fn typedef_names_are_types() {
+        assert_eq!(format("typedef long size;\nsize*p=(size*)0;int y=a*b;"), "typedef long size;\nsize *p = (size *)0; int y = a * b;\n");
+    }
+
+    #[test]
+    fn bodies_without_braces_are_indented() {
+        assert_eq!(format("int f(int n) {\nfor (;;)\nfor (;;)\nif (n) {\nreturn (char) n;\n}\nelse if (n > 1)\nreturn 1;\nelse\nreturn 0;\nint a[2][2] = { {1,2} , {3,4} };\n}\n"), "\
+int f(int n) {
+    for (;;)
+        for (;;)
+            if (n) {
+                return (char)n;
+            }
+            else if (n > 1)
+                return 1;
+            else
+                return 0;
+    int a[2][2] = {{1, 2}, {3, 4}};
+}
+");
+    }
+
+    #[test]
+    fn lines_that_carry_on_are_indented_once_more() {
+        assert_eq!(format("int a[] = {\n1,\n2\n};\nint f() {\nreturn g(1,\n2) +\n3;\n}\n"), "\
+int a[] = {
+    1,
+    2
+};
+int f() {
+    return g(1,
+        2) +
+        3;
+}
+");
+    }
+
+    #[test]
+    fn comments_stay_on_their_lines() {
+        let source = "// leading\nint f(){return 1;}   // trailing\n\n\n\n/* between\n   functions */\nint g(){\n  /* inside */ return /* mid */2;\n}\n";
+        assert_eq!(format(source), "\
+// leading
+int f() { return 1; } // trailing
+
+/* between
+   functions */
+int g() {
+    /* inside */ return /* mid */ 2;
+}
+");
+    }
+
+    #[test]
+    fn the_indent_width_can_be_chosen() {
+        let options = FormatOptions { indent_width: 2 };
+        assert_eq!(format_source("int main() {\nif (1) {\nreturn 0;\n}\n}", &options).unwrap(), "int main() {\n  if (1) {\n    return 0;\n  }\n}\n");
+    }
+
+    #[test]
+    fn pragmas_start_their_lines() {
+        assert_eq!(format("int main() {\n    #pragma GCC diagnostic push   \nreturn 0;\n}"), "int main() {\n#pragma GCC diagnostic push\n    return 0;\n}\n");
+    }
+
+    #[test]
+    fn what_cant_be_lexed_is_an_error() {
+        assert_eq!(format_source("int x = 1 @ 2;", &FormatOptions::default()), Err(LexError::UnrecognizedInput { line: 1, column: 11 }));
+        assert_eq!(format(""), "");
+    }
+}
@@ -0,0 +1,183 @@
+//! Classifies each piece of a program for syntax highlighting, from what the
+//! compiler itself makes of it rather than from patterns.
+//!
+//! Everything the lexer can make out is classified, so that a program that is
+//! being written, and so has errors, is still highlighted: what can't be
+//! lexed is left out, and the rest is classified from the lexemes alone where
+//! the program doesn't parse.
+
+use std::collections::HashSet;
+use lexer::{self, Lexeme, LexemeKind};
+use parser;
+use resolve::{self, Symbol};
+use standard::Standard;
+
+/// What a span of the source is, for choosing how to show it.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[non_exhaustive]
+pub enum Category {
+    Keyword,
+    /// An identifier that isn't any of the names below, such as a variable
+    /// or a struct member.
+    Identifier,
+    /// An identifier that is called, or that names a function.
+    FunctionName,
+    /// A name declared by a `typedef`, or a struct's tag.
+    TypeName,
+    Number,
+    /// A string or character literal.
+    String,
+    Comment,
+    Operator,
+    /// Brackets and separators: `( ) [ ] { } ; , : ...`.
+    Punctuation,
+    /// A `#pragma` line.
+    Directive,
+}
+
+/// A span of the source and what it is.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ClassifiedSpan {
+    /// The byte offsets of the span's first character and of the character
+    /// after its last.
+    pub start: usize,
+    pub end: usize,
+    /// The line and column the span starts at.
+    pub line: usize,
+    pub column: usize,
+    pub category: Category,
+}
+
+/// Classifies `source`, a program written in the default standard, in the
+/// order the spans are in it.
+pub fn classify(source: &str) -> Vec<ClassifiedSpan> {
+    classify_with(source, Standard::default())
+}
+
+/// Like `classify`, but for a program written in `standard`.
+pub fn classify_with(source: &str, standard: Standard) -> Vec<ClassifiedSpan> {
+    let (lexemes, lex_errors) = lexer::lex_with_comments(source, standard);
+    let code: Vec<Lexeme> = lexemes.iter().filter(|lexeme| !is_comment(lexeme)).cloned().collect();
+
+    // The parser knows which names are typedefs even in a program it can't
+    // parse all of, but names are only resolved in one that parses.
+    let (program, parse_errors) = parser::parse_program_partial_with(&code, standard);
+    let typedefs: HashSet<&str> = program.typedefs.iter().map(String::as_str).collect();
+    let mut functions = HashSet::new();
+    if lex_errors.is_empty() && parse_errors.is_empty() {
+        let (resolutions, _) = resolve::resolve(&program);
+        for (&expr, symbol) in &resolutions {
+            if let Symbol::Function(_) = *symbol {
+                functions.insert(program.ast[expr].span.start);
+            }
+        }
+    }
+
+    lexemes.iter().enumerate().map(|(index, lexeme)| {
+        let previous = lexemes[..index].iter().rev().find(|lexeme| !is_comment(lexeme)).map(|lexeme| &lexeme.kind);
+        let next = lexemes[index + 1..].iter().find(|lexeme| !is_comment(lexeme)).map(|lexeme| &lexeme.kind);
+        let category = match lexeme.kind {
+            LexemeKind::Identifier(name) => {
+                if typedefs.contains(name) || previous == Some(&LexemeKind::Keyword("struct")) {
+                    Category::TypeName
+                } else if next == Some(&LexemeKind::OpenParen) || functions.contains(&lexeme.start) {
+                    Category::FunctionName
+                } else {
+                    Category::Identifier
+                }
+            },
+            ref kind => category(kind),
+        };
+        ClassifiedSpan { start: lexeme.start, end: lexeme.end, line: lexeme.line, column: lexeme.column, category }
+    }).collect()
+}
+
+fn is_comment(lexeme: &Lexeme) -> bool {
+    matches!(lexeme.kind, LexemeKind::Comment(_))
+}
+
+/// The category of a lexeme that isn't an identifier.
+fn category(kind: &LexemeKind) -> Category {
+    match *kind {
+        LexemeKind::Keyword(_) => Category::Keyword,
+        LexemeKind::Identifier(_) => Category::Identifier,
+        LexemeKind::IntLiteral(..) => Category::Number,
+        LexemeKind::CharLiteral(_) | LexemeKind::StringLiteral(_) => Category::String,
+        LexemeKind::Comment(_) | LexemeKind::Whitespace(_) => Category::Comment,
+        LexemeKind::Pragma(_) => Category::Directive,
+        LexemeKind::OpenBrace
+        | LexemeKind::CloseBrace
+        | LexemeKind::OpenParen
+        | LexemeKind::CloseParen
+        | LexemeKind::OpenBracket
+        | LexemeKind::CloseBracket
+        | LexemeKind::Semicolon
+        | LexemeKind::Comma
+        | LexemeKind::Colon
+        | LexemeKind::Ellipsis => Category::Punctuation,
+        _ => Category::Operator,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The text of each span of `source` and its category.
+    fn classified(source: &str) -> Vec<(&str, Category)> {
+        classify(source).into_iter().map(|span| (&source[span.start..span.end], span.category)).collect()
+    }
+
+    #[test]
+    fn every_category_is_found() {
+        use self::Category::*;
+        let source = "#pragma once\n\
+                      struct point { int x; };\n\
+                      typedef struct point point_t;\n\
+                      int square(int x) { return x * x; }\n\
+                      int main() {\n\
+                          /* both */ point_t p; char *s = \"s\";\n\
+                          p.x = square ? 'a' : 0;\n\
+                          return square(p.x) + sizeof(int); // done\n\
+                      }\n";
+        assert_eq!(classified(source), vec![
+            ("#pragma once", Directive),
+            ("struct", Keyword), ("point", TypeName), ("{", Punctuation), ("int", Keyword), ("x", Identifier), (";", Punctuation),
+            ("}", Punctuation), (";", Punctuation),
+            ("typedef", Keyword), ("struct", Keyword), ("point", TypeName), ("point_t", TypeName), (";", Punctuation),
+            ("int", Keyword), ("square", FunctionName), ("(", Punctuation), ("int", Keyword), ("x", Identifier), (")", Punctuation),
+            ("{", Punctuation), ("return", Keyword), ("x", Identifier), ("*", Operator), ("x", Identifier), (";", Punctuation), ("}", Punctuation),
+            ("int", Keyword), ("main", FunctionName), ("(", Punctuation), (")", Punctuation), ("{", Punctuation),
+            ("/* both */", Comment), ("point_t", TypeName), ("p", Identifier), (";", Punctuation),
+            ("char", Keyword), ("*", Operator), ("s", Identifier), ("=", Operator), ("\"s\"", String), (";", Punctuation),
+            // Only the names resolution shows `square` to be a function.
+            ("p", Identifier), (".", Operator), ("x", Identifier), ("=", Operator), ("square", FunctionName), ("?", Operator),
+            ("'a'", String), (":", Punctuation), ("0", Number), (";", Punctuation),
+            ("return", Keyword), ("square", FunctionName), ("(", Punctuation), ("p", Identifier), (".", Operator), ("x", Identifier),
+            (")", Punctuation), ("+", Operator), ("sizeof", Keyword), ("(", Punctuation), ("int", Keyword), (")", Punctuation),
+            (";", Punctuation), ("// done", Comment),
+            ("}", Punctuation),
+        ]);
+        let main = classify(source).into_iter().find(|span| span.category == FunctionName && span.line == 5).unwrap();
+        assert_eq!((main.start, main.end, main.column), (source.find("main").unwrap(), source.find("main").unwrap() + 4, 5));
+    }
+
+    #[test]
+    fn broken_programs_are_still_classified() {
+        use self::Category::*;
+        // Neither lexes nor parses: `@` is left out, and what comes after it
+        // is classified as before it.
+        let source = "typedef int num;\nnum f(num a) { return a @ 1 }\nint g() { return f(2); }";
+        assert_eq!(classified(source), vec![
+            ("typedef", Keyword), ("int", Keyword), ("num", TypeName), (";", Punctuation),
+            ("num", TypeName), ("f", FunctionName), ("(", Punctuation), ("num", TypeName), ("a", Identifier), (")", Punctuation),
+            ("{", Punctuation), ("return", Keyword), ("a", Identifier), ("1", Number), ("}", Punctuation),
+            ("int", Keyword), ("g", FunctionName), ("(", Punctuation), (")", Punctuation), ("{", Punctuation),
+            ("return", Keyword), ("f", FunctionName), ("(", Punctuation), ("2", Number), (")", Punctuation), (";", Punctuation),
+            ("}", Punctuation),
+        ]);
+
+        // The rest of an unclosed comment is a comment.
+        assert_eq!(classified("int x; /* todo"), vec![("int", Keyword), ("x", Identifier), (";", Punctuation), ("/* todo", Comment)]);
+    }
+}
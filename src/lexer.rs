@@ -1,47 +1,285 @@
-/// Responsible for lexing the source input.
-/// This is the first of three parsing stages.
+//! Responsible for lexing the source input.
+//! This is the first of three parsing stages.
 
 use std::vec::Vec;
 use std::collections::HashSet;
 use std::iter::FromIterator;
+use std::error::Error;
+use std::fmt;
 use regex::Regex;
+use standard::{Feature, Standard};
+use ast::Type;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum LexemeKind<'a> {
     Whitespace(&'a str),
+    /// A `/* */` or `//` comment, which is skipped like whitespace.
+    Comment(&'a str),
+    /// A `#pragma` line, which the parser sets aside for the stages it has
+    /// to do with.
+    Pragma(&'a str),
     OpenBrace,
     CloseBrace,
     OpenParen,
     CloseParen,
+    OpenBracket,
+    CloseBracket,
     Semicolon,
+    Comma,
+    Dot,
+    Ellipsis,
+    Arrow,
+    QuestionMark,
+    Colon,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    Bang,
+    Assign,
+    PlusAssign,
+    MinusAssign,
+    StarAssign,
+    SlashAssign,
+    PercentAssign,
+    AmpersandAssign,
+    PipeAssign,
+    CaretAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
+    Increment,
+    Decrement,
+    ShiftLeft,
+    ShiftRight,
+    LogicalAnd,
+    LogicalOr,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
     Keyword(&'a str),
     Identifier(&'a str),
-    IntLiteral(i32),
+    /// An integer literal's value and type, which its suffix and how large it
+    /// is decide. An `unsigned long` too large for an `i64` wraps around.
+    IntLiteral(i64, Type),
+    /// A character constant, with any escape sequence already decoded.
+    CharLiteral(u8),
+    /// The bytes of a string literal, with escape sequences decoded and no terminator.
+    StringLiteral(Vec<u8>),
 }
 
-#[derive(Debug, PartialEq)]
+impl<'a> LexemeKind<'a> {
+    /// Describes this lexeme for diagnostics, e.g. ``identifier `foo` `` or `` `;` ``.
+    pub fn describe(&self) -> String {
+        match *self {
+            LexemeKind::Whitespace(_) => "whitespace".to_owned(),
+            LexemeKind::Comment(_) => "comment".to_owned(),
+            LexemeKind::Identifier(_) => format!("identifier `{}`", self),
+            LexemeKind::IntLiteral(..) => format!("integer literal `{}`", self),
+            LexemeKind::CharLiteral(_) => format!("character literal `{}`", self),
+            LexemeKind::StringLiteral(_) => format!("string literal `{}`", self),
+            _ => format!("`{}`", self),
+        }
+    }
+}
+
+/// Writes the lexeme as it would be spelled in source code.
+impl<'a> fmt::Display for LexemeKind<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match *self {
+            LexemeKind::Whitespace(text)
+            | LexemeKind::Comment(text)
+            | LexemeKind::Pragma(text)
+            | LexemeKind::Keyword(text)
+            | LexemeKind::Identifier(text) => text,
+            LexemeKind::IntLiteral(value, ref ty) => return f.write_str(&spell_int(value, ty)),
+            LexemeKind::CharLiteral(value) => return write!(f, "'{}'", escape(&[value], b'\'')),
+            LexemeKind::StringLiteral(ref bytes) => return write!(f, "\"{}\"", escape(bytes, b'"')),
+            LexemeKind::OpenBrace => "{",
+            LexemeKind::CloseBrace => "}",
+            LexemeKind::OpenParen => "(",
+            LexemeKind::CloseParen => ")",
+            LexemeKind::OpenBracket => "[",
+            LexemeKind::CloseBracket => "]",
+            LexemeKind::Semicolon => ";",
+            LexemeKind::Comma => ",",
+            LexemeKind::Dot => ".",
+            LexemeKind::Ellipsis => "...",
+            LexemeKind::Arrow => "->",
+            LexemeKind::QuestionMark => "?",
+            LexemeKind::Colon => ":",
+            LexemeKind::Plus => "+",
+            LexemeKind::Minus => "-",
+            LexemeKind::Star => "*",
+            LexemeKind::Slash => "/",
+            LexemeKind::Percent => "%",
+            LexemeKind::Ampersand => "&",
+            LexemeKind::Pipe => "|",
+            LexemeKind::Caret => "^",
+            LexemeKind::Tilde => "~",
+            LexemeKind::Bang => "!",
+            LexemeKind::Assign => "=",
+            LexemeKind::PlusAssign => "+=",
+            LexemeKind::MinusAssign => "-=",
+            LexemeKind::StarAssign => "*=",
+            LexemeKind::SlashAssign => "/=",
+            LexemeKind::PercentAssign => "%=",
+            LexemeKind::AmpersandAssign => "&=",
+            LexemeKind::PipeAssign => "|=",
+            LexemeKind::CaretAssign => "^=",
+            LexemeKind::ShiftLeftAssign => "<<=",
+            LexemeKind::ShiftRightAssign => ">>=",
+            LexemeKind::Increment => "++",
+            LexemeKind::Decrement => "--",
+            LexemeKind::ShiftLeft => "<<",
+            LexemeKind::ShiftRight => ">>",
+            LexemeKind::LogicalAnd => "&&",
+            LexemeKind::LogicalOr => "||",
+            LexemeKind::Equal => "==",
+            LexemeKind::NotEqual => "!=",
+            LexemeKind::LessThan => "<",
+            LexemeKind::LessEqual => "<=",
+            LexemeKind::GreaterThan => ">",
+            LexemeKind::GreaterEqual => ">=",
+        };
+
+        f.write_str(symbol)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Lexeme<'a> {
     pub kind: LexemeKind<'a>,
     pub line: usize,
     pub column: usize,
+    /// The byte offsets of the lexeme's first character and of the character after its last.
+    pub start: usize,
+    pub end: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum LexError {
     UnrecognizedInput { line: usize, column: usize },
+    /// An integer literal too large for any integer type.
+    IntLiteralOutOfRange { line: usize, column: usize },
+    /// Something the standard being lexed doesn't have.
+    NeedsStandard { feature: Feature, line: usize, column: usize },
+    /// A comment or literal that is never closed, at the delimiter that opens
+    /// it. `end` is where what it could have been closed in ends: the input,
+    /// for a comment, or the line, for a literal, which can't go on to the
+    /// next. `end_of_input` is whether that is the end of the input.
+    Unterminated { delimited: Delimited, line: usize, column: usize, end: (usize, usize), end_of_input: bool },
 }
 
+/// What a `LexError::Unterminated` is about.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Delimited {
+    Comment,
+    String,
+    Char,
+}
+
+impl fmt::Display for Delimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Delimited::Comment => "block comment",
+            Delimited::String => "string literal",
+            Delimited::Char => "character literal",
+        })
+    }
+}
+
+impl LexError {
+    /// The code of the error, from `codes::CODES`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            LexError::UnrecognizedInput { .. } => "E0001",
+            LexError::IntLiteralOutOfRange { .. } => "E0002",
+            LexError::NeedsStandard { .. } => "E0003",
+            LexError::Unterminated { delimited: Delimited::Comment, .. } => "E0004",
+            LexError::Unterminated { delimited: Delimited::String, .. } => "E0005",
+            LexError::Unterminated { delimited: Delimited::Char, .. } => "E0006",
+        }
+    }
+
+    /// The other places the error has to do with, each with what it has to do
+    /// with them.
+    pub fn labels(&self) -> Vec<((usize, usize), &'static str)> {
+        match *self {
+            LexError::Unterminated { delimited, end, end_of_input: true, .. } => match delimited {
+                Delimited::Comment => vec![(end, "the file ends here, before a `*/` closes the comment")],
+                Delimited::String | Delimited::Char => vec![(end, "the file ends here, with no closing quote")],
+            },
+            LexError::Unterminated { end, end_of_input: false, .. } => vec![(end, "the line ends here, with no closing quote")],
+            _ => Vec::new(),
+        }
+    }
+
+    /// What the error is, without where: a diagnostic shows that itself.
+    pub fn message(&self) -> String {
+        match *self {
+            LexError::UnrecognizedInput { .. } => "unrecognized input".to_owned(),
+            LexError::IntLiteralOutOfRange { .. } => "integer literal is too large".to_owned(),
+            LexError::NeedsStandard { feature, .. } => format!("{} {}", feature, feature.requirement()),
+            LexError::Unterminated { delimited, .. } => format!("unterminated {}", delimited),
+        }
+    }
+
+    /// The line and column the error is at.
+    pub fn position(&self) -> (usize, usize) {
+        match *self {
+            LexError::UnrecognizedInput { line, column }
+            | LexError::IntLiteralOutOfRange { line, column }
+            | LexError::NeedsStandard { line, column, .. }
+            | LexError::Unterminated { line, column, .. } => (line, column),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LexError::NeedsStandard { feature, line, column } =>
+                write!(f, "{} at {}:{} {}", feature, line, column, feature.requirement()),
+            _ => {
+                let (line, column) = self.position();
+                write!(f, "{} at {}:{}", self.message(), line, column)
+            },
+        }
+    }
+}
+
+impl Error for LexError {}
+
 // All the patterns that are used to match stuff
 lazy_static! {
     static ref KEYWORDS: HashSet<&'static str> = HashSet::from_iter(vec![
-        "return", "int"
+        "return", "int", "if", "else", "for", "while", "do", "break", "continue", "goto",
+        "typedef", "char", "struct", "static", "extern",
+        "const", "sizeof", "long", "signed", "unsigned", "switch", "case", "default"
     ]);
+    /// The keywords that C99 adds, which are identifiers before it.
+    static ref C99_KEYWORDS: HashSet<&'static str> = HashSet::from_iter(vec!["inline", "restrict", "_Bool"]);
 
     static ref AFTER_LAST_NEWLINE_REGEX: Regex = Regex::new(r"\n([^\n]*)$").unwrap();
     static ref WHITESPACE_REGEX: Regex = Regex::new(r"^\s+").unwrap();
-    static ref IDENTIFIER_REGEX: Regex = Regex::new(r"^[a-zA-Z]\w*").unwrap();
-    static ref INT_LITERAL_REGEX: Regex = Regex::new(r"^[0-9]+").unwrap();
-    static ref SYMBOL_REGEX: Regex = Regex::new(r"^[\(\)\{\};]").unwrap();
+    static ref COMMENT_REGEX: Regex = Regex::new(r"^(/\*(?s:.)*?\*/|//[^\n]*)").unwrap();
+    static ref PRAGMA_REGEX: Regex = Regex::new(r"^#[ \t]*pragma\b[^\n]*").unwrap();
+    static ref IDENTIFIER_REGEX: Regex = Regex::new(r"^[a-zA-Z_]\w*").unwrap();
+    static ref INT_LITERAL_REGEX: Regex = Regex::new(r"^(0[xX][0-9a-fA-F]+|[0-9]+)([uU][lL]?|[lL][uU]?)?").unwrap();
+    static ref CHAR_LITERAL_REGEX: Regex = Regex::new(r"^'(\\x[0-9a-fA-F]+|\\[0-7]{1,3}|\\[^\n]|[^'\\\n])'").unwrap();
+    static ref STRING_LITERAL_REGEX: Regex = Regex::new(r#"^"(\\[^\n]|[^"\\\n])*""#).unwrap();
+    // Longer symbols come first so that e.g. `<<=` isn't lexed as `<` `<=`.
+    static ref SYMBOL_REGEX: Regex = Regex::new(
+        r"^(\.\.\.|<<=|>>=|\+\+|--|->|&&|\|\||<<|>>|[-+*/%&|^!=<>]=|[-+*/%&|^!~=<>?:,.;\(\)\{\}\[\]])"
+    ).unwrap();
 }
 
 fn try_get<'a, F>(current_input: &'a str, pattern: &Regex, transformer: F) -> Option<(&'a str, &'a str, LexemeKind<'a>)>
@@ -57,8 +295,18 @@ where F: Fn(&'a str) -> LexemeKind<'a>,
     }
 }
 
-fn convert_identifier_str<'a>(identifier: &'a str) -> LexemeKind<'a> {
-    if KEYWORDS.contains(identifier) {
+/// The keywords of `standard`, in alphabetical order.
+pub fn keywords(standard: Standard) -> Vec<&'static str> {
+    let mut keywords: Vec<_> = KEYWORDS.iter().cloned().collect();
+    if standard.has(Feature::Inline) {
+        keywords.extend(C99_KEYWORDS.iter().cloned());
+    }
+    keywords.sort_unstable();
+    keywords
+}
+
+fn convert_identifier_str(identifier: &str, standard: Standard) -> LexemeKind<'_> {
+    if KEYWORDS.contains(identifier) || (standard >= Standard::C99 && C99_KEYWORDS.contains(identifier)) {
         LexemeKind::Keyword(identifier)
     }
     else {
@@ -72,71 +320,357 @@ fn convert_symbol_str<'a>(symbol: &'a str) -> LexemeKind<'a> {
         "}" => LexemeKind::CloseBrace,
         "(" => LexemeKind::OpenParen,
         ")" => LexemeKind::CloseParen,
+        "[" => LexemeKind::OpenBracket,
+        "]" => LexemeKind::CloseBracket,
         ";" => LexemeKind::Semicolon,
+        "," => LexemeKind::Comma,
+        "." => LexemeKind::Dot,
+        "..." => LexemeKind::Ellipsis,
+        "->" => LexemeKind::Arrow,
+        "?" => LexemeKind::QuestionMark,
+        ":" => LexemeKind::Colon,
+        "+" => LexemeKind::Plus,
+        "-" => LexemeKind::Minus,
+        "*" => LexemeKind::Star,
+        "/" => LexemeKind::Slash,
+        "%" => LexemeKind::Percent,
+        "&" => LexemeKind::Ampersand,
+        "|" => LexemeKind::Pipe,
+        "^" => LexemeKind::Caret,
+        "~" => LexemeKind::Tilde,
+        "!" => LexemeKind::Bang,
+        "=" => LexemeKind::Assign,
+        "+=" => LexemeKind::PlusAssign,
+        "-=" => LexemeKind::MinusAssign,
+        "*=" => LexemeKind::StarAssign,
+        "/=" => LexemeKind::SlashAssign,
+        "%=" => LexemeKind::PercentAssign,
+        "&=" => LexemeKind::AmpersandAssign,
+        "|=" => LexemeKind::PipeAssign,
+        "^=" => LexemeKind::CaretAssign,
+        "<<=" => LexemeKind::ShiftLeftAssign,
+        ">>=" => LexemeKind::ShiftRightAssign,
+        "++" => LexemeKind::Increment,
+        "--" => LexemeKind::Decrement,
+        "<<" => LexemeKind::ShiftLeft,
+        ">>" => LexemeKind::ShiftRight,
+        "&&" => LexemeKind::LogicalAnd,
+        "||" => LexemeKind::LogicalOr,
+        "==" => LexemeKind::Equal,
+        "!=" => LexemeKind::NotEqual,
+        "<" => LexemeKind::LessThan,
+        "<=" => LexemeKind::LessEqual,
+        ">" => LexemeKind::GreaterThan,
+        ">=" => LexemeKind::GreaterEqual,
         _ => unreachable!()
     }
 }
 
-fn get_next_token<'a>(current_input: &'a str) -> Option<(&'a str, &'a str, LexemeKind<'a>)> {
-    try_get(current_input, &WHITESPACE_REGEX, |s| LexemeKind::Whitespace(s))
-        .or_else(|| try_get(current_input, &IDENTIFIER_REGEX, convert_identifier_str))
+/// Decodes the escape sequences in the body of a character or string literal.
+/// Unknown escapes stand for the escaped character itself, as in GCC.
+fn unescape(body: &str) -> Vec<u8> {
+    let bytes = body.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] != b'\\' {
+            result.push(bytes[index]);
+            index += 1;
+            continue;
+        }
+
+        index += 1;
+        let escaped = bytes[index];
+        index += 1;
+        let value = match escaped {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            b'x' => {
+                let mut value: u32 = 0;
+                while index < bytes.len() && (bytes[index] as char).is_ascii_hexdigit() {
+                    value = value.wrapping_mul(16).wrapping_add((bytes[index] as char).to_digit(16).unwrap());
+                    index += 1;
+                }
+                value as u8
+            },
+            b'0'..=b'7' => {
+                let mut value = u32::from(escaped - b'0');
+                let mut digits = 1;
+                while digits < 3 && index < bytes.len() && bytes[index] >= b'0' && bytes[index] <= b'7' {
+                    value = value * 8 + u32::from(bytes[index] - b'0');
+                    index += 1;
+                    digits += 1;
+                }
+                value as u8
+            },
+            other => other,
+        };
+        result.push(value);
+    }
+
+    result
+}
+
+/// Escapes bytes for use inside a character or string literal delimited by `quote`,
+/// such that `unescape` gives back the original bytes.
+pub fn escape(bytes: &[u8], quote: u8) -> String {
+    let mut result = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        match byte {
+            b'\n' => result.push_str("\\n"),
+            b'\t' => result.push_str("\\t"),
+            b'\r' => result.push_str("\\r"),
+            b'\\' => result.push_str("\\\\"),
+            _ if byte == quote => {
+                result.push('\\');
+                result.push(byte as char);
+            },
+            0x20..=0x7e => result.push(byte as char),
+            // Octal escapes take at most three digits, so they can't swallow a following digit.
+            _ => result.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    result
+}
+
+fn convert_char_literal_str<'a>(literal: &'a str) -> LexemeKind<'a> {
+    LexemeKind::CharLiteral(unescape(&literal[1..literal.len() - 1])[0])
+}
+
+fn convert_string_literal_str<'a>(literal: &'a str) -> LexemeKind<'a> {
+    LexemeKind::StringLiteral(unescape(&literal[1..literal.len() - 1]))
+}
+
+/// The value and type of a decimal, hexadecimal (`0x`) or octal (leading `0`)
+/// literal, with an optional `u` and `l` suffix in either order. Its type is
+/// the first of those the suffix allows that can hold it: `int`, then `long`,
+/// then `unsigned long`, with `unsigned int` after `int` for hexadecimal and
+/// octal literals.
+fn int_literal_value(literal: &str) -> Option<(i64, Type)> {
+    let digits = literal.trim_end_matches(|c| "uUlL".contains(c));
+    let suffix = literal[digits.len()..].to_lowercase();
+    let (digits, radix) = if digits.starts_with("0x") || digits.starts_with("0X") {
+        (&digits[2..], 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+    let value = u64::from_str_radix(digits, radix).ok()?;
+
+    let candidates: &[Type] = match (suffix.contains('u'), suffix.contains('l')) {
+        (false, false) if radix == 10 => &[Type::Int, Type::Long, Type::UnsignedLong],
+        (false, false) => &[Type::Int, Type::UnsignedInt, Type::Long, Type::UnsignedLong],
+        (true, false) => &[Type::UnsignedInt, Type::UnsignedLong],
+        (false, true) => &[Type::Long, Type::UnsignedLong],
+        (true, true) => &[Type::UnsignedLong],
+    };
+    let ty = candidates.iter().find(|ty| {
+        let max = match **ty {
+            Type::Int => i32::MAX as u64,
+            Type::UnsignedInt => u64::from(u32::MAX),
+            Type::Long => i64::MAX as u64,
+            _ => u64::MAX,
+        };
+        value <= max
+    });
+    ty.map(|ty| (value as i64, ty.clone()))
+}
+
+/// Spells an integer literal of `value` and `ty` with the suffix for that
+/// type, so that lexing it gives back both.
+pub fn spell_int(value: i64, ty: &Type) -> String {
+    match *ty {
+        Type::Long => format!("{}L", value),
+        Type::UnsignedInt => format!("{}u", value),
+        Type::UnsignedLong => format!("{}ul", value as u64),
+        _ => value.to_string(),
+    }
+}
+
+fn get_next_token(current_input: &str, standard: Standard) -> Option<(&str, &str, LexemeKind<'_>)> {
+    try_get(current_input, &WHITESPACE_REGEX, LexemeKind::Whitespace)
+        .or_else(|| try_get(current_input, &COMMENT_REGEX, LexemeKind::Comment))
+        .or_else(|| try_get(current_input, &PRAGMA_REGEX, LexemeKind::Pragma))
+        .or_else(|| try_get(current_input, &IDENTIFIER_REGEX, |s| convert_identifier_str(s, standard)))
+        .or_else(|| try_get(current_input, &CHAR_LITERAL_REGEX, convert_char_literal_str))
+        .or_else(|| try_get(current_input, &STRING_LITERAL_REGEX, convert_string_literal_str))
         .or_else(|| try_get(current_input, &SYMBOL_REGEX, convert_symbol_str))
-        .or_else(|| try_get(current_input, &INT_LITERAL_REGEX, |s| LexemeKind::IntLiteral(s.parse().unwrap())))
+        .or_else(|| try_get(current_input, &INT_LITERAL_REGEX, |s| {
+            let (value, ty) = int_literal_value(s).unwrap();
+            LexemeKind::IntLiteral(value, ty)
+        }))
+}
+
+/// Renders `lexemes`, lexed from `source`, one per line, as their kind, their
+/// text quoted, and the line and column they start at: `Keyword "int" 1:1`.
+pub fn pretty_print(source: &str, lexemes: &[Lexeme]) -> String {
+    let mut output = String::new();
+    for lexeme in lexemes {
+        let kind = format!("{:?}", lexeme.kind);
+        let kind = kind.split('(').next().unwrap();
+        output += &format!("{} {:?} {}:{}\n", kind, &source[lexeme.start..lexeme.end], lexeme.line, lexeme.column);
+    }
+    output
+}
+
+/// The line and column of the end of `text`, which starts at `line` and
+/// `column`. A newline it ends with is left out, so that the end of a file is
+/// the end of its last line, rather than the empty line after it.
+fn end_of(text: &str, line: usize, column: usize) -> (usize, usize) {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    match text.rfind('\n') {
+        Some(newline) => (line + text.matches('\n').count(), text.len() - newline),
+        None => (line, column + text.len()),
+    }
+}
+
+pub fn lex_str(input: &str) -> Result<Vec<Lexeme<'_>>, LexError> {
+    lex_str_with(input, Standard::default())
+}
+
+/// Like `lex_str`, but for a program written in `standard`.
+pub fn lex_str_with(input: &str, standard: Standard) -> Result<Vec<Lexeme<'_>>, LexError> {
+    let (lexemes, errors) = lex_all(input, standard);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(lexemes),
+    }
+}
+
+/// Lexes `input` as a program written in `standard` the whole way through,
+/// rather than stopping at the first error: what can't be lexed is skipped
+/// over a character at a time, and literals that don't fit and comments the
+/// standard doesn't have are left out. Gives the lexemes and every error, in
+/// the order they are in the input.
+pub fn lex_all(input: &str, standard: Standard) -> (Vec<Lexeme<'_>>, Vec<LexError>) {
+    lex(input, standard, false)
+}
+
+/// Like `lex_all`, but keeps the comments, for what shows the source as it is
+/// written. A `/*` that is never closed comments out the rest of the input.
+pub fn lex_with_comments(input: &str, standard: Standard) -> (Vec<Lexeme<'_>>, Vec<LexError>) {
+    lex(input, standard, true)
 }
 
-pub fn lex_str(input: &str) -> Result<Vec<Lexeme>, LexError> {
+fn lex(input: &str, standard: Standard, keep_comments: bool) -> (Vec<Lexeme<'_>>, Vec<LexError>) {
     let mut result = Vec::new();
+    let mut errors = Vec::new();
     let mut current_input = input;
     let mut current_line: usize = 1;
     let mut current_column: usize = 1;
 
-    loop {
-        if let Some((new_input, consumed_input, lexeme_kind)) = get_next_token(current_input) {
-            current_input = new_input;
-
-            // Skip over whitespace
-            match lexeme_kind {
-                LexemeKind::Whitespace(_) => {},
-                _ => result.push(Lexeme {
-                    kind: lexeme_kind,
-                    line: current_line,
-                    column: current_column,
-                }),
+    while !current_input.is_empty() {
+        // A comment that is never closed would be lexed as `/` and `*` and
+        // whatever is after them.
+        if current_input.starts_with("/*") && !current_input[2..].contains("*/") {
+            errors.push(LexError::Unterminated {
+                delimited: Delimited::Comment,
+                line: current_line,
+                column: current_column,
+                end: end_of(current_input, current_line, current_column),
+                end_of_input: true,
+            });
+            if keep_comments {
+                let start = input.len() - current_input.len();
+                result.push(Lexeme { kind: LexemeKind::Comment(current_input), line: current_line, column: current_column, start, end: input.len() });
             }
+            break;
+        }
 
-            // Now update the current line and column info
-            // Collect all the newlines in the string
-            let line_change_count = consumed_input.matches("\n").count();
-            current_line += line_change_count;
+        let start = input.len() - current_input.len();
+        let (consumed_input, lexeme_kind) = match INT_LITERAL_REGEX.find(current_input) {
+            // Converting a lexeme can't fail, so literals that don't fit are caught first.
+            Some(literal) if int_literal_value(literal.as_str()).is_none() => {
+                errors.push(LexError::IntLiteralOutOfRange { line: current_line, column: current_column });
+                (literal.as_str(), None)
+            },
+            _ => match get_next_token(current_input, standard) {
+                Some((_, consumed_input, _)) if consumed_input.starts_with("//") && !standard.has(Feature::LineComments) => {
+                    errors.push(LexError::NeedsStandard { feature: Feature::LineComments, line: current_line, column: current_column });
+                    (consumed_input, None)
+                },
+                // A `#pragma` has to start its line.
+                Some((_, _, LexemeKind::Pragma(_))) if !input[..start].rsplit('\n').next().unwrap().trim().is_empty() => {
+                    errors.push(LexError::UnrecognizedInput { line: current_line, column: current_column });
+                    (&current_input[..1], None)
+                },
+                Some((_, consumed_input, lexeme_kind)) => (consumed_input, Some(lexeme_kind)),
+                // A quote that doesn't start a literal is taken to start one
+                // that isn't closed, and the rest of the line to be in it,
+                // unless it is a character literal with more or less than a
+                // character in it.
+                None if current_input.starts_with('"') || current_input.starts_with('\'') => {
+                    let line_end = current_input.find('\n').unwrap_or(current_input.len());
+                    let rest_of_line = &current_input[..line_end];
+                    match rest_of_line[1..].find('\'') {
+                        Some(close) if rest_of_line.starts_with('\'') => {
+                            errors.push(LexError::UnrecognizedInput { line: current_line, column: current_column });
+                            (&rest_of_line[..close + 2], None)
+                        },
+                        _ => {
+                            errors.push(LexError::Unterminated {
+                                delimited: if rest_of_line.starts_with('"') { Delimited::String } else { Delimited::Char },
+                                line: current_line,
+                                column: current_column,
+                                end: end_of(rest_of_line, current_line, current_column),
+                                end_of_input: line_end == current_input.len(),
+                            });
+                            (rest_of_line, None)
+                        },
+                    }
+                },
+                None => {
+                    errors.push(LexError::UnrecognizedInput { line: current_line, column: current_column });
+                    let skipped = current_input.chars().next().unwrap().len_utf8();
+                    (&current_input[..skipped], None)
+                },
+            },
+        };
+        current_input = &current_input[consumed_input.len()..];
 
-            // If the line count changed...
-            if line_change_count > 0 {
-                // ...reset the column...
-                current_column = 1;
+        // Skip over whitespace and, unless they are kept, comments
+        match lexeme_kind {
+            None | Some(LexemeKind::Whitespace(_)) => {},
+            Some(LexemeKind::Comment(_)) if !keep_comments => {},
+            Some(lexeme_kind) => result.push(Lexeme {
+                kind: lexeme_kind,
+                line: current_line,
+                column: current_column,
+                start,
+                end: start + consumed_input.len(),
+            }),
+        }
 
-                // ...and increment by the amount of characters after the last newline.
-                if let Some(matched) = AFTER_LAST_NEWLINE_REGEX.find(consumed_input) {
-                    let amount = matched.as_str().len();
-                    current_column += amount;
-                }
-            }
-            // ...otherwise, just increment the column.
-            else {
-                current_column += consumed_input.len();
+        // Now update the current line and column info
+        // Collect all the newlines in the string
+        let line_change_count = consumed_input.matches("\n").count();
+        current_line += line_change_count;
+
+        // If the line count changed...
+        if line_change_count > 0 {
+            // ...reset the column...
+            current_column = 1;
+
+            // ...and increment by the amount of characters after the last newline.
+            if let Some(captures) = AFTER_LAST_NEWLINE_REGEX.captures(consumed_input) {
+                let amount = captures[1].len();
+                current_column += amount;
             }
-        } else {
-            break
+        }
+        // ...otherwise, just increment the column.
+        else {
+            current_column += consumed_input.len();
         }
     }
 
-    if !current_input.is_empty() {
-        return Err(LexError::UnrecognizedInput {
-            line: current_line,
-            column: current_column,
-        })
-    }
-
-    Ok(result)
+    (result, errors)
 }
 
 #[cfg(test)]
@@ -145,7 +679,7 @@ mod test {
 
     #[test]
     fn try_get_test() {
-        let (new_input, consumed_input, lexed_kind) = try_get("test", &IDENTIFIER_REGEX, |s| LexemeKind::Identifier(s)).unwrap();
+        let (new_input, consumed_input, lexed_kind) = try_get("test", &IDENTIFIER_REGEX, LexemeKind::Identifier).unwrap();
         assert_eq!(new_input, "");
         assert_eq!(consumed_input, "test");
         assert_eq!(lexed_kind, LexemeKind::Identifier("test"));
@@ -153,12 +687,12 @@ mod test {
 
     #[test]
     fn get_next_token_test() {
-        let (new_input, consumed_input, lexed_kind) = get_next_token("  test").unwrap();
+        let (new_input, consumed_input, lexed_kind) = get_next_token("  test", Standard::default()).unwrap();
         assert_eq!(new_input, "test");
         assert_eq!(consumed_input, "  ");
         assert_eq!(lexed_kind, LexemeKind::Whitespace("  "));
 
-        let (new_input, consumed_input, lexed_kind) = get_next_token(new_input).unwrap();
+        let (new_input, consumed_input, lexed_kind) = get_next_token(new_input, Standard::default()).unwrap();
         assert_eq!(new_input, "");
         assert_eq!(consumed_input, "test");
         assert_eq!(lexed_kind, LexemeKind::Identifier("test"));
@@ -173,20 +707,32 @@ mod test {
                 kind: LexemeKind::Identifier("test"),
                 line: 1,
                 column: 1,
+                start: 0,
+                end: 4,
             },
             Lexeme {
                 kind: LexemeKind::Identifier("foo"),
                 line: 1,
                 column: 6,
+                start: 5,
+                end: 8,
             }, 
             Lexeme {
                 kind: LexemeKind::Identifier("bar"),
                 line: 1,
                 column: 10,
+                start: 9,
+                end: 12,
             }
         ]);
     }
 
+    #[test]
+    fn identifiers_may_start_with_underscores() {
+        let kinds: Vec<LexemeKind> = lex_str("_ _x __y1").unwrap().into_iter().map(|lexeme| lexeme.kind).collect();
+        assert_eq!(kinds, vec![LexemeKind::Identifier("_"), LexemeKind::Identifier("_x"), LexemeKind::Identifier("__y1")]);
+    }
+
     #[test]
     fn lexing_identifiers_vs_keywords() {
         let lexed = lex_str("test return").unwrap();
@@ -195,11 +741,15 @@ mod test {
                 kind: LexemeKind::Identifier("test"),
                 line: 1,
                 column: 1,
+                start: 0,
+                end: 4,
             },
             Lexeme {
                 kind: LexemeKind::Keyword("return"),
                 line: 1,
                 column: 6,
+                start: 5,
+                end: 11,
             }
         ]);
     }
@@ -212,26 +762,36 @@ mod test {
                 kind: LexemeKind::OpenBrace,
                 line: 1,
                 column: 1,
+                start: 0,
+                end: 1,
             },
             Lexeme {
                 kind: LexemeKind::CloseBrace,
                 line: 1,
                 column: 2,
+                start: 1,
+                end: 2,
             },
             Lexeme {
                 kind: LexemeKind::OpenParen,
                 line: 1,
                 column: 3,
+                start: 2,
+                end: 3,
             },
             Lexeme {
                 kind: LexemeKind::CloseParen,
                 line: 1,
                 column: 4,
+                start: 3,
+                end: 4,
             },
             Lexeme {
                 kind: LexemeKind::Semicolon,
                 line: 1,
                 column: 5,
+                start: 4,
+                end: 5,
             },
         ]);
     }
@@ -241,15 +801,233 @@ mod test {
         let lexed = lex_str("123 456").unwrap();
         assert_eq!(lexed, vec![
             Lexeme {
-                kind: LexemeKind::IntLiteral(123),
+                kind: LexemeKind::IntLiteral(123, Type::Int),
                 line: 1,
                 column: 1,
+                start: 0,
+                end: 3,
             },
             Lexeme {
-                kind: LexemeKind::IntLiteral(456),
+                kind: LexemeKind::IntLiteral(456, Type::Int),
                 line: 1,
                 column: 5,
+                start: 4,
+                end: 7,
             }
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn int_literal_out_of_range() {
+        assert_eq!(lex_str("18446744073709551615").unwrap()[0].kind, LexemeKind::IntLiteral(-1, Type::UnsignedLong));
+        assert_eq!(lex_str("x = 18446744073709551616;").unwrap_err(), LexError::IntLiteralOutOfRange { line: 1, column: 5 });
+        assert_eq!(lex_str("x = 0x10000000000000000u;").unwrap_err(), LexError::IntLiteralOutOfRange { line: 1, column: 5 });
+    }
+
+    #[test]
+    fn int_literal_types() {
+        let types = |source| -> Vec<LexemeKind> { lex_str(source).unwrap().into_iter().map(|l| l.kind).collect() };
+        // Decimal literals go from `int` to `long`, and only then to `unsigned long`.
+        assert_eq!(types("2147483647 2147483648 9223372036854775808"), vec![
+            LexemeKind::IntLiteral(2147483647, Type::Int),
+            LexemeKind::IntLiteral(2147483648, Type::Long),
+            LexemeKind::IntLiteral(i64::MIN, Type::UnsignedLong),
+        ]);
+        // Hexadecimal and octal ones can be `unsigned int` first.
+        assert_eq!(types("0x80000000 037777777777 0x100000000"), vec![
+            LexemeKind::IntLiteral(2147483648, Type::UnsignedInt),
+            LexemeKind::IntLiteral(4294967295, Type::UnsignedInt),
+            LexemeKind::IntLiteral(4294967296, Type::Long),
+        ]);
+        assert_eq!(types("1u 2U 3l 4L 5ul 6LU 4294967296u 0x8000000000000000L"), vec![
+            LexemeKind::IntLiteral(1, Type::UnsignedInt),
+            LexemeKind::IntLiteral(2, Type::UnsignedInt),
+            LexemeKind::IntLiteral(3, Type::Long),
+            LexemeKind::IntLiteral(4, Type::Long),
+            LexemeKind::IntLiteral(5, Type::UnsignedLong),
+            LexemeKind::IntLiteral(6, Type::UnsignedLong),
+            LexemeKind::IntLiteral(4294967296, Type::UnsignedLong),
+            LexemeKind::IntLiteral(i64::MIN, Type::UnsignedLong),
+        ]);
+    }
+
+    #[test]
+    fn int_literals_are_spelled_with_the_suffix_for_their_type() {
+        let spelled: Vec<String> = lex_str("7 2147483648 0x80000000 0xffffffffffffffff 0l 0u").unwrap()
+            .into_iter()
+            .map(|l| l.kind.to_string())
+            .collect();
+        assert_eq!(spelled, ["7", "2147483648L", "2147483648u", "18446744073709551615ul", "0L", "0u"]);
+    }
+
+    #[test]
+    fn hexadecimal_and_octal_literals() {
+        let lexed: Vec<LexemeKind> = lex_str("0x0A 0XfF 012 0 0x7fffffff").unwrap().into_iter().map(|l| l.kind).collect();
+        assert_eq!(lexed, vec![
+            LexemeKind::IntLiteral(10, Type::Int),
+            LexemeKind::IntLiteral(255, Type::Int),
+            LexemeKind::IntLiteral(10, Type::Int),
+            LexemeKind::IntLiteral(0, Type::Int),
+            LexemeKind::IntLiteral(2147483647, Type::Int),
+        ]);
+    }
+
+    #[test]
+    fn multi_character_symbols() {
+        let lexed: Vec<LexemeKind> = lex_str("a<<=b>>c++->d!=e").unwrap().into_iter().map(|l| l.kind).collect();
+        assert_eq!(lexed, vec![
+            LexemeKind::Identifier("a"),
+            LexemeKind::ShiftLeftAssign,
+            LexemeKind::Identifier("b"),
+            LexemeKind::ShiftRight,
+            LexemeKind::Identifier("c"),
+            LexemeKind::Increment,
+            LexemeKind::Arrow,
+            LexemeKind::Identifier("d"),
+            LexemeKind::NotEqual,
+            LexemeKind::Identifier("e"),
+        ]);
+    }
+
+    #[test]
+    fn char_literals() {
+        let lexed: Vec<LexemeKind> = lex_str(r"'a' '\n' '\'' '\0' '\x41' '\101'").unwrap().into_iter().map(|l| l.kind).collect();
+        assert_eq!(lexed, vec![
+            LexemeKind::CharLiteral(b'a'),
+            LexemeKind::CharLiteral(b'\n'),
+            LexemeKind::CharLiteral(b'\''),
+            LexemeKind::CharLiteral(0),
+            LexemeKind::CharLiteral(b'A'),
+            LexemeKind::CharLiteral(b'A'),
+        ]);
+    }
+
+    #[test]
+    fn string_literals() {
+        let lexed = lex_str(r#"s = "hi \"there\"\n";"#).unwrap();
+        assert_eq!(lexed[2], Lexeme {
+            kind: LexemeKind::StringLiteral(b"hi \"there\"\n".to_vec()),
+            line: 1,
+            column: 5,
+            start: 4,
+            end: 20,
+        });
+        assert_eq!(lexed[3].column, 21);
+
+        // Strings may not span lines.
+        assert!(lex_str("\"abc\ndef\"").is_err());
+    }
+
+    #[test]
+    fn columns_after_newlines() {
+        let lexed = lex_str("a\nb\n  c").unwrap();
+        let positions: Vec<(usize, usize)> = lexed.iter().map(|l| (l.line, l.column)).collect();
+        assert_eq!(positions, vec![(1, 1), (2, 1), (3, 3)]);
+    }
+
+    #[test]
+    fn lexemes_are_printed_one_per_line() {
+        let source = "int x =\n  'a' + \"b c\";";
+        assert_eq!(pretty_print(source, &lex_str(source).unwrap()), "\
+Keyword \"int\" 1:1
+Identifier \"x\" 1:5
+Assign \"=\" 1:7
+CharLiteral \"'a'\" 2:3
+Plus \"+\" 2:7
+StringLiteral \"\\\"b c\\\"\" 2:9
+Semicolon \";\" 2:14
+");
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let lexed: Vec<LexemeKind> = lex_str("a /* b\n * c */ d // e\nf").unwrap().into_iter().map(|l| l.kind).collect();
+        assert_eq!(lexed, vec![LexemeKind::Identifier("a"), LexemeKind::Identifier("d"), LexemeKind::Identifier("f")]);
+        assert_eq!(lex_str("a /* b\n */ c").unwrap()[1].line, 2);
+        assert_eq!(lex_str("1 /2").unwrap()[1].kind, LexemeKind::Slash);
+    }
+
+    #[test]
+    fn comments_can_be_kept() {
+        let (lexemes, errors) = lex_with_comments("a /* b */ c // d
+e /* f", Standard::default());
+        let lexed: Vec<_> = lexemes.iter().map(|l| (l.kind.clone(), l.start, l.end)).collect();
+        assert_eq!(lexed, vec![
+            (LexemeKind::Identifier("a"), 0, 1),
+            (LexemeKind::Comment("/* b */"), 2, 9),
+            (LexemeKind::Identifier("c"), 10, 11),
+            (LexemeKind::Comment("// d"), 12, 16),
+            (LexemeKind::Identifier("e"), 17, 18),
+            (LexemeKind::Comment("/* f"), 19, 23),
+        ]);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn each_unterminated_construct_has_its_own_code() {
+        let code = |source| lex_str(source).unwrap_err().code();
+        assert_eq!((code("/* a"), code("\"a"), code("'a")), ("E0004", "E0005", "E0006"));
+    }
+
+    #[test]
+    fn unterminated_constructs_are_reported_where_they_start() {
+        let unterminated = |source| lex_all(source, Standard::default()).1;
+        assert_eq!(unterminated("a /* b\n  c */ d /* e\nfg\n"), vec![
+            LexError::Unterminated { delimited: Delimited::Comment, line: 2, column: 10, end: (3, 3), end_of_input: true },
+        ]);
+        assert_eq!(lex_str("a /* b").unwrap_err().to_string(), "unterminated block comment at 1:3");
+
+        // A literal can't go on past the end of its line.
+        assert_eq!(unterminated("s = \"abc;\nx = 'y;\nt = \"\\\""), vec![
+            LexError::Unterminated { delimited: Delimited::String, line: 1, column: 5, end: (1, 10), end_of_input: false },
+            LexError::Unterminated { delimited: Delimited::Char, line: 2, column: 5, end: (2, 8), end_of_input: false },
+            LexError::Unterminated { delimited: Delimited::String, line: 3, column: 5, end: (3, 8), end_of_input: true },
+        ]);
+        assert_eq!(lex_str("'ab' \"").unwrap_err(), LexError::UnrecognizedInput { line: 1, column: 1 });
+        assert_eq!(unterminated("'ab' \"").len(), 2);
+    }
+
+    #[test]
+    fn pragmas_are_whole_lines() {
+        let source = "#pragma once\n  # pragma GCC diagnostic push\nint x; #pragma once\n";
+        let (lexemes, errors) = lex_all(source, Standard::default());
+        assert_eq!(lexemes[0].kind, LexemeKind::Pragma("#pragma once"));
+        assert_eq!(lexemes[1].kind, LexemeKind::Pragma("# pragma GCC diagnostic push"));
+        assert_eq!((lexemes[1].line, lexemes[1].column), (2, 3));
+        assert_eq!(lexemes[2].kind, LexemeKind::Keyword("int"));
+        // One that doesn't start its line is a stray `#`.
+        assert_eq!(errors, vec![LexError::UnrecognizedInput { line: 3, column: 8 }]);
+    }
+
+    #[test]
+    fn every_error_is_found() {
+        let (lexemes, errors) = lex_all("a @ b\n99999999999999999999 `$ c // d\n/* e", Standard::C89);
+        let kinds: Vec<LexemeKind> = lexemes.into_iter().map(|lexeme| lexeme.kind).collect();
+        assert_eq!(kinds, vec![LexemeKind::Identifier("a"), LexemeKind::Identifier("b"), LexemeKind::Identifier("c")]);
+        assert_eq!(errors, vec![
+            LexError::UnrecognizedInput { line: 1, column: 3 },
+            LexError::IntLiteralOutOfRange { line: 2, column: 1 },
+            LexError::UnrecognizedInput { line: 2, column: 22 },
+            LexError::UnrecognizedInput { line: 2, column: 23 },
+            LexError::NeedsStandard { feature: Feature::LineComments, line: 2, column: 27 },
+            LexError::Unterminated { delimited: Delimited::Comment, line: 3, column: 1, end: (3, 5), end_of_input: true },
+        ]);
+        assert_eq!(lex_all("int x;", Standard::C89).1, vec![]);
+    }
+
+    #[test]
+    fn standards_decide_what_is_lexed() {
+        assert_eq!(
+            lex_str_with("x; // y", Standard::C89),
+            Err(LexError::NeedsStandard { feature: Feature::LineComments, line: 1, column: 4 }),
+        );
+        assert_eq!(
+            lex_str_with("x; // y", Standard::C89).unwrap_err().to_string(),
+            "`//` comments at 1:4 are a C99 feature; use --std=c99",
+        );
+        assert!(lex_str_with("x; /* y */", Standard::C89).is_ok());
+        assert_eq!(lex_str_with("inline", Standard::C89).unwrap()[0].kind, LexemeKind::Identifier("inline"));
+        assert_eq!(lex_str_with("inline", Standard::C99).unwrap()[0].kind, LexemeKind::Keyword("inline"));
+        assert_eq!(lex_str("_Bool").unwrap()[0].kind, LexemeKind::Keyword("_Bool"));
+    }
+}
@@ -0,0 +1,42 @@
+#[macro_use]
+extern crate lazy_static;
+extern crate regex;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
+pub mod lexer;
+pub mod ast;
+pub mod codegen;
+pub mod const_eval;
+pub mod flow;
+pub mod parser;
+pub mod pragma;
+pub mod resolve;
+pub mod statics;
+pub mod switches;
+pub mod typecheck;
+pub mod uninitialized;
+pub mod unused;
+pub mod semantic;
+pub mod diagnostics;
+pub mod codes;
+pub mod suggest;
+pub mod standard;
+pub mod source;
+pub mod compile;
+pub mod format;
+pub mod highlight;
+#[cfg(feature = "serde")]
+pub mod playground;
+#[cfg(feature = "capi")]
+pub mod capi;
+
+pub use compile::{compile_str, CompileError, CompileOptions, CompileOutput, Compiler, Emit};
+pub use format::{format_source, FormatOptions};
+pub use highlight::{classify, Category, ClassifiedSpan};
+#[cfg(feature = "fs")]
+pub use compile::compile_file;
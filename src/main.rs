@@ -1,9 +1,915 @@
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
+extern crate rust_cc;
 
-mod lexer;
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, IsTerminal, Write};
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rust_cc::{codegen, codes, compile, diagnostics, CompileOptions, Emit};
+use rust_cc::diagnostics::{Diagnostic, SourceMap};
+use rust_cc::format::{format_source, FormatOptions};
+use rust_cc::semantic::{Severity, WarningSettings};
+use rust_cc::source::Source;
+use rust_cc::standard::Standard;
+
+const USAGE: &str = "usage: rust-cc [-c | -S | --emit <kind>[,<kind>]...] [--spans] [--error-format=human | --error-format=short | --error-format=json] [--color=auto | --color=always | --color=never] [--max-errors=<n>] \
+                     [--std=c89 | --std=c99 | --std=c11] \
+                     [-w | -Werror | -W[no-]<warning> | -W[no-]error=<warning>]... [-O0 | -O1 | -O2] [--no-<pass>]... [-f[no-]omit-frame-pointer] [--timings] \
+                     [-j <jobs>] [--target <target>] [--cc <program>] [--save-temps] [--verbose] [-o <output>] <file>...\n\
+                     <file> can be `-`, to read standard input\n\
+                     rust-cc --explain <code> explains the code of a diagnostic, as in `error[E0102]`\n\
+                     rust-cc --format [--indent=<n>] <file>... prints each file formatted, indenting by <n> spaces, 4 by default\n\
+                     as with gcc, flags can come in any order, -std=, -g, -I and -D are accepted, and so are -f and -W flags \
+                     that aren't known; --verbose says which do nothing\n\
+                     kinds: tokens, ast, ast-dot, ast-json, llvm-ir, wat, asm\n\
+                     targets: x86_64-linux, x86_64-macos, x86_64-windows, aarch64-linux, aarch64-macos";
+
+/// The extension of the file what is emitted as `emit` is written to.
+fn extension(emit: Emit) -> &'static str {
+    match emit {
+        Emit::Tokens => "tokens",
+        Emit::Ast => "ast",
+        Emit::AstDot => "dot",
+        #[cfg(feature = "serde")]
+        Emit::AstJson => "json",
+        Emit::LlvmIr => "ll",
+        Emit::Wat => "wat",
+        Emit::Assembly | _ => "s",
+    }
+}
+
+/// Makes a directory of its own in the temporary directory, for what is
+/// assembled, named so that it can't be guessed, and only open to this user on
+/// Unix. It fails if anything has that name already, so that nothing is ever
+/// written into, or removed with, a directory made by someone else.
+fn scratch_directory() -> io::Result<PathBuf> {
+    // Each `RandomState` has keys of its own, seeded from the system.
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos()));
+    let directory = env::temp_dir().join(format!("rust-cc-{}-{:016x}", process::id(), hasher.finish()));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(&directory)?;
+    Ok(directory)
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// The stack each thread that compiles is given, as much as the main thread
+/// has, so that nothing compiles on one that wouldn't on the other.
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+#[cfg(feature = "serde")]
+fn diagnostic_json(map: &SourceMap, diagnostic: &Diagnostic) -> String {
+    diagnostics::to_json(map, diagnostic)
+}
+
+#[cfg(not(feature = "serde"))]
+fn diagnostic_json(_: &SourceMap, _: &Diagnostic) -> String {
+    unreachable!("--error-format=json isn't accepted without the `serde` feature");
+}
+
+#[cfg(feature = "serde")]
+fn bare_diagnostic_json(diagnostic: &Diagnostic) -> String {
+    diagnostics::bare_to_json(diagnostic)
+}
+
+#[cfg(not(feature = "serde"))]
+fn bare_diagnostic_json(_: &Diagnostic) -> String {
+    unreachable!("--error-format=json isn't accepted without the `serde` feature");
+}
+
+/// What is made of the input files.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Mode {
+    /// What `--emit` asks for.
+    Emit,
+    /// Assembly, with `-S`.
+    Assembly,
+    /// An object file, assembled by `Settings::cc` from the assembly, with
+    /// `-c`.
+    Object,
+    /// An executable, linked by `Settings::cc` from the assembly.
+    Link,
+}
+
+/// How diagnostics are written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ErrorFormat {
+    /// With the line of source they are about, as `diagnostics` renders them.
+    Human,
+    /// On one line each, as `path:line:column: severity: message`.
+    Short,
+    /// As a JSON object each, on one line.
+    Json,
+}
+
+/// What the command line asks for, apart from the input files.
+#[derive(Debug, PartialEq)]
+struct Settings {
+    mode: Mode,
+    emits: Vec<Emit>,
+    spans: bool,
+    standard: Standard,
+    warnings: WarningSettings,
+    error_format: ErrorFormat,
+    /// Whether diagnostics are colored, as `--color` says. Without it, they
+    /// are if standard error is a terminal and `NO_COLOR` isn't set.
+    color: Option<bool>,
+    /// How many errors are shown before the rest are left out, as
+    /// `--max-errors` says, or `None` for all of them.
+    max_errors: Option<usize>,
+    /// Where `-o` says to write the output, with `-` for standard output.
+    output: Option<String>,
+    opt_level: codegen::OptLevel,
+    disabled_passes: Vec<String>,
+    /// Whether `-fomit-frame-pointer` or `-fno-omit-frame-pointer` was given,
+    /// the last of them winning, or `None` for what `opt_level` implies.
+    omit_frame_pointer: Option<bool>,
+    target: codegen::TargetSpec,
+    /// The program that assembles and links.
+    cc: String,
+    /// Whether `cc` was named, and so can be trusted to assemble and link for
+    /// a target other than the host.
+    cross_cc: bool,
+    /// Whether to keep the assembly that is linked, beside the executable.
+    save_temps: bool,
+    timings: bool,
+    /// How many files can be compiled at once.
+    jobs: usize,
+    /// Whether to say what was made of the flags that do less than they do
+    /// with `gcc`.
+    verbose: bool,
+    /// The directories `-I` names, and the macros `-D` defines, which are
+    /// accepted for the sake of the make rules that pass them, but do nothing.
+    include_dirs: Vec<String>,
+    defines: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            mode: Mode::Link,
+            emits: Vec::new(),
+            spans: false,
+            standard: Standard::default(),
+            warnings: WarningSettings::default(),
+            error_format: ErrorFormat::Human,
+            color: None,
+            max_errors: Some(20),
+            output: None,
+            opt_level: codegen::OptLevel::O0,
+            disabled_passes: Vec::new(),
+            omit_frame_pointer: None,
+            target: codegen::TargetSpec::host(),
+            cc: "cc".to_owned(),
+            cross_cc: false,
+            save_temps: false,
+            timings: false,
+            jobs: thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+            verbose: false,
+            include_dirs: Vec::new(),
+            defines: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Whether the assembly is given to `cc` to assemble.
+    fn assembles(&self) -> bool {
+        self.mode == Mode::Object || self.mode == Mode::Link
+    }
+}
+
+/// A diagnostic as it is written, with its position, if it has one, and how
+/// severe it is.
+type Rendered = (Option<(usize, usize)>, Severity, String);
+
+/// What compiling one file made, kept until every file is compiled, so that
+/// what is printed doesn't depend on which finished first.
+#[derive(Default)]
+struct Unit {
+    /// What is wrong with the file.
+    diagnostics: Vec<Rendered>,
+    /// What is to be printed to standard output.
+    printed: String,
+    /// The files written, if it compiled.
+    written: Option<Vec<PathBuf>>,
+    stages: Option<compile::Stages>,
+}
+
+impl Unit {
+    /// Adds `diagnostic`, about the file `map` maps, written as `settings` say.
+    fn report(&mut self, settings: &Settings, map: &SourceMap, diagnostic: &Diagnostic) {
+        let color = settings.color == Some(true);
+        let rendered = match settings.error_format {
+            ErrorFormat::Human => diagnostics::render(map, diagnostic, color),
+            ErrorFormat::Short => diagnostics::render_short(map, diagnostic, color),
+            ErrorFormat::Json => diagnostic_json(map, diagnostic),
+        };
+        self.diagnostics.push((diagnostic.position(), diagnostic.severity, rendered));
+    }
+
+    /// Adds the error that the file `name` couldn't be read or written.
+    fn report_io(&mut self, settings: &Settings, name: &str, err: &io::Error) {
+        let rendered = match settings.error_format {
+            ErrorFormat::Json => diagnostic_json(&SourceMap::new(name, ""), &Diagnostic::new(Severity::Error, err.to_string(), None)),
+            ErrorFormat::Human | ErrorFormat::Short => format!("{}: {}", name, err),
+        };
+        self.diagnostics.push((None, Severity::Error, rendered));
+    }
+
+    /// Writes what is wrong with the file, in order of where it is, as far as
+    /// `limit` allows, and then what it printed.
+    fn print(&mut self, limit: &mut Limit) {
+        self.diagnostics.sort_by_key(|&(position, _, _)| position);
+        for &(_, severity, ref diagnostic) in &self.diagnostics {
+            limit.show(severity, || eprintln!("{}", diagnostic));
+        }
+        print!("{}", self.printed);
+    }
+
+    /// How many of the diagnostics are errors.
+    fn errors(&self) -> usize {
+        self.diagnostics.iter().filter(|&&(_, severity, _)| severity == Severity::Error).count()
+    }
+}
+
+/// How many more errors can be shown before `--max-errors` is reached, and
+/// how many diagnostics were left out since it was, and files not compiled.
+/// Warnings don't count towards it, but aren't shown after it either.
+struct Limit {
+    remaining: Option<usize>,
+    left_out: usize,
+    not_compiled: usize,
+}
+
+impl Limit {
+    /// Shows a diagnostic as `severity` with `show`, unless the limit has been
+    /// reached.
+    fn show<F: FnOnce()>(&mut self, severity: Severity, show: F) {
+        if self.remaining == Some(0) {
+            self.left_out += 1;
+            return;
+        }
+        show();
+        if severity == Severity::Error {
+            self.remaining = self.remaining.map(|remaining| remaining - 1);
+        }
+    }
+
+    /// Writes that the limit was reached, and how much was left out for it,
+    /// if anything was.
+    fn print_summary(&self, settings: &Settings) {
+        let left_out = match self.left_out {
+            0 => None,
+            1 => Some("1 more diagnostic wasn't shown".to_owned()),
+            left_out => Some(format!("{} more diagnostics weren't shown", left_out)),
+        };
+        let not_compiled = match self.not_compiled {
+            0 => None,
+            1 => Some("1 file wasn't compiled".to_owned()),
+            not_compiled => Some(format!("{} files weren't compiled", not_compiled)),
+        };
+        let left_out = match (left_out, not_compiled) {
+            (None, None) => return,
+            (Some(left_out), None) => left_out,
+            (None, Some(not_compiled)) => not_compiled,
+            (Some(left_out), Some(not_compiled)) => format!("{}, and {}", left_out, not_compiled),
+        };
+        let summary = Diagnostic::new(Severity::Error, format!("too many errors emitted, stopping now; {}", left_out), None);
+        match settings.error_format {
+            ErrorFormat::Json => eprintln!("{}", bare_diagnostic_json(&summary)),
+            ErrorFormat::Human | ErrorFormat::Short => eprintln!("{}", diagnostics::render_bare(&summary, settings.color == Some(true))),
+        }
+    }
+}
+
+/// Compiles the file at `path` as `settings` say. What it emits is to be
+/// printed, or is written to `output`, or to files named `stem` in
+/// `directory`. Nothing is shared with the compiling of any other file, so
+/// that they can be compiled at once.
+fn compile(settings: &Settings, path: &str, stem: &str, output: Option<&str>, directory: &Path) -> Unit {
+    let mut unit = Unit { stages: if settings.timings { Some(compile::Stages::default()) } else { None }, ..Unit::default() };
+    unit.written = compile_into(&mut unit, settings, path, stem, output, directory);
+    unit
+}
+
+/// Compiles the file at `path` into `unit`, returning the files written if it
+/// compiled.
+fn compile_into(unit: &mut Unit, settings: &Settings, path: &str, stem: &str, output: Option<&str>, directory: &Path) -> Option<Vec<PathBuf>> {
+    let emits = &settings.emits;
+
+    // Standard input is named `<stdin>` in diagnostics.
+    let (source, path) = if path == "-" {
+        let source = match Source::stdin() {
+            Ok(source) => source,
+            Err(err) => {
+                unit.report_io(settings, "<stdin>", &err);
+                return None;
+            },
+        };
+        (source, "<stdin>".to_owned())
+    } else {
+        let source = match Source::open(path) {
+            Ok(source) => source,
+            Err(err) => {
+                unit.report_io(settings, path, &err);
+                return None;
+            },
+        };
+        (source, path.to_owned())
+    };
+    let map = SourceMap::new(&path, &source);
+
+    let options = CompileOptions {
+        target: settings.target,
+        opt_level: settings.opt_level,
+        standard: settings.standard,
+        warnings: settings.warnings.clone(),
+        file_name: Some(path.clone()),
+        omit_frame_pointer: settings.omit_frame_pointer,
+        disabled_passes: settings.disabled_passes.clone(),
+        spans: settings.spans,
+        ..CompileOptions::default()
+    };
+    let outputs = match compile::compile_str_to(&source, &options, emits, &mut unit.stages) {
+        Ok(compiled) => {
+            for warning in &compiled.warnings {
+                unit.report(settings, &map, warning);
+            }
+            compiled.outputs
+        },
+        Err(err) => {
+            for diagnostic in &err.diagnostics {
+                unit.report(settings, &map, diagnostic);
+            }
+            return None;
+        },
+    };
+
+    // A single kind asked for is printed, as is what `-o -` names. Several
+    // are each written to a file named after the input, as the assembly is
+    // with `-S`.
+    let mut written = Vec::new();
+    for (&emit, generated) in emits.iter().zip(&outputs) {
+        let file = match output {
+            Some("-") => {
+                unit.printed.push_str(generated);
+                continue;
+            },
+            Some(output) => PathBuf::from(output),
+            None if settings.mode == Mode::Emit && emits.len() == 1 => {
+                unit.printed.push_str(generated);
+                continue;
+            },
+            None => directory.join(format!("{}.{}", stem, extension(emit))),
+        };
+        if let Err(err) = fs::write(&file, generated) {
+            unit.report_io(settings, &file.display().to_string(), &err);
+            return None;
+        }
+        written.push(file);
+    }
+    Some(written)
+}
+
+/// The name of what is written from each of `inputs`: its file name without
+/// the extension, or `a` for standard input, as `gcc` names them. Inputs with
+/// the same name, from different directories, have their index after it, so
+/// that what is written from one doesn't overwrite the other's.
+fn stems(inputs: &[String]) -> Vec<String> {
+    let stems: Vec<String> = inputs.iter().map(|input| match &input[..] {
+        "-" => "a".to_owned(),
+        path => Path::new(path).file_stem().unwrap().to_string_lossy().into_owned(),
+    }).collect();
+    stems.iter().enumerate().map(|(index, stem)| match stems.iter().filter(|&other| other == stem).count() {
+        1 => stem.clone(),
+        _ => format!("{}-{}", stem, index),
+    }).collect()
+}
+
+/// The errors found in each of the files compiled so far, by their index, to
+/// tell when `--max-errors` has been reached.
+struct ErrorCounts {
+    max_errors: Option<usize>,
+    found: Mutex<Vec<Option<usize>>>,
+}
+
+impl ErrorCounts {
+    /// Whether the files before the one at `index` have been found to have as
+    /// many errors as can be shown, so that none of its would be. Those still
+    /// being compiled count as having none yet.
+    fn reached_before(&self, index: usize) -> bool {
+        let found = self.found.lock().unwrap();
+        self.max_errors.is_some_and(|max_errors| found[..index].iter().flatten().sum::<usize>() >= max_errors)
+    }
+
+    fn record(&self, index: usize, unit: &Unit) {
+        self.found.lock().unwrap()[index] = Some(unit.errors());
+    }
+}
+
+/// Compiles each of `inputs` as `compile` does, up to `settings.jobs` of them
+/// at once. Returns what each made, in the order they were given, or `None`
+/// for those not compiled because `--max-errors` was reached before them.
+fn compile_all(settings: &Settings, inputs: &[String], output: Option<&str>, directory: &Path) -> Vec<Option<Unit>> {
+    let stems = &stems(inputs);
+    let counts = ErrorCounts { max_errors: settings.max_errors, found: Mutex::new(vec![None; inputs.len()]) };
+    let compile_next = &|index: usize| {
+        if counts.reached_before(index) {
+            return None;
+        }
+        let unit = compile(settings, &inputs[index], &stems[index], output, directory);
+        counts.record(index, &unit);
+        Some(unit)
+    };
+    let jobs = settings.jobs.min(inputs.len());
+    if jobs <= 1 {
+        return (0..inputs.len()).map(compile_next).collect();
+    }
+
+    // Each thread takes the next file no other has taken, until there are
+    // none left.
+    let next = &AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, move || loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= inputs.len() {
+                    break;
+                }
+                sender.send((index, compile_next(index))).unwrap();
+            }).unwrap_or_else(|err| fail(&format!("rust-cc: {}", err)));
+        }
+    });
+    drop(sender);
+    let mut units: Vec<_> = receiver.iter().collect();
+    units.sort_by_key(|&(index, _)| index);
+    units.into_iter().map(|(_, unit)| unit).collect()
+}
+
+/// Runs `settings.cc` on `assembly` with `flags`, to write `output`, its
+/// errors going straight to standard error. Returns the status to exit with if
+/// it fails.
+fn cc(settings: &Settings, assembly: &[PathBuf], flags: &[&str], output: &Path) -> Result<(), i32> {
+    match process::Command::new(&settings.cc).args(flags).args(assembly).arg("-o").arg(output).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(status.code().unwrap_or(1)),
+        Err(err) => {
+            eprintln!("{}: {}", settings.cc, err);
+            Err(1)
+        },
+    }
+}
+
+/// A command line, parsed.
+#[derive(Debug, PartialEq)]
+struct CommandLine {
+    settings: Settings,
+    inputs: Vec<String>,
+    /// What was made of the flags that were accepted but do nothing, or less
+    /// than they do with `gcc`, which `--verbose` prints.
+    notes: Vec<String>,
+}
+
+/// The value of the flag `flag` in `arg`: what follows it, as in `-Iinclude`,
+/// or else the next of `args`, as in `-I include`. `what` says what it should
+/// be if there is neither.
+fn value<I: Iterator<Item = String>>(arg: &str, flag: &str, args: &mut I, what: &str) -> Result<String, String> {
+    match &arg[flag.len()..] {
+        "" => args.next().ok_or_else(|| format!("{} needs {}\n{}", flag.trim_end_matches('='), what, USAGE)),
+        value => Ok(value.to_owned()),
+    }
+}
+
+/// Parses the command line `args`, which are as `gcc` takes them: flags and
+/// files in any order, and flags' values after a space or right after them.
+/// Flags that make rules commonly pass are accepted, even the ones that do
+/// nothing here, and so are any `-f` or `-W` flags that aren't known.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<CommandLine, String> {
+    let mut settings = Settings::default();
+    let mut inputs = Vec::new();
+    let mut notes = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--emit" => {
+                for kind in value(&arg, "--emit", &mut args, "a kind")?.split(',') {
+                    match Emit::from_name(kind) {
+                        Some(emit) if !settings.emits.contains(&emit) => settings.emits.push(emit),
+                        Some(_) => {},
+                        None if kind == "ast-json" => return Err("--emit ast-json needs rust-cc to be built with the `serde` feature".to_owned()),
+                        None => return Err(format!("unknown --emit kind `{}`\n{}", kind, USAGE)),
+                    }
+                }
+                settings.mode = Mode::Emit;
+            },
+            // As with `gcc`, `-S` stops at the assembly, even with `-c`.
+            "-S" => settings.mode = Mode::Assembly,
+            "-c" if settings.mode != Mode::Assembly => settings.mode = Mode::Object,
+            "-c" => {},
+            "--spans" => settings.spans = true,
+            "--error-format=human" => settings.error_format = ErrorFormat::Human,
+            "--error-format=short" => settings.error_format = ErrorFormat::Short,
+            "--error-format=json" if cfg!(feature = "serde") => settings.error_format = ErrorFormat::Json,
+            "--error-format=json" => return Err("--error-format=json needs rust-cc to be built with the `serde` feature".to_owned()),
+            "--color=always" => settings.color = Some(true),
+            "--color=never" => settings.color = Some(false),
+            "--color=auto" => settings.color = None,
+            // `-fmax-errors` is `gcc`'s spelling.
+            _ if arg.starts_with("--max-errors=") || arg.starts_with("-fmax-errors=") => {
+                settings.max_errors = match arg[arg.find('=').unwrap() + 1..].parse() {
+                    Ok(0) => None,
+                    Ok(max_errors) => Some(max_errors),
+                    Err(_) => return Err(format!("--max-errors needs a number of errors, or 0 for no limit\n{}", USAGE)),
+                };
+            },
+            _ if arg.starts_with("-o") => settings.output = Some(value(&arg, "-o", &mut args, "a path")?),
+            "-O0" => settings.opt_level = codegen::OptLevel::O0,
+            "-O1" => settings.opt_level = codegen::OptLevel::O1,
+            "-O2" => settings.opt_level = codegen::OptLevel::O2,
+            // `gcc`'s other levels are taken as the nearest of these.
+            "-O" | "-Og" | "-Os" | "-Oz" => {
+                settings.opt_level = codegen::OptLevel::O1;
+                notes.push(format!("`{}` is taken as `-O1`", arg));
+            },
+            "-O3" | "-Ofast" => {
+                settings.opt_level = codegen::OptLevel::O2;
+                notes.push(format!("`{}` is taken as `-O2`", arg));
+            },
+            // Line information is generated whether it is asked for or not.
+            _ if arg.starts_with("-g") => {},
+            "--timings" | "-ftime-report" => settings.timings = true,
+            "--verbose" => settings.verbose = true,
+            _ if arg.starts_with("-j") => {
+                settings.jobs = match value(&arg, "-j", &mut args, "a number of jobs")?.parse() {
+                    Ok(jobs) if jobs > 0 => jobs,
+                    _ => return Err(format!("-j needs a number of jobs, at least 1\n{}", USAGE)),
+                };
+            },
+            _ if arg == "--target" || arg.starts_with("--target=") => {
+                let flag = if arg == "--target" { "--target" } else { "--target=" };
+                let name = value(&arg, flag, &mut args, "a target")?;
+                settings.target = codegen::TargetSpec::from_name(&name)
+                    .ok_or_else(|| format!("unknown target `{}`; the targets are {}", name, codegen::TARGETS.join(", ")))?;
+            },
+            "--cc" => {
+                settings.cc = value(&arg, "--cc", &mut args, "a program")?;
+                settings.cross_cc = true;
+            },
+            "--save-temps" => settings.save_temps = true,
+            // There is no preprocessor for these to go to.
+            _ if arg.starts_with("-I") => {
+                let directory = value(&arg, "-I", &mut args, "a directory")?;
+                notes.push(format!("`-I{}` does nothing, since there is no preprocessor", directory));
+                settings.include_dirs.push(directory);
+            },
+            _ if arg.starts_with("-D") => {
+                let definition = value(&arg, "-D", &mut args, "a macro")?;
+                notes.push(format!("`-D{}` does nothing, since there is no preprocessor", definition));
+                settings.defines.push(definition);
+            },
+            // `-std` is `gcc`'s spelling. The GNU dialects are taken as the
+            // standards they extend.
+            _ if arg.starts_with("--std=") || arg.starts_with("-std=") => {
+                let name = &arg[arg.find('=').unwrap() + 1..];
+                let standard = match name.strip_prefix("gnu") {
+                    Some(version) => {
+                        notes.push(format!("`{}` is taken as `--std=c{}`, without GNU extensions", arg, version));
+                        Standard::from_name(&format!("c{}", version))
+                    },
+                    None => Standard::from_name(name),
+                };
+                settings.standard = standard.ok_or_else(|| format!("unknown standard `{}`\n{}", name, USAGE))?;
+            },
+            "-w" => settings.warnings.silenced = true,
+            "-Werror" => settings.warnings.all_errors = true,
+            // Every warning is on unless it is turned off.
+            "-Wall" | "-Wextra" => {},
+            _ if arg.starts_with("-W") => {
+                let (name, severity) = match &arg["-W".len()..] {
+                    flag if flag.starts_with("error=") => (&flag["error=".len()..], Some(Severity::Error)),
+                    flag if flag.starts_with("no-error=") => (&flag["no-error=".len()..], Some(Severity::Warning)),
+                    flag if flag.starts_with("no-") => (&flag["no-".len()..], None),
+                    flag => (flag, Some(Severity::Warning)),
+                };
+                if !settings.warnings.set(name, severity) {
+                    notes.push(format!("ignoring `{}`, since there is no `{}` warning", arg, name));
+                }
+            },
+            _ if arg.starts_with("--no-") => {
+                let pass = &arg["--no-".len()..];
+                if !codegen::pass_names().contains(&pass) {
+                    return Err(format!("unknown pass `{}`; the passes are {}", pass, codegen::pass_names().join(", ")));
+                }
+                settings.disabled_passes.push(pass.to_owned());
+            },
+            "-fomit-frame-pointer" => settings.omit_frame_pointer = Some(true),
+            "-fno-omit-frame-pointer" => settings.omit_frame_pointer = Some(false),
+            _ if arg.starts_with("-f") => notes.push(format!("ignoring unknown flag `{}`", arg)),
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option `{}`\n{}", arg, USAGE)),
+            _ => inputs.push(arg),
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err(format!("no input file\n{}", USAGE));
+    }
+    if inputs.iter().filter(|&input| input == "-").count() > 1 {
+        return Err("standard input can only be read once".to_owned());
+    }
+    if settings.mode != Mode::Emit {
+        settings.emits = vec![Emit::Assembly];
+    }
+    if settings.output.is_some() && settings.emits.len() > 1 {
+        return Err(format!("-o can't name the output of more than one --emit kind\n{}", USAGE));
+    }
+    if settings.output.is_some() && inputs.len() > 1 && settings.mode != Mode::Link {
+        return Err(format!("-o can't name the output of more than one input file\n{}", USAGE));
+    }
+    // Only text is written to standard output, as `gcc -o -` writes it.
+    if settings.output.as_deref() == Some("-") && settings.assembles() {
+        return Err(format!("-o - can only write text, with -S or --emit, to standard output\n{}", USAGE));
+    }
+    // The host's `cc` only assembles and links for the host, so anything else
+    // needs a cross compiler named with `--cc`.
+    let host = codegen::TargetSpec::host();
+    if settings.assembles() && settings.target.name() != host.name() && !settings.cross_cc {
+        return Err(format!(
+            "can't assemble for {} with the {} host's `cc`; use -S, or name a cross compiler with --cc",
+            settings.target.name(), host.name(),
+        ));
+    }
+    // With several inputs, each is written to files named after it, even if
+    // only one kind is asked for, so that they don't run together.
+    if inputs.len() > 1 && settings.mode == Mode::Emit {
+        settings.mode = Mode::Assembly;
+    }
+    Ok(CommandLine { settings, inputs, notes })
+}
+
+/// Prints the explanation of the diagnostic code `code`, as `--explain` asks.
+/// Whatever reads it may stop before the end, as `head` does, which isn't
+/// an error.
+fn explain(code: &str) -> ! {
+    let code = match codes::lookup(code) {
+        Some(code) => code,
+        None => fail(&format!("rust-cc: no diagnostic has the code `{}`", code)),
+    };
+    let mut stdout = io::stdout().lock();
+    match writeln!(stdout, "{}: {}\n\n{}", code.code, code.title, code.explanation).and_then(|()| stdout.flush()) {
+        Ok(()) => process::exit(0),
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+        Err(err) => fail(&format!("<stdout>: {}", err)),
+    }
+}
+
+/// Prints each of the files `args` names formatted, as `--format` asks, with
+/// the indent width it gives. Files that can't be read or lexed are reported,
+/// and the rest are formatted anyway.
+fn format_files<I: Iterator<Item = String>>(args: I) -> ! {
+    let mut options = FormatOptions::default();
+    let mut inputs = Vec::new();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--indent") {
+            let width = value(&arg, if arg == "--indent" { "--indent" } else { "--indent=" }, &mut args, "a number of spaces");
+            options.indent_width = match width.map(|width| width.parse()) {
+                Ok(Ok(width)) => width,
+                Ok(Err(_)) => fail(&format!("--indent needs a number of spaces\n{}", USAGE)),
+                Err(message) => fail(&message),
+            };
+        } else if arg.starts_with('-') && arg != "-" {
+            fail(&format!("unknown option `{}`\n{}", arg, USAGE));
+        } else {
+            inputs.push(arg);
+        }
+    }
+    if inputs.is_empty() {
+        fail(&format!("no input file\n{}", USAGE));
+    }
+
+    let mut status = 0;
+    for input in &inputs {
+        let (source, name) = if input == "-" { (Source::stdin(), "<stdin>") } else { (Source::open(input), input.as_str()) };
+        let source = match source {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}: {}", name, err);
+                status = 1;
+                continue;
+            },
+        };
+        match format_source(&source, &options) {
+            Ok(formatted) => print!("{}", formatted),
+            Err(err) => {
+                let map = SourceMap::new(name, &source);
+                eprintln!("{}", diagnostics::render_short(&map, &compile::lex_diagnostic(&map, &err), false));
+                status = 1;
+            },
+        }
+    }
+    process::exit(status);
+}
 
 fn main() {
-    println!("Hello, world!");
+    // `--explain` and `--format` are commands of their own, which compile
+    // nothing.
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("--explain") {
+        let flag = args.next().unwrap();
+        explain(&value(&flag, "--explain", &mut args, "a code").unwrap_or_else(|message| fail(&message)));
+    }
+    if args.peek().map(String::as_str) == Some("--format") {
+        args.next();
+        format_files(args);
+    }
+    let CommandLine { mut settings, inputs, notes } = parse_args(args).unwrap_or_else(|message| fail(&message));
+    settings.color.get_or_insert_with(|| io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()));
+    if settings.verbose {
+        for note in &notes {
+            eprintln!("rust-cc: note: {}", note);
+        }
+    }
+
+    // What is assembled is written to a directory of its own, which is removed
+    // afterwards, unless it is to be kept, as `gcc -save-temps` keeps it.
+    let directory = match settings.mode {
+        Mode::Object | Mode::Link if !settings.save_temps =>
+            scratch_directory().unwrap_or_else(|err| fail(&format!("{}: {}", env::temp_dir().display(), err))),
+        _ => PathBuf::from("."),
+    };
+    let output = match settings.mode {
+        Mode::Object | Mode::Link => None,
+        _ => settings.output.as_deref(),
+    };
+
+    // Every file is compiled, whatever is wrong with those before it, so that
+    // everything wrong is reported at once, until as many errors have been
+    // found as can be shown.
+    let mut result = Ok(());
+    let mut assembly = Vec::new();
+    let mut stages = if settings.timings { Some(compile::Stages::default()) } else { None };
+    let mut limit = Limit { remaining: settings.max_errors, left_out: 0, not_compiled: 0 };
+    for unit in compile_all(&settings, &inputs, output, &directory) {
+        // A file compiled at once with those before it, before they reached
+        // the limit, is left out as if it hadn't been, so that what is shown
+        // doesn't depend on which finished first.
+        let mut unit = match unit {
+            Some(unit) if limit.remaining != Some(0) => unit,
+            _ => {
+                limit.not_compiled += 1;
+                result = Err(1);
+                continue;
+            },
+        };
+        unit.print(&mut limit);
+        match unit.written {
+            Some(written) => assembly.extend(written),
+            None => result = Err(1),
+        }
+        if let (Some(stages), Some(unit)) = (stages.as_mut(), unit.stages) {
+            stages.merge(unit);
+        }
+    }
+    limit.print_summary(&settings);
+    if result.is_ok() && settings.mode == Mode::Object {
+        // Each object file is named after its input, in the current directory.
+        for file in &assembly {
+            let object = match settings.output {
+                Some(ref output) => PathBuf::from(output),
+                None => PathBuf::from(file.file_name().unwrap()).with_extension("o"),
+            };
+            result = compile::timed(&mut stages, "assemble", || cc(&settings, std::slice::from_ref(file), &["-c"], &object));
+            if result.is_err() {
+                break;
+            }
+        }
+    }
+    if result.is_ok() && settings.mode == Mode::Link {
+        let output = Path::new(settings.output.as_deref().unwrap_or("a.out"));
+        result = compile::timed(&mut stages, "link", || cc(&settings, &assembly, &[], output));
+    }
+    if settings.assembles() && !settings.save_temps {
+        let _ = fs::remove_dir_all(&directory);
+    }
+    if let Some(ref stages) = stages {
+        eprint!("{}", stages);
+    }
+    if let Err(status) = result {
+        process::exit(status);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(command_line: &str) -> Result<CommandLine, String> {
+        parse_args(command_line.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn make_rules_command_lines_are_understood() {
+        let CommandLine { settings, inputs, notes } = parse("-O2 -Wall -Iinc -DDEBUG -c foo.c -o foo.o").unwrap();
+        assert_eq!(settings, Settings {
+            mode: Mode::Object,
+            emits: vec![Emit::Assembly],
+            opt_level: codegen::OptLevel::O2,
+            output: Some("foo.o".to_owned()),
+            include_dirs: vec!["inc".to_owned()],
+            defines: vec!["DEBUG".to_owned()],
+            ..Settings::default()
+        });
+        assert_eq!(inputs, ["foo.c"]);
+        assert_eq!(notes, [
+            "`-Iinc` does nothing, since there is no preprocessor",
+            "`-DDEBUG` does nothing, since there is no preprocessor",
+        ]);
+
+        // Flags can come after the files, with their values apart or not.
+        let spaced = parse("foo.c -c -o foo.o -I inc -D DEBUG -O2 -Wall").unwrap();
+        assert_eq!(spaced.settings, settings);
+        assert_eq!(parse("-DFOO=1 -std=gnu99 -g -O3 -ofoo foo.c").unwrap(), CommandLine {
+            settings: Settings {
+                standard: Standard::C99,
+                opt_level: codegen::OptLevel::O2,
+                output: Some("foo".to_owned()),
+                defines: vec!["FOO=1".to_owned()],
+                emits: vec![Emit::Assembly],
+                ..Settings::default()
+            },
+            inputs: vec!["foo.c".to_owned()],
+            notes: vec![
+                "`-DFOO=1` does nothing, since there is no preprocessor".to_owned(),
+                "`-std=gnu99` is taken as `--std=c99`, without GNU extensions".to_owned(),
+                "`-O3` is taken as `-O2`".to_owned(),
+            ],
+        });
+    }
+
+    #[test]
+    fn unknown_f_and_w_flags_are_noted() {
+        let CommandLine { settings, notes, .. } = parse("-fPIC -fno-strict-aliasing -Wshadow -Werror=unused-variable foo.c").unwrap();
+        let mut warnings = WarningSettings::default();
+        warnings.set("unused-variable", Some(Severity::Error));
+        assert_eq!(settings.warnings, warnings);
+        assert_eq!(notes, [
+            "ignoring unknown flag `-fPIC`",
+            "ignoring unknown flag `-fno-strict-aliasing`",
+            "ignoring `-Wshadow`, since there is no `shadow` warning",
+        ]);
+        assert!(parse("-x c foo.c").unwrap_err().starts_with("unknown option `-x`\n"));
+        assert!(parse("foo.c -I").unwrap_err().starts_with("-I needs a directory\n"));
+        assert!(parse("-j0 foo.c").unwrap_err().starts_with("-j needs a number of jobs, at least 1\n"));
+    }
+
+    #[test]
+    fn max_errors_can_be_set_or_lifted() {
+        assert_eq!(parse("foo.c").unwrap().settings.max_errors, Some(20));
+        assert_eq!(parse("--max-errors=5 foo.c").unwrap().settings.max_errors, Some(5));
+        assert_eq!(parse("-fmax-errors=3 foo.c").unwrap().settings.max_errors, Some(3));
+        assert_eq!(parse("--max-errors=0 foo.c").unwrap().settings.max_errors, None);
+        assert!(parse("--max-errors=many foo.c").unwrap_err().starts_with("--max-errors needs a number of errors, or 0 for no limit\n"));
+    }
+
+    #[test]
+    fn the_last_frame_pointer_flag_wins() {
+        assert_eq!(parse("-O2 foo.c").unwrap().settings.omit_frame_pointer, None);
+        assert_eq!(parse("-fomit-frame-pointer foo.c").unwrap().settings.omit_frame_pointer, Some(true));
+        assert_eq!(parse("-O2 -fno-omit-frame-pointer foo.c").unwrap().settings.omit_frame_pointer, Some(false));
+        let CommandLine { settings, notes, .. } = parse("-fno-omit-frame-pointer -fomit-frame-pointer foo.c").unwrap();
+        assert_eq!(settings.omit_frame_pointer, Some(true));
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn scratch_directories_are_made_afresh() {
+        let (first, second) = (scratch_directory().unwrap(), scratch_directory().unwrap());
+        assert_ne!(first, second);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&first).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        fs::remove_dir(first).unwrap();
+        fs::remove_dir(second).unwrap();
+    }
 }
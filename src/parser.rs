@@ -0,0 +1,2283 @@
+//! Responsible for turning the lexed input into an AST.
+//! This is the second of three parsing stages.
+
+use std::vec::Vec;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use lexer::{self, Lexeme, LexemeKind};
+use ast::*;
+use const_eval::{const_eval, ConstEnv, ConstEvalError};
+use standard::{Feature, Standard};
+use pragma;
+use suggest;
+
+/// Something the parser would have accepted at the point it failed.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expected {
+    Token(LexemeKind<'static>),
+    Identifier,
+    TypeName,
+    Expression,
+    /// Any operator that could continue the expression before this point.
+    Operator,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expected::Token(ref kind) => write!(f, "`{}`", kind),
+            Expected::Identifier => f.write_str("identifier"),
+            Expected::TypeName => f.write_str("type name"),
+            Expected::Expression => f.write_str("expression"),
+            Expected::Operator => f.write_str("operator"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// A lexeme where it can't be. `suggestion` is the keyword that the
+    /// identifier before it, at the start of a statement, is a misspelling of,
+    /// if it looks like one.
+    UnexpectedToken { found: String, expected: Vec<Expected>, line: usize, column: usize, suggestion: Option<&'static str> },
+    /// The input ended early. `last_token_position` is where the final lexeme was, if there was one.
+    UnexpectedEof { expected: Vec<Expected>, last_token_position: Option<(usize, usize)> },
+    /// An initializer list has more elements than the object it initializes.
+    TooManyInitializers { line: usize, column: usize },
+    /// An initializer's shape doesn't fit the object it initializes, e.g. `int a[2] = 5;`.
+    InvalidInitializer { line: usize, column: usize },
+    /// Designated initializers (`.x = 1`, `[0] = 1`) aren't supported yet.
+    UnsupportedDesignator { line: usize, column: usize },
+    /// A parameter list consisting of only `...`; at least one named parameter must come first.
+    VariadicWithoutNamedParameter { line: usize, column: usize },
+    /// A `...` followed by further parameters.
+    VariadicNotLast { line: usize, column: usize },
+    /// A file-scope name declared both with and without `static`.
+    ConflictingLinkage { name: String, line: usize, column: usize, previous_line: usize, previous_column: usize },
+    /// An array size that isn't an integer constant expression.
+    InvalidConstant(ConstEvalError),
+    NegativeArraySize { line: usize, column: usize },
+    /// A combination of integer type keywords that doesn't name a supported
+    /// type, such as `long long` or `signed unsigned`.
+    InvalidTypeSpecifier { words: String, line: usize, column: usize },
+    /// Statements, expressions, initializer lists, or declarators nested more than
+    /// `MAX_DEPTH` levels deep.
+    NestingTooDeep { line: usize, column: usize },
+    /// Something the standard being parsed doesn't have.
+    NeedsStandard { feature: Feature, line: usize, column: usize },
+    /// A `{` with no `}` to close it, the innermost if there are several.
+    /// `end` is the end of the last lexeme, where the input runs out.
+    UnclosedBrace { line: usize, column: usize, end: (usize, usize) },
+}
+
+/// How deeply constructs may nest. This keeps the parser, and every pass that
+/// recurses over the tree it builds, from overflowing the stack.
+pub const MAX_DEPTH: usize = 128;
+
+/// `expected` as a list like ``a, `b`, or `c` ``.
+fn expected_list(expected: &[Expected]) -> String {
+    let mut list = String::new();
+    for (index, item) in expected.iter().enumerate() {
+        if index > 0 {
+            if expected.len() > 2 {
+                list.push(',');
+            }
+            if index == expected.len() - 1 {
+                list.push_str(" or");
+            }
+            list.push(' ');
+        }
+        list.push_str(&item.to_string());
+    }
+    list
+}
+
+impl ParseError {
+    /// The span the error is about, where more is known of it than the
+    /// position it starts at.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            ParseError::InvalidConstant(ref error) => Some(error.span()),
+            _ => None,
+        }
+    }
+
+    /// The code of the error, from `codes::CODES`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ParseError::NeedsStandard { .. } => "E0003",
+            ParseError::UnexpectedToken { .. } => "E0010",
+            ParseError::UnexpectedEof { .. } => "E0011",
+            ParseError::TooManyInitializers { .. } => "E0012",
+            ParseError::InvalidInitializer { .. } => "E0013",
+            ParseError::UnsupportedDesignator { .. } => "E0014",
+            ParseError::VariadicWithoutNamedParameter { .. } => "E0015",
+            ParseError::VariadicNotLast { .. } => "E0016",
+            ParseError::ConflictingLinkage { .. } => "E0017",
+            ParseError::NegativeArraySize { .. } => "E0018",
+            ParseError::InvalidTypeSpecifier { .. } => "E0019",
+            ParseError::NestingTooDeep { .. } => "E0020",
+            ParseError::UnclosedBrace { .. } => "E0021",
+            ParseError::InvalidConstant(ref error) => error.code(),
+        }
+    }
+
+    /// The other places the error has to do with, each with what it has to do
+    /// with them.
+    pub fn labels(&self) -> Vec<((usize, usize), &'static str)> {
+        match *self {
+            ParseError::ConflictingLinkage { previous_line, previous_column, .. } =>
+                vec![((previous_line, previous_column), "previously declared here")],
+            ParseError::UnclosedBrace { end, .. } => vec![(end, "the file ends here, before a `}` closes it")],
+            _ => Vec::new(),
+        }
+    }
+
+    /// What more there is to say about the error.
+    pub fn notes(&self) -> Vec<&'static str> {
+        match *self {
+            ParseError::ConflictingLinkage { .. } =>
+                vec!["a file-scope name has the same linkage in every declaration of it, though `extern` takes on that of an earlier one"],
+            _ => Vec::new(),
+        }
+    }
+
+    /// What might be done about the error.
+    pub fn help(&self) -> Vec<String> {
+        match *self {
+            ParseError::UnexpectedToken { suggestion: Some(keyword), .. } => vec![format!("did you mean `{}`?", keyword)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// What the error is, without where: a diagnostic shows that itself.
+    pub fn message(&self) -> String {
+        match *self {
+            ParseError::UnexpectedToken { ref found, ref expected, .. } if expected.is_empty() => format!("unexpected {}", found),
+            ParseError::UnexpectedToken { ref found, ref expected, .. } =>
+                format!("expected {}, found {}", expected_list(expected), found),
+            ParseError::UnexpectedEof { ref expected, .. } if expected.is_empty() => "unexpected end of input".to_owned(),
+            ParseError::UnexpectedEof { ref expected, .. } => format!("expected {}, found end of input", expected_list(expected)),
+            ParseError::TooManyInitializers { .. } => "too many initializers".to_owned(),
+            ParseError::InvalidInitializer { .. } => "initializer does not fit the declared type".to_owned(),
+            ParseError::UnsupportedDesignator { .. } => "designated initializers are not supported".to_owned(),
+            ParseError::VariadicWithoutNamedParameter { .. } => "`...` must follow at least one named parameter".to_owned(),
+            ParseError::VariadicNotLast { .. } => "`...` must be the last parameter".to_owned(),
+            ParseError::ConflictingLinkage { ref name, .. } => format!("`{}` declared with conflicting linkage", name),
+            ParseError::InvalidConstant(ref error) => error.message().to_owned(),
+            ParseError::NegativeArraySize { .. } => "array size is negative".to_owned(),
+            ParseError::InvalidTypeSpecifier { ref words, .. } => format!("`{}` is not a supported type", words),
+            ParseError::NestingTooDeep { .. } => format!("nesting exceeds the limit of {} levels", MAX_DEPTH),
+            ParseError::NeedsStandard { feature, .. } => format!("{} {}", feature, feature.requirement()),
+            ParseError::UnclosedBrace { .. } => "unclosed `{`".to_owned(),
+        }
+    }
+
+    /// The line and column the error is at. The end of input has none, unless
+    /// a lexeme came before it.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match *self {
+            ParseError::UnexpectedEof { last_token_position, .. } => last_token_position,
+            ParseError::InvalidConstant(ref error) => Some((error.span().line, error.span().column)),
+            ParseError::UnexpectedToken { line, column, .. }
+            | ParseError::TooManyInitializers { line, column }
+            | ParseError::InvalidInitializer { line, column }
+            | ParseError::UnsupportedDesignator { line, column }
+            | ParseError::VariadicWithoutNamedParameter { line, column }
+            | ParseError::VariadicNotLast { line, column }
+            | ParseError::ConflictingLinkage { line, column, .. }
+            | ParseError::NegativeArraySize { line, column }
+            | ParseError::InvalidTypeSpecifier { line, column, .. }
+            | ParseError::NestingTooDeep { line, column }
+            | ParseError::NeedsStandard { line, column, .. }
+            | ParseError::UnclosedBrace { line, column, .. } => Some((line, column)),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::UnexpectedEof { last_token_position: None, .. } => f.write_str(&self.message()),
+            ParseError::UnexpectedEof { last_token_position: Some((line, column)), .. } =>
+                write!(f, "{} after {}:{}", self.message(), line, column),
+            ParseError::InvalidConstant(ref error) => error.fmt(f),
+            ParseError::ConflictingLinkage { ref name, line, column, previous_line, previous_column } => write!(
+                f,
+                "`{}` declared with conflicting linkage at {}:{} (previously declared at {}:{})",
+                name, line, column, previous_line, previous_column,
+            ),
+            ParseError::NeedsStandard { feature, line, column } =>
+                write!(f, "{} at {}:{} {}", feature, line, column, feature.requirement()),
+            _ => {
+                let (line, column) = self.position().unwrap();
+                write!(f, "{} at {}:{}", self.message(), line, column)
+            },
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+struct Parser<'a> {
+    lexemes: &'a [Lexeme<'a>],
+    position: usize,
+    /// Every typedef name declared so far, mapped to the type it stands for.
+    /// Typedefs are resolved as they are parsed, so the AST never refers to them.
+    typedefs: HashMap<String, Type>,
+    /// The members of every struct defined so far, by tag.
+    structs: HashMap<String, Vec<Member>>,
+    /// Whether each file-scope name has internal linkage, and where that was first decided.
+    linkage: HashMap<String, (bool, usize, usize)>,
+    /// Everything that was tried and rejected at `expected_position`. Every decision point
+    /// records into this, so errors report exactly what the grammar allowed there.
+    expected: Vec<Expected>,
+    expected_position: usize,
+    /// Errors that have been recovered from so far.
+    errors: Vec<ParseError>,
+    /// Where parsed nodes are allocated.
+    ast: Ast,
+    /// How many levels deep the construct being parsed is nested.
+    depth: usize,
+    /// The standard the program is written in.
+    standard: Standard,
+}
+
+/// Returns the binary operator a lexeme represents.
+fn binary_operator(kind: &LexemeKind) -> Option<BinaryOp> {
+    match *kind {
+        LexemeKind::LogicalOr => Some(BinaryOp::LogicalOr),
+        LexemeKind::LogicalAnd => Some(BinaryOp::LogicalAnd),
+        LexemeKind::Pipe => Some(BinaryOp::BitwiseOr),
+        LexemeKind::Caret => Some(BinaryOp::BitwiseXor),
+        LexemeKind::Ampersand => Some(BinaryOp::BitwiseAnd),
+        LexemeKind::Equal => Some(BinaryOp::Equal),
+        LexemeKind::NotEqual => Some(BinaryOp::NotEqual),
+        LexemeKind::LessThan => Some(BinaryOp::LessThan),
+        LexemeKind::LessEqual => Some(BinaryOp::LessEqual),
+        LexemeKind::GreaterThan => Some(BinaryOp::GreaterThan),
+        LexemeKind::GreaterEqual => Some(BinaryOp::GreaterEqual),
+        LexemeKind::ShiftLeft => Some(BinaryOp::ShiftLeft),
+        LexemeKind::ShiftRight => Some(BinaryOp::ShiftRight),
+        LexemeKind::Plus => Some(BinaryOp::Add),
+        LexemeKind::Minus => Some(BinaryOp::Subtract),
+        LexemeKind::Star => Some(BinaryOp::Multiply),
+        LexemeKind::Slash => Some(BinaryOp::Divide),
+        LexemeKind::Percent => Some(BinaryOp::Modulo),
+        _ => None,
+    }
+}
+
+/// Returns the operator a compound assignment applies, or `Some(None)` for plain `=`.
+fn assignment_operator(kind: &LexemeKind) -> Option<Option<BinaryOp>> {
+    match *kind {
+        LexemeKind::Assign => Some(None),
+        LexemeKind::PlusAssign => Some(Some(BinaryOp::Add)),
+        LexemeKind::MinusAssign => Some(Some(BinaryOp::Subtract)),
+        LexemeKind::StarAssign => Some(Some(BinaryOp::Multiply)),
+        LexemeKind::SlashAssign => Some(Some(BinaryOp::Divide)),
+        LexemeKind::PercentAssign => Some(Some(BinaryOp::Modulo)),
+        LexemeKind::AmpersandAssign => Some(Some(BinaryOp::BitwiseAnd)),
+        LexemeKind::PipeAssign => Some(Some(BinaryOp::BitwiseOr)),
+        LexemeKind::CaretAssign => Some(Some(BinaryOp::BitwiseXor)),
+        LexemeKind::ShiftLeftAssign => Some(Some(BinaryOp::ShiftLeft)),
+        LexemeKind::ShiftRightAssign => Some(Some(BinaryOp::ShiftRight)),
+        _ => None,
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&LexemeKind<'a>> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&LexemeKind<'a>> {
+        self.lexemes.get(self.position + offset).map(|lexeme| &lexeme.kind)
+    }
+
+    fn advance(&mut self) -> Option<&LexemeKind<'a>> {
+        let lexeme = self.lexemes.get(self.position);
+        if lexeme.is_some() {
+            self.position += 1;
+        }
+        lexeme.map(|lexeme| &lexeme.kind)
+    }
+
+    /// Returns the line and column of the lexeme at the current position, or of
+    /// the end of the input if there is none.
+    fn location(&self) -> (usize, usize) {
+        match self.lexemes.get(self.position).or_else(|| self.lexemes.last()) {
+            Some(lexeme) => (lexeme.line, lexeme.column),
+            None => (1, 1),
+        }
+    }
+
+    /// Returns the span from the lexeme at index `start` through the last lexeme consumed.
+    fn span_from(&self, start: usize) -> Span {
+        let first = &self.lexemes[start];
+        let end = if self.position > start {
+            self.lexemes[self.position - 1].end
+        } else {
+            first.start
+        };
+        Span { start: first.start, end, line: first.line, column: first.column }
+    }
+
+    /// Allocates an expression spanning from the lexeme at index `start` through the last one consumed.
+    fn expression_from(&mut self, start: usize, kind: ExpressionKind) -> ExprId {
+        let span = self.span_from(start);
+        self.ast.alloc_expr(Expression { kind, span })
+    }
+
+    fn statement_from(&mut self, start: usize, kind: StatementKind) -> StmtId {
+        let span = self.span_from(start);
+        self.ast.alloc_stmt(Statement { kind, span })
+    }
+
+    /// Notes that `expected` would have been accepted at the current position.
+    fn record(&mut self, expected: Expected) {
+        if self.expected_position != self.position {
+            self.expected.clear();
+            self.expected_position = self.position;
+        }
+
+        if !self.expected.contains(&expected) {
+            self.expected.push(expected);
+        }
+    }
+
+    /// Builds an error describing the lexeme at the current position and what was expected there.
+    fn error(&self) -> ParseError {
+        let expected = if self.expected_position == self.position {
+            self.expected.clone()
+        } else {
+            Vec::new()
+        };
+
+        match self.lexemes.get(self.position) {
+            Some(lexeme) => ParseError::UnexpectedToken {
+                found: lexeme.kind.describe(),
+                expected,
+                line: lexeme.line,
+                column: lexeme.column,
+                suggestion: None,
+            },
+            None => ParseError::UnexpectedEof {
+                expected,
+                last_token_position: self.lexemes.last().map(|lexeme| (lexeme.line, lexeme.column)),
+            },
+        }
+    }
+
+    /// Returns whether the next lexeme is `kind`, without recording it as expected.
+    /// This is for lookahead that doesn't decide what the grammar allows.
+    fn check(&self, kind: &LexemeKind) -> bool {
+        self.peek() == Some(kind)
+    }
+
+    /// Consumes the next lexeme if it is `kind`, returning whether it did.
+    fn eat(&mut self, kind: &LexemeKind<'static>) -> bool {
+        self.record(Expected::Token(kind.clone()));
+        if self.check(kind) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: &LexemeKind<'static>) -> Result<(), ParseError> {
+        if self.eat(kind) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// Fails if nesting `levels` more levels deep would go past `MAX_DEPTH`.
+    fn check_depth(&self, levels: usize) -> Result<(), ParseError> {
+        if self.depth + levels > MAX_DEPTH {
+            let (line, column) = self.location();
+            Err(ParseError::NestingTooDeep { line, column })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs `parse` one level of nesting deeper. Every cycle of recursion in the
+    /// parser goes through here, so its depth is bounded.
+    fn nested<T, F: FnOnce(&mut Parser<'a>) -> Result<T, ParseError>>(&mut self, parse: F) -> Result<T, ParseError> {
+        self.check_depth(1)?;
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, ParseError> {
+        self.record(Expected::Identifier);
+        match self.peek() {
+            Some(&LexemeKind::Identifier(name)) => {
+                self.position += 1;
+                Ok(name.to_owned())
+            },
+            _ => Err(self.error()),
+        }
+    }
+
+    /// Whether the next lexemes are a label, `name:`, which may be the name of
+    /// a typedef.
+    fn at_label(&self) -> bool {
+        matches!(self.peek(), Some(&LexemeKind::Identifier(_))) && self.peek_at(1) == Some(&LexemeKind::Colon)
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        self.check(&LexemeKind::Keyword(keyword))
+    }
+
+    /// Fails if the standard doesn't have `feature`, which the lexeme at the
+    /// current position uses.
+    fn require(&self, feature: Feature) -> Result<(), ParseError> {
+        if self.standard.has(feature) {
+            return Ok(());
+        }
+        let (line, column) = self.location();
+        Err(ParseError::NeedsStandard { feature, line, column })
+    }
+
+    /// Fails if the next lexeme is `keyword`, used for `feature` in a standard
+    /// without it, where it is only an identifier. `used` says whether what
+    /// comes after it shows that it is meant as the keyword.
+    fn require_keyword<F: Fn(&Parser) -> bool>(&self, keyword: &str, feature: Feature, used: F) -> Result<(), ParseError> {
+        if self.peek() == Some(&LexemeKind::Identifier(keyword)) && used(self) {
+            self.require(feature)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns whether the lexeme `offset` places ahead begins a type name.
+    fn at_type(&self, offset: usize) -> bool {
+        match self.peek_at(offset) {
+            Some(&LexemeKind::Keyword("int"))
+            | Some(&LexemeKind::Keyword("char"))
+            | Some(&LexemeKind::Keyword("long"))
+            | Some(&LexemeKind::Keyword("signed"))
+            | Some(&LexemeKind::Keyword("unsigned"))
+            | Some(&LexemeKind::Keyword("struct"))
+            | Some(&LexemeKind::Keyword("_Bool"))
+            | Some(&LexemeKind::Keyword("const")) => true,
+            Some(&LexemeKind::Identifier(name)) => self.typedefs.contains_key(name),
+            _ => false,
+        }
+    }
+
+    /// Consumes any number of `const` and `restrict` qualifiers, returning
+    /// whether there were any `const`s. `restrict` only promises something
+    /// about aliasing, so it makes no difference to the type.
+    fn parse_qualifiers(&mut self) -> bool {
+        let mut found = false;
+        loop {
+            if self.at_keyword("const") {
+                found = true;
+            } else if !self.at_keyword("restrict") {
+                return found;
+            }
+            self.advance();
+        }
+    }
+
+    /// Parses a type specifier along with any qualifiers written before or after it.
+    fn parse_base_type(&mut self) -> Result<Type, ParseError> {
+        let leading_const = self.parse_qualifiers();
+        let ty = self.parse_type_specifier()?;
+        let trailing_const = self.parse_qualifiers();
+
+        if leading_const || trailing_const {
+            Ok(ty.with_const())
+        } else {
+            Ok(ty)
+        }
+    }
+
+    fn parse_type_specifier(&mut self) -> Result<Type, ParseError> {
+        self.record(Expected::TypeName);
+        let ty = match self.peek() {
+            Some(&LexemeKind::Keyword("int"))
+            | Some(&LexemeKind::Keyword("char"))
+            | Some(&LexemeKind::Keyword("long"))
+            | Some(&LexemeKind::Keyword("signed"))
+            | Some(&LexemeKind::Keyword("unsigned")) => return self.parse_integer_specifier(),
+            Some(&LexemeKind::Identifier(name)) if self.typedefs.contains_key(name) => self.typedefs[name].clone(),
+            Some(&LexemeKind::Keyword("struct")) => {
+                self.advance();
+                return Ok(Type::Struct(self.expect_identifier()?));
+            },
+            Some(&LexemeKind::Keyword("_Bool")) => {
+                let (line, column) = self.location();
+                return Err(ParseError::InvalidTypeSpecifier { words: "_Bool".to_owned(), line, column });
+            },
+            _ => return Err(self.error()),
+        };
+
+        self.advance();
+        Ok(ty)
+    }
+
+    /// Parses the keywords naming an integer type, which may come in any order:
+    /// `long unsigned int` is `unsigned long`.
+    fn parse_integer_specifier(&mut self) -> Result<Type, ParseError> {
+        let start = self.position;
+        let mut words = Vec::new();
+        while let Some(&LexemeKind::Keyword(word)) = self.peek() {
+            if !["int", "char", "long", "signed", "unsigned"].contains(&word) {
+                break;
+            }
+            words.push(word);
+            self.advance();
+        }
+
+        let count = |word| words.iter().filter(|&&found| found == word).count();
+        let (ints, chars, longs) = (count("int"), count("char"), count("long"));
+        let (signs, unsigned) = (count("signed") + count("unsigned"), count("unsigned") == 1);
+        let ty = match (chars, longs) {
+            _ if ints > 1 || signs > 1 => None,
+            (0, 0) if unsigned => Some(Type::UnsignedInt),
+            (0, 0) => Some(Type::Int),
+            (0, 1) if unsigned => Some(Type::UnsignedLong),
+            (0, 1) => Some(Type::Long),
+            // `unsigned char` needs a type of its own, which there isn't yet.
+            (1, 0) if ints == 0 && !unsigned => Some(Type::Char),
+            _ => None,
+        };
+        ty.ok_or_else(|| {
+            let lexeme = &self.lexemes[start];
+            ParseError::InvalidTypeSpecifier { words: words.join(" "), line: lexeme.line, column: lexeme.column }
+        })
+    }
+
+    fn parse_pointers(&mut self, base: Type) -> Result<Type, ParseError> {
+        let mut ty = base;
+        let mut levels = 0;
+        while self.eat(&LexemeKind::Star) {
+            levels += 1;
+            self.check_depth(levels)?;
+            ty = Type::Pointer(Box::new(ty));
+            self.require_keyword("restrict", Feature::Restrict, |parser| matches!(parser.peek_at(1), Some(&LexemeKind::Identifier(_))))?;
+            if self.parse_qualifiers() {
+                ty = ty.with_const();
+            }
+        }
+        Ok(ty)
+    }
+
+    fn parse_storage_class(&mut self) -> Option<StorageClass> {
+        let storage = match self.peek() {
+            Some(&LexemeKind::Keyword("static")) => StorageClass::Static,
+            Some(&LexemeKind::Keyword("extern")) => StorageClass::Extern,
+            _ => return None,
+        };
+
+        self.advance();
+        Some(storage)
+    }
+
+    /// Records the linkage of a file-scope declaration of `name`, checking it against any
+    /// earlier declaration. A later declaration without `static` inherits internal linkage
+    /// when it is `extern` or a function, but a plain variable declaration conflicts with it.
+    fn declare_linkage(&mut self, name: &str, storage: Option<StorageClass>, is_function: bool, line: usize, column: usize) -> Result<(), ParseError> {
+        let internal = storage == Some(StorageClass::Static);
+        let (previous_internal, previous_line, previous_column) = match self.linkage.get(name) {
+            Some(&previous) => previous,
+            None => {
+                self.linkage.insert(name.to_owned(), (internal, line, column));
+                return Ok(());
+            },
+        };
+
+        let inherits = storage == Some(StorageClass::Extern) || (storage.is_none() && is_function);
+        if internal == previous_internal || (previous_internal && inherits) {
+            return Ok(());
+        }
+
+        Err(ParseError::ConflictingLinkage { name: name.to_owned(), line, column, previous_line, previous_column })
+    }
+
+    /// Parses any array dimensions following a declarator's name. `int m[2][3]`
+    /// is an array of two arrays of three ints.
+    fn parse_array_suffixes(&mut self, element: Type) -> Result<Type, ParseError> {
+        let mut sizes = Vec::new();
+        while self.eat(&LexemeKind::OpenBracket) {
+            self.check_depth(sizes.len() + 1)?;
+            // The size is only needed for its value, so its nodes are dropped
+            // once it has been evaluated.
+            let mark = self.ast.expression_count();
+            let size = self.parse_conditional()?;
+            let span = self.ast[size].span;
+            let value = const_eval(&self.ast[size], &ConstEnv::new(&self.ast)).map_err(ParseError::InvalidConstant)?;
+            self.ast.truncate_expressions(mark);
+            if value < 0 {
+                return Err(ParseError::NegativeArraySize { line: span.line, column: span.column });
+            }
+            sizes.push(value as usize);
+            self.expect(&LexemeKind::CloseBracket)?;
+        }
+
+        Ok(sizes.into_iter().rev().fold(element, |ty, size| Type::Array(Box::new(ty), size)))
+    }
+
+    /// Parses a declarator (pointers, a name, and array dimensions) applied to `base`.
+    fn parse_declarator(&mut self, base: Type) -> Result<(Type, String), ParseError> {
+        let ty = self.parse_pointers(base)?;
+        let name = self.expect_identifier()?;
+        let ty = self.parse_array_suffixes(ty)?;
+        Ok((ty, name))
+    }
+
+    /// Parses a type with no declarator name, as used in casts.
+    fn parse_type_name(&mut self) -> Result<Type, ParseError> {
+        let base = self.parse_base_type()?;
+        self.parse_pointers(base)
+    }
+
+    /// Skips past the rest of a statement or top-level item that failed to parse, so that
+    /// parsing can resume after it. Skipping restarts from `start`, the beginning of the
+    /// failed construct, so that brackets opened before the error are balanced properly;
+    /// it stops after a `;` outside any brackets, after a `}` closing the construct's
+    /// outermost block, or before a `}` that closes an enclosing block.
+    fn synchronize(&mut self, start: usize) {
+        self.position = start;
+        let mut braces = 0;
+        let mut parens = 0;
+
+        while let Some(kind) = self.peek() {
+            match *kind {
+                LexemeKind::OpenParen => parens += 1,
+                LexemeKind::CloseParen if parens > 0 => parens -= 1,
+                LexemeKind::OpenBrace => braces += 1,
+                LexemeKind::CloseBrace => {
+                    if braces == 0 {
+                        return;
+                    }
+
+                    braces -= 1;
+                    if braces == 0 {
+                        self.advance();
+                        return;
+                    }
+                },
+                LexemeKind::Semicolon if braces == 0 && parens == 0 => {
+                    self.advance();
+                    return;
+                },
+                _ => {},
+            }
+
+            self.advance();
+        }
+    }
+
+    fn parse_program(&mut self) -> Program {
+        let mut items = Vec::new();
+        while self.peek().is_some() {
+            let start = self.position;
+            let result = if self.at_keyword("typedef") {
+                self.parse_typedef().map(|_| None)
+            } else if self.at_keyword("struct") && self.peek_at(2) == Some(&LexemeKind::OpenBrace) {
+                self.parse_struct_definition().map(|definition| Some(TopLevel::Struct(definition)))
+            } else {
+                self.parse_top_level().map(Some)
+            };
+
+            match result {
+                Ok(Some(item)) => items.push(item),
+                Ok(None) => {},
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(start);
+
+                    // A stray `}` stops synchronization without being consumed.
+                    if self.position == start {
+                        self.advance();
+                    }
+                },
+            }
+        }
+
+        let mut typedefs: Vec<String> = self.typedefs.keys().cloned().collect();
+        typedefs.sort();
+        Program { items, ast: mem::take(&mut self.ast), pragmas: Vec::new(), typedefs }
+    }
+
+    /// Consumes any `inline` function specifiers. Whether a function is inlined
+    /// is up to the optimizer, whatever it says.
+    fn parse_function_specifiers(&mut self) -> Result<(), ParseError> {
+        self.require_keyword("inline", Feature::Inline, |parser| {
+            parser.at_type(1) || [Some(&LexemeKind::Keyword("static")), Some(&LexemeKind::Keyword("extern"))].contains(&parser.peek_at(1))
+        })?;
+        while self.at_keyword("inline") {
+            self.advance();
+        }
+        Ok(())
+    }
+
+    fn parse_top_level(&mut self) -> Result<TopLevel, ParseError> {
+        let start = self.position;
+        self.parse_function_specifiers()?;
+        let storage = self.parse_storage_class();
+        self.parse_function_specifiers()?;
+        let base = self.parse_base_type()?;
+        let declarator_start = self.position;
+        let ty = self.parse_pointers(base.clone())?;
+        let (line, column) = self.location();
+        let name = self.expect_identifier()?;
+
+        if !self.eat(&LexemeKind::OpenParen) {
+            self.declare_linkage(&name, storage, false, line, column)?;
+            let ty = self.parse_array_suffixes(ty)?;
+            let declarations = self.parse_declarators(storage, base, ty, name, declarator_start, true)?;
+            return Ok(TopLevel::Declaration(declarations));
+        }
+
+        self.declare_linkage(&name, storage, true, line, column)?;
+
+        let mut parameters = Vec::new();
+        let mut variadic = false;
+        if !self.eat(&LexemeKind::CloseParen) {
+            loop {
+                if self.check(&LexemeKind::Ellipsis) {
+                    let (line, column) = self.location();
+                    if parameters.is_empty() {
+                        return Err(ParseError::VariadicWithoutNamedParameter { line, column });
+                    }
+
+                    self.advance();
+                    if !self.check(&LexemeKind::CloseParen) {
+                        return Err(ParseError::VariadicNotLast { line, column });
+                    }
+
+                    variadic = true;
+                    break;
+                }
+
+                let parameter_start = self.position;
+                let ty = self.parse_type_name()?;
+                let name = self.expect_identifier()?;
+                parameters.push(Parameter { ty, name, span: self.span_from(parameter_start) });
+
+                if !self.eat(&LexemeKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(&LexemeKind::CloseParen)?;
+        }
+
+        let (body, closing_brace) = if self.eat(&LexemeKind::Semicolon) {
+            (None, None)
+        } else {
+            let body = self.parse_block()?;
+            (Some(body), Some(self.span_from(self.position - 1)))
+        };
+
+        let span = self.span_from(start);
+        Ok(TopLevel::Function(Function { storage, return_type: ty, name, parameters, variadic, body, span, closing_brace }))
+    }
+
+    /// Parses the declarators of a declaration whose first declarator has already been consumed,
+    /// up to and including the terminating semicolon. Each later declarator applies its own
+    /// pointers to `base`, and at file scope has its linkage checked. `first_start` is the
+    /// index of the first declarator's first lexeme.
+    fn parse_declarators(
+        &mut self,
+        storage: Option<StorageClass>,
+        base: Type,
+        first_ty: Type,
+        first_name: String,
+        first_start: usize,
+        file_scope: bool,
+    ) -> Result<Vec<DeclId>, ParseError> {
+        let mut declarations = Vec::new();
+        let mut ty = first_ty;
+        let mut name = first_name;
+        let mut start = first_start;
+
+        loop {
+            let initializer = if self.eat(&LexemeKind::Assign) {
+                let (line, column) = self.location();
+                let initializer = self.parse_initializer()?;
+                self.check_initializer(&ty, &initializer, line, column)?;
+                Some(initializer)
+            } else {
+                None
+            };
+            let span = self.span_from(start);
+            declarations.push(self.ast.alloc_decl(Declaration { storage, ty, name, initializer, span }));
+
+            if !self.eat(&LexemeKind::Comma) {
+                break;
+            }
+            start = self.position;
+            let (line, column) = self.location();
+            let (next_ty, next_name) = self.parse_declarator(base.clone())?;
+            if file_scope {
+                self.declare_linkage(&next_name, storage, false, line, column)?;
+            }
+            ty = next_ty;
+            name = next_name;
+        }
+
+        self.expect(&LexemeKind::Semicolon)?;
+        Ok(declarations)
+    }
+
+    fn parse_declaration(&mut self) -> Result<Vec<DeclId>, ParseError> {
+        let storage = self.parse_storage_class();
+        let base = self.parse_base_type()?;
+        let start = self.position;
+        let (ty, name) = self.parse_declarator(base.clone())?;
+        self.parse_declarators(storage, base, ty, name, start, false)
+    }
+
+    fn parse_initializer(&mut self) -> Result<Initializer, ParseError> {
+        if !self.eat(&LexemeKind::OpenBrace) {
+            return Ok(Initializer::Expr(self.parse_assignment()?));
+        }
+
+        let mut items = Vec::new();
+        while !self.eat(&LexemeKind::CloseBrace) {
+            match self.peek() {
+                Some(&LexemeKind::Dot) | Some(&LexemeKind::OpenBracket) => {
+                    let (line, column) = self.location();
+                    return Err(ParseError::UnsupportedDesignator { line, column });
+                },
+                _ => {},
+            }
+
+            items.push(self.nested(Parser::parse_initializer)?);
+
+            // A trailing comma before the closing brace is allowed.
+            if !self.eat(&LexemeKind::Comma) {
+                self.expect(&LexemeKind::CloseBrace)?;
+                break;
+            }
+        }
+
+        Ok(Initializer::List(items))
+    }
+
+    /// Checks that `initializer` fits an object of type `ty`, reporting problems at `line:column`.
+    /// Brace elision (`int m[2][2] = {1, 2, 3, 4};`) isn't supported, so nested aggregates
+    /// need their own braces.
+    fn check_initializer(&self, ty: &Type, initializer: &Initializer, line: usize, column: usize) -> Result<(), ParseError> {
+        let items = match *initializer {
+            Initializer::List(ref items) => items,
+            Initializer::Expr(expression) => {
+                return match (ty.unqualified(), &self.ast[expression].kind) {
+                    (Type::Array(element, size), ExpressionKind::StringLiteral(bytes)) if element.unqualified() == &Type::Char => {
+                        // The terminating null may be dropped if the array is exactly long enough.
+                        if bytes.len() > *size {
+                            Err(ParseError::TooManyInitializers { line, column })
+                        } else {
+                            Ok(())
+                        }
+                    },
+                    (Type::Array(..), _) => Err(ParseError::InvalidInitializer { line, column }),
+                    _ => Ok(()),
+                };
+            },
+        };
+
+        match *ty.unqualified() {
+            Type::Array(ref element, size) => {
+                if items.len() > size {
+                    return Err(ParseError::TooManyInitializers { line, column });
+                }
+                for item in items {
+                    self.check_initializer(element, item, line, column)?;
+                }
+                Ok(())
+            },
+            Type::Struct(ref tag) => {
+                let members = match self.structs.get(tag) {
+                    Some(members) => members,
+                    None => return Err(ParseError::InvalidInitializer { line, column }),
+                };
+                if items.len() > members.len() {
+                    return Err(ParseError::TooManyInitializers { line, column });
+                }
+                for (member, item) in members.iter().zip(items) {
+                    self.check_initializer(&member.ty, item, line, column)?;
+                }
+                Ok(())
+            },
+            // A scalar may be wrapped in a single pair of braces.
+            _ => match items.len() {
+                0 => Ok(()),
+                1 => match items[0] {
+                    Initializer::Expr(_) => Ok(()),
+                    Initializer::List(_) => Err(ParseError::InvalidInitializer { line, column }),
+                },
+                _ => Err(ParseError::TooManyInitializers { line, column }),
+            },
+        }
+    }
+
+    /// Parses a struct definition, `struct Tag { members };`, registering its members.
+    fn parse_struct_definition(&mut self) -> Result<StructDefinition, ParseError> {
+        let start = self.position;
+        self.expect(&LexemeKind::Keyword("struct"))?;
+        let name = self.expect_identifier()?;
+        self.expect(&LexemeKind::OpenBrace)?;
+
+        let mut members = Vec::new();
+        while !self.eat(&LexemeKind::CloseBrace) {
+            let base = self.parse_base_type()?;
+            loop {
+                let member_start = self.position;
+                let (ty, name) = self.parse_declarator(base.clone())?;
+                members.push(Member { ty, name, span: self.span_from(member_start) });
+
+                if !self.eat(&LexemeKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(&LexemeKind::Semicolon)?;
+        }
+
+        self.expect(&LexemeKind::Semicolon)?;
+        self.structs.insert(name.clone(), members.clone());
+        Ok(StructDefinition { name, members, span: self.span_from(start) })
+    }
+
+    /// Parses a typedef declaration, registering each name it declares.
+    fn parse_typedef(&mut self) -> Result<(), ParseError> {
+        self.expect(&LexemeKind::Keyword("typedef"))?;
+        let base = self.parse_base_type()?;
+
+        loop {
+            let (ty, name) = self.parse_declarator(base.clone())?;
+            self.typedefs.insert(name, ty);
+
+            if !self.eat(&LexemeKind::Comma) {
+                break;
+            }
+        }
+
+        self.expect(&LexemeKind::Semicolon)
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<BlockItem>, ParseError> {
+        self.expect(&LexemeKind::OpenBrace)?;
+
+        let mut items = Vec::new();
+        while !self.eat(&LexemeKind::CloseBrace) {
+            let start = self.position;
+            let result = if self.at_keyword("typedef") {
+                self.parse_typedef().map(|_| None)
+            } else if !self.at_label() && (self.at_type(0) || self.at_keyword("static") || self.at_keyword("extern")) {
+                // The declaration is kept, so that the rest of the block makes sense.
+                if items.iter().any(|item| matches!(*item, BlockItem::Statement(_))) {
+                    if let Err(error) = self.require(Feature::MixedDeclarations) {
+                        self.errors.push(error);
+                    }
+                }
+                self.parse_declaration().map(|declaration| Some(BlockItem::Declaration(declaration)))
+            } else {
+                self.parse_statement().map(|statement| Some(BlockItem::Statement(statement)))
+            };
+
+            match result {
+                Ok(Some(item)) => items.push(item),
+                Ok(None) => {},
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(start);
+
+                    // The block can't be closed if the input ran out; the error already says so.
+                    if self.peek().is_none() {
+                        break;
+                    }
+                },
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Parses an optional expression followed by `terminator`.
+    fn parse_optional_expression(&mut self, terminator: &LexemeKind<'static>) -> Result<Option<ExprId>, ParseError> {
+        if self.eat(terminator) {
+            return Ok(None);
+        }
+
+        let expression = self.parse_expression()?;
+        self.expect(terminator)?;
+        Ok(Some(expression))
+    }
+
+    fn parse_parenthesized(&mut self) -> Result<ExprId, ParseError> {
+        self.expect(&LexemeKind::OpenParen)?;
+        let expression = self.parse_expression()?;
+        self.expect(&LexemeKind::CloseParen)?;
+        Ok(expression)
+    }
+
+    fn parse_statement(&mut self) -> Result<StmtId, ParseError> {
+        let start = self.position;
+        let kind = self.nested(Parser::parse_statement_kind)?;
+        Ok(self.statement_from(start, kind))
+    }
+
+    fn parse_statement_kind(&mut self) -> Result<StatementKind, ParseError> {
+        if self.at_label() {
+            let label = self.expect_identifier()?;
+            self.advance();
+            return Ok(StatementKind::Labeled(label, self.parse_statement()?));
+        }
+
+        let keyword = match self.peek() {
+            Some(&LexemeKind::Keyword(keyword)) => Some(keyword),
+            Some(&LexemeKind::OpenBrace) => return Ok(StatementKind::Compound(self.parse_block()?)),
+            _ => None,
+        };
+
+        match keyword {
+            Some("return") => {
+                self.advance();
+                let expression = self.parse_expression()?;
+                self.expect(&LexemeKind::Semicolon)?;
+                Ok(StatementKind::Return(expression))
+            },
+            Some("if") => {
+                self.advance();
+                let condition = self.parse_parenthesized()?;
+                let then = self.parse_statement()?;
+                let otherwise = if self.eat(&LexemeKind::Keyword("else")) {
+                    Some(self.parse_statement()?)
+                } else {
+                    None
+                };
+                Ok(StatementKind::If(condition, then, otherwise))
+            },
+            Some("while") => {
+                self.advance();
+                let condition = self.parse_parenthesized()?;
+                let body = self.parse_statement()?;
+                Ok(StatementKind::While(condition, body))
+            },
+            Some("do") => {
+                self.advance();
+                let body = self.parse_statement()?;
+                self.expect(&LexemeKind::Keyword("while"))?;
+                let condition = self.parse_parenthesized()?;
+                self.expect(&LexemeKind::Semicolon)?;
+                Ok(StatementKind::DoWhile(body, condition))
+            },
+            Some("for") => {
+                self.advance();
+                self.expect(&LexemeKind::OpenParen)?;
+                let init = if self.at_type(0) {
+                    self.require(Feature::ForDeclarations)?;
+                    ForInit::Declaration(self.parse_declaration()?)
+                } else {
+                    ForInit::Expression(self.parse_optional_expression(&LexemeKind::Semicolon)?)
+                };
+                let condition = self.parse_optional_expression(&LexemeKind::Semicolon)?;
+                let post = self.parse_optional_expression(&LexemeKind::CloseParen)?;
+                let body = self.parse_statement()?;
+                Ok(StatementKind::For { init, condition, post, body })
+            },
+            Some("break") => {
+                self.advance();
+                self.expect(&LexemeKind::Semicolon)?;
+                Ok(StatementKind::Break)
+            },
+            Some("continue") => {
+                self.advance();
+                self.expect(&LexemeKind::Semicolon)?;
+                Ok(StatementKind::Continue)
+            },
+            Some("goto") => {
+                self.advance();
+                let label = self.expect_identifier()?;
+                self.expect(&LexemeKind::Semicolon)?;
+                Ok(StatementKind::Goto(label))
+            },
+            Some("switch") => {
+                self.advance();
+                let value = self.parse_parenthesized()?;
+                let body = self.parse_statement()?;
+                Ok(StatementKind::Switch(value, body))
+            },
+            Some("case") => {
+                self.advance();
+                let value = self.parse_conditional()?;
+                self.expect(&LexemeKind::Colon)?;
+                Ok(StatementKind::Case(value, self.parse_statement()?))
+            },
+            Some("default") => {
+                self.advance();
+                self.expect(&LexemeKind::Colon)?;
+                Ok(StatementKind::Default(self.parse_statement()?))
+            },
+            _ => {
+                let start = self.position;
+                let expression = self.parse_optional_expression(&LexemeKind::Semicolon)
+                    .map_err(|error| self.suggest_keyword(start, error))?;
+                Ok(StatementKind::Expression(expression))
+            },
+        }
+    }
+
+    /// Adds to `error` the keyword that the identifier at `start`, which begins
+    /// a statement, is a misspelling of, if the error is at the lexeme right
+    /// after it and it is close to one, as in `retrun 5;`.
+    fn suggest_keyword(&self, start: usize, error: ParseError) -> ParseError {
+        let (name, next) = match (self.lexemes.get(start), self.lexemes.get(start + 1)) {
+            (Some(&Lexeme { kind: LexemeKind::Identifier(name), .. }), Some(next)) => (name, next),
+            _ => return error,
+        };
+        match error {
+            ParseError::UnexpectedToken { found, expected, line, column, suggestion: None } if (line, column) == (next.line, next.column) => {
+                let suggestion = suggest::closest(name, lexer::keywords(self.standard));
+                ParseError::UnexpectedToken { found, expected, line, column, suggestion }
+            },
+            error => error,
+        }
+    }
+
+    /// Parses a full expression, including the comma operator.
+    fn parse_expression(&mut self) -> Result<ExprId, ParseError> {
+        let start = self.position;
+        let first = self.parse_assignment()?;
+        if !self.check(&LexemeKind::Comma) {
+            self.record(Expected::Operator);
+            return Ok(first);
+        }
+
+        let mut expressions = vec![first];
+        while self.eat(&LexemeKind::Comma) {
+            expressions.push(self.parse_assignment()?);
+        }
+
+        Ok(self.expression_from(start, ExpressionKind::Comma(expressions)))
+    }
+
+    /// Parses an assignment expression. This is the level used wherever a comma
+    /// means something other than the comma operator, e.g. in argument lists.
+    fn parse_assignment(&mut self) -> Result<ExprId, ParseError> {
+        let start = self.position;
+        let target = self.parse_conditional()?;
+
+        match self.peek().and_then(assignment_operator) {
+            Some(operator) => {
+                self.advance();
+                let value = self.nested(Parser::parse_assignment)?;
+                Ok(self.expression_from(start, ExpressionKind::Assign(operator, target, value)))
+            },
+            None => {
+                self.record(Expected::Operator);
+                Ok(target)
+            },
+        }
+    }
+
+    fn parse_conditional(&mut self) -> Result<ExprId, ParseError> {
+        let start = self.position;
+        let condition = self.parse_binary(1)?;
+        if !self.check(&LexemeKind::QuestionMark) {
+            self.record(Expected::Operator);
+            return Ok(condition);
+        }
+        self.advance();
+
+        let then = self.nested(Parser::parse_expression)?;
+        self.expect(&LexemeKind::Colon)?;
+        let otherwise = self.nested(Parser::parse_conditional)?;
+        Ok(self.expression_from(start, ExpressionKind::Conditional(condition, then, otherwise)))
+    }
+
+    /// Parses binary operators with at least `min_precedence` by precedence climbing.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<ExprId, ParseError> {
+        let start = self.position;
+        let mut lhs = self.parse_unary()?;
+        // Each operator in a chain nests the chain so far one level deeper in the tree.
+        let mut levels = 0;
+
+        while let Some(operator) = self.peek().and_then(binary_operator) {
+            let precedence = operator.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+
+            levels += 1;
+            self.check_depth(levels)?;
+            self.advance();
+            let rhs = self.parse_binary(precedence + 1)?;
+            lhs = self.expression_from(start, ExpressionKind::Binary(operator, lhs, rhs));
+        }
+
+        self.record(Expected::Operator);
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<ExprId, ParseError> {
+        let start = self.position;
+        let operator = match self.peek() {
+            Some(&LexemeKind::Minus) => UnaryOp::Negate,
+            Some(&LexemeKind::Tilde) => UnaryOp::BitwiseNot,
+            Some(&LexemeKind::Bang) => UnaryOp::LogicalNot,
+            Some(&LexemeKind::Increment) => UnaryOp::PreIncrement,
+            Some(&LexemeKind::Decrement) => UnaryOp::PreDecrement,
+            Some(&LexemeKind::Star) => UnaryOp::Dereference,
+            Some(&LexemeKind::Ampersand) => UnaryOp::AddressOf,
+            Some(&LexemeKind::OpenParen) if self.at_type(1) => {
+                self.advance();
+                let to = self.parse_type_name()?;
+                self.expect(&LexemeKind::CloseParen)?;
+                let expr = self.nested(Parser::parse_unary)?;
+                return Ok(self.expression_from(start, ExpressionKind::Cast { to, expr }));
+            },
+            Some(&LexemeKind::Keyword("sizeof")) => {
+                self.advance();
+                self.expect(&LexemeKind::OpenParen)?;
+                let ty = self.parse_type_name()?;
+                self.expect(&LexemeKind::CloseParen)?;
+                return Ok(self.expression_from(start, ExpressionKind::SizeOf(ty)));
+            },
+            _ => return self.parse_postfix(),
+        };
+
+        self.advance();
+        let operand = self.nested(Parser::parse_unary)?;
+        Ok(self.expression_from(start, ExpressionKind::Unary(operator, operand)))
+    }
+
+    fn parse_postfix(&mut self) -> Result<ExprId, ParseError> {
+        let start = self.position;
+        let mut expression = self.parse_primary()?;
+        // Like binary operators, each postfix operator nests the expression so far.
+        let mut levels = 0;
+
+        loop {
+            self.check_depth(levels)?;
+            levels += 1;
+            let operator = match self.peek() {
+                Some(&LexemeKind::Increment) => UnaryOp::PostIncrement,
+                Some(&LexemeKind::Decrement) => UnaryOp::PostDecrement,
+                Some(&LexemeKind::OpenParen) => {
+                    // Only named functions can be called, but the name may be parenthesized.
+                    // The callee was only just parsed, so it is the last node allocated, and
+                    // the call replaces it.
+                    let name = match self.ast.discard_expr(expression).kind {
+                        ExpressionKind::Variable(name) => name,
+                        _ => return Err(self.error()),
+                    };
+                    self.advance();
+                    let arguments = self.nested(Parser::parse_arguments)?;
+                    expression = self.expression_from(start, ExpressionKind::Call(name, arguments));
+                    continue;
+                },
+                Some(&LexemeKind::OpenBracket) => {
+                    self.advance();
+                    let index = self.nested(Parser::parse_expression)?;
+                    self.expect(&LexemeKind::CloseBracket)?;
+                    expression = self.expression_from(start, ExpressionKind::Subscript(expression, index));
+                    continue;
+                },
+                Some(&LexemeKind::Dot) => {
+                    self.advance();
+                    let member = self.expect_identifier()?;
+                    expression = self.expression_from(start, ExpressionKind::Member(expression, member));
+                    continue;
+                },
+                Some(&LexemeKind::Arrow) => {
+                    self.advance();
+                    let member = self.expect_identifier()?;
+                    expression = self.expression_from(start, ExpressionKind::PointerMember(expression, member));
+                    continue;
+                },
+                _ => {
+                    self.record(Expected::Operator);
+                    break;
+                },
+            };
+
+            self.advance();
+            expression = self.expression_from(start, ExpressionKind::Unary(operator, expression));
+        }
+
+        Ok(expression)
+    }
+
+    /// Parses a call's argument list after its opening parenthesis.
+    fn parse_arguments(&mut self) -> Result<Vec<ExprId>, ParseError> {
+        let mut arguments = Vec::new();
+        if self.eat(&LexemeKind::CloseParen) {
+            return Ok(arguments);
+        }
+
+        loop {
+            arguments.push(self.parse_assignment()?);
+            if !self.eat(&LexemeKind::Comma) {
+                break;
+            }
+        }
+
+        self.expect(&LexemeKind::CloseParen)?;
+        Ok(arguments)
+    }
+
+    fn parse_primary(&mut self) -> Result<ExprId, ParseError> {
+        let start = self.position;
+        let kind = match self.peek() {
+            Some(&LexemeKind::IntLiteral(value, ref ty)) => {
+                let ty = ty.clone();
+                self.advance();
+                ExpressionKind::IntLiteral(value, ty)
+            },
+            Some(&LexemeKind::CharLiteral(value)) => {
+                self.advance();
+                ExpressionKind::CharConstant(value)
+            },
+            Some(&LexemeKind::StringLiteral(_)) => {
+                // Adjacent string literals are concatenated.
+                let mut bytes = Vec::new();
+                while let Some(LexemeKind::StringLiteral(next)) = self.peek() {
+                    bytes.extend_from_slice(next);
+                    self.advance();
+                }
+                ExpressionKind::StringLiteral(bytes)
+            },
+            Some(&LexemeKind::Identifier(_)) => ExpressionKind::Variable(self.expect_identifier()?),
+            Some(&LexemeKind::OpenParen) => return self.nested(Parser::parse_parenthesized),
+            _ => {
+                self.record(Expected::Expression);
+                return Err(self.error());
+            },
+        };
+
+        Ok(self.expression_from(start, kind))
+    }
+}
+
+/// Parses a whole program, recovering from syntax errors. Returns every error found along
+/// with the program made of whatever parsed successfully.
+pub fn parse_program_partial(lexemes: &[Lexeme]) -> (Program, Vec<ParseError>) {
+    parse_program_partial_with(lexemes, Standard::default())
+}
+
+/// Like `parse_program_partial`, but for a program written in `standard`.
+pub fn parse_program_partial_with(lexemes: &[Lexeme], standard: Standard) -> (Program, Vec<ParseError>) {
+    // A `#pragma` can be between any two lexemes, so they are set aside
+    // before parsing.
+    let pragmas: Vec<_> = lexemes.iter().filter_map(pragma::parse).collect();
+    let without_pragmas: Vec<Lexeme>;
+    let lexemes = if lexemes.iter().any(|lexeme| matches!(lexeme.kind, LexemeKind::Pragma(_))) {
+        without_pragmas = lexemes.iter().filter(|lexeme| !matches!(lexeme.kind, LexemeKind::Pragma(_))).cloned().collect();
+        &without_pragmas[..]
+    } else {
+        lexemes
+    };
+    let mut parser = Parser {
+        lexemes,
+        position: 0,
+        typedefs: HashMap::new(),
+        structs: HashMap::new(),
+        linkage: HashMap::new(),
+        expected: Vec::new(),
+        expected_position: 0,
+        errors: Vec::new(),
+        ast: Ast::new(),
+        depth: 0,
+        standard,
+    };
+
+    let program = Program { pragmas, ..parser.parse_program() };
+    let mut errors = parser.errors;
+    // Everything after a `{` that isn't closed is parsed as if it were inside
+    // it, so the errors after it are most likely only that, and are left out
+    // for the one error about it.
+    if let Some(brace) = unclosed_brace(lexemes) {
+        errors.retain(|error| error.position().is_some_and(|position| position < (brace.line, brace.column)));
+        let last = lexemes.last().unwrap();
+        let end = (last.line, last.column + (last.end - last.start));
+        errors.push(ParseError::UnclosedBrace { line: brace.line, column: brace.column, end });
+    }
+    (program, errors)
+}
+
+/// The innermost `{` in `lexemes` that no `}` closes, if there is one.
+fn unclosed_brace<'a>(lexemes: &'a [Lexeme<'a>]) -> Option<&'a Lexeme<'a>> {
+    let mut open = Vec::new();
+    for lexeme in lexemes {
+        match lexeme.kind {
+            LexemeKind::OpenBrace => open.push(lexeme),
+            LexemeKind::CloseBrace => {
+                open.pop();
+            },
+            _ => {},
+        }
+    }
+    open.pop()
+}
+
+/// Parses a whole program, failing with every syntax error found if there were any.
+pub fn parse_program(lexemes: &[Lexeme]) -> Result<Program, Vec<ParseError>> {
+    parse_program_with(lexemes, Standard::default())
+}
+
+/// Like `parse_program`, but for a program written in `standard`.
+pub fn parse_program_with(lexemes: &[Lexeme], standard: Standard) -> Result<Program, Vec<ParseError>> {
+    let (program, errors) = parse_program_partial_with(lexemes, standard);
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+
+    /// Parses `source`, checking that the tree survives being printed back out as C
+    /// and reparsed, so that every test here also exercises `to_c_source`.
+    fn parse(source: &str) -> Program {
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let printed = to_c_source(&program);
+        let reparsed = parse_program(&lex_str(&printed).unwrap()).unwrap_or_else(|errors| panic!("{:?} in:\n{}", errors, printed));
+        // Typedefs are printed as the types they name.
+        let reparsed = Program { typedefs: program.typedefs.clone(), ..reparsed };
+        assert_eq!(reparsed, program, "round trip through:\n{}", printed);
+        program
+    }
+
+    fn parse_errors(source: &str) -> (Program, Vec<ParseError>) {
+        parse_program_partial(&lex_str(source).unwrap())
+    }
+
+    /// Parses `source` as the body of `main` and pretty-prints its block items.
+    fn parse_body(source: &str) -> String {
+        let printed = pretty_print(&parse(&format!("int main() {{ {} }}", source)));
+        // Drop the `program`, `function`, and `block` lines, and the indentation beneath them.
+        printed.lines().skip(3).map(|line| format!("{}\n", &line[6..])).collect()
+    }
+
+    #[test]
+    fn return_constant() {
+        assert_eq!(pretty_print(&parse("int main() { return 2; }")), "\
+program
+  function int main
+    block
+      return
+        int 2
+");
+    }
+
+    #[test]
+    fn binary_precedence() {
+        assert_eq!(parse_body("return 1 + 2 * 3 - 4;"), "\
+return
+  binary -
+    binary +
+      int 1
+      binary *
+        int 2
+        int 3
+    int 4
+");
+    }
+
+    #[test]
+    fn assignment_is_right_associative() {
+        assert_eq!(parse_body("a = b += 1;"), "\
+assign =
+  var a
+  assign +=
+    var b
+    int 1
+");
+    }
+
+    #[test]
+    fn comma_in_parenthesized_sequence() {
+        assert_eq!(parse_body("x = (a = 1, b = 2, a + b);"), "\
+assign =
+  var x
+  comma
+    assign =
+      var a
+      int 1
+    assign =
+      var b
+      int 2
+    binary +
+      var a
+      var b
+");
+    }
+
+    #[test]
+    fn comma_is_lowest_precedence() {
+        // Without parentheses the assignment binds tighter than the comma.
+        assert_eq!(parse_body("x = 1, 2;"), "\
+comma
+  assign =
+    var x
+    int 1
+  int 2
+");
+    }
+
+    #[test]
+    fn comma_in_for_post_clause() {
+        assert_eq!(parse_body("for (i = 0; i < 10; i++, j++) ;"), "\
+for
+  assign =
+    var i
+    int 0
+  binary <
+    var i
+    int 10
+  comma
+    postfix ++
+      var i
+    postfix ++
+      var j
+  empty
+");
+    }
+
+    #[test]
+    fn call_arguments_are_not_comma_expressions() {
+        assert_eq!(parse_body("f(a, b);"), "\
+call f
+  var a
+  var b
+");
+
+        // A parenthesized comma expression is still a single argument.
+        assert_eq!(parse_body("f((a, b));"), "\
+call f
+  comma
+    var a
+    var b
+");
+    }
+
+    #[test]
+    fn declarations_with_multiple_declarators() {
+        assert_eq!(parse_body("int a = 1, b;"), "\
+declare int a
+  int 1
+declare int b
+");
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let error = parse_error("int main() { return 2 }");
+        assert_eq!(error, ParseError::UnexpectedToken {
+            found: "`}`".to_owned(),
+            expected: vec![Expected::Operator, Expected::Token(LexemeKind::Semicolon)],
+            line: 1,
+            column: 23,
+            suggestion: None,
+        });
+        assert_eq!(error.to_string(), "expected operator or `;`, found `}` at 1:23");
+    }
+
+    #[test]
+    fn missing_close_paren() {
+        let error = parse_error("int main() {\n    return (1 + 2;\n}");
+        assert_eq!(error, ParseError::UnexpectedToken {
+            found: "`;`".to_owned(),
+            expected: vec![Expected::Operator, Expected::Token(LexemeKind::CloseParen)],
+            line: 2,
+            column: 18,
+            suggestion: None,
+        });
+    }
+
+    #[test]
+    fn stray_token_at_top_level() {
+        let error = parse_error("int main() { return 0; }\nreturn 1;");
+        assert_eq!(error, ParseError::UnexpectedToken {
+            found: "`return`".to_owned(),
+            expected: vec![Expected::TypeName],
+            line: 2,
+            column: 1,
+            suggestion: None,
+        });
+        assert_eq!(error.to_string(), "expected type name, found `return` at 2:1");
+        assert_eq!(error.position(), Some((2, 1)));
+    }
+
+    #[test]
+    fn misspelled_keywords_are_suggested() {
+        let error = parse_error("int main() {\n    retrun 5;\n}");
+        assert_eq!(error, ParseError::UnexpectedToken {
+            found: "integer literal `5`".to_owned(),
+            expected: vec![Expected::Operator, Expected::Token(LexemeKind::Semicolon)],
+            line: 2,
+            column: 12,
+            suggestion: Some("return"),
+        });
+        assert_eq!(error.help(), vec!["did you mean `return`?"]);
+        match parse_error("int main() {\n    itn x = 5;\n}") {
+            ParseError::UnexpectedToken { suggestion, .. } => assert_eq!(suggestion, Some("int")),
+            error => panic!("{:?}", error),
+        }
+
+        // Only an error right after the identifier is from it being a keyword,
+        // and only a name like a keyword is taken for one.
+        assert_eq!(parse_error("int main() {\n    retrun + 5 6;\n}").help(), Vec::<String>::new());
+        assert_eq!(parse_error("int main() {\n    frobnicate 5;\n}").help(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unexpected_eof() {
+        let error = parse_error("int main() { return 2; }\nint x = (1 +");
+        assert_eq!(error, ParseError::UnexpectedEof {
+            expected: vec![Expected::Expression],
+            last_token_position: Some((2, 12)),
+        });
+        assert_eq!(error.to_string(), "expected expression, found end of input after 2:12");
+        assert_eq!(error.position(), Some((2, 12)));
+    }
+
+    #[test]
+    fn unclosed_braces_are_reported_where_they_open() {
+        let error = parse_error("int main() { if (1) { return 2;\n}");
+        assert_eq!(error, ParseError::UnclosedBrace { line: 1, column: 12, end: (2, 2) });
+        assert_eq!(error.to_string(), "unclosed `{` at 1:12");
+        assert_eq!(error.labels(), vec![((2, 2), "the file ends here, before a `}` closes it")]);
+
+        // What follows the brace is taken to be inside it, so there are no
+        // errors about that, but there still are about what comes before it.
+        let (_, errors) = parse_errors("int f() { return 1 }\nint g() { return 2;\nint h() { return 3; }\n");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert_eq!(errors[0].position(), Some((1, 20)));
+        assert_eq!(errors[1], ParseError::UnclosedBrace { line: 2, column: 9, end: (3, 22) });
+    }
+
+    #[test]
+    fn pointer_declarators() {
+        assert_eq!(parse_body("int *p, q;"), "\
+declare int* p
+declare int q
+");
+    }
+
+    #[test]
+    fn cast_of_literal() {
+        assert_eq!(parse_body("return (int)5;"), "\
+return
+  cast int
+    int 5
+");
+    }
+
+    #[test]
+    fn cast_of_parenthesized_expression() {
+        assert_eq!(parse_body("return (int *)(a + b);"), "\
+return
+  cast int*
+    binary +
+      var a
+      var b
+");
+    }
+
+    #[test]
+    fn casts_nest_with_unary_operators() {
+        assert_eq!(parse_body("return -(int)(int)~x;"), "\
+return
+  prefix -
+    cast int
+      cast int
+        prefix ~
+          var x
+");
+    }
+
+    #[test]
+    fn cast_to_typedef_name() {
+        assert_eq!(parse_body("typedef int *intptr; intptr p; return (intptr)(x);"), "\
+declare int* p
+return
+  cast int*
+    var x
+");
+    }
+
+    #[test]
+    fn parenthesized_non_type_is_a_call() {
+        assert_eq!(parse_body("return (notatype)(x);"), "\
+return
+  call notatype
+    var x
+");
+    }
+
+    #[test]
+    fn char_constant_arithmetic() {
+        assert_eq!(parse_body("return 'a' + 1;"), "\
+return
+  binary +
+    char 'a'
+    int 1
+");
+    }
+
+    #[test]
+    fn char_comparison_in_condition() {
+        assert_eq!(parse_body(r"char c = 'x'; if (c == '\n') return 1;"), "\
+declare char c
+  char 'x'
+if
+  binary ==
+    var c
+    char '\\n'
+  return
+    int 1
+");
+    }
+
+    #[test]
+    fn string_literal_initializer() {
+        assert_eq!(parse_body(r#"char *s = "hello" " world";"#), "\
+declare char* s
+  string \"hello world\"
+");
+    }
+
+    fn parse_error(source: &str) -> ParseError {
+        parse_program(&lex_str(source).unwrap()).unwrap_err().remove(0)
+    }
+
+    #[test]
+    fn pointer_and_member_expressions() {
+        assert_eq!(parse_body("return *p->next[2].value + &x;"), "\
+return
+  binary +
+    prefix *
+      member .value
+        subscript
+          member ->next
+            var p
+          int 2
+    prefix &
+      var x
+");
+    }
+
+    #[test]
+    fn array_initializer_list() {
+        assert_eq!(parse_body("int a[3] = {1, 2, 3};"), "\
+declare int[3] a
+  list
+    int 1
+    int 2
+    int 3
+");
+    }
+
+    #[test]
+    fn array_sizes_are_constant_expressions() {
+        let program = parse("char buffer[2 * sizeof(int)][1 ? 3 : 1 / 0];");
+        assert_eq!(pretty_print(&program), "\
+program
+  declare char[8][3] buffer
+");
+        // Size expressions don't stay behind in the arena once evaluated.
+        assert_eq!(program.ast.expression_count(), 0);
+
+        match parse_error("int main() { int n = 2; int a[n + 1]; }") {
+            ParseError::InvalidConstant(ConstEvalError::NotConstant { span }) => assert_eq!(span.column, 31),
+            error => panic!("{:?}", error),
+        }
+        assert_eq!(parse_error("int a[1 - 2];"), ParseError::NegativeArraySize { line: 1, column: 7 });
+    }
+
+    #[test]
+    fn struct_initializer_list() {
+        let program = parse("struct Point { int x; int y; }; int main() { struct Point p = {4, 5}; }");
+        assert_eq!(program.items[0], TopLevel::Struct(StructDefinition {
+            name: "Point".to_owned(),
+            members: vec![
+                Member { ty: Type::Int, name: "x".to_owned(), span: Span::default() },
+                Member { ty: Type::Int, name: "y".to_owned(), span: Span::default() },
+            ],
+            span: Span::default(),
+        }));
+        assert_eq!(pretty_print(&program), "\
+program
+  struct Point
+    member int x
+    member int y
+  function int main
+    block
+      declare struct Point p
+        list
+          int 4
+          int 5
+");
+    }
+
+    #[test]
+    fn short_initializer_list_is_accepted() {
+        assert_eq!(parse_body("int a[4] = {1};"), "\
+declare int[4] a
+  list
+    int 1
+");
+    }
+
+    #[test]
+    fn too_many_initializers() {
+        assert_eq!(parse_error("int main() { int a[2] = {1, 2, 3}; }"), ParseError::TooManyInitializers { line: 1, column: 25 });
+        assert_eq!(
+            parse_error("struct P { int x; }; int main() { struct P p = {1, 2}; }"),
+            ParseError::TooManyInitializers { line: 1, column: 48 },
+        );
+        assert_eq!(parse_error(r#"char s[2] = "abc";"#), ParseError::TooManyInitializers { line: 1, column: 13 });
+    }
+
+    #[test]
+    fn nested_initializer_lists() {
+        assert_eq!(parse_body("int m[2][2] = {{1,2},{3,4}};"), "\
+declare int[2][2] m
+  list
+    list
+      int 1
+      int 2
+    list
+      int 3
+      int 4
+");
+
+        assert_eq!(parse_error("int m[2][2] = {{1, 2, 3}};"), ParseError::TooManyInitializers { line: 1, column: 15 });
+        assert_eq!(parse_error("int a[2] = {{{1}}, 2};"), ParseError::InvalidInitializer { line: 1, column: 12 });
+    }
+
+    #[test]
+    fn trailing_comma_in_initializer_list() {
+        assert_eq!(parse_body("int a[2] = {1, 2,};"), "\
+declare int[2] a
+  list
+    int 1
+    int 2
+");
+    }
+
+    #[test]
+    fn designated_initializers_are_unsupported() {
+        assert_eq!(parse_error("struct P { int x; }; struct P p = {.x = 1};"), ParseError::UnsupportedDesignator { line: 1, column: 36 });
+        assert_eq!(parse_error("int a[2] = {[1] = 1};"), ParseError::UnsupportedDesignator { line: 1, column: 13 });
+    }
+
+    #[test]
+    fn variadic_prototype() {
+        let program = parse("int printf(char *fmt, ...); int main() { printf(\"%d %d\", 1, 2); }");
+        assert_eq!(program.items[0], TopLevel::Function(Function {
+            storage: None,
+            return_type: Type::Int,
+            name: "printf".to_owned(),
+            parameters: vec![Parameter { ty: Type::Pointer(Box::new(Type::Char)), name: "fmt".to_owned(), span: Span::default() }],
+            variadic: true,
+            body: None,
+            span: Span::default(),
+            closing_brace: None,
+        }));
+    }
+
+    #[test]
+    fn variadic_without_named_parameter() {
+        assert_eq!(parse_error("int f(...);"), ParseError::VariadicWithoutNamedParameter { line: 1, column: 7 });
+    }
+
+    #[test]
+    fn variadic_not_last() {
+        assert_eq!(parse_error("int f(int a, ..., int b);"), ParseError::VariadicNotLast { line: 1, column: 14 });
+    }
+
+    #[test]
+    fn storage_classes_are_recorded() {
+        let program = parse("static int counter = 0; extern int errno; static int helper() { static int calls; return 0; }");
+        assert_eq!(pretty_print(&program), "\
+program
+  declare static int counter
+    int 0
+  declare extern int errno
+  function static int helper
+    block
+      declare static int calls
+      return
+        int 0
+");
+    }
+
+    #[test]
+    fn compatible_linkage() {
+        // `extern` and function redeclarations inherit the earlier internal linkage.
+        parse("static int x; extern int x; static int x;");
+        parse("static int f(); int f() { return 0; }");
+        parse("int y; extern int y; int y;");
+    }
+
+    #[test]
+    fn conflicting_linkage() {
+        assert_eq!(parse_error("static int x;\nint x;"), ParseError::ConflictingLinkage {
+            name: "x".to_owned(),
+            line: 2,
+            column: 5,
+            previous_line: 1,
+            previous_column: 12,
+        });
+        assert_eq!(parse_error("int f();\nint a, f;\nstatic int f() { return 0; }"), ParseError::ConflictingLinkage {
+            name: "f".to_owned(),
+            line: 3,
+            column: 12,
+            previous_line: 1,
+            previous_column: 5,
+        });
+    }
+
+    fn const_of(ty: Type) -> Type {
+        Type::Const(Box::new(ty))
+    }
+
+    fn pointer_to(ty: Type) -> Type {
+        Type::Pointer(Box::new(ty))
+    }
+
+    #[test]
+    fn const_declarations() {
+        let program = parse("int main() { const int x = 5; int const y = 6; const char *s; char *const p; const char *const q; }");
+        let types: Vec<Type> = function_bodies(&program)[0].1.iter().map(|item| match *item {
+            BlockItem::Declaration(ref declarations) => program.ast[declarations[0]].ty.clone(),
+            _ => panic!("expected a declaration"),
+        }).collect();
+
+        assert_eq!(types, vec![
+            const_of(Type::Int),
+            const_of(Type::Int),
+            pointer_to(const_of(Type::Char)),
+            const_of(pointer_to(Type::Char)),
+            const_of(pointer_to(const_of(Type::Char))),
+        ]);
+    }
+
+    #[test]
+    fn const_parameters_and_typedefs() {
+        let program = parse("typedef const int cint; int f(const cint a, int *const b);");
+        match program.items[0] {
+            TopLevel::Function(ref function) => assert_eq!(function.parameters, vec![
+                Parameter { ty: const_of(Type::Int), name: "a".to_owned(), span: Span::default() },
+                Parameter { ty: const_of(pointer_to(Type::Int)), name: "b".to_owned(), span: Span::default() },
+            ]),
+            _ => panic!("expected a function declaration"),
+        }
+        assert_eq!(program.typedefs, vec!["cint"]);
+        assert_eq!(parse("typedef int b, *a; b x;").typedefs, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn cast_to_const_pointer() {
+        assert_eq!(parse_body("return (const char *)p;"), "\
+return
+  cast const char*
+    var p
+");
+    }
+
+    #[test]
+    fn switch_statements() {
+        assert_eq!(parse_body("switch (x + 1) { case 1: case 'a' + 1: y = 2; break; default: return 0; }"), "\
+switch
+  binary +
+    var x
+    int 1
+  block
+    case
+      int 1
+      case
+        binary +
+          char 'a'
+          int 1
+        assign =
+          var y
+          int 2
+    break
+    default
+      return
+        int 0
+");
+        // Labels don't need a block or a `switch` around them to parse.
+        assert_eq!(parse_body("switch (x) case 1: return x ? 1 : 2; default: ;"), "\
+switch
+  var x
+  case
+    int 1
+    return
+      conditional
+        var x
+        int 1
+        int 2
+default
+  empty
+");
+    }
+
+    #[test]
+    fn integer_type_keywords() {
+        let program = parse("int main() { long a; long int b; unsigned c; unsigned int d; int unsigned e; unsigned long f; long unsigned int g; signed h; signed char i; const unsigned j; }");
+        let types: Vec<Type> = function_bodies(&program)[0].1.iter().map(|item| match *item {
+            BlockItem::Declaration(ref declarations) => program.ast[declarations[0]].ty.clone(),
+            _ => panic!("expected a declaration"),
+        }).collect();
+
+        assert_eq!(types, vec![
+            Type::Long,
+            Type::Long,
+            Type::UnsignedInt,
+            Type::UnsignedInt,
+            Type::UnsignedInt,
+            Type::UnsignedLong,
+            Type::UnsignedLong,
+            Type::Int,
+            Type::Char,
+            const_of(Type::UnsignedInt),
+        ]);
+        assert_eq!(parse_body("return (unsigned long)sizeof(long);"), "\
+return
+  cast unsigned long
+    sizeof long
+");
+    }
+
+    #[test]
+    fn invalid_integer_type_keywords() {
+        for &(source, words) in &[
+            ("long long x;", "long long"),
+            ("signed unsigned x;", "signed unsigned"),
+            ("unsigned char x;", "unsigned char"),
+            ("int long char x;", "int long char"),
+            ("int int x;", "int int"),
+        ] {
+            assert_eq!(
+                parse_error(source).to_string(),
+                format!("`{}` is not a supported type at 1:1", words),
+                "{}", source,
+            );
+        }
+    }
+
+    fn function_bodies(program: &Program) -> Vec<(&str, &Vec<BlockItem>)> {
+        program.items.iter().filter_map(|item| match *item {
+            TopLevel::Function(Function { ref name, body: Some(ref body), .. }) => Some((name.as_str(), body)),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn goto_and_labels() {
+        // A label may share its name with a typedef, and may label another label.
+        assert_eq!(parse_body("typedef int t; goto t; t: done: return 0;"), "\
+goto t
+label t
+  label done
+    return
+      int 0
+");
+    }
+
+    #[test]
+    fn recovers_from_independent_bad_statements() {
+        let (program, errors) = parse_errors("int main() {\n    int x = ;\n    x = 1;\n    return x +;\n    return 0;\n}");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "expected `{` or expression, found `;` at 2:13");
+        assert_eq!(errors[1].to_string(), "expected expression, found `;` at 4:15");
+
+        // The good statements around the bad ones are kept.
+        assert_eq!(pretty_print(&program), "\
+program
+  function int main
+    block
+      assign =
+        var x
+        int 1
+      return
+        int 0
+");
+    }
+
+    #[test]
+    fn missing_semicolon_does_not_cascade() {
+        let (_, errors) = parse_errors("int main() {\n    int x = 1\n    int y = 2;\n    return x + y;\n}");
+        assert_eq!(errors.len(), 1);
+
+        let (_, errors) = parse_errors("int main() {\n    if (1) {\n        return 1\n    }\n    return 0;\n}\nint f() { return 2; }");
+        assert_eq!(errors.len(), 1);
+
+        let (_, errors) = parse_errors("int main() {\n    for (i = 0; i < ; i++) {\n        x;\n    }\n    return 0;\n}");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn bad_function_followed_by_good_function() {
+        let (program, errors) = parse_errors("int f( { return 1; }\nint g() { return 2; }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(pretty_print(&program), "\
+program
+  function int g
+    block
+      return
+        int 2
+");
+    }
+
+    #[test]
+    fn recovers_at_end_of_input() {
+        let (program, errors) = parse_errors("int main() { return 1 + ; ");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(function_bodies(&program).len(), 1);
+
+        let (_, errors) = parse_errors("} int main() { return 0; }");
+        assert_eq!(errors.len(), 1);
+    }
+
+    fn range(span: Span) -> (usize, usize) {
+        (span.start, span.end)
+    }
+
+    #[test]
+    fn spans_cover_whole_nodes() {
+        let source = "int main() {\n    int *p = 0, q;\n    while (q) {\n        q = p[1] + 2;\n    }\n}";
+        let program = parse(source);
+        let ast = &program.ast;
+        let function = match program.items[0] {
+            TopLevel::Function(ref function) => function,
+            _ => panic!("expected a function definition"),
+        };
+        assert_eq!(range(function.span), (0, 77));
+
+        let body = function.body.as_ref().unwrap();
+        match body[0] {
+            BlockItem::Declaration(ref declarations) => {
+                assert_eq!(range(ast[declarations[0]].span), (21, 27));
+                assert_eq!(range(ast[declarations[1]].span), (29, 30));
+            },
+            _ => panic!("expected a declaration"),
+        }
+
+        let (condition, block) = match body[1] {
+            BlockItem::Statement(statement) => match ast[statement].kind {
+                StatementKind::While(condition, block) => {
+                    // The loop runs from `while` to the `}` on a later line.
+                    let span = ast[statement].span;
+                    assert_eq!(range(span), (36, 75));
+                    assert_eq!((span.line, span.column), (3, 5));
+                    (condition, block)
+                },
+                _ => panic!("expected a while loop"),
+            },
+            _ => panic!("expected a statement"),
+        };
+        assert_eq!(range(ast[condition].span), (43, 44));
+        assert_eq!(range(ast[block].span), (46, 75));
+
+        let statement = match ast[block].kind {
+            StatementKind::Compound(ref items) => match items[0] {
+                BlockItem::Statement(statement) => &ast[statement],
+                _ => panic!("expected a statement"),
+            },
+            _ => panic!("expected a compound statement"),
+        };
+        assert_eq!(range(statement.span), (56, 69));
+        assert_eq!((statement.span.line, statement.span.column), (4, 9));
+
+        let assignment = match statement.kind {
+            StatementKind::Expression(Some(expression)) => &ast[expression],
+            _ => panic!("expected an expression statement"),
+        };
+        assert_eq!(range(assignment.span), (56, 68));
+        let value = match assignment.kind {
+            ExpressionKind::Assign(_, _, value) => &ast[value],
+            _ => panic!("expected an assignment"),
+        };
+        assert_eq!(range(value.span), (60, 68));
+        match value.kind {
+            ExpressionKind::Binary(_, lhs, rhs) => {
+                assert_eq!(range(ast[lhs].span), (60, 64));
+                assert_eq!(range(ast[rhs].span), (67, 68));
+            },
+            _ => panic!("expected a binary expression"),
+        }
+    }
+
+    #[test]
+    fn parenthesized_expressions_keep_inner_span() {
+        let program = parse("int main() { return (1 + 2) * 3; }");
+        let ast = &program.ast;
+        let value = match function_bodies(&program)[0].1[0] {
+            BlockItem::Statement(statement) => match ast[statement].kind {
+                StatementKind::Return(value) => &ast[value],
+                _ => panic!("expected a return"),
+            },
+            _ => panic!("expected a statement"),
+        };
+        match value.kind {
+            ExpressionKind::Binary(BinaryOp::Multiply, lhs, _) => {
+                assert_eq!(range(value.span), (20, 31));
+                assert_eq!(range(ast[lhs].span), (21, 26));
+            },
+            _ => panic!("expected a multiplication"),
+        }
+    }
+
+    fn parse_errors_in(source: &str, standard: Standard) -> Vec<String> {
+        let lexemes = ::lexer::lex_str_with(source, standard).unwrap();
+        parse_program_partial_with(&lexemes, standard).1.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn c89_declarations_come_first() {
+        let source = "int main() {\n    int a = 1;\n    a = 2;\n    int b = a;\n    for (int i = 0; i < b; i++) a++;\n    return a;\n}";
+        assert_eq!(parse_errors_in(source, Standard::C89), [
+            "declarations after statements at 4:5 are a C99 feature; use --std=c99",
+            "declarations in `for` loops at 5:10 are a C99 feature; use --std=c99",
+        ]);
+        assert!(parse_errors_in(source, Standard::C99).is_empty());
+        // Declarations in nested blocks come first in those blocks.
+        assert!(parse_errors_in("int main() { int a = 1; a = 2; { int b = a; return b; } }", Standard::C89).is_empty());
+    }
+
+    #[test]
+    fn inline_and_restrict() {
+        let program = parse("static inline int f(int *restrict p, const int *const restrict q) { return *p + *q; }");
+        match program.items[0] {
+            TopLevel::Function(ref function) => {
+                assert_eq!(function.storage, Some(StorageClass::Static));
+                assert_eq!(function.parameters[0].ty, Type::Pointer(Box::new(Type::Int)));
+            },
+            _ => panic!("expected a function"),
+        }
+        assert!(parse_errors_in("inline static int f() { return 0; }", Standard::C99).is_empty());
+
+        assert_eq!(parse_errors_in("inline int f() { return 0; }", Standard::C89), [
+            "`inline` functions at 1:1 are a C99 feature; use --std=c99",
+        ]);
+        assert_eq!(parse_errors_in("int f(int *restrict p) { return 0; }", Standard::C89), [
+            "`restrict` pointers at 1:12 are a C99 feature; use --std=c99",
+        ]);
+        // Before C99 they are only names.
+        assert!(parse_errors_in("int inline; int *restrict;", Standard::C89).is_empty());
+        assert_eq!(parse_errors_in("_Bool b;", Standard::C99), ["`_Bool` is not a supported type at 1:1"]);
+    }
+}
@@ -0,0 +1,168 @@
+//! What the WebAssembly playground in `wasm` asks the compiler for, and how it
+//! is answered, both as JSON so that they cross into JavaScript as strings.
+//!
+//! It is asked with the options, all of which can be left out:
+//!
+//! ```text
+//! {"emit": ["tokens", "ast", "asm"], "target": "x86_64-linux", "opt_level": 1, "std": "c99", "file_name": "main.c"}
+//! ```
+//!
+//! and answers with each kind of output it could make, by the name it was
+//! asked for by, and the diagnostics, each as `--error-format=json` writes it:
+//!
+//! ```text
+//! {"success": true, "artifacts": {"tokens": "...", "ast": "...", "asm": "..."}, "diagnostics": []}
+//! ```
+//!
+//! The diagnostics are those from the stage furthest along that was asked
+//! for, which are the earlier stages' too, since each stops at the first that
+//! fails. `success` says whether that stage succeeded, and the artifacts
+//! include those of earlier stages whether or not it did, so that the tokens
+//! of a program that doesn't parse can be seen.
+
+use serde::Deserialize;
+use serde_json;
+use codegen::{OptLevel, TargetSpec};
+use compile::{compile_str, CompileOptions, Emit};
+use diagnostics::{self, Diagnostic, SourceMap};
+use semantic::Severity;
+use standard::Standard;
+
+/// The options the playground is asked with.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Request {
+    emit: Vec<String>,
+    target: Option<String>,
+    opt_level: u8,
+    std: Option<String>,
+    file_name: Option<String>,
+}
+
+impl Request {
+    /// The kinds of output asked for, by default assembly, and the options to
+    /// compile with, or what is wrong with them.
+    fn options(self) -> Result<(Vec<(String, Emit)>, CompileOptions), String> {
+        let mut emits = Vec::new();
+        for name in self.emit {
+            match Emit::from_name(&name) {
+                Some(emit) => emits.push((name, emit)),
+                None => return Err(format!("unknown kind of output `{}`", name)),
+            }
+        }
+        if emits.is_empty() {
+            emits.push(("asm".to_owned(), Emit::Assembly));
+        }
+        let target = match self.target {
+            Some(name) => TargetSpec::from_name(&name).ok_or_else(|| format!("unknown target `{}`", name))?,
+            None => TargetSpec::default(),
+        };
+        let opt_level = match self.opt_level {
+            0 => OptLevel::O0,
+            1 => OptLevel::O1,
+            2 => OptLevel::O2,
+            level => return Err(format!("unknown optimization level {}", level)),
+        };
+        let standard = match self.std {
+            Some(name) => Standard::from_name(&name).ok_or_else(|| format!("unknown standard `{}`", name))?,
+            None => Standard::default(),
+        };
+        Ok((emits, CompileOptions { target, opt_level, standard, file_name: self.file_name, ..CompileOptions::default() }))
+    }
+}
+
+/// Compiles `source` as `request`, the options as JSON, asks, and answers with
+/// what it made, as JSON.
+pub fn compile(source: &str, request: &str) -> String {
+    let request: Request = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(err) => return rejected(&format!("invalid options: {}", err)),
+    };
+    let (emits, options) = match request.options() {
+        Ok(options) => options,
+        Err(message) => return rejected(&message),
+    };
+
+    let mut artifacts = Vec::new();
+    let mut furthest = None;
+    for (name, emit) in emits {
+        let result = compile_str(source, &CompileOptions { emit, ..options.clone() });
+        if let Ok(ref output) = result {
+            artifacts.push((name, output.output.clone()));
+        }
+        if furthest.as_ref().is_none_or(|&(stage, _)| emit > stage) {
+            furthest = Some((emit, result));
+        }
+    }
+    let (success, found) = match furthest.expect("at least one kind of output is asked for").1 {
+        Ok(output) => (true, output.warnings),
+        Err(err) => (false, err.diagnostics),
+    };
+    let map = SourceMap::new(options.file_name.as_ref().map_or("<source>", String::as_str), source);
+    let diagnostics: Vec<String> = found.iter().map(|diagnostic| diagnostics::to_json(&map, diagnostic)).collect();
+    answer(success, &artifacts, &diagnostics)
+}
+
+/// The answer to a request that couldn't be understood, with why as its one
+/// diagnostic.
+fn rejected(message: &str) -> String {
+    answer(false, &[], &[diagnostics::bare_to_json(&Diagnostic::new(Severity::Error, message.to_owned(), None))])
+}
+
+/// The answer with `artifacts`, by name, and `diagnostics`, each already
+/// written as JSON, in the order they are in.
+fn answer(success: bool, artifacts: &[(String, String)], diagnostics: &[String]) -> String {
+    let artifacts: Vec<String> = artifacts.iter()
+        .map(|(name, text)| format!("{}:{}", serde_json::to_string(name).unwrap(), serde_json::to_string(text).unwrap()))
+        .collect();
+    format!("{{\"success\":{},\"artifacts\":{{{}}},\"diagnostics\":[{}]}}", success, artifacts.join(","), diagnostics.join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value;
+
+    fn compile_to_value(source: &str, request: &str) -> Value {
+        serde_json::from_str(&compile(source, request)).unwrap()
+    }
+
+    #[test]
+    fn each_kind_asked_for_is_answered_with() {
+        let answer = compile_to_value("int main() { return 0; }", r#"{"emit": ["tokens", "asm"], "target": "aarch64-linux"}"#);
+        assert_eq!(answer["success"], true);
+        assert!(answer["artifacts"]["tokens"].as_str().unwrap().starts_with("Keyword \"int\" 1:1"));
+        assert!(answer["artifacts"]["asm"].as_str().unwrap().contains("main:"));
+        assert_eq!(answer["diagnostics"], Value::Array(Vec::new()));
+        // Assembly, for the host, is what is made by default.
+        assert!(compile_to_value("int main() { return 0; }", "{}")["artifacts"]["asm"].is_string());
+    }
+
+    #[test]
+    fn earlier_stages_are_answered_with_when_later_ones_fail() {
+        let answer = compile_to_value("int main() { return 1 2; }", r#"{"emit": ["tokens", "ast"], "file_name": "main.c"}"#);
+        assert_eq!(answer["success"], false);
+        assert!(answer["artifacts"]["tokens"].is_string());
+        assert!(answer["artifacts"]["ast"].is_null());
+        assert_eq!(answer["diagnostics"].as_array().unwrap().len(), 1);
+        assert_eq!(answer["diagnostics"][0]["code"], "E0010");
+        assert_eq!(answer["diagnostics"][0]["file"], "main.c");
+        assert_eq!(answer["diagnostics"][0]["span"]["column_start"], 23);
+    }
+
+    #[test]
+    fn bad_options_are_rejected_with_why() {
+        for &(request, message) in &[
+            ("{", "invalid options: EOF while parsing an object at line 1 column 1"),
+            (r#"{"emit": ["binary"]}"#, "unknown kind of output `binary`"),
+            (r#"{"target": "mips-linux"}"#, "unknown target `mips-linux`"),
+            (r#"{"opt_level": 3}"#, "unknown optimization level 3"),
+            (r#"{"std": "c23"}"#, "unknown standard `c23`"),
+        ] {
+            let answer = compile_to_value("int main() { return 0; }", request);
+            assert_eq!(answer["success"], false);
+            assert_eq!(answer["diagnostics"][0]["message"], message);
+            assert_eq!(answer["diagnostics"][0]["file"], Value::Null);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! `#pragma GCC diagnostic`, which changes how warnings are reported from the
+//! line it is on, as it does with `gcc`:
+//!
+//! ```c
+//! #pragma GCC diagnostic push
+//! #pragma GCC diagnostic ignored "-Wunused-variable"
+//! int f() { int unused; return 0; }
+//! #pragma GCC diagnostic pop
+//! ```
+//!
+//! `ignored`, `warning` and `error` report the warning they name as they say,
+//! and `pop` goes back to how warnings were reported at the matching `push`.
+//! Any other `#pragma` is ignored.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use lexer::{Lexeme, LexemeKind};
+use semantic::{SemanticError, WARNINGS};
+
+/// What a `#pragma GCC diagnostic` does.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "option"))]
+pub enum DiagnosticAction {
+    Push,
+    Pop,
+    /// Turns off the warning the option, such as `-Wunused-variable`, is for.
+    Ignored(String),
+    Warning(String),
+    Error(String),
+}
+
+/// A `#pragma GCC diagnostic`, and the line and column it starts at.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DiagnosticPragma {
+    pub action: DiagnosticAction,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl DiagnosticPragma {
+    /// The option an `ignored`, `warning` or `error` pragma names, as it is
+    /// written.
+    pub fn option(&self) -> Option<&str> {
+        match self.action {
+            DiagnosticAction::Ignored(ref option) | DiagnosticAction::Warning(ref option) | DiagnosticAction::Error(ref option) => Some(option),
+            DiagnosticAction::Push | DiagnosticAction::Pop => None,
+        }
+    }
+
+    /// The warning, from `WARNINGS`, that the pragma's option is for, if it
+    /// names one.
+    pub fn warning(&self) -> Option<&'static str> {
+        let name = self.option()?.strip_prefix("-W")?;
+        WARNINGS.iter().find(|&&warning| warning == name).cloned()
+    }
+}
+
+/// The `#pragma GCC diagnostic` that `lexeme` is, if it is one.
+pub fn parse(lexeme: &Lexeme) -> Option<DiagnosticPragma> {
+    let text = match lexeme.kind {
+        LexemeKind::Pragma(text) => text,
+        _ => return None,
+    };
+    let mut words = text.trim_start_matches('#').split_whitespace().skip(1);
+    if words.next() != Some("GCC") || words.next() != Some("diagnostic") {
+        return None;
+    }
+    let kind = words.next()?;
+    let mut option = || {
+        let option = words.next()?;
+        Some(option.strip_prefix('"')?.strip_suffix('"')?.to_owned())
+    };
+    let action = match kind {
+        "push" => DiagnosticAction::Push,
+        "pop" => DiagnosticAction::Pop,
+        "ignored" => DiagnosticAction::Ignored(option()?),
+        "warning" => DiagnosticAction::Warning(option()?),
+        "error" => DiagnosticAction::Error(option()?),
+        _ => return None,
+    };
+    Some(DiagnosticPragma { action, line: lexeme.line, column: lexeme.column })
+}
+
+/// Warns about each of `pragmas` that names an option that isn't for a
+/// warning.
+pub fn check(pragmas: &[DiagnosticPragma]) -> Vec<SemanticError> {
+    pragmas.iter()
+        .filter(|pragma| pragma.option().is_some() && pragma.warning().is_none())
+        .map(|pragma| SemanticError::UnknownWarningInPragma {
+            option: pragma.option().unwrap().to_owned(),
+            line: pragma.line,
+            column: pragma.column,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+
+    fn pragmas(source: &str) -> Vec<DiagnosticPragma> {
+        lex_str(source).unwrap().iter().filter_map(parse).collect()
+    }
+
+    #[test]
+    fn diagnostic_pragmas_are_understood() {
+        let source = "#pragma GCC diagnostic push\n  #  pragma GCC diagnostic ignored \"-Wunused-variable\"\n#pragma once\n#pragma GCC diagnostic pop\n";
+        assert_eq!(pragmas(source), vec![
+            DiagnosticPragma { action: DiagnosticAction::Push, line: 1, column: 1 },
+            DiagnosticPragma { action: DiagnosticAction::Ignored("-Wunused-variable".to_owned()), line: 2, column: 3 },
+            DiagnosticPragma { action: DiagnosticAction::Pop, line: 4, column: 1 },
+        ]);
+        assert_eq!(pragmas(source)[1].warning(), Some("unused-variable"));
+        // Those that don't say what to do are ignored, as any other pragma is.
+        assert_eq!(pragmas("#pragma GCC diagnostic ignored\n#pragma GCC diagnostic frob \"-Wall\"\n"), vec![]);
+    }
+
+    #[test]
+    fn unknown_warnings_are_warned_about() {
+        let pragmas = pragmas("#pragma GCC diagnostic warning \"-Wfrobnicate\"\n#pragma GCC diagnostic error \"return-type\"\n#pragma GCC diagnostic error \"-Wreturn-type\"\n");
+        assert_eq!(check(&pragmas), vec![
+            SemanticError::UnknownWarningInPragma { option: "-Wfrobnicate".to_owned(), line: 1, column: 1 },
+            SemanticError::UnknownWarningInPragma { option: "return-type".to_owned(), line: 2, column: 1 },
+        ]);
+    }
+}
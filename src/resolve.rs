@@ -0,0 +1,514 @@
+//! Resolves every use of a name to the declaration it refers to, following C's
+//! block scoping: a name is in scope from the end of its declarator to the end
+//! of the block it is declared in, and inner declarations shadow outer ones.
+//! A name may only be declared once in each block, but any number of times at
+//! file scope, where redeclarations are checked for compatibility instead: every
+//! declaration of a function must agree on its return type, parameter types,
+//! and whether it is variadic, and every declaration of a global on its type.
+//! A later declaration replaces an earlier one in scope, so calls after a
+//! definition resolve to the definition, and those before it to a prototype
+//! with the same signature.
+//!
+//! Labels are names of their own kind, in scope throughout the function that
+//! defines them.
+
+use std::collections::HashMap;
+use ast::*;
+use ast::visit::{self, Visitor};
+use semantic::SemanticError;
+use suggest;
+
+/// A declaration that a name can refer to.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Symbol {
+    /// A variable, at file scope or in a block.
+    Variable(DeclId),
+    /// The parameter numbered `index` of the function at `function` in the
+    /// program's items.
+    Parameter { function: usize, index: usize },
+    /// The function declared by the item at this index in the program's items.
+    /// This is the declaration in scope at the use, which may be a prototype.
+    Function(usize),
+}
+
+/// What each `Variable` and `Call` expression refers to. Calls to undeclared
+/// functions are left out.
+pub type Resolutions = HashMap<ExprId, Symbol>;
+
+/// What a file-scope declaration says about the name it declares.
+#[derive(Debug, PartialEq)]
+enum Signature {
+    Object(Type),
+    /// Qualifiers on the return type and parameters don't matter to callers,
+    /// and are left out.
+    Function { return_type: Type, parameters: Vec<Type>, variadic: bool },
+}
+
+impl Signature {
+    fn of_function(function: &Function) -> Signature {
+        Signature::Function {
+            return_type: function.return_type.unqualified().clone(),
+            parameters: function.parameters.iter().map(|parameter| parameter.ty.unqualified().clone()).collect(),
+            variadic: function.variadic,
+        }
+    }
+}
+
+/// A declaration of a name in a scope.
+struct Binding {
+    symbol: Symbol,
+    span: Span,
+    /// Whether the name may be declared again in the same scope. This is so of
+    /// `extern` declarations, which all refer to the same object.
+    redeclarable: bool,
+}
+
+struct Resolver {
+    /// The innermost scope is last, and the first is file scope.
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Where each name was last declared in a scope that has since ended, for
+    /// pointing out declarations a failed lookup couldn't see.
+    ended: HashMap<String, Span>,
+    /// The index of the item being resolved in the program's items.
+    item: usize,
+    /// The labels defined in the function being resolved.
+    labels: HashMap<String, Span>,
+    /// The signature of the latest file-scope declaration of each name, and where it is.
+    signatures: HashMap<String, (Signature, Span)>,
+    resolutions: Resolutions,
+    errors: Vec<SemanticError>,
+}
+
+impl Resolver {
+    /// Declares `name` in the innermost scope. Redeclaring a name already in it
+    /// is an error, except at file scope, and the original declaration stays.
+    fn declare(&mut self, name: &str, symbol: Symbol, span: Span, redeclarable: bool) {
+        let file_scope = self.scopes.len() == 1;
+        let scope = self.scopes.last_mut().unwrap();
+        if !file_scope {
+            if let Some(previous) = scope.get(name) {
+                if !(redeclarable && previous.redeclarable) {
+                    self.errors.push(SemanticError::Redeclaration {
+                        name: name.to_owned(),
+                        line: span.line,
+                        column: span.column,
+                        previous_line: previous.span.line,
+                        previous_column: previous.span.column,
+                    });
+                }
+                return;
+            }
+        }
+        scope.insert(name.to_owned(), Binding { symbol, span, redeclarable });
+    }
+
+    /// Records the `signature` of a file-scope declaration of `name`, checking
+    /// it against the previous one.
+    fn redeclare(&mut self, name: &str, signature: Signature, span: Span) {
+        if let Some(&(ref previous, previous_span)) = self.signatures.get(name) {
+            if *previous != signature {
+                self.errors.push(SemanticError::ConflictingTypes {
+                    name: name.to_owned(),
+                    line: span.line,
+                    column: span.column,
+                    previous_line: previous_span.line,
+                    previous_column: previous_span.column,
+                });
+            }
+        }
+        self.signatures.insert(name.to_owned(), (signature, span));
+    }
+
+    /// Declares the parameters of `function`, the item being resolved.
+    fn declare_parameters(&mut self, function: &Function) {
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            let symbol = Symbol::Parameter { function: self.item, index };
+            self.declare(&parameter.name, symbol, parameter.span, false);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Symbol> {
+        self.scopes.iter().rev().filter_map(|scope| scope.get(name)).map(|binding| binding.symbol).next()
+    }
+
+    /// Runs `resolve` in a new innermost scope.
+    fn scoped<F: FnOnce(&mut Resolver)>(&mut self, resolve: F) {
+        self.scopes.push(HashMap::new());
+        resolve(self);
+        for (name, binding) in self.scopes.pop().unwrap() {
+            self.ended.insert(name, binding.span);
+        }
+    }
+}
+
+/// Collects the labels defined in a function body.
+struct Labels {
+    labels: HashMap<String, Span>,
+    errors: Vec<SemanticError>,
+}
+
+impl Visitor for Labels {
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        if let StatementKind::Labeled(ref name, _) = ast[statement].kind {
+            let span = ast[statement].span;
+            match self.labels.get(name) {
+                Some(previous) => self.errors.push(SemanticError::DuplicateLabel {
+                    name: name.clone(),
+                    line: span.line,
+                    column: span.column,
+                    previous_line: previous.line,
+                    previous_column: previous.column,
+                }),
+                None => {
+                    self.labels.insert(name.clone(), span);
+                },
+            }
+        }
+        visit::walk_stmt(self, ast, statement);
+    }
+
+    fn visit_expr(&mut self, _ast: &Ast, _expression: ExprId) {}
+}
+
+impl Visitor for Resolver {
+    fn visit_program(&mut self, program: &Program) {
+        for (index, item) in program.items.iter().enumerate() {
+            self.item = index;
+            self.visit_top_level(&program.ast, item);
+        }
+    }
+
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        // Declared before its body, so that it can call itself.
+        self.redeclare(&function.name, Signature::of_function(function), function.span);
+        self.declare(&function.name, Symbol::Function(self.item), function.span, true);
+
+        match function.body {
+            // The parameters are in the same scope as the body's outermost block.
+            Some(ref body) => {
+                let mut labels = Labels { labels: HashMap::new(), errors: Vec::new() };
+                labels.visit_block(ast, body);
+                self.labels = labels.labels;
+                self.errors.append(&mut labels.errors);
+
+                self.scoped(|resolver| {
+                    resolver.declare_parameters(function);
+                    visit::walk_block(resolver, ast, body);
+                });
+            },
+            // A prototype's parameters can't be referred to, but still can't share names.
+            None => {
+                self.scopes.push(HashMap::new());
+                self.declare_parameters(function);
+                self.scopes.pop();
+            },
+        }
+    }
+
+    fn visit_block(&mut self, ast: &Ast, items: &[BlockItem]) {
+        self.scoped(|resolver| visit::walk_block(resolver, ast, items));
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        // The name is in scope in its own initializer.
+        let Declaration { ref name, ref ty, span, storage, .. } = ast[declaration];
+        if self.scopes.len() == 1 {
+            self.redeclare(name, Signature::Object(ty.clone()), span);
+        }
+        self.declare(name, Symbol::Variable(declaration), span, storage == Some(StorageClass::Extern));
+        visit::walk_declaration(self, ast, declaration);
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        match ast[statement].kind {
+            // A declaration in a `for` is scoped to the loop.
+            StatementKind::For { .. } => self.scoped(|resolver| visit::walk_stmt(resolver, ast, statement)),
+            StatementKind::Goto(ref name) if !self.labels.contains_key(name) => {
+                let span = ast[statement].span;
+                self.errors.push(SemanticError::UndefinedLabel { name: name.clone(), line: span.line, column: span.column });
+            },
+            _ => visit::walk_stmt(self, ast, statement),
+        }
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        match ast[expression].kind {
+            ExpressionKind::Variable(ref name) => match self.lookup(name) {
+                Some(symbol) => {
+                    self.resolutions.insert(expression, symbol);
+                },
+                None => {
+                    let span = ast[expression].span;
+                    let out_of_scope = self.ended.get(name).map(|span| (span.line, span.column));
+                    // A declaration that has gone out of scope explains it
+                    // better than a name that is like it.
+                    let suggestion = match out_of_scope {
+                        Some(_) => None,
+                        None => suggest::closest(name, self.scopes.iter().flat_map(|scope| scope.keys().map(String::as_str))).map(str::to_owned),
+                    };
+                    self.errors.push(SemanticError::UndeclaredIdentifier {
+                        name: name.clone(),
+                        line: span.line,
+                        column: span.column,
+                        out_of_scope,
+                        suggestion,
+                    });
+                },
+            },
+            // Calling an undeclared function declares it implicitly, as in C89,
+            // so only calls to declared functions are resolved.
+            ExpressionKind::Call(ref name, _) => {
+                if let Some(symbol) = self.lookup(name) {
+                    self.resolutions.insert(expression, symbol);
+                }
+            },
+            _ => {},
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// Resolves the names used in `program`, returning what each refers to along
+/// with an error for each use of an undeclared name.
+pub fn resolve(program: &Program) -> (Resolutions, Vec<SemanticError>) {
+    let mut resolver = Resolver {
+        scopes: vec![HashMap::new()],
+        ended: HashMap::new(),
+        item: 0,
+        labels: HashMap::new(),
+        signatures: HashMap::new(),
+        resolutions: HashMap::new(),
+        errors: Vec::new(),
+    };
+    resolver.visit_program(program);
+    (resolver.resolutions, resolver.errors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    /// Resolves `source` and returns what each variable use refers to, in source
+    /// order, by where it was declared.
+    fn resolve_uses(source: &str) -> Vec<(String, (usize, usize))> {
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let (resolutions, errors) = resolve(&program);
+        assert_eq!(errors, vec![]);
+
+        let mut uses: Vec<(ExprId, Symbol)> = resolutions.into_iter()
+            .filter(|&(id, _)| matches!(program.ast[id].kind, ExpressionKind::Variable(_)))
+            .collect();
+        uses.sort_by_key(|&(id, _)| program.ast[id].span.start);
+        uses.into_iter().map(|(id, symbol)| {
+            let name = match program.ast[id].kind {
+                ExpressionKind::Variable(ref name) => name.clone(),
+                _ => unreachable!(),
+            };
+            let span = match symbol {
+                Symbol::Variable(declaration) => program.ast[declaration].span,
+                Symbol::Parameter { function, index } => match program.items[function] {
+                    TopLevel::Function(ref function) => function.parameters[index].span,
+                    _ => unreachable!(),
+                },
+                Symbol::Function(item) => match program.items[item] {
+                    TopLevel::Function(ref function) => function.span,
+                    _ => unreachable!(),
+                },
+            };
+            (name, (span.line, span.column))
+        }).collect()
+    }
+
+    fn errors(source: &str) -> Vec<SemanticError> {
+        resolve(&parse_program(&lex_str(source).unwrap()).unwrap()).1
+    }
+
+    #[test]
+    fn shadowing_resolves_to_innermost_declaration() {
+        let source = "\
+int main() {
+    int x = 1;
+    {
+        int x = 2;
+        { x; }
+    }
+    return x;
+}";
+        assert_eq!(resolve_uses(source), vec![("x".to_owned(), (4, 13)), ("x".to_owned(), (2, 9))]);
+    }
+
+    #[test]
+    fn parameters_and_globals() {
+        let source = "\
+int g;
+int f(int a, int *b) {
+    for (int a = 0; a < 1; a++) g = *b;
+    return a + f(g, b);
+}";
+        assert_eq!(resolve_uses(source), vec![
+            ("a".to_owned(), (3, 14)),
+            ("a".to_owned(), (3, 14)),
+            ("g".to_owned(), (1, 5)),
+            ("b".to_owned(), (2, 14)),
+            ("a".to_owned(), (2, 7)),
+            ("g".to_owned(), (1, 5)),
+            ("b".to_owned(), (2, 14)),
+        ]);
+    }
+
+    #[test]
+    fn calls_resolve_to_functions() {
+        let program = parse_program(&lex_str("int f(int x); int main() { return f(1) + g(2); }").unwrap()).unwrap();
+        let (resolutions, errors) = resolve(&program);
+        assert_eq!(errors, vec![]);
+
+        let calls: Vec<(&str, Symbol)> = resolutions.iter()
+            .filter_map(|(&id, &symbol)| match program.ast[id].kind {
+                ExpressionKind::Call(ref name, _) => Some((name.as_str(), symbol)),
+                _ => None,
+            })
+            .collect();
+        // `g` is implicitly declared, so it isn't resolved.
+        assert_eq!(calls, vec![("f", Symbol::Function(0))]);
+    }
+
+    #[test]
+    fn use_before_declaration_is_undeclared() {
+        assert_eq!(errors("int main() {\n    x = 1;\n    int x;\n}"), vec![
+            SemanticError::UndeclaredIdentifier { name: "x".to_owned(), line: 2, column: 5, out_of_scope: None, suggestion: None },
+        ]);
+    }
+
+    fn redeclaration(name: &str, (line, column): (usize, usize), (previous_line, previous_column): (usize, usize)) -> SemanticError {
+        SemanticError::Redeclaration { name: name.to_owned(), line, column, previous_line, previous_column }
+    }
+
+    #[test]
+    fn duplicate_locals() {
+        let errors = errors("int main() {\n    int x;\n    int y, x = 1;\n    for (int i = 0, i = 1; ; ) ;\n}");
+        assert_eq!(errors, vec![redeclaration("x", (3, 12), (2, 9)), redeclaration("i", (4, 21), (4, 14))]);
+        assert_eq!(errors[0].to_string(), "redeclaration of `x` at 3:12 (previous declaration of `x` was at 2:9)");
+    }
+
+    #[test]
+    fn duplicate_parameters() {
+        assert_eq!(errors("int f(int a, char a);\nint g(int b, int *b) { return 0; }\nint h(int c) { int c; }"), vec![
+            redeclaration("a", (1, 14), (1, 7)),
+            redeclaration("b", (2, 14), (2, 7)),
+            redeclaration("c", (3, 20), (3, 7)),
+        ]);
+    }
+
+    #[test]
+    fn legal_redeclarations() {
+        // Shadowing in a nested scope, redeclaring at file scope, and repeating an
+        // `extern` declaration are all fine.
+        let source = "\
+int g;
+int g;
+int f(int x);
+int f(int x) {
+    extern int e;
+    extern int e;
+    { int x; { int x; } }
+    for (int x = 0; ; ) { int x; }
+    return f(x);
+}";
+        assert_eq!(errors(source), vec![]);
+    }
+
+    #[test]
+    fn compatible_file_scope_redeclarations() {
+        // Parameter names and qualifiers on parameters don't matter.
+        let source = "\
+int f(int a, const char *s);
+int f(int b, const char *const t);
+int g;
+extern int g;
+int printf(const char *format, ...);
+int printf(const char *format, ...);
+int f(const int c, const char *u) { return c + printf(u); }";
+        assert_eq!(errors(source), vec![]);
+    }
+
+    fn conflict(name: &str, (line, column): (usize, usize), (previous_line, previous_column): (usize, usize)) -> SemanticError {
+        SemanticError::ConflictingTypes { name: name.to_owned(), line, column, previous_line, previous_column }
+    }
+
+    #[test]
+    fn conflicting_function_declarations() {
+        let found = errors("int f(int a);\nint f(int a, int b) { return a; }");
+        assert_eq!(found, vec![conflict("f", (2, 1), (1, 1))]);
+        assert_eq!(found[0].to_string(), "conflicting types for `f` at 2:1 (previous declaration of `f` was at 1:1)");
+
+        assert_eq!(errors("int f(int a);\nchar f(int a);"), vec![conflict("f", (2, 1), (1, 1))]);
+        assert_eq!(errors("int f(int *a);\nint f(int a);"), vec![conflict("f", (2, 1), (1, 1))]);
+        assert_eq!(errors("int printf(char *format, ...);\nint printf(char *format);"), vec![conflict("printf", (2, 1), (1, 1))]);
+        // Each declaration is checked against the one before it.
+        assert_eq!(errors("int f();\nint f(int a);\nint f(int b);\nint f();"), vec![
+            conflict("f", (2, 1), (1, 1)),
+            conflict("f", (4, 1), (3, 1)),
+        ]);
+    }
+
+    #[test]
+    fn conflicting_global_declarations() {
+        assert_eq!(errors("int x;\nlong x;"), vec![conflict("x", (2, 6), (1, 5))]);
+        assert_eq!(errors("extern int *p;\nint p[4];"), vec![conflict("p", (2, 5), (1, 12))]);
+        assert_eq!(errors("int x;\nconst int x;"), vec![conflict("x", (2, 11), (1, 5))]);
+        assert_eq!(errors("int f;\nint f();"), vec![conflict("f", (2, 1), (1, 5))]);
+    }
+
+    #[test]
+    fn calls_resolve_to_the_latest_declaration() {
+        let source = "int f(int x);\nint main() { return f(1); }\nint f(int x) { return x; }\nint g() { return f(2); }";
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let (resolutions, errors) = resolve(&program);
+        assert_eq!(errors, vec![]);
+        let mut calls: Vec<(usize, Symbol)> = resolutions.iter()
+            .filter(|&(&id, _)| matches!(program.ast[id].kind, ExpressionKind::Call(..)))
+            .map(|(&id, &symbol)| (program.ast[id].span.line, symbol))
+            .collect();
+        calls.sort_by_key(|&(line, _)| line);
+        assert_eq!(calls, vec![(2, Symbol::Function(0)), (4, Symbol::Function(2))]);
+    }
+
+    #[test]
+    fn misspelled_names_are_suggested() {
+        let errors = errors("int counter;\nint main() { int total = 0; return countr + totl + banana; }");
+        let suggestions: Vec<_> = errors.iter().map(|error| match *error {
+            SemanticError::UndeclaredIdentifier { ref suggestion, .. } => suggestion.clone(),
+            ref error => panic!("{:?}", error),
+        }).collect();
+        assert_eq!(suggestions, vec![Some("counter".to_owned()), Some("total".to_owned()), None]);
+        assert_eq!(errors[0].help(), vec!["did you mean `counter`?"]);
+        assert!(errors[2].help().is_empty());
+    }
+
+    #[test]
+    fn undeclared_identifier() {
+        let errors = errors("int main() { { int y; } return y + z; }");
+        assert_eq!(errors, vec![
+            SemanticError::UndeclaredIdentifier { name: "y".to_owned(), line: 1, column: 32, out_of_scope: Some((1, 20)), suggestion: None },
+            SemanticError::UndeclaredIdentifier { name: "z".to_owned(), line: 1, column: 36, out_of_scope: None, suggestion: None },
+        ]);
+        assert_eq!(errors[0].to_string(), "use of undeclared identifier `y` at 1:32 (a declaration at 1:20 is not in scope here)");
+        assert_eq!(errors[1].to_string(), "use of undeclared identifier `z` at 1:36");
+    }
+
+    #[test]
+    fn labels() {
+        // Labels are function-wide and don't clash with variables.
+        assert_eq!(errors("int main() { int end; goto end; { end: ; } goto inner; { { inner: return 0; } } }"), vec![]);
+
+        let errors = errors("int f() { a: ; goto b; }\nint g() { b: ; { b: a: ; } goto a; }");
+        assert_eq!(errors, vec![
+            SemanticError::UndefinedLabel { name: "b".to_owned(), line: 1, column: 16 },
+            SemanticError::DuplicateLabel { name: "b".to_owned(), line: 2, column: 18, previous_line: 2, previous_column: 11 },
+        ]);
+        assert_eq!(errors[0].to_string(), "use of undeclared label `b` at 1:16");
+        assert_eq!(errors[1].to_string(), "redefinition of label `b` at 2:18 (previous definition was at 2:11)");
+    }
+}
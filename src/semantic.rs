@@ -0,0 +1,756 @@
+//! Responsible for checking the AST for errors that the grammar can't express.
+//! This is the last of three parsing stages.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use ast::*;
+use ast::visit::{self, Visitor};
+use flow;
+use pragma::{self, DiagnosticAction, DiagnosticPragma};
+use const_eval::{const_eval, ConstEnv, ConstEvalError};
+use resolve::{self, Resolutions};
+use statics::{self, StaticValues};
+use switches::{self, Switches};
+use typecheck::{self, Conversions, TypeError, Types};
+use uninitialized;
+use unused;
+
+/// How serious a diagnostic is. Errors stop compilation, but warnings only point
+/// out code that is legal and probably wrong.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// The names of the warnings, which are those `gcc` gives the same warnings,
+/// as in `-Wno-unused-variable`.
+pub const WARNINGS: &[&str] = &[
+    "return-type",
+    "unused-variable",
+    "unused-parameter",
+    "maybe-uninitialized",
+    "unreachable-code",
+    "switch-outside-range",
+    "implicit-function-declaration",
+    "overflow",
+    "discarded-qualifiers",
+    "shift-count-overflow",
+    "pragmas",
+];
+
+/// How each warning is reported, as the driver's `-W` flags say, and as
+/// `#pragma GCC diagnostic` changes that in the part of a file after it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WarningSettings {
+    /// Whether every warning is an error, as with `-Werror`.
+    pub all_errors: bool,
+    /// Whether no warning is reported, as with `-w`.
+    pub silenced: bool,
+    /// The warnings reported otherwise than they would be: as errors, as
+    /// warnings even with `all_errors`, or, for `None`, not at all.
+    overrides: HashMap<&'static str, Option<Severity>>,
+}
+
+impl WarningSettings {
+    /// Reports the warning `name` with `severity`, or not at all for `None`.
+    /// Returns false if there is no warning of that name.
+    pub fn set(&mut self, name: &str, severity: Option<Severity>) -> bool {
+        match WARNINGS.iter().find(|&&warning| warning == name) {
+            Some(&warning) => {
+                self.overrides.insert(warning, severity);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// These settings, as each of `pragmas` changes them from where it is
+    /// on.
+    pub fn with_pragmas(&self, pragmas: &[DiagnosticPragma]) -> PragmaWarningSettings {
+        let mut current = self.clone();
+        let mut pushed = Vec::new();
+        let mut changes = Vec::new();
+        for pragma in pragmas {
+            match pragma.action {
+                DiagnosticAction::Push => pushed.push(current.clone()),
+                // A `pop` without a `push` goes back to the settings the file
+                // started with.
+                DiagnosticAction::Pop => current = pushed.pop().unwrap_or_else(|| self.clone()),
+                DiagnosticAction::Ignored(_) | DiagnosticAction::Warning(_) | DiagnosticAction::Error(_) => {
+                    let severity = match pragma.action {
+                        DiagnosticAction::Ignored(_) => None,
+                        DiagnosticAction::Warning(_) => Some(Severity::Warning),
+                        _ => Some(Severity::Error),
+                    };
+                    if let Some(warning) = pragma.warning() {
+                        current.set(warning, severity);
+                    }
+                },
+            }
+            changes.push(((pragma.line, pragma.column), current.clone()));
+        }
+        PragmaWarningSettings { settings: self.clone(), changes }
+    }
+
+    /// The severity `diagnostic` is reported with, or `None` if it isn't.
+    pub fn severity(&self, diagnostic: &SemanticError) -> Option<Severity> {
+        let warning = match diagnostic.warning() {
+            Some(warning) => warning,
+            None => return Some(Severity::Error),
+        };
+        match self.overrides.get(warning) {
+            _ if self.silenced => None,
+            Some(&severity) => severity,
+            None if self.all_errors => Some(Severity::Error),
+            None => Some(Severity::Warning),
+        }
+    }
+}
+
+/// How each warning is reported at each point in a file, as
+/// `WarningSettings::with_pragmas` makes it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PragmaWarningSettings {
+    /// The settings before the first pragma.
+    settings: WarningSettings,
+    /// The settings from the position of each pragma on, in order.
+    changes: Vec<((usize, usize), WarningSettings)>,
+}
+
+impl PragmaWarningSettings {
+    /// The severity `diagnostic` is reported with where it is, or `None` if it
+    /// isn't.
+    pub fn severity(&self, diagnostic: &SemanticError) -> Option<Severity> {
+        let position = diagnostic.position();
+        let settings = match self.changes.iter().rposition(|&(start, _)| start <= position) {
+            Some(change) => &self.changes[change].1,
+            None => &self.settings,
+        };
+        settings.severity(diagnostic)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SemanticError {
+    /// A `break` outside any loop or `switch`.
+    BreakOutsideLoop { line: usize, column: usize },
+    ContinueOutsideLoop { line: usize, column: usize },
+    /// A `case` label outside any `switch`.
+    CaseOutsideSwitch { line: usize, column: usize },
+    DefaultOutsideSwitch { line: usize, column: usize },
+    /// A name used where no declaration of it is in scope. `out_of_scope` is where
+    /// it was declared in a scope that has ended, if it was, and `suggestion`
+    /// the name in scope it looks like a misspelling of, if there is one.
+    UndeclaredIdentifier { name: String, line: usize, column: usize, out_of_scope: Option<(usize, usize)>, suggestion: Option<String> },
+    /// A name declared twice in the same block, or two parameters with the same name.
+    Redeclaration { name: String, line: usize, column: usize, previous_line: usize, previous_column: usize },
+    /// A file-scope declaration whose type disagrees with the previous
+    /// declaration of the same name.
+    ConflictingTypes { name: String, line: usize, column: usize, previous_line: usize, previous_column: usize },
+    Type(TypeError),
+    /// A warning at the closing brace of a function whose end control can reach.
+    MissingReturn { name: String, line: usize, column: usize },
+    /// A warning at the declaration of a local variable or parameter that is
+    /// never read. `assigned` says whether it is written to.
+    UnusedVariable { name: String, line: usize, column: usize, parameter: bool, assigned: bool },
+    /// A warning at the first read of a local variable that may come before
+    /// any assignment to it.
+    MaybeUninitialized { name: String, line: usize, column: usize },
+    /// A warning at the first statement of a stretch that control can't reach.
+    UnreachableStatement { line: usize, column: usize },
+    /// A `goto` to a label that isn't in the function.
+    UndefinedLabel { name: String, line: usize, column: usize },
+    /// A label defined twice in the same function.
+    DuplicateLabel { name: String, line: usize, column: usize, previous_line: usize, previous_column: usize },
+    /// An initializer of a global or `static` local that isn't a constant.
+    StaticInitializer(ConstEvalError),
+    /// An address constant that `statics` can't fold yet.
+    UnsupportedAddressConstant { line: usize, column: usize },
+    /// A `case` label that isn't a constant.
+    CaseLabel(ConstEvalError),
+    /// Two `case` labels in the same `switch` with the same value, after
+    /// conversion to the promoted type of the `switch` value.
+    DuplicateCase { value: i64, line: usize, column: usize, previous_line: usize, previous_column: usize },
+    /// A second `default` label in the same `switch`.
+    DuplicateDefault { line: usize, column: usize, previous_line: usize, previous_column: usize },
+    /// A warning at a `case` label whose value doesn't fit the type of the `switch` value.
+    CaseOutOfRange { value: i64, ty: Type, line: usize, column: usize },
+    /// A divisor of `/`, `%`, `/=`, or `%=` in a function body that is a
+    /// constant zero, at the divisor.
+    DivisionByZero { line: usize, column: usize },
+    /// A warning at a `#pragma GCC diagnostic` whose option isn't one for a
+    /// warning.
+    UnknownWarningInPragma { option: String, line: usize, column: usize },
+    /// A construct that code can't be generated for yet, as `construct`
+    /// describes it, such as a `goto` in WebAssembly.
+    Unsupported { construct: &'static str, line: usize, column: usize },
+}
+
+impl SemanticError {
+    pub fn severity(&self) -> Severity {
+        match self.warning() {
+            Some(_) => Severity::Warning,
+            None => Severity::Error,
+        }
+    }
+
+    /// The name of the warning this is, if it is one, from `WARNINGS`.
+    pub fn warning(&self) -> Option<&'static str> {
+        match *self {
+            SemanticError::Type(ref error) => error.warning(),
+            SemanticError::MissingReturn { .. } => Some("return-type"),
+            SemanticError::UnusedVariable { parameter: false, .. } => Some("unused-variable"),
+            SemanticError::UnusedVariable { parameter: true, .. } => Some("unused-parameter"),
+            SemanticError::MaybeUninitialized { .. } => Some("maybe-uninitialized"),
+            SemanticError::UnreachableStatement { .. } => Some("unreachable-code"),
+            SemanticError::CaseOutOfRange { .. } => Some("switch-outside-range"),
+            SemanticError::UnknownWarningInPragma { .. } => Some("pragmas"),
+            _ => None,
+        }
+    }
+
+    /// The code of the diagnostic, from `codes::CODES`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            SemanticError::Redeclaration { .. } => "E0100",
+            SemanticError::ConflictingTypes { .. } => "E0101",
+            SemanticError::UndeclaredIdentifier { .. } => "E0102",
+            SemanticError::UndefinedLabel { .. } => "E0103",
+            SemanticError::DuplicateLabel { .. } => "E0104",
+            SemanticError::BreakOutsideLoop { .. } => "E0105",
+            SemanticError::ContinueOutsideLoop { .. } => "E0106",
+            SemanticError::CaseOutsideSwitch { .. } => "E0107",
+            SemanticError::DefaultOutsideSwitch { .. } => "E0108",
+            SemanticError::DuplicateCase { .. } => "E0109",
+            SemanticError::DuplicateDefault { .. } => "E0110",
+            SemanticError::Type(ref error) => error.code(),
+            SemanticError::StaticInitializer(ref error) | SemanticError::CaseLabel(ref error) => error.code(),
+            SemanticError::UnsupportedAddressConstant { .. } => "E0304",
+            SemanticError::DivisionByZero { .. } => "E0305",
+            SemanticError::Unsupported { .. } => "E0400",
+            SemanticError::MissingReturn { .. } => "W0001",
+            SemanticError::UnusedVariable { parameter: false, .. } => "W0002",
+            SemanticError::UnusedVariable { parameter: true, .. } => "W0003",
+            SemanticError::MaybeUninitialized { .. } => "W0004",
+            SemanticError::UnreachableStatement { .. } => "W0005",
+            SemanticError::CaseOutOfRange { .. } => "W0006",
+            SemanticError::UnknownWarningInPragma { .. } => "W0011",
+        }
+    }
+
+    /// The other places the diagnostic has to do with, each with what it has
+    /// to do with them.
+    pub fn labels(&self) -> Vec<((usize, usize), &'static str)> {
+        match *self {
+            SemanticError::Type(ref error) => error.labels(),
+            SemanticError::UndeclaredIdentifier { out_of_scope: Some(declaration), .. } =>
+                vec![(declaration, "declared here, in a scope that has ended")],
+            SemanticError::Redeclaration { previous_line, previous_column, .. }
+            | SemanticError::ConflictingTypes { previous_line, previous_column, .. } =>
+                vec![((previous_line, previous_column), "previously declared here")],
+            SemanticError::DuplicateLabel { previous_line, previous_column, .. } =>
+                vec![((previous_line, previous_column), "first defined here")],
+            SemanticError::DuplicateCase { previous_line, previous_column, .. } =>
+                vec![((previous_line, previous_column), "first used here")],
+            SemanticError::DuplicateDefault { previous_line, previous_column, .. } =>
+                vec![((previous_line, previous_column), "the first `default` is here")],
+            _ => Vec::new(),
+        }
+    }
+
+    /// What might be done about the diagnostic.
+    pub fn help(&self) -> Vec<String> {
+        match *self {
+            SemanticError::UndeclaredIdentifier { suggestion: Some(ref suggestion), .. } => vec![format!("did you mean `{}`?", suggestion)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The span the diagnostic is about, where more is known of it than the
+    /// position it starts at.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            SemanticError::Type(ref error) => Some(error.span()),
+            SemanticError::StaticInitializer(ref error) | SemanticError::CaseLabel(ref error) => Some(error.span()),
+            _ => None,
+        }
+    }
+
+    /// What the diagnostic is, without where: it shows that itself.
+    pub fn message(&self) -> String {
+        match *self {
+            SemanticError::BreakOutsideLoop { .. } => "`break` outside of a loop or `switch`".to_owned(),
+            SemanticError::ContinueOutsideLoop { .. } => "`continue` outside of a loop".to_owned(),
+            SemanticError::CaseOutsideSwitch { .. } => "`case` label outside of a `switch`".to_owned(),
+            SemanticError::DefaultOutsideSwitch { .. } => "`default` label outside of a `switch`".to_owned(),
+            SemanticError::UndeclaredIdentifier { ref name, .. } => format!("use of undeclared identifier `{}`", name),
+            SemanticError::Redeclaration { ref name, .. } => format!("redeclaration of `{}`", name),
+            SemanticError::ConflictingTypes { ref name, .. } => format!("conflicting types for `{}`", name),
+            SemanticError::Type(ref error) => error.message(),
+            SemanticError::MissingReturn { ref name, .. } => format!("control reaches end of non-void function `{}`", name),
+            SemanticError::UnusedVariable { ref name, parameter, assigned, .. } => {
+                let kind = if parameter { "parameter" } else { "variable" };
+                if assigned {
+                    format!("{} `{}` is assigned but never read", kind, name)
+                } else {
+                    format!("unused {} `{}`", kind, name)
+                }
+            },
+            SemanticError::MaybeUninitialized { ref name, .. } => format!("variable `{}` may be used uninitialized", name),
+            SemanticError::UnreachableStatement { .. } => "unreachable statement".to_owned(),
+            SemanticError::UndefinedLabel { ref name, .. } => format!("use of undeclared label `{}`", name),
+            SemanticError::DuplicateLabel { ref name, .. } => format!("redefinition of label `{}`", name),
+            SemanticError::StaticInitializer(ref error) =>
+                format!("initializer of a static object must be constant: {}", error.message()),
+            SemanticError::UnsupportedAddressConstant { .. } =>
+                "the address of a struct member isn't supported in a static initializer".to_owned(),
+            SemanticError::CaseLabel(ref error) => format!("case label must be constant: {}", error.message()),
+            SemanticError::DuplicateCase { value, .. } => format!("duplicate case value `{}`", value),
+            SemanticError::DuplicateDefault { .. } => "multiple `default` labels in one `switch`".to_owned(),
+            SemanticError::CaseOutOfRange { value, ref ty, .. } => format!("case value `{}` is out of range for `{}`", value, ty),
+            SemanticError::DivisionByZero { .. } => "division by zero".to_owned(),
+            SemanticError::UnknownWarningInPragma { ref option, .. } =>
+                format!("unknown warning `{}` in `#pragma GCC diagnostic`", option),
+            SemanticError::Unsupported { construct, .. } => format!("{} isn't supported yet", construct),
+        }
+    }
+
+    /// The line and column the diagnostic is at.
+    pub fn position(&self) -> (usize, usize) {
+        match *self {
+            SemanticError::Type(ref error) => (error.span().line, error.span().column),
+            SemanticError::StaticInitializer(ref error) | SemanticError::CaseLabel(ref error) =>
+                (error.span().line, error.span().column),
+            SemanticError::BreakOutsideLoop { line, column }
+            | SemanticError::ContinueOutsideLoop { line, column }
+            | SemanticError::CaseOutsideSwitch { line, column }
+            | SemanticError::DefaultOutsideSwitch { line, column }
+            | SemanticError::UndeclaredIdentifier { line, column, .. }
+            | SemanticError::Redeclaration { line, column, .. }
+            | SemanticError::ConflictingTypes { line, column, .. }
+            | SemanticError::MissingReturn { line, column, .. }
+            | SemanticError::UnusedVariable { line, column, .. }
+            | SemanticError::MaybeUninitialized { line, column, .. }
+            | SemanticError::UnreachableStatement { line, column }
+            | SemanticError::UndefinedLabel { line, column, .. }
+            | SemanticError::DuplicateLabel { line, column, .. }
+            | SemanticError::UnsupportedAddressConstant { line, column }
+            | SemanticError::DuplicateCase { line, column, .. }
+            | SemanticError::DuplicateDefault { line, column, .. }
+            | SemanticError::CaseOutOfRange { line, column, .. }
+            | SemanticError::DivisionByZero { line, column }
+            | SemanticError::UnknownWarningInPragma { line, column, .. }
+            | SemanticError::Unsupported { line, column, .. } => (line, column),
+        }
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SemanticError::UndeclaredIdentifier { ref name, line, column, out_of_scope: Some((previous_line, previous_column)), .. } => write!(
+                f,
+                "use of undeclared identifier `{}` at {}:{} (a declaration at {}:{} is not in scope here)",
+                name, line, column, previous_line, previous_column,
+            ),
+            SemanticError::Redeclaration { ref name, line, column, previous_line, previous_column } => write!(
+                f,
+                "redeclaration of `{}` at {}:{} (previous declaration of `{}` was at {}:{})",
+                name, line, column, name, previous_line, previous_column,
+            ),
+            SemanticError::ConflictingTypes { ref name, line, column, previous_line, previous_column } => write!(
+                f,
+                "conflicting types for `{}` at {}:{} (previous declaration of `{}` was at {}:{})",
+                name, line, column, name, previous_line, previous_column,
+            ),
+            SemanticError::Type(ref error) => error.fmt(f),
+            SemanticError::DuplicateLabel { ref name, line, column, previous_line, previous_column } => write!(
+                f,
+                "redefinition of label `{}` at {}:{} (previous definition was at {}:{})",
+                name, line, column, previous_line, previous_column,
+            ),
+            SemanticError::StaticInitializer(ref error) =>
+                write!(f, "initializer of a static object must be constant: {}", error),
+            SemanticError::CaseLabel(ref error) =>
+                write!(f, "case label must be constant: {}", error),
+            SemanticError::DuplicateCase { value, line, column, previous_line, previous_column } => write!(
+                f,
+                "duplicate case value `{}` at {}:{} (previously used at {}:{})",
+                value, line, column, previous_line, previous_column,
+            ),
+            SemanticError::DuplicateDefault { line, column, previous_line, previous_column } => write!(
+                f,
+                "multiple `default` labels in one `switch` at {}:{} (the first is at {}:{})",
+                line, column, previous_line, previous_column,
+            ),
+            _ => {
+                let (line, column) = self.position();
+                write!(f, "{} at {}:{}", self.message(), line, column)
+            },
+        }
+    }
+}
+
+impl Error for SemanticError {}
+
+/// A statement that `break`, `continue`, `case`, or `default` can belong to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Context {
+    Loop,
+    Switch,
+}
+
+/// Finds statements that only make sense inside a loop or `switch` but aren't
+/// in one: `break` outside both, `continue` outside a loop, and `case` and
+/// `default` outside a `switch`. A `continue` in a `switch` belongs to the loop
+/// around it.
+struct ContextChecker {
+    /// The loops and `switch`es around the statement being checked, innermost last.
+    contexts: Vec<Context>,
+    errors: Vec<SemanticError>,
+}
+
+impl Visitor for ContextChecker {
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        self.contexts.clear();
+        visit::walk_function(self, ast, function);
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        let span = ast[statement].span;
+        let (line, column) = (span.line, span.column);
+        let context = match ast[statement].kind {
+            StatementKind::Break if self.contexts.is_empty() =>
+                Err(SemanticError::BreakOutsideLoop { line, column }),
+            StatementKind::Continue if !self.contexts.contains(&Context::Loop) =>
+                Err(SemanticError::ContinueOutsideLoop { line, column }),
+            StatementKind::Case(..) if !self.contexts.contains(&Context::Switch) =>
+                Err(SemanticError::CaseOutsideSwitch { line, column }),
+            StatementKind::Default(_) if !self.contexts.contains(&Context::Switch) =>
+                Err(SemanticError::DefaultOutsideSwitch { line, column }),
+            StatementKind::While(..) | StatementKind::DoWhile(..) | StatementKind::For { .. } => Ok(Some(Context::Loop)),
+            StatementKind::Switch(..) => Ok(Some(Context::Switch)),
+            _ => Ok(None),
+        };
+        match context {
+            Err(error) => {
+                self.errors.push(error);
+                visit::walk_stmt(self, ast, statement);
+            },
+            Ok(Some(context)) => {
+                self.contexts.push(context);
+                visit::walk_stmt(self, ast, statement);
+                self.contexts.pop();
+            },
+            Ok(None) => visit::walk_stmt(self, ast, statement),
+        }
+    }
+}
+
+/// Finds division by a constant zero in function bodies, which would raise
+/// `SIGFPE` when run. Constant expressions elsewhere, in the initializers of
+/// static objects and in `case` labels, report it when they are folded.
+struct DivisionChecker {
+    errors: Vec<SemanticError>,
+}
+
+impl Visitor for DivisionChecker {
+    fn visit_top_level(&mut self, ast: &Ast, item: &TopLevel) {
+        if let TopLevel::Function(ref function) = *item {
+            self.visit_function(ast, function);
+        }
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        if ast[declaration].storage != Some(StorageClass::Static) {
+            visit::walk_declaration(self, ast, declaration);
+        }
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        match ast[statement].kind {
+            StatementKind::Case(_, body) => self.visit_stmt(ast, body),
+            _ => visit::walk_stmt(self, ast, statement),
+        }
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        // A constant with a value divides by zero nowhere it is evaluated, as
+        // in `0 && 1 / 0`.
+        if const_eval(&ast[expression], &ConstEnv::new(ast)).is_ok() {
+            return;
+        }
+        match ast[expression].kind {
+            ExpressionKind::Binary(BinaryOp::Divide, _, divisor)
+            | ExpressionKind::Binary(BinaryOp::Modulo, _, divisor)
+            | ExpressionKind::Assign(Some(BinaryOp::Divide), _, divisor)
+            | ExpressionKind::Assign(Some(BinaryOp::Modulo), _, divisor)
+                if const_eval(&ast[divisor], &ConstEnv::new(ast)) == Ok(0) => {
+                let span = ast[divisor].span;
+                self.errors.push(SemanticError::DivisionByZero { line: span.line, column: span.column });
+            },
+            _ => {},
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// Finds the uses of a whole struct as a value, which code can't be generated
+/// for yet: passing or returning one, assigning one, and initializing one from
+/// another. Its members and its address can still be used.
+struct StructValues<'a> {
+    types: &'a Types,
+    /// Whether the declarations visited are in a function, where an
+    /// initializer is evaluated like an assignment.
+    in_function: bool,
+    errors: Vec<SemanticError>,
+}
+
+impl<'a> StructValues<'a> {
+    fn is_struct(&self, expression: ExprId) -> bool {
+        matches!(self.types.get(&expression).map(Type::unqualified), Some(&Type::Struct(_)))
+    }
+
+    fn unsupported(&mut self, construct: &'static str, span: Span) {
+        self.errors.push(SemanticError::Unsupported { construct, line: span.line, column: span.column });
+    }
+}
+
+impl<'a> Visitor for StructValues<'a> {
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        if let Type::Struct(_) = *function.return_type.unqualified() {
+            self.unsupported("returning a struct by value", function.span);
+        }
+        for parameter in &function.parameters {
+            if let Type::Struct(_) = *parameter.ty.unqualified() {
+                self.unsupported("passing a struct by value", parameter.span);
+            }
+        }
+        self.in_function = true;
+        visit::walk_function(self, ast, function);
+        self.in_function = false;
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        let local = self.in_function && ast[declaration].storage != Some(StorageClass::Static);
+        if let Some(Initializer::Expr(value)) = ast[declaration].initializer {
+            if local && self.is_struct(value) {
+                self.unsupported("initializing a struct from another", ast[value].span);
+            }
+        }
+        visit::walk_declaration(self, ast, declaration);
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        match ast[expression].kind {
+            ExpressionKind::Assign(_, target, _) if self.is_struct(target) => {
+                self.unsupported("assigning a struct", ast[expression].span);
+            },
+            ExpressionKind::Call(_, ref arguments) => {
+                for &argument in arguments {
+                    if self.is_struct(argument) {
+                        self.unsupported("passing a struct by value", ast[argument].span);
+                    }
+                }
+            },
+            ExpressionKind::Conditional(..) | ExpressionKind::Comma(_) if self.is_struct(expression) => {
+                self.unsupported("a struct as the value of `?:` or `,`", ast[expression].span);
+            },
+            _ => {},
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// What analysis found out about a program, kept for the passes after it.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub resolutions: Resolutions,
+    /// The type of each expression, as `typecheck` describes.
+    pub types: Types,
+    /// The implicit conversion of each expression whose value changes type.
+    pub conversions: Conversions,
+    /// The starting value of each global and `static` local, as `statics` describes.
+    pub statics: StaticValues,
+    /// The labels of each `switch`, as `switches` describes.
+    pub switches: Switches,
+}
+
+/// Checks `program`, returning what was learned about it along with every error found.
+pub fn analyze(program: &Program) -> (Analysis, Vec<SemanticError>) {
+    let (resolutions, mut errors) = resolve::resolve(program);
+    // Uses that failed to resolve may have been meant for variables that would
+    // then look unused.
+    let resolved = errors.is_empty();
+
+    let mut contexts = ContextChecker { contexts: Vec::new(), errors: Vec::new() };
+    contexts.visit_program(program);
+    errors.append(&mut contexts.errors);
+
+    let (types, conversions, type_errors) = typecheck::check(program, &resolutions);
+    errors.extend(type_errors.into_iter().map(SemanticError::Type));
+    // Folding initializers needs every name resolved and every expression typed.
+    let typed = errors.iter().all(|error| error.severity() == Severity::Warning);
+    let statics = if typed {
+        let (statics, mut static_errors) = statics::check(program, &resolutions, &types);
+        errors.append(&mut static_errors);
+        statics
+    } else {
+        StaticValues::new()
+    };
+    let (switches, mut switch_errors) = switches::check(program, &types);
+    errors.append(&mut switch_errors);
+    let mut divisions = DivisionChecker { errors: Vec::new() };
+    divisions.visit_program(program);
+    errors.append(&mut divisions.errors);
+    let mut struct_values = StructValues { types: &types, in_function: false, errors: Vec::new() };
+    struct_values.visit_program(program);
+    errors.append(&mut struct_values.errors);
+    errors.append(&mut flow::check(program));
+    errors.append(&mut pragma::check(&program.pragmas));
+    if resolved {
+        errors.append(&mut uninitialized::check(program, &resolutions));
+        errors.append(&mut unused::check(program, &resolutions));
+    }
+
+    (Analysis { resolutions, types, conversions, statics, switches }, errors)
+}
+
+/// Checks `program`, returning every error and warning found.
+pub fn check_program(program: &Program) -> Vec<SemanticError> {
+    analyze(program).1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+
+    fn check(source: &str) -> Vec<SemanticError> {
+        check_program(&parse_program(&lex_str(source).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn break_and_continue_inside_loops() {
+        assert_eq!(check("int main() { while (1) { if (1) break; continue; } for (;;) break; do continue; while (0); }"), vec![]);
+    }
+
+    #[test]
+    fn break_and_continue_outside_loops() {
+        let errors = check("int main() {\n    if (1) break;\n    while (1) {}\n    { continue; }\n}");
+        assert_eq!(errors, vec![
+            SemanticError::BreakOutsideLoop { line: 2, column: 12 },
+            SemanticError::ContinueOutsideLoop { line: 4, column: 7 },
+            SemanticError::UnreachableStatement { line: 4, column: 5 },
+        ]);
+        assert_eq!(errors[0].to_string(), "`break` outside of a loop or `switch` at 2:12");
+    }
+
+    #[test]
+    fn break_and_continue_in_switches() {
+        // `break` leaves the `switch`, and `continue` goes on to the loop around it.
+        assert_eq!(check("int main(int x) { while (x) { switch (x) { case 1: continue; default: break; } } return 0; }"), vec![]);
+        assert_eq!(check("int main(int x) { switch (x) { case 1: x++; break; } return x; }"), vec![]);
+        assert_eq!(check("int main(int x) { for (;;) switch (x) { case 0: while (x) continue; } }"), vec![]);
+
+        let errors = check("int main(int x) {\n    switch (x) {\n    case 1:\n        continue;\n    }\n    return x;\n}");
+        assert_eq!(errors, vec![SemanticError::ContinueOutsideLoop { line: 4, column: 9 }]);
+    }
+
+    #[test]
+    fn case_and_default_inside_switches() {
+        // Labels may be nested in other statements in the body, even loops.
+        assert_eq!(check("int main(int x) { switch (x) { case 1: { case 2: x++; } default: while (x) { case 3: x--; } } return x; }"), vec![]);
+        assert_eq!(check("int main(int x) { switch (x) default: return 1; }"), vec![]);
+    }
+
+    #[test]
+    fn case_and_default_outside_switches() {
+        let errors = check("int main(int x) {\n    case 1: x++;\n    while (x) {\n        default: x--;\n    }\n    return x;\n}");
+        assert_eq!(errors, vec![
+            SemanticError::CaseOutsideSwitch { line: 2, column: 5 },
+            SemanticError::DefaultOutsideSwitch { line: 4, column: 9 },
+        ]);
+        assert_eq!(errors[0].to_string(), "`case` label outside of a `switch` at 2:5");
+        assert_eq!(errors[1].to_string(), "`default` label outside of a `switch` at 4:9");
+
+        // Nor does a `switch` in another function count.
+        let errors = check("int f(int x) { switch (x) { case 0: return 0; } return 1; }\nint main() { default: return 0; }");
+        assert_eq!(errors, vec![SemanticError::DefaultOutsideSwitch { line: 2, column: 14 }]);
+    }
+
+    #[test]
+    fn division_by_a_constant_zero() {
+        let errors = check("int main(int x) {\n    x %= 0;\n    return x / (1 - 1) + 0 / x;\n}");
+        assert_eq!(errors, vec![
+            SemanticError::DivisionByZero { line: 2, column: 10 },
+            SemanticError::DivisionByZero { line: 3, column: 17 },
+        ]);
+        assert_eq!(errors[0].to_string(), "division by zero at 2:10");
+        assert_eq!(check("int main() { return 0 && 1 / 0; }"), vec![]);
+
+        // Static initializers and `case` labels report it as not being constant.
+        let errors = check("int g = 1 / 0;\nint main(int x) { static int s = 2 % 0; switch (x) { case 1 / 0: return s; } return g; }");
+        assert_eq!(errors.iter().filter(|error| matches!(error, SemanticError::DivisionByZero { .. })).count(), 0);
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn warnings_have_names_and_settings() {
+        let (unused, undeclared) = (&check("int main() { int x; return 0; }")[0], &check("int main() { return y; }")[0]);
+        assert_eq!(unused.warning(), Some("unused-variable"));
+        assert!(WARNINGS.contains(&unused.warning().unwrap()));
+        assert_eq!(undeclared.warning(), None);
+
+        let mut settings = WarningSettings::default();
+        assert_eq!(settings.severity(unused), Some(Severity::Warning));
+        settings.all_errors = true;
+        assert_eq!(settings.severity(unused), Some(Severity::Error));
+        assert!(settings.set("unused-variable", None));
+        assert_eq!(settings.severity(unused), None);
+        assert!(!settings.set("unused-frobnication", None));
+        settings.silenced = true;
+        assert!(settings.set("unused-variable", Some(Severity::Error)));
+        assert_eq!(settings.severity(unused), None);
+        assert_eq!(settings.severity(undeclared), Some(Severity::Error));
+    }
+
+    #[test]
+    fn pragmas_change_warnings_from_where_they_are() {
+        let source = "\
+int f() { int a; return 0; }
+#pragma GCC diagnostic push
+#pragma GCC diagnostic ignored \"-Wunused-variable\"
+int g() { int b; return 0; }
+#pragma GCC diagnostic error \"-Wunused-variable\"
+int h() { int c; return 0; }
+#pragma GCC diagnostic pop
+int i() { int d; return 0; }
+#pragma GCC diagnostic ignored \"-Wunused-varaible\"
+";
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let diagnostics = check_program(&program);
+        let warnings = WarningSettings::default().with_pragmas(&program.pragmas);
+        let reported: Vec<_> = diagnostics.iter().map(|diagnostic| (diagnostic.position(), warnings.severity(diagnostic))).collect();
+        assert_eq!(reported, vec![
+            ((9, 1), Some(Severity::Warning)),
+            ((1, 15), Some(Severity::Warning)),
+            ((4, 15), None),
+            ((6, 15), Some(Severity::Error)),
+            ((8, 15), Some(Severity::Warning)),
+        ]);
+        assert_eq!(diagnostics[0].to_string(), "unknown warning `-Wunused-varaible` in `#pragma GCC diagnostic` at 9:1");
+    }
+}
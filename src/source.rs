@@ -0,0 +1,238 @@
+//! The source of a file being compiled, held however suits it: read into a
+//! `String`, or, with the `mmap` feature, mapped from the file, so that a large
+//! file isn't copied before it is lexed. Either way it is checked to be UTF-8
+//! once, and lexed from the `&str` it derefs to. Without the `fs` feature, it
+//! can only be made from a `String`.
+
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::fs;
+#[cfg(feature = "fs")]
+use std::io::{self, Read};
+use std::ops::{Deref, Range};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::str;
+
+pub struct Source {
+    text: Text,
+}
+
+enum Text {
+    Owned(String),
+    /// As with any map, changing the file while it is mapped changes what it
+    /// holds, which the compiler doesn't guard against.
+    #[cfg(all(feature = "mmap", unix))]
+    Mapped(memmap2::Mmap),
+}
+
+#[cfg(feature = "fs")]
+impl Source {
+    /// The source of the file at `path`, mapped if it is a regular file, and
+    /// read if not. A pipe or a device has no length to map, whatever it holds.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Source> {
+        #[cfg(all(feature = "mmap", unix))]
+        {
+            let mut file = fs::File::open(path)?;
+            if !file.metadata()?.is_file() {
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes)?;
+                return Source::from_bytes(bytes);
+            }
+            let map = unsafe { memmap2::Mmap::map(&file)? };
+            str::from_utf8(&map).map_err(invalid)?;
+            Ok(Source { text: Text::Mapped(map) })
+        }
+        #[cfg(not(all(feature = "mmap", unix)))]
+        Source::read(path)
+    }
+
+    /// The source of the file at `path`, read into memory.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Source> {
+        Source::from_bytes(fs::read(path)?)
+    }
+
+    /// The source on standard input, which can't be mapped.
+    pub fn stdin() -> io::Result<Source> {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        Source::from_bytes(bytes)
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> io::Result<Source> {
+        let text = String::from_utf8(bytes).map_err(|err| invalid(err.utf8_error()))?;
+        Ok(Source { text: Text::Owned(text) })
+    }
+
+    /// Whether the source is mapped from its file.
+    pub fn is_mapped(&self) -> bool {
+        match self.text {
+            Text::Owned(_) => false,
+            #[cfg(all(feature = "mmap", unix))]
+            Text::Mapped(_) => true,
+        }
+    }
+}
+
+/// The error for a source that isn't UTF-8, the same whichever way it was got.
+#[cfg(feature = "fs")]
+fn invalid(err: str::Utf8Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid UTF-8 at byte {}", err.valid_up_to()))
+}
+
+impl From<String> for Source {
+    fn from(text: String) -> Source {
+        Source { text: Text::Owned(text) }
+    }
+}
+
+impl Deref for Source {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self.text {
+            Text::Owned(ref text) => text,
+            // Checked when it was mapped.
+            #[cfg(all(feature = "mmap", unix))]
+            Text::Mapped(ref map) => unsafe { str::from_utf8_unchecked(map) },
+        }
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Where each line of a text starts, built once, so that the line and column
+/// of an offset are found by a binary search rather than by counting the
+/// newlines before it. Lines and columns are counted from 1, as the lexer
+/// counts them, and columns in bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineIndex {
+    /// The offset each line starts at, the first line first.
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(offset, _)| offset + 1));
+        LineIndex { starts, len: text.len() }
+    }
+
+    /// How many lines there are. An empty text has one, empty line, and a
+    /// newline at the end starts another.
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The line and column of the byte at `offset`, or of the end of the text
+    /// for its length. Past that, the last line's columns go on being counted.
+    pub fn lookup(&self, offset: usize) -> (usize, usize) {
+        let line = match self.starts.binary_search(&offset) {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        (line + 1, offset - self.starts[line] + 1)
+    }
+
+    /// The offsets of the text of `line`, without its newline, if there is
+    /// such a line.
+    pub fn line_span(&self, line: usize) -> Option<Range<usize>> {
+        let start = *self.starts.get(line.checked_sub(1)?)?;
+        let end = self.starts.get(line).map_or(self.len, |&next| next - 1);
+        Some(start..end)
+    }
+
+    /// The offset of `line` and `column`, if it is in the text or at its end.
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let offset = self.starts.get(line.checked_sub(1)?)? + column.checked_sub(1)?;
+        if offset <= self.len { Some(offset) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use std::env;
+    #[cfg(feature = "fs")]
+    use lexer;
+
+    #[cfg(feature = "fs")]
+    fn file(name: &str, contents: &[u8]) -> ::std::path::PathBuf {
+        let path = env::temp_dir().join(format!("rust-cc-source-{}-{}", ::std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn opened_and_read_sources_are_the_same() {
+        let program = "int f(int a) { return a * 2; }\n".repeat(10_000);
+        let path = file("large.c", program.as_bytes());
+        let (opened, read) = (Source::open(&path).unwrap(), Source::read(&path).unwrap());
+        assert_eq!(opened.is_mapped(), cfg!(all(feature = "mmap", unix)));
+        assert!(!read.is_mapped());
+        assert_eq!(&*opened, &program[..]);
+        assert_eq!(lexer::lex_str(&opened).unwrap(), lexer::lex_str(&read).unwrap());
+
+        let empty = file("empty.c", b"");
+        assert_eq!(&*Source::open(&empty).unwrap(), "");
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(empty);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn invalid_utf8_is_an_error_either_way() {
+        let path = file("invalid.c", b"int main() { return 0; } \xff\n");
+        let (opened, read) = (Source::open(&path).unwrap_err(), Source::read(&path).unwrap_err());
+        assert_eq!(opened.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(opened.to_string(), "invalid UTF-8 at byte 25");
+        assert_eq!(read.to_string(), opened.to_string());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn lines_are_found_from_offsets() {
+        let text = "ab\ncd\n\nef";
+        let index = LineIndex::new(text);
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.lookup(0), (1, 1));
+        // A newline is at the end of the line it ends.
+        assert_eq!(index.lookup(2), (1, 3));
+        assert_eq!(index.lookup(3), (2, 1));
+        assert_eq!(index.lookup(6), (3, 1));
+        assert_eq!(index.lookup(7), (4, 1));
+        // The last byte, with no newline after it, and the end of the text.
+        assert_eq!(index.lookup(8), (4, 2));
+        assert_eq!(index.lookup(9), (4, 3));
+        assert_eq!(index.line_span(1), Some(0..2));
+        assert_eq!(index.line_span(3), Some(6..6));
+        assert_eq!(index.line_span(4), Some(7..9));
+        assert_eq!(index.line_span(5), None);
+        assert_eq!(index.line_span(0), None);
+        assert_eq!(index.offset(2, 2), Some(4));
+        assert_eq!(index.offset(4, 4), None);
+        for offset in 0..=text.len() {
+            let (line, column) = index.lookup(offset);
+            assert_eq!(index.offset(line, column), Some(offset));
+        }
+
+        // A newline at the end starts an empty last line.
+        let index = LineIndex::new("ab\n");
+        assert_eq!(index.line_count(), 2);
+        assert_eq!(index.lookup(3), (2, 1));
+        assert_eq!(index.line_span(2), Some(3..3));
+
+        let empty = LineIndex::new("");
+        assert_eq!(empty.line_count(), 1);
+        assert_eq!(empty.lookup(0), (1, 1));
+        assert_eq!(empty.line_span(1), Some(0..0));
+        assert_eq!(empty.line_span(2), None);
+    }
+}
@@ -0,0 +1,105 @@
+//! The versions of the C standard that programs can be written in, and the
+//! features each adds to the one before.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Standard {
+    C89,
+    C99,
+    #[default]
+    C11,
+}
+
+impl Standard {
+    /// The standard named `name`, as in `--std=c99`. `c90` is another name for
+    /// `c89`.
+    pub fn from_name(name: &str) -> Option<Standard> {
+        match name {
+            "c89" | "c90" => Some(Standard::C89),
+            "c99" => Some(Standard::C99),
+            "c11" => Some(Standard::C11),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Standard::C89 => "c89",
+            Standard::C99 => "c99",
+            Standard::C11 => "c11",
+        }
+    }
+
+    /// Whether programs written in this standard can use `feature`.
+    pub fn has(self, feature: Feature) -> bool {
+        self >= feature.standard()
+    }
+}
+
+/// Something that only programs written in later standards can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    LineComments,
+    /// Declarations after the first statement of a block.
+    MixedDeclarations,
+    /// A declaration as the first clause of a `for` loop.
+    ForDeclarations,
+    Inline,
+    Restrict,
+}
+
+impl Feature {
+    /// The first standard with the feature.
+    pub fn standard(self) -> Standard {
+        match self {
+            Feature::LineComments
+            | Feature::MixedDeclarations
+            | Feature::ForDeclarations
+            | Feature::Inline
+            | Feature::Restrict => Standard::C99,
+        }
+    }
+
+    /// Says which standard the feature is from, and how to ask for it:
+    /// ``are a C99 feature; use --std=c99``.
+    pub fn requirement(self) -> String {
+        let standard = self.standard().name();
+        format!("are a {} feature; use --std={}", standard.to_uppercase(), standard)
+    }
+}
+
+/// Names the feature, in the plural: ``declarations in `for` loops``.
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Feature::LineComments => "`//` comments",
+            Feature::MixedDeclarations => "declarations after statements",
+            Feature::ForDeclarations => "declarations in `for` loops",
+            Feature::Inline => "`inline` functions",
+            Feature::Restrict => "`restrict` pointers",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn standards_are_named() {
+        for &standard in &[Standard::C89, Standard::C99, Standard::C11] {
+            assert_eq!(Standard::from_name(standard.name()), Some(standard));
+        }
+        assert_eq!(Standard::from_name("c90"), Some(Standard::C89));
+        assert_eq!(Standard::from_name("gnu99"), None);
+    }
+
+    #[test]
+    fn features_come_with_their_standard() {
+        assert!(!Standard::C89.has(Feature::LineComments));
+        assert!(Standard::C99.has(Feature::LineComments));
+        assert!(Standard::C11.has(Feature::Inline));
+        assert_eq!(Feature::ForDeclarations.requirement(), "are a C99 feature; use --std=c99");
+    }
+}
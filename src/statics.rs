@@ -0,0 +1,379 @@
+//! Folds the initializers of objects with static storage duration, globals and
+//! `static` locals, to the values codegen places in the data section.
+//!
+//! Such an initializer must be a constant, worked out by `const_eval`, or an
+//! address constant: the address of a static object or string literal, plus or
+//! minus an integer constant. The address of a struct member would need the
+//! struct's layout, and isn't supported yet.
+
+use std::collections::{HashMap, HashSet};
+use ast::*;
+use ast::visit::{self, Visitor};
+use const_eval::{const_eval, convert, ConstEnv, ConstEvalError};
+use resolve::{Resolutions, Symbol};
+use semantic::SemanticError;
+use typecheck::Types;
+
+/// Something with a static address, which the linker fills in.
+#[derive(Debug, PartialEq, Clone)]
+pub enum StaticObject {
+    /// A global or `static` local.
+    Declaration(DeclId),
+    /// A string literal, which codegen places in read-only data.
+    String(ExprId),
+}
+
+/// The value an object with static storage duration starts with.
+#[derive(Debug, PartialEq, Clone)]
+pub enum StaticValue {
+    /// All zeros, for an object without an initializer, which can go in `.bss`.
+    Zero,
+    /// A scalar, converted to the object's type. Null pointers are `Int(0)`.
+    Int(i64),
+    /// The address of `object` plus `offset` bytes.
+    Address { object: StaticObject, offset: i64 },
+    /// The elements of an array or the members of a struct, in order. Those
+    /// past the end of the list are zero.
+    List(Vec<StaticValue>),
+}
+
+/// The folded initializer of each object with static storage duration that is
+/// defined, rather than only declared, in the program.
+pub type StaticValues = HashMap<DeclId, StaticValue>;
+
+/// Finds the declarations of objects with static storage duration.
+struct Collector {
+    /// Whether the walk is inside a function body.
+    local: bool,
+    statics: Vec<DeclId>,
+}
+
+impl Visitor for Collector {
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        self.local = true;
+        visit::walk_function(self, ast, function);
+        self.local = false;
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        let Declaration { storage, ref initializer, .. } = ast[declaration];
+        // Without an initializer, an `extern` global is defined elsewhere.
+        let defined = if self.local {
+            storage == Some(StorageClass::Static)
+        } else {
+            storage != Some(StorageClass::Extern) || initializer.is_some()
+        };
+        if defined {
+            self.statics.push(declaration);
+        }
+        visit::walk_declaration(self, ast, declaration);
+    }
+}
+
+/// Why an initializer couldn't be folded.
+enum Unfoldable {
+    Constant(ConstEvalError),
+    MemberAddress(Span),
+}
+
+impl From<ConstEvalError> for Unfoldable {
+    fn from(error: ConstEvalError) -> Unfoldable {
+        Unfoldable::Constant(error)
+    }
+}
+
+struct Folder<'a> {
+    program: &'a Program,
+    resolutions: &'a Resolutions,
+    types: &'a Types,
+    env: ConstEnv<'a>,
+    /// The members of each defined struct, by tag.
+    structs: HashMap<&'a str, &'a [Member]>,
+    statics: HashSet<DeclId>,
+}
+
+impl<'a> Folder<'a> {
+    fn not_constant(&self, expression: ExprId) -> Unfoldable {
+        Unfoldable::Constant(ConstEvalError::NotConstant { span: self.program.ast[expression].span })
+    }
+
+    fn eval(&self, expression: ExprId) -> Result<i64, Unfoldable> {
+        Ok(const_eval(&self.program.ast[expression], &self.env)?)
+    }
+
+    /// `count` elements of type `element` in bytes, for the offset of a pointer
+    /// `expression` computes.
+    fn scaled(&self, count: i64, element: &Type, expression: ExprId) -> Result<i64, Unfoldable> {
+        let span = self.program.ast[expression].span;
+        let size = self.env.size_of(element).ok_or(ConstEvalError::UnknownSize { span })?;
+        Ok(size.checked_mul(count).ok_or(ConstEvalError::Overflow { span })?)
+    }
+
+    fn initializer(&self, target: &Type, initializer: &Initializer) -> Result<StaticValue, Unfoldable> {
+        let ast = &self.program.ast;
+        match (target.unqualified(), initializer) {
+            (Type::Array(element, length), Initializer::Expr(value)) if element.unqualified() == &Type::Char => {
+                match ast[*value].kind {
+                    // The terminating nul is dropped if the array has no room for it.
+                    ExpressionKind::StringLiteral(ref bytes) => Ok(StaticValue::List(
+                        bytes.iter().chain(Some(&0)).take(*length).map(|&byte| StaticValue::Int(i64::from(byte as i8))).collect(),
+                    )),
+                    _ => Err(self.not_constant(*value)),
+                }
+            },
+            (Type::Array(element, length), Initializer::List(items)) => items.iter().take(*length)
+                .map(|item| self.initializer(element, item))
+                .collect::<Result<_, _>>()
+                .map(StaticValue::List),
+            (Type::Struct(tag), Initializer::List(items)) => {
+                let members = self.structs.get(tag.as_str()).cloned().unwrap_or(&[]);
+                members.iter().zip(items)
+                    .map(|(member, item)| self.initializer(&member.ty, item))
+                    .collect::<Result<_, _>>()
+                    .map(StaticValue::List)
+            },
+            // A scalar may be initialized from a braced list of one value.
+            (_, Initializer::List(items)) => match items.first() {
+                Some(item) => self.initializer(target, item),
+                None => Ok(StaticValue::Zero),
+            },
+            (Type::Pointer(_), Initializer::Expr(value)) => self.address(*value),
+            (_, Initializer::Expr(value)) => match convert(self.eval(*value)?, target) {
+                Some(converted) => Ok(StaticValue::Int(converted)),
+                None => Err(self.not_constant(*value)),
+            },
+        }
+    }
+
+    /// Folds `expression`, a value converted to a pointer.
+    fn address(&self, expression: ExprId) -> Result<StaticValue, Unfoldable> {
+        let ast = &self.program.ast;
+        match self.types[&expression] {
+            ref ty if ty.is_integer() => return Ok(StaticValue::Int(self.eval(expression)?)),
+            // An array converts to the address of its first element.
+            Type::Array(..) => return self.object(expression),
+            _ => {},
+        }
+        match ast[expression].kind {
+            ExpressionKind::Unary(UnaryOp::AddressOf, operand) => self.object(operand),
+            ExpressionKind::Cast { expr, .. } => self.address(expr),
+            ExpressionKind::Binary(operator @ BinaryOp::Add, lhs, rhs)
+            | ExpressionKind::Binary(operator @ BinaryOp::Subtract, lhs, rhs) => {
+                let (pointer, count) = if self.types[&lhs].is_integer() { (rhs, lhs) } else { (lhs, rhs) };
+                let element = match self.types[&pointer].decay() {
+                    Type::Pointer(element) => *element,
+                    _ => return Err(self.not_constant(expression)),
+                };
+                let overflow = || ConstEvalError::Overflow { span: ast[expression].span };
+                let count = match operator {
+                    BinaryOp::Add => self.eval(count)?,
+                    _ => self.eval(count)?.checked_neg().ok_or_else(overflow)?,
+                };
+                let delta = self.scaled(count, &element, expression)?;
+                match self.address(pointer)? {
+                    StaticValue::Address { object, offset } =>
+                        Ok(StaticValue::Address { object, offset: offset.checked_add(delta).ok_or_else(overflow)? }),
+                    StaticValue::Int(value) => Ok(StaticValue::Int(value.checked_add(delta).ok_or_else(overflow)?)),
+                    _ => Err(self.not_constant(expression)),
+                }
+            },
+            _ => Err(self.not_constant(expression)),
+        }
+    }
+
+    /// Folds the address of `expression`, an lvalue.
+    fn object(&self, expression: ExprId) -> Result<StaticValue, Unfoldable> {
+        let ast = &self.program.ast;
+        match ast[expression].kind {
+            ExpressionKind::Variable(_) => match self.resolutions.get(&expression) {
+                Some(&Symbol::Variable(declaration)) if self.statics.contains(&declaration) =>
+                    Ok(StaticValue::Address { object: StaticObject::Declaration(declaration), offset: 0 }),
+                _ => Err(self.not_constant(expression)),
+            },
+            ExpressionKind::StringLiteral(_) => Ok(StaticValue::Address { object: StaticObject::String(expression), offset: 0 }),
+            ExpressionKind::Unary(UnaryOp::Dereference, pointer) => self.address(pointer),
+            // `a[i]` is `*(a + i)`, whichever of the two is the pointer.
+            ExpressionKind::Subscript(array, index) => {
+                let (pointer, index) = if self.types[&array].is_integer() { (index, array) } else { (array, index) };
+                let delta = self.scaled(self.eval(index)?, &self.types[&expression], expression)?;
+                match self.address(pointer)? {
+                    StaticValue::Address { object, offset } => Ok(StaticValue::Address {
+                        object,
+                        offset: offset.checked_add(delta)
+                            .ok_or(ConstEvalError::Overflow { span: ast[expression].span })?,
+                    }),
+                    _ => Err(self.not_constant(expression)),
+                }
+            },
+            ExpressionKind::Member(..) | ExpressionKind::PointerMember(..) => Err(Unfoldable::MemberAddress(ast[expression].span)),
+            _ => Err(self.not_constant(expression)),
+        }
+    }
+}
+
+/// Folds the initializer of every global and `static` local in `program`,
+/// using what resolution and type checking found out about it. Objects
+/// without an initializer are zero.
+pub fn check(program: &Program, resolutions: &Resolutions, types: &Types) -> (StaticValues, Vec<SemanticError>) {
+    let mut collector = Collector { local: false, statics: Vec::new() };
+    collector.visit_program(program);
+
+    let folder = Folder {
+        program,
+        resolutions,
+        types,
+        env: ConstEnv::new(&program.ast),
+        structs: program.items.iter()
+            .filter_map(|item| match *item {
+                TopLevel::Struct(ref definition) => Some((definition.name.as_str(), &definition.members[..])),
+                _ => None,
+            })
+            .collect(),
+        statics: collector.statics.iter().cloned().collect(),
+    };
+    let mut values = HashMap::new();
+    let mut errors = Vec::new();
+    for &declaration in &collector.statics {
+        let Declaration { ref ty, ref initializer, .. } = program.ast[declaration];
+        let value = match *initializer {
+            Some(ref initializer) => folder.initializer(ty, initializer),
+            None => Ok(StaticValue::Zero),
+        };
+        match value {
+            Ok(value) => {
+                values.insert(declaration, value);
+            },
+            Err(Unfoldable::Constant(error)) => errors.push(SemanticError::StaticInitializer(error)),
+            Err(Unfoldable::MemberAddress(span)) =>
+                errors.push(SemanticError::UnsupportedAddressConstant { line: span.line, column: span.column }),
+        }
+    }
+    (values, errors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+    use resolve::resolve;
+    use semantic::Severity;
+    use typecheck;
+
+    fn fold_program(source: &str) -> (Program, StaticValues, Vec<String>) {
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let (resolutions, errors) = resolve(&program);
+        assert_eq!(errors, vec![]);
+        let (types, _, errors) = typecheck::check(&program, &resolutions);
+        assert!(errors.iter().all(|error| error.severity() == Severity::Warning), "{:?}", errors);
+        let (values, errors) = check(&program, &resolutions, &types);
+        (program, values, errors.iter().map(|error| error.to_string()).collect())
+    }
+
+    /// The folded value of each static object in `source`, by name in order of
+    /// declaration, or the errors found folding them.
+    fn fold(source: &str) -> Result<Vec<(String, StaticValue)>, Vec<String>> {
+        let (program, values, errors) = fold_program(source);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut values: Vec<_> = values.into_iter().collect();
+        values.sort_by_key(|&(declaration, _)| declaration);
+        Ok(values.into_iter().map(|(declaration, value)| (program.ast[declaration].name.clone(), value)).collect())
+    }
+
+    fn named(values: &[(&str, StaticValue)]) -> Result<Vec<(String, StaticValue)>, Vec<String>> {
+        Ok(values.iter().map(|&(name, ref value)| (name.to_owned(), value.clone())).collect())
+    }
+
+    #[test]
+    fn constant_initializers_are_folded() {
+        let source = "\
+int size = 4 * (2 + 1) - 1;
+char c = 'a' + 1;
+char wrapped = 300;
+int flags[3] = {1 << 4, sizeof(int), !0};
+char text[3] = \"hi\";
+char cut[2] = \"hi\";
+struct s { int x; char y; };
+struct s v = {-1};
+int main() { static int calls = 10 / 3; return calls; }";
+        let list = |values: &[i64]| StaticValue::List(values.iter().map(|&value| StaticValue::Int(value)).collect());
+        assert_eq!(fold(source), named(&[
+            ("size", StaticValue::Int(11)),
+            ("c", StaticValue::Int(98)),
+            ("wrapped", StaticValue::Int(44)),
+            ("flags", list(&[16, 4, 1])),
+            ("text", list(&[104, 105, 0])),
+            ("cut", list(&[104, 105])),
+            ("v", list(&[-1])),
+            ("calls", StaticValue::Int(3)),
+        ]));
+    }
+
+    #[test]
+    fn unsigned_operands_are_converted() {
+        assert_eq!(fold("int g = -1 < 1u;\nunsigned h = (0u - 1) / 2;"), named(&[
+            ("g", StaticValue::Int(0)),
+            ("h", StaticValue::Int(2147483647)),
+        ]));
+    }
+
+    #[test]
+    fn objects_without_initializers_are_zero() {
+        // Locals without `static`, and `extern` declarations, aren't static objects here.
+        let source = "int g; int a[100]; extern int e; int main() { static int s; extern int f; int local = g; return local; }";
+        assert_eq!(fold(source), named(&[("g", StaticValue::Zero), ("a", StaticValue::Zero), ("s", StaticValue::Zero)]));
+    }
+
+    #[test]
+    fn initializers_must_be_constant() {
+        assert_eq!(fold("int g; int h = g + 1;"), Err(vec!["initializer of a static object must be constant: expression is not a compile-time constant at 1:16".to_owned()]));
+        assert_eq!(fold("int f(); int g = f();").unwrap_err(), vec!["initializer of a static object must be constant: expression is not a compile-time constant at 1:18"]);
+        assert_eq!(fold("int g = 1 / 0;").unwrap_err(), vec!["initializer of a static object must be constant: division by zero in constant expression at 1:9"]);
+        assert_eq!(fold("int main(int n) { static int s = n; int *p = &n; static int *q = &n; return s + *p + *q; }").unwrap_err(), vec![
+            "initializer of a static object must be constant: expression is not a compile-time constant at 1:34",
+            "initializer of a static object must be constant: expression is not a compile-time constant at 1:67",
+        ]);
+    }
+
+    #[test]
+    fn address_constants() {
+        let source = "\
+int g;
+int a[4];
+int *p = &g;
+int *q = a;
+int *r = &a[2];
+int *s = a + 3 - 1;
+char *t = \"hi\";
+int *n = 0;
+int *m = (int *)4096;
+int main() { static int x; static int *y = &x; return *y; }";
+        let (program, statics, _) = fold_program(source);
+        let address = |name: &str, offset| {
+            let declaration = *statics.keys().find(|&&id| program.ast[id].name == name).unwrap();
+            StaticValue::Address { object: StaticObject::Declaration(declaration), offset }
+        };
+        let values = fold(source).unwrap();
+        assert_eq!(values[2..6].to_vec(), named(&[
+            ("p", address("g", 0)),
+            ("q", address("a", 0)),
+            ("r", address("a", 8)),
+            ("s", address("a", 8)),
+        ]).unwrap());
+        match values[6].1 {
+            StaticValue::Address { object: StaticObject::String(literal), offset: 0 } =>
+                assert_eq!(program.ast[literal].kind, ExpressionKind::StringLiteral(b"hi".to_vec())),
+            ref value => panic!("{:?}", value),
+        }
+        assert_eq!(values[7..].to_vec(), named(&[
+            ("n", StaticValue::Int(0)),
+            ("m", StaticValue::Int(4096)),
+            ("x", StaticValue::Zero),
+            ("y", address("x", 0)),
+        ]).unwrap());
+
+        assert_eq!(fold("struct s { int x; }; struct s v; int *p = &v.x;").unwrap_err(), vec!["the address of a struct member isn't supported in a static initializer at 1:44"]);
+    }
+}
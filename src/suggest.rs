@@ -0,0 +1,75 @@
+//! Suggestions of what a misspelled name was meant to be, from how few edits
+//! it takes to turn it into one that is known.
+
+/// How many characters have to be inserted, deleted, replaced, or swapped with
+/// the one beside them to turn `a` into `b`.
+pub fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // The distances from each prefix of `a` to the prefixes of `b`, for the
+    // last two prefixes of `a` and the current one.
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let replace = previous[j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = replace.min(previous[j] + 1).min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = previous;
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The most edits a name as long as `name` can be from what it is taken to be
+/// a misspelling of: one for every three characters. Names shorter than that
+/// are too easily one edit from anything to be taken for a misspelling.
+fn threshold(name: &str) -> usize {
+    name.chars().count() / 3
+}
+
+/// The one of `candidates` that `name` is most likely a misspelling of, if any
+/// is close enough to it. Of those as close as each other, the first in
+/// alphabetical order is taken, so that the suggestion doesn't depend on the
+/// order the candidates come in.
+pub fn closest<'a, I: IntoIterator<Item = &'a str>>(name: &str, candidates: I) -> Option<&'a str> {
+    candidates.into_iter()
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= threshold(name))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distances_count_edits() {
+        assert_eq!(distance("return", "return"), 0);
+        assert_eq!(distance("retrun", "return"), 1);
+        assert_eq!(distance("countr", "counter"), 1);
+        assert_eq!(distance("whle", "while"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "int"), 3);
+        assert_eq!(distance("ab", "ba"), 1);
+    }
+
+    #[test]
+    fn only_close_names_are_suggested() {
+        let names = ["counter", "total", "index", "x"];
+        assert_eq!(closest("countr", names.iter().cloned()), Some("counter"));
+        assert_eq!(closest("totl", names.iter().cloned()), Some("total"));
+        assert_eq!(closest("indx", names.iter().cloned()), Some("index"));
+        assert_eq!(closest("banana", names.iter().cloned()), None);
+        assert_eq!(closest("idx", names.iter().cloned()), None);
+        assert_eq!(closest("y", names.iter().cloned()), None);
+        // A tie goes the same way whatever the order.
+        assert_eq!(closest("countr", vec!["counter", "count"]), Some("count"));
+        assert_eq!(closest("countr", vec!["count", "counter"]), Some("count"));
+    }
+}
@@ -0,0 +1,259 @@
+//! Folds the `case` labels of each `switch` to the values codegen builds its
+//! jump tables from, and checks them: every label must be a constant, no two
+//! labels in one `switch` may have the same value, and there may be only one
+//! `default`.
+//!
+//! A label's value is converted to the promoted type of the `switch` value, the
+//! type the comparison is done in. A label that doesn't fit the unpromoted type
+//! is warned about: `case 300:` in a `switch` on a `char` can never match, and
+//! `case -1:` in one on an `unsigned` matches `4294967295`.
+
+use std::collections::HashMap;
+use ast::*;
+use ast::visit::{self, Visitor};
+use const_eval::{const_eval, convert, ConstEnv};
+use semantic::SemanticError;
+use typecheck::{promote, Types};
+
+/// The labels that belong to one `switch`.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SwitchLabels {
+    /// The value of each `case` label and the label itself, in source order.
+    pub cases: Vec<(i64, StmtId)>,
+    pub default: Option<StmtId>,
+}
+
+/// The labels of each `switch` in the program.
+pub type Switches = HashMap<StmtId, SwitchLabels>;
+
+struct Checker<'a> {
+    types: &'a Types,
+    env: ConstEnv<'a>,
+    /// The `switch`es around the statement being checked, innermost last, with
+    /// the type of each one's value if it is an integer.
+    stack: Vec<(StmtId, Option<Type>)>,
+    switches: Switches,
+    errors: Vec<SemanticError>,
+}
+
+impl<'a> Checker<'a> {
+    fn case(&mut self, ast: &Ast, label: StmtId, value: ExprId) {
+        let (switch, ty) = match self.stack.last() {
+            Some(&(switch, ref ty)) => (switch, ty.clone()),
+            // `ContextChecker` reports labels outside a `switch`.
+            None => return,
+        };
+        let value = match const_eval(&ast[value], &self.env) {
+            Ok(value) => value,
+            Err(error) => {
+                self.errors.push(SemanticError::CaseLabel(error));
+                return;
+            },
+        };
+        let span = ast[label].span;
+        let value = match ty {
+            Some(ty) => {
+                if convert(value, &ty) != Some(value) {
+                    self.errors.push(SemanticError::CaseOutOfRange {
+                        value,
+                        ty: ty.clone(),
+                        line: span.line,
+                        column: span.column,
+                    });
+                }
+                convert(value, &promote(&ty)).unwrap_or(value)
+            },
+            None => value,
+        };
+
+        let labels = self.switches.get_mut(&switch).unwrap();
+        if let Some(&(_, previous)) = labels.cases.iter().find(|&&(other, _)| other == value) {
+            let previous = ast[previous].span;
+            self.errors.push(SemanticError::DuplicateCase {
+                value,
+                line: span.line,
+                column: span.column,
+                previous_line: previous.line,
+                previous_column: previous.column,
+            });
+        } else {
+            labels.cases.push((value, label));
+        }
+    }
+
+    fn default(&mut self, ast: &Ast, label: StmtId) {
+        let switch = match self.stack.last() {
+            Some(&(switch, _)) => switch,
+            None => return,
+        };
+        let labels = self.switches.get_mut(&switch).unwrap();
+        match labels.default {
+            Some(previous) => {
+                let (span, previous) = (ast[label].span, ast[previous].span);
+                self.errors.push(SemanticError::DuplicateDefault {
+                    line: span.line,
+                    column: span.column,
+                    previous_line: previous.line,
+                    previous_column: previous.column,
+                });
+            },
+            None => labels.default = Some(label),
+        }
+    }
+}
+
+impl<'a> Visitor for Checker<'a> {
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        match ast[statement].kind {
+            StatementKind::Switch(value, _) => {
+                let ty = self.types.get(&value)
+                    .map(|ty| ty.unqualified().clone())
+                    .filter(|ty| ty.is_integer());
+                self.switches.insert(statement, SwitchLabels::default());
+                self.stack.push((statement, ty));
+                visit::walk_stmt(self, ast, statement);
+                self.stack.pop();
+                return;
+            },
+            StatementKind::Case(value, _) => self.case(ast, statement, value),
+            StatementKind::Default(_) => self.default(ast, statement),
+            _ => {},
+        }
+        visit::walk_stmt(self, ast, statement);
+    }
+}
+
+/// Folds and checks the labels of every `switch` in `program`. Labels outside
+/// any `switch` are left to `semantic`, and range checks are skipped for a
+/// `switch` whose value has no integer type.
+pub fn check(program: &Program, types: &Types) -> (Switches, Vec<SemanticError>) {
+    let mut checker = Checker {
+        types,
+        env: ConstEnv::new(&program.ast),
+        stack: Vec::new(),
+        switches: HashMap::new(),
+        errors: Vec::new(),
+    };
+    checker.visit_program(program);
+    (checker.switches, checker.errors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+    use const_eval::ConstEvalError;
+    use resolve::resolve;
+    use semantic::Severity;
+    use typecheck;
+
+    fn check_source(source: &str) -> (Program, Switches, Vec<SemanticError>) {
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let (resolutions, errors) = resolve(&program);
+        assert_eq!(errors, vec![]);
+        let (types, _, errors) = typecheck::check(&program, &resolutions);
+        assert_eq!(errors, vec![]);
+        let (switches, errors) = check(&program, &types);
+        (program, switches, errors)
+    }
+
+    /// The case values of each `switch`, in source order of the `switch`es.
+    fn values(program: &Program, switches: &Switches) -> Vec<Vec<i64>> {
+        let mut switches: Vec<_> = switches.iter().collect();
+        switches.sort_by_key(|&(&switch, _)| {
+            let span = program.ast[switch].span;
+            (span.line, span.column)
+        });
+        switches.into_iter().map(|(_, labels)| labels.cases.iter().map(|&(value, _)| value).collect()).collect()
+    }
+
+    #[test]
+    fn labels_are_folded() {
+        let (program, switches, errors) = check_source(
+            "int main(int x) { switch (x) { case 1 + 1: case -3: default: switch (x) { case 'a': break; } } return 0; }"
+        );
+        assert_eq!(errors, vec![]);
+        assert_eq!(values(&program, &switches), vec![vec![2, -3], vec![97]]);
+        let outer = switches.values().find(|labels| labels.cases.len() == 2).unwrap();
+        match program.ast[outer.default.unwrap()].kind {
+            StatementKind::Default(_) => {},
+            ref kind => panic!("expected a default label, found {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn values_convert_to_the_promoted_type() {
+        let (program, switches, errors) = check_source(
+            "int main(unsigned x, long y, char c) { switch (x) { case -1: break; } switch (y) { case -1: break; } switch (c) { case -1: break; } return 0; }"
+        );
+        assert_eq!(errors, vec![SemanticError::CaseOutOfRange { value: -1, ty: Type::UnsignedInt, line: 1, column: 53 }]);
+        assert_eq!(values(&program, &switches), vec![vec![4294967295], vec![-1], vec![-1]]);
+    }
+
+    #[test]
+    fn labels_convert_their_operands() {
+        let (program, switches, errors) = check_source("int main(int x) { switch (x) { case -1 < 1u: break; case 1: break; } return 0; }");
+        assert_eq!(errors, vec![]);
+        assert_eq!(values(&program, &switches), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn duplicate_cases() {
+        let (_, _, errors) = check_source(
+            "int main(int x) {\n    switch (x) {\n    case 1: break;\n    case 1: break;\n    }\n    return 0;\n}"
+        );
+        assert_eq!(errors, vec![SemanticError::DuplicateCase { value: 1, line: 4, column: 5, previous_line: 3, previous_column: 5 }]);
+        assert_eq!(errors[0].to_string(), "duplicate case value `1` at 4:5 (previously used at 3:5)");
+    }
+
+    #[test]
+    fn duplicate_cases_spelled_differently() {
+        let (_, _, errors) = check_source(
+            "int main(int x) {\n    switch (x) {\n    case 10: break;\n    case 0x0A: break;\n    case 5 * 2: break;\n    }\n    return 0;\n}"
+        );
+        assert_eq!(errors, vec![
+            SemanticError::DuplicateCase { value: 10, line: 4, column: 5, previous_line: 3, previous_column: 5 },
+            SemanticError::DuplicateCase { value: 10, line: 5, column: 5, previous_line: 3, previous_column: 5 },
+        ]);
+    }
+
+    #[test]
+    fn nested_switches_have_their_own_labels() {
+        let (_, _, errors) = check_source(
+            "int main(int x) { switch (x) { case 1: switch (x) { case 1: default: break; } default: break; } return 0; }"
+        );
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn two_defaults() {
+        let (_, switches, errors) = check_source(
+            "int main(int x) {\n    switch (x) {\n    default: break;\n    case 1: default: break;\n    }\n    return 0;\n}"
+        );
+        assert_eq!(errors, vec![SemanticError::DuplicateDefault { line: 4, column: 13, previous_line: 3, previous_column: 5 }]);
+        assert_eq!(errors[0].to_string(), "multiple `default` labels in one `switch` at 4:13 (the first is at 3:5)");
+        assert!(switches.values().all(|labels| labels.default.is_some()));
+    }
+
+    #[test]
+    fn out_of_range_cases_warn() {
+        let (_, _, errors) = check_source(
+            "int main(char c) {\n    switch (c) {\n    case 127: break;\n    case 300: break;\n    case -129: break;\n    }\n    return 0;\n}"
+        );
+        assert_eq!(errors, vec![
+            SemanticError::CaseOutOfRange { value: 300, ty: Type::Char, line: 4, column: 5 },
+            SemanticError::CaseOutOfRange { value: -129, ty: Type::Char, line: 5, column: 5 },
+        ]);
+        assert_eq!(errors[0].severity(), Severity::Warning);
+        assert_eq!(errors[0].to_string(), "case value `300` is out of range for `char` at 4:5");
+    }
+
+    #[test]
+    fn labels_must_be_constant() {
+        let (_, _, errors) = check_source("int main(int x) {\n    switch (x) {\n    case x: break;\n    }\n    return 0;\n}");
+        assert_eq!(errors, vec![SemanticError::CaseLabel(ConstEvalError::NotConstant {
+            span: Span { line: 3, column: 10, start: 38, end: 39 },
+        })]);
+    }
+}
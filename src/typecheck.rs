@@ -0,0 +1,1397 @@
+//! Computes the type of every expression, and checks that operators,
+//! assignments, initializers, and returns are given operands of types they
+//! accept, and that whatever is assigned to, incremented, or has its address
+//! taken is an lvalue. An lvalue that is assigned to or incremented mustn't be
+//! const, and a member of a const struct is const. A pointer to const may not
+//! implicitly lose the qualifier, though only with a warning, as C compilers
+//! do; gaining one is always fine.
+//!
+//! An expression's recorded type is its own, with arrays and qualifiers kept:
+//! `a` in `const int a[2]` is `const int[2]`. Operands are converted to values
+//! first, so arrays decay to pointers and qualifiers are dropped before an
+//! operator looks at them. An expression whose type can't be worked out gets
+//! none, and whatever contains it isn't checked, so one mistake is reported
+//! once rather than at every enclosing expression.
+//!
+//! Integer operands are brought to a common type by the usual arithmetic
+//! conversions, and values are converted to the types they're assigned,
+//! passed, or returned as. Those implicit conversions are recorded beside the
+//! types, so that codegen knows where to extend or truncate a value.
+
+use std::collections::HashMap;
+use std::fmt;
+use ast::*;
+use ast::visit::{self, Visitor};
+use const_eval::{const_eval, convert, ConstEnv};
+use resolve::{Resolutions, Symbol};
+use semantic::Severity;
+
+/// The type of each expression that has one.
+pub type Types = HashMap<ExprId, Type>;
+
+/// The integer type each expression's value is implicitly converted to, for
+/// expressions whose value is used as a different integer type than their
+/// own. The target of a compound assignment is converted like the left operand
+/// of its operator, but isn't in the table, as it is used as an lvalue.
+pub type Conversions = HashMap<ExprId, Type>;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeError {
+    /// A binary operator, or the operator of a compound assignment, given
+    /// operands it doesn't apply to.
+    InvalidOperands { operator: BinaryOp, compound: bool, lhs: Type, rhs: Type, span: Span },
+    InvalidOperand { operator: UnaryOp, operand: Type, span: Span },
+    IncompatibleAssignment { target: Type, value: Type, span: Span },
+    IncompatibleInitializer { target: Type, value: Type, span: Span },
+    IncompatibleReturn { return_type: Type, value: Type, span: Span },
+    /// A condition whose type can't be tested for truth.
+    NotScalar { ty: Type, span: Span },
+    /// A `switch` on a value that isn't an integer.
+    SwitchNotInteger { ty: Type, span: Span },
+    /// The two branches of a conditional expression have no common type.
+    MismatchedBranches { then: Type, otherwise: Type, span: Span },
+    InvalidCast { from: Type, to: Type, span: Span },
+    InvalidSubscript { array: Type, index: Type, span: Span },
+    /// A call to something declared as a variable or parameter.
+    NotAFunction { name: String, ty: Type, span: Span },
+    /// A call with too few arguments, or too many for a function that isn't
+    /// variadic. `declaration` is the declaration of the function in scope.
+    WrongArgumentCount { name: String, expected: usize, found: usize, variadic: bool, span: Span, declaration: Span },
+    /// An argument that can't be converted to its parameter's type, numbered
+    /// from 0. `declaration` is the parameter's.
+    IncompatibleArgument { name: String, index: usize, parameter: Type, argument: Type, span: Span, declaration: Span },
+    /// A warning for a call to an undeclared function, which C89 implicitly
+    /// declares as `int name()`.
+    ImplicitDeclaration { name: String, span: Span },
+    /// A function named where a value is needed. There are no function pointers.
+    FunctionAsValue { name: String, span: Span },
+    /// `.` applied to a non-struct, or `->` to a non-pointer-to-struct.
+    InvalidMemberAccess { operator: &'static str, ty: Type, span: Span },
+    NoSuchMember { ty: Type, member: String, span: Span },
+    /// An operation that needs an lvalue given something else: `operation`
+    /// says what it tried to do, `found` what the expression is instead.
+    NotAnLvalue { operation: &'static str, found: String, span: Span },
+    /// A warning for a constant initializer whose value changes when it is
+    /// converted to the type of the object it initializes.
+    NarrowingConversion { from: Type, to: Type, value: i64, converted: i64, span: Span },
+    /// An assignment, increment, or decrement of a const lvalue. `target` is
+    /// the lvalue as written, and `variable` the variable it is found through
+    /// and where that is declared, if there is one.
+    ConstModification { operation: &'static str, target: String, ty: Type, variable: Option<(String, Span)>, span: Span },
+    /// A warning for a conversion of a pointer to const to a pointer to
+    /// non-const, through which the pointee could be modified. `parameter` is
+    /// the declaration of the parameter for an argument.
+    DiscardedConst { from: Type, to: Type, span: Span, parameter: Option<Span> },
+    /// A warning for a constant shift amount that is negative, or at least the
+    /// width in bits of the promoted type `ty` being shifted, at the amount.
+    ShiftOutOfRange { amount: i64, ty: Type, span: Span },
+}
+
+impl TypeError {
+    pub fn span(&self) -> Span {
+        match *self {
+            TypeError::InvalidOperands { span, .. }
+            | TypeError::InvalidOperand { span, .. }
+            | TypeError::IncompatibleAssignment { span, .. }
+            | TypeError::IncompatibleInitializer { span, .. }
+            | TypeError::IncompatibleReturn { span, .. }
+            | TypeError::NotScalar { span, .. }
+            | TypeError::SwitchNotInteger { span, .. }
+            | TypeError::MismatchedBranches { span, .. }
+            | TypeError::InvalidCast { span, .. }
+            | TypeError::InvalidSubscript { span, .. }
+            | TypeError::NotAFunction { span, .. }
+            | TypeError::WrongArgumentCount { span, .. }
+            | TypeError::IncompatibleArgument { span, .. }
+            | TypeError::ImplicitDeclaration { span, .. }
+            | TypeError::FunctionAsValue { span, .. }
+            | TypeError::InvalidMemberAccess { span, .. }
+            | TypeError::NoSuchMember { span, .. }
+            | TypeError::NotAnLvalue { span, .. }
+            | TypeError::NarrowingConversion { span, .. }
+            | TypeError::ConstModification { span, .. }
+            | TypeError::DiscardedConst { span, .. }
+            | TypeError::ShiftOutOfRange { span, .. } => span,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.warning() {
+            Some(_) => Severity::Warning,
+            None => Severity::Error,
+        }
+    }
+
+    /// The code of the error, from `codes::CODES`.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            TypeError::InvalidOperands { .. } => "E0200",
+            TypeError::InvalidOperand { .. } => "E0201",
+            TypeError::IncompatibleAssignment { .. } => "E0202",
+            TypeError::IncompatibleInitializer { .. } => "E0203",
+            TypeError::IncompatibleReturn { .. } => "E0204",
+            TypeError::NotScalar { .. } => "E0205",
+            TypeError::SwitchNotInteger { .. } => "E0206",
+            TypeError::MismatchedBranches { .. } => "E0207",
+            TypeError::InvalidCast { .. } => "E0208",
+            TypeError::InvalidSubscript { .. } => "E0209",
+            TypeError::NotAFunction { .. } => "E0210",
+            TypeError::WrongArgumentCount { .. } => "E0211",
+            TypeError::IncompatibleArgument { .. } => "E0212",
+            TypeError::FunctionAsValue { .. } => "E0213",
+            TypeError::InvalidMemberAccess { .. } => "E0214",
+            TypeError::NoSuchMember { .. } => "E0215",
+            TypeError::NotAnLvalue { .. } => "E0216",
+            TypeError::ConstModification { .. } => "E0217",
+            TypeError::ImplicitDeclaration { .. } => "W0007",
+            TypeError::NarrowingConversion { .. } => "W0008",
+            TypeError::DiscardedConst { .. } => "W0009",
+            TypeError::ShiftOutOfRange { .. } => "W0010",
+        }
+    }
+
+    /// The name of the warning this is, if it is one, as `semantic::WARNINGS`
+    /// lists.
+    pub fn warning(&self) -> Option<&'static str> {
+        match *self {
+            TypeError::ImplicitDeclaration { .. } => Some("implicit-function-declaration"),
+            TypeError::NarrowingConversion { .. } => Some("overflow"),
+            TypeError::DiscardedConst { .. } => Some("discarded-qualifiers"),
+            TypeError::ShiftOutOfRange { .. } => Some("shift-count-overflow"),
+            _ => None,
+        }
+    }
+
+    /// The other places the error has to do with, each with what it has to do
+    /// with them.
+    pub fn labels(&self) -> Vec<((usize, usize), &'static str)> {
+        match *self {
+            TypeError::WrongArgumentCount { declaration, .. }
+            | TypeError::ConstModification { variable: Some((_, declaration)), .. } =>
+                vec![((declaration.line, declaration.column), "declared here")],
+            TypeError::IncompatibleArgument { declaration, .. }
+            | TypeError::DiscardedConst { parameter: Some(declaration), .. } =>
+                vec![((declaration.line, declaration.column), "the parameter is declared here")],
+            _ => Vec::new(),
+        }
+    }
+
+    /// What the error is, without where: a diagnostic shows that itself.
+    pub fn message(&self) -> String {
+        match *self {
+            TypeError::ImplicitDeclaration { ref name, .. } =>
+                format!("{} (assumed to be `int {}()`, which takes any arguments)", self.summary(), name),
+            _ => self.summary(),
+        }
+    }
+
+    /// The error, without where it is or anything said about it after that.
+    fn summary(&self) -> String {
+        match *self {
+            TypeError::InvalidOperands { operator, compound, ref lhs, ref rhs, .. } => format!(
+                "invalid operands to binary `{}{}` (`{}` and `{}`)",
+                operator.symbol(), if compound { "=" } else { "" }, lhs, rhs,
+            ),
+            TypeError::InvalidOperand { operator, ref operand, .. } =>
+                format!("invalid operand to unary `{}` (`{}`)", operator.symbol(), operand),
+            TypeError::IncompatibleAssignment { ref target, ref value, .. } =>
+                format!("cannot assign `{}` to `{}`", value, target),
+            TypeError::IncompatibleInitializer { ref target, ref value, .. } =>
+                format!("cannot initialize `{}` with `{}`", target, value),
+            TypeError::IncompatibleReturn { ref return_type, ref value, .. } =>
+                format!("cannot return `{}` from a function returning `{}`", value, return_type),
+            TypeError::NotScalar { ref ty, .. } =>
+                format!("condition has type `{}`, which isn't a scalar", ty),
+            TypeError::SwitchNotInteger { ref ty, .. } =>
+                format!("switch value has type `{}`, which isn't an integer", ty),
+            TypeError::MismatchedBranches { ref then, ref otherwise, .. } =>
+                format!("branches of conditional have incompatible types (`{}` and `{}`)", then, otherwise),
+            TypeError::InvalidCast { ref from, ref to, .. } =>
+                format!("cannot cast `{}` to `{}`", from, to),
+            TypeError::InvalidSubscript { ref array, ref index, .. } =>
+                format!("cannot subscript `{}` with `{}`", array, index),
+            TypeError::NotAFunction { ref name, ref ty, .. } =>
+                format!("called object `{}` has type `{}`, which isn't a function", name, ty),
+            TypeError::WrongArgumentCount { ref name, expected, found, variadic, .. } => format!(
+                "`{}` takes {}{}, but {} given",
+                name,
+                if variadic { "at least " } else { "" },
+                count_of(expected, "argument"),
+                if found == 1 { "1 was".to_owned() } else { format!("{} were", found) },
+            ),
+            TypeError::IncompatibleArgument { ref name, index, ref parameter, ref argument, .. } => format!(
+                "cannot pass `{}` as argument {} of `{}`, which takes `{}`",
+                argument, index + 1, name, parameter,
+            ),
+            TypeError::ImplicitDeclaration { ref name, .. } =>
+                format!("implicit declaration of function `{}`", name),
+            TypeError::FunctionAsValue { ref name, .. } =>
+                format!("function `{}` used as a value", name),
+            TypeError::InvalidMemberAccess { operator, ref ty, .. } =>
+                format!("member access with `{}` on `{}`", operator, ty),
+            TypeError::NoSuchMember { ref ty, ref member, .. } =>
+                format!("`{}` has no member `{}`", ty, member),
+            TypeError::NotAnLvalue { operation, ref found, .. } =>
+                format!("cannot {} this expression: {}", operation, found),
+            TypeError::NarrowingConversion { ref from, ref to, value, converted, .. } => format!(
+                "implicit conversion from `{}` to `{}` changes value from {} to {}",
+                from, to, value, converted,
+            ),
+            TypeError::ConstModification { operation, ref target, ref ty, .. } =>
+                format!("cannot {} `{}`, which has const-qualified type `{}`", operation, target, ty),
+            TypeError::DiscardedConst { ref from, ref to, .. } =>
+                format!("conversion from `{}` to `{}` discards the `const` qualifier of the pointee", from, to),
+            TypeError::ShiftOutOfRange { amount, ref ty, .. } if amount < 0 =>
+                format!("shift amount {} is negative, shifting `{}`", amount, ty),
+            TypeError::ShiftOutOfRange { amount, ref ty, .. } =>
+                format!("shift amount {} is at least the width of `{}`", amount, ty),
+        }
+    }
+}
+
+/// `count` followed by `noun`, pluralized if `count` isn't one.
+fn count_of(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span();
+        write!(f, "{} at {}:{}", self.summary(), span.line, span.column)?;
+        match *self {
+            TypeError::ImplicitDeclaration { ref name, .. } =>
+                write!(f, " (assumed to be `int {}()`, which takes any arguments)", name),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The type an integer of type `ty` is promoted to before arithmetic: types
+/// narrower than `int` become `int`, and the rest stay as they are.
+pub fn promote(ty: &Type) -> Type {
+    match *ty.unqualified() {
+        Type::Char => Type::Int,
+        ref ty => ty.clone(),
+    }
+}
+
+/// The type the usual arithmetic conversions bring integer operands of types
+/// `lhs` and `rhs` to. Both are promoted, and then the narrower converts to the
+/// wider. Of a signed and an unsigned type, the unsigned one wins unless it is
+/// narrower, since `long` holds every `unsigned int` but `int` doesn't.
+pub fn common_type(lhs: &Type, rhs: &Type) -> Type {
+    let wide = |ty: &Type| matches!(*ty, Type::Long | Type::UnsignedLong);
+    let (lhs, rhs) = (promote(lhs), promote(rhs));
+    if lhs.is_signed() == rhs.is_signed() {
+        return if wide(&rhs) { rhs } else { lhs };
+    }
+    let (signed, unsigned) = if lhs.is_signed() { (lhs, rhs) } else { (rhs, lhs) };
+    if wide(&signed) && !wide(&unsigned) { signed } else { unsigned }
+}
+
+/// Whether converting the constant `value` to the integer type `to` changes
+/// it. A negative value that fits the signed type of the same width doesn't
+/// count for an unsigned type, since `unsigned u = -1;` is a common way of
+/// writing all ones.
+fn changes_value(value: i64, to: &Type) -> bool {
+    let signed = match *to.unqualified() {
+        Type::UnsignedInt => Type::Int,
+        ref ty => ty.clone(),
+    };
+    convert(value, to) != Some(value) && convert(value, &signed) != Some(value)
+}
+
+/// Whether a value of type `from` can be converted to `to` by assignment.
+/// `null` says whether the value is a null pointer constant.
+fn assignable(to: &Type, from: &Type, null: bool) -> bool {
+    match (to.unqualified(), from) {
+        (to, from) if to.is_integer() && from.is_integer() => true,
+        (Type::Pointer(to), Type::Pointer(from)) => to.unqualified() == from.unqualified(),
+        (&Type::Pointer(_), from) => null && from.is_integer(),
+        (Type::Struct(to), Type::Struct(from)) => to == from,
+        _ => false,
+    }
+}
+
+/// Whether converting a value of type `from` to `to` turns a pointer to const
+/// into a pointer to non-const.
+fn discards_const(to: &Type, from: &Type) -> bool {
+    match (to.unqualified(), from) {
+        (Type::Pointer(to), Type::Pointer(from)) => from.is_const() && !to.is_const(),
+        _ => false,
+    }
+}
+
+/// The type of a member of type `ty` of a const struct: the member's type made
+/// const, or for an array its element type.
+fn const_member(ty: &Type) -> Type {
+    match *ty {
+        Type::Array(ref element, length) => Type::Array(Box::new(const_member(element)), length),
+        ref ty => ty.clone().with_const(),
+    }
+}
+
+/// The type of `lhs operator rhs` for operand values of the given types, if the
+/// operator applies to them. `rhs_null` says whether `rhs` is a null pointer
+/// constant, and `lhs_null` the same of `lhs`.
+fn binary_result(operator: BinaryOp, lhs: &Type, rhs: &Type, lhs_null: bool, rhs_null: bool) -> Option<Type> {
+    let integers = lhs.is_integer() && rhs.is_integer();
+    let same_pointers = match (lhs, rhs) {
+        (Type::Pointer(lhs), Type::Pointer(rhs)) => lhs.unqualified() == rhs.unqualified(),
+        _ => false,
+    };
+    let valid = match operator {
+        BinaryOp::Add => match (lhs, rhs) {
+            (&Type::Pointer(_), rhs) if rhs.is_integer() => return Some(lhs.clone()),
+            (lhs, &Type::Pointer(_)) if lhs.is_integer() => return Some(rhs.clone()),
+            _ => integers,
+        },
+        BinaryOp::Subtract => match (lhs, rhs) {
+            (&Type::Pointer(_), rhs) if rhs.is_integer() => return Some(lhs.clone()),
+            _ => integers || same_pointers,
+        },
+        BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo
+        | BinaryOp::ShiftLeft | BinaryOp::ShiftRight
+        | BinaryOp::BitwiseAnd | BinaryOp::BitwiseXor | BinaryOp::BitwiseOr => integers,
+        BinaryOp::LessThan | BinaryOp::LessEqual | BinaryOp::GreaterThan | BinaryOp::GreaterEqual =>
+            integers || same_pointers,
+        BinaryOp::Equal | BinaryOp::NotEqual => {
+            integers
+                || same_pointers
+                || (matches!(*lhs, Type::Pointer(_)) && rhs_null)
+                || (lhs_null && matches!(*rhs, Type::Pointer(_)))
+        },
+        BinaryOp::LogicalAnd | BinaryOp::LogicalOr => lhs.is_scalar() && rhs.is_scalar(),
+    };
+    if !valid {
+        return None;
+    }
+    Some(match operator {
+        // The difference of two pointers.
+        BinaryOp::Subtract if !integers => Type::Long,
+        BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo
+        | BinaryOp::BitwiseAnd | BinaryOp::BitwiseXor | BinaryOp::BitwiseOr => common_type(lhs, rhs),
+        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => promote(lhs),
+        _ => Type::Int,
+    })
+}
+
+/// Describes `expression` if it isn't an lvalue, an expression that
+/// designates an object. Conditional expressions aren't, for now.
+fn non_lvalue(ast: &Ast, expression: ExprId) -> Option<String> {
+    Some(match ast[expression].kind {
+        ExpressionKind::Variable(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::Unary(UnaryOp::Dereference, _)
+        | ExpressionKind::Subscript(..)
+        | ExpressionKind::PointerMember(..) => return None,
+        // A member is an object only if the struct it's in is.
+        ExpressionKind::Member(object, _) => return non_lvalue(ast, object).map(|found| format!("member of {}", found)),
+        ExpressionKind::IntLiteral(..) => "integer literal".to_owned(),
+        ExpressionKind::CharConstant(_) => "character constant".to_owned(),
+        ExpressionKind::Unary(operator @ UnaryOp::PreIncrement, _)
+        | ExpressionKind::Unary(operator @ UnaryOp::PreDecrement, _)
+        | ExpressionKind::Unary(operator @ UnaryOp::PostIncrement, _)
+        | ExpressionKind::Unary(operator @ UnaryOp::PostDecrement, _) => format!("result of `{}`", operator.symbol()),
+        ExpressionKind::Unary(operator, _) => format!("result of unary `{}`", operator.symbol()),
+        ExpressionKind::Binary(operator, ..) => format!("result of binary `{}`", operator.symbol()),
+        ExpressionKind::Assign(..) => "result of assignment".to_owned(),
+        ExpressionKind::Conditional(..) => "conditional expression".to_owned(),
+        ExpressionKind::Call(ref name, _) => format!("result of call to `{}`", name),
+        ExpressionKind::Cast { .. } => "cast".to_owned(),
+        ExpressionKind::SizeOf(_) => "`sizeof` expression".to_owned(),
+        ExpressionKind::Comma(_) => "comma expression".to_owned(),
+    })
+}
+
+/// The variable an lvalue is found through: `p` in `*p`, `p->x`, or `p[1].y`.
+fn base_variable(ast: &Ast, expression: ExprId) -> Option<ExprId> {
+    match ast[expression].kind {
+        ExpressionKind::Variable(_) => Some(expression),
+        ExpressionKind::Unary(UnaryOp::Dereference, inner)
+        | ExpressionKind::Subscript(inner, _)
+        | ExpressionKind::Member(inner, _)
+        | ExpressionKind::PointerMember(inner, _) => base_variable(ast, inner),
+        _ => None,
+    }
+}
+
+struct Checker<'a> {
+    program: &'a Program,
+    resolutions: &'a Resolutions,
+    /// The members of each defined struct, by tag.
+    structs: HashMap<&'a str, &'a [Member]>,
+    /// The return type of the function being checked.
+    return_type: Type,
+    types: Types,
+    conversions: Conversions,
+    errors: Vec<TypeError>,
+}
+
+impl<'a> Checker<'a> {
+    /// Records `error` and gives no type, for returning from `expr`.
+    fn error(&mut self, error: TypeError) -> Option<Type> {
+        self.errors.push(error);
+        None
+    }
+
+    fn function(&self, item: usize) -> &'a Function {
+        match self.program.items[item] {
+            TopLevel::Function(ref function) => function,
+            _ => unreachable!("resolved to a non-function item"),
+        }
+    }
+
+    /// Whether `expression` is a null pointer constant: an integer constant
+    /// expression that evaluates to zero.
+    fn is_null(&self, expression: ExprId) -> bool {
+        let ast = &self.program.ast;
+        self.types.get(&expression).is_some_and(|ty| ty.is_integer())
+            && const_eval(&ast[expression], &ConstEnv::new(ast)) == Ok(0)
+    }
+
+    /// Warns if `operator` is a shift of a `ty` by a constant `amount` that
+    /// doesn't fit: a negative one, or one of at least the width of `ty`, which
+    /// the hardware would reduce to a different amount.
+    fn shift_amount(&mut self, operator: BinaryOp, ty: &Type, amount: ExprId) {
+        if operator != BinaryOp::ShiftLeft && operator != BinaryOp::ShiftRight {
+            return;
+        }
+        let ast = &self.program.ast;
+        let env = ConstEnv::new(ast);
+        if let (Ok(value), Some(size)) = (const_eval(&ast[amount], &env), env.size_of(ty)) {
+            if value < 0 || value >= size * 8 {
+                self.errors.push(TypeError::ShiftOutOfRange { amount: value, ty: ty.unqualified().clone(), span: ast[amount].span });
+            }
+        }
+    }
+
+    /// Records that the value of `expression`, of type `from`, is converted to
+    /// `to`, if those are different integer types.
+    fn convert(&mut self, expression: ExprId, from: &Type, to: &Type) {
+        if from.is_integer() && to.is_integer() && from.unqualified() != to.unqualified() {
+            self.conversions.insert(expression, to.unqualified().clone());
+        }
+    }
+
+    /// Records the conversions of the operands of `lhs operator rhs`, values of
+    /// types `lhs_type` and `rhs_type`, if they are integers. Shifts promote each
+    /// operand on its own; other arithmetic operators and comparisons bring both
+    /// to their common type.
+    fn convert_operands(&mut self, operator: BinaryOp, (lhs, lhs_type): (ExprId, &Type), (rhs, rhs_type): (ExprId, &Type)) {
+        if !lhs_type.is_integer() || !rhs_type.is_integer() {
+            return;
+        }
+        let (lhs_target, rhs_target) = match operator {
+            BinaryOp::LogicalAnd | BinaryOp::LogicalOr => return,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => (promote(lhs_type), promote(rhs_type)),
+            _ => (common_type(lhs_type, rhs_type), common_type(lhs_type, rhs_type)),
+        };
+        self.convert(lhs, lhs_type, &lhs_target);
+        self.convert(rhs, rhs_type, &rhs_target);
+    }
+
+    /// Types `expression`, which `operation` needs to be an lvalue, and checks
+    /// that it is one, and that it isn't const if `operation` modifies it.
+    fn lvalue(&mut self, expression: ExprId, operation: &'static str) -> Option<Type> {
+        let ty = self.expr(expression)?;
+        let ast = &self.program.ast;
+        let span = ast[expression].span;
+        if let Some(found) = non_lvalue(ast, expression) {
+            return self.error(TypeError::NotAnLvalue { operation, found, span });
+        }
+        if ty.is_const() && operation != "take the address of" {
+            let variable = base_variable(ast, expression).and_then(|variable| self.declaration_of(variable));
+            let target = expression_to_c_source(ast, expression);
+            return self.error(TypeError::ConstModification { operation, target, ty, variable, span });
+        }
+        Some(ty)
+    }
+
+    /// The name of the variable or parameter `variable` refers to and where it
+    /// is declared.
+    fn declaration_of(&self, variable: ExprId) -> Option<(String, Span)> {
+        match *self.resolutions.get(&variable)? {
+            Symbol::Variable(declaration) => {
+                let declaration = &self.program.ast[declaration];
+                Some((declaration.name.clone(), declaration.span))
+            },
+            Symbol::Parameter { function, index } => {
+                let parameter = &self.function(function).parameters[index];
+                Some((parameter.name.clone(), parameter.span))
+            },
+            Symbol::Function(_) => None,
+        }
+    }
+
+    /// Warns if converting `value`, of type `from`, to `to` discards a const
+    /// qualifier. `parameter` is the declaration of the parameter it is passed as.
+    fn check_const(&mut self, value: ExprId, from: &Type, to: &Type, parameter: Option<Span>) {
+        if discards_const(to, from) {
+            let span = self.program.ast[value].span;
+            self.errors.push(TypeError::DiscardedConst { from: from.clone(), to: to.unqualified().clone(), span, parameter });
+        }
+    }
+
+    /// Types `expression` and converts it to a value.
+    fn value(&mut self, expression: ExprId) -> Option<Type> {
+        self.expr(expression).map(|ty| ty.decay())
+    }
+
+    /// Types `expression`, records its type, and returns it.
+    fn expr(&mut self, expression: ExprId) -> Option<Type> {
+        let ty = self.expr_kind(expression)?;
+        self.types.insert(expression, ty.clone());
+        Some(ty)
+    }
+
+    fn expr_kind(&mut self, expression: ExprId) -> Option<Type> {
+        let program = self.program;
+        let span = program.ast[expression].span;
+        match program.ast[expression].kind {
+            ExpressionKind::IntLiteral(_, ref ty) => Some(ty.clone()),
+            ExpressionKind::CharConstant(_) | ExpressionKind::SizeOf(_) => Some(Type::Int),
+            ExpressionKind::StringLiteral(ref bytes) => Some(Type::Array(Box::new(Type::Char), bytes.len() + 1)),
+            // An undeclared name has already been reported.
+            ExpressionKind::Variable(ref name) => match *self.resolutions.get(&expression)? {
+                Symbol::Variable(declaration) => Some(program.ast[declaration].ty.clone()),
+                Symbol::Parameter { function, index } => Some(self.function(function).parameters[index].ty.clone()),
+                Symbol::Function(_) => self.error(TypeError::FunctionAsValue { name: name.clone(), span }),
+            },
+            ExpressionKind::Unary(operator, operand) => self.unary(operator, operand, span),
+            ExpressionKind::Binary(operator, lhs, rhs) => {
+                let (lhs_type, rhs_type) = (self.value(lhs), self.value(rhs));
+                let (lhs_type, rhs_type) = (lhs_type?, rhs_type?);
+                match binary_result(operator, &lhs_type, &rhs_type, self.is_null(lhs), self.is_null(rhs)) {
+                    Some(ty) => {
+                        self.convert_operands(operator, (lhs, &lhs_type), (rhs, &rhs_type));
+                        self.shift_amount(operator, &ty, rhs);
+                        Some(ty)
+                    },
+                    None => self.error(TypeError::InvalidOperands { operator, compound: false, lhs: lhs_type, rhs: rhs_type, span }),
+                }
+            },
+            ExpressionKind::Assign(operator, target, value) => {
+                let (target_type, value_type) = (self.lvalue(target, "assign to"), self.value(value));
+                let (target_type, value_type) = (target_type?, value_type?);
+                let (value_type, null) = match operator {
+                    Some(operator) => match binary_result(operator, &target_type.decay(), &value_type, false, self.is_null(value)) {
+                        Some(result) => {
+                            // The target's own conversion is left for codegen, which loads it.
+                            let rhs_target = match operator {
+                                BinaryOp::ShiftLeft | BinaryOp::ShiftRight => promote(&value_type),
+                                _ if target_type.is_integer() => common_type(&target_type, &value_type),
+                                _ => value_type.clone(),
+                            };
+                            self.convert(value, &value_type, &rhs_target);
+                            self.shift_amount(operator, &promote(&target_type), value);
+                            (result, false)
+                        },
+                        None => return self.error(TypeError::InvalidOperands {
+                            operator,
+                            compound: true,
+                            lhs: target_type.decay(),
+                            rhs: value_type,
+                            span,
+                        }),
+                    },
+                    None => {
+                        self.convert(value, &value_type, &target_type);
+                        (value_type, self.is_null(value))
+                    },
+                };
+                if !assignable(&target_type, &value_type, null) {
+                    let target = target_type.unqualified().clone();
+                    return self.error(TypeError::IncompatibleAssignment { target, value: value_type, span });
+                }
+                if operator.is_none() {
+                    self.check_const(value, &value_type, &target_type, None);
+                }
+                Some(target_type.unqualified().clone())
+            },
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                self.condition(condition);
+                let (then_type, otherwise_type) = (self.value(then), self.value(otherwise));
+                let (then_type, otherwise_type) = (then_type?, otherwise_type?);
+                match (&then_type, &otherwise_type) {
+                    (then_type, otherwise_type) if then_type.is_integer() && otherwise_type.is_integer() => {
+                        let common = common_type(then_type, otherwise_type);
+                        self.convert(then, then_type, &common);
+                        self.convert(otherwise, otherwise_type, &common);
+                        Some(common)
+                    },
+                    (then, otherwise) if then == otherwise => Some(then_type.clone()),
+                    (Type::Pointer(then), Type::Pointer(otherwise)) if then.unqualified() == otherwise.unqualified() =>
+                        Some(then_type.clone()),
+                    (&Type::Pointer(_), _) if self.is_null(otherwise) => Some(then_type.clone()),
+                    (_, &Type::Pointer(_)) if self.is_null(then) => Some(otherwise_type.clone()),
+                    _ => self.error(TypeError::MismatchedBranches { then: then_type, otherwise: otherwise_type, span }),
+                }
+            },
+            ExpressionKind::Call(ref name, ref arguments) => {
+                let types: Vec<Option<Type>> = arguments.iter().map(|&argument| self.value(argument)).collect();
+                match self.resolutions.get(&expression) {
+                    Some(&Symbol::Function(item)) => {
+                        let function = self.function(item);
+                        self.arguments(function, arguments, &types, span);
+                        Some(function.return_type.unqualified().clone())
+                    },
+                    Some(&Symbol::Variable(declaration)) => {
+                        let ty = program.ast[declaration].ty.clone();
+                        self.error(TypeError::NotAFunction { name: name.clone(), ty, span })
+                    },
+                    Some(&Symbol::Parameter { function, index }) => {
+                        let ty = self.function(function).parameters[index].ty.clone();
+                        self.error(TypeError::NotAFunction { name: name.clone(), ty, span })
+                    },
+                    None => {
+                        self.errors.push(TypeError::ImplicitDeclaration { name: name.clone(), span });
+                        self.promote_arguments(arguments, &types);
+                        Some(Type::Int)
+                    },
+                }
+            },
+            ExpressionKind::Cast { ref to, expr } => {
+                let from = self.value(expr)?;
+                if from.is_scalar() && to.is_scalar() {
+                    Some(to.unqualified().clone())
+                } else {
+                    self.error(TypeError::InvalidCast { from, to: to.clone(), span })
+                }
+            },
+            ExpressionKind::Subscript(array, index) => {
+                let (array_type, index_type) = (self.value(array), self.value(index));
+                let (array_type, index_type) = (array_type?, index_type?);
+                match (&array_type, &index_type) {
+                    (Type::Pointer(element), index) if index.is_integer() => Some((**element).clone()),
+                    (array, Type::Pointer(element)) if array.is_integer() => Some((**element).clone()),
+                    _ => self.error(TypeError::InvalidSubscript { array: array_type, index: index_type, span }),
+                }
+            },
+            ExpressionKind::Member(object, ref member) => {
+                let ty = self.expr(object)?;
+                self.member(".", &ty, &ty, member, span)
+            },
+            ExpressionKind::PointerMember(pointer, ref member) => {
+                let ty = self.value(pointer)?;
+                match ty {
+                    Type::Pointer(ref pointee) => self.member("->", pointee, &ty, member, span),
+                    _ => self.error(TypeError::InvalidMemberAccess { operator: "->", ty: ty.clone(), span }),
+                }
+            },
+            ExpressionKind::Comma(ref expressions) => {
+                let mut last = None;
+                for &expression in expressions {
+                    last = self.value(expression);
+                }
+                last
+            },
+        }
+    }
+
+    fn unary(&mut self, operator: UnaryOp, operand: ExprId, span: Span) -> Option<Type> {
+        let operand_type = match operator {
+            UnaryOp::AddressOf =>
+                return self.lvalue(operand, "take the address of").map(|ty| Type::Pointer(Box::new(ty))),
+            // Arrays can't be incremented, so these don't decay.
+            UnaryOp::PreIncrement | UnaryOp::PostIncrement => self.lvalue(operand, "increment")?.unqualified().clone(),
+            UnaryOp::PreDecrement | UnaryOp::PostDecrement => self.lvalue(operand, "decrement")?.unqualified().clone(),
+            _ => self.value(operand)?,
+        };
+        let result = match operator {
+            UnaryOp::Negate | UnaryOp::BitwiseNot if operand_type.is_integer() => {
+                let promoted = promote(&operand_type);
+                self.convert(operand, &operand_type, &promoted);
+                Some(promoted)
+            },
+            UnaryOp::LogicalNot if operand_type.is_scalar() => Some(Type::Int),
+            UnaryOp::PreIncrement | UnaryOp::PreDecrement | UnaryOp::PostIncrement | UnaryOp::PostDecrement
+                if operand_type.is_scalar() => Some(operand_type.clone()),
+            UnaryOp::Dereference => match operand_type {
+                Type::Pointer(ref pointee) => Some((**pointee).clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        match result {
+            Some(ty) => Some(ty),
+            None => self.error(TypeError::InvalidOperand { operator, operand: operand_type, span }),
+        }
+    }
+
+    /// Checks the `arguments` of a call to `function`, which have the given
+    /// types, against its parameters.
+    fn arguments(&mut self, function: &'a Function, arguments: &[ExprId], types: &[Option<Type>], span: Span) {
+        let (expected, found) = (function.parameters.len(), arguments.len());
+        if found < expected || (found > expected && !function.variadic) {
+            self.errors.push(TypeError::WrongArgumentCount {
+                name: function.name.clone(),
+                expected,
+                found,
+                variadic: function.variadic,
+                span,
+                declaration: function.span,
+            });
+        }
+
+        // Arguments past the parameters of a variadic function can be anything,
+        // and are only promoted.
+        let count = function.parameters.len().min(arguments.len());
+        self.promote_arguments(&arguments[count..], &types[count..]);
+        for (index, (parameter, (&argument, ty))) in function.parameters.iter().zip(arguments.iter().zip(types)).enumerate() {
+            if let Some(ref ty) = *ty {
+                self.convert(argument, ty, &parameter.ty);
+                if !assignable(&parameter.ty, ty, self.is_null(argument)) {
+                    self.errors.push(TypeError::IncompatibleArgument {
+                        name: function.name.clone(),
+                        index,
+                        parameter: parameter.ty.unqualified().clone(),
+                        argument: ty.clone(),
+                        span: self.program.ast[argument].span,
+                        declaration: parameter.span,
+                    });
+                } else {
+                    self.check_const(argument, ty, &parameter.ty, Some(parameter.span));
+                }
+            }
+        }
+    }
+
+    /// Records the promotion of `arguments`, of the given types, that aren't
+    /// matched by a parameter's type.
+    fn promote_arguments(&mut self, arguments: &[ExprId], types: &[Option<Type>]) {
+        for (&argument, ty) in arguments.iter().zip(types) {
+            if let Some(ref ty) = *ty {
+                self.convert(argument, ty, &promote(ty));
+            }
+        }
+    }
+
+    /// Types access to `member` of a struct of type `object`, which may be
+    /// const, through an operand of type `operand`.
+    fn member(&mut self, operator: &'static str, object: &Type, operand: &Type, member: &str, span: Span) -> Option<Type> {
+        let object_const = object.is_const();
+        let object = object.unqualified();
+        let tag = match *object {
+            Type::Struct(ref tag) => tag,
+            _ => return self.error(TypeError::InvalidMemberAccess { operator, ty: operand.clone(), span }),
+        };
+        let found = self.structs.get(tag.as_str())
+            .and_then(|members| members.iter().find(|candidate| candidate.name == member));
+        match found {
+            // A member of a const struct is const too.
+            Some(found) if object_const => Some(const_member(&found.ty)),
+            Some(found) => Some(found.ty.clone()),
+            None => self.error(TypeError::NoSuchMember { ty: object.clone(), member: member.to_owned(), span }),
+        }
+    }
+
+    /// Types `condition` and checks that it can be tested for truth.
+    fn condition(&mut self, condition: ExprId) {
+        if let Some(ty) = self.value(condition) {
+            if !ty.is_scalar() {
+                let span = self.program.ast[condition].span;
+                self.errors.push(TypeError::NotScalar { ty, span });
+            }
+        }
+    }
+
+    /// Checks `initializer` against the type of the object it initializes.
+    fn initializer(&mut self, target: &Type, initializer: &Initializer) {
+        let program = self.program;
+        match (target.unqualified(), initializer) {
+            (Type::Array(element, _), Initializer::Expr(value))
+                if element.unqualified() == &Type::Char
+                    && matches!(program.ast[*value].kind, ExpressionKind::StringLiteral(_)) => {
+                self.expr(*value);
+            },
+            (Type::Array(element, _), Initializer::List(items)) => {
+                for item in items {
+                    self.initializer(element, item);
+                }
+            },
+            (Type::Struct(tag), Initializer::List(items)) => {
+                let members = self.structs.get(tag.as_str()).cloned().unwrap_or(&[]);
+                for (member, item) in members.iter().zip(items) {
+                    self.initializer(&member.ty, item);
+                }
+            },
+            // A scalar may be initialized from a braced list of one value.
+            (_, Initializer::List(items)) => {
+                for item in items {
+                    self.initializer(target, item);
+                }
+            },
+            (_, Initializer::Expr(value)) => {
+                if let Some(ty) = self.value(*value) {
+                    let span = program.ast[*value].span;
+                    if !assignable(target, &ty, self.is_null(*value)) {
+                        self.errors.push(TypeError::IncompatibleInitializer { target: target.clone(), value: ty, span });
+                        return;
+                    }
+                    self.convert(*value, &ty, target);
+                    self.check_const(*value, &ty, target, None);
+                    // A constant that doesn't fit its own type has overflowed, which
+                    // isn't a conversion's doing.
+                    if ty.is_integer() && target.is_integer() && ty != *target.unqualified() {
+                        if let Ok(constant) = const_eval(&program.ast[*value], &ConstEnv::new(&program.ast)) {
+                            if changes_value(constant, target) {
+                                self.errors.push(TypeError::NarrowingConversion {
+                                    from: ty,
+                                    to: target.unqualified().clone(),
+                                    value: constant,
+                                    converted: convert(constant, target).unwrap(),
+                                    span,
+                                });
+                            }
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl<'a> Visitor for Checker<'a> {
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        self.return_type = function.return_type.clone();
+        visit::walk_function(self, ast, function);
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        let Declaration { ref ty, ref initializer, .. } = ast[declaration];
+        if let Some(ref initializer) = *initializer {
+            self.initializer(ty, initializer);
+        }
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        match ast[statement].kind {
+            StatementKind::Return(value) => {
+                if let Some(ty) = self.value(value) {
+                    let return_type = self.return_type.clone();
+                    self.convert(value, &ty, &return_type);
+                    if !assignable(&self.return_type, &ty, self.is_null(value)) {
+                        let return_type = self.return_type.unqualified().clone();
+                        let span = ast[value].span;
+                        self.errors.push(TypeError::IncompatibleReturn { return_type, value: ty, span });
+                    } else {
+                        self.check_const(value, &ty, &return_type, None);
+                    }
+                }
+            },
+            StatementKind::If(condition, then, otherwise) => {
+                self.condition(condition);
+                self.visit_stmt(ast, then);
+                if let Some(otherwise) = otherwise {
+                    self.visit_stmt(ast, otherwise);
+                }
+            },
+            StatementKind::While(condition, body) => {
+                self.condition(condition);
+                self.visit_stmt(ast, body);
+            },
+            StatementKind::DoWhile(body, condition) => {
+                self.visit_stmt(ast, body);
+                self.condition(condition);
+            },
+            StatementKind::Switch(value, body) => {
+                if let Some(ty) = self.value(value) {
+                    if ty.is_integer() {
+                        self.convert(value, &ty, &promote(&ty));
+                    } else {
+                        self.errors.push(TypeError::SwitchNotInteger { ty, span: ast[value].span });
+                    }
+                }
+                self.visit_stmt(ast, body);
+            },
+            StatementKind::For { ref init, condition, post, body } => {
+                match *init {
+                    ForInit::Declaration(ref declarations) => {
+                        for &declaration in declarations {
+                            self.visit_declaration(ast, declaration);
+                        }
+                    },
+                    ForInit::Expression(Some(init)) => {
+                        self.value(init);
+                    },
+                    ForInit::Expression(None) => {},
+                }
+                if let Some(condition) = condition {
+                    self.condition(condition);
+                }
+                if let Some(post) = post {
+                    self.value(post);
+                }
+                self.visit_stmt(ast, body);
+            },
+            _ => visit::walk_stmt(self, ast, statement),
+        }
+    }
+
+    fn visit_expr(&mut self, _ast: &Ast, expression: ExprId) {
+        self.value(expression);
+    }
+}
+
+/// Types every expression in `program`, whose names are resolved by
+/// `resolutions`, returning the types and implicit conversions along with every
+/// type error in source order.
+pub fn check(program: &Program, resolutions: &Resolutions) -> (Types, Conversions, Vec<TypeError>) {
+    let structs = program.items.iter()
+        .filter_map(|item| match *item {
+            TopLevel::Struct(ref definition) => Some((definition.name.as_str(), &definition.members[..])),
+            _ => None,
+        })
+        .collect();
+    let mut checker = Checker {
+        program,
+        resolutions,
+        structs,
+        return_type: Type::Int,
+        types: HashMap::new(),
+        conversions: HashMap::new(),
+        errors: Vec::new(),
+    };
+    checker.visit_program(program);
+
+    let mut errors = checker.errors;
+    errors.sort_by_key(|error| error.span().start);
+    (checker.types, checker.conversions, errors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+    use resolve::resolve;
+
+    fn check_source(source: &str) -> (Program, Types, Vec<TypeError>) {
+        let (program, types, _, errors) = check_conversions(source);
+        (program, types, errors)
+    }
+
+    fn check_conversions(source: &str) -> (Program, Types, Conversions, Vec<TypeError>) {
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let (resolutions, errors) = resolve(&program);
+        assert_eq!(errors, vec![]);
+        let (types, conversions, errors) = check(&program, &resolutions);
+        (program, types, conversions, errors)
+    }
+
+    fn errors(source: &str) -> Vec<String> {
+        check_source(source).2.iter().map(|error| error.to_string()).collect()
+    }
+
+    /// Checks `expression` as a statement on line 2, column 5, with some
+    /// variables of each type in scope.
+    fn expression_errors(expression: &str) -> Vec<String> {
+        errors(&format!(
+            "struct s {{ int x; }}; int main() {{ int i; char c; int *p; int *q; char *s; int a[3]; struct s v; struct s *sp;\n    {};\n}}",
+            expression,
+        ))
+    }
+
+    #[test]
+    fn valid_operands() {
+        for expression in &[
+            "i + c", "p + i", "i + p", "p - i", "p - q", "a + 1", "*a", "a[i]", "i[a]", "c[p]",
+            "p == q", "p == 0", "0 != p", "p < q", "p && i", "!p", "-c", "~i", "i % c", "i << 2",
+            "*p = 1", "p = a", "p = 0", "p += 1", "c *= i", "i ? p : 0", "i ? c : i", "(char *)p", "(int)p",
+            "v.x", "sp->x", "(&v)->x", "&v", "v = *sp", "sizeof(int)", "i, p", "p++", "--c", "s = \"hi\"",
+        ] {
+            assert_eq!(expression_errors(expression), Vec::<String>::new(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn invalid_operands() {
+        for &(expression, error) in &[
+            ("p + q", "invalid operands to binary `+` (`int*` and `int*`)"),
+            ("p - s", "invalid operands to binary `-` (`int*` and `char*`)"),
+            ("i - p", "invalid operands to binary `-` (`int` and `int*`)"),
+            ("p * 2", "invalid operands to binary `*` (`int*` and `int`)"),
+            ("i % p", "invalid operands to binary `%` (`int` and `int*`)"),
+            ("p < i", "invalid operands to binary `<` (`int*` and `int`)"),
+            ("p == 1", "invalid operands to binary `==` (`int*` and `int`)"),
+            ("v + 1", "invalid operands to binary `+` (`struct s` and `int`)"),
+            ("v && i", "invalid operands to binary `&&` (`struct s` and `int`)"),
+            ("p += q", "invalid operands to binary `+=` (`int*` and `int*`)"),
+            ("*i", "invalid operand to unary `*` (`int`)"),
+            ("-p", "invalid operand to unary `-` (`int*`)"),
+            ("!v", "invalid operand to unary `!` (`struct s`)"),
+            ("a++", "invalid operand to unary `++` (`int[3]`)"),
+            ("i = p", "cannot assign `int*` to `int`"),
+            ("p = s", "cannot assign `char*` to `int*`"),
+            ("p = 1", "cannot assign `int` to `int*`"),
+            ("i = v", "cannot assign `struct s` to `int`"),
+            ("i ? p : s", "branches of conditional have incompatible types (`int*` and `char*`)"),
+            ("i ? v : 1", "branches of conditional have incompatible types (`struct s` and `int`)"),
+            ("(int)v", "cannot cast `struct s` to `int`"),
+            ("i[c]", "cannot subscript `int` with `char`"),
+            ("v.y", "`struct s` has no member `y`"),
+            ("i.x", "member access with `.` on `int`"),
+            ("v->x", "member access with `->` on `struct s`"),
+            ("i(1)", "called object `i` has type `int`, which isn't a function"),
+            ("main + 1", "function `main` used as a value"),
+        ] {
+            assert_eq!(expression_errors(expression), vec![format!("{} at 2:5", error)], "{}", expression);
+        }
+    }
+
+    #[test]
+    fn lvalues() {
+        for expression in &[
+            "i = 1", "*p = 1", "a[i] = 1", "i[a] = 2", "v.x = 1", "sp->x = 1", "(*sp).x = 1", "*(p + 1) = 1",
+            "&i", "&a[1]", "&*p", "&v.x", "&sp->x", "&a", "&\"hi\"", "i++", "++*p", "a[0]--", "--v.x", "c += 1",
+        ] {
+            assert_eq!(expression_errors(expression), Vec::<String>::new(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn non_lvalues() {
+        for &(expression, error) in &[
+            ("1 = i", "cannot assign to this expression: integer literal at 2:5"),
+            ("'a' = c", "cannot assign to this expression: character constant at 2:5"),
+            ("i + 1 = 2", "cannot assign to this expression: result of binary `+` at 2:5"),
+            ("-i = 1", "cannot assign to this expression: result of unary `-` at 2:5"),
+            ("&i = p", "cannot assign to this expression: result of unary `&` at 2:5"),
+            ("i++ = 1", "cannot assign to this expression: result of `++` at 2:5"),
+            ("(i = 1) = 2", "cannot assign to this expression: result of assignment at 2:6"),
+            ("(i ? i : c) = 1", "cannot assign to this expression: conditional expression at 2:6"),
+            ("main() = 1", "cannot assign to this expression: result of call to `main` at 2:5"),
+            ("(int)c = 1", "cannot assign to this expression: cast at 2:5"),
+            ("sizeof(int) = 1", "cannot assign to this expression: `sizeof` expression at 2:5"),
+            ("(i, c) += 1", "cannot assign to this expression: comma expression at 2:6"),
+            ("(v = *sp).x = 1", "cannot assign to this expression: member of result of assignment at 2:5"),
+            ("&(i + 1)", "cannot take the address of this expression: result of binary `+` at 2:7"),
+            ("&1", "cannot take the address of this expression: integer literal at 2:6"),
+            ("++(i + 1)", "cannot increment this expression: result of binary `+` at 2:8"),
+            ("i++++", "cannot increment this expression: result of `++` at 2:5"),
+            ("--'a'", "cannot decrement this expression: character constant at 2:7"),
+        ] {
+            assert_eq!(expression_errors(expression), vec![error], "{}", expression);
+        }
+    }
+
+    #[test]
+    fn const_objects_cannot_be_modified() {
+        let source = "\
+struct point { int x; const int y; };
+int main(const int n, const int *p, int *const q, const struct point *sp) {
+    const int limit = 10;
+    const struct point origin = { 0, 0 };
+    struct point here;
+    limit = 1;
+    n++;
+    *p = 1;
+    p[1] += 2;
+    sp->x = 3;
+    origin.x = 4;
+    here.y = 5;
+    q = 0;
+    *q = 6;
+    here.x = 7;
+    p = q;
+    return limit + n;
+}";
+        assert_eq!(errors(source), vec![
+            "cannot assign to `limit`, which has const-qualified type `const int` at 6:5",
+            "cannot increment `n`, which has const-qualified type `const int` at 7:5",
+            "cannot assign to `*p`, which has const-qualified type `const int` at 8:5",
+            "cannot assign to `p[1]`, which has const-qualified type `const int` at 9:5",
+            "cannot assign to `sp->x`, which has const-qualified type `const int` at 10:5",
+            "cannot assign to `origin.x`, which has const-qualified type `const int` at 11:5",
+            "cannot assign to `here.y`, which has const-qualified type `const int` at 12:5",
+            "cannot assign to `q`, which has const-qualified type `int* const` at 13:5",
+        ]);
+        // Each points at the declaration of the variable it is made through.
+        let declarations: Vec<_> = check_source(source).2.iter().flat_map(TypeError::labels).collect();
+        assert_eq!(declarations, vec![
+            ((3, 15), "declared here"),
+            ((2, 10), "declared here"),
+            ((2, 23), "declared here"),
+            ((2, 23), "declared here"),
+            ((2, 51), "declared here"),
+            ((4, 24), "declared here"),
+            ((5, 18), "declared here"),
+            ((2, 37), "declared here"),
+        ]);
+    }
+
+    #[test]
+    fn pointers_to_const_cannot_become_pointers_to_non_const() {
+        let source = "\
+int length(char *s);
+int print(const char *s);
+char *name(const char *s) {
+    char *copy = s;
+    const char *view = copy;
+    copy = view;
+    print(copy);
+    length(s);
+    return s;
+}";
+        let (_, _, errors) = check_source(source);
+        assert!(errors.iter().all(|error| error.severity() == Severity::Warning));
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(messages, vec![
+            "conversion from `const char*` to `char*` discards the `const` qualifier of the pointee at 4:18",
+            "conversion from `const char*` to `char*` discards the `const` qualifier of the pointee at 6:12",
+            "conversion from `const char*` to `char*` discards the `const` qualifier of the pointee at 8:12",
+            "conversion from `const char*` to `char*` discards the `const` qualifier of the pointee at 9:12",
+        ]);
+        assert_eq!(errors[2].labels(), vec![((1, 12), "the parameter is declared here")]);
+    }
+
+    #[test]
+    fn const_objects_can_be_initialized_and_read() {
+        let source = "\
+struct point { int x; const int y; };
+int main() {
+    const int n = 1;
+    const struct point origin = { 0, 0 };
+    const int *p = &n;
+    const char *s = \"hi\";
+    p++;
+    return n + *p + origin.y + s[0];
+}";
+        assert_eq!(errors(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn errors_are_not_repeated_by_enclosing_expressions() {
+        assert_eq!(expression_errors("((p + q) * 2)[a] = *i"), vec![
+            "invalid operands to binary `+` (`int*` and `int*`) at 2:7",
+            "invalid operand to unary `*` (`int`) at 2:24",
+        ]);
+    }
+
+    #[test]
+    fn pointer_and_integer_conversions_need_a_cast() {
+        // Converting between pointers and integers implicitly is an error rather
+        // than a warning; only the constant 0 converts to a pointer unasked.
+        let source = "int main() { int *p = 0; int i = p; p = i; i = (int)p; p = (int *)i; return p == 0; }";
+        assert_eq!(errors(source), vec![
+            "cannot initialize `int` with `int*` at 1:34",
+            "cannot assign `int` to `int*` at 1:37",
+        ]);
+    }
+
+    #[test]
+    fn return_types() {
+        let source = "\
+struct s { int x; };
+int *f(int *p) { return 1; }
+int g(int *p) { return p; }
+char h(int *p) { return *p; }
+int *n() { return 0; }
+struct s copy(struct s *v) { return *v; }
+struct s bad(struct s *v) { return v; }";
+        assert_eq!(errors(source), vec![
+            "cannot return `int` from a function returning `int*` at 2:25",
+            "cannot return `int*` from a function returning `int` at 3:24",
+            "cannot return `struct s*` from a function returning `struct s` at 7:36",
+        ]);
+    }
+
+    #[test]
+    fn conditions_must_be_scalar() {
+        let source = "struct s { int x; }; int main(struct s v) { if (v) ; for (; v.x; ) ; do ; while (v); return 0; }";
+        assert_eq!(errors(source), vec![
+            "condition has type `struct s`, which isn't a scalar at 1:49",
+            "condition has type `struct s`, which isn't a scalar at 1:82",
+        ]);
+        let source = "int main(int *p, char c) { switch (p) { case 0: ; } switch (c) default: ; return 0; }";
+        assert_eq!(errors(source), vec!["switch value has type `int*`, which isn't an integer at 1:36"]);
+    }
+
+    #[test]
+    fn initializers() {
+        let source = "\
+struct s { int x; char *name; };
+int a[2] = {1, 0};
+char text[4] = \"abc\";
+int *p[2] = {0, a};
+struct s v = {1, \"x\"};
+int *q = {a};
+struct s w = {1, 2};";
+        assert_eq!(errors(source), vec!["cannot initialize `char*` with `int` at 7:18"]);
+    }
+
+    #[test]
+    fn calls_match_declarations() {
+        let source = "\
+int f(int a, char *b);
+int printf(const char *format, ...);
+int main() {
+    char *s;
+    f(1, s);
+    f(1);
+    f(1, s, 2);
+    f(s, 0);
+    printf(\"%d\\n\");
+    printf(\"%d %s\\n\", 1, s);
+    return printf();
+}";
+        assert_eq!(errors(source), vec![
+            "`f` takes 2 arguments, but 1 was given at 6:5",
+            "`f` takes 2 arguments, but 3 were given at 7:5",
+            "cannot pass `char*` as argument 1 of `f`, which takes `int` at 8:7",
+            "`printf` takes at least 1 argument, but 0 were given at 11:12",
+        ]);
+        let labels: Vec<_> = check_source(source).2.iter().flat_map(TypeError::labels).collect();
+        assert_eq!(labels, vec![
+            ((1, 1), "declared here"),
+            ((1, 1), "declared here"),
+            ((1, 7), "the parameter is declared here"),
+            ((2, 1), "declared here"),
+        ]);
+    }
+
+    #[test]
+    fn calls_to_undeclared_functions_warn() {
+        let (_, types, errors) = check_source("int main() { return g(1, 2) + 1; }");
+        assert_eq!(errors, vec![TypeError::ImplicitDeclaration { name: "g".to_owned(), span: Span::default() }]);
+        assert_eq!(errors[0].severity(), Severity::Warning);
+        assert_eq!(errors[0].to_string(), "implicit declaration of function `g` at 1:21 (assumed to be `int g()`, which takes any arguments)");
+        assert!(types.values().all(|ty| *ty == Type::Int));
+    }
+
+    /// The type of `expression`, checked as a statement with a variable of each
+    /// integer type in scope, and the conversions of its subexpressions, by source.
+    fn conversions(expression: &str) -> (String, Vec<(String, String)>) {
+        let source = format!(
+            "int main() {{ char c; int i; unsigned u; long l; unsigned long ul; int *p;\n    {};\n}}",
+            expression,
+        );
+        let (program, types, conversions, errors) = check_conversions(&source);
+        assert_eq!(errors, vec![], "{}", expression);
+        let text = |id: ExprId| {
+            let span = program.ast[id].span;
+            source[span.start..span.end].to_owned()
+        };
+        let whole = types.keys().cloned().max_by_key(|&id| program.ast[id].span.end - program.ast[id].span.start).unwrap();
+        let mut converted: Vec<(ExprId, &Type)> = conversions.iter().map(|(&id, ty)| (id, ty)).collect();
+        converted.sort_by_key(|&(id, _)| program.ast[id].span.start);
+        (types[&whole].to_string(), converted.into_iter().map(|(id, ty)| (text(id), ty.to_string())).collect())
+    }
+
+    fn converted(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|&(expression, ty)| (expression.to_owned(), ty.to_owned())).collect()
+    }
+
+    #[test]
+    fn integer_promotions() {
+        assert_eq!(conversions("c + c"), ("int".to_owned(), converted(&[("c", "int"), ("c", "int")])));
+        assert_eq!(conversions("-c"), ("int".to_owned(), converted(&[("c", "int")])));
+        assert_eq!(conversions("c << l"), ("int".to_owned(), converted(&[("c", "int")])));
+        assert_eq!(conversions("!c"), ("int".to_owned(), converted(&[])));
+        assert_eq!(conversions("c++"), ("char".to_owned(), converted(&[])));
+    }
+
+    #[test]
+    fn usual_arithmetic_conversions() {
+        assert_eq!(conversions("l + i"), ("long".to_owned(), converted(&[("i", "long")])));
+        assert_eq!(conversions("c * l"), ("long".to_owned(), converted(&[("c", "long")])));
+        assert_eq!(conversions("i / u"), ("unsigned int".to_owned(), converted(&[("i", "unsigned int")])));
+        assert_eq!(conversions("u - l"), ("long".to_owned(), converted(&[("u", "long")])));
+        assert_eq!(conversions("ul % l"), ("unsigned long".to_owned(), converted(&[("l", "unsigned long")])));
+        assert_eq!(conversions("i ? u : c"), ("unsigned int".to_owned(), converted(&[("c", "unsigned int")])));
+        assert_eq!(conversions("p + c"), ("int*".to_owned(), converted(&[])));
+        assert_eq!(conversions("p - p"), ("long".to_owned(), converted(&[])));
+    }
+
+    #[test]
+    fn comparisons_convert_operands_but_give_int() {
+        // `-1` becomes a large unsigned value here, so `-1 < 1u` is false.
+        assert_eq!(conversions("u < i"), ("int".to_owned(), converted(&[("i", "unsigned int")])));
+        assert_eq!(conversions("l == u"), ("int".to_owned(), converted(&[("u", "long")])));
+        assert_eq!(conversions("c && ul"), ("int".to_owned(), converted(&[])));
+    }
+
+    #[test]
+    fn values_convert_to_their_destination() {
+        assert_eq!(conversions("l = c"), ("long".to_owned(), converted(&[("c", "long")])));
+        assert_eq!(conversions("c = l"), ("char".to_owned(), converted(&[("l", "char")])));
+        assert_eq!(conversions("c += i"), ("char".to_owned(), converted(&[])));
+        assert_eq!(conversions("i += c"), ("int".to_owned(), converted(&[("c", "int")])));
+        assert_eq!(conversions("i += l"), ("int".to_owned(), converted(&[])));
+        assert_eq!(conversions("u <<= c"), ("unsigned int".to_owned(), converted(&[("c", "int")])));
+
+        let source = "long f(long x, ...); int main() { char c; int i = c; return f(i, c); }";
+        let (program, _, conversions, _) = check_conversions(source);
+        let mut converted: Vec<(usize, String)> = conversions.iter()
+            .map(|(&id, ty)| (program.ast[id].span.start, ty.to_string()))
+            .collect();
+        converted.sort();
+        assert_eq!(converted, vec![
+            (source.find("c; return").unwrap(), "int".to_owned()),
+            (source.find("f(i").unwrap(), "int".to_owned()),
+            (source.find("i, c").unwrap(), "long".to_owned()),
+            (source.find("c);").unwrap(), "int".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn narrowing_constant_initializers_warn() {
+        let source = "\
+char c = 300;
+char d = 'a' + 1;
+unsigned u = -1;
+unsigned v = 65536 * 65536;
+int i = (long)65536 * 65536 + 1;
+long l = 2147483647 + 1;
+int main() { char e = 128; char f = -128; int g = f; return e + f + g; }";
+        let (_, _, errors) = check_source(source);
+        assert!(errors.iter().all(|error| error.severity() == Severity::Warning));
+        assert_eq!(errors.iter().map(|error| error.to_string()).collect::<Vec<_>>(), vec![
+            "implicit conversion from `int` to `char` changes value from 300 to 44 at 1:10",
+            "implicit conversion from `int` to `unsigned int` changes value from 4294967296 to 0 at 4:14",
+            "implicit conversion from `long` to `int` changes value from 4294967297 to 1 at 5:9",
+            "implicit conversion from `int` to `char` changes value from 128 to -128 at 7:23",
+        ]);
+    }
+
+    #[test]
+    fn recorded_types_keep_arrays_and_qualifiers() {
+        let source = "int main() { const int a[2]; char *s; return a[0] + *s + \"hi\"[1]; }";
+        let (program, types, errors) = check_source(source);
+        assert_eq!(errors, vec![]);
+
+        let mut recorded: Vec<(Span, String)> = types.iter()
+            .map(|(&id, ty)| (program.ast[id].span, ty.to_string()))
+            .collect();
+        recorded.sort_by_key(|&(span, _)| (span.start, span.end));
+        let recorded: Vec<(&str, &str)> = recorded.iter()
+            .map(|&(span, ref ty)| (&source[span.start..span.end], ty.as_str()))
+            .collect();
+        assert_eq!(recorded, vec![
+            ("a", "const int[2]"),
+            ("a[0]", "const int"),
+            ("a[0] + *s", "int"),
+            ("a[0] + *s + \"hi\"[1]", "int"),
+            ("0", "int"),
+            ("*s", "char"),
+            ("s", "char*"),
+            ("\"hi\"", "char[3]"),
+            ("\"hi\"[1]", "char"),
+            ("1", "int"),
+        ]);
+    }
+
+    #[test]
+    fn out_of_range_shift_amounts_warn() {
+        let source = "int main(int i, char c, long l) {\n    i = i << 31 >> 32;\n    l = c << -1;\n    l = l << 63 >> 64;\n    i <<= 40;\n    return i >> i;\n}";
+        let (_, _, errors) = check_source(source);
+        assert!(errors.iter().all(|error| error.severity() == Severity::Warning));
+        assert_eq!(errors.iter().map(|error| error.to_string()).collect::<Vec<_>>(), vec![
+            "shift amount 32 is at least the width of `int` at 2:20",
+            "shift amount -1 is negative, shifting `int` at 3:14",
+            "shift amount 64 is at least the width of `long` at 4:20",
+            "shift amount 40 is at least the width of `int` at 5:11",
+        ]);
+    }
+}
@@ -0,0 +1,396 @@
+//! Finds reads of local variables that may not have been assigned yet.
+//!
+//! Each function body is walked once in execution order, tracking the scalar
+//! locals that may still be unassigned. Where control paths join, after an
+//! `if`, a loop, or at a label, a variable is unassigned if it is on any of
+//! them. A loop body is walked once: a variable declared outside the loop and
+//! assigned in it is assigned after the loop only on the paths that leave it
+//! through the body, and a read in the body before the assignment warns.
+//! `goto`s are followed forward but not backward.
+//!
+//! A variable whose address is taken may be assigned through the pointer, so
+//! it is never warned about. Neither are arrays and structs, which may be
+//! assigned an element or member at a time.
+
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use ast::*;
+use ast::visit::{self, Visitor};
+use flow::always_true;
+use resolve::{Resolutions, Symbol};
+use semantic::SemanticError;
+
+/// The variables that may be unassigned at a point in a function. Those at a
+/// point control can't reach are none.
+type State = HashSet<DeclId>;
+
+/// Finds the variables whose address is taken in a function.
+struct AddressTaken<'a> {
+    resolutions: &'a Resolutions,
+    escaped: HashSet<DeclId>,
+}
+
+impl<'a> Visitor for AddressTaken<'a> {
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        if let ExpressionKind::Unary(UnaryOp::AddressOf, operand) = ast[expression].kind {
+            if let Some(&Symbol::Variable(declaration)) = self.resolutions.get(&operand) {
+                self.escaped.insert(declaration);
+            }
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// The `switch` being walked, innermost of those around the statement.
+struct Switch {
+    /// The state its `case` and `default` labels are jumped to from.
+    entry: State,
+    has_default: bool,
+}
+
+struct Checker<'a> {
+    resolutions: &'a Resolutions,
+    /// The variables whose address is taken in the function being checked.
+    escaped: HashSet<DeclId>,
+    /// The variables already warned about, which aren't warned about again.
+    warned: HashSet<DeclId>,
+    state: State,
+    /// The states at the `break`s of each loop and `switch` around the
+    /// statement being walked, innermost last.
+    breaks: Vec<State>,
+    /// The states at the `continue`s of each loop around it.
+    continues: Vec<State>,
+    switches: Vec<Switch>,
+    /// The states at the `goto`s walked so far, by label.
+    gotos: HashMap<String, State>,
+    errors: Vec<SemanticError>,
+}
+
+impl<'a> Checker<'a> {
+    fn read(&mut self, ast: &Ast, expression: ExprId) {
+        if let Some(&Symbol::Variable(declaration)) = self.resolutions.get(&expression) {
+            if self.state.contains(&declaration) && !self.escaped.contains(&declaration) && self.warned.insert(declaration) {
+                let span = ast[expression].span;
+                self.errors.push(SemanticError::MaybeUninitialized {
+                    name: ast[declaration].name.clone(),
+                    line: span.line,
+                    column: span.column,
+                });
+            }
+        }
+    }
+
+    /// Walks `statement`, a loop body, and returns the states at its `break`s
+    /// and `continue`s.
+    fn loop_body(&mut self, ast: &Ast, statement: StmtId) -> (State, State) {
+        self.breaks.push(State::new());
+        self.continues.push(State::new());
+        self.visit_stmt(ast, statement);
+        (self.breaks.pop().unwrap(), self.continues.pop().unwrap())
+    }
+
+    /// Sets the state after a loop, which control leaves when `condition` is
+    /// false, if it can be, or through a `break`.
+    fn leave_loop(&mut self, ast: &Ast, condition: Option<ExprId>, tested: State, breaks: State) {
+        self.state = match condition {
+            Some(condition) if !always_true(ast, condition) => tested,
+            _ => State::new(),
+        };
+        self.state.extend(breaks);
+    }
+}
+
+impl<'a> Visitor for Checker<'a> {
+    fn visit_program(&mut self, program: &Program) {
+        for item in &program.items {
+            if let TopLevel::Function(ref function) = *item {
+                self.visit_function(&program.ast, function);
+            }
+        }
+    }
+
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        if let Some(ref body) = function.body {
+            let mut finder = AddressTaken { resolutions: self.resolutions, escaped: HashSet::new() };
+            finder.visit_block(ast, body);
+            self.escaped = finder.escaped;
+            self.state.clear();
+            self.gotos.clear();
+            self.visit_block(ast, body);
+        }
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        let Declaration { storage, ref ty, ref initializer, .. } = ast[declaration];
+        // The variable is in scope, and unassigned, in its own initializer.
+        if storage.is_none() && ty.is_scalar() {
+            self.state.insert(declaration);
+        }
+        visit::walk_declaration(self, ast, declaration);
+        if initializer.is_some() {
+            self.state.remove(&declaration);
+        }
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        match ast[statement].kind {
+            StatementKind::Return(_) => {
+                visit::walk_stmt(self, ast, statement);
+                self.state.clear();
+            },
+            StatementKind::Break => {
+                let state = mem::take(&mut self.state);
+                if let Some(breaks) = self.breaks.last_mut() {
+                    breaks.extend(state);
+                }
+            },
+            StatementKind::Continue => {
+                let state = mem::take(&mut self.state);
+                if let Some(continues) = self.continues.last_mut() {
+                    continues.extend(state);
+                }
+            },
+            StatementKind::Goto(ref label) => {
+                let state = mem::take(&mut self.state);
+                self.gotos.entry(label.clone()).or_default().extend(state);
+            },
+            StatementKind::Labeled(ref label, body) => {
+                if let Some(state) = self.gotos.get(label) {
+                    self.state.extend(state.iter().cloned());
+                }
+                self.visit_stmt(ast, body);
+            },
+            // The label's value is a constant, which reads no variables.
+            StatementKind::Case(_, body) | StatementKind::Default(body) => {
+                if let Some(switch) = self.switches.last_mut() {
+                    self.state.extend(switch.entry.iter().cloned());
+                    if let StatementKind::Default(_) = ast[statement].kind {
+                        switch.has_default = true;
+                    }
+                }
+                self.visit_stmt(ast, body);
+            },
+            StatementKind::Switch(value, body) => {
+                self.visit_expr(ast, value);
+                // Control only enters the body at a label.
+                let entry = mem::take(&mut self.state);
+                self.switches.push(Switch { entry, has_default: false });
+                self.breaks.push(State::new());
+                self.visit_stmt(ast, body);
+                let breaks = self.breaks.pop().unwrap();
+                let switch = self.switches.pop().unwrap();
+                self.state.extend(breaks);
+                // Without a `default`, a value no `case` matches skips the body.
+                if !switch.has_default {
+                    self.state.extend(switch.entry);
+                }
+            },
+            StatementKind::If(condition, then, otherwise) => {
+                self.visit_expr(ast, condition);
+                let entry = self.state.clone();
+                self.visit_stmt(ast, then);
+                let after_then = mem::replace(&mut self.state, entry);
+                if let Some(otherwise) = otherwise {
+                    self.visit_stmt(ast, otherwise);
+                }
+                self.state.extend(after_then);
+            },
+            StatementKind::While(condition, body) => {
+                self.visit_expr(ast, condition);
+                let tested = self.state.clone();
+                let (breaks, _) = self.loop_body(ast, body);
+                self.leave_loop(ast, Some(condition), tested, breaks);
+            },
+            StatementKind::DoWhile(body, condition) => {
+                let (breaks, continues) = self.loop_body(ast, body);
+                self.state.extend(continues);
+                self.visit_expr(ast, condition);
+                let tested = mem::take(&mut self.state);
+                self.leave_loop(ast, Some(condition), tested, breaks);
+            },
+            StatementKind::For { ref init, condition, post, body } => {
+                match *init {
+                    ForInit::Declaration(ref declarations) => {
+                        for &declaration in declarations {
+                            self.visit_declaration(ast, declaration);
+                        }
+                    },
+                    ForInit::Expression(init) => init.into_iter().for_each(|init| self.visit_expr(ast, init)),
+                }
+                if let Some(condition) = condition {
+                    self.visit_expr(ast, condition);
+                }
+                let tested = self.state.clone();
+                let (breaks, continues) = self.loop_body(ast, body);
+                self.state.extend(continues);
+                if let Some(post) = post {
+                    self.visit_expr(ast, post);
+                }
+                self.leave_loop(ast, condition, tested, breaks);
+            },
+            _ => visit::walk_stmt(self, ast, statement),
+        }
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        match ast[expression].kind {
+            ExpressionKind::Variable(_) => self.read(ast, expression),
+            ExpressionKind::Assign(None, target, value) if matches!(ast[target].kind, ExpressionKind::Variable(_)) => {
+                self.visit_expr(ast, value);
+                if let Some(&Symbol::Variable(declaration)) = self.resolutions.get(&target) {
+                    self.state.remove(&declaration);
+                }
+            },
+            // Taking an address doesn't read the variable.
+            ExpressionKind::Unary(UnaryOp::AddressOf, operand) if matches!(ast[operand].kind, ExpressionKind::Variable(_)) => {},
+            // Assignments in the right operand may not happen.
+            ExpressionKind::Binary(BinaryOp::LogicalAnd, lhs, rhs) | ExpressionKind::Binary(BinaryOp::LogicalOr, lhs, rhs) => {
+                self.visit_expr(ast, lhs);
+                let state = self.state.clone();
+                self.visit_expr(ast, rhs);
+                self.state.extend(state);
+            },
+            ExpressionKind::Conditional(condition, then, otherwise) => {
+                self.visit_expr(ast, condition);
+                let entry = self.state.clone();
+                self.visit_expr(ast, then);
+                let after_then = mem::replace(&mut self.state, entry);
+                self.visit_expr(ast, otherwise);
+                self.state.extend(after_then);
+            },
+            _ => visit::walk_expr(self, ast, expression),
+        }
+    }
+}
+
+/// Finds the first read of each local variable in `program` that may happen
+/// before it is assigned, using `resolutions` to tell what each use refers to.
+pub fn check(program: &Program, resolutions: &Resolutions) -> Vec<SemanticError> {
+    let mut checker = Checker {
+        resolutions,
+        escaped: HashSet::new(),
+        warned: HashSet::new(),
+        state: State::new(),
+        breaks: Vec::new(),
+        continues: Vec::new(),
+        switches: Vec::new(),
+        gotos: HashMap::new(),
+        errors: Vec::new(),
+    };
+    checker.visit_program(program);
+    checker.errors
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+    use resolve::resolve;
+
+    /// The names of the variables warned about, and where.
+    fn check_source(source: &str) -> Vec<(String, usize, usize)> {
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let (resolutions, errors) = resolve(&program);
+        assert_eq!(errors, vec![]);
+        check(&program, &resolutions).into_iter()
+            .map(|error| match error {
+                SemanticError::MaybeUninitialized { name, line, column } => (name, line, column),
+                error => panic!("expected an uninitialized read, found {:?}", error),
+            })
+            .collect()
+    }
+
+    fn warned(name: &str, line: usize, column: usize) -> Vec<(String, usize, usize)> {
+        vec![(name.to_owned(), line, column)]
+    }
+
+    #[test]
+    fn reads_before_any_assignment() {
+        assert_eq!(check_source("int main() {\n    int x;\n    return x;\n}"), warned("x", 3, 12));
+        assert_eq!(check_source("int main() {\n    int x = x + 1;\n    return x;\n}"), warned("x", 2, 13));
+        assert_eq!(check_source("int main() { int x; x = 1; return x; }"), vec![]);
+        assert_eq!(check_source("int main() { int x = 1; return x; }"), vec![]);
+    }
+
+    #[test]
+    fn only_the_first_read_warns() {
+        assert_eq!(check_source("int main() {\n    int x, y;\n    y = x + x;\n    return x + y;\n}"), warned("x", 3, 9));
+    }
+
+    #[test]
+    fn assignment_in_one_branch() {
+        let source = "int main(int c) {\n    int x;\n    if (c)\n        x = 1;\n    return x;\n}";
+        assert_eq!(check_source(source), warned("x", 5, 12));
+        let source = "int main(int c) {\n    int x;\n    if (c)\n        return 0;\n    else\n        x = 1;\n    return x;\n}";
+        assert_eq!(check_source(source), vec![]);
+    }
+
+    #[test]
+    fn assignment_in_both_branches() {
+        assert_eq!(check_source("int main(int c) { int x; if (c) x = 1; else x = 2; return x; }"), vec![]);
+        assert_eq!(check_source("int main(int c) { int x; c ? (x = 1) : (x = 2); return x; }"), vec![]);
+        assert_eq!(check_source("int main(int c) { int x; if ((x = c) && c) return x; return 0; }"), vec![]);
+
+        // Only the left operand of `&&` is sure to be evaluated.
+        let source = "int main(int c) {\n    int x;\n    if (c && (x = 1))\n        return 0;\n    return x;\n}";
+        assert_eq!(check_source(source), warned("x", 5, 12));
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(check_source("int main(int n) { int i, total; total = 0; for (i = 0; i < n; i++) total += i; return total; }"), vec![]);
+        assert_eq!(check_source("int main(int n) { int x; do x = n--; while (n); return x; }"), vec![]);
+        assert_eq!(check_source("int main(int n) { int x; while (1) { x = n; if (x) break; } return x; }"), vec![]);
+        assert_eq!(check_source("int main(int n) { int x; for (;;) { if (n) continue; x = n; break; } return x; }"), vec![]);
+
+        // The loop may not run at all.
+        let source = "int main(int n) {\n    int x;\n    while (n--)\n        x = n;\n    return x;\n}";
+        assert_eq!(check_source(source), warned("x", 5, 12));
+        // Nor may the assignment on the way out of it.
+        let source = "int main(int n) {\n    int x;\n    do {\n        if (n) break;\n        x = 1;\n    } while (0);\n    return x;\n}";
+        assert_eq!(check_source(source), warned("x", 7, 12));
+    }
+
+    #[test]
+    fn variables_declared_in_loops_start_unassigned() {
+        let source = "int main(int n) {\n    while (n--) {\n        int x;\n        if (n) x = 1;\n        n += x;\n    }\n    return 0;\n}";
+        assert_eq!(check_source(source), warned("x", 5, 14));
+    }
+
+    #[test]
+    fn switches() {
+        let source = "int main(int c) { int x; switch (c) { case 1: x = 1; break; default: x = 2; } return x; }";
+        assert_eq!(check_source(source), vec![]);
+        let source = "int main(int c) {\n    int x;\n    switch (c) {\n    case 1:\n        x = 1;\n    }\n    return x;\n}";
+        assert_eq!(check_source(source), warned("x", 7, 12));
+        let source = "int main(int c) {\n    int x;\n    switch (c) {\n    case 1:\n        x = 1;\n    case 2:\n        return x;\n    }\n    return 0;\n}";
+        assert_eq!(check_source(source), warned("x", 7, 16));
+    }
+
+    #[test]
+    fn gotos() {
+        assert_eq!(check_source("int main(int c) { int x; x = 1; if (c) goto end; x = 2; end: return x; }"), vec![]);
+        let source = "int main(int c) {\n    int x;\n    if (c) goto end;\n    x = 2;\nend:\n    return x;\n}";
+        assert_eq!(check_source(source), warned("x", 6, 12));
+    }
+
+    #[test]
+    fn taking_the_address_suppresses_warnings() {
+        assert_eq!(check_source("int set(int *p);\nint main() { int x; set(&x); return x; }"), vec![]);
+        assert_eq!(check_source("int main() { int x; int y; y = x; int *p; p = &x; return y; }"), vec![]);
+    }
+
+    #[test]
+    fn aggregates_and_statics_are_not_tracked() {
+        assert_eq!(check_source("int main() { int a[2]; a[0] = 1; return a[0]; }"), vec![]);
+        assert_eq!(check_source("int main() { static int count; extern int total; return count + total; }"), vec![]);
+        assert_eq!(check_source("struct point { int x; };\nint main() { struct point p; p.x = 1; return p.x; }"), vec![]);
+    }
+
+    #[test]
+    fn writes_through_pointers_read_them() {
+        let source = "int main() {\n    int *p;\n    *p = 1;\n    return 0;\n}";
+        assert_eq!(check_source(source), warned("p", 3, 6));
+    }
+}
@@ -0,0 +1,213 @@
+//! Finds local variables and parameters whose values are never read.
+//!
+//! Assigning to a variable doesn't read it, and neither does a compound
+//! assignment or an increment whose result is thrown away, so a variable that
+//! is only ever assigned is as unused as one that is never mentioned. Taking a
+//! variable's address counts as reading it, since it may be read through the
+//! pointer. Names starting with `_` are exempt.
+
+use std::collections::HashSet;
+use ast::*;
+use ast::visit::{self, Visitor};
+use resolve::{Resolutions, Symbol};
+use semantic::SemanticError;
+
+/// A local variable or parameter that could go unused.
+struct Candidate {
+    symbol: Symbol,
+    name: String,
+    span: Span,
+    parameter: bool,
+}
+
+struct Finder<'a> {
+    resolutions: &'a Resolutions,
+    /// The index of the item being walked in the program's items.
+    item: usize,
+    /// Whether the walk is inside a function body.
+    local: bool,
+    candidates: Vec<Candidate>,
+    /// Expressions whose values are thrown away.
+    discarded: HashSet<ExprId>,
+    /// Uses of variables that only write to them.
+    writes: HashSet<ExprId>,
+    read: HashSet<Symbol>,
+    written: HashSet<Symbol>,
+}
+
+impl<'a> Finder<'a> {
+    fn candidate(&mut self, symbol: Symbol, name: &str, span: Span, parameter: bool) {
+        if !name.starts_with('_') {
+            self.candidates.push(Candidate { symbol, name: name.to_owned(), span, parameter });
+        }
+    }
+
+    /// Records that `target`'s only effect on the variable it names, if it is
+    /// one, is to write to it.
+    fn write(&mut self, ast: &Ast, target: ExprId) {
+        if let ExpressionKind::Variable(_) = ast[target].kind {
+            self.writes.insert(target);
+        }
+    }
+}
+
+impl<'a> Visitor for Finder<'a> {
+    fn visit_program(&mut self, program: &Program) {
+        for (index, item) in program.items.iter().enumerate() {
+            self.item = index;
+            self.visit_top_level(&program.ast, item);
+        }
+    }
+
+    fn visit_function(&mut self, ast: &Ast, function: &Function) {
+        if let Some(ref body) = function.body {
+            for (index, parameter) in function.parameters.iter().enumerate() {
+                let symbol = Symbol::Parameter { function: self.item, index };
+                self.candidate(symbol, &parameter.name, parameter.span, true);
+            }
+            self.local = true;
+            self.visit_block(ast, body);
+            self.local = false;
+        }
+    }
+
+    fn visit_declaration(&mut self, ast: &Ast, declaration: DeclId) {
+        let Declaration { ref name, span, storage, .. } = ast[declaration];
+        // An `extern` declaration refers to an object defined elsewhere, which
+        // may be used there.
+        if self.local && storage != Some(StorageClass::Extern) {
+            self.candidate(Symbol::Variable(declaration), name, span, false);
+        }
+        visit::walk_declaration(self, ast, declaration);
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, statement: StmtId) {
+        match ast[statement].kind {
+            StatementKind::Expression(Some(expression)) => {
+                self.discarded.insert(expression);
+            },
+            StatementKind::For { ref init, post, .. } => {
+                if let ForInit::Expression(Some(init)) = *init {
+                    self.discarded.insert(init);
+                }
+                self.discarded.extend(post);
+            },
+            _ => {},
+        }
+        visit::walk_stmt(self, ast, statement);
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expression: ExprId) {
+        let discarded = self.discarded.contains(&expression);
+        match ast[expression].kind {
+            ExpressionKind::Variable(_) => {
+                if let Some(&symbol) = self.resolutions.get(&expression) {
+                    if self.writes.contains(&expression) {
+                        self.written.insert(symbol);
+                    } else {
+                        self.read.insert(symbol);
+                    }
+                }
+            },
+            // A compound assignment reads the target, but only to work out the
+            // value written back.
+            ExpressionKind::Assign(operator, target, _) if operator.is_none() || discarded => self.write(ast, target),
+            ExpressionKind::Unary(UnaryOp::PreIncrement, operand)
+            | ExpressionKind::Unary(UnaryOp::PreDecrement, operand)
+            | ExpressionKind::Unary(UnaryOp::PostIncrement, operand)
+            | ExpressionKind::Unary(UnaryOp::PostDecrement, operand) if discarded => self.write(ast, operand),
+            ExpressionKind::Comma(ref expressions) => {
+                let (last, rest) = expressions.split_last().unwrap();
+                self.discarded.extend(rest);
+                if discarded {
+                    self.discarded.insert(*last);
+                }
+            },
+            _ => {},
+        }
+        visit::walk_expr(self, ast, expression);
+    }
+}
+
+/// Finds the local variables and parameters in `program` that are never read,
+/// using `resolutions` to tell what each use refers to.
+pub fn check(program: &Program, resolutions: &Resolutions) -> Vec<SemanticError> {
+    let mut finder = Finder {
+        resolutions,
+        item: 0,
+        local: false,
+        candidates: Vec::new(),
+        discarded: HashSet::new(),
+        writes: HashSet::new(),
+        read: HashSet::new(),
+        written: HashSet::new(),
+    };
+    finder.visit_program(program);
+
+    finder.candidates.iter()
+        .filter(|candidate| !finder.read.contains(&candidate.symbol))
+        .map(|candidate| SemanticError::UnusedVariable {
+            name: candidate.name.clone(),
+            line: candidate.span.line,
+            column: candidate.span.column,
+            parameter: candidate.parameter,
+            assigned: finder.written.contains(&candidate.symbol),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lexer::lex_str;
+    use parser::parse_program;
+    use resolve::resolve;
+
+    fn unused(source: &str) -> Vec<String> {
+        let program = parse_program(&lex_str(source).unwrap()).unwrap();
+        let (resolutions, errors) = resolve(&program);
+        assert_eq!(errors, vec![]);
+        check(&program, &resolutions).iter().map(|error| error.to_string()).collect()
+    }
+
+    #[test]
+    fn unused_locals_and_parameters() {
+        assert_eq!(unused("int f(int a, int b) {\n    int x;\n    int y = 1, z;\n    return b + z;\n}"), vec![
+            "unused parameter `a` at 1:7",
+            "unused variable `x` at 2:9",
+            "unused variable `y` at 3:9",
+        ]);
+    }
+
+    #[test]
+    fn write_only_locals_are_unused() {
+        let source = "int main() {\n    int x;\n    x = 1;\n    x += 2;\n    x++, --x;\n    for (x = 0; ; x++) ;\n}";
+        assert_eq!(unused(source), vec!["variable `x` is assigned but never read at 2:9"]);
+    }
+
+    #[test]
+    fn reads() {
+        // Each of these reads `x`: as an operand, through a compound assignment
+        // or increment whose value is used, or as the last of a comma expression.
+        for body in &["return x;", "int y; y = x; return y;", "int y; y = x++; return y;", "return x += 1;", "return (x = 1, x);", "return 0, x;", "if (x) ;"] {
+            assert_eq!(unused(&format!("int main() {{ int x; {} }}", body)), Vec::<String>::new(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn exemptions() {
+        // Underscored names, variables whose address is taken, `extern`
+        // declarations, globals, and the parameters of prototypes.
+        let source = "\
+int g;
+int f(int unused);
+int h(int _flags) {
+    int _unused;
+    int x;
+    int *p = &x;
+    extern int e;
+    return *p;
+}";
+        assert_eq!(unused(source), Vec::<String>::new());
+    }
+}
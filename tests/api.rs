@@ -0,0 +1,145 @@
+//! Compiles programs through the library's API, as a build script or a service
+//! would, and checks that the errors from it, and from each stage, compose.
+
+extern crate rust_cc;
+
+use std::error::Error;
+#[cfg(feature = "fs")]
+use std::io;
+
+#[cfg(feature = "fs")]
+use rust_cc::compile_file;
+use rust_cc::{compile_str, lexer, parser, semantic, CompileError, CompileOptions, Compiler, Emit};
+use rust_cc::codegen::{OptLevel, TargetSpec};
+use rust_cc::lexer::LexError;
+use rust_cc::semantic::{Severity, WarningSettings};
+use rust_cc::standard::Standard;
+
+const PROGRAM: &str = "int square(int x) { return x * x; }\nint main() { return square(3); }\n";
+
+#[test]
+fn assembly_is_generated_for_the_target() {
+    let options = CompileOptions {
+        target: TargetSpec::from_name("x86_64-linux").unwrap(),
+        ..CompileOptions::default()
+    };
+    let output = compile_str(PROGRAM, &options).unwrap();
+    assert!(output.output.contains(".globl main"), "{}", output.output);
+    assert!(output.output.contains("square:"), "{}", output.output);
+    assert!(output.output.contains("imul"), "{}", output.output);
+    assert!(output.warnings.is_empty());
+
+    let aarch64 = Compiler::new().target(TargetSpec::from_name("aarch64-linux").unwrap()).compile(PROGRAM).unwrap();
+    assert!(aarch64.output.contains("mul"), "{}", aarch64.output);
+    assert!(!aarch64.output.contains("imul"), "{}", aarch64.output);
+}
+
+#[test]
+fn each_kind_of_output_can_be_asked_for() {
+    let emit = |emit| Compiler::new().emit(emit).compile(PROGRAM).unwrap().output;
+    assert!(emit(Emit::Tokens).contains("Identifier \"square\" 1:5"), "{}", emit(Emit::Tokens));
+    assert!(emit(Emit::Ast).contains("square"), "{}", emit(Emit::Ast));
+    assert!(emit(Emit::AstDot).starts_with("digraph"), "{}", emit(Emit::AstDot));
+    assert!(emit(Emit::LlvmIr).contains("define i32 @main()"), "{}", emit(Emit::LlvmIr));
+    assert!(emit(Emit::Wat).starts_with("(module"), "{}", emit(Emit::Wat));
+}
+
+#[test]
+fn warnings_are_collected_with_the_output() {
+    let source = "int main() {\n    int unused;\n    return 0;\n}\n";
+    let output = Compiler::new().file_name("unused.c").compile(source).unwrap();
+    assert_eq!(output.warnings.len(), 1);
+    let warning = &output.warnings[0];
+    assert_eq!(warning.severity, Severity::Warning);
+    assert_eq!(warning.code, Some("W0002"));
+    assert_eq!(warning.position(), Some((2, 9)));
+    assert!(warning.message.ends_with("[-Wunused-variable]"), "{}", warning.message);
+    assert!(!output.output.is_empty());
+
+    // They can be turned off, or made errors, as the `-W` flags do.
+    let mut off = WarningSettings::default();
+    off.set("unused-variable", None);
+    assert!(Compiler::new().warnings(off).compile(source).unwrap().warnings.is_empty());
+    let mut errors = WarningSettings::default();
+    errors.all_errors = true;
+    let errors = Compiler::new().warnings(errors).compile(source).unwrap_err();
+    assert_eq!(errors.errors().count(), 1);
+    assert!(errors.to_string().contains("[-Werror=unused-variable]"), "{}", errors);
+}
+
+#[test]
+fn errors_say_where_they_are() {
+    let errors = Compiler::new().file_name("main.c").compile("int main() {\n    return x;\n}\n").unwrap_err();
+    assert_eq!(errors.diagnostics.len(), 1);
+    assert_eq!(errors.diagnostics[0].position(), Some((2, 12)));
+    assert_eq!(errors.to_string(), "main.c:2:12: error[E0102]: use of undeclared identifier `x`");
+}
+
+#[test]
+fn the_builder_mirrors_the_options() {
+    let compiler = Compiler::new()
+        .emit(Emit::LlvmIr)
+        .opt_level(OptLevel::O2)
+        .omit_frame_pointer(false)
+        .standard(Standard::C99)
+        .define("NDEBUG", None)
+        .define("LEVEL", Some("2"))
+        .include_dir("include")
+        .file_name("main.c");
+    let options = compiler.options();
+    assert_eq!(options.emit, Emit::LlvmIr);
+    assert_eq!(options.opt_level, OptLevel::O2);
+    assert_eq!(options.omit_frame_pointer, Some(false));
+    assert_eq!(options.standard, Standard::C99);
+    assert_eq!(options.defines, vec![("NDEBUG".to_owned(), None), ("LEVEL".to_owned(), Some("2".to_owned()))]);
+    assert_eq!(options.include_dirs.len(), 1);
+    assert_eq!(compiler.compile(PROGRAM).unwrap(), compile_str(PROGRAM, options).unwrap());
+
+    // The standard is the one the program is lexed with.
+    let c89 = Compiler::new().standard(Standard::C89).compile("int main() { // no\n return 0; }").unwrap_err();
+    assert_eq!(c89.diagnostics[0].code, Some("E0003"));
+}
+
+#[test]
+fn lex_errors_keep_what_the_lexer_made_of_them() {
+    let error = Compiler::new().file_name("main.c").compile("int main() { return 1 @ 2; }").unwrap_err();
+    assert_eq!(error.to_string(), "main.c:1:23: error[E0001]: unrecognized input");
+    let source = error.source().unwrap();
+    assert_eq!(source.to_string(), "unrecognized input at 1:23");
+    assert_eq!(source.downcast_ref::<LexError>(), Some(&LexError::UnrecognizedInput { line: 1, column: 23 }));
+    assert!(source.source().is_none());
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn files_that_cant_be_read_are_errors() {
+    let error = compile_file("tests/no-such-file.c", &CompileOptions::default()).unwrap_err();
+    let io = error.source().unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(io.kind(), io::ErrorKind::NotFound);
+    assert_eq!(error.to_string(), format!("tests/no-such-file.c: error: {}", io));
+    // A file that can be read is called by its path.
+    let output = Compiler::new().compile_file("tests/cases/valid/unused_variables.c").unwrap();
+    assert!(output.warnings.iter().all(|warning| warning.severity == Severity::Warning));
+    let mut errors = WarningSettings::default();
+    errors.all_errors = true;
+    let error = Compiler::new().warnings(errors).compile_file("tests/cases/valid/unused_variables.c").unwrap_err();
+    assert!(error.to_string().starts_with("tests/cases/valid/unused_variables.c:3:9: error[W0002]"), "{}", error);
+}
+
+/// Runs a program through each stage itself, with `?` on each.
+fn build(source: &str) -> Result<String, Box<dyn Error>> {
+    let lexemes = lexer::lex_str(source).map_err(CompileError::from)?;
+    let program = parser::parse_program(&lexemes).map_err(CompileError::from)?;
+    if let Some(err) = semantic::check_program(&program).into_iter().find(|err| err.severity() == Severity::Error) {
+        return Err(CompileError::from(err).into());
+    }
+    Ok(Compiler::new().compile(source)?.output)
+}
+
+#[test]
+fn every_stage_can_be_put_behind_a_question_mark() {
+    assert!(build(PROGRAM).is_ok());
+    assert_eq!(build("int main() { return 1 @ 2; }").unwrap_err().to_string(), "error[E0001]: unrecognized input at 1:23");
+    assert_eq!(build("int main() { return 1 2; }").unwrap_err().to_string(), "error[E0010]: expected operator or `;`, found integer literal `2` at 1:23");
+    assert_eq!(build("int main() { return y; }").unwrap_err().to_string(), "error[E0102]: use of undeclared identifier `y` at 1:21");
+}
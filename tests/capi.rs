@@ -0,0 +1,47 @@
+//! Links the C interface into `capi/harness.c`, which compiles programs
+//! through it, and runs that. It needs a C compiler, so it only runs with
+//! `RUST_CC_EXECUTE=1` set, as the tests that run what is compiled do, and the
+//! `capi` feature.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn c_programs_can_compile_through_the_c_interface() {
+    if !env::var("RUST_CC_EXECUTE").is_ok_and(|value| value == "1") {
+        return;
+    }
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // A target directory of its own, so as not to wait on the one the tests
+    // were built in.
+    let target = manifest.join("target").join("capi");
+    let built = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()))
+        .args(["rustc", "--lib", "--features", "capi", "--crate-type", "staticlib", "--manifest-path"])
+        .arg(manifest.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", &target)
+        .output()
+        .unwrap();
+    assert!(built.status.success(), "{}", String::from_utf8_lossy(&built.stderr));
+
+    let harness = target.join("harness");
+    let mut cc = Command::new("cc");
+    cc.arg(manifest.join("tests").join("capi").join("harness.c"))
+        .arg("-I").arg(manifest.join("include"))
+        .arg(target.join("debug").join("librust_cc.a"))
+        .arg("-o").arg(&harness);
+    // What the standard library needs from the system, as `rustc --print
+    // native-static-libs` lists it.
+    if cfg!(target_os = "linux") {
+        cc.args(["-lpthread", "-ldl", "-lm"]);
+    }
+    let linked = cc.output().unwrap();
+    assert!(linked.status.success(), "{}", String::from_utf8_lossy(&linked.stderr));
+
+    let ran = Command::new(&harness).output().unwrap();
+    assert!(ran.status.success(), "{}", String::from_utf8_lossy(&ran.stderr));
+    assert_eq!(String::from_utf8(ran.stdout).unwrap(), "\
+<source>:1:18: warning[W0002]: unused variable `unused` [-Wunused-variable]
+broken.c:1:21: error[E0102]: use of undeclared identifier `y`
+");
+}
@@ -0,0 +1,57 @@
+/* Compiles programs through the C interface, as an embedder would, and checks
+ * what comes back. Exits with 0 if everything is as it should be, and says
+ * what isn't otherwise. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "rcc.h"
+
+static int failures = 0;
+
+static void check(int ok, const char *what) {
+    if (!ok) {
+        fprintf(stderr, "failed: %s\n", what);
+        failures++;
+    }
+}
+
+int main(void) {
+    RccResult result;
+
+    /* A program that compiles, to assembly, with a warning. */
+    int status = rcc_compile("int main() { int unused; return 0; }", NULL, &result);
+    check(status == RCC_OK, "a valid program compiles");
+    check(result.output != NULL && strstr(result.output, "main") != NULL, "the assembly defines main");
+    check(result.diagnostic_count == 1, "the unused variable is warned about");
+    if (result.diagnostic_count == 1) {
+        check(strstr(result.diagnostics[0], "warning[W0002]") != NULL, "the warning has its code");
+        printf("%s\n", result.diagnostics[0]);
+    }
+    rcc_result_free(&result);
+    rcc_result_free(&result);
+
+    /* One that doesn't, with options. */
+    RccOptions options = { "aarch64-linux", "llvm-ir", "c99", "broken.c", 1 };
+    status = rcc_compile("int main() { return y; }", &options, &result);
+    check(status == RCC_COMPILE_ERROR, "an invalid program doesn't compile");
+    check(result.output == NULL, "nothing is made of it");
+    check(result.diagnostic_count == 1, "its error is reported");
+    if (result.diagnostic_count == 1) {
+        check(strncmp(result.diagnostics[0], "broken.c:1:21: error[E0102]", 27) == 0, "the error says where it is");
+        printf("%s\n", result.diagnostics[0]);
+    }
+    rcc_result_free(&result);
+
+    /* And arguments that make no sense. */
+    options.target = "mips-linux";
+    status = rcc_compile("int main() { return 0; }", &options, &result);
+    check(status == RCC_INVALID_ARGUMENT, "an unknown target is rejected");
+    check(result.diagnostic_count == 1 && strcmp(result.diagnostics[0], "error: unknown target `mips-linux`") == 0, "the target is named");
+    rcc_result_free(&result);
+    check(rcc_compile(NULL, NULL, &result) == RCC_INVALID_ARGUMENT, "a null source is rejected");
+    rcc_result_free(&result);
+    check(rcc_compile("int main() { return 0; }", NULL, NULL) == RCC_INVALID_ARGUMENT, "a null result is rejected");
+
+    return failures != 0;
+}
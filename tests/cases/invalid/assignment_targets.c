@@ -0,0 +1,12 @@
+int next(int *counter) {
+    return ++*counter;
+}
+
+int main() {
+    int count = 0;
+    int *p = &(count + 1);
+    next(&count) = 2;
+    count + 1 = 3;
+    (count++)++;
+    return count + *p;
+}
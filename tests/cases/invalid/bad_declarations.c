@@ -0,0 +1,11 @@
+int f(..., int x);
+int g(int x, ..., int y);
+static int h;
+int h;
+int a[2] = {1, 2, 3};
+int m[2] = 5;
+int n[-1];
+int main() {
+    int size = 3;
+    int v[size];
+}
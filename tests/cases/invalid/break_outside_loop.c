@@ -0,0 +1,8 @@
+int main() {
+    if (1)
+        break;
+    while (1) {
+        continue;
+    }
+    continue;
+}
@@ -0,0 +1,8 @@
+int area(int width, int height);
+int printf(const char *format, ...);
+
+int main() {
+    int *sizes;
+    printf("%d %d\n", area(2, 3), area(4));
+    return area(sizes, 2, 1) + printf();
+}
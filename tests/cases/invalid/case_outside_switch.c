@@ -0,0 +1,13 @@
+int main(int x) {
+    switch (x) {
+    case 1:
+        break;
+    }
+    case 2:
+        x++;
+    while (x) {
+        default:
+            x--;
+    }
+    return x;
+}
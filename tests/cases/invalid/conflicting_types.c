@@ -0,0 +1,15 @@
+int count;
+long count;
+
+int area(int width, int height);
+int area(int w, int h) {
+    return w * h;
+}
+
+int scale(int factor);
+char scale(int factor);
+
+int log(char *format, ...);
+int log(char *format) {
+    return 0;
+}
@@ -0,0 +1,11 @@
+struct buffer { char *data; const int capacity; };
+
+int fill(char *data, int count);
+
+int reset(const struct buffer *buffer, const char *source) {
+    const int limit = 16;
+    buffer->capacity = limit;
+    limit--;
+    *source = 0;
+    return fill(source, limit);
+}
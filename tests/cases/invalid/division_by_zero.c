@@ -0,0 +1,7 @@
+int limit = 10 / 0;
+
+int main(int x) {
+    int y = x / 0;
+    y %= 2 - 2;
+    return y + (0 && 1 / 0);
+}
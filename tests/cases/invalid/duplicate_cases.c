@@ -0,0 +1,21 @@
+int classify(int x, char c) {
+    switch (x) {
+    case 10:
+        return 1;
+    case 0x0A:
+        return 2;
+    default:
+        break;
+    case x:
+        return 3;
+    default:
+        return 4;
+    }
+    switch (c) {
+    case 'a':
+        return 5;
+    case 300:
+        return 6;
+    }
+    return 0;
+}
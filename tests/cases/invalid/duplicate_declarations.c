@@ -0,0 +1,11 @@
+int area(int width, int width);
+
+int scale(int factor, int value) {
+    int factor = 2;
+    int result = value, result;
+    {
+        int value = result * factor;
+        result = value;
+    }
+    return result;
+}
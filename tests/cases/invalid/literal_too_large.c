@@ -0,0 +1 @@
+unsigned long big = 18446744073709551616;
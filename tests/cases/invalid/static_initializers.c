@@ -0,0 +1,11 @@
+struct point { int x; int y; };
+
+struct point origin;
+int count = 2 * 3;
+int twice = count * 2;
+int *last = &origin.y;
+
+int main() {
+    static int calls = main();
+    return count + twice + *last + calls;
+}
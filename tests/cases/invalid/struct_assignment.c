@@ -0,0 +1,11 @@
+struct point { int x; int y; };
+
+struct point origin;
+
+int main() {
+    struct point a = { 1, 2 };
+    struct point b = a;
+    b = a;
+    b = 1 ? a : origin;
+    return (a, b).x;
+}
@@ -0,0 +1,15 @@
+struct point { int x; int y; };
+
+int length(struct point p);
+struct point make(int x, int y);
+
+int length(struct point p) {
+    return p.x + p.y;
+}
+
+int main() {
+    struct point a = { 3, 4 };
+    struct point *p = &a;
+    make(1, 2);
+    return length(a) + length(*p);
+}
@@ -0,0 +1,14 @@
+struct point { int x; int y; };
+
+int *first(int *values) {
+    return *values;
+}
+
+int main() {
+    struct point p = {1, 2};
+    int *a = &p.x;
+    int *b = &p.y;
+    int n = a;
+    if (p) n = a + b;
+    return p.z + *n;
+}
@@ -0,0 +1,13 @@
+int twice(int n) {
+    for (int i = 0; i < 2; i++) {
+        int step = n;
+        n += step;
+    }
+    return n + i + step;
+}
+
+int main() {
+    count = 1;
+    int count;
+    return twice(count) + n;
+}
@@ -0,0 +1,7 @@
+char buffer[sizeof(int) * 4];
+int flags[1 << 3 | 1];
+int table[2 > 1 ? 3 : 0][(char)257 + 1];
+
+int main() {
+    return sizeof(char *) + (int)'a';
+}
@@ -0,0 +1,7 @@
+int classify(int x) {
+    if (x < 0)
+        return -1;
+    else if (x == 0)
+        return 0;
+    return x > 100 ? 2 : 1;
+}
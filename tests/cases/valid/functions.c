@@ -0,0 +1,10 @@
+int printf(const char *format, ...);
+static int square(int x);
+
+int square(int x) {
+    return x * x;
+}
+
+int main() {
+    return printf("%d\n", square(square(3)));
+}
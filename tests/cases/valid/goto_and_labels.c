@@ -0,0 +1,14 @@
+int main() {
+    int i = 0;
+    int total = 0;
+again:
+    total += i;
+    if (++i < 5)
+        goto again;
+    if (total > 100)
+        goto fail;
+    return total;
+    total = 0;
+fail:
+    return -1;
+}
@@ -0,0 +1,13 @@
+unsigned long total;
+
+long widen(int value) {
+    return value;
+}
+
+int main() {
+    char small = 300;
+    unsigned int mask = -1;
+    long big = widen(small) * 65536;
+    total = mask + big;
+    return (small + small) < mask;
+}
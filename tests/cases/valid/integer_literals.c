@@ -0,0 +1,8 @@
+long big = 3000000000;
+unsigned int mask = 0xffffffff;
+unsigned long all = 18446744073709551615u;
+
+int main() {
+    long scaled = 4L * big;
+    return (mask >> 31) + (all > 1ul) + (scaled > 10000000000);
+}
@@ -0,0 +1,6 @@
+int main() {
+    int a = 1, b;
+    b = a += 2;
+    a <<= b--;
+    return a = b = ++a, b;
+}
@@ -0,0 +1,18 @@
+int main() {
+    int total = 0;
+    for (int i = 0; i < 10; i++) {
+        if (i % 2)
+            continue;
+        total += i;
+    }
+    while (total > 5)
+        total--;
+    do {
+        if (total == 3)
+            break;
+        total++;
+    } while (1);
+    for (;;)
+        break;
+    return total;
+}
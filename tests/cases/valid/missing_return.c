@@ -0,0 +1,10 @@
+int sign(int x) {
+    if (x < 0)
+        return -1;
+    else if (x > 0)
+        return 1;
+}
+
+int main() {
+    return sign(-4) + 1;
+}
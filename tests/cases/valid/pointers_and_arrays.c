@@ -0,0 +1,12 @@
+int sum(int *values, int count) {
+    int total = 0;
+    int *end = &values[count];
+    while (values < end)
+        total += *values++;
+    return total;
+}
+
+int main() {
+    int grid[2][3] = {{1, 2, 3}, {4, 5, 6}};
+    return sum(grid[1], 3) + *(*grid + 1);
+}
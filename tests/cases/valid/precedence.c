@@ -0,0 +1,3 @@
+int main() {
+    return 1 + 2 * 3 - (4 - 5) / 6 % 7 << 1 < 3 == 1 & 6 ^ 3 | 8 && !0 || ~1;
+}
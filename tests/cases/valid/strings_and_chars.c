@@ -0,0 +1,7 @@
+char greeting[6] = "hello";
+char message[32] = "tab\there\n" "and \"quotes\"";
+int main() {
+    char c = '\x41';
+    char nul = '\0';
+    return c + nul + '\'';
+}
@@ -0,0 +1,18 @@
+struct point {
+    int x;
+    int y;
+};
+
+struct segment {
+    struct point from, *to;
+};
+
+int length(struct segment *s) {
+    return s->to->x - s->from.x + (s->to->y - s->from.y);
+}
+
+int main() {
+    struct point end = {3, 4};
+    struct segment s = {{0, 0}, &end};
+    return length(&s);
+}
@@ -0,0 +1,29 @@
+int classify(int c) {
+    int kind = 0;
+    switch (c) {
+    case ' ':
+    case '\n':
+        kind = 1;
+        break;
+    case '0':
+        kind = 2;
+    default:
+        kind++;
+    }
+    return kind;
+}
+
+int main() {
+    int total = 0;
+    for (int i = 0; i < 4; i++) {
+        switch (i) {
+        case 1:
+            continue;
+        case 2:
+            break;
+        default:
+            total += classify(i);
+        }
+    }
+    return total;
+}
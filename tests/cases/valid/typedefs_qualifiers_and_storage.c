@@ -0,0 +1,18 @@
+typedef int number;
+typedef number *number_pointer;
+struct pair { int first; int second; };
+typedef struct pair pair_t;
+
+extern number count;
+static const char *const names[2] = {"first", "second"};
+static number limit = 10;
+
+number next(number_pointer p) {
+    const number step = 1;
+    return *p + step;
+}
+
+number difference(pair_t *p) {
+    pair_t swapped = {p->second, p->first};
+    return swapped.first - swapped.second;
+}
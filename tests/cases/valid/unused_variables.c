@@ -0,0 +1,15 @@
+int count(int *values, int length, int _flags) {
+    int total = 0;
+    int last;
+    int scratch;
+    for (int i = 0; i < length; i++) {
+        last = values[i];
+        total++;
+    }
+    return total;
+}
+
+int main() {
+    int values[3] = {1, 2, 3};
+    return count(values, 3, 0);
+}
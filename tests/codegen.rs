@@ -0,0 +1,1266 @@
+//! Compiles programs through to assembly and checks what comes out. With
+//! `RUST_CC_EXECUTE=1` set, each program is also assembled and linked with
+//! `gcc` and run, and its exit status checked; without it, only the assembly
+//! text is checked, so the tests pass without a toolchain. Each program is
+//! compiled to LLVM IR too, which is run with `lli` if it is installed, and
+//! must exit the same way, and to WebAssembly, whose `main` is run with
+//! `wasmtime` if it is installed and must return the exit status.
+
+extern crate rust_cc;
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::process::Command;
+
+use rust_cc::{codegen, lexer, parser, semantic};
+use rust_cc::codegen::{Architecture, TargetSpec};
+use rust_cc::semantic::Severity;
+
+/// The architectures every program is compiled for.
+const ARCHITECTURES: [Architecture; 2] = [Architecture::X86_64, Architecture::Aarch64];
+
+/// Compiles `source`, which must be free of errors, to x86-64 assembly, and
+/// checks that the stack is aligned at every call.
+fn compile(source: &str) -> String {
+    compile_with(source, &codegen::Options { target: TargetSpec { arch: Architecture::X86_64, ..TargetSpec::host() }, ..codegen::Options::default() })
+}
+
+/// Like `compile`, but with `options`.
+fn compile_with(source: &str, options: &codegen::Options) -> String {
+    let program = parser::parse_program(&lexer::lex_str(source).unwrap()).unwrap();
+    let errors: Vec<_> = semantic::check_program(&program).into_iter()
+        .chain(codegen::check(&program, options))
+        .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+        .collect();
+    assert_eq!(errors, vec![], "{}", source);
+    let assembly = codegen::generate_with(&program, options);
+    if options.format != codegen::Format::Assembly {
+        return assembly;
+    }
+    check_labels(&assembly);
+    // The check reads x86-64 AT&T syntax.
+    if options.target.arch == Architecture::X86_64 && options.syntax == codegen::Syntax::Att {
+        check_alignment(&assembly);
+    }
+    assembly
+}
+
+/// Checks that no label is defined twice in `assembly`, which the assembler
+/// would reject.
+fn check_labels(assembly: &str) {
+    let mut defined = HashSet::new();
+    for line in assembly.lines().filter(|line| !line.starts_with(' ') && line.ends_with(':')) {
+        assert!(defined.insert(line), "`{}` is defined twice:\n{}", line, assembly);
+    }
+}
+
+/// Follows `%rsp` through each function of `assembly` and checks that it is a
+/// multiple of 16 at every `call`, as the ABI requires. Instructions are
+/// followed in the order they are written: code after a jump or `ret` is
+/// entered with the stack as it is between statements, just after the
+/// prologue, which is `pushq %rbp` and `movq %rsp, %rbp` if the function keeps
+/// a frame pointer, then `subq` if it has a frame.
+fn check_alignment(assembly: &str) {
+    let number = |operand: &str| operand.trim_start_matches('$').trim_end_matches(',').parse::<i64>().unwrap();
+    // Offsets from a 16-byte boundary; the call leaves the return address below one.
+    let (mut rsp, mut rbp, mut between_statements) = (0, 0, 0);
+    let mut in_prologue = false;
+    for line in assembly.lines() {
+        if !line.starts_with(' ') && !line.starts_with(".L") && line.ends_with(':') {
+            rsp = -8;
+            between_statements = rsp;
+            in_prologue = true;
+            continue;
+        }
+        let words: Vec<_> = line.split_whitespace().collect();
+        // Neither directives nor local labels move the stack.
+        if words[0].starts_with('.') {
+            continue;
+        }
+        match words[..] {
+            ["pushq", _] => rsp -= 8,
+            ["popq", _] => rsp += 8,
+            ["subq", amount, "%rsp"] => rsp -= number(amount),
+            ["addq", amount, "%rsp"] => rsp += number(amount),
+            ["movq", "%rsp,", "%rbp"] => rbp = rsp,
+            ["movq", "%rbp,", "%rsp"] => rsp = rbp,
+            ["call", name] => assert_eq!(rsp % 16, 0, "misaligned call to {}:\n{}", name, assembly),
+            ["ret"] => rsp = between_statements,
+            _ => {},
+        }
+        in_prologue = in_prologue && matches!(words[..], ["pushq", "%rbp"] | ["movq", "%rsp,", "%rbp"] | ["subq", _, "%rsp"]);
+        if in_prologue {
+            between_statements = rsp;
+        }
+    }
+}
+
+/// Compiles `source` under the name `name` for every architecture and, if
+/// executing is enabled, runs the host's, checking that it exits with `status`.
+/// Returns the x86-64 assembly.
+fn run(name: &str, source: &str, status: i32) -> String {
+    run_with(name, source, &codegen::Options::default(), status, None)
+}
+
+/// Like `run`, but also checks what the program writes to standard output.
+fn run_printing(name: &str, source: &str, status: i32, output: &str) -> String {
+    run_with(name, source, &codegen::Options::default(), status, Some(output))
+}
+
+/// Like `run`, but with `options` apart from the architecture, and checking
+/// the output if there is one to check. The x86-64 program must also run the
+/// same without the frame pointer.
+fn run_with(name: &str, source: &str, options: &codegen::Options, status: i32, output: Option<&str>) -> String {
+    let mut x86_64 = String::new();
+    let frameless = codegen::Options { target: TargetSpec { arch: Architecture::X86_64, ..options.target }, omit_frame_pointer: Some(true), ..options.clone() };
+    for options in ARCHITECTURES.iter().map(|&arch| codegen::Options { target: TargetSpec { arch, ..options.target }, ..options.clone() }).chain(Some(frameless)) {
+        let assembly = compile_with(source, &options);
+        let printed = execute(name, &assembly, options.target.arch, status);
+        if let (Some(printed), Some(output)) = (printed, output) {
+            assert_eq!(printed, output, "{} printed the wrong output:\n{}", name, assembly);
+        }
+        if options.target.arch == Architecture::X86_64 && x86_64.is_empty() {
+            x86_64 = assembly;
+        }
+    }
+    let ir = compile_with(source, &codegen::Options { format: codegen::Format::LlvmIr, ..options.clone() });
+    let printed = interpret(name, &ir, status);
+    if let (Some(printed), Some(output)) = (printed, output) {
+        assert_eq!(printed, output, "{} printed the wrong output from LLVM IR:\n{}", name, ir);
+    }
+    // WebAssembly has no variadic functions or `goto`, among what `check`
+    // finds.
+    let program = parser::parse_program(&lexer::lex_str(source).unwrap()).unwrap();
+    let wat_options = codegen::Options { format: codegen::Format::Wat, ..options.clone() };
+    if codegen::check(&program, &wat_options).is_empty() {
+        let wat = compile_with(source, &wat_options);
+        run_wasm(name, &wat, options, status);
+    }
+    x86_64
+}
+
+/// Whether `RUST_CC_EXECUTE=1` is set.
+fn executing() -> bool {
+    env::var("RUST_CC_EXECUTE").is_ok_and(|value| value == "1")
+}
+
+/// If executing is enabled and `assembly` is for the host's architecture,
+/// assembles and links it under the name `name` and runs it, checking that it
+/// exits with `status`. Returns what it wrote to standard output.
+fn execute(name: &str, assembly: &str, architecture: Architecture, status: i32) -> Option<String> {
+    if !executing() || architecture != Architecture::host() {
+        return None;
+    }
+    Some(link_and_run(name, assembly, "gcc", None, status))
+}
+
+/// If executing is enabled and LLVM's `lli` is installed, runs the LLVM IR
+/// `ir` under the name `name` with it, checking that it exits with `status`
+/// just as the assembly does. Returns what it wrote to standard output.
+fn interpret(name: &str, ir: &str, status: i32) -> Option<String> {
+    if !executing() {
+        return None;
+    }
+    let version = match Command::new("lli").arg("--version").output() {
+        Ok(output) => String::from_utf8(output.stdout).unwrap(),
+        Err(_) => return None,
+    };
+    let directory = env::temp_dir().join(format!("rust-cc-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(format!("{}.ll", name));
+    fs::write(&path, ir).unwrap();
+    let mut lli = Command::new("lli");
+    // LLVM 14 only reads opaque pointers when asked to.
+    if version.contains("LLVM version 14.") {
+        lli.arg("-opaque-pointers");
+    }
+    let ran = lli.arg(&path).output().unwrap();
+    assert_eq!(ran.status.code(), Some(status), "{} exited with the wrong status from LLVM IR:\n{}\n{}", name, ir, String::from_utf8_lossy(&ran.stderr));
+    fs::remove_file(&path).unwrap();
+    Some(String::from_utf8(ran.stdout).unwrap())
+}
+
+/// If executing is enabled and `wasmtime` is installed, runs `main` of the
+/// WebAssembly module `wat` under the name `name` with it, checking that it
+/// returns `status`, as far as an exit status holds it, or traps if `status`
+/// is the one a division by zero exits with under `options`' checks. A module
+/// that imports functions isn't run, since nothing would provide them.
+fn run_wasm(name: &str, wat: &str, options: &codegen::Options, status: i32) {
+    if !executing() || wat.contains("(import ") || Command::new("wasmtime").arg("--version").output().is_err() {
+        return;
+    }
+    let directory = env::temp_dir().join(format!("rust-cc-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(format!("{}.wat", name));
+    fs::write(&path, wat).unwrap();
+    let ran = Command::new("wasmtime").arg("--invoke").arg("main").arg(&path).output().unwrap();
+    if options.checked_arithmetic && status == codegen::DIVISION_BY_ZERO_STATUS {
+        assert!(!ran.status.success(), "{} didn't trap in WebAssembly:\n{}", name, wat);
+    } else {
+        assert!(ran.status.success(), "{} failed in WebAssembly:\n{}\n{}", name, wat, String::from_utf8_lossy(&ran.stderr));
+        let stdout = String::from_utf8(ran.stdout).unwrap();
+        let returned: i64 = stdout.lines().last().unwrap().trim().parse().unwrap();
+        assert_eq!(returned & 0xff, i64::from(status), "{} returned the wrong value from WebAssembly:\n{}", name, wat);
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+/// Like `execute`, but for x86-64 assembly for Windows, which is run on a
+/// Windows host, or under Wine if it and MinGW's `gcc` are installed.
+fn execute_on_windows(name: &str, assembly: &str, status: i32) -> Option<String> {
+    if !executing() {
+        return None;
+    }
+    if cfg!(all(windows, target_arch = "x86_64")) {
+        return Some(link_and_run(name, assembly, "gcc", None, status));
+    }
+    let installed = |program: &str| Command::new(program).arg("--version").output().is_ok();
+    if !installed("x86_64-w64-mingw32-gcc") || !installed("wine") {
+        return None;
+    }
+    Some(link_and_run(name, assembly, "x86_64-w64-mingw32-gcc", Some("wine"), status))
+}
+
+/// Assembles and links `assembly` under the name `name` with `compiler`, runs
+/// it, with `runner` if there is one, and checks that it exits with `status`.
+/// Returns what it wrote to standard output, with Windows line endings made
+/// Unix ones.
+fn link_and_run(name: &str, assembly: &str, compiler: &str, runner: Option<&str>, status: i32) -> String {
+    let directory = env::temp_dir().join(format!("rust-cc-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let (assembly_path, executable) = (directory.join(format!("{}.s", name)), directory.join(format!("{}.exe", name)));
+    fs::write(&assembly_path, assembly).unwrap();
+
+    let assembled = Command::new(compiler).arg("-o").arg(&executable).arg(&assembly_path).status().unwrap();
+    assert!(assembled.success(), "{} didn't assemble:\n{}", name, assembly);
+    let ran = match runner {
+        Some(runner) => Command::new(runner).arg(&executable).output().unwrap(),
+        None => Command::new(&executable).output().unwrap(),
+    };
+    assert_eq!(ran.status.code(), Some(status), "{} exited with the wrong status:\n{}", name, assembly);
+    fs::remove_file(&assembly_path).unwrap();
+    fs::remove_file(&executable).unwrap();
+    String::from_utf8(ran.stdout).unwrap().replace("\r\n", "\n")
+}
+
+#[test]
+fn return_a_constant() {
+    let assembly = run("return_a_constant", "int main() { return 2; }", 2);
+    for line in &[".globl main", "main:", "movl $2, %eax", "ret"] {
+        assert!(assembly.lines().any(|candidate| candidate.trim() == *line), "missing `{}` in:\n{}", line, assembly);
+    }
+}
+
+#[test]
+fn unary_operators() {
+    run("logical_not_of_zero", "int main() { return !0; }", 1);
+    run("logical_not_of_nonzero", "int main() { return !7; }", 0);
+    run("double_negation", "int main() { return -(-5); }", 5);
+    // Exit statuses are taken modulo 256.
+    run("complement", "int main() { return ~-8; }", 7);
+    run("nested", "int main() { return !-~3; }", 0);
+}
+
+#[test]
+fn binary_arithmetic() {
+    run("add", "int main() { return 2 + 3; }", 5);
+    run("subtract", "int main() { return 7 - 2; }", 5);
+    run("subtract_negative", "int main() { return 2 - 7 + 10; }", 5);
+    run("multiply", "int main() { return 6 * 7; }", 42);
+    run("divide", "int main() { return 7 / 2; }", 3);
+    run("divide_negative", "int main() { return -7 / 2 + 10; }", 7);
+    run("modulo", "int main() { return 7 % 3; }", 1);
+    run("modulo_negative", "int main() { return -7 % 3 + 10; }", 9);
+    run("precedence", "int main() { return 2 + 3 * 4 - 10 / 5; }", 12);
+    run("associativity", "int main() { return 100 / 10 / 5 - 1 - 1; }", 0);
+    run("deep_nesting", "int main() { return (((1 + 2) * (3 + 4)) - ((5 - 6) * (7 % 4))) / ((8 / 2) - (9 % 5) + 2); }", 12);
+}
+
+#[test]
+fn comparisons() {
+    for &(name, expression, status) in &[
+        ("equal_true", "3 == 3", 1),
+        ("equal_false", "3 == 4", 0),
+        ("not_equal_true", "3 != 4", 1),
+        ("not_equal_false", "3 != 3", 0),
+        ("less_true", "-2 < 1", 1),
+        ("less_false", "1 < 1", 0),
+        ("less_equal_true", "1 <= 1", 1),
+        ("less_equal_false", "2 <= -1", 0),
+        ("greater_true", "2 > -1", 1),
+        ("greater_false", "1 > 1", 0),
+        ("greater_equal_true", "1 >= 1", 1),
+        ("greater_equal_false", "-3 >= 1", 0),
+    ] {
+        run(name, &format!("int main() {{ return {}; }}", expression), status);
+    }
+    run("compound_comparison", "int main() { return (1 < 2) + (3 == 3); }", 2);
+    run("unsigned_comparison", "int main() { return (-1 < (unsigned)1) + 2 * ((unsigned)-1 > 1); }", 2);
+}
+
+#[test]
+fn logical_operators() {
+    for &(name, expression, status) in &[
+        ("and_false_false", "0 && 0", 0),
+        ("and_false_true", "0 && 5", 0),
+        ("and_true_false", "5 && 0", 0),
+        ("and_true_true", "5 && -5", 1),
+        ("or_false_false", "0 || 0", 0),
+        ("or_false_true", "0 || 5", 1),
+        ("or_true_false", "5 || 0", 1),
+        ("or_true_true", "5 || -5", 1),
+        ("nested", "(1 && (0 || 3)) + (0 || (2 && 0)) + ((1 || 0) && 2)", 2),
+        // Dividing by zero would crash, so it mustn't be evaluated.
+        ("and_short_circuits", "0 && 1 / 0", 0),
+        ("or_short_circuits", "1 || 1 / 0", 1),
+    ] {
+        run(name, &format!("int main() {{ return {}; }}", expression), status);
+    }
+}
+
+#[test]
+fn bitwise_operators() {
+    for &(name, expression, status) in &[
+        ("and", "12 & 10", 8),
+        ("or", "12 | 10", 14),
+        ("xor", "12 ^ 10", 6),
+        ("and_negative", "-1 & 200", 200),
+        ("precedence", "1 | 6 ^ 3 & 5", 7),
+        ("with_shifts", "(1 << 3 | 1) ^ 3", 10),
+    ] {
+        run(name, &format!("int main() {{ int a = 0; return a + ({}); }}", expression), status);
+    }
+    run("compound", "int main() { int a = 13; a &= 7; a |= 8; a ^= 3; return a; }", 14);
+    run("long", "int main() { long b = 1; b = b << 40; return (int)(((b | 6) >> 39 ^ (b & 5)) + (b ^ b)); }", 2);
+    run("unsigned", "int main() { unsigned int u = -1; return (u & 255) - (u ^ -2); }", 254);
+}
+
+#[test]
+fn conditional_operator() {
+    run("constant", "int main() { return 1 ? 2 : 3; }", 2);
+    run("taken", "int main() { int a = 4; return a > 2 ? a * 2 : -a; }", 8);
+    run("not_taken", "int main() { int a = 1; return a > 2 ? a * 2 : a + 10; }", 11);
+    // Dividing by zero would crash, so the operand that isn't picked mustn't
+    // be evaluated.
+    run("short_circuits", "int main() { int a = 0; return a ? 1 / a : 7; }", 7);
+    run("nested", "int main() { int a = 2; return a > 1 ? a > 2 ? 3 : 20 : 1; }", 20);
+    run("in_an_operand", "int main() { int a = 1; return 10 + (a ? 2 : 3) * (a - 1 ? 5 : 4); }", 18);
+    run("converted", "int main() { int a = -1; long b = 5; long c = a ? a : b; return c < 0 ? 3 : 4; }", 3);
+    run("null_pointer", "int main() { int x = 4; int *p = 0; int *q = p ? p : &x; return *(p ? q : q) + (q ? 1 : 0); }", 5);
+}
+
+#[test]
+fn comma_operator() {
+    run("for_clauses", "int main() { int i, j; for (i = 0, j = 0; i < 3; i++, j++) ; return i * 10 + j; }", 33);
+    run("last_value", "int main() { int a = 1; int b = (a += 2, a * 5); return b; }", 15);
+    run("in_a_condition", "int main() { int n = 0; while (n++, n < 4) ; return n; }", 4);
+    run("converted", "int main() { int a = 1; long b = (a, -1); return b < 0 ? 6 : 7; }", 6);
+}
+
+#[test]
+fn goto_statements() {
+    run("backward", "int main() { int i = 0; again: i++; if (i < 5) goto again; return i; }", 5);
+    run("forward", "int main() { int i = 1; goto skip; i = 2; skip: return i; }", 1);
+    run("out_of_loops", "int main() { int i, j; for (i = 0; i < 10; i++) for (j = 0; j < 10; j++) if (i * j == 12) goto done; done: return i * 10 + j; }", 26);
+    run("into_a_block", "int main() { int i = 3; goto inside; { i = 10; inside: i++; } return i; }", 4);
+    // Folding can't remove a branch that is never taken while a jump gets into it.
+    let source = "int main() { int i = 1; goto inside; if (0) { i = 10; inside: i += 2; } else i = 20; \
+                  goto looped; while (0) { looped: i *= 5; } return i; }";
+    run("into_a_dead_branch", source, 15);
+    let options = codegen::Options { opt_level: codegen::OptLevel::O2, ..codegen::Options::default() };
+    run_with("into_a_dead_branch_optimized", source, &options, 15, None);
+}
+
+#[test]
+fn local_variables() {
+    run("three_locals", "int main() { int a = 3; int b; int c = a * 2; b = c - a; c = a + b + c; return c * b; }", 36);
+    run("assignment_value", "int main() { int a; int b; a = (b = 4) + 1; return a * 10 + b; }", 54);
+    run("compound_assignment", "int main() { int a = 10; a += 5; a *= 2; a -= 1; a /= 3; a %= 5; return a; }", 4);
+    run("nested_scopes", "int main() { int a = 1; { int a = 2; { int a = 3; } } { int b = a + 4; a = b; } return a; }", 5);
+    run("uninitialized_then_assigned", "int main() { int x; x = 7; return x; }", 7);
+    run("fall_off_main", "int main() { int x = 7; x = x + 1; }", 0);
+}
+
+#[test]
+fn conditionals() {
+    run("if_taken", "int main() { int a = 1; if (a) return 3; return 4; }", 3);
+    run("if_not_taken", "int main() { int a = 0; if (a) return 3; return 4; }", 4);
+    run("if_else_then", "int main() { int a = 5; int b; if (a > 2) b = 1; else b = 2; return b; }", 1);
+    run("if_else_else", "int main() { int a = 1; int b; if (a > 2) b = 1; else b = 2; return b; }", 2);
+    run("nested_if", "int main() { int a = 3; int b = 0; if (a > 1) { if (a > 2) b = 3; else b = 2; } else b = 1; return b; }", 3);
+    run("dangling_else", "int main() { int a = 1; int b = 5; if (a) if (a - 1) b = 6; else b = 7; return b; }", 7);
+    run("else_if_chain", "int main() { int a = 2; if (a == 1) return 10; else if (a == 2) return 20; else return 30; }", 20);
+}
+
+#[test]
+fn loops() {
+    run("counting_while", "int main() { int n = 0; while (n < 10) n = n + 1; return n; }", 10);
+    run("do_while_runs_once", "int main() { int n = 0; do n = n + 1; while (0); return n; }", 1);
+    run("do_while_counts", "int main() { int n = 0; do { n += 2; } while (n < 9); return n; }", 10);
+    run("for_with_continue", "int main() { int sum = 0; for (int i = 0; i < 10; i += 1) { if (i % 2) continue; sum += i; } return sum; }", 20);
+    run("for_without_clauses", "int main() { int i = 0; for (;;) { i += 1; if (i == 7) break; } return i; }", 7);
+    run("while_with_continue", "int main() { int i = 0; int n = 0; while (i < 10) { i += 1; if (i > 3) continue; n += 1; } return n * 10 + i; }", 40);
+    run("do_while_continue_tests_the_condition", "int main() { int i = 0; do { i += 1; continue; } while (i < 5); return i; }", 5);
+    run(
+        "nested_break",
+        "int main() { int count = 0; for (int i = 0; i < 4; i += 1) { for (int j = 0; j < 10; j += 1) { if (j == 2) break; count += 1; } } return count; }",
+        8,
+    );
+    run(
+        "nested_continue",
+        "int main() { int count = 0; int i = 0; while (i < 3) { i += 1; int j = 0; do { j += 1; if (j == 2) continue; count += 1; } while (j < 3); } return count; }",
+        6,
+    );
+}
+
+#[test]
+fn function_calls() {
+    run("add", "int add(int a, int b) { return a + b; }\nint main() { return add(40, 2); }", 42);
+    run("argument_order", "int sub(int a, int b) { return a - b; }\nint main() { return sub(10, 3); }", 7);
+    run(
+        "factorial",
+        "int factorial(int n) { if (n <= 1) return 1; return n * factorial(n - 1); }\nint main() { return factorial(5); }",
+        120,
+    );
+    run(
+        "seven_parameters",
+        "int f(int a, int b, int c, int d, int e, int f, int g) { return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g; }\n\
+         int main() { return f(1, 2, 3, 4, 5, 6, 7); }",
+        140,
+    );
+    run(
+        "eight_parameters",
+        "int f(int a, int b, int c, int d, int e, int f, int g, int h) { return g * 10 + h - a; }\n\
+         int main() { return f(1, 2, 3, 4, 5, 6, 7, 8); }",
+        77,
+    );
+    // The left operand of `-` waits on the stack across the call.
+    run(
+        "call_inside_expression",
+        "int twice(int x) { return x + x; }\nint main() { int a = 50; return a - twice(a - twice(10)); }",
+        -10 & 0xff,
+    );
+    run("prototype_before_definition", "int f(int x);\nint main() { return f(3); }\nint f(int x) { return x * x; }", 9);
+    run("parameters_are_variables", "int f(int x) { x = x + 1; return x; }\nint main() { int y = 4; return f(y) + y; }", 9);
+}
+
+#[test]
+fn calls_keep_the_stack_aligned() {
+    run(
+        "putchar_with_temporaries",
+        "int putchar(int c);\nint main() { return 1 + (2 + (3 + putchar(10) - 10)); }",
+        6,
+    );
+    run(
+        "nested_calls_with_stack_arguments",
+        "int putchar(int c);\n\
+         int f(int a, int b, int c, int d, int e, int f, int g) { return putchar(g) + a; }\n\
+         int main() { return 1 + f(1, 2, 3, 4, 5, 6, 1 + f(0, 0, 0, 0, 0, 0, 10)); }",
+        13,
+    );
+}
+
+#[test]
+fn global_variables() {
+    run(
+        "global_counter",
+        "int counter;\nint bump(int by) { counter += by; return counter; }\nint main() { bump(2); bump(3); return bump(4); }",
+        9,
+    );
+    run("zero_initialized", "int x;\nint main() { return x; }", 0);
+    run("initialized", "int x = 40;\nint y = 2;\nint main() { return x + y; }", 42);
+    run("tentative_then_defined", "int x;\nint x = 7;\nint main() { return x; }", 7);
+    run("block_scope_extern", "int main() { extern int x; return x; }\nint x = 12;", 12);
+    run(
+        "static_locals",
+        "int next() { static int n = 10; n += 1; return n; }\nint main() { next(); next(); return next(); }",
+        13,
+    );
+    run(
+        "static_locals_in_different_functions",
+        "int f() { static int n; n += 1; return n; }\nint g() { static int n = 5; n += 1; return n; }\n\
+         int main() { f(); g(); return f() * 10 + g(); }",
+        27,
+    );
+    // The right operand would set `called` if it were evaluated.
+    run(
+        "short_circuits_skip_calls",
+        "int called;\nint f() { called = 1; return 1; }\nint main() { int a = 0 && f(); int b = 1 || f(); return called * 10 + a + b; }",
+        1,
+    );
+}
+
+#[test]
+fn string_literals() {
+    run_printing("puts", "int puts(const char *s);\nint main() { puts(\"hello\"); return 0; }", 0, "hello\n");
+    run_printing(
+        "escapes",
+        "int puts(const char *s);\nint main() { puts(\"a \\\"quote\\\"\\nand a \\\\ backslash\\tafter a tab\"); return 0; }",
+        0,
+        "a \"quote\"\nand a \\ backslash\tafter a tab\n",
+    );
+    run_printing("embedded_null", "int puts(const char *s);\nint main() { puts(\"cut\\0off\"); return 0; }", 0, "cut\n");
+    run_printing(
+        "string_variables",
+        "int puts(const char *s);\nchar *greeting = \"hi\";\n\
+         int main() { char *s = \"there\"; char *t = s; puts(greeting); puts(t); return 0; }",
+        0,
+        "hi\nthere\n",
+    );
+    let assembly = run_printing(
+        "shared_literals",
+        "int puts(const char *s);\nint main() { puts(\"same\"); puts(\"same\"); return 0; }",
+        0,
+        "same\nsame\n",
+    );
+    assert_eq!(assembly.matches(".asciz").count(), 1);
+    run_printing(
+        "printf",
+        "int printf(const char *format, ...);\nint main() { return printf(\"%d-%d\\n\", 1 + 2, 40) - 5; }",
+        0,
+        "3-40\n",
+    );
+}
+
+#[test]
+fn chars() {
+    // `b` is laid out right below `a`, so a store to it wider than a byte would
+    // overwrite `a`.
+    run("char_beside_int", "int main() { int a = 1000; char b = 7; b = 9; return (a == 1000) * 100 + b; }", 109);
+    run("chars_beside_each_other", "int main() { char a = 1; char b = 2; char c = 3; b = 20; return a + b + c; }", 24);
+    // `char` is signed, so 200 wraps around to -56.
+    run("char_wraps", "int main() { char c = 200; return (c < 0) * 100 + (c == -56) * 10 + (c + 56 == 0); }", 111);
+    run("char_truncates", "int main() { char c = 257; return c; }", 1);
+    run("char_arithmetic_is_done_in_int", "int main() { char a = 100; char b = 100; int sum = a + b; return sum == 200; }", 1);
+    run("char_compound_assignment", "int main() { char c = 120; c += 10; return c == -126; }", 1);
+    run(
+        "char_parameters",
+        "int f(char a, char b, char c, char d, char e, char f, char g) { return a + b + c + d + e + f + g; }\n\
+         int main() { return f(1, 2, 3, 4, 5, 6, 300); }",
+        65,
+    );
+    run("char_globals", "char c = 'A';\nint main() { char d = 'a'; c = c + 1; return d - c; }", 31);
+    run_printing("char_to_putchar", "int putchar(int c);\nint main() { char c = 'x'; putchar(c); return 0; }", 0, "x");
+}
+
+#[test]
+fn longs() {
+    // 10^12 doesn't fit in 32 bits, so doing this in `int` would give garbage.
+    run("long_multiply", "int main() { long big = (long)1000000 * 1000000; return big / 1000000 / 10000; }", 100);
+    run("long_keeps_high_bits", "int main() { long big = (long)65536 * 65536; return big / 65536 / 65536 + (big > 2147483647); }", 2);
+    run("long_comparisons", "int main() { long a = (long)3000000 * 1000; long b = (long)3000000 * 999; return (a > b) + 2 * (b < a) + 4 * (a != b) + 8 * (a == a); }", 15);
+    run("long_division", "int main() { long a = (long)-7000000 * 1003; return a / 1000000000 + a % 1000000000 / 1000000 + 50; }", 22);
+    // Only the low 32 bits survive the conversion to the return type.
+    run("long_truncates_to_int", "int f() { long big = (long)65536 * 65536 + 42; return big; }\nint main() { return f(); }", 42);
+    run("int_widens_to_long", "long f(long x) { return x * 2; }\nint main() { int n = -5; return f(n) + 20; }", 10);
+    run("long_negation", "int main() { long a = (long)65536 * 65536; long b = -a; return (b < 0) + (~b == a - 1) * 2 + !b * 4; }", 3);
+    run("long_globals", "long total = 5;\nint main() { total += (long)1000000 * 1000000; return total % 256; }", (1000000000005i64 % 256) as i32);
+    run("long_condition", "int main() { long a = (long)65536 * 65536; if (a) return 1; return 0; }", 1);
+}
+
+#[test]
+fn unsigned_arithmetic() {
+    // Compared as signed, `-1` would be less than 1.
+    run("unsigned_less", "int main() { return (unsigned)1 < (unsigned)-1; }", 1);
+    run("mixed_comparison_converts_to_unsigned", "int main() { int a = -1; unsigned b = 1; return a > b; }", 1);
+    // -16 is 4294967280 as an `unsigned`.
+    run("unsigned_division", "int main() { unsigned a = -16; return a / 16 == 268435455; }", 1);
+    run("unsigned_division_result", "int main() { unsigned a = -16; return a / 16 / 1048576; }", 255);
+    run("unsigned_modulo", "int main() { unsigned a = -16; return a % 10; }", 0);
+    run("signed_modulo_differs", "int main() { int a = -16; return a % 10 + 10; }", 4);
+    run("unsigned_right_shift", "int main() { unsigned a = -8; int b = -8; return (a >> 28) * 10 + (b >> 1 == -4); }", 151);
+    run("unsigned_long_division", "int main() { unsigned long a = (unsigned long)-1; return a / 65536 / 65536 / 16777216; }", 255);
+    run("unsigned_wraps", "int main() { unsigned a = 0; a -= 1; return a == (unsigned)-1; }", 1);
+    run("unsigned_to_long_is_zero_extended", "int main() { unsigned a = -1; long b = a; return b > 0; }", 1);
+
+    // Constant expressions convert their operands the same way, wherever
+    // they are worked out.
+    let source = "\
+int g = -1 < 1u;
+unsigned h = (0u - 1) / 2;
+int main() {
+    int x = 0;
+    switch (0) { case -1 < 1u: x = 4; break; default: x = 8; }
+    while (-1 < 1u) x = 100;
+    return g + (h == 2147483647) * 2 + x;
+}";
+    for &(name, opt_level) in &[("constants_o0", codegen::OptLevel::O0), ("constants_o1", codegen::OptLevel::O1), ("constants_o2", codegen::OptLevel::O2)] {
+        run_with(name, source, &codegen::Options { opt_level, ..codegen::Options::default() }, 6, None);
+    }
+}
+
+#[test]
+fn pointers() {
+    run(
+        "swap",
+        "int swap(int *a, int *b) { int t = *a; *a = *b; *b = t; return 0; }\n\
+         int main() { int x = 3; int y = 7; swap(&x, &y); return x * 10 + y; }",
+        73,
+    );
+    run("deref_of_address", "int main() { int x = 1; *(&x) = 5; return x; }", 5);
+    run("pointer_to_pointer", "int main() { int x = 1; int *p = &x; int **pp = &p; **pp = 9; return *p + x; }", 18);
+    run("char_through_pointer", "int main() { int x = 0; char c = 1; char *p = &c; *p = 200; return (c == -56) + x; }", 1);
+    run("long_through_pointer", "int main() { long l = 0; long *p = &l; *p = (long)65536 * 65536; return l / 65536 / 65536; }", 1);
+    run("global_through_pointer", "int g = 4;\nint main() { int *p = &g; *p += 3; return g; }", 7);
+    run(
+        "compound_through_pointer",
+        "int f(int *p) { *p *= 3; *p -= 1; return *p; }\nint main() { int x = 5; return f(&x) + x; }",
+        28,
+    );
+    run("increments", "int main() { int x = 5; int a = x++; int b = ++x; int c = x--; int d = --x; return a + b * 10 + c + d * 10 + x; }", 5 + 70 + 7 + 50 + 5);
+    run("char_increment_wraps", "int main() { char c = 127; c++; return c == -128; }", 1);
+    run(
+        "pointer_arithmetic",
+        "int main() { int x; int *p = &x; int *q = p + 5; long *r = 0; r += 2; return (q - p) * 10 + (r - (long *)0) + (p + 5 == q); }",
+        53,
+    );
+    run("pointer_comparisons", "int main() { int x; int *p = &x; int *q = p + 1; return (p < q) + (q > p) * 2 + (p != q) * 4 + (p == p) * 8; }", 15);
+    run("null_pointer_condition", "int main() { int *p = 0; int x; if (p) return 1; p = &x; if (!p) return 2; return 3; }", 3);
+}
+
+#[test]
+fn arrays() {
+    run(
+        "fill_and_sum",
+        "int main() { int a[5]; int i; int sum = 0; for (i = 0; i < 5; i++) a[i] = i * i; for (i = 0; i < 5; i++) sum += a[i]; return sum; }",
+        30,
+    );
+    run("computed_index", "int main() { int a[4] = { 1, 2, 3, 4 }; int i = 1; a[i * 2 + 1] = 40; a[i] += 20; return a[3] + a[1]; }", 62);
+    run(
+        "array_argument",
+        "int sum(int *p, int n) { int total = 0; int i; for (i = 0; i < n; i++) total += p[i]; return total; }\n\
+         int main() { int a[3] = { 5, 6, 7 }; return sum(a, 3) + 2[a]; }",
+        25,
+    );
+    run(
+        "two_dimensions",
+        "int main() { int m[3][4]; int i; int j; for (i = 0; i < 3; i++) for (j = 0; j < 4; j++) m[i][j] = i * 4 + j; return m[2][1] * 10 + *(*(m + 1) + 3) + (&m[1][0] - &m[0][0]); }",
+        9 * 10 + 7 + 4,
+    );
+    run(
+        "pointer_walk",
+        "int main() { long a[4] = { 1, 2, 3 }; long *p = a; long total = 0; while (p < a + 4) total = total * 10 + *p++; return total / 10; }",
+        123,
+    );
+    run(
+        "char_arrays",
+        "int main() { char s[8] = \"abc\"; char t[3] = \"xyz\"; s[1] = t[2]; return (s[0] == 'a') + (s[1] == 'z') * 2 + (s[3] == 0) * 4 + (s[7] == 0) * 8 + sizeof(long) * 16; }",
+        15 + 8 * 16,
+    );
+    run(
+        "global_arrays",
+        "int table[4] = { 3, 1 };\nint *second = &table[1];\nchar name[3] = \"hi\";\nint zeros[10];\n\
+         int main() { *second += 5; return table[0] * 10 + table[1] + table[3] + zeros[9] + (name[1] == 'i') * 100; }",
+        136,
+    );
+}
+
+#[test]
+fn structs() {
+    run(
+        "point",
+        "struct point { int x; int y; };\n\
+         int main() { struct point p; p.x = 3; p.y = 4; p.x += p.y; return p.x * 10 + p.y; }",
+        74,
+    );
+    run(
+        "padding",
+        "struct padded { char c; int i; };\n\
+         int main() { struct padded a[2]; a[0].c = -1; a[0].i = 1000; a[1].c = 2; a[1].i = 3; \
+         return sizeof(struct padded) * 10 + (a[0].c == -1) + (a[0].i == 1000) * 2 + a[1].c + a[1].i; }",
+        80 + 1 + 2 + 5,
+    );
+    run(
+        "arrow",
+        "struct counter { char tag; long total; int count; };\n\
+         int add(struct counter *c, int value) { c->total += value; c->count++; return c->count; }\n\
+         int main() { struct counter c; c.total = 0; c.count = 0; add(&c, 5); add(&c, 7); return (int)c.total + (&c)->count * 100; }",
+        212,
+    );
+    run(
+        "nested",
+        "struct inner { char c; int values[3]; };\nstruct outer { char c; struct inner in; long l; };\n\
+         int main() { struct outer o; struct inner *p = &o.in; o.c = 1; p->values[2] = 20; o.in.c = 3; o.l = 4; \
+         return o.c + o.in.values[2] + p->c + o.l + sizeof(struct outer); }",
+        1 + 20 + 3 + 4 + 32,
+    );
+    run(
+        "initializers",
+        "struct pair { char a; int b; };\nstruct pair g = { 1, 2 };\nstruct pair zero;\n\
+         int main() { struct pair p = { 3 }; struct pair q[2] = { { 4, 5 }, { 6 } }; \
+         return g.a + g.b * 10 + p.a * 10 + p.b + q[0].b + zero.b + q[1].b; }",
+        1 + 20 + 30 + 5,
+    );
+    run(
+        "discarded",
+        "struct point { int x; int y; };\n\
+         int main() { struct point p = { 3, 4 }; struct point *q = &p; p; *q; return q->y; }",
+        4,
+    );
+}
+
+#[test]
+fn switches() {
+    let dense = "int f(int x) { switch (x) { case 0: return 10; case 1: return 11; case 2: return 12; case 3: return 13; case 4: return 14; case 5: return 15; default: return 99; } }\n";
+    let assembly = run("dense_in_range", &format!("{}int main() {{ return f(0) + f(3) + f(5); }}", dense), 38);
+    assert!(assembly.contains("jmp *(%rcx,%rax,8)") && assembly.contains(".data.rel.ro"), "{}", assembly);
+    run("dense_below", &format!("{}int main() {{ return f(-1); }}", dense), 99);
+    run("dense_above", &format!("{}int main() {{ return f(6) + f(1000000); }}", dense), 198);
+    run(
+        "dense_without_default",
+        "int f(int x) { int y = 1; switch (x) { case 2: y = 2; break; case 3: y = 3; break; case 4: y = 4; break; case 6: y = 6; } return y; }\n\
+         int main() { return f(1) + f(2) * 10 + f(5) * 100 + f(6); }",
+        1 + 20 + 100 + 6,
+    );
+    let assembly = run(
+        "sparse",
+        "int f(int x) { switch (x) { case -100: return 1; case 7: return 2; case 5000: return 3; default: return 4; } }\n\
+         int main() { return f(-100) + f(7) * 4 + f(5000) * 16 + f(8) * 40; }",
+        1 + 8 + 48 + 160,
+    );
+    assert!(!assembly.contains("jmp *"), "{}", assembly);
+    run(
+        "fall_through",
+        "int f(int x) { int y = 0; switch (x) { case 1: y += 1; case 2: y += 2; break; case 3: y += 3; } return y; }\n\
+         int main() { return f(1) * 100 + f(2) * 10 + f(3) + f(4); }",
+        (300 + 20 + 3) % 256,
+    );
+    run(
+        "inside_a_loop",
+        "int main() { int i; int total = 0; for (i = 0; i < 10; i++) { switch (i % 3) { case 0: continue; case 1: total += 1; break; default: total += 10; } total += 100; } return total; }",
+        (6 * 100 + 3 + 30) % 256,
+    );
+    run(
+        "long_and_char_values",
+        "int f(long x) { switch (x) { case -1: return 1; case 0: return 2; case 1: return 3; case 2: return 4; } return 5; }\n\
+         int g(char c) { switch (c) { case 'a': return 1; case 'b': return 2; case 'c': return 3; case 'd': return 4; } return 0; }\n\
+         int main() { return f(-1) + f(2) * 10 + f((long)65536 * 65536) * 100 + g('c') - g('z'); }",
+        (1 + 40 + 500 + 3) % 256,
+    );
+}
+
+#[test]
+fn checked_division() {
+    let options = codegen::Options { checked_arithmetic: true, ..codegen::Options::default() };
+    let source = "int divide(int x, int y) { return x / y; }\n\
+                  int main() { int zero = 0; puts(\"before\"); return divide(7, zero); }";
+    let assembly = run_with("divide_by_zero", source, &options, codegen::DIVISION_BY_ZERO_STATUS, Some("before\n"));
+    assert!(assembly.contains("    cmpl $0, %ecx\n    je .Ldivision_by_zero\n"), "{}", assembly);
+
+    let source = "int main() { int min = -2147483647 - 1; int minus_one = -1; long lmin = (long)min * 65536 * 65536; \
+                  return (min / minus_one == min) + (min % minus_one == 0) * 2 + (lmin / minus_one == lmin) * 4 \
+                  + (7 / minus_one == -7) * 8 + (-7 / 2 == -3) * 16 + (-7 % 2 == -1) * 32 + ((unsigned)7 % 4 == 3) * 64; }";
+    run_with("divide_min_by_minus_one", source, &options, 127, None);
+
+    // Without checks, nothing is in the way of `idiv`.
+    assert!(!compile("int main(int x) { return 7 / x; }").contains("division_by_zero"));
+}
+
+#[test]
+fn shifts() {
+    run(
+        "variable_shifts",
+        "int main() { int one = 1; int four = 4; int x = 3 << four; unsigned y = (unsigned)200 >> four; return (x == 48) + (y == 12) * 2 + ((one << 31 >> 31) == -1) * 4; }",
+        7,
+    );
+    let assembly = run("constant_shift", "int main() { int x = 5; return x << 4; }", 80);
+    assert!(assembly.contains("shll $4, %eax"), "{}", assembly);
+    run(
+        "signed_right_shift",
+        "int main() { int x = -64; long l = (long)-1 * 65536 * 65536; return (x >> 3 == -8) + (l >> 33 == -1) * 2 + ((unsigned)x >> 28 == 15) * 4; }",
+        7,
+    );
+    run(
+        "shift_amount_in_a_nested_expression",
+        "int f(int x) { return x; }\nint main() { int n = 2; long l = 3; return (l << f(n + 1)) + (f(1) << (n << 1)); }",
+        24 + 16,
+    );
+    run("compound_shifts", "int main() { int x = 3; int n = 2; x <<= n; x <<= 1; x >>= 2; return x; }", 6);
+    run(
+        "unsigned_shift_amounts",
+        "int main() { unsigned x = 64u; return (x >> (1u <= ~31u)) + (x >> ((0u-1u)/2147483648u)); }",
+        32 + 32,
+    );
+}
+
+#[test]
+fn integer_literal_types() {
+    run(
+        "suffixes_and_sizes",
+        "int main() { long big = 3000000000; unsigned u = 0xffffffff; \
+         return (big / 1000000000 == 3) + (u + 1u == 0) * 2 + (-1L < 0) * 4 + (-1ul > 0) * 8 + (4294967296 >> 32 == 1) * 16; }",
+        31,
+    );
+    run("unsigned_long_maximum", "unsigned long all = 18446744073709551615u; int main() { return all == -1 && all / 2 == 9223372036854775807; }", 1);
+}
+
+#[test]
+fn aarch64() {
+    run(
+        "ten_mixed_parameters",
+        "int f(char a, int b, long c, int d, int e, int f, int g, int h, char i, long j) { return a + b + c + d + e + f + g + h + i * 2 + j * 3; }\n\
+         int main() { return f(1, 2, 3, 4, 5, 6, 7, 8, -9, (long)65536 * 65536 + 10); }",
+        36 - 18 + 30,
+    );
+    run(
+        "large_frame",
+        "int main() { char big[5000]; int a[3] = {70000, -70000, 4096}; long l = (long)a[0] * 70000; big[4999] = 7; \
+         return big[4999] + (a[0] + a[1] == 0) * 8 + (a[2] == 4096) * 16 + (l / 70000 == 70000) * 32; }",
+        63,
+    );
+    run_printing(
+        "variadic_arguments",
+        "int printf(const char *format, ...);\nint main() { char c = 'x'; long l = (long)-5 * 65536 * 65536; printf(\"%c %d %ld %s %d %d %d %d %d\\n\", c, 2, l, \"s\", 5, 6, 7, 8, 9); return 0; }",
+        0,
+        "x 2 -21474836480 s 5 6 7 8 9\n",
+    );
+    // `sdiv` doesn't trap, so without checks dividing by zero gives zero.
+    let options = codegen::Options { target: TargetSpec { arch: Architecture::Aarch64, ..TargetSpec::host() }, ..codegen::Options::default() };
+    let source = "int main() { int zero = 0; return 7 / zero + 1; }";
+    let assembly = compile_with(source, &options);
+    assert!(assembly.contains("    sdiv w0, w0, w1\n"), "{}", assembly);
+    execute("unchecked_division_by_zero", &assembly, options.target.arch, 1);
+}
+
+#[test]
+fn windows_calling_convention() {
+    let options = codegen::Options {
+        target: TargetSpec::new(Architecture::X86_64, codegen::TargetOs::Windows),
+        checked_arithmetic: true,
+        ..codegen::Options::default()
+    };
+    let source = "int puts(const char *s);\n\
+                  int f(int a, int b, int c, int d, int e, int g, int h) { int *p = &a; *p = 10 * a; return a + b + c + d + e * g + h; }\n\
+                  int main() { int x = 2; puts(\"windows\"); return f(1, x, 3, 4, 5, 6, 7) + 7 / x; }";
+    let assembly = compile_with(source, &options);
+    assert!(assembly.contains("    movq %r14, %rcx\n    movq %r13, %rdx\n    movq %r12, %r8\n    movq %rdi, %r9\n    subq $32, %rsp\n    call f\n    addq $64, %rsp\n"), "{}", assembly);
+    assert!(assembly.contains("    movl 48(%rbp), %eax\n"), "{}", assembly);
+    if let Some(printed) = execute_on_windows("windows_calling_convention", &assembly, 10 + 2 + 3 + 4 + 30 + 7 + 3) {
+        assert_eq!(printed, "windows\n");
+    }
+}
+
+#[test]
+fn intel_syntax() {
+    let options = codegen::Options {
+        target: TargetSpec { arch: Architecture::X86_64, ..TargetSpec::host() },
+        syntax: codegen::Syntax::Intel,
+        checked_arithmetic: true,
+        ..codegen::Options::default()
+    };
+    let source = "struct point { char tag; long x; };\n\
+                  long total;\n\
+                  int sum(int *values, int count) { int i; int s = 0; for (i = 0; i < count; i++) s += values[i]; return s; }\n\
+                  int classify(int x) { switch (x) { case 0: return 1; case 1: return 2; case 2: return 3; case 3: return 4; default: return 0; } }\n\
+                  int main() { int a[3] = {4, 5, 6}; struct point p; struct point *q = &p; p.tag = 'a'; q->x = 7; total = (long)-9 / 2; \
+                  puts(\"intel\"); return sum(a, 3) + classify(2) * 16 + (q->x == 7) * 64 + (total == -4) * 128 + (p.tag >> 5) - 3; }";
+    let assembly = compile_with(source, &options);
+    assert!(assembly.starts_with("    .intel_syntax noprefix\n"), "{}", assembly);
+    if let Some(printed) = execute("intel_syntax", &assembly, Architecture::X86_64, 15 + 48 + 64 + 128 + 3 - 3) {
+        assert_eq!(printed, "intel\n");
+    }
+}
+
+/// The instructions in the x86-64 `assembly`, and how many of them read or
+/// write memory.
+fn count_instructions(assembly: &str) -> (usize, usize) {
+    let instructions: Vec<_> = assembly
+        .lines()
+        .filter(|line| line.starts_with("    ") && !line.trim_start().starts_with('.'))
+        .collect();
+    let memory = instructions
+        .iter()
+        .filter(|line| line.contains('(') || line.contains("push") || line.contains("pop"))
+        .count();
+    (instructions.len(), memory)
+}
+
+/// Compares the code for a function of some size with its temporaries in
+/// registers and with every one of them in the frame, as when each was pushed.
+/// Run with `--nocapture` to see the counts.
+#[test]
+fn temporaries_in_registers() {
+    let source = "int clamp(int x, int low, int high) { if (x < low) return low; if (x > high) return high; return x; }\n\
+                  int mix(int a, int b, int c) {\n\
+                      int i; int h = 17;\n\
+                      for (i = 0; i < a; i++) {\n\
+                          h = (h * 31 + (a - i) * (b + i)) % 65521 + clamp(c * i - b, a << 2, (b + c) * (a - 1));\n\
+                          h = h - ((h >> 3) + (i * i - (a + b) * (c - i))) / (b + 1);\n\
+                      }\n\
+                      return h;\n\
+                  }\n\
+                  int main() { return mix(10, 3, 4) % 256; }";
+    let allocated = run("temporaries_in_registers", source, 32);
+    let options = codegen::Options { spill_temporaries: true, ..codegen::Options::default() };
+    let spilled = run_with("temporaries_spilled", source, &options, 32, None);
+    let (allocated, spilled) = (count_instructions(&allocated), count_instructions(&spilled));
+    println!("instructions: {} allocated, {} spilled", allocated.0, spilled.0);
+    println!("memory accesses: {} allocated, {} spilled", allocated.1, spilled.1);
+    // A temporary takes as many instructions to save and restore wherever it
+    // is, but the registers a function preserves take more to save and restore
+    // in the prologue and each epilogue.
+    assert!(allocated.0 <= spilled.0 + 12, "{:?} {:?}", allocated, spilled);
+    assert!(allocated.1 * 3 < spilled.1 * 2, "{:?} {:?}", allocated, spilled);
+}
+
+#[test]
+fn peephole_optimization() {
+    let source = "int sum_odd(int n) {\n\
+                      int i = 0; int total = 0;\n\
+                      while (n - i) {\n\
+                          if (i % 2) total = total + (i << 0); else {}\n\
+                          i++;\n\
+                      }\n\
+                      return total;\n\
+                  }\n\
+                  int main() { return sum_odd(10); }";
+    let unoptimized = run("peephole_unoptimized", source, 25);
+    let options = codegen::Options { opt_level: codegen::OptLevel::O1, ..codegen::Options::default() };
+    let optimized = run_with("peephole_optimized", source, &options, 25, None);
+    let lines = |assembly: &str| assembly.lines().map(str::trim).map(str::to_owned).collect::<Vec<_>>();
+    let (unoptimized, optimized) = (lines(&unoptimized), lines(&optimized));
+    // `n - i` sets the flags the loop tests, and the empty `else` is jumped to
+    // just before its end.
+    let redundant = |lines: &[String]| {
+        let test = lines.windows(2).any(|pair| pair[0] == "subl %ecx, %eax" && pair[1] == "cmpl $0, %eax");
+        let jump = lines.windows(3).any(|run| run[0].starts_with("jmp ") && run[2] == format!("{}:", &run[0][4..]));
+        let shift = lines.iter().any(|line| line == "shll $0, %eax");
+        (test, jump, shift)
+    };
+    assert_eq!(redundant(&unoptimized), (true, true, true), "{:#?}", unoptimized);
+    assert_eq!(redundant(&optimized), (false, false, false), "{:#?}", optimized);
+    // What follows each `return` at the end of a function is unreachable.
+    assert_eq!(unoptimized.len(), optimized.len() + 14);
+}
+
+#[test]
+fn constant_folding() {
+    let options = codegen::Options { opt_level: codegen::OptLevel::O1, ..codegen::Options::default() };
+    let source = "int main() { if (2 > 1) return 2 * 3 + 4; return 1; }";
+    let unfolded = run("constant_folding_unfolded", source, 10);
+    let folded = run_with("constant_folding", source, &options, 10, None);
+    let body = |assembly: &str| assembly.lines().skip_while(|line| *line != "main:").take_while(|line| line.trim() != "ret")
+        .map(str::trim).filter(|line| line.contains('$') || line.starts_with('j')).map(str::to_owned).collect::<Vec<_>>();
+    assert!(body(&unfolded).len() > 3, "{}", unfolded);
+    assert_eq!(body(&folded), vec!["movl $10, %eax"], "{}", folded);
+
+    // Folding leaves alone what the program would compute differently, and
+    // keeps side effects.
+    let source = "int calls;\n\
+                  int touch() { calls = calls + 1; return calls; }\n\
+                  int main() {\n\
+                      int total = 2 * 3 + 4;\n\
+                      if (1 && touch()) total = total + 1;\n\
+                      if (0 || touch() - 2) total = total + 100;\n\
+                      total = total + (-1 < (unsigned)1) * 50;\n\
+                      while (0) total = 0;\n\
+                      return total + ((char)300 >> 2) + calls;\n\
+                  }";
+    run("constant_folding_unfolded_effects", source, 24);
+    run_with("constant_folding_effects", source, &options, 24, None);
+
+    // A `case` label in a branch that is never taken can still be jumped to.
+    let source = "int main() { int x = 2; switch (x) { case 1: if (0) { case 2: return 5; } return 1; } return 0; }";
+    let assembly = compile_with(source, &codegen::Options { target: TargetSpec { arch: Architecture::X86_64, ..options.target }, ..options.clone() });
+    assert!(assembly.contains(".Lmain_case_"), "{}", assembly);
+    execute("constant_folding_label", &assembly, Architecture::X86_64, 5);
+}
+
+#[test]
+fn dead_store_elimination() {
+    let options = codegen::Options { opt_level: codegen::OptLevel::O1, ..codegen::Options::default() };
+    let stores = |assembly: &str, slot: &str| assembly.lines()
+        .filter(|line| line.trim_start().starts_with("movl") && line.ends_with(&format!(", {}(%rbp)", slot)))
+        .count();
+    let source = "int two() { return 2; } int main() { int x; x = 1; x = two(); return x; }";
+    assert_eq!(stores(&run("dead_stores_kept", source, 2), "-4"), 2);
+    assert_eq!(stores(&run_with("dead_stores", source, &options, 2, None), "-4"), 1);
+
+    // Through a pointer, either store might be read.
+    let source = "int main() { int x; int *p = &x; x = 1; x = 2; return *p; }";
+    assert_eq!(stores(&run_with("dead_stores_address_taken", source, &options, 2, None), "-4"), 2);
+
+    // The store to `x` at the end of each iteration is read in the next, but
+    // the one after the loop never is.
+    let source = "int main() {\n\
+                      int i = 0; int last = 0; int x = 5;\n\
+                      while (i < 3) { last = x; x = i; i++; }\n\
+                      x = 7;\n\
+                      return last * 10 + i;\n\
+                  }";
+    assert_eq!(stores(&run("dead_stores_loop_unoptimized", source, 13), "-12"), 3);
+    assert_eq!(stores(&run_with("dead_stores_loop", source, &options, 13, None), "-12"), 2);
+}
+
+#[test]
+fn constant_propagation() {
+    let options = codegen::Options { opt_level: codegen::OptLevel::O2, ..codegen::Options::default() };
+    let body = |assembly: &str| assembly.lines().skip_while(|line| *line != "main:").take_while(|line| line.trim() != "ret")
+        .map(str::trim).filter(|line| line.starts_with("movl")).map(str::to_owned).collect::<Vec<_>>();
+    let source = "int main() { int x = 5; return x + 2; }";
+    run("constant_propagation_unoptimized", source, 7);
+    let assembly = run_with("constant_propagation", source, &options, 7, None);
+    assert_eq!(body(&assembly), vec!["movl $7, %eax"], "{}", assembly);
+
+    // An `if` that may assign `x` leaves it unknown after, and so does a loop.
+    let source = "int pick(int c) { int x = 1; if (c) x = 2; return x; }
+                  int count(int n) { int i = 0; int x = 3; while (i < n) { x = x + i; i++; } return x; }
+                  int main() { return pick(0) + pick(5) * 10 + count(4) * 100; }";
+    run("constant_propagation_branches_unoptimized", source, 921 % 256);
+    run_with("constant_propagation_branches", source, &options, 921 % 256, None);
+}
+
+/// The lines of the function `name` in the x86-64 `assembly`.
+fn function_lines<'a>(assembly: &'a str, name: &str) -> Vec<&'a str> {
+    let start = format!("{}:", name);
+    assembly.lines().skip_while(|line| *line != start).skip(1)
+        .take_while(|line| line.starts_with(' ') || line.starts_with(".L"))
+        .collect()
+}
+
+#[test]
+fn strength_reduction() {
+    // Each result is checked against a division by a divisor that isn't constant.
+    let source = "int quotient(int x, int d) { return x / d; }\n\
+                  int remainder(int x, int d) { return x % d; }\n\
+                  long lquotient(long x, long d) { return x / d; }\n\
+                  long lremainder(long x, long d) { return x % d; }\n\
+                  unsigned uquotient(unsigned x, unsigned d) { return x / d; }\n\
+                  unsigned uremainder(unsigned x, unsigned d) { return x % d; }\n\
+                  unsigned long ulremainder(unsigned long x, unsigned long d) { return x % d; }\n\
+                  int main() {\n\
+                      int wrong = 0; int x;\n\
+                      for (x = -300; x <= 300; x = x + 7) {\n\
+                          long y = (long)x * 100000007;\n\
+                          unsigned u = x;\n\
+                          unsigned long v = y;\n\
+                          wrong = wrong + (x / 4 != quotient(x, 4)) + (x % 16 != remainder(x, 16));\n\
+                          wrong = wrong + (x * 8 != x * quotient(16, 2)) + (2 * x != x + x);\n\
+                          wrong = wrong + (x / 1 != x) + (x % 1 != 0) + (x / 1073741824 != 0) + (x % 2 != remainder(x, 2));\n\
+                          wrong = wrong + (y / 1024 != lquotient(y, 1024)) + (y % ((long)1 << 40) != lremainder(y, (long)1 << 40));\n\
+                          wrong = wrong + (y % 64 != lremainder(y, 64)) + (y / ((long)1 << 35) != lquotient(y, (long)1 << 35));\n\
+                          wrong = wrong + (u / 8 != uquotient(u, 8)) + (u % 32 != uremainder(u, 32));\n\
+                          wrong = wrong + (u % ((unsigned)1 << 31) != uremainder(u, (unsigned)1 << 31));\n\
+                          wrong = wrong + (v % ((unsigned long)1 << 62) != ulremainder(v, (unsigned long)1 << 62));\n\
+                          wrong = wrong + (v % ((unsigned long)1 << 36) != ulremainder(v, (unsigned long)1 << 36));\n\
+                      }\n\
+                      return wrong;\n\
+                  }";
+    let unoptimized = run("strength_reduction_unoptimized", source, 0);
+    // The divisions to check against would otherwise be inlined into `main`.
+    let options = codegen::Options {
+        opt_level: codegen::OptLevel::O2,
+        disabled_passes: vec!["inline".to_owned()],
+        ..codegen::Options::default()
+    };
+    let optimized = run_with("strength_reduction", source, &options, 0, None);
+    let divisions = |assembly: &str| function_lines(assembly, "main").iter().filter(|line| line.contains("div")).count();
+    let multiplications = |assembly: &str| function_lines(assembly, "main").iter().filter(|line| line.contains("imul")).count();
+    assert!(divisions(&unoptimized) > 10, "{}", unoptimized);
+    assert_eq!(divisions(&optimized), 0, "{}", optimized);
+    // Only the multiplications by what isn't constant are left.
+    assert_eq!(multiplications(&optimized), 2, "{}", optimized);
+}
+
+#[test]
+fn jump_cleanup() {
+    let source = "int classify(int a, int b) {\n\
+                      int x;\n\
+                      if (a > 0) {\n\
+                          if (b > 0) return 1; else x = 2;\n\
+                      } else {\n\
+                          if (b > 0) { return 3; } else if (b < 0) { x = 4; } else { return 5; }\n\
+                      }\n\
+                      return x;\n\
+                  }\n\
+                  int main() { return classify(1, 1) + classify(1, -1) * 10 + classify(-1, 1) + classify(-1, -1) * 2 + classify(0, 0); }";
+    let unoptimized = run("jump_cleanup_unoptimized", source, 37);
+    let options = codegen::Options { opt_level: codegen::OptLevel::O1, ..codegen::Options::default() };
+    let optimized = run_with("jump_cleanup", source, &options, 37, None);
+    let jumps = |assembly: &str| function_lines(assembly, "classify").iter().filter(|line| line.trim_start().starts_with('j')).count();
+    // The jump over the `else` after each `return` can never be reached.
+    assert_eq!(jumps(&unoptimized), 8, "{}", unoptimized);
+    assert_eq!(jumps(&optimized), 6, "{}", optimized);
+}
+
+#[test]
+fn inlining() {
+    let source = "static int sq(int x) { return x * x; }\n\
+                  int n;\n\
+                  int next() { n = n * 10 + 1; return n; }\n\
+                  int sub(int a, int b) { return a - b; }\n\
+                  long scale(long x, char by) { return sub(x, 1) * by; }\n\
+                  int fact(int n) { if (n < 2) return 1; return n * fact(n - 1); }\n\
+                  int main() {\n\
+                      int x = 3;\n\
+                      int once = sq(next()) + n;\n\
+                      return sq(x + 1) + sub(sq(2), sub(x, 1)) + scale(5, 2) + fact(4) + once;\n\
+                  }";
+    // An argument is evaluated once, however often the parameter is used.
+    let status = 16 + 2 + 8 + 24 + 2;
+    let calls = |assembly: &str, name: &str| function_lines(assembly, "main").iter().filter(|line| line.trim() == format!("call {}", name)).count();
+    let unoptimized = run("inlining_unoptimized", source, status);
+    let options = codegen::Options { opt_level: codegen::OptLevel::O2, ..codegen::Options::default() };
+    let optimized = run_with("inlining", source, &options, status, None);
+    assert_eq!(calls(&unoptimized, "sq"), 3, "{}", unoptimized);
+    assert_eq!((calls(&optimized, "sq"), calls(&optimized, "sub"), calls(&optimized, "scale")), (0, 0, 0), "{}", optimized);
+    // Neither a function with more than a `return` nor a recursive one is inlined.
+    assert_eq!((calls(&optimized, "next"), calls(&optimized, "fact")), (1, 1), "{}", optimized);
+    let options = codegen::Options { disabled_passes: vec!["inline".to_owned()], ..options };
+    let called = run_with("inlining_disabled", source, &options, status, None);
+    assert_eq!(calls(&called, "sq"), 3, "{}", called);
+}
+
+#[test]
+fn optimization_levels() {
+    let source = "int scale(int x) { return x * 8; }\n\
+                  int main() {\n\
+                      int n = 3; int total = 0; int i;\n\
+                      for (i = 0; i < 10; i++) total = total + scale(i) % 5;\n\
+                      return total + n * (4 + 3);\n\
+                  }";
+    let at = |opt_level: codegen::OptLevel, disabled: &[&str]| codegen::Options {
+        opt_level,
+        disabled_passes: disabled.iter().map(|&name| name.to_owned()).collect(),
+        ..codegen::Options::default()
+    };
+    let o0 = run_with("optimization_levels_o0", source, &at(codegen::OptLevel::O0, &[]), 41, None);
+    let o1 = run_with("optimization_levels_o1", source, &at(codegen::OptLevel::O1, &[]), 41, None);
+    let o2 = run_with("optimization_levels_o2", source, &at(codegen::OptLevel::O2, &[]), 41, None);
+    let unfolded = run_with("optimization_levels_unfolded", source, &at(codegen::OptLevel::O1, &["fold"]), 41, None);
+    let has = |assembly: &str, function: &str, text: &str| function_lines(assembly, function).iter().any(|line| line.contains(text));
+    // `4 + 3` is folded from -O1, unless folding is turned off.
+    assert!(!has(&o0, "main", "$7,") && has(&o1, "main", "$7,") && !has(&unfolded, "main", "$7,"), "{}", o1);
+    // `n` is only propagated, `x * 8` only shifted, and `scale` only inlined at -O2.
+    assert!(has(&o1, "main", "call scale") && has(&o1, "scale", "imul"), "{}", o1);
+    assert!(!has(&o2, "main", "call scale") && has(&o2, "scale", "shll $3") && has(&o2, "main", "$21,"), "{}", o2);
+    for name in codegen::pass_names() {
+        run_with("optimization_levels_disabled", source, &at(codegen::OptLevel::O2, &[name]), 41, None);
+    }
+}
+
+#[test]
+fn labels_are_unique_in_the_file() {
+    let source = "int sign(int x) { if (x < 0) return -1; else if (x > 0) return 1; return 0; }\n\
+                  int clamp(int x) { if (x > 9) x = 9; else if (x < -9) x = -9; return x; }\n\
+                  int count(int n) { int i = 0; int odd = 0; while (i < n) { if (i % 2 && i != 5) odd++; i++; } return odd; }\n\
+                  int main() { return sign(-4) + clamp(12) + count(10) * 10; }";
+    let assembly = run("labels_are_unique_in_the_file", source, 48);
+    let labels: Vec<_> = assembly.lines().filter(|line| line.starts_with(".L") && line.ends_with(':')).collect();
+    for function in &["sign", "clamp", "count"] {
+        let prefix = format!(".L{}_", function);
+        assert!(labels.iter().filter(|label| label.starts_with(&prefix)).count() >= 2, "{}", assembly);
+    }
+    // The same program gets the same labels every time.
+    assert_eq!(compile(source), assembly);
+}
+
+#[test]
+fn debug_lines() {
+    let source = "int add(int a, int b) {\n    int c = a + b;\n    return c;\n}\nint main() {\n    return add(1, 2);\n}\n";
+    let options = codegen::Options { source_path: Some("debug_lines.c".to_owned()), ..codegen::Options::default() };
+    let assembly = run_with("debug_lines", source, &options, 3, None);
+    let commented = codegen::Options { source: Some(source.to_owned()), source_comments: true, ..options.clone() };
+    let with_comments = run_with("debug_lines_commented", source, &commented, 3, None);
+    assert!(with_comments.contains("    # 2: int c = a + b;\n    .loc 1 2 9\n"), "{}", with_comments);
+    for line in &[".file 1 \"debug_lines.c\"", ".loc 1 1 1", ".loc 1 2 9", ".loc 1 3 5", ".loc 1 5 1", ".loc 1 6 5"] {
+        assert!(assembly.lines().any(|candidate| candidate.trim() == *line), "missing `{}` in:\n{}", line, assembly);
+    }
+
+    // The assembler makes a line table of the positions, which maps the start
+    // of each function back to the line it is defined on.
+    let installed = |program: &str| Command::new(program).arg("--version").output().is_ok();
+    if !executing() || !installed("nm") || !installed("addr2line") {
+        return;
+    }
+    let assembly = compile_with(source, &codegen::Options { target: TargetSpec { arch: Architecture::host(), ..options.target }, ..options });
+    let directory = env::temp_dir().join(format!("rust-cc-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let (assembly_path, executable) = (directory.join("debug_lines.s"), directory.join("debug_lines.exe"));
+    fs::write(&assembly_path, &assembly).unwrap();
+    assert!(Command::new("gcc").arg("-o").arg(&executable).arg(&assembly_path).status().unwrap().success());
+    let symbols = String::from_utf8(Command::new("nm").arg(&executable).output().unwrap().stdout).unwrap();
+    for &(function, line) in &[("add", 1), ("main", 5)] {
+        let address = symbols.lines()
+            .map(|symbol| symbol.split_whitespace().collect::<Vec<_>>())
+            .find(|words| words.len() == 3 && words[2] == function)
+            .map(|words| format!("0x{}", words[0]))
+            .unwrap();
+        let found = Command::new("addr2line").arg("-s").arg("-e").arg(&executable).arg(&address).output().unwrap();
+        let found = String::from_utf8(found.stdout).unwrap();
+        assert_eq!(found.trim(), format!("debug_lines.c:{}", line), "{}", assembly);
+    }
+    fs::remove_file(&assembly_path).unwrap();
+    fs::remove_file(&executable).unwrap();
+}
+
+#[test]
+fn llvm_ir() {
+    let source = "struct point { char tag; long x; int y; };\n\
+                  struct point origin = { 1, 2, 3 };\n\
+                  int table[5] = { 1, 2 };\n\
+                  int *second = &table[1];\n\
+                  int printf(const char *format, ...);\n\
+                  int sum(int *values, int n) { int total = 0; for (int i = 0; i < n; i++) total += values[i]; return total; }\n\
+                  char upper(char c) { return c - 32; }\n\
+                  int main() {\n\
+                      struct point p = { 5 };\n\
+                      int a[3] = { 4, 5 };\n\
+                      char s[4] = \"abc\";\n\
+                      int i = 0;\n\
+                      while (i < 10) { i++; if (i == 5) break; }\n\
+                      switch (i) { case 5: i = 10; case 6: i += 20; break; default: i = 0; }\n\
+                      printf(\"%d %c %s %ld\\n\", sum(a, 3), upper('a'), s, origin.x + p.x);\n\
+                      return i + *second + (int)p.tag + (s + 3 - s);\n\
+                  }";
+    run_printing("llvm_ir", source, 30 + 2 + 5 + 3, "9 A abc 2\n");
+    let ir = compile_with(source, &codegen::Options { format: codegen::Format::LlvmIr, ..codegen::Options::default() });
+    for line in &[
+        "%struct.point = type { i8, i64, i32 }",
+        "@second = global ptr getelementptr (i8, ptr @table, i64 4)",
+        "define signext i8 @upper(i8 signext %0) {",
+        "  %p = alloca %struct.point",
+        "declare i32 @printf(ptr, ...)",
+    ] {
+        assert!(ir.lines().any(|candidate| candidate == *line), "missing `{}` in:\n{}", line, ir);
+    }
+}
+
+#[test]
+fn webassembly() {
+    let source = "struct point { char tag; long x; int y; };\n\
+                  struct point origin = { 1, 2, 3 };\n\
+                  int table[5] = { 1, 2 };\n\
+                  int *second = &table[1];\n\
+                  char *greeting = \"hi\";\n\
+                  int sum(int *values, int n) { int total = 0; for (int i = 0; i < n; i++) total += values[i]; return total; }\n\
+                  int bump(int x) { int *p = &x; *p += 1; return x; }\n\
+                  int classify(int v) { switch (v) { case 0: return 10; case 1: case 2: return 20; case 3: v++; case 4: return v; default: return -1; } }\n\
+                  int main() {\n\
+                      struct point p = { 5 };\n\
+                      int a[3] = { 4, 5 };\n\
+                      char s[4] = \"abc\";\n\
+                      char c = 127;\n\
+                      long big = 1;\n\
+                      static int calls = 2;\n\
+                      c++;\n\
+                      big = big << 40;\n\
+                      if (sum(a, 3) != 9 || bump(4) != 5 || c != -128 || big >> 38 != 4) return 1;\n\
+                      if (classify(0) + classify(2) + classify(3) + classify(9) != 33) return 2;\n\
+                      if (greeting[1] != 'i' || s[2] != 'c') return 3;\n\
+                      calls++;\n\
+                      return origin.x + p.x + *second + p.tag + (s + 3 - s) + calls;\n\
+                  }";
+    run("webassembly", source, 2 + 2 + 5 + 3 + 3);
+    let wat = compile_with(source, &codegen::Options { format: codegen::Format::Wat, ..codegen::Options::default() });
+    for line in &[
+        "  (data (i32.const 64) \",\\00\\00\\00\\00\\00\\00\\00\")",
+        "  (func $bump (export \"bump\") (param $x i32) (result i32)",
+        "                br_table $sw.case2 $sw.case3 $sw.case3 $sw.case4 $sw.case5 $sw.default6",
+        "    i32.extend8_s",
+        "    memory.copy",
+    ] {
+        assert!(wat.lines().any(|candidate| candidate == *line), "missing `{}` in:\n{}", line, wat);
+    }
+}
@@ -0,0 +1,203 @@
+    .text
+    .globl _mix
+_mix:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    stur w1, [x29, #-8]
+    stur w2, [x29, #-12]
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-12]
+    mov w1, w0
+    mov x0, x11
+    mul w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-4]
+    mov x12, x0
+    ldur w0, [x29, #-8]
+    mov x13, x0
+    mov w0, #1
+    mov w1, w0
+    mov x0, x13
+    add w0, w0, w1
+    mov w1, w0
+    mov x0, x12
+    sdiv w0, w0, w1
+    mov x12, x0
+    mov w0, #7
+    mov w1, w0
+    mov x0, x12
+    sdiv w9, w0, w1
+    msub w0, w9, w1, w0
+    mov w1, w0
+    mov x0, x11
+    sub w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-12]
+    lsl w0, w0, #3
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-4]
+    asr w0, w0, #2
+    mov w1, w0
+    mov x0, x11
+    sub w0, w0, w1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl _widen
+_widen:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    stur x1, [x29, #-16]
+    ldur w0, [x29, #-4]
+    sxtw x0, w0
+    mov x11, x0
+    ldur x0, [x29, #-16]
+    mov x1, x0
+    mov x0, x11
+    mul x0, x0, x1
+    mov x11, x0
+    ldur x0, [x29, #-16]
+    mov x12, x0
+    ldur w0, [x29, #-4]
+    sxtw x0, w0
+    mov x1, x0
+    mov x0, x12
+    sub x0, x0, x1
+    mov x1, x0
+    mov x0, x11
+    add x0, x0, x1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl _scale
+_scale:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    lsl w0, w0, #3
+    lsr w0, w0, #2
+    mov x11, x0
+    ldur w0, [x29, #-4]
+    ubfx w0, w0, #0, #4
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl _negate
+_negate:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    neg w0, w0
+    mov x11, x0
+    ldur w0, [x29, #-4]
+    cmp w0, #0
+    cset w0, eq
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl _main
+_main:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #32
+    mov w0, #5
+    mov x11, x0
+    mov w0, #3
+    mov x12, x0
+    mov w0, #7
+    mov x13, x0
+    mov x0, x13
+    mov x1, x12
+    mov x2, x11
+    bl _mix
+    mov x11, x0
+    mov w0, #10
+    sxtw x0, w0
+    mov x12, x0
+    mov w0, #2
+    mov x13, x0
+    mov x0, x13
+    stur w0, [x29, #-4]
+    mov x0, x12
+    stur x0, [x29, #-16]
+    ldur w0, [x29, #-4]
+    sxtw x0, w0
+    mov x12, x0
+    ldur x0, [x29, #-16]
+    mov x1, x0
+    mov x0, x12
+    mul x0, x0, x1
+    mov x12, x0
+    ldur x0, [x29, #-16]
+    mov x13, x0
+    ldur w0, [x29, #-4]
+    sxtw x0, w0
+    mov x1, x0
+    mov x0, x13
+    sub x0, x0, x1
+    mov x1, x0
+    mov x0, x12
+    add x0, x0, x1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    mov w0, #9
+    mov x12, x0
+    mov x0, x12
+    stur w0, [x29, #-20]
+    ldur w0, [x29, #-20]
+    lsl w0, w0, #3
+    lsr w0, w0, #2
+    mov x12, x0
+    ldur w0, [x29, #-20]
+    ubfx w0, w0, #0, #4
+    mov w1, w0
+    mov x0, x12
+    add w0, w0, w1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    mov w0, #0
+    mov x12, x0
+    mov x0, x12
+    stur w0, [x29, #-24]
+    ldur w0, [x29, #-24]
+    neg w0, w0
+    mov x12, x0
+    ldur w0, [x29, #-24]
+    cmp w0, #0
+    cset w0, eq
+    mov w1, w0
+    mov x0, x12
+    add w0, w0, w1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
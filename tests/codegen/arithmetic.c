@@ -0,0 +1,19 @@
+int mix(int a, int b, int c) {
+    return (a + b) * c - a / (b + 1) % 7 + (c << 3) - (a >> 2);
+}
+
+long widen(int x, long y) {
+    return x * y + (y - x);
+}
+
+unsigned int scale(unsigned int x) {
+    return x * 8 / 4 + x % 16;
+}
+
+int negate(int x) {
+    return -x + !x;
+}
+
+int main() {
+    return mix(7, 3, 5) + (int)widen(2, 10) + scale(9) + negate(0);
+}
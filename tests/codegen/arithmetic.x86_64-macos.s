@@ -0,0 +1,196 @@
+    .text
+    .globl _mix
+_mix:
+    subq $40, %rsp
+    movq %rbx, 8(%rsp)
+    movl %edi, 28(%rsp)
+    movl %esi, 24(%rsp)
+    movl %edx, 20(%rsp)
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl 24(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 20(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    imull %ecx, %eax
+    movq %rax, %r10
+    movl 28(%rsp), %eax
+    movq %rax, %r11
+    movl 24(%rsp), %eax
+    movq %rax, %rbx
+    movl $1, %eax
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    cltd
+    idivl %ecx
+    movq %rax, %r11
+    movl $7, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    cltd
+    idivl %ecx
+    movl %edx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    subl %ecx, %eax
+    movq %rax, %r10
+    movl 20(%rsp), %eax
+    shll $3, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 28(%rsp), %eax
+    sarl $2, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    subl %ecx, %eax
+    movq 8(%rsp), %rbx
+    addq $40, %rsp
+    ret
+    .globl _widen
+_widen:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movq %rsi, (%rsp)
+    movl 12(%rsp), %eax
+    movslq %eax, %rax
+    movq %rax, %r10
+    movq (%rsp), %rax
+    movq %rax, %rcx
+    movq %r10, %rax
+    imulq %rcx, %rax
+    movq %rax, %r10
+    movq (%rsp), %rax
+    movq %rax, %r11
+    movl 12(%rsp), %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    subq %rcx, %rax
+    movq %rax, %rcx
+    movq %r10, %rax
+    addq %rcx, %rax
+    addq $24, %rsp
+    ret
+    .globl _scale
+_scale:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl 12(%rsp), %eax
+    shll $3, %eax
+    shrl $2, %eax
+    movq %rax, %r10
+    movl 12(%rsp), %eax
+    andl $15, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    addq $24, %rsp
+    ret
+    .globl _negate
+_negate:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl 12(%rsp), %eax
+    negl %eax
+    movq %rax, %r10
+    movl 12(%rsp), %eax
+    cmpl $0, %eax
+    sete %al
+    movzbl %al, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    addq $24, %rsp
+    ret
+    .globl _main
+_main:
+    subq $40, %rsp
+    movq %rbx, (%rsp)
+    movl $5, %eax
+    movq %rax, %r10
+    movl $3, %eax
+    movq %rax, %r11
+    movl $7, %eax
+    movq %rax, %rbx
+    movq %rbx, %rdi
+    movq %r11, %rsi
+    movq %r10, %rdx
+    call _mix
+    movq %rax, %r10
+    movl $10, %eax
+    movslq %eax, %rax
+    movq %rax, %r11
+    movl $2, %eax
+    movq %rax, %rbx
+    movq %rbx, %rax
+    movl %eax, 28(%rsp)
+    movq %r11, %rax
+    movq %rax, 16(%rsp)
+    movl 28(%rsp), %eax
+    movslq %eax, %rax
+    movq %rax, %r11
+    movq 16(%rsp), %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    imulq %rcx, %rax
+    movq %rax, %r11
+    movq 16(%rsp), %rax
+    movq %rax, %rbx
+    movl 28(%rsp), %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %rbx, %rax
+    subq %rcx, %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    addq %rcx, %rax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl $9, %eax
+    movq %rax, %r11
+    movq %r11, %rax
+    movl %eax, 12(%rsp)
+    movl 12(%rsp), %eax
+    shll $3, %eax
+    shrl $2, %eax
+    movq %rax, %r11
+    movl 12(%rsp), %eax
+    andl $15, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    addl %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl $0, %eax
+    movq %rax, %r11
+    movq %r11, %rax
+    movl %eax, 8(%rsp)
+    movl 8(%rsp), %eax
+    negl %eax
+    movq %rax, %r11
+    movl 8(%rsp), %eax
+    cmpl $0, %eax
+    sete %al
+    movzbl %al, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    addl %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq (%rsp), %rbx
+    addq $40, %rsp
+    ret
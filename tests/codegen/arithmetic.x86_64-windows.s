@@ -0,0 +1,208 @@
+    .text
+    .globl mix
+mix:
+    subq $56, %rsp
+    movq %rsi, 8(%rsp)
+    movq %rbx, 24(%rsp)
+    movl %ecx, 44(%rsp)
+    movl %edx, 40(%rsp)
+    movl %r8d, 36(%rsp)
+    movl 44(%rsp), %eax
+    movq %rax, %r10
+    movl 40(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 36(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    imull %ecx, %eax
+    movq %rax, %r10
+    movl 44(%rsp), %eax
+    movq %rax, %r11
+    movl 40(%rsp), %eax
+    movq %rax, %rbx
+    movl $1, %eax
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    cltd
+    idivl %ecx
+    movq %rax, %r11
+    movl $7, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    cltd
+    idivl %ecx
+    movl %edx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    subl %ecx, %eax
+    movq %rax, %r10
+    movl 36(%rsp), %eax
+    shll $3, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 44(%rsp), %eax
+    sarl $2, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    subl %ecx, %eax
+    movq 24(%rsp), %rbx
+    movq 8(%rsp), %rsi
+    addq $56, %rsp
+    ret
+    .globl widen
+widen:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movl %ecx, 28(%rsp)
+    movq %rdx, 16(%rsp)
+    movl 28(%rsp), %eax
+    movslq %eax, %rax
+    movq %rax, %r10
+    movq 16(%rsp), %rax
+    movq %rax, %rcx
+    movq %r10, %rax
+    imulq %rcx, %rax
+    movq %rax, %r10
+    movq 16(%rsp), %rax
+    movq %rax, %r11
+    movl 28(%rsp), %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    subq %rcx, %rax
+    movq %rax, %rcx
+    movq %r10, %rax
+    addq %rcx, %rax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .globl scale
+scale:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movl %ecx, 28(%rsp)
+    movl 28(%rsp), %eax
+    shll $3, %eax
+    shrl $2, %eax
+    movq %rax, %r10
+    movl 28(%rsp), %eax
+    andl $15, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .globl negate
+negate:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movl %ecx, 28(%rsp)
+    movl 28(%rsp), %eax
+    negl %eax
+    movq %rax, %r10
+    movl 28(%rsp), %eax
+    cmpl $0, %eax
+    sete %al
+    movzbl %al, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .globl main
+main:
+    subq $56, %rsp
+    movq %rsi, 8(%rsp)
+    movq %rbx, 16(%rsp)
+    movl $5, %eax
+    movq %rax, %r10
+    movl $3, %eax
+    movq %rax, %r11
+    movl $7, %eax
+    movq %rax, %rbx
+    movq %rbx, %rcx
+    movq %r11, %rdx
+    movq %r10, %r8
+    subq $32, %rsp
+    call mix
+    addq $32, %rsp
+    movq %rax, %r10
+    movl $10, %eax
+    movslq %eax, %rax
+    movq %rax, %r11
+    movl $2, %eax
+    movq %rax, %rbx
+    movq %rbx, %rax
+    movl %eax, 44(%rsp)
+    movq %r11, %rax
+    movq %rax, 32(%rsp)
+    movl 44(%rsp), %eax
+    movslq %eax, %rax
+    movq %rax, %r11
+    movq 32(%rsp), %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    imulq %rcx, %rax
+    movq %rax, %r11
+    movq 32(%rsp), %rax
+    movq %rax, %rbx
+    movl 44(%rsp), %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %rbx, %rax
+    subq %rcx, %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    addq %rcx, %rax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl $9, %eax
+    movq %rax, %r11
+    movq %r11, %rax
+    movl %eax, 28(%rsp)
+    movl 28(%rsp), %eax
+    shll $3, %eax
+    shrl $2, %eax
+    movq %rax, %r11
+    movl 28(%rsp), %eax
+    andl $15, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    addl %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl $0, %eax
+    movq %rax, %r11
+    movq %r11, %rax
+    movl %eax, 24(%rsp)
+    movl 24(%rsp), %eax
+    negl %eax
+    movq %rax, %r11
+    movl 24(%rsp), %eax
+    cmpl $0, %eax
+    sete %al
+    movzbl %al, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    addl %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 16(%rsp), %rbx
+    movq 8(%rsp), %rsi
+    addq $56, %rsp
+    ret
@@ -0,0 +1,288 @@
+    .text
+    .globl fib
+fib:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur x19, [x29, #-16]
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    mov w0, #2
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, lt
+    cmp w0, #0
+    b.eq .Lfib_end_1
+    ldur w0, [x29, #-4]
+    ldur x19, [x29, #-16]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+.Lfib_end_1:
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    mov w0, #1
+    mov w1, w0
+    mov x0, x11
+    sub w0, w0, w1
+    mov x11, x0
+    mov x0, x11
+    bl fib
+    mov x19, x0
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    mov w0, #2
+    mov w1, w0
+    mov x0, x11
+    sub w0, w0, w1
+    mov x11, x0
+    mov x0, x11
+    bl fib
+    mov w1, w0
+    mov x0, x19
+    add w0, w0, w1
+    ldur x19, [x29, #-16]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl many
+many:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #32
+    stur w0, [x29, #-4]
+    stur w1, [x29, #-8]
+    stur w2, [x29, #-12]
+    stur w3, [x29, #-16]
+    stur w4, [x29, #-20]
+    stur w5, [x29, #-24]
+    stur w6, [x29, #-28]
+    stur w7, [x29, #-32]
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    lsl w0, w0, #1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-12]
+    mov x12, x0
+    mov w0, #3
+    mov w1, w0
+    mov x0, x12
+    mul w0, w0, w1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-16]
+    lsl w0, w0, #2
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-20]
+    mov x12, x0
+    mov w0, #5
+    mov w1, w0
+    mov x0, x12
+    mul w0, w0, w1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-24]
+    mov x12, x0
+    mov w0, #6
+    mov w1, w0
+    mov x0, x12
+    mul w0, w0, w1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-28]
+    mov x12, x0
+    mov w0, #7
+    mov w1, w0
+    mov x0, x12
+    mul w0, w0, w1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    ldur w0, [x29, #-32]
+    lsl w0, w0, #3
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+twice:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    ldur w0, [x29, #-4]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl nested
+nested:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #48
+    stur x19, [x29, #-16]
+    stur x20, [x29, #-24]
+    stur x21, [x29, #-32]
+    stur x22, [x29, #-40]
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    mov x0, x11
+    stur w0, [x29, #-8]
+    ldur w0, [x29, #-8]
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x19, x0
+    mov w0, #7
+    mov x11, x0
+    mov w0, #6
+    mov x12, x0
+    mov w0, #5
+    mov x13, x0
+    mov w0, #4
+    mov x14, x0
+    mov w0, #3
+    mov x15, x0
+    mov w0, #2
+    mov x20, x0
+    mov w0, #1
+    mov x21, x0
+    ldur w0, [x29, #-4]
+    mov x22, x0
+    mov x0, x22
+    mov x1, x21
+    mov x2, x20
+    mov x3, x15
+    mov x4, x14
+    mov x5, x13
+    mov x6, x12
+    mov x7, x11
+    bl many
+    mov w1, w0
+    mov x0, x19
+    add w0, w0, w1
+    mov x11, x0
+    mov x0, x11
+    stur w0, [x29, #-8]
+    ldur w0, [x29, #-8]
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    ldur x19, [x29, #-16]
+    ldur x20, [x29, #-24]
+    ldur x21, [x29, #-32]
+    ldur x22, [x29, #-40]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl main
+main:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #48
+    stur x19, [x29, #-16]
+    stur x20, [x29, #-24]
+    stur x21, [x29, #-32]
+    stur x22, [x29, #-40]
+    stur x23, [x29, #-48]
+    mov w0, #10
+    mov x11, x0
+    mov x0, x11
+    bl fib
+    mov x19, x0
+    mov w0, #3
+    mov x11, x0
+    mov x0, x11
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    mov x0, x11
+    stur w0, [x29, #-8]
+    ldur w0, [x29, #-8]
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x20, x0
+    mov w0, #7
+    mov x11, x0
+    mov w0, #6
+    mov x12, x0
+    mov w0, #5
+    mov x13, x0
+    mov w0, #4
+    mov x14, x0
+    mov w0, #3
+    mov x15, x0
+    mov w0, #2
+    mov x21, x0
+    mov w0, #1
+    mov x22, x0
+    ldur w0, [x29, #-4]
+    mov x23, x0
+    mov x0, x23
+    mov x1, x22
+    mov x2, x21
+    mov x3, x15
+    mov x4, x14
+    mov x5, x13
+    mov x6, x12
+    mov x7, x11
+    bl many
+    mov w1, w0
+    mov x0, x20
+    add w0, w0, w1
+    mov x11, x0
+    mov x0, x11
+    stur w0, [x29, #-8]
+    ldur w0, [x29, #-8]
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    mov w0, #100
+    mov w1, w0
+    mov x0, x11
+    sdiv w9, w0, w1
+    msub w0, w9, w1, w0
+    mov w1, w0
+    mov x0, x19
+    add w0, w0, w1
+    ldur x19, [x29, #-16]
+    ldur x20, [x29, #-24]
+    ldur x21, [x29, #-32]
+    ldur x22, [x29, #-40]
+    ldur x23, [x29, #-48]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .section .note.GNU-stack,"",@progbits
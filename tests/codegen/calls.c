@@ -0,0 +1,21 @@
+int fib(int n) {
+    if (n < 2)
+        return n;
+    return fib(n - 1) + fib(n - 2);
+}
+
+int many(int a, int b, int c, int d, int e, int f, int g, int h) {
+    return a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8;
+}
+
+static int twice(int x) {
+    return x + x;
+}
+
+int nested(int x) {
+    return twice(twice(x) + many(x, 1, 2, 3, 4, 5, 6, 7));
+}
+
+int main() {
+    return fib(10) + nested(3) % 100;
+}
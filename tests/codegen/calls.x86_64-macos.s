@@ -0,0 +1,275 @@
+    .text
+    .globl _fib
+_fib:
+    subq $24, %rsp
+    movq %rbx, (%rsp)
+    movl %edi, 12(%rsp)
+    movl 12(%rsp), %eax
+    movq %rax, %r10
+    movl $2, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setl %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je Lfib_end_1
+    movl 12(%rsp), %eax
+    movq (%rsp), %rbx
+    addq $24, %rsp
+    ret
+Lfib_end_1:
+    movl 12(%rsp), %eax
+    movq %rax, %r10
+    movl $1, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    subl %ecx, %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call _fib
+    movq %rax, %rbx
+    movl 12(%rsp), %eax
+    movq %rax, %r10
+    movl $2, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    subl %ecx, %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call _fib
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movq (%rsp), %rbx
+    addq $24, %rsp
+    ret
+    .globl _many
+_many:
+    subq $40, %rsp
+    movl %edi, 28(%rsp)
+    movl %esi, 24(%rsp)
+    movl %edx, 20(%rsp)
+    movl %ecx, 16(%rsp)
+    movl %r8d, 12(%rsp)
+    movl %r9d, 8(%rsp)
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl 24(%rsp), %eax
+    shll $1, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 20(%rsp), %eax
+    movq %rax, %r11
+    movl $3, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    imull %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 16(%rsp), %eax
+    shll $2, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 12(%rsp), %eax
+    movq %rax, %r11
+    movl $5, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    imull %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 8(%rsp), %eax
+    movq %rax, %r11
+    movl $6, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    imull %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 48(%rsp), %eax
+    movq %rax, %r11
+    movl $7, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    imull %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 56(%rsp), %eax
+    shll $3, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    addq $40, %rsp
+    ret
+_twice:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl 12(%rsp), %eax
+    movq %rax, %r10
+    movl 12(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    addq $24, %rsp
+    ret
+    .globl _nested
+_nested:
+    subq $72, %rsp
+    movq %rbx, 32(%rsp)
+    movq %r12, 24(%rsp)
+    movq %r13, 16(%rsp)
+    movq %r14, 8(%rsp)
+    movq %r15, (%rsp)
+    movl %edi, 60(%rsp)
+    movl 60(%rsp), %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 56(%rsp)
+    movl 56(%rsp), %eax
+    movq %rax, %r10
+    movl 56(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, 48(%rsp)
+    movl $7, %eax
+    movq %rax, %r10
+    movl $6, %eax
+    movq %rax, %r11
+    movl $5, %eax
+    movq %rax, %r12
+    movl $4, %eax
+    movq %rax, %r13
+    movl $3, %eax
+    movq %rax, %r14
+    movl $2, %eax
+    movq %rax, %r15
+    movl $1, %eax
+    movq %rax, %rbx
+    movl 60(%rsp), %eax
+    movq %rax, 40(%rsp)
+    pushq %r10
+    pushq %r11
+    movq 56(%rsp), %rdi
+    movq %rbx, %rsi
+    movq %r15, %rdx
+    movq %r14, %rcx
+    movq %r13, %r8
+    movq %r12, %r9
+    call _many
+    addq $16, %rsp
+    movl %eax, %ecx
+    movq 48(%rsp), %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 56(%rsp)
+    movl 56(%rsp), %eax
+    movq %rax, %r10
+    movl 56(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 32(%rsp), %rbx
+    movq 24(%rsp), %r12
+    movq 16(%rsp), %r13
+    movq 8(%rsp), %r14
+    movq (%rsp), %r15
+    addq $72, %rsp
+    ret
+    .globl _main
+_main:
+    subq $88, %rsp
+    movq %rbx, 40(%rsp)
+    movq %r12, 32(%rsp)
+    movq %r13, 24(%rsp)
+    movq %r14, 16(%rsp)
+    movq %r15, 8(%rsp)
+    movl $10, %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call _fib
+    movq %rax, 64(%rsp)
+    movl $3, %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 76(%rsp)
+    movl 76(%rsp), %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 72(%rsp)
+    movl 72(%rsp), %eax
+    movq %rax, %r10
+    movl 72(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, 56(%rsp)
+    movl $7, %eax
+    movq %rax, %r10
+    movl $6, %eax
+    movq %rax, %r11
+    movl $5, %eax
+    movq %rax, %r13
+    movl $4, %eax
+    movq %rax, %r14
+    movl $3, %eax
+    movq %rax, %r15
+    movl $2, %eax
+    movq %rax, %rbx
+    movl $1, %eax
+    movq %rax, %r12
+    movl 76(%rsp), %eax
+    movq %rax, 48(%rsp)
+    pushq %r10
+    pushq %r11
+    movq 64(%rsp), %rdi
+    movq %r12, %rsi
+    movq %rbx, %rdx
+    movq %r15, %rcx
+    movq %r14, %r8
+    movq %r13, %r9
+    call _many
+    addq $16, %rsp
+    movl %eax, %ecx
+    movq 56(%rsp), %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 72(%rsp)
+    movl 72(%rsp), %eax
+    movq %rax, %r10
+    movl 72(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl $100, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cltd
+    idivl %ecx
+    movl %edx, %eax
+    movl %eax, %ecx
+    movq 64(%rsp), %rax
+    addl %ecx, %eax
+    movq 40(%rsp), %rbx
+    movq 32(%rsp), %r12
+    movq 24(%rsp), %r13
+    movq 16(%rsp), %r14
+    movq 8(%rsp), %r15
+    addq $88, %rsp
+    ret
@@ -0,0 +1,296 @@
+    .text
+    .globl fib
+fib:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movq %rbx, 16(%rsp)
+    movl %ecx, 28(%rsp)
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl $2, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setl %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lfib_end_1
+    movl 28(%rsp), %eax
+    movq 16(%rsp), %rbx
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+.Lfib_end_1:
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl $1, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    subl %ecx, %eax
+    movq %rax, %r10
+    movq %r10, %rcx
+    subq $32, %rsp
+    call fib
+    addq $32, %rsp
+    movq %rax, %rbx
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl $2, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    subl %ecx, %eax
+    movq %rax, %r10
+    movq %r10, %rcx
+    subq $32, %rsp
+    call fib
+    addq $32, %rsp
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movq 16(%rsp), %rbx
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .globl many
+many:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movl %ecx, 28(%rsp)
+    movl %edx, 24(%rsp)
+    movl %r8d, 20(%rsp)
+    movl %r9d, 16(%rsp)
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl 24(%rsp), %eax
+    shll $1, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 20(%rsp), %eax
+    movq %rax, %r11
+    movl $3, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    imull %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 16(%rsp), %eax
+    shll $2, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 80(%rsp), %eax
+    movq %rax, %r11
+    movl $5, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    imull %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 88(%rsp), %eax
+    movq %rax, %r11
+    movl $6, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    imull %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 96(%rsp), %eax
+    movq %rax, %r11
+    movl $7, %eax
+    movl %eax, %ecx
+    movq %r11, %rax
+    imull %ecx, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl 104(%rsp), %eax
+    shll $3, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+twice:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movl %ecx, 28(%rsp)
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl 28(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .globl nested
+nested:
+    subq $88, %rsp
+    movq %rsi, 8(%rsp)
+    movq %rbx, 56(%rsp)
+    movq %rdi, 48(%rsp)
+    movq %r12, 40(%rsp)
+    movq %r13, 32(%rsp)
+    movq %r14, 24(%rsp)
+    movq %r15, 16(%rsp)
+    movl %ecx, 76(%rsp)
+    movl 76(%rsp), %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 72(%rsp)
+    movl 72(%rsp), %eax
+    movq %rax, %r10
+    movl 72(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, 64(%rsp)
+    movl $7, %eax
+    movq %rax, %r10
+    movl $6, %eax
+    movq %rax, %r11
+    movl $5, %eax
+    movq %rax, %rdi
+    movl $4, %eax
+    movq %rax, %r12
+    movl $3, %eax
+    movq %rax, %r13
+    movl $2, %eax
+    movq %rax, %r14
+    movl $1, %eax
+    movq %rax, %r15
+    movl 76(%rsp), %eax
+    movq %rax, %rbx
+    pushq %r10
+    pushq %r11
+    pushq %rdi
+    pushq %r12
+    movq %rbx, %rcx
+    movq %r15, %rdx
+    movq %r14, %r8
+    movq %r13, %r9
+    subq $32, %rsp
+    call many
+    addq $64, %rsp
+    movl %eax, %ecx
+    movq 64(%rsp), %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 72(%rsp)
+    movl 72(%rsp), %eax
+    movq %rax, %r10
+    movl 72(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 56(%rsp), %rbx
+    movq 48(%rsp), %rdi
+    movq 40(%rsp), %r12
+    movq 32(%rsp), %r13
+    movq 24(%rsp), %r14
+    movq 16(%rsp), %r15
+    movq 8(%rsp), %rsi
+    addq $88, %rsp
+    ret
+    .globl main
+main:
+    subq $104, %rsp
+    movq %rsi, 8(%rsp)
+    movq %rbx, 64(%rsp)
+    movq %rdi, 56(%rsp)
+    movq %r12, 48(%rsp)
+    movq %r13, 40(%rsp)
+    movq %r14, 32(%rsp)
+    movq %r15, 24(%rsp)
+    movl $10, %eax
+    movq %rax, %r10
+    movq %r10, %rcx
+    subq $32, %rsp
+    call fib
+    addq $32, %rsp
+    movq %rax, 80(%rsp)
+    movl $3, %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 92(%rsp)
+    movl 92(%rsp), %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 88(%rsp)
+    movl 88(%rsp), %eax
+    movq %rax, %r10
+    movl 88(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, 72(%rsp)
+    movl $7, %eax
+    movq %rax, %r10
+    movl $6, %eax
+    movq %rax, %r11
+    movl $5, %eax
+    movq %rax, %r12
+    movl $4, %eax
+    movq %rax, %r13
+    movl $3, %eax
+    movq %rax, %r14
+    movl $2, %eax
+    movq %rax, %r15
+    movl $1, %eax
+    movq %rax, %rbx
+    movl 92(%rsp), %eax
+    movq %rax, %rdi
+    pushq %r10
+    pushq %r11
+    pushq %r12
+    pushq %r13
+    movq %rdi, %rcx
+    movq %rbx, %rdx
+    movq %r15, %r8
+    movq %r14, %r9
+    subq $32, %rsp
+    call many
+    addq $64, %rsp
+    movl %eax, %ecx
+    movq 72(%rsp), %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movq %r10, %rax
+    movl %eax, 88(%rsp)
+    movl 88(%rsp), %eax
+    movq %rax, %r10
+    movl 88(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movl $100, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cltd
+    idivl %ecx
+    movl %edx, %eax
+    movl %eax, %ecx
+    movq 80(%rsp), %rax
+    addl %ecx, %eax
+    movq 64(%rsp), %rbx
+    movq 56(%rsp), %rdi
+    movq 48(%rsp), %r12
+    movq 40(%rsp), %r13
+    movq 32(%rsp), %r14
+    movq 24(%rsp), %r15
+    movq 8(%rsp), %rsi
+    addq $104, %rsp
+    ret
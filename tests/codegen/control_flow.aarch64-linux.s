@@ -0,0 +1,238 @@
+    .text
+    .globl collatz
+collatz:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    mov w0, #0
+    stur w0, [x29, #-8]
+.Lcollatz_loop_1:
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    mov w0, #1
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, ne
+    cmp w0, #0
+    b.eq .Lcollatz_end_2
+    ldur w0, [x29, #-4]
+    asr w9, w0, #31
+    add w9, w0, w9, lsr #31
+    asr w9, w9, #1
+    sub w0, w0, w9, lsl #1
+    mov x11, x0
+    mov w0, #0
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, eq
+    cmp w0, #0
+    b.eq .Lcollatz_else_4
+    ldur w0, [x29, #-4]
+    asr w9, w0, #31
+    add w0, w0, w9, lsr #31
+    asr w0, w0, #1
+    stur w0, [x29, #-4]
+    b .Lcollatz_end_3
+.Lcollatz_else_4:
+    mov w0, #3
+    mov x11, x0
+    ldur w0, [x29, #-4]
+    mov w1, w0
+    mov x0, x11
+    mul w0, w0, w1
+    mov x11, x0
+    mov w0, #1
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    stur w0, [x29, #-4]
+.Lcollatz_end_3:
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    add w0, w0, #1
+    stur w0, [x29, #-8]
+    mov w0, w1
+    b .Lcollatz_loop_1
+.Lcollatz_end_2:
+    ldur w0, [x29, #-8]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl classify
+classify:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    add w0, w0, #0
+    cmp w0, #5
+    b.hi .Lclassify_default_10
+    adrp x9, .Lclassify_table_11
+    add x9, x9, :lo12:.Lclassify_table_11
+    ldr x9, [x9, x0, lsl #3]
+    br x9
+.Lclassify_case_6:
+    mov w0, #10
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+.Lclassify_case_7:
+.Lclassify_case_8:
+    mov w0, #20
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+.Lclassify_case_9:
+    mov w0, #50
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+.Lclassify_default_10:
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    mov w0, #0
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, lt
+    cmp w0, #0
+    b.eq .Lclassify_end_12
+    mov w0, #-1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+.Lclassify_end_12:
+    mov w0, #1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl sum_skipping
+sum_skipping:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    mov w0, #0
+    stur w0, [x29, #-8]
+    mov w0, #0
+    stur w0, [x29, #-12]
+.Lsum_skipping_loop_13:
+    ldur w0, [x29, #-12]
+    mov x11, x0
+    ldur w0, [x29, #-4]
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, lt
+    cmp w0, #0
+    b.eq .Lsum_skipping_end_15
+    ldur w0, [x29, #-12]
+    mov x11, x0
+    mov w0, #3
+    mov w1, w0
+    mov x0, x11
+    sdiv w9, w0, w1
+    msub w0, w9, w1, w0
+    mov x11, x0
+    mov w0, #0
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, eq
+    cmp w0, #0
+    b.eq .Lsum_skipping_end_16
+    b .Lsum_skipping_next_14
+.Lsum_skipping_end_16:
+    ldur w0, [x29, #-8]
+    mov x11, x0
+    mov w0, #100
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, gt
+    cmp w0, #0
+    b.eq .Lsum_skipping_end_17
+    b .Lsum_skipping_end_15
+.Lsum_skipping_end_17:
+    ldur w0, [x29, #-12]
+    mov w1, w0
+    ldur w0, [x29, #-8]
+    add w0, w0, w1
+    stur w0, [x29, #-8]
+.Lsum_skipping_next_14:
+    ldur w0, [x29, #-12]
+    mov w1, w0
+    add w0, w0, #1
+    stur w0, [x29, #-12]
+    mov w0, w1
+    b .Lsum_skipping_loop_13
+.Lsum_skipping_end_15:
+.Lsum_skipping_loop_18:
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    sub w0, w0, #1
+    stur w0, [x29, #-8]
+    mov w0, w1
+    ldur w0, [x29, #-8]
+    mov x11, x0
+    mov w0, #50
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, gt
+    cmp w0, #0
+    b.eq .Lsum_skipping_end_21
+    ldur w0, [x29, #-4]
+    cmp w0, #0
+.Lsum_skipping_end_21:
+    cset w0, ne
+    cmp w0, #0
+    b.ne .Lsum_skipping_loop_18
+    ldur w0, [x29, #-8]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl main
+main:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur x19, [x29, #-8]
+    mov w0, #27
+    mov x11, x0
+    mov x0, x11
+    bl collatz
+    mov x19, x0
+    mov w0, #2
+    mov x11, x0
+    mov x0, x11
+    bl classify
+    mov w1, w0
+    mov x0, x19
+    add w0, w0, w1
+    mov x19, x0
+    mov w0, #20
+    mov x11, x0
+    mov x0, x11
+    bl sum_skipping
+    mov w1, w0
+    mov x0, x19
+    add w0, w0, w1
+    ldur x19, [x29, #-8]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .section .data.rel.ro
+    .balign 8
+.Lclassify_table_11:
+    .quad .Lclassify_case_6
+    .quad .Lclassify_case_7
+    .quad .Lclassify_case_8
+    .quad .Lclassify_default_10
+    .quad .Lclassify_default_10
+    .quad .Lclassify_case_9
+    .section .note.GNU-stack,"",@progbits
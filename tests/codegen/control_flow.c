@@ -0,0 +1,47 @@
+int collatz(int n) {
+    int steps = 0;
+    while (n != 1) {
+        if (n % 2 == 0)
+            n = n / 2;
+        else
+            n = 3 * n + 1;
+        steps++;
+    }
+    return steps;
+}
+
+int classify(int x) {
+    switch (x) {
+    case 0:
+        return 10;
+    case 1:
+    case 2:
+        return 20;
+    case 5:
+        return 50;
+    default:
+        if (x < 0)
+            return -1;
+        return 1;
+    }
+}
+
+int sum_skipping(int limit) {
+    int total = 0;
+    int i;
+    for (i = 0; i < limit; i++) {
+        if (i % 3 == 0)
+            continue;
+        if (total > 100)
+            break;
+        total += i;
+    }
+    do {
+        total--;
+    } while (total > 50 && limit);
+    return total;
+}
+
+int main() {
+    return collatz(27) + classify(2) + sum_skipping(20);
+}
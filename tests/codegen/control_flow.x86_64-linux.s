@@ -0,0 +1,232 @@
+    .text
+    .globl collatz
+collatz:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl $0, %eax
+    movl %eax, 8(%rsp)
+.Lcollatz_loop_1:
+    movl 12(%rsp), %eax
+    movq %rax, %r10
+    movl $1, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setne %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lcollatz_end_2
+    movl 12(%rsp), %eax
+    movl %eax, %ecx
+    sarl $31, %ecx
+    shrl $31, %ecx
+    addl %eax, %ecx
+    andl $-2, %ecx
+    subl %ecx, %eax
+    movq %rax, %r10
+    movl $0, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    sete %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lcollatz_else_4
+    movl 12(%rsp), %eax
+    movl %eax, %ecx
+    sarl $31, %ecx
+    shrl $31, %ecx
+    addl %ecx, %eax
+    sarl $1, %eax
+    movl %eax, 12(%rsp)
+    jmp .Lcollatz_end_3
+.Lcollatz_else_4:
+    movl $3, %eax
+    movq %rax, %r10
+    movl 12(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    imull %ecx, %eax
+    movq %rax, %r10
+    movl $1, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movl %eax, 12(%rsp)
+.Lcollatz_end_3:
+    movl 8(%rsp), %eax
+    movl %eax, %ecx
+    addl $1, %eax
+    movl %eax, 8(%rsp)
+    movl %ecx, %eax
+    jmp .Lcollatz_loop_1
+.Lcollatz_end_2:
+    movl 8(%rsp), %eax
+    addq $24, %rsp
+    ret
+    .globl classify
+classify:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl 12(%rsp), %eax
+    cmpl $5, %eax
+    ja .Lclassify_default_10
+    leaq .Lclassify_table_11(%rip), %rcx
+    jmp *(%rcx,%rax,8)
+.Lclassify_case_6:
+    movl $10, %eax
+    addq $24, %rsp
+    ret
+.Lclassify_case_7:
+.Lclassify_case_8:
+    movl $20, %eax
+    addq $24, %rsp
+    ret
+.Lclassify_case_9:
+    movl $50, %eax
+    addq $24, %rsp
+    ret
+.Lclassify_default_10:
+    movl 12(%rsp), %eax
+    movq %rax, %r10
+    movl $0, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setl %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lclassify_end_12
+    movl $-1, %eax
+    addq $24, %rsp
+    ret
+.Lclassify_end_12:
+    movl $1, %eax
+    addq $24, %rsp
+    ret
+    .globl sum_skipping
+sum_skipping:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl $0, %eax
+    movl %eax, 8(%rsp)
+    movl $0, %eax
+    movl %eax, 4(%rsp)
+.Lsum_skipping_loop_13:
+    movl 4(%rsp), %eax
+    movq %rax, %r10
+    movl 12(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setl %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_skipping_end_15
+    movl 4(%rsp), %eax
+    movq %rax, %r10
+    movl $3, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cltd
+    idivl %ecx
+    movl %edx, %eax
+    movq %rax, %r10
+    movl $0, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    sete %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_skipping_end_16
+    jmp .Lsum_skipping_next_14
+.Lsum_skipping_end_16:
+    movl 8(%rsp), %eax
+    movq %rax, %r10
+    movl $100, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setg %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_skipping_end_17
+    jmp .Lsum_skipping_end_15
+.Lsum_skipping_end_17:
+    movl 4(%rsp), %eax
+    movl %eax, %ecx
+    movl 8(%rsp), %eax
+    addl %ecx, %eax
+    movl %eax, 8(%rsp)
+.Lsum_skipping_next_14:
+    movl 4(%rsp), %eax
+    movl %eax, %ecx
+    addl $1, %eax
+    movl %eax, 4(%rsp)
+    movl %ecx, %eax
+    jmp .Lsum_skipping_loop_13
+.Lsum_skipping_end_15:
+.Lsum_skipping_loop_18:
+    movl 8(%rsp), %eax
+    movl %eax, %ecx
+    subl $1, %eax
+    movl %eax, 8(%rsp)
+    movl %ecx, %eax
+    movl 8(%rsp), %eax
+    movq %rax, %r10
+    movl $50, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setg %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_skipping_end_21
+    movl 12(%rsp), %eax
+    cmpl $0, %eax
+.Lsum_skipping_end_21:
+    setne %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    jne .Lsum_skipping_loop_18
+    movl 8(%rsp), %eax
+    addq $24, %rsp
+    ret
+    .globl main
+main:
+    subq $24, %rsp
+    movq %rbx, 8(%rsp)
+    movl $27, %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call collatz
+    movq %rax, %rbx
+    movl $2, %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call classify
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movq %rax, %rbx
+    movl $20, %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call sum_skipping
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movq 8(%rsp), %rbx
+    addq $24, %rsp
+    ret
+    .section .data.rel.ro
+    .balign 8
+.Lclassify_table_11:
+    .quad .Lclassify_case_6
+    .quad .Lclassify_case_7
+    .quad .Lclassify_case_8
+    .quad .Lclassify_default_10
+    .quad .Lclassify_default_10
+    .quad .Lclassify_case_9
+    .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,249 @@
+    .text
+    .globl collatz
+collatz:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movl %ecx, 28(%rsp)
+    movl $0, %eax
+    movl %eax, 24(%rsp)
+.Lcollatz_loop_1:
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl $1, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setne %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lcollatz_end_2
+    movl 28(%rsp), %eax
+    movl %eax, %ecx
+    sarl $31, %ecx
+    shrl $31, %ecx
+    addl %eax, %ecx
+    andl $-2, %ecx
+    subl %ecx, %eax
+    movq %rax, %r10
+    movl $0, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    sete %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lcollatz_else_4
+    movl 28(%rsp), %eax
+    movl %eax, %ecx
+    sarl $31, %ecx
+    shrl $31, %ecx
+    addl %ecx, %eax
+    sarl $1, %eax
+    movl %eax, 28(%rsp)
+    jmp .Lcollatz_end_3
+.Lcollatz_else_4:
+    movl $3, %eax
+    movq %rax, %r10
+    movl 28(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    imull %ecx, %eax
+    movq %rax, %r10
+    movl $1, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movl %eax, 28(%rsp)
+.Lcollatz_end_3:
+    movl 24(%rsp), %eax
+    movl %eax, %ecx
+    addl $1, %eax
+    movl %eax, 24(%rsp)
+    movl %ecx, %eax
+    jmp .Lcollatz_loop_1
+.Lcollatz_end_2:
+    movl 24(%rsp), %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .globl classify
+classify:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movl %ecx, 28(%rsp)
+    movl 28(%rsp), %eax
+    cmpl $5, %eax
+    ja .Lclassify_default_10
+    leaq .Lclassify_table_11(%rip), %rcx
+    jmp *(%rcx,%rax,8)
+.Lclassify_case_6:
+    movl $10, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+.Lclassify_case_7:
+.Lclassify_case_8:
+    movl $20, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+.Lclassify_case_9:
+    movl $50, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+.Lclassify_default_10:
+    movl 28(%rsp), %eax
+    movq %rax, %r10
+    movl $0, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setl %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lclassify_end_12
+    movl $-1, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+.Lclassify_end_12:
+    movl $1, %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .globl sum_skipping
+sum_skipping:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movl %ecx, 28(%rsp)
+    movl $0, %eax
+    movl %eax, 24(%rsp)
+    movl $0, %eax
+    movl %eax, 20(%rsp)
+.Lsum_skipping_loop_13:
+    movl 20(%rsp), %eax
+    movq %rax, %r10
+    movl 28(%rsp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setl %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_skipping_end_15
+    movl 20(%rsp), %eax
+    movq %rax, %r10
+    movl $3, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cltd
+    idivl %ecx
+    movl %edx, %eax
+    movq %rax, %r10
+    movl $0, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    sete %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_skipping_end_16
+    jmp .Lsum_skipping_next_14
+.Lsum_skipping_end_16:
+    movl 24(%rsp), %eax
+    movq %rax, %r10
+    movl $100, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setg %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_skipping_end_17
+    jmp .Lsum_skipping_end_15
+.Lsum_skipping_end_17:
+    movl 20(%rsp), %eax
+    movl %eax, %ecx
+    movl 24(%rsp), %eax
+    addl %ecx, %eax
+    movl %eax, 24(%rsp)
+.Lsum_skipping_next_14:
+    movl 20(%rsp), %eax
+    movl %eax, %ecx
+    addl $1, %eax
+    movl %eax, 20(%rsp)
+    movl %ecx, %eax
+    jmp .Lsum_skipping_loop_13
+.Lsum_skipping_end_15:
+.Lsum_skipping_loop_18:
+    movl 24(%rsp), %eax
+    movl %eax, %ecx
+    subl $1, %eax
+    movl %eax, 24(%rsp)
+    movl %ecx, %eax
+    movl 24(%rsp), %eax
+    movq %rax, %r10
+    movl $50, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setg %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_skipping_end_21
+    movl 28(%rsp), %eax
+    cmpl $0, %eax
+.Lsum_skipping_end_21:
+    setne %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    jne .Lsum_skipping_loop_18
+    movl 24(%rsp), %eax
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .globl main
+main:
+    subq $40, %rsp
+    movq %rsi, 8(%rsp)
+    movq %rbx, 24(%rsp)
+    movl $27, %eax
+    movq %rax, %r10
+    movq %r10, %rcx
+    subq $32, %rsp
+    call collatz
+    addq $32, %rsp
+    movq %rax, %rbx
+    movl $2, %eax
+    movq %rax, %r10
+    movq %r10, %rcx
+    subq $32, %rsp
+    call classify
+    addq $32, %rsp
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movq %rax, %rbx
+    movl $20, %eax
+    movq %rax, %r10
+    movq %r10, %rcx
+    subq $32, %rsp
+    call sum_skipping
+    addq $32, %rsp
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movq 24(%rsp), %rbx
+    movq 8(%rsp), %rsi
+    addq $40, %rsp
+    ret
+    .section .rdata,"dr"
+    .balign 8
+.Lclassify_table_11:
+    .quad .Lclassify_case_6
+    .quad .Lclassify_case_7
+    .quad .Lclassify_case_8
+    .quad .Lclassify_default_10
+    .quad .Lclassify_default_10
+    .quad .Lclassify_case_9
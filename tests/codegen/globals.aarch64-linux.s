@@ -0,0 +1,161 @@
+    .text
+    .globl bump
+bump:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    mov w1, w0
+    adrp x10, counter
+    add x10, x10, :lo12:counter
+    ldr w0, [x10]
+    add w0, w0, w1
+    adrp x10, counter
+    add x10, x10, :lo12:counter
+    str w0, [x10]
+    adrp x10, counter
+    add x10, x10, :lo12:counter
+    ldr w0, [x10]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl sum_table
+sum_table:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    mov w0, #0
+    stur w0, [x29, #-4]
+    mov w0, #0
+    stur w0, [x29, #-8]
+.Lsum_table_loop_1:
+    ldur w0, [x29, #-8]
+    mov x11, x0
+    mov w0, #4
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, lt
+    cmp w0, #0
+    b.eq .Lsum_table_end_3
+    adrp x0, table
+    add x0, x0, :lo12:table
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    sxtw x0, w0
+    mov x9, #4
+    mul x0, x0, x9
+    mov x1, x0
+    mov x0, x11
+    add x0, x0, x1
+    ldr w0, [x0]
+    mov w1, w0
+    ldur w0, [x29, #-4]
+    add w0, w0, w1
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    add w0, w0, #1
+    stur w0, [x29, #-8]
+    mov w0, w1
+    b .Lsum_table_loop_1
+.Lsum_table_end_3:
+    ldur w0, [x29, #-4]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl main
+main:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur x19, [x29, #-8]
+    adrp x10, limit
+    add x10, x10, :lo12:limit
+    ldr w0, [x10]
+    mov x11, x0
+    mov x0, x11
+    bl bump
+    adrp x10, hidden
+    add x10, x10, :lo12:hidden
+    ldr w0, [x10]
+    mov x11, x0
+    mov x0, x11
+    bl bump
+    adrp x10, counter
+    add x10, x10, :lo12:counter
+    ldr w0, [x10]
+    mov x19, x0
+    bl sum_table
+    mov w1, w0
+    mov x0, x19
+    add w0, w0, w1
+    mov x11, x0
+    adrp x10, greeting
+    add x10, x10, :lo12:greeting
+    ldr x0, [x10]
+    mov x12, x0
+    mov w0, #1
+    sxtw x0, w0
+    mov x1, x0
+    mov x0, x12
+    add x0, x0, x1
+    ldrsb w0, [x0]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    adrp x10, big
+    add x10, x10, :lo12:big
+    ldr x0, [x10]
+    mov x12, x0
+    mov w0, #7
+    sxtw x0, w0
+    mov x1, x0
+    mov x0, x12
+    sdiv x9, x0, x1
+    msub x0, x9, x1, x0
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    ldur x19, [x29, #-8]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .bss
+    .balign 4
+    .globl counter
+counter:
+    .zero 4
+    .data
+    .balign 4
+    .globl limit
+limit:
+    .long 10
+    .data
+    .balign 8
+    .globl big
+big:
+    .quad 1234567890
+    .data
+    .balign 8
+    .globl greeting
+greeting:
+    .quad .Lstr0
+    .data
+    .balign 4
+    .globl table
+table:
+    .long 1
+    .long 2
+    .long 3
+    .long 4
+    .data
+    .balign 4
+hidden:
+    .long 3
+    .section .rodata
+.Lstr0:
+    .asciz "hello"
+    .section .note.GNU-stack,"",@progbits
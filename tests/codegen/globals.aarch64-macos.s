@@ -0,0 +1,160 @@
+    .text
+    .globl _bump
+_bump:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-4]
+    mov w1, w0
+    adrp x10, _counter@PAGE
+    add x10, x10, _counter@PAGEOFF
+    ldr w0, [x10]
+    add w0, w0, w1
+    adrp x10, _counter@PAGE
+    add x10, x10, _counter@PAGEOFF
+    str w0, [x10]
+    adrp x10, _counter@PAGE
+    add x10, x10, _counter@PAGEOFF
+    ldr w0, [x10]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl _sum_table
+_sum_table:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    mov w0, #0
+    stur w0, [x29, #-4]
+    mov w0, #0
+    stur w0, [x29, #-8]
+Lsum_table_loop_1:
+    ldur w0, [x29, #-8]
+    mov x11, x0
+    mov w0, #4
+    mov w1, w0
+    mov x0, x11
+    cmp w0, w1
+    cset w0, lt
+    cmp w0, #0
+    b.eq Lsum_table_end_3
+    adrp x0, _table@PAGE
+    add x0, x0, _table@PAGEOFF
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    sxtw x0, w0
+    mov x9, #4
+    mul x0, x0, x9
+    mov x1, x0
+    mov x0, x11
+    add x0, x0, x1
+    ldr w0, [x0]
+    mov w1, w0
+    ldur w0, [x29, #-4]
+    add w0, w0, w1
+    stur w0, [x29, #-4]
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    add w0, w0, #1
+    stur w0, [x29, #-8]
+    mov w0, w1
+    b Lsum_table_loop_1
+Lsum_table_end_3:
+    ldur w0, [x29, #-4]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .globl _main
+_main:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur x19, [x29, #-8]
+    adrp x10, _limit@PAGE
+    add x10, x10, _limit@PAGEOFF
+    ldr w0, [x10]
+    mov x11, x0
+    mov x0, x11
+    bl _bump
+    adrp x10, _hidden@PAGE
+    add x10, x10, _hidden@PAGEOFF
+    ldr w0, [x10]
+    mov x11, x0
+    mov x0, x11
+    bl _bump
+    adrp x10, _counter@PAGE
+    add x10, x10, _counter@PAGEOFF
+    ldr w0, [x10]
+    mov x19, x0
+    bl _sum_table
+    mov w1, w0
+    mov x0, x19
+    add w0, w0, w1
+    mov x11, x0
+    adrp x10, _greeting@PAGE
+    add x10, x10, _greeting@PAGEOFF
+    ldr x0, [x10]
+    mov x12, x0
+    mov w0, #1
+    sxtw x0, w0
+    mov x1, x0
+    mov x0, x12
+    add x0, x0, x1
+    ldrsb w0, [x0]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov x11, x0
+    adrp x10, _big@PAGE
+    add x10, x10, _big@PAGEOFF
+    ldr x0, [x10]
+    mov x12, x0
+    mov w0, #7
+    sxtw x0, w0
+    mov x1, x0
+    mov x0, x12
+    sdiv x9, x0, x1
+    msub x0, x9, x1, x0
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    ldur x19, [x29, #-8]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .bss
+    .balign 4
+    .globl _counter
+_counter:
+    .zero 4
+    .data
+    .balign 4
+    .globl _limit
+_limit:
+    .long 10
+    .data
+    .balign 8
+    .globl _big
+_big:
+    .quad 1234567890
+    .data
+    .balign 8
+    .globl _greeting
+_greeting:
+    .quad Lstr0
+    .data
+    .balign 4
+    .globl _table
+_table:
+    .long 1
+    .long 2
+    .long 3
+    .long 4
+    .data
+    .balign 4
+_hidden:
+    .long 3
+    .cstring
+Lstr0:
+    .asciz "hello"
@@ -0,0 +1,25 @@
+int counter;
+int limit = 10;
+long big = 1234567890;
+char *greeting = "hello";
+int table[4] = {1, 2, 3, 4};
+static int hidden = 3;
+
+int bump(int by) {
+    counter += by;
+    return counter;
+}
+
+int sum_table() {
+    int total = 0;
+    int i;
+    for (i = 0; i < 4; i++)
+        total += table[i];
+    return total;
+}
+
+int main() {
+    bump(limit);
+    bump(hidden);
+    return counter + sum_table() + greeting[1] + (int)(big % 7);
+}
@@ -0,0 +1,136 @@
+    .text
+    .globl bump
+bump:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl 12(%rsp), %eax
+    movl %eax, %ecx
+    movl counter(%rip), %eax
+    addl %ecx, %eax
+    movl %eax, counter(%rip)
+    movl counter(%rip), %eax
+    addq $24, %rsp
+    ret
+    .globl sum_table
+sum_table:
+    subq $24, %rsp
+    movl $0, %eax
+    movl %eax, 12(%rsp)
+    movl $0, %eax
+    movl %eax, 8(%rsp)
+.Lsum_table_loop_1:
+    movl 8(%rsp), %eax
+    movq %rax, %r10
+    movl $4, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setl %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je .Lsum_table_end_3
+    leaq table(%rip), %rax
+    movq %rax, %r10
+    movl 8(%rsp), %eax
+    movslq %eax, %rax
+    imulq $4, %rax
+    movq %rax, %rcx
+    movq %r10, %rax
+    addq %rcx, %rax
+    movl (%rax), %eax
+    movl %eax, %ecx
+    movl 12(%rsp), %eax
+    addl %ecx, %eax
+    movl %eax, 12(%rsp)
+    movl 8(%rsp), %eax
+    movl %eax, %ecx
+    addl $1, %eax
+    movl %eax, 8(%rsp)
+    movl %ecx, %eax
+    jmp .Lsum_table_loop_1
+.Lsum_table_end_3:
+    movl 12(%rsp), %eax
+    addq $24, %rsp
+    ret
+    .globl main
+main:
+    subq $24, %rsp
+    movq %rbx, 8(%rsp)
+    movl limit(%rip), %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call bump
+    movl hidden(%rip), %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call bump
+    movl counter(%rip), %eax
+    movq %rax, %rbx
+    call sum_table
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movq greeting(%rip), %rax
+    movq %rax, %r11
+    movl $1, %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    addq %rcx, %rax
+    movsbl (%rax), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movq big(%rip), %rax
+    movq %rax, %r11
+    movl $7, %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    cqto
+    idivq %rcx
+    movq %rdx, %rax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 8(%rsp), %rbx
+    addq $24, %rsp
+    ret
+    .bss
+    .balign 4
+    .globl counter
+counter:
+    .zero 4
+    .data
+    .balign 4
+    .globl limit
+limit:
+    .long 10
+    .data
+    .balign 8
+    .globl big
+big:
+    .quad 1234567890
+    .data
+    .balign 8
+    .globl greeting
+greeting:
+    .quad .Lstr0
+    .data
+    .balign 4
+    .globl table
+table:
+    .long 1
+    .long 2
+    .long 3
+    .long 4
+    .data
+    .balign 4
+hidden:
+    .long 3
+    .section .rodata
+.Lstr0:
+    .asciz "hello"
+    .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,135 @@
+    .text
+    .globl _bump
+_bump:
+    subq $24, %rsp
+    movl %edi, 12(%rsp)
+    movl 12(%rsp), %eax
+    movl %eax, %ecx
+    movl _counter(%rip), %eax
+    addl %ecx, %eax
+    movl %eax, _counter(%rip)
+    movl _counter(%rip), %eax
+    addq $24, %rsp
+    ret
+    .globl _sum_table
+_sum_table:
+    subq $24, %rsp
+    movl $0, %eax
+    movl %eax, 12(%rsp)
+    movl $0, %eax
+    movl %eax, 8(%rsp)
+Lsum_table_loop_1:
+    movl 8(%rsp), %eax
+    movq %rax, %r10
+    movl $4, %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    cmpl %ecx, %eax
+    setl %al
+    movzbl %al, %eax
+    cmpl $0, %eax
+    je Lsum_table_end_3
+    leaq _table(%rip), %rax
+    movq %rax, %r10
+    movl 8(%rsp), %eax
+    movslq %eax, %rax
+    imulq $4, %rax
+    movq %rax, %rcx
+    movq %r10, %rax
+    addq %rcx, %rax
+    movl (%rax), %eax
+    movl %eax, %ecx
+    movl 12(%rsp), %eax
+    addl %ecx, %eax
+    movl %eax, 12(%rsp)
+    movl 8(%rsp), %eax
+    movl %eax, %ecx
+    addl $1, %eax
+    movl %eax, 8(%rsp)
+    movl %ecx, %eax
+    jmp Lsum_table_loop_1
+Lsum_table_end_3:
+    movl 12(%rsp), %eax
+    addq $24, %rsp
+    ret
+    .globl _main
+_main:
+    subq $24, %rsp
+    movq %rbx, 8(%rsp)
+    movl _limit(%rip), %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call _bump
+    movl _hidden(%rip), %eax
+    movq %rax, %r10
+    movq %r10, %rdi
+    call _bump
+    movl _counter(%rip), %eax
+    movq %rax, %rbx
+    call _sum_table
+    movl %eax, %ecx
+    movq %rbx, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movq _greeting(%rip), %rax
+    movq %rax, %r11
+    movl $1, %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    addq %rcx, %rax
+    movsbl (%rax), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rax, %r10
+    movq _big(%rip), %rax
+    movq %rax, %r11
+    movl $7, %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %r11, %rax
+    cqto
+    idivq %rcx
+    movq %rdx, %rax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq 8(%rsp), %rbx
+    addq $24, %rsp
+    ret
+    .bss
+    .balign 4
+    .globl _counter
+_counter:
+    .zero 4
+    .data
+    .balign 4
+    .globl _limit
+_limit:
+    .long 10
+    .data
+    .balign 8
+    .globl _big
+_big:
+    .quad 1234567890
+    .data
+    .balign 8
+    .globl _greeting
+_greeting:
+    .quad Lstr0
+    .data
+    .balign 4
+    .globl _table
+_table:
+    .long 1
+    .long 2
+    .long 3
+    .long 4
+    .data
+    .balign 4
+_hidden:
+    .long 3
+    .cstring
+Lstr0:
+    .asciz "hello"
@@ -0,0 +1,240 @@
+//! Compiles each program in `tests/codegen` with `-O2` for every target, and
+//! checks its assembly, without comments or blank lines, against the
+//! `<name>.<target>.s` file beside it, so that any change to the code that is
+//! generated shows up in review, even one that changes no behavior. The
+//! `<name>.<target>.counts` file beside that records how many instructions
+//! each function has, and a function growing by more than
+//! `GROWTH_PERCENT` percent of that fails a test of its own.
+//!
+//! To add a program, write the `.c` file and run with `UPDATE_SNAPSHOTS=1` to
+//! create its snapshots from the current output, then check that they're
+//! right. A change that makes functions bigger on purpose updates them the
+//! same way.
+
+extern crate rust_cc;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rust_cc::{codegen, lexer, parser, semantic};
+use rust_cc::ast::{Program, TopLevel};
+use rust_cc::codegen::{DebugInfo, OptLevel, TargetSpec};
+use rust_cc::semantic::Severity;
+
+/// How many percent a function's instruction count can grow by, rounded
+/// down, before `instruction_counts_dont_grow` fails.
+const GROWTH_PERCENT: usize = 5;
+
+/// A program in `tests/codegen`, parsed.
+struct Case {
+    path: PathBuf,
+    program: Program,
+}
+
+impl Case {
+    /// The file beside the program for `target`, with `extension`.
+    fn snapshot(&self, target: &str, extension: &str) -> PathBuf {
+        self.path.with_extension(format!("{}.{}", target, extension))
+    }
+}
+
+/// The programs in `tests/codegen`, in order, which must be free of errors.
+fn cases() -> Vec<Case> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/codegen");
+    let mut paths: Vec<PathBuf> = fs::read_dir(&directory)
+        .unwrap_or_else(|error| panic!("{}: {}", directory.display(), error))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "c"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no programs in {}", directory.display());
+    paths.into_iter().map(|path| {
+        let source = fs::read_to_string(&path).unwrap();
+        let program = parser::parse_program(&lexer::lex_str(&source).unwrap())
+            .unwrap_or_else(|errors| panic!("{}: {:?}", path.display(), errors));
+        let errors: Vec<_> = semantic::check_program(&program).into_iter()
+            .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+            .collect();
+        assert_eq!(errors, vec![], "{}", path.display());
+        Case { path, program }
+    }).collect()
+}
+
+/// The assembly for `program` on `target`, with the comments and blank lines
+/// taken out.
+fn assembly(program: &Program, target: &str) -> String {
+    let options = codegen::Options {
+        target: TargetSpec::from_name(target).unwrap(),
+        opt_level: OptLevel::O2,
+        debug_info: DebugInfo::Off,
+        ..codegen::Options::default()
+    };
+    let mut normalized = String::new();
+    for line in codegen::generate_with(program, &options).lines() {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && !trimmed.starts_with('#') && !trimmed.starts_with("//") {
+            normalized.push_str(line);
+            normalized.push('\n');
+        }
+    }
+    normalized
+}
+
+/// Each function `program` defines, with how many instructions `assembly` has
+/// for it, in the order they are in it.
+fn instruction_counts(program: &Program, assembly: &str) -> Vec<(String, usize)> {
+    let functions: Vec<&str> = program.items.iter().filter_map(|item| match *item {
+        TopLevel::Function(ref function) if function.body.is_some() => Some(function.name.as_str()),
+        _ => None,
+    }).collect();
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    // Whether the lines are a function's, which they are from its label to
+    // the next section.
+    let mut in_function = false;
+    for line in assembly.lines() {
+        if let Some(label) = line.strip_suffix(':').filter(|_| !line.starts_with(' ')) {
+            // Functions on macOS start with an underscore.
+            if let Some(&name) = functions.iter().find(|&&name| label == name || label.strip_prefix('_') == Some(name)) {
+                counts.push((name.to_owned(), 0));
+                in_function = true;
+            }
+            continue;
+        }
+        let line = line.trim();
+        if line.starts_with(".text") || line.starts_with(".data") || line.starts_with(".bss") || line.starts_with(".section") || line.starts_with(".cstring") {
+            in_function = false;
+        } else if in_function && !line.starts_with('.') {
+            counts.last_mut().unwrap().1 += 1;
+        }
+    }
+    counts
+}
+
+fn format_counts(counts: &[(String, usize)]) -> String {
+    counts.iter().map(|&(ref function, count)| format!("{} {}\n", function, count)).collect()
+}
+
+fn parse_counts(path: &Path, text: &str) -> Vec<(String, usize)> {
+    text.lines().map(|line| {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next().and_then(|count| count.parse().ok()), words.next()) {
+            (Some(function), Some(count), None) => (function.to_owned(), count),
+            _ => panic!("{}: `{}` isn't a function and its instruction count", path.display(), line),
+        }
+    }).collect()
+}
+
+fn updating() -> bool {
+    env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value == "1")
+}
+
+/// Reads the snapshot at `path`, or writes `actual` to it when updating, in
+/// which case there is nothing to compare.
+fn read_snapshot(path: &Path, actual: &str) -> Result<Option<String>, String> {
+    if updating() {
+        fs::write(path, actual).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+        return Ok(None);
+    }
+    fs::read_to_string(path)
+        .map(Some)
+        .map_err(|_| format!("{} is missing; run with UPDATE_SNAPSHOTS=1 to create it.\nactual:\n{}", path.display(), actual))
+}
+
+#[test]
+fn assembly_matches_the_snapshots() {
+    let mut failures = Vec::new();
+    for case in cases() {
+        for &target in codegen::TARGETS {
+            let path = case.snapshot(target, "s");
+            let actual = assembly(&case.program, target);
+            match read_snapshot(&path, &actual) {
+                Ok(Some(ref expected)) if *expected != actual => {
+                    failures.push(format!("{} doesn't match.\nexpected:\n{}\nactual:\n{}", path.display(), expected, actual));
+                },
+                Ok(_) => {},
+                Err(failure) => failures.push(failure),
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{} snapshot(s) failed:\n\n{}", failures.len(), failures.join("\n"));
+}
+
+/// The counts in `expected` and `actual` side by side, with the functions
+/// that grew too much, or that are in only one of them, marked.
+fn compare_counts(expected: &[(String, usize)], actual: &[(String, usize)]) -> Option<String> {
+    let mut rows = Vec::new();
+    let mut failed = false;
+    for &(ref function, count) in actual {
+        let row = match expected.iter().find(|&(name, _)| name == function) {
+            Some(&(_, before)) => {
+                let grew = count > before + before * GROWTH_PERCENT / 100;
+                failed |= grew;
+                let change = (count as f64 - before as f64) / before.max(1) as f64 * 100.0;
+                format!("{:<16} {:>8} {:>8} {:>+7.1}%{}", function, before, count, change, if grew { "  <- grew too much" } else { "" })
+            },
+            None => {
+                failed = true;
+                format!("{:<16} {:>8} {:>8}          <- new", function, "-", count)
+            },
+        };
+        rows.push(row);
+    }
+    for &(ref function, before) in expected {
+        if !actual.iter().any(|(name, _)| name == function) {
+            failed = true;
+            rows.push(format!("{:<16} {:>8} {:>8}          <- gone", function, before, "-"));
+        }
+    }
+    if failed {
+        Some(format!("{:<16} {:>8} {:>8} {:>8}\n{}", "function", "expected", "actual", "change", rows.join("\n")))
+    } else {
+        None
+    }
+}
+
+#[test]
+fn instruction_counts_dont_grow() {
+    let mut failures = Vec::new();
+    for case in cases() {
+        for &target in codegen::TARGETS {
+            let path = case.snapshot(target, "counts");
+            let actual = instruction_counts(&case.program, &assembly(&case.program, target));
+            assert!(!actual.is_empty(), "no functions were found in the assembly for {} on {}", case.path.display(), target);
+            match read_snapshot(&path, &format_counts(&actual)) {
+                Ok(Some(expected)) => {
+                    let failure = compare_counts(&parse_counts(&path, &expected), &actual);
+                    failures.extend(failure.map(|table| format!("{}:\n{}\n", path.display(), table)));
+                },
+                Ok(None) => {},
+                Err(failure) => failures.push(failure),
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "instruction counts grew by more than {}%; if that is meant, run with UPDATE_SNAPSHOTS=1 to record them.\n\n{}",
+        GROWTH_PERCENT,
+        failures.join("\n"),
+    );
+}
+
+#[test]
+fn counts_are_compared_by_function() {
+    let counts = |counts: &[(&str, usize)]| counts.iter().map(|&(name, count)| (name.to_owned(), count)).collect::<Vec<_>>();
+    let expected = counts(&[("main", 20), ("f", 40), ("g", 3)]);
+    // Shrinking, and growing by no more than 5%, pass.
+    assert_eq!(compare_counts(&expected, &counts(&[("main", 21), ("f", 30), ("g", 3)])), None);
+    assert_eq!(
+        compare_counts(&expected, &counts(&[("main", 22), ("f", 40), ("h", 5)])).unwrap(),
+        "function         expected   actual   change\n\
+         main                   20       22   +10.0%  <- grew too much\n\
+         f                      40       40    +0.0%\n\
+         h                       -        5          <- new\n\
+         g                       3        -          <- gone",
+    );
+
+    let program = parser::parse_program(&lexer::lex_str("int x = 1; int f(int a); int f(int a) { return a; } int main() { return f(x); }").unwrap()).unwrap();
+    let assembly = "    .text\n_f:\n    mov w0, w0\nLf_end:\n    ret\n    .globl main\nmain:\n    call f\n    ret\n    .data\nx:\n    .long 1\n";
+    assert_eq!(instruction_counts(&program, assembly), counts(&[("f", 2), ("main", 2)]));
+}
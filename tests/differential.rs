@@ -0,0 +1,119 @@
+//! Compiles each program in `exec/` with `rust-cc`, at every optimization
+//! level, and with a reference compiler, runs them all, and checks that they
+//! exit with the same status and print the same output.
+//!
+//! It needs a C toolchain, so it only runs with `RUST_CC_EXECUTE=1` set, as
+//! the tests in `codegen.rs` only run their programs then. The reference
+//! compiler is `cc`, or whatever `RUST_CC_REFERENCE` names.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// The programs that are expected to differ, for want of the features they
+/// use. Each must still differ, by `rust-cc` rejecting it with a diagnostic
+/// rather than panicking; when one doesn't differ, it comes off the list.
+const EXPECTED_FAILURES: &[&str] = &[
+    "function_pointers",
+];
+
+const OPT_LEVELS: [&str; 3] = ["-O0", "-O1", "-O2"];
+
+/// The status a Rust program exits with when it panics.
+const PANIC_STATUS: i32 = 101;
+
+/// How a program ran: its status, and what it printed.
+#[derive(Debug, PartialEq)]
+struct Run {
+    status: Option<i32>,
+    stdout: String,
+}
+
+fn run(executable: &Path) -> Run {
+    let output = Command::new(executable).output().unwrap();
+    Run { status: output.status.code(), stdout: String::from_utf8_lossy(&output.stdout).into_owned() }
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Compiles `program` with `compiler` and `flags` to `executable`, and runs
+/// it. Returns what the compiler did instead if it doesn't compile.
+fn compile_and_run(compiler: &Path, flags: &[&str], program: &Path, executable: &Path) -> Result<Run, Output> {
+    let output = Command::new(compiler).args(flags).arg(program).arg("-o").arg(executable).output().unwrap();
+    if !output.status.success() {
+        return Err(output);
+    }
+    Ok(run(executable))
+}
+
+/// How `rust-cc` differs from the reference compiler on `program`, at each
+/// level it differs at, and which of those differences are panics.
+fn differences(reference: &Path, program: &Path, directory: &Path) -> (Vec<String>, Vec<String>) {
+    let name = program.file_stem().unwrap().to_string_lossy().into_owned();
+    let expected = compile_and_run(reference, &["-w"], program, &directory.join(format!("{}.reference", name)))
+        .unwrap_or_else(|output| panic!("{} didn't compile:\n{}", program.display(), stderr(&output)));
+
+    let rust_cc = Path::new(env!("CARGO_BIN_EXE_rust-cc"));
+    let (mut differences, mut panics) = (Vec::new(), Vec::new());
+    for level in &OPT_LEVELS {
+        let executable = directory.join(format!("{}{}", name, level));
+        match compile_and_run(rust_cc, &[level], program, &executable) {
+            Ok(ref actual) if *actual == expected => {},
+            Ok(actual) => differences.push(format!(
+                "{} at {}:\nexpected status {:?}, output:\n{}\nactual status {:?}, output:\n{}",
+                name, level, expected.status, expected.stdout, actual.status, actual.stdout,
+            )),
+            Err(ref output) if output.status.code() == Some(PANIC_STATUS) => {
+                let panic = format!("{} at {} panicked:\n{}", name, level, stderr(output));
+                differences.push(panic.clone());
+                panics.push(panic);
+            },
+            Err(output) => differences.push(format!("{} at {} didn't compile:\n{}", name, level, stderr(&output))),
+        }
+    }
+    (differences, panics)
+}
+
+fn programs() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/exec");
+    let mut programs: Vec<_> = fs::read_dir(directory).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "c"))
+        .collect();
+    programs.sort();
+    programs
+}
+
+#[test]
+fn programs_behave_as_with_the_reference_compiler() {
+    if !env::var("RUST_CC_EXECUTE").is_ok_and(|value| value == "1") {
+        return;
+    }
+    let reference = PathBuf::from(env::var("RUST_CC_REFERENCE").unwrap_or_else(|_| "cc".to_owned()));
+    let directory = env::temp_dir().join(format!("rust-cc-differential-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    let programs = programs();
+    assert!(!programs.is_empty(), "no programs in tests/exec");
+    let mut failures = Vec::new();
+    for program in &programs {
+        let name = program.file_stem().unwrap().to_string_lossy().into_owned();
+        let (differences, panics) = differences(&reference, program, &directory);
+        match (EXPECTED_FAILURES.contains(&&name[..]), differences.is_empty()) {
+            (false, _) => failures.extend(differences),
+            (true, true) => failures.push(format!("{} is expected to fail, but doesn't; take it off the list", name)),
+            // Even a program that is expected to fail mustn't crash the compiler.
+            (true, false) => failures.extend(panics),
+        }
+    }
+    for name in EXPECTED_FAILURES {
+        if !programs.iter().any(|program| program.file_stem().unwrap() == *name) {
+            failures.push(format!("{} is expected to fail, but there is no such program", name));
+        }
+    }
+    let _ = fs::remove_dir_all(&directory);
+    assert!(failures.is_empty(), "{} differences:\n\n{}", failures.len(), failures.join("\n\n"));
+}
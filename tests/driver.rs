@@ -0,0 +1,847 @@
+//! Runs the `rust-cc` binary the way it is run from a shell, and checks what it
+//! writes and how it exits.
+//!
+//! What `--emit` writes for the files in `emit/` is checked against the
+//! snapshot beside each, named after the kind; run with `UPDATE_SNAPSHOTS=1`
+//! to write them from the current output, then check that they're right.
+
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// A directory of its own for the test `name`, empty.
+fn directory(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("rust-cc-driver-{}", std::process::id())).join(name);
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Runs the driver in `directory` with `args`.
+fn rust_cc(directory: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rust-cc")).args(args).current_dir(directory).output().unwrap()
+}
+
+/// Runs the driver in `directory` with `args`, writing `input` to its
+/// standard input.
+fn rust_cc_with_input<I: AsRef<[u8]>>(directory: &PathBuf, args: &[&str], input: I) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust-cc"))
+        .args(args)
+        .current_dir(directory)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_ref()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Whether there is a `cc` to link with.
+fn has_cc() -> bool {
+    Command::new("cc").arg("--version").output().is_ok_and(|output| output.status.success())
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Checks that `--emit kind` prints the snapshot `emit/<case>.<kind>` for
+/// `emit/<case>.c`.
+fn check_emit(case: &str, kind: &str) {
+    check_snapshot(case, &["--emit", kind], kind);
+}
+
+/// Checks that compiling `emit/<case>.c` with `args` prints the snapshot
+/// `emit/<case>.<extension>`.
+fn check_snapshot(case: &str, args: &[&str], extension: &str) -> String {
+    let emit = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/emit");
+    let source = format!("{}.c", case);
+    let output = rust_cc(&emit, &[args, &[&source[..]]].concat());
+    assert!(output.status.success(), "{}", stderr(&output));
+    compare_snapshot(&emit.join(format!("{}.{}", case, extension)), stdout(&output))
+}
+
+/// Checks that `actual` is what the file `snapshot` holds, or writes it there
+/// with `UPDATE_SNAPSHOTS=1`.
+fn compare_snapshot(snapshot: &Path, actual: String) -> String {
+    if env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value == "1") {
+        fs::write(snapshot, &actual).unwrap();
+        return actual;
+    }
+    let expected = fs::read_to_string(snapshot)
+        .unwrap_or_else(|_| panic!("{} is missing; run with UPDATE_SNAPSHOTS=1 to create it", snapshot.display()));
+    assert_eq!(actual, expected, "{} doesn't match", snapshot.display());
+    expected
+}
+
+/// Parses each line of `text` as a JSON value of its own.
+#[cfg(feature = "serde")]
+fn json_lines(text: &str) -> Vec<serde_json::Value> {
+    text.lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|err| panic!("{:?} isn't JSON: {}", line, err)))
+        .collect()
+}
+
+#[test]
+fn assembly_is_written_to_a_file_named_after_the_input() {
+    let directory = directory("success");
+    fs::create_dir(directory.join("src")).unwrap();
+    fs::write(directory.join("src/answer.c"), "int main() { return 42; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "src/answer.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stderr(&output), "");
+    assert!(output.stdout.is_empty());
+    // In the current directory, as `gcc -S` does.
+    let assembly = fs::read_to_string(directory.join("answer.s")).unwrap();
+    assert!(assembly.contains("main:"), "{}", assembly);
+    assert!(assembly.contains("42"), "{}", assembly);
+}
+
+#[test]
+fn assembly_quotes_the_source_lines() {
+    let directory = directory("comments");
+    fs::write(directory.join("sum.c"), "int main() {\n    int a = 40;\n    return a +\n        2;\n}\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "--target=x86_64-linux", "sum.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let assembly = fs::read_to_string(directory.join("sum.s")).unwrap();
+    // A statement over several lines is quoted by its first.
+    assert!(assembly.contains("    # 2: int a = 40;\n"), "{}", assembly);
+    assert!(assembly.contains("    # 3: return a +\n"), "{}", assembly);
+    assert!(!assembly.contains("2;"), "{}", assembly);
+
+    // Nothing else quotes it.
+    let output = rust_cc(&directory, &["--emit", "llvm-ir", "sum.c"]);
+    assert!(!stdout(&output).contains("return a"), "{}", stdout(&output));
+}
+
+#[test]
+fn output_path_can_be_given() {
+    let directory = directory("output");
+    fs::write(directory.join("answer.c"), "int main() { return 42; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "-o", "out/renamed.asm", "answer.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("out/renamed.asm: "), "{}", stderr(&output));
+
+    fs::create_dir(directory.join("out")).unwrap();
+    let output = rust_cc(&directory, &["answer.c", "-S", "-o", "out/renamed.asm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(directory.join("out/renamed.asm")).unwrap().contains("main:"));
+    assert!(!directory.join("answer.s").exists());
+
+    // Other output goes to standard output, unless it is given a path.
+    let output = rust_cc(&directory, &["--emit", "ast", "answer.c"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("int 42"));
+    let output = rust_cc(&directory, &["--emit", "ast", "-o", "answer.ast", "answer.c"]);
+    assert!(output.stdout.is_empty());
+    assert!(fs::read_to_string(directory.join("answer.ast")).unwrap().contains("int 42"));
+}
+
+#[test]
+fn text_can_be_written_to_standard_output() {
+    let directory = directory("dash_output");
+    fs::write(directory.join("answer.c"), "int main() { return 42; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "-o", "-", "answer.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("main:"), "{}", stdout(&output));
+    let output = rust_cc(&directory, &["--emit", "llvm-ir", "-o", "-", "answer.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("define i32 @main()"), "{}", stdout(&output));
+    assert!(!directory.join("-").exists());
+
+    // Object files and executables aren't text.
+    for args in &[&["-c", "-o", "-", "answer.c"][..], &["-o", "-", "answer.c"][..]] {
+        let output = rust_cc(&directory, args);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stderr(&output).starts_with("-o - can only write text"), "{}", stderr(&output));
+        assert!(!directory.join("-").exists());
+    }
+}
+
+#[test]
+fn errors_are_reported_at_their_positions() {
+    let directory = directory("errors");
+    fs::write(directory.join("lex.c"), "int main() {\n    return 2 @ 3;\n}\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "lex.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "\
+error[E0001]: unrecognized input
+ --> lex.c:2:14
+  |
+2 |     return 2 @ 3;
+  |              ^
+
+");
+    assert!(!directory.join("lex.s").exists());
+    let output = rust_cc(&directory, &["-S", "lex.c", "--error-format=short"]);
+    assert_eq!(stderr(&output), "lex.c:2:14: error[E0001]: unrecognized input\n");
+
+    fs::write(directory.join("parse.c"), "int main() { return 0 }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "parse.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("parse.c:1:23: error[E0010]: "), "{}", stderr(&output));
+
+    // Warnings don't stop the assembly being written.
+    fs::write(directory.join("warning.c"), "int main() { int x; return 0; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "warning.c"]);
+    assert!(output.status.success());
+    assert_eq!(stderr(&output), "\
+warning[W0002]: unused variable `x` [-Wunused-variable]
+ --> warning.c:1:18
+  |
+1 | int main() { int x; return 0; }
+  |                  ^
+
+");
+    assert!(directory.join("warning.s").exists());
+}
+
+#[test]
+fn diagnostics_are_colored_when_asked() {
+    let directory = directory("color");
+    fs::write(directory.join("undeclared.c"), "int main() { return y; }\n").unwrap();
+    let output = rust_cc(&directory, &["--color=always", "-S", "undeclared.c"]);
+    assert!(stderr(&output).starts_with("\x1b[1;31merror[E0102]\x1b[0m\x1b[1m: use of undeclared identifier"), "{:?}", stderr(&output));
+    let output = rust_cc(&directory, &["--color=always", "--error-format=short", "-S", "undeclared.c"]);
+    assert!(stderr(&output).starts_with("\x1b[1mundeclared.c:1:21:\x1b[0m \x1b[1;31merror[E0102]:\x1b[0m "), "{:?}", stderr(&output));
+
+    // Standard error isn't a terminal here, so there is no color unless it
+    // is asked for.
+    for args in &[&["-S", "undeclared.c"][..], &["--color=never", "-S", "undeclared.c"], &["--color=auto", "-S", "undeclared.c"]] {
+        let output = rust_cc(&directory, args);
+        assert!(stderr(&output).starts_with("error[E0102]: use of undeclared identifier"), "{:?}", stderr(&output));
+    }
+}
+
+#[test]
+fn labels_point_at_what_diagnostics_have_to_do_with() {
+    let emit = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/emit");
+    for case in &["redeclared", "conflicting", "modified", "mismatched-call", "unterminated", "unterminated-string", "unterminated-string-eof", "unclosed"] {
+        let output = rust_cc(&emit, &["--emit", "asm", &format!("{}.c", case)]);
+        assert_eq!(output.status.code(), Some(1));
+        compare_snapshot(&emit.join(format!("{}.stderr", case)), stderr(&output));
+    }
+}
+
+#[test]
+fn misspellings_are_suggested() {
+    let directory = directory("suggestions");
+    fs::write(directory.join("keyword.c"), "int main() {\n    retrun 5;\n}\n").unwrap();
+    fs::write(directory.join("name.c"), "int main() {\n    int counter = 0;\n    return countr + frobnicate;\n}\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "keyword.c", "name.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "\
+keyword.c:2:12: error[E0010]: expected operator or `;`, found integer literal `5`
+keyword.c:2:12: help: did you mean `return`?
+name.c:3:12: error[E0102]: use of undeclared identifier `countr`
+name.c:3:12: help: did you mean `counter`?
+name.c:3:21: error[E0102]: use of undeclared identifier `frobnicate`
+");
+}
+
+#[test]
+fn errors_stop_being_shown_at_the_limit() {
+    let directory = directory("max_errors");
+    // A stray `@` on each of 32 lines, with a warning-free program around them.
+    let source: String = (1..=32).map(|line| format!("int x{} = {} @;\n", line, line)).collect();
+    fs::write(directory.join("stray.c"), source).unwrap();
+
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "stray.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    let limited = stderr(&output);
+    let lines: Vec<&str> = limited.lines().collect();
+    assert_eq!(lines.len(), 21, "{}", limited);
+    assert_eq!(lines[0], "stray.c:1:12: error[E0001]: unrecognized input");
+    assert_eq!(lines[19], "stray.c:20:14: error[E0001]: unrecognized input");
+    assert_eq!(lines[20], "error: too many errors emitted, stopping now; 12 more diagnostics weren't shown");
+
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "--max-errors=5", "stray.c"]);
+    let five = stderr(&output);
+    assert_eq!(five.lines().count(), 6, "{}", five);
+    assert!(five.ends_with("error: too many errors emitted, stopping now; 27 more diagnostics weren't shown\n"));
+
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "--max-errors=0", "stray.c"]);
+    let all = stderr(&output);
+    assert_eq!(all.lines().count(), 32, "{}", all);
+    assert!(!all.contains("too many errors"));
+
+    // Warnings don't count towards the limit.
+    fs::write(directory.join("unused.c"), "int main() { int a; int b; return 0; }\n").unwrap();
+    fs::write(directory.join("undeclared.c"), "int main() { return d + e; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "--max-errors=1", "unused.c", "undeclared.c"]);
+    assert_eq!(stderr(&output), "\
+unused.c:1:18: warning[W0002]: unused variable `a` [-Wunused-variable]
+unused.c:1:25: warning[W0002]: unused variable `b` [-Wunused-variable]
+undeclared.c:1:21: error[E0102]: use of undeclared identifier `d`
+error: too many errors emitted, stopping now; 1 more diagnostic wasn't shown
+");
+
+    // Files after the limit is reached aren't compiled, however many are
+    // compiled at once.
+    for jobs in &["1", "2"] {
+        let output = rust_cc(&directory, &["-j", jobs, "-S", "--error-format=short", "--max-errors=5", "stray.c", "undeclared.c", "unused.c"]);
+        assert_eq!(output.status.code(), Some(1));
+        let five = stderr(&output);
+        assert_eq!(five.lines().count(), 6, "{}", five);
+        assert!(five.ends_with("error: too many errors emitted, stopping now; 27 more diagnostics weren't shown, and 2 files weren't compiled\n"), "{}", five);
+    }
+}
+
+#[test]
+fn pragmas_turn_warnings_off_where_they_are() {
+    let directory = directory("pragmas");
+    fs::write(directory.join("region.c"), "\
+#pragma GCC diagnostic push
+#pragma GCC diagnostic ignored \"-Wunused-variable\"
+int f() { int quiet; return 0; }
+#pragma GCC diagnostic pop
+int g() { int loud; return 0; }
+#pragma GCC diagnostic ignored \"-Wunused-varaible\"
+").unwrap();
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "region.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stderr(&output), "\
+region.c:5:15: warning[W0002]: unused variable `loud` [-Wunused-variable]
+region.c:6:1: warning[W0011]: unknown warning `-Wunused-varaible` in `#pragma GCC diagnostic` [-Wpragmas]
+");
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "-Wno-pragmas", "-Werror", "region.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "\
+region.c:5:15: error[W0002]: unused variable `loud` [-Werror=unused-variable]
+");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn diagnostics_can_be_written_as_json() {
+    let emit = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/emit");
+    let output = rust_cc(&emit, &["--emit", "ast", "--error-format=json", "linkage.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    let diagnostics = json_lines(&compare_snapshot(&emit.join("linkage.json"), stderr(&output)));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["span"]["line_start"], 3);
+    assert_eq!(diagnostics[0]["labels"][0]["message"], "previously declared here");
+
+    // Each diagnostic is a line of its own, even when there are several, or
+    // the file can't be read.
+    let directory = directory("json");
+    fs::write(directory.join("warnings.c"), "int main() { int a; int b; return 0; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "--error-format=json", "-Wall", "warnings.c", "missing.c"]);
+    let diagnostics = json_lines(&stderr(&output));
+    assert_eq!(diagnostics.len(), 3, "{}", stderr(&output));
+    assert_eq!(diagnostics[1]["severity"], "warning");
+    assert_eq!(diagnostics[2]["file"], "missing.c");
+    assert!(diagnostics[2]["span"].is_null());
+
+    // So is the summary of those left out, which is about no file.
+    let output = rust_cc(&directory, &["-S", "--error-format=json", "--max-errors=1", "missing.c", "gone.c"]);
+    let diagnostics = json_lines(&stderr(&output));
+    assert_eq!(diagnostics.len(), 2, "{}", stderr(&output));
+    assert!(diagnostics[1]["file"].is_null());
+    assert_eq!(diagnostics[1]["message"], "too many errors emitted, stopping now; 1 file wasn't compiled");
+}
+
+#[cfg(not(feature = "serde"))]
+#[test]
+fn diagnostics_can_only_be_written_as_json_with_serde() {
+    let directory = directory("json");
+    let output = rust_cc(&directory, &["-S", "--error-format=json", "main.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("needs rust-cc to be built with the `serde` feature"), "{}", stderr(&output));
+}
+
+#[test]
+fn codes_are_explained() {
+    let directory = directory("explain");
+    let output = rust_cc(&directory, &["--explain", "E0102"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let explanation = stdout(&output);
+    assert!(explanation.starts_with("E0102: use of an undeclared identifier\n\n"), "{}", explanation);
+    assert!(explanation.contains("```c\n"), "{}", explanation);
+    assert_eq!(stdout(&rust_cc(&directory, &["--explain", "e0102"])), explanation);
+
+    let output = rust_cc(&directory, &["--explain", "E9999"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(stderr(&output), "rust-cc: no diagnostic has the code `E9999`\n");
+    let output = rust_cc(&directory, &["--explain"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("--explain needs a code\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn explanations_can_be_cut_short() {
+    // A pipe that nothing reads, as with `rust-cc --explain E0102 | head -0`.
+    let (reader, writer) = std::io::pipe().unwrap();
+    drop(reader);
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-cc")).args(["--explain", "E0102"]).stdout(writer).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(output.stderr.is_empty(), "{}", stderr(&output));
+
+    // Any other error writing is reported.
+    if cfg!(target_os = "linux") {
+        let full = fs::OpenOptions::new().write(true).open("/dev/full").unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_rust-cc")).args(["--explain", "E0102"]).stdout(full).output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(stderr(&output).starts_with("<stdout>: "), "{}", stderr(&output));
+    }
+}
+
+#[test]
+fn files_are_formatted() {
+    check_snapshot("messy", &["--format"], "formatted");
+    let emit = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/emit");
+    let output = rust_cc(&emit, &["--format", "messy.formatted"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), fs::read_to_string(emit.join("messy.formatted")).unwrap());
+
+    let directory = directory("format");
+    let output = rust_cc_with_input(&directory, &["--format", "--indent=2", "-"], "int main() {\nif (1)\nreturn 0;\n}\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "int main() {\n  if (1)\n    return 0;\n}\n");
+
+    // Files that can't be lexed are reported, and the rest still formatted.
+    fs::write(directory.join("lex.c"), "int x = 1 @ 2;\n").unwrap();
+    fs::write(directory.join("fine.c"), "int  y;\n").unwrap();
+    let output = rust_cc(&directory, &["--format", "lex.c", "fine.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "lex.c:1:11: error[E0001]: unrecognized input\n");
+    assert_eq!(stdout(&output), "int y;\n");
+    let output = rust_cc(&directory, &["--format", "--indent=wide", "fine.c"]);
+    assert!(stderr(&output).starts_with("--indent needs a number of spaces\nusage: rust-cc "), "{}", stderr(&output));
+    let output = rust_cc(&directory, &["--format"]);
+    assert!(stderr(&output).starts_with("no input file\nusage: rust-cc "), "{}", stderr(&output));
+}
+
+#[test]
+fn bad_command_lines_are_usage_errors() {
+    let directory = directory("usage");
+    let output = rust_cc(&directory, &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("no input file\nusage: rust-cc "), "{}", stderr(&output));
+
+    let output = rust_cc(&directory, &["--frobnicate", "a.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("unknown option `--frobnicate`\nusage: rust-cc "), "{}", stderr(&output));
+
+    let output = rust_cc(&directory, &["missing.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("missing.c: "), "{}", stderr(&output));
+
+    let output = rust_cc(&directory, &["a.c", "-o"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("-o needs a path\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn each_stage_can_be_emitted() {
+    check_emit("sample", "tokens");
+    check_emit("sample", "ast");
+
+    // The assembly depends on the host, so only its shape is checked.
+    let emit = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/emit");
+    let output = rust_cc(&emit, &["--emit", "asm", "sample.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("square:"), "{}", stdout(&output));
+    assert!(!emit.join("sample.s").exists());
+}
+
+#[test]
+fn emitting_tokens_stops_before_parsing() {
+    check_emit("unparsed", "tokens");
+    let emit = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/emit");
+    let output = rust_cc(&emit, &["--emit", "tokens,ast", "--error-format=short", "unparsed.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("unparsed.c:2:15: error[E0010]: "), "{}", stderr(&output));
+
+    // An ast is only parsed, so what semantic analysis rejects still has one.
+    let directory = directory("unchecked");
+    fs::write(directory.join("undeclared.c"), "int main() { return y; }\n").unwrap();
+    let output = rust_cc(&directory, &["--emit", "ast", "undeclared.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("y"));
+    let output = rust_cc(&directory, &["--emit", "asm", "undeclared.c"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn several_kinds_are_written_to_files() {
+    let directory = directory("several");
+    fs::write(directory.join("answer.c"), "int main() { return 42; }\n").unwrap();
+    let output = rust_cc(&directory, &["--emit", "tokens,ast,asm", "answer.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(output.stdout.is_empty());
+    assert!(fs::read_to_string(directory.join("answer.tokens")).unwrap().starts_with("Keyword \"int\" 1:1\n"));
+    assert!(fs::read_to_string(directory.join("answer.ast")).unwrap().contains("int 42"));
+    assert!(fs::read_to_string(directory.join("answer.s")).unwrap().contains("main:"));
+
+    let output = rust_cc(&directory, &["--emit", "tokens,ast", "-o", "out", "answer.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    let output = rust_cc(&directory, &["--emit", "tokens,bytecode", "answer.c"]);
+    assert!(stderr(&output).starts_with("unknown --emit kind `bytecode`\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn source_can_be_read_from_standard_input() {
+    let directory = directory("stdin");
+    let output = rust_cc_with_input(&directory, &["--emit", "asm", "-"], "int main(){return 3;}");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("main:"), "{}", stdout(&output));
+
+    // With `-S`, the assembly is written to `a.s`, as `gcc -S -` does.
+    let output = rust_cc_with_input(&directory, &["-S", "-"], "int main(){return 3;}");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(output.stdout.is_empty());
+    assert!(fs::read_to_string(directory.join("a.s")).unwrap().contains("main:"));
+
+    let output = rust_cc_with_input(&directory, &["-S", "-", "-o", "three.s"], "int main(){return 3;}");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(directory.join("three.s")).unwrap().contains("main:"));
+
+    let output = rust_cc_with_input(&directory, &["--error-format=short", "-"], "int main(){return 3 @ 4;}");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "<stdin>:1:21: error[E0001]: unrecognized input\n");
+}
+
+#[test]
+fn files_and_standard_input_are_read_alike() {
+    // Files are mapped with the `mmap` feature, and standard input never is.
+    let directory = directory("sources");
+    let program = "int f(int a) { int b = a + 1; return b * 2; }\n".repeat(20_000) + "int main() { return f(1); }\n";
+    fs::write(directory.join("large.c"), &program).unwrap();
+    let from_file = rust_cc(&directory, &["--emit", "ast", "large.c"]);
+    assert!(from_file.status.success(), "{}", stderr(&from_file));
+    let from_stdin = rust_cc_with_input(&directory, &["--emit", "ast", "-"], &program);
+    assert!(from_file.stdout == from_stdin.stdout);
+
+    let invalid = b"int main() { return 0; }\n/* \xc3\x28 */\n";
+    fs::write(directory.join("invalid.c"), invalid).unwrap();
+    let output = rust_cc(&directory, &["-S", "invalid.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "invalid.c: invalid UTF-8 at byte 28\n");
+    let output = rust_cc_with_input(&directory, &["-S", "-"], &invalid[..]);
+    assert_eq!(stderr(&output), "<stdin>: invalid UTF-8 at byte 28\n");
+
+    // A pipe named as a file, as `rust-cc <(cat large.c)` names one, says it
+    // is empty, so it is read rather than mapped.
+    if cfg!(unix) {
+        let from_pipe = rust_cc_with_input(&directory, &["--emit", "ast", "/dev/stdin"], &program);
+        assert!(from_pipe.status.success(), "{}", stderr(&from_pipe));
+        assert!(from_file.stdout == from_pipe.stdout);
+    }
+}
+
+#[test]
+fn several_files_are_compiled_separately() {
+    let directory = directory("units");
+    fs::write(directory.join("main.c"), "int add(int a, int b);\nint main() { return add(40, 2); }\n").unwrap();
+    fs::write(directory.join("add.c"), "int add(int a, int b) { return a + b; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "main.c", "add.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(directory.join("main.s")).unwrap().contains("call add"));
+    assert!(fs::read_to_string(directory.join("add.s")).unwrap().contains("add:"));
+
+    if has_cc() {
+        let output = rust_cc(&directory, &["main.c", "add.c", "-o", "prog"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(Command::new(directory.join("prog")).status().unwrap().code(), Some(42));
+    }
+
+    let output = rust_cc(&directory, &["-S", "main.c", "add.c", "-o", "prog.s"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("-o can't name the output of more than one input file\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn files_with_the_same_name_are_written_apart() {
+    let directory = directory("same-name");
+    fs::create_dir(directory.join("a")).unwrap();
+    fs::create_dir(directory.join("b")).unwrap();
+    fs::write(directory.join("main.c"), "int f();\nint g();\nint main() { return f() + g(); }\n").unwrap();
+    fs::write(directory.join("a/util.c"), "int f() { return 40; }\n").unwrap();
+    fs::write(directory.join("b/util.c"), "int g() { return 2; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "main.c", "a/util.c", "b/util.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(directory.join("main.s")).unwrap().contains("main:"));
+    assert!(fs::read_to_string(directory.join("util-1.s")).unwrap().contains("f:"));
+    assert!(fs::read_to_string(directory.join("util-2.s")).unwrap().contains("g:"));
+    assert!(!directory.join("util.s").exists());
+
+    if has_cc() {
+        let output = rust_cc(&directory, &["main.c", "a/util.c", "b/util.c", "-o", "prog"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(Command::new(directory.join("prog")).status().unwrap().code(), Some(42));
+    }
+}
+
+#[test]
+fn every_file_is_compiled_whatever_fails() {
+    let directory = directory("failing-unit");
+    fs::write(directory.join("bad.c"), "int main() { return 0 }\n").unwrap();
+    fs::write(directory.join("undeclared.c"), "int f() { return y; }\n").unwrap();
+    fs::write(directory.join("good.c"), "int main() { return 0; }\n").unwrap();
+    let output = rust_cc(&directory, &["-S", "--error-format=short", "bad.c", "undeclared.c", "good.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(stderr.starts_with("bad.c:1:23: error[E0010]: "), "{}", stderr);
+    assert!(stderr.contains("\nundeclared.c:1:18: error[E0102]: "), "{}", stderr);
+    assert!(!stderr.contains("good.c"), "{}", stderr);
+    assert!(directory.join("good.s").exists());
+    assert!(!directory.join("bad.s").exists());
+}
+
+#[test]
+fn files_compiled_at_once_come_out_as_if_one_at_a_time() {
+    let directory = directory("jobs");
+    let names: Vec<_> = (0..8).map(|n| format!("unit{}.c", n)).collect();
+    for (n, name) in names.iter().enumerate() {
+        fs::write(directory.join(name), format!("int f{0}(int a) {{ int b = a * {0}; return b + {0}; }}\n", n)).unwrap();
+    }
+    let mut outputs = Vec::new();
+    for jobs in &["-j1", "-j4"] {
+        let mut args = vec![*jobs, "-S", "-O2"];
+        args.extend(names.iter().map(|name| &name[..]));
+        let output = rust_cc(&directory, &args);
+        assert!(output.status.success(), "{}", stderr(&output));
+        outputs.push(names.iter().map(|name| fs::read(directory.join(name).with_extension("s")).unwrap()).collect::<Vec<_>>());
+    }
+    assert_eq!(outputs[0], outputs[1]);
+
+    // What is wrong is reported file by file, in the order they were given,
+    // however the compiling of them finishes.
+    fs::write(directory.join("slow.c"), "int main() { return x; }\n".repeat(200)).unwrap();
+    fs::write(directory.join("fast.c"), "int main() { return 0 }\n").unwrap();
+    let expected = stderr(&rust_cc(&directory, &["-j", "1", "-S", "--error-format=short", "--max-errors=0", "slow.c", "fast.c"]));
+    assert!(expected.starts_with("slow.c:1:21: error[E0102]: "), "{}", expected);
+    assert!(expected.contains("\nfast.c:1:23: error[E0010]: "), "{}", expected);
+    for _ in 0..4 {
+        assert_eq!(stderr(&rust_cc(&directory, &["-j", "2", "-S", "--error-format=short", "--max-errors=0", "slow.c", "fast.c"])), expected);
+    }
+
+    let output = rust_cc(&directory, &["-j", "0", "unit0.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("-j needs a number of jobs, at least 1\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn executables_are_linked_with_cc() {
+    if !has_cc() {
+        return;
+    }
+    let directory = directory("link");
+    fs::write(directory.join("answer.c"), "int main() { return 42; }\n").unwrap();
+    let output = rust_cc(&directory, &["answer.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(Command::new(directory.join("a.out")).status().unwrap().code(), Some(42));
+    assert!(!directory.join("answer.s").exists());
+
+    let output = rust_cc(&directory, &["--save-temps", "answer.c", "-o", "answer"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(Command::new(directory.join("answer")).status().unwrap().code(), Some(42));
+    assert!(fs::read_to_string(directory.join("answer.s")).unwrap().contains("main:"));
+
+    // What the linker says, and the status it exits with, are passed on.
+    fs::write(directory.join("undefined.c"), "int f();\nint main() { return f(); }\n").unwrap();
+    let output = rust_cc(&directory, &["undefined.c", "-o", "undefined"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("f"), "{}", stderr(&output));
+    assert!(!directory.join("undefined").exists());
+
+    let output = rust_cc(&directory, &["--cc", "no-such-cc", "answer.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("no-such-cc: "), "{}", stderr(&output));
+}
+
+#[test]
+fn object_files_are_assembled_with_cc() {
+    if !has_cc() {
+        return;
+    }
+    let directory = directory("object");
+    fs::create_dir(directory.join("src")).unwrap();
+    fs::write(directory.join("src/main.c"), "int add(int a, int b);\nint main() { return add(40, 2); }\n").unwrap();
+    fs::write(directory.join("src/add.c"), "int add(int a, int b) { return a + b; }\n").unwrap();
+    let output = rust_cc(&directory, &["-c", "src/main.c", "src/add.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!directory.join("main.s").exists());
+    let nm = Command::new("nm").arg("main.o").current_dir(&directory).output().unwrap();
+    let symbols = String::from_utf8_lossy(&nm.stdout).into_owned();
+    assert!(symbols.contains("T main"), "{}", symbols);
+    assert!(symbols.contains("U add"), "{}", symbols);
+
+    let output = rust_cc(&directory, &["-c", "src/add.c", "-o", "sum.o"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(directory.join("sum.o").exists());
+
+    let gcc = Command::new("cc").args(["main.o", "sum.o", "-o", "prog"]).current_dir(&directory).output().unwrap();
+    assert!(gcc.status.success(), "{}", stderr(&gcc));
+    assert_eq!(Command::new(directory.join("prog")).status().unwrap().code(), Some(42));
+
+    // As with `gcc`, `-o` can't name the output of several files.
+    let output = rust_cc(&directory, &["-c", "src/main.c", "src/add.c", "-o", "both.o"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!directory.join("both.o").exists());
+}
+
+#[test]
+fn assembly_is_written_with_s_even_with_c() {
+    let directory = directory("assembly");
+    fs::write(directory.join("answer.c"), "int main() { return 42; }\n").unwrap();
+    for args in &[["-S", "-c"], ["-c", "-S"]] {
+        let _ = fs::remove_file(directory.join("answer.s"));
+        let output = rust_cc(&directory, &[args[0], args[1], "answer.c"]);
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(fs::read_to_string(directory.join("answer.s")).unwrap().contains("main:"));
+        assert!(!directory.join("answer.o").exists());
+    }
+
+    // No other program is needed for it.
+    let output = rust_cc(&directory, &["-S", "--cc", "no-such-cc", "answer.c", "-o", "renamed.s"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(directory.join("renamed.s")).unwrap().contains("main:"));
+}
+
+#[test]
+fn standards_gate_features() {
+    let directory = directory("standards");
+    let cases = [
+        ("comment.c", "int main() {\n    return 0; // done\n}\n", "comment.c:2:15: error[E0003]: `//` comments are a C99 feature; use --std=c99\n"),
+        (
+            "mixed.c",
+            "int main() {\n    int a = 1;\n    a++;\n    int b = a;\n    return b;\n}\n",
+            "mixed.c:4:5: error[E0003]: declarations after statements are a C99 feature; use --std=c99\n",
+        ),
+        (
+            "for.c",
+            "int main() {\n    int n = 0;\n    for (int i = 0; i < 3; i++)\n        n += i;\n    return n;\n}\n",
+            "for.c:3:10: error[E0003]: declarations in `for` loops are a C99 feature; use --std=c99\n",
+        ),
+        (
+            "inline.c",
+            "inline int one() { return 1; }\nint main() { return one(); }\n",
+            "inline.c:1:1: error[E0003]: `inline` functions are a C99 feature; use --std=c99\n",
+        ),
+    ];
+    for &(name, source, error) in &cases {
+        fs::write(directory.join(name), source).unwrap();
+        for standard in &["--std=c99", "--std=c11"] {
+            let output = rust_cc(&directory, &[standard, "-S", name]);
+            assert!(output.status.success(), "{} with {}: {}", name, standard, stderr(&output));
+        }
+        let output = rust_cc(&directory, &["--std=c89", "-S", "--error-format=short", name]);
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(stderr(&output), error);
+    }
+
+    let output = rust_cc(&directory, &["--std=c17", "-S", "comment.c"]);
+    assert!(stderr(&output).starts_with("unknown standard `c17`\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn warnings_can_be_turned_off_or_made_errors() {
+    let directory = directory("warnings");
+    fs::write(directory.join("unused.c"), "int main() { int x; return 0; }\n").unwrap();
+    let warning = "unused.c:1:18: warning[W0002]: unused variable `x` [-Wunused-variable]\n";
+    let error = "unused.c:1:18: error[W0002]: unused variable `x` [-Werror=unused-variable]\n";
+    let cases: &[(&[&str], bool, &str)] = &[
+        (&[], true, warning),
+        (&["-Werror"], false, error),
+        (&["-Werror", "-Wno-unused-variable"], true, ""),
+        (&["-Werror", "-Wno-error=unused-variable"], true, warning),
+        (&["-Werror=unused-variable"], false, error),
+        (&["-Werror=return-type"], true, warning),
+        (&["-Wno-unused-variable", "-Wunused-variable"], true, warning),
+        (&["-Werror", "-w"], true, ""),
+    ];
+    for &(flags, succeeds, stderr_expected) in cases {
+        let mut args = flags.to_vec();
+        args.extend(&["-S", "--error-format=short", "unused.c"]);
+        let output = rust_cc(&directory, &args);
+        assert_eq!(output.status.success(), succeeds, "{:?}", flags);
+        assert_eq!(stderr(&output), stderr_expected, "{:?}", flags);
+        assert_eq!(directory.join("unused.s").exists(), succeeds, "{:?}", flags);
+        let _ = fs::remove_file(directory.join("unused.s"));
+    }
+
+    // Errors stay errors, and an unknown warning is ignored, as `gcc` ignores
+    // those that make rules pass, but noted with `--verbose`.
+    fs::write(directory.join("undeclared.c"), "int main() { return y; }\n").unwrap();
+    let output = rust_cc(&directory, &["-w", "-Wno-unused-frobnication", "-S", "--error-format=short", "undeclared.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("undeclared.c:1:21: error[E0102]: "), "{}", stderr(&output));
+    let output = rust_cc(&directory, &["-w", "-Wno-unused-frobnication", "-S", "--error-format=short", "undeclared.c", "--verbose"]);
+    let stderr = stderr(&output);
+    assert!(stderr.starts_with("rust-cc: note: ignoring `-Wno-unused-frobnication`, since there is no `unused-frobnication` warning\n"), "{}", stderr);
+    assert!(stderr.contains("undeclared.c:1:21: error[E0102]: "), "{}", stderr);
+}
+
+#[test]
+fn make_rules_flags_are_accepted() {
+    let directory = directory("make");
+    fs::write(directory.join("foo.c"), "int main() { return 0; }\n").unwrap();
+    let output = rust_cc(&directory, &["-O2", "-g", "-Wall", "-fwhatever", "-Iinc", "-DDEBUG", "-std=c99", "-S", "foo.c", "-o", "foo.s"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stderr(&output), "");
+    assert!(fs::read_to_string(directory.join("foo.s")).unwrap().contains("main:"));
+
+    let output = rust_cc(&directory, &["--verbose", "-fwhatever", "-S", "foo.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stderr(&output), "rust-cc: note: ignoring unknown flag `-fwhatever`\n");
+}
+
+#[test]
+fn targets_decide_the_dialect() {
+    let linux = check_snapshot("targets", &["--target", "x86_64-linux", "--emit", "asm"], "x86_64-linux.s");
+    let macos = check_snapshot("targets", &["--target=x86_64-macos", "--emit", "asm"], "x86_64-macos.s");
+    let aarch64 = check_snapshot("targets", &["--target", "aarch64-linux", "--emit", "asm"], "aarch64-linux.s");
+
+    // Mach-O puts `_` before symbols and drops the `.` from local labels.
+    assert!(linux.contains("\nmain:\n") && linux.contains(".Lstr0"), "{}", linux);
+    assert!(macos.contains("\n_main:\n") && macos.contains("call _add") && macos.contains("Lstr0"), "{}", macos);
+    assert!(!macos.contains(".Lstr0") && !macos.contains(".type"), "{}", macos);
+    // The same ELF directives, but AArch64 instructions.
+    assert!(linux.contains("movl ") && !aarch64.contains("movl "), "{}", aarch64);
+    assert!(aarch64.contains(".type main, %function") && aarch64.contains("bl add"), "{}", aarch64);
+
+    let directory = directory("targets");
+    let output = rust_cc(&directory, &["--target", "riscv64-linux", "-S", "targets.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "unknown target `riscv64-linux`; the targets are x86_64-linux, x86_64-macos, x86_64-windows, aarch64-linux, aarch64-macos\n",
+    );
+
+    // The host's `cc` isn't run for another target, unless a cross compiler
+    // is named.
+    fs::write(directory.join("main.c"), "int main() { return 0; }\n").unwrap();
+    let other = if cfg!(target_arch = "aarch64") { "x86_64-windows" } else { "aarch64-linux" };
+    let output = rust_cc(&directory, &["--target", other, "main.c"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with(&format!("can't assemble for {} with the ", other)), "{}", stderr(&output));
+    let output = rust_cc(&directory, &["--target", other, "--cc", "true", "main.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+fn timings_list_every_stage() {
+    let emit = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/emit");
+    let output = rust_cc(&emit, &["--timings", "-O2", "--emit", "asm", "sample.c"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stderr = stderr(&output);
+    let stages: Vec<_> = stderr.lines().map(|line| line.split_whitespace().next().unwrap()).collect();
+    assert_eq!(stages, ["stage", "lex", "parse", "semantic", "fold", "propagate", "inline", "strength-reduce", "jumps", "dead-stores", "peephole", "codegen", "total"]);
+    assert!(stderr.contains("ms  32 lexemes\n"), "{}", stderr);
+
+    // Without it, nothing is said.
+    let output = rust_cc(&emit, &["-O2", "--emit", "asm", "sample.c"]);
+    assert_eq!(self::stderr(&output), "");
+}
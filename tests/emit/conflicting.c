@@ -0,0 +1,9 @@
+int area(int width, int height);
+
+int main() {
+    return area(2, 3);
+}
+
+long area(int width, int height) {
+    return width * height;
+}
@@ -0,0 +1,5 @@
+static int count;
+
+int count;
+
+int main() { return 0; }
@@ -0,0 +1,23 @@
+typedef   unsigned long  size;
+struct   pair{int first ;int second;};
+
+
+/* Swaps the halves
+   of a pair. */
+void swap(struct pair*p){
+  int t=p->first;p->first=p->second ;
+      p->second=t; // done
+}
+size total(int*values,int count){
+size sum=0;
+for(int i=0;i<count;i++)
+if(values[i]>0)sum+=values[i];
+  else
+sum-=(size)-values[i];
+    return sum>100?100:sum;
+}
+int main( ){int values[3]={1,-2,3};
+    struct pair p={1,2};
+switch(values[0]){case 1:swap(&p);break;
+default:return -1;}
+  return total(values,3)+p.first*2;}
@@ -0,0 +1,5 @@
+int scale(int value, int factor);
+
+int main() {
+    return scale(2);
+}
@@ -0,0 +1,5 @@
+int main() {
+    const int limit = 10;
+    limit = 1;
+    return limit;
+}
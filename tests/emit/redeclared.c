@@ -0,0 +1,7 @@
+int main() {
+    int total = 0;
+    for (int i = 0; i < 10; i++)
+        total += i;
+    int total = 1;
+    return total;
+}
@@ -0,0 +1,8 @@
+int square(int x) {
+    return x * x;
+}
+
+int main() {
+    int n = 3;
+    return square(n) + 1;
+}
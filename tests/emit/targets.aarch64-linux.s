@@ -0,0 +1,80 @@
+    .file 1 "targets.c"
+    .text
+    .globl add
+    .type add, %function
+add:
+    // 3: int add(int a, int b) {
+    .loc 1 3 1
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    stur w0, [x29, #-4]
+    stur w1, [x29, #-8]
+    // 4: return a + b;
+    .loc 1 4 5
+    ldur w0, [x29, #-4]
+    mov x11, x0
+    ldur w0, [x29, #-8]
+    mov w1, w0
+    mov x0, x11
+    add w0, w0, w1
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    // 5: }
+    .loc 1 5 1
+    mov w0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .size add, .-add
+    .globl main
+    .type main, %function
+main:
+    // 7: int main() {
+    .loc 1 7 1
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    // 8: char *greeting = "hi";
+    .loc 1 8 10
+    adrp x0, .Lstr0
+    add x0, x0, :lo12:.Lstr0
+    stur x0, [x29, #-8]
+    // 9: return add(counter, greeting[0]);
+    .loc 1 9 5
+    ldur x0, [x29, #-8]
+    mov x11, x0
+    mov w0, #0
+    sxtw x0, w0
+    mov x1, x0
+    mov x0, x11
+    add x0, x0, x1
+    ldrsb w0, [x0]
+    mov x11, x0
+    adrp x10, counter
+    add x10, x10, :lo12:counter
+    ldr w0, [x10]
+    mov x12, x0
+    mov x0, x12
+    mov x1, x11
+    bl add
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    // 10: }
+    .loc 1 10 1
+    mov w0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    .size main, .-main
+    .data
+    .balign 4
+    .globl counter
+counter:
+    .long 1
+    .section .rodata
+.Lstr0:
+    .asciz "hi"
+    .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,10 @@
+int counter = 1;
+
+int add(int a, int b) {
+    return a + b;
+}
+
+int main() {
+    char *greeting = "hi";
+    return add(counter, greeting[0]);
+}
@@ -0,0 +1,77 @@
+    .file 1 "targets.c"
+    .text
+    .globl add
+    .type add, @function
+add:
+    # 3: int add(int a, int b) {
+    .loc 1 3 1
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $16, %rsp
+    movl %edi, -4(%rbp)
+    movl %esi, -8(%rbp)
+    # 4: return a + b;
+    .loc 1 4 5
+    movl -4(%rbp), %eax
+    movq %rax, %r10
+    movl -8(%rbp), %eax
+    movl %eax, %ecx
+    movq %r10, %rax
+    addl %ecx, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    # 5: }
+    .loc 1 5 1
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .size add, .-add
+    .globl main
+    .type main, @function
+main:
+    # 7: int main() {
+    .loc 1 7 1
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $16, %rsp
+    # 8: char *greeting = "hi";
+    .loc 1 8 10
+    leaq .Lstr0(%rip), %rax
+    movq %rax, -8(%rbp)
+    # 9: return add(counter, greeting[0]);
+    .loc 1 9 5
+    movq -8(%rbp), %rax
+    movq %rax, %r10
+    movl $0, %eax
+    movslq %eax, %rax
+    movq %rax, %rcx
+    movq %r10, %rax
+    addq %rcx, %rax
+    movsbl (%rax), %eax
+    movq %rax, %r10
+    movl counter(%rip), %eax
+    movq %rax, %r11
+    movq %r11, %rdi
+    movq %r10, %rsi
+    call add
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    # 10: }
+    .loc 1 10 1
+    movl $0, %eax
+    movq %rbp, %rsp
+    popq %rbp
+    ret
+    .size main, .-main
+    .data
+    .balign 4
+    .globl counter
+counter:
+    .long 1
+    .section .rodata
+.Lstr0:
+    .asciz "hi"
+    .section .note.GNU-stack,"",@progbits
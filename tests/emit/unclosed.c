@@ -0,0 +1,13 @@
+int first() {
+    return 1;
+
+int second() {
+    if (1) {
+        return 2;
+    }
+    return 0;
+}
+
+int main() {
+    return first() + second();
+}
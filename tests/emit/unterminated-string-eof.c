@@ -0,0 +1,4 @@
+int main() {
+    return 0;
+}
+char *s = "never closed
\ No newline at end of file
@@ -0,0 +1,9 @@
+int printf(const char *format, ...);
+
+int main() {
+    int a = 17;
+    int b = 5;
+    printf("%d %d %d %d %d\n", a + b, a - b, a * b, a / b, a % b);
+    printf("%d %d\n", -a / b, -a % b);
+    return (a * b - 3) % 256;
+}
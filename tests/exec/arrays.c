@@ -0,0 +1,20 @@
+int printf(const char *format, ...);
+
+int sum(int *values, int count) {
+    int total = 0;
+    for (int i = 0; i < count; i++)
+        total += values[i];
+    return total;
+}
+
+int main() {
+    int squares[10];
+    for (int i = 0; i < 10; i++)
+        squares[i] = i * i;
+    int grid[3][4];
+    for (int i = 0; i < 3; i++)
+        for (int j = 0; j < 4; j++)
+            grid[i][j] = i * 4 + j;
+    printf("%d %d %d\n", sum(squares, 10), grid[2][3], sum(grid[1], 4));
+    return sum(squares, 5) + grid[1][2];
+}
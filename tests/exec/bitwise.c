@@ -0,0 +1,12 @@
+int printf(const char *format, ...);
+
+int main() {
+    int a = 0x5a;
+    int b = 0x0f;
+    printf("%d %d %d\n", a & b, a | b, a ^ b);
+    int c = a;
+    c &= 0x3c;
+    c |= 1;
+    c ^= 0x10;
+    return c;
+}
@@ -0,0 +1,17 @@
+int main() {
+    int total = 0;
+    for (int i = 0; i < 100; i++) {
+        if (i % 3 == 0)
+            continue;
+        if (i > 20)
+            break;
+        total += i;
+    }
+    int n = 0;
+    while (1) {
+        n++;
+        if (n == 7)
+            break;
+    }
+    return total + n;
+}
@@ -0,0 +1,21 @@
+int printf(const char *format, ...);
+
+int sort(int *values, int count) {
+    for (int i = 0; i < count; i++)
+        for (int j = 0; j + 1 < count - i; j++)
+            if (values[j] > values[j + 1]) {
+                int t = values[j];
+                values[j] = values[j + 1];
+                values[j + 1] = t;
+            }
+    return 0;
+}
+
+int main() {
+    int values[8] = {5, 3, 9, 1, 7, 2, 8, 4};
+    sort(values, 8);
+    for (int i = 0; i < 8; i++)
+        printf("%d ", values[i]);
+    printf("\n");
+    return values[0] + values[7] * 10;
+}
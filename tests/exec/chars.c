@@ -0,0 +1,10 @@
+int putchar(int c);
+
+int main() {
+    char c;
+    for (c = 'a'; c <= 'e'; c++)
+        putchar(c - 'a' + 'A');
+    putchar('\n');
+    char high = 'z';
+    return high - 'a' + '\t';
+}
@@ -0,0 +1,8 @@
+int printf(const char *format, ...);
+
+int main() {
+    int a = 3;
+    int b = 7;
+    printf("%d %d %d %d %d %d\n", a < b, a > b, a <= 3, b >= 8, a == 3, a != b);
+    return (a < b) + (b < a) * 2 + (a == a) * 4;
+}
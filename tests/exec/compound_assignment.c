@@ -0,0 +1,11 @@
+int main() {
+    int x = 10;
+    x += 5;
+    x -= 3;
+    x *= 4;
+    x /= 3;
+    x %= 7;
+    x <<= 4;
+    x >>= 1;
+    return x;
+}
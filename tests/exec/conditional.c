@@ -0,0 +1,7 @@
+int max(int a, int b) {
+    return a > b ? a : b;
+}
+
+int main() {
+    return max(3, 9) + (max(-1, -4) < 0 ? 10 : 20);
+}
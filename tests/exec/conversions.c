@@ -0,0 +1,10 @@
+int main() {
+    char c = 300;
+    int i = c;
+    long l = -1;
+    unsigned int u = 0;
+    u = u - 1;
+    long widened = u;
+    widened = widened + 1;
+    return i + (l < 0) + widened / 4096 / 4096 / 1024;
+}
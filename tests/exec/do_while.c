@@ -0,0 +1,15 @@
+int printf(const char *format, ...);
+
+int main() {
+    int i = 10;
+    int runs = 0;
+    do {
+        runs++;
+    } while (i < 5);
+    do {
+        printf("%d ", i);
+        i -= 3;
+    } while (i > 0);
+    printf("\n");
+    return runs * 10 + i + 5;
+}
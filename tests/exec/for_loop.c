@@ -0,0 +1,11 @@
+int printf(const char *format, ...);
+
+int main() {
+    int total = 0;
+    int i;
+    for (i = 0; i < 10; i++)
+        total += i * i;
+    for (int j = 3; j > 0; j--)
+        printf("%d\n", j);
+    return total % 256;
+}
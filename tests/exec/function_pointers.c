@@ -0,0 +1,11 @@
+int add(int a, int b) {
+    return a + b;
+}
+
+int apply(int (*f)(int, int), int a, int b) {
+    return f(a, b);
+}
+
+int main() {
+    return apply(add, 20, 22);
+}
@@ -0,0 +1,19 @@
+int add(int a, int b) {
+    return a + b;
+}
+
+int twice(int x) {
+    return add(x, x);
+}
+
+int six(int a, int b, int c, int d, int e, int f) {
+    return a - b + c - d + e - f;
+}
+
+int eight(int a, int b, int c, int d, int e, int f, int g, int h) {
+    return a * 1 + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8;
+}
+
+int main() {
+    return twice(add(3, 4)) + six(60, 5, 4, 3, 2, 1) + eight(1, 2, 3, 4, 5, 6, 7, 8) % 100;
+}
@@ -0,0 +1,15 @@
+int counter;
+int start = 40;
+int table[4] = {1, 2, 3, 4};
+
+int bump(int by) {
+    counter += by;
+    return counter;
+}
+
+int main() {
+    bump(start);
+    bump(table[3]);
+    table[0] = counter;
+    return table[0] + table[1];
+}
@@ -0,0 +1,13 @@
+int main() {
+    int i = 0;
+    int total = 0;
+again:
+    total += i;
+    i++;
+    if (i < 10)
+        goto again;
+    goto done;
+    total = 0;
+done:
+    return total;
+}
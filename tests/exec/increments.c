@@ -0,0 +1,11 @@
+int printf(const char *format, ...);
+
+int main() {
+    int i = 5;
+    int a = i++;
+    int b = ++i;
+    int c = i--;
+    int d = --i;
+    printf("%d %d %d %d %d\n", a, b, c, d, i);
+    return a + b + c + d + i;
+}
@@ -0,0 +1,25 @@
+int printf(const char *format, ...);
+
+struct node {
+    int value;
+    struct node *next;
+};
+
+int sum(struct node *list) {
+    int total = 0;
+    for (; list; list = list->next)
+        total += list->value;
+    return total;
+}
+
+int main() {
+    struct node nodes[5];
+    for (int i = 0; i < 5; i++) {
+        nodes[i].value = (i + 1) * 3;
+        nodes[i].next = 0;
+        if (i > 0)
+            nodes[i - 1].next = &nodes[i];
+    }
+    printf("%d\n", sum(&nodes[0]));
+    return sum(&nodes[2]);
+}
@@ -0,0 +1,14 @@
+int main() {
+    int a = 1;
+    int b = 2;
+    {
+        int a = 10;
+        b = b + a;
+        {
+            int b = 100;
+            a = a + b;
+        }
+        b = b + a;
+    }
+    return a + b;
+}
@@ -0,0 +1,17 @@
+int printf(const char *format, ...);
+
+int calls;
+
+int touch(int value) {
+    calls++;
+    return value;
+}
+
+int main() {
+    int a = touch(0) && touch(1);
+    int b = touch(1) || touch(0);
+    int c = touch(1) && touch(2);
+    int d = touch(0) || touch(0);
+    printf("%d %d %d %d\n", a, b, c, d);
+    return calls;
+}
@@ -0,0 +1,14 @@
+int printf(const char *format, ...);
+
+long power(long base, int exponent) {
+    long result = 1;
+    while (exponent-- > 0)
+        result *= base;
+    return result;
+}
+
+int main() {
+    long big = power(3, 30);
+    printf("%ld\n", big);
+    return big % 1000 / 4;
+}
@@ -0,0 +1,20 @@
+int printf(const char *format, ...);
+
+int main() {
+    int count = 0;
+    for (int i = 2; i < 50; i++) {
+        int prime = 1;
+        for (int j = 2; j * j <= i; j++) {
+            if (i % j == 0) {
+                prime = 0;
+                break;
+            }
+        }
+        if (prime) {
+            printf("%d ", i);
+            count++;
+        }
+    }
+    printf("\n");
+    return count;
+}
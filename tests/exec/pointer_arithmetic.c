@@ -0,0 +1,9 @@
+int main() {
+    int values[6] = {5, 10, 15, 20, 25, 30};
+    int *p = values;
+    int *q = &values[5];
+    p += 2;
+    q--;
+    int distance = q - p;
+    return *p + *q + distance + (p < q) + p[1];
+}
@@ -0,0 +1,16 @@
+int swap(int *a, int *b) {
+    int t = *a;
+    *a = *b;
+    *b = t;
+    return t;
+}
+
+int main() {
+    int x = 3;
+    int y = 8;
+    int *p = &x;
+    int **pp = &p;
+    swap(&x, &y);
+    **pp += 1;
+    return x * 10 + y;
+}
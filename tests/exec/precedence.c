@@ -0,0 +1,6 @@
+int main() {
+    int x = 2 + 3 * 4 - 10 / 2;
+    int y = (2 + 3) * (4 - 10) / 2;
+    int z = 1 + 2 << 3 >> 1;
+    return x + y + z;
+}
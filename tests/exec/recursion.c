@@ -0,0 +1,24 @@
+int printf(const char *format, ...);
+
+int factorial(int n) {
+    if (n <= 1)
+        return 1;
+    return n * factorial(n - 1);
+}
+
+int fibonacci(int n) {
+    if (n < 2)
+        return n;
+    return fibonacci(n - 1) + fibonacci(n - 2);
+}
+
+int gcd(int a, int b) {
+    if (b == 0)
+        return a;
+    return gcd(b, a % b);
+}
+
+int main() {
+    printf("%d %d %d\n", factorial(10), fibonacci(20), gcd(1071, 462));
+    return fibonacci(12) % 256;
+}
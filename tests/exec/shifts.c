@@ -0,0 +1,12 @@
+int printf(const char *format, ...);
+
+int main() {
+    int a = 0x5a;
+    long big = 1;
+    printf("%d %d %d\n", a << 3, a >> 2, ~a);
+    big = big << 40;
+    printf("%ld %ld\n", big, big >> 35);
+    int n = -64;
+    n >>= 3;
+    return (a >> 4) - n;
+}
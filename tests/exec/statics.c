@@ -0,0 +1,12 @@
+int next() {
+    static int n = 5;
+    return n++;
+}
+
+static int hidden = 3;
+
+int main() {
+    next();
+    next();
+    return next() * hidden;
+}
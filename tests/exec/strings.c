@@ -0,0 +1,27 @@
+int printf(const char *format, ...);
+int puts(const char *s);
+
+int length(char *s) {
+    int n = 0;
+    while (*s++)
+        n++;
+    return n;
+}
+
+int reverse(char *s) {
+    char *end = s + length(s) - 1;
+    while (s < end) {
+        char t = *s;
+        *s++ = *end;
+        *end-- = t;
+    }
+    return 0;
+}
+
+int main() {
+    char word[16] = "compiler";
+    reverse(word);
+    puts(word);
+    printf("%s has %d letters\n", "differential", length("differential"));
+    return length(word);
+}
@@ -0,0 +1,25 @@
+struct point {
+    int x;
+    int y;
+};
+
+struct rectangle {
+    struct point corner;
+    int width;
+    int height;
+};
+
+int area(struct rectangle *r) {
+    return r->width * r->height;
+}
+
+int main() {
+    struct rectangle r;
+    r.corner.x = 2;
+    r.corner.y = 3;
+    r.width = 5;
+    r.height = 6;
+    struct point *p = &r.corner;
+    p->x += 10;
+    return area(&r) + r.corner.x + p->y;
+}
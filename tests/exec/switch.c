@@ -0,0 +1,24 @@
+int printf(const char *format, ...);
+
+int describe(int n) {
+    switch (n) {
+    case 0:
+        return 10;
+    case 1:
+    case 2:
+        return 20;
+    case 5:
+        n = n * 2;
+    case 6:
+        return n + 1;
+    default:
+        return -1;
+    }
+}
+
+int main() {
+    for (int i = 0; i < 8; i++)
+        printf("%d ", describe(i));
+    printf("\n");
+    return describe(5) + describe(6) + describe(1);
+}
@@ -0,0 +1,18 @@
+typedef int number;
+struct pair {
+    number first;
+    number second;
+};
+typedef struct pair pair;
+
+number total(pair *p) {
+    return p->first + p->second;
+}
+
+int main() {
+    pair p;
+    const number scale = 3;
+    p.first = 4;
+    p.second = 5;
+    return total(&p) * scale;
+}
@@ -0,0 +1,8 @@
+int printf(const char *format, ...);
+
+int main() {
+    int a = 6;
+    printf("%d %d %d %d\n", -a, ~a, !a, !!a);
+    printf("%d %d\n", -(-a), ~~a);
+    return !0 + !5;
+}
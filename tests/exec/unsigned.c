@@ -0,0 +1,9 @@
+int printf(const char *format, ...);
+
+int main() {
+    unsigned int u = 0;
+    u = u - 1;
+    unsigned int half = u / 2;
+    printf("%u %u %u\n", u, half, u >> 28);
+    return (u > 5) + (half % 7) * 2;
+}
@@ -0,0 +1,67 @@
+//! Formats every C file under `tests` and checks that formatting changes
+//! nothing but the whitespace, and that formatting again changes nothing.
+
+extern crate rust_cc;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rust_cc::{lexer, parser};
+use rust_cc::format::{format_source, FormatOptions};
+use rust_cc::lexer::LexemeKind;
+use rust_cc::standard::Standard;
+
+/// The `.c` files under `directory`, in order.
+fn c_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<_> = fs::read_dir(directory).unwrap().map(|entry| entry.unwrap().path()).collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            c_files(&path, files);
+        } else if path.extension().is_some_and(|extension| extension == "c") {
+            files.push(path);
+        }
+    }
+}
+
+/// The lexemes of `source`, with the space at the end of comments and
+/// `#pragma`s, which formatting takes off, left out.
+fn lexemes(source: &str) -> Vec<LexemeKind<'_>> {
+    lexer::lex_with_comments(source, Standard::default()).0.into_iter()
+        .map(|lexeme| match lexeme.kind {
+            LexemeKind::Comment(text) => LexemeKind::Comment(text.trim_end()),
+            LexemeKind::Pragma(text) => LexemeKind::Pragma(text.trim_end()),
+            kind => kind,
+        })
+        .collect()
+}
+
+#[test]
+fn formatting_the_corpus_only_changes_whitespace_and_is_idempotent() {
+    let mut files = Vec::new();
+    c_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests"), &mut files);
+    let mut formatted_count = 0;
+    for file in files {
+        let source = fs::read_to_string(&file).unwrap();
+        // Files with errors the lexer finds on purpose can't be formatted.
+        let formatted = match format_source(&source, &FormatOptions::default()) {
+            Ok(formatted) => formatted,
+            Err(_) => continue,
+        };
+        formatted_count += 1;
+        // The programs that are meant to compile are written the way the
+        // formatter writes them.
+        if file.parent().is_some_and(|parent| parent.ends_with("cases/valid") || parent.ends_with("exec")) {
+            assert_eq!(formatted, source, "{} isn't formatted", file.display());
+        }
+        assert_eq!(lexemes(&formatted), lexemes(&source), "formatting {} changed more than whitespace:\n{}", file.display(), formatted);
+        let again = format_source(&formatted, &FormatOptions::default()).unwrap();
+        assert_eq!(again, formatted, "formatting {} again changed it", file.display());
+
+        // What parsed before parses the same afterwards.
+        if let Ok(program) = parser::parse_program(&lexer::lex_str(&source).unwrap()) {
+            assert_eq!(parser::parse_program(&lexer::lex_str(&formatted).unwrap()).unwrap(), program, "{}", file.display());
+        }
+    }
+    assert!(formatted_count > 50, "only {} files were formatted", formatted_count);
+}
@@ -0,0 +1,111 @@
+//! Runs the front end over the programs in `tests/cases`. Each `valid/*.c` must
+//! compile, and its pretty-printed AST must match the `.ast` file beside it.
+//! Each `invalid/*.c` must fail, with diagnostics matching the `.stderr` file
+//! beside it. A valid case that compiles with warnings has a `.stderr` file too.
+//!
+//! To add a case, write the `.c` file and run with `UPDATE_SNAPSHOTS=1` to
+//! create its snapshot from the current output, then check that it's right.
+
+extern crate rust_cc;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rust_cc::{ast, lexer, parser, semantic};
+use rust_cc::semantic::Severity;
+
+/// Lexes, parses, and checks `source`, returning its pretty-printed AST, or the
+/// diagnostics the driver would print for it, one per line. Warnings come with
+/// the AST when there are no errors.
+fn compile(name: &str, source: &str) -> Result<(String, String), String> {
+    let diagnostic = |position: Option<(usize, usize)>, severity: Severity, message: String| match position {
+        Some((line, column)) => format!("{}:{}:{}: {}: {}\n", name, line, column, severity, message),
+        None => format!("{}: {}: {}\n", name, severity, message),
+    };
+
+    let lexemes = lexer::lex_str(source).map_err(|error| diagnostic(Some(error.position()), Severity::Error, error.message()))?;
+    let program = parser::parse_program(&lexemes)
+        .map_err(|errors| errors.iter().map(|error| diagnostic(error.position(), Severity::Error, error.message())).collect::<String>())?;
+
+    let checked = semantic::check_program(&program);
+    let printed = checked.iter()
+        .map(|checked| diagnostic(Some(checked.position()), checked.severity(), checked.message()))
+        .collect();
+    if checked.iter().any(|diagnostic| diagnostic.severity() == Severity::Error) {
+        return Err(printed);
+    }
+
+    Ok((ast::pretty_print(&program), printed))
+}
+
+/// The `.c` files in `tests/cases/<directory>`, in order.
+fn cases(directory: &str) -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases").join(directory);
+    let mut cases: Vec<PathBuf> = fs::read_dir(&directory)
+        .unwrap_or_else(|error| panic!("{}: {}", directory.display(), error))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "c"))
+        .collect();
+    cases.sort();
+    cases
+}
+
+/// Compares `actual` against the snapshot at `path`, or overwrites the snapshot
+/// when updating. Returns a description of the mismatch, if there is one.
+fn check_snapshot(path: &Path, actual: &str, update: bool) -> Option<String> {
+    check_snapshot_or_absent(path, Some(actual), update)
+}
+
+/// Like `check_snapshot`, except that `None` means there should be no snapshot.
+fn check_snapshot_or_absent(path: &Path, actual: Option<&str>, update: bool) -> Option<String> {
+    let actual = match actual {
+        Some(actual) => actual,
+        None if update => {
+            let _ = fs::remove_file(path);
+            return None;
+        },
+        None if path.exists() => return Some(format!("{} exists, but there is nothing to compare it with", path.display())),
+        None => return None,
+    };
+    if update {
+        fs::write(path, actual).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+        return None;
+    }
+
+    match fs::read_to_string(path) {
+        Ok(ref expected) if expected == actual => None,
+        Ok(expected) => Some(format!("{} doesn't match.\nexpected:\n{}\nactual:\n{}", path.display(), expected, actual)),
+        Err(_) => Some(format!("{} is missing; run with UPDATE_SNAPSHOTS=1 to create it.\nactual:\n{}", path.display(), actual)),
+    }
+}
+
+#[test]
+fn golden_cases() {
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value == "1");
+    let mut failures = Vec::new();
+
+    for (directory, valid) in &[("valid", true), ("invalid", false)] {
+        let cases = cases(directory);
+        assert!(!cases.is_empty(), "no cases in tests/cases/{}", directory);
+
+        for case in cases {
+            let name = case.file_name().unwrap().to_string_lossy().into_owned();
+            let source = fs::read_to_string(&case).unwrap();
+
+            let failure = match (compile(&name, &source), *valid) {
+                (Ok((printed, warnings)), true) => {
+                    let warnings = if warnings.is_empty() { None } else { Some(warnings.as_str()) };
+                    check_snapshot(&case.with_extension("ast"), &printed, update)
+                        .or_else(|| check_snapshot_or_absent(&case.with_extension("stderr"), warnings, update))
+                },
+                (Err(diagnostics), false) => check_snapshot(&case.with_extension("stderr"), &diagnostics, update),
+                (Ok(_), false) => Some(format!("{} compiled, but should have failed", case.display())),
+                (Err(diagnostics), true) => Some(format!("{} failed to compile:\n{}", case.display(), diagnostics)),
+            };
+            failures.extend(failure);
+        }
+    }
+
+    assert!(failures.is_empty(), "{} golden case(s) failed:\n\n{}", failures.len(), failures.join("\n"));
+}
@@ -0,0 +1,69 @@
+//! Inputs that have crashed the lexer or parser, replayed to make sure they now
+//! fail with an error instead. New finds from `fuzz/` go here.
+
+extern crate rust_cc;
+
+use rust_cc::lexer::{lex_str, LexError};
+use rust_cc::parser::{parse_program_partial, ParseError, MAX_DEPTH};
+
+/// Lexes and parses `source`, returning the parse errors.
+fn parse_errors(source: &str) -> Vec<ParseError> {
+    parse_program_partial(&lex_str(source).unwrap()).1
+}
+
+fn nested(open: &str, inner: &str, close: &str, levels: usize) -> String {
+    format!("{}{}{}", open.repeat(levels), inner, close.repeat(levels))
+}
+
+#[test]
+fn int_literal_too_large_for_any_type() {
+    assert_eq!(lex_str("int x = 99999999999999999999;").unwrap_err(), LexError::IntLiteralOutOfRange { line: 1, column: 9 });
+}
+
+#[test]
+fn deeply_nested_parentheses() {
+    let source = format!("int x = {};", nested("(", "1", ")", 10_000));
+    assert_eq!(parse_errors(&source), vec![ParseError::NestingTooDeep { line: 1, column: 9 + MAX_DEPTH }]);
+
+    // Up to the limit is fine.
+    assert_eq!(parse_errors(&format!("int x = {};", nested("(", "1", ")", MAX_DEPTH))), vec![]);
+}
+
+#[test]
+fn deeply_nested_blocks_and_statements() {
+    let blocks = format!("int main() {{ {} }}", nested("{", "", "}", 10_000));
+    assert!(matches!(parse_errors(&blocks)[..], [ParseError::NestingTooDeep { .. }]));
+
+    let ifs = format!("int main() {{ {} }}", nested("if (1) ", ";", "", 10_000));
+    assert!(matches!(parse_errors(&ifs)[..], [ParseError::NestingTooDeep { .. }]));
+}
+
+#[test]
+fn long_operator_chains() {
+    // Chains don't recurse in the parser, but they nest in the tree, which the
+    // array size's evaluation and every later pass recurse over.
+    for source in &[
+        format!("int a[{}1];", "1 + ".repeat(10_000)),
+        format!("int x = {}1;", "-".repeat(10_000)),
+        format!("int main() {{ {}1; }}", "x = ".repeat(10_000)),
+        format!("int main() {{ {}1; }}", "x ? 1 : ".repeat(10_000)),
+        format!("int main() {{ {}; }}", nested("", "x", "[0]", 10_000)),
+        format!("int main() {{ {}; }}", nested("f(", "1", ")", 10_000)),
+    ] {
+        assert!(matches!(parse_errors(source)[..], [ParseError::NestingTooDeep { .. }]), "{}", &source[..20]);
+    }
+}
+
+#[test]
+fn declarator_soup() {
+    assert!(matches!(parse_errors(&format!("int {}p;", "*".repeat(10_000)))[..], [ParseError::NestingTooDeep { .. }]));
+    assert!(matches!(parse_errors(&format!("int a{};", "[1]".repeat(10_000)))[..], [ParseError::NestingTooDeep { .. }]));
+    assert!(matches!(parse_errors(&format!("int a = {};", nested("{", "1", "}", 10_000)))[..], [ParseError::NestingTooDeep { .. }, ..]));
+}
+
+#[test]
+fn truncated_input() {
+    for source in &["int", "int main(", "int main() {", "int main() { return", "int a[", "int a[2] = {", "struct S {", "typedef", "int main() { (int)"] {
+        assert!(!parse_errors(source).is_empty(), "{}", source);
+    }
+}
@@ -0,0 +1,460 @@
+//! Property tests that what the compiler prints lexes and parses back to what
+//! it printed: random lexemes, spelled as `LexemeKind` writes them and laid
+//! out by `format_source`, lex back to the same lexemes, and random
+//! expressions, printed by `to_c_source`, parse back to the same tree.
+//!
+//! The generators and the shrinking are here rather than in a crate, so that
+//! the tests build offline. Each property is checked on `ROUNDTRIP_CASES`
+//! values, 300 by default, from a seed that `ROUNDTRIP_SEED` can set; a
+//! failure is shrunk to a smallest value that still fails, and reported with
+//! the seed to run it again with. What they have found is kept in the
+//! regression tests at the end.
+
+extern crate rust_cc;
+
+use std::env;
+use std::fmt::Debug;
+
+use rust_cc::ast::*;
+use rust_cc::format::{format_source, FormatOptions};
+use rust_cc::lexer::{lex_str, LexemeKind};
+use rust_cc::parser::parse_program;
+
+/// A xorshift generator, which is all the randomness these need.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number below `bound`.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+fn setting(name: &str, default: u64) -> u64 {
+    env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+/// Checks `property` on values from `generate`. The first that fails is
+/// shrunk, by trying each of the smaller values `shrink` gives for it and
+/// keeping the first that still fails, until none does.
+fn check<T, G, S, P>(generate: G, shrink: S, property: P)
+where
+    T: Debug,
+    G: Fn(&mut Rng) -> T,
+    S: Fn(&T) -> Vec<T>,
+    P: Fn(&T) -> Result<(), String>,
+{
+    let seed = setting("ROUNDTRIP_SEED", 0x2545_f491_4f6c_dd1d);
+    for case in 0..setting("ROUNDTRIP_CASES", 300) {
+        let case_seed = seed.wrapping_add(case).max(1);
+        let mut value = generate(&mut Rng(case_seed));
+        let mut failure = match property(&value) {
+            Ok(()) => continue,
+            Err(failure) => failure,
+        };
+        'shrinking: loop {
+            for smaller in shrink(&value) {
+                if let Err(smaller_failure) = property(&smaller) {
+                    value = smaller;
+                    failure = smaller_failure;
+                    continue 'shrinking;
+                }
+            }
+            break;
+        }
+        panic!("failed for {:?}, from ROUNDTRIP_SEED={}:\n{}", value, case_seed, failure);
+    }
+}
+
+/// Every keyword in C11, which is what is lexed by default.
+const KEYWORDS: &[&str] = &[
+    "return", "int", "if", "else", "for", "while", "do", "break", "continue", "goto", "typedef", "char", "struct",
+    "static", "extern", "const", "sizeof", "long", "signed", "unsigned", "switch", "case", "default", "inline",
+    "restrict", "_Bool",
+];
+
+const SYMBOLS: &[LexemeKind<'static>] = &[
+    LexemeKind::OpenBrace, LexemeKind::CloseBrace, LexemeKind::OpenParen, LexemeKind::CloseParen,
+    LexemeKind::OpenBracket, LexemeKind::CloseBracket, LexemeKind::Semicolon, LexemeKind::Comma, LexemeKind::Dot,
+    LexemeKind::Ellipsis, LexemeKind::Arrow, LexemeKind::QuestionMark, LexemeKind::Colon, LexemeKind::Plus,
+    LexemeKind::Minus, LexemeKind::Star, LexemeKind::Slash, LexemeKind::Percent, LexemeKind::Ampersand,
+    LexemeKind::Pipe, LexemeKind::Caret, LexemeKind::Tilde, LexemeKind::Bang, LexemeKind::Assign,
+    LexemeKind::PlusAssign, LexemeKind::MinusAssign, LexemeKind::StarAssign, LexemeKind::SlashAssign,
+    LexemeKind::PercentAssign, LexemeKind::AmpersandAssign, LexemeKind::PipeAssign, LexemeKind::CaretAssign,
+    LexemeKind::ShiftLeftAssign, LexemeKind::ShiftRightAssign, LexemeKind::Increment, LexemeKind::Decrement,
+    LexemeKind::ShiftLeft, LexemeKind::ShiftRight, LexemeKind::LogicalAnd, LexemeKind::LogicalOr, LexemeKind::Equal,
+    LexemeKind::NotEqual, LexemeKind::LessThan, LexemeKind::LessEqual, LexemeKind::GreaterThan,
+    LexemeKind::GreaterEqual,
+];
+
+/// A lexeme to print, owning what a `LexemeKind` borrows.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Keyword(&'static str),
+    Identifier(String),
+    Int(i64, Type),
+    Char(u8),
+    Str(Vec<u8>),
+    Symbol(LexemeKind<'static>),
+}
+
+impl Token {
+    fn kind(&self) -> LexemeKind<'_> {
+        match *self {
+            Token::Keyword(keyword) => LexemeKind::Keyword(keyword),
+            Token::Identifier(ref name) => LexemeKind::Identifier(name),
+            Token::Int(value, ref ty) => LexemeKind::IntLiteral(value, ty.clone()),
+            Token::Char(value) => LexemeKind::CharLiteral(value),
+            Token::Str(ref bytes) => LexemeKind::StringLiteral(bytes.clone()),
+            Token::Symbol(ref kind) => kind.clone(),
+        }
+    }
+
+    /// The token for `name`, which is a keyword if it is spelled like one.
+    fn named(name: String) -> Token {
+        match KEYWORDS.iter().find(|&&keyword| keyword == name) {
+            Some(keyword) => Token::Keyword(keyword),
+            None => Token::Identifier(name),
+        }
+    }
+}
+
+/// A name, often one a letter away from a keyword.
+fn name(rng: &mut Rng) -> String {
+    const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_";
+    const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyz_0123456789";
+    let mut name = match rng.below(3) {
+        0 => rng.pick(KEYWORDS).to_string(),
+        _ => (*rng.pick(FIRST) as char).to_string(),
+    };
+    match rng.below(3) {
+        0 => {
+            name.pop();
+        },
+        _ => {
+            for _ in 0..rng.below(4) {
+                name.push(*rng.pick(REST) as char);
+            }
+        },
+    }
+    if name.is_empty() { "x".to_owned() } else { name }
+}
+
+/// Bytes for a literal, mostly the ones escapes are made of.
+fn bytes(rng: &mut Rng) -> Vec<u8> {
+    const TRICKY: &[u8] = b"\\\"'?\n\t\r\0 0178xXaAfF";
+    (0..rng.below(6)).map(|_| if rng.below(2) == 0 { *rng.pick(TRICKY) } else { rng.next() as u8 }).collect()
+}
+
+/// A literal's value and a type that can hold it, mostly `int`.
+fn int(rng: &mut Rng) -> (i64, Type) {
+    let ty = match rng.below(8) {
+        0 => Type::Long,
+        1 => Type::UnsignedInt,
+        2 => Type::UnsignedLong,
+        _ => Type::Int,
+    };
+    let bits = match ty {
+        Type::Int => 31,
+        Type::UnsignedInt => 32,
+        Type::Long => 63,
+        _ => 64,
+    };
+    let value = match rng.below(4) {
+        0 => *rng.pick(&[0, 1, 7, 8, 9, 10, 16, 255, u64::MAX >> (64 - bits)]),
+        1 => rng.next() >> (64 - bits),
+        _ => rng.below(100) as u64,
+    };
+    (value as i64, ty)
+}
+
+/// Smaller literals of the same type.
+fn shrink_int(value: i64, ty: &Type) -> Vec<(i64, Type)> {
+    vec![(0, Type::Int), (((value as u64) / 2) as i64, ty.clone())]
+}
+
+fn token(rng: &mut Rng) -> Token {
+    match rng.below(10) {
+        0..=1 => Token::named(name(rng)),
+        2 => {
+            let (value, ty) = int(rng);
+            Token::Int(value, ty)
+        },
+        3 => Token::Char(rng.next() as u8),
+        4 => Token::Str(bytes(rng)),
+        _ => Token::Symbol(rng.pick(SYMBOLS).clone()),
+    }
+}
+
+fn tokens(rng: &mut Rng) -> Vec<Token> {
+    (0..rng.below(24) + 1).map(|_| token(rng)).collect()
+}
+
+/// Smaller lists of tokens: with runs of them taken out, and with each made
+/// simpler.
+fn shrink_tokens(tokens: &[Token]) -> Vec<Vec<Token>> {
+    let mut smaller = Vec::new();
+    let mut length = tokens.len() / 2;
+    while length > 0 {
+        for start in (0..tokens.len()).step_by(length) {
+            let mut without = tokens.to_vec();
+            without.drain(start..(start + length).min(tokens.len()));
+            smaller.push(without);
+        }
+        length /= 2;
+    }
+    for (index, token) in tokens.iter().enumerate() {
+        let simpler = match *token {
+            Token::Int(value, ref ty) if value != 0 => shrink_int(value, ty).into_iter().map(|(value, ty)| Token::Int(value, ty)).collect(),
+            Token::Str(ref bytes) if !bytes.is_empty() => vec![Token::Str(bytes[1..].to_vec()), Token::Str(bytes[..bytes.len() - 1].to_vec())],
+            Token::Char(value) if value != b'a' => vec![Token::Char(b'a')],
+            Token::Identifier(ref name) if name.len() > 1 => vec![Token::named(name[1..].to_owned()), Token::named(name[..name.len() - 1].to_owned())],
+            _ => vec![],
+        };
+        for simpler in simpler {
+            let mut replaced = tokens.to_vec();
+            replaced[index] = simpler;
+            smaller.push(replaced);
+        }
+    }
+    smaller
+}
+
+/// Whether `source` lexes to `tokens`.
+fn lexes_to(source: &str, tokens: &[Token]) -> Result<(), String> {
+    let lexed = lex_str(source).map_err(|err| format!("{} in {:?}", err, source))?;
+    let lexed: Vec<_> = lexed.into_iter().map(|lexeme| lexeme.kind).collect();
+    let expected: Vec<_> = tokens.iter().map(Token::kind).collect();
+    if lexed == expected {
+        Ok(())
+    } else {
+        Err(format!("{:?} lexes to\n{:?}\nrather than\n{:?}", source, lexed, expected))
+    }
+}
+
+/// `tokens` spelled out, with whitespace of different kinds between them.
+fn spelled(tokens: &[Token]) -> String {
+    const SEPARATORS: &[&str] = &[" ", "\n", "\t", "  ", " \n  "];
+    tokens.iter().enumerate().map(|(index, token)| format!("{}{}", token.kind(), SEPARATORS[index % SEPARATORS.len()])).collect()
+}
+
+#[test]
+fn spelled_lexemes_lex_back_the_same() {
+    check(tokens, |tokens| shrink_tokens(tokens), |tokens| lexes_to(&spelled(tokens), tokens));
+}
+
+#[test]
+fn formatted_lexemes_lex_back_the_same() {
+    check(tokens, |tokens| shrink_tokens(tokens), |tokens| {
+        let formatted = format_source(&spelled(tokens), &FormatOptions::default()).map_err(|err| err.to_string())?;
+        lexes_to(&formatted, tokens)?;
+        let again = format_source(&formatted, &FormatOptions::default()).map_err(|err| err.to_string())?;
+        if again == formatted { Ok(()) } else { Err(format!("formatting {:?} again gives {:?}", formatted, again)) }
+    });
+}
+
+/// An expression to print, as a tree of its own, which is simpler to shrink
+/// than one in an `Ast`.
+#[derive(Debug, Clone)]
+enum Expr {
+    Int(i64, Type),
+    Char(u8),
+    Str(Vec<u8>),
+    Variable(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Assign(Option<BinaryOp>, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Cast(Type, Box<Expr>),
+    SizeOf(Type),
+    Subscript(Box<Expr>, Box<Expr>),
+    Member(Box<Expr>, String),
+    PointerMember(Box<Expr>, String),
+    Comma(Vec<Expr>),
+}
+
+const UNARY_OPS: &[UnaryOp] = &[
+    UnaryOp::Negate, UnaryOp::BitwiseNot, UnaryOp::LogicalNot, UnaryOp::PreIncrement, UnaryOp::PreDecrement,
+    UnaryOp::PostIncrement, UnaryOp::PostDecrement, UnaryOp::Dereference, UnaryOp::AddressOf,
+];
+
+const BINARY_OPS: &[BinaryOp] = &[
+    BinaryOp::Add, BinaryOp::Subtract, BinaryOp::Multiply, BinaryOp::Divide, BinaryOp::Modulo, BinaryOp::ShiftLeft,
+    BinaryOp::ShiftRight, BinaryOp::LessThan, BinaryOp::LessEqual, BinaryOp::GreaterThan, BinaryOp::GreaterEqual,
+    BinaryOp::Equal, BinaryOp::NotEqual, BinaryOp::BitwiseAnd, BinaryOp::BitwiseXor, BinaryOp::BitwiseOr,
+    BinaryOp::LogicalAnd, BinaryOp::LogicalOr,
+];
+
+/// The operators there is an assignment for, as `+=` is for `+`.
+const COMPOUND_OPS: &[BinaryOp] = &[
+    BinaryOp::Add, BinaryOp::Subtract, BinaryOp::Multiply, BinaryOp::Divide, BinaryOp::Modulo, BinaryOp::ShiftLeft,
+    BinaryOp::ShiftRight, BinaryOp::BitwiseAnd, BinaryOp::BitwiseXor, BinaryOp::BitwiseOr,
+];
+
+/// A name that isn't a keyword.
+fn identifier(rng: &mut Rng) -> String {
+    loop {
+        if let Token::Identifier(name) = Token::named(name(rng)) {
+            return name;
+        }
+    }
+}
+
+/// A type a cast or `sizeof` can name.
+fn type_name(rng: &mut Rng) -> Type {
+    let base = rng.pick(&[Type::Int, Type::Char, Type::Long, Type::UnsignedInt, Type::UnsignedLong, Type::Struct("s".to_owned())]).clone();
+    let base = if rng.below(4) == 0 { base.with_const() } else { base };
+    match rng.below(3) {
+        0 => base,
+        1 => Type::Pointer(Box::new(base)),
+        _ => Type::Pointer(Box::new(Type::Pointer(Box::new(base)))).with_const(),
+    }
+}
+
+fn expr(rng: &mut Rng, depth: usize) -> Expr {
+    let boxed = |rng: &mut Rng| Box::new(expr(rng, depth - 1));
+    match if depth == 0 { rng.below(4) } else { rng.below(15) } {
+        0 => {
+            let (value, ty) = int(rng);
+            Expr::Int(value, ty)
+        },
+        1 => Expr::Char(rng.next() as u8),
+        2 => Expr::Str(bytes(rng)),
+        3 => Expr::Variable(identifier(rng)),
+        4 | 5 => Expr::Unary(*rng.pick(UNARY_OPS), boxed(rng)),
+        6 | 7 => Expr::Binary(*rng.pick(BINARY_OPS), boxed(rng), boxed(rng)),
+        8 => {
+            let op = if rng.below(2) == 0 { None } else { Some(*rng.pick(COMPOUND_OPS)) };
+            Expr::Assign(op, boxed(rng), boxed(rng))
+        },
+        9 => Expr::Conditional(boxed(rng), boxed(rng), boxed(rng)),
+        10 => Expr::Call(identifier(rng), (0..rng.below(3)).map(|_| expr(rng, depth - 1)).collect()),
+        11 => if rng.below(2) == 0 { Expr::Cast(type_name(rng), boxed(rng)) } else { Expr::SizeOf(type_name(rng)) },
+        12 => Expr::Subscript(boxed(rng), boxed(rng)),
+        13 => if rng.below(2) == 0 { Expr::Member(boxed(rng), identifier(rng)) } else { Expr::PointerMember(boxed(rng), identifier(rng)) },
+        _ => Expr::Comma((0..rng.below(2) + 2).map(|_| expr(rng, depth - 1)).collect()),
+    }
+}
+
+/// Smaller expressions: each of its operands, and a literal.
+fn shrink_expr(expr: &Expr) -> Vec<Expr> {
+    let mut smaller = match *expr {
+        Expr::Int(0, _) => return vec![],
+        Expr::Int(value, ref ty) => return shrink_int(value, ty).into_iter().map(|(value, ty)| Expr::Int(value, ty)).collect(),
+        Expr::Char(_) | Expr::Str(_) | Expr::Variable(_) | Expr::SizeOf(_) => vec![],
+        Expr::Unary(_, ref operand) | Expr::Cast(_, ref operand) | Expr::Member(ref operand, _) | Expr::PointerMember(ref operand, _) => vec![(**operand).clone()],
+        Expr::Binary(_, ref lhs, ref rhs) | Expr::Assign(_, ref lhs, ref rhs) | Expr::Subscript(ref lhs, ref rhs) => vec![(**lhs).clone(), (**rhs).clone()],
+        Expr::Conditional(ref a, ref b, ref c) => vec![(**a).clone(), (**b).clone(), (**c).clone()],
+        Expr::Call(_, ref operands) | Expr::Comma(ref operands) => operands.clone(),
+    };
+    // Shrinking inside an operand, keeping the rest.
+    let rebuilt: Vec<Expr> = match *expr {
+        Expr::Unary(op, ref operand) => shrink_expr(operand).into_iter().map(|operand| Expr::Unary(op, Box::new(operand))).collect(),
+        Expr::Binary(op, ref lhs, ref rhs) => shrink_expr(lhs).into_iter().map(|lhs| Expr::Binary(op, Box::new(lhs), rhs.clone()))
+            .chain(shrink_expr(rhs).into_iter().map(|rhs| Expr::Binary(op, lhs.clone(), Box::new(rhs))))
+            .collect(),
+        _ => vec![],
+    };
+    smaller.extend(rebuilt);
+    smaller.push(Expr::Int(0, Type::Int));
+    smaller
+}
+
+/// Allocates `expr` in `ast` the way the parser does, operands first.
+fn alloc(ast: &mut Ast, expr: &Expr) -> ExprId {
+    let kind = match *expr {
+        Expr::Int(value, ref ty) => ExpressionKind::IntLiteral(value, ty.clone()),
+        Expr::Char(value) => ExpressionKind::CharConstant(value),
+        Expr::Str(ref bytes) => ExpressionKind::StringLiteral(bytes.clone()),
+        Expr::Variable(ref name) => ExpressionKind::Variable(name.clone()),
+        Expr::Unary(op, ref operand) => ExpressionKind::Unary(op, alloc(ast, operand)),
+        Expr::Binary(op, ref lhs, ref rhs) => {
+            let lhs = alloc(ast, lhs);
+            ExpressionKind::Binary(op, lhs, alloc(ast, rhs))
+        },
+        Expr::Assign(op, ref target, ref value) => {
+            let target = alloc(ast, target);
+            ExpressionKind::Assign(op, target, alloc(ast, value))
+        },
+        Expr::Conditional(ref condition, ref then, ref otherwise) => {
+            let condition = alloc(ast, condition);
+            let then = alloc(ast, then);
+            ExpressionKind::Conditional(condition, then, alloc(ast, otherwise))
+        },
+        Expr::Call(ref name, ref arguments) => ExpressionKind::Call(name.clone(), arguments.iter().map(|argument| alloc(ast, argument)).collect()),
+        Expr::Cast(ref to, ref operand) => ExpressionKind::Cast { to: to.clone(), expr: alloc(ast, operand) },
+        Expr::SizeOf(ref ty) => ExpressionKind::SizeOf(ty.clone()),
+        Expr::Subscript(ref array, ref index) => {
+            let array = alloc(ast, array);
+            ExpressionKind::Subscript(array, alloc(ast, index))
+        },
+        Expr::Member(ref object, ref member) => ExpressionKind::Member(alloc(ast, object), member.clone()),
+        Expr::PointerMember(ref pointer, ref member) => ExpressionKind::PointerMember(alloc(ast, pointer), member.clone()),
+        Expr::Comma(ref operands) => ExpressionKind::Comma(operands.iter().map(|operand| alloc(ast, operand)).collect()),
+    };
+    ast.alloc_expr(kind.into())
+}
+
+/// `int main() { return expr; }`.
+fn program(expr: &Expr) -> Program {
+    let mut ast = Ast::new();
+    let value = alloc(&mut ast, expr);
+    let body = ast.alloc_stmt(StatementKind::Return(value).into());
+    let main = Function {
+        storage: None,
+        return_type: Type::Int,
+        name: "main".to_owned(),
+        parameters: Vec::new(),
+        variadic: false,
+        body: Some(vec![BlockItem::Statement(body)]),
+        span: Span::default(),
+        closing_brace: Some(Span::default()),
+    };
+    Program { items: vec![TopLevel::Function(main)], ast, pragmas: Vec::new(), typedefs: Vec::new() }
+}
+
+/// Whether `expr`, printed, parses back to itself.
+fn parses_back(expr: &Expr) -> Result<(), String> {
+    let program = program(expr);
+    let printed = to_c_source(&program);
+    let lexemes = lex_str(&printed).map_err(|err| format!("{} in:\n{}", err, printed))?;
+    let parsed = parse_program(&lexemes).map_err(|errors| format!("{:?} in:\n{}", errors, printed))?;
+    if parsed == program {
+        Ok(())
+    } else {
+        Err(format!("{}parses to\n{}", printed, pretty_print(&parsed)))
+    }
+}
+
+#[test]
+fn printed_expressions_parse_back_the_same() {
+    check(|rng| expr(rng, 4), shrink_expr, parses_back);
+}
+
+// What the properties above have found.
+
+/// `format_source` wrote lexemes that nothing is spaced between with no space
+/// at all, even where they then ran together.
+#[test]
+fn formatted_lexemes_dont_run_together() {
+    let format = |source: &str| format_source(source, &FormatOptions::default()).unwrap();
+    assert_eq!(format("& &"), "& &\n");
+    assert_eq!(format("x = - -y - --z;"), "x = - -y - --z;\n");
+    assert_eq!(format("p = & &q;"), "p = & &q;\n");
+    assert_eq!(format("a . . . b"), "a.. .b\n");
+    assert_eq!(format("(int) -1 / / 2"), "(int)-1 / / 2\n");
+    let tokens = vec![Token::Symbol(LexemeKind::Star), Token::Symbol(LexemeKind::Slash)];
+    assert_eq!(lexes_to(&format("* /"), &tokens), Ok(()));
+}
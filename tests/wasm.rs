@@ -0,0 +1,29 @@
+//! Builds the library for `wasm32-unknown-unknown` without its default
+//! features, as the playground in `wasm` builds it, if that target is
+//! installed. Without it, there is nothing to check.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn the_library_builds_for_webassembly() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let sysroot = match Command::new(rustc).args(["--print", "sysroot"]).output() {
+        Ok(output) => String::from_utf8(output.stdout).unwrap(),
+        Err(_) => return,
+    };
+    if !Path::new(sysroot.trim()).join("lib/rustlib/wasm32-unknown-unknown").exists() {
+        return;
+    }
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()))
+        .args(["build", "--lib", "--target", "wasm32-unknown-unknown", "--no-default-features", "--features", "serde", "--manifest-path"])
+        .arg(manifest.join("Cargo.toml"))
+        // A target directory of its own, so as not to wait on the one the
+        // tests were built in.
+        .env("CARGO_TARGET_DIR", manifest.join("target").join("wasm"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,11 @@
+//! The compiler, for a page that shows the tokens, tree and assembly of the C
+//! pasted into it. Build it with `wasm-pack build --target web`.
+
+use wasm_bindgen::prelude::*;
+
+/// Compiles `source` as `options_json` asks, and answers with what it made
+/// and the diagnostics, as JSON. `rust_cc::playground` describes both.
+#[wasm_bindgen]
+pub fn compile(source: &str, options_json: &str) -> String {
+    rust_cc::playground::compile(source, options_json)
+}